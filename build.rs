@@ -0,0 +1,23 @@
+//! Generates the C header of src/ffi.rs with cbindgen into OUT_DIR (feature 'ffi'), a test of
+//! src/ffi.rs checks that include/net_utils.h matches it.
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "ffi")]
+    generate_header();
+}
+
+#[cfg(feature = "ffi")]
+fn generate_header() {
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    let dir = std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR");
+    let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR");
+    let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", dir)).expect("cbindgen.toml");
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(format!("{}/src/ffi.rs", dir))
+        .generate()
+        .expect("C header of src/ffi.rs")
+        .write_to_file(format!("{}/net_utils.h", out_dir));
+}
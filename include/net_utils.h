@@ -0,0 +1,79 @@
+/* C interface of net-utils (feature "ffi"), generated from src/ffi.rs by cbindgen.
+ * Functions return 0 (or a file descriptor) on success and a negative errno value on failure. */
+
+#ifndef NET_UTILS_H
+#define NET_UTILS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * Length of the name buffer of `NetUtilsInterface` including the terminating NUL.
+ */
+#define NET_UTILS_NAME_LEN 16
+
+/**
+ * IP interface configuration (struct net_utils_interface).
+ */
+struct net_utils_interface {
+  /**
+   * interface index
+   */
+  uint32_t index;
+  /**
+   * NUL terminated interface name, truncated to NET_UTILS_NAME_LEN - 1 bytes
+   */
+  char name[NET_UTILS_NAME_LEN];
+  /**
+   * IFF_* interface flags
+   */
+  uint32_t flags;
+  /**
+   * AF_INET or AF_INET6
+   */
+  int family;
+  /**
+   * address in network byte order, IPv4 addresses use the first 4 bytes
+   */
+  uint8_t address[16];
+  /**
+   * prefix length of the network
+   */
+  uint8_t prefix_len;
+};
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Retrieves the IP interface configurations of the system into an array allocated by the
+ * library, which has to be released with `net_utils_free_interfaces`.
+ * # Safety
+ * `interfaces` and `count` must be valid pointers.
+ */
+int net_utils_list_interfaces(struct net_utils_interface **interfaces, size_t *count);
+
+/**
+ * Releases an array returned by `net_utils_list_interfaces`.
+ * # Safety
+ * `interfaces` and `count` must be the values returned by `net_utils_list_interfaces`, each
+ * array must be released once.
+ */
+void net_utils_free_interfaces(struct net_utils_interface *interfaces, size_t count);
+
+/**
+ * Creates a UDP socket bound to the port of the multicast group (textual IPv4 or IPv6
+ * address), which joined the group on the interface with the given local address (NULL for
+ * the default interface), see `MulticastSocketBuilder`. Returns the file descriptor, which
+ * the caller closes.
+ * # Safety
+ * `group` and `interface` (unless NULL) must be NUL terminated strings.
+ */
+int net_utils_create_mc_socket(const char *group, uint16_t port, const char *interface);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* NET_UTILS_H */
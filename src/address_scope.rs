@@ -0,0 +1,185 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Reachability scope of an IPv4 or IPv6 address.
+/// The variants are ordered from the narrowest to the widest scope, so scopes can be compared
+/// (e.g. `scope >= AddressScope::Private`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AddressScope {
+    /// The unspecified address (0.0.0.0 or ::).
+    Unspecified,
+    /// Loopback addresses and interface-local multicast, valid on the host only.
+    Host,
+    /// Link-local unicast (169.254.0.0/16, fe80::/10) and link-local multicast
+    /// (224.0.0.0/24, ff02::/16), valid on a single link only.
+    LinkLocal,
+    /// Private addresses: RFC 1918 and RFC 6598 (shared address space) for IPv4, unique local
+    /// (fc00::/7) and the deprecated site-local (fec0::/10) addresses for IPv6, administratively
+    /// scoped multicast (239.0.0.0/8, ff03::/16 - ff08::/16).
+    Private,
+    /// Globally routable addresses.
+    Global,
+}
+
+impl AddressScope {
+
+    /// Classifies the given IPv4 or IPv6 address.
+    pub fn of(address: &IpAddr) -> AddressScope {
+        match address {
+            IpAddr::V4(a) => AddressScope::of_ipv4(a),
+            IpAddr::V6(a) => AddressScope::of_ipv6(a),
+        }
+    }
+
+    /// Classifies the given IPv4 address.
+    pub fn of_ipv4(address: &Ipv4Addr) -> AddressScope {
+        let o = address.octets();
+        if address.is_unspecified() {
+            AddressScope::Unspecified
+        } else if address.is_loopback() {
+            AddressScope::Host
+        } else if address.is_link_local() || (o[0] == 224 && o[1] == 0 && o[2] == 0) {
+            AddressScope::LinkLocal
+        } else if address.is_private() || (o[0] == 100 && (o[1] & 0xc0) == 64) || o[0] == 239 {
+            AddressScope::Private
+        } else {
+            AddressScope::Global
+        }
+    }
+
+    /// Classifies the given IPv6 address. IPv4-mapped addresses (::ffff:a.b.c.d) are classified
+    /// as their IPv4 address.
+    pub fn of_ipv6(address: &Ipv6Addr) -> AddressScope {
+        let s = address.segments();
+        if address.is_unspecified() {
+            AddressScope::Unspecified
+        } else if address.is_loopback() {
+            AddressScope::Host
+        } else if address.is_multicast() {
+            match s[0] & 0x000f {
+                0x0 | 0x1 => AddressScope::Host,
+                0x2 => AddressScope::LinkLocal,
+                0x3..=0x8 => AddressScope::Private,
+                _ => AddressScope::Global,
+            }
+        } else if (s[0] & 0xffc0) == 0xfe80 {
+            AddressScope::LinkLocal
+        } else if (s[0] & 0xfe00) == 0xfc00 || (s[0] & 0xffc0) == 0xfec0 {
+            AddressScope::Private
+        } else if let Some(v4) = ipv4_mapped(address) {
+            AddressScope::of_ipv4(&v4)
+        } else {
+            AddressScope::Global
+        }
+    }
+
+    /// Returns whether the scope is limited to a single link.
+    pub fn is_link_local(&self) -> bool {
+        *self == AddressScope::LinkLocal
+    }
+
+    /// Returns whether the scope is a private network (RFC 1918, unique local).
+    pub fn is_unique_local(&self) -> bool {
+        *self == AddressScope::Private
+    }
+
+    /// Returns whether the address is globally routable.
+    pub fn is_global(&self) -> bool {
+        *self == AddressScope::Global
+    }
+}
+
+impl From<IpAddr> for AddressScope {
+    fn from(address: IpAddr) -> AddressScope {
+        AddressScope::of(&address)
+    }
+}
+
+/// Returns the IPv4 address of an IPv4-mapped IPv6 address (::ffff:a.b.c.d).
+fn ipv4_mapped(address: &Ipv6Addr) -> Option<Ipv4Addr> {
+    match address.segments() {
+        [0, 0, 0, 0, 0, 0xffff, hi, lo] => Some(Ipv4Addr::new((hi >> 8) as u8, hi as u8, (lo >> 8) as u8, lo as u8)),
+        _ => None,
+    }
+}
+
+#[cfg(unix)]
+impl super::IpInterface {
+
+    /// Returns the scope of the interface's address.
+    pub fn scope(&self) -> AddressScope {
+        AddressScope::of(&self.address.ip())
+    }
+
+    /// Returns whether the interface's address is a link-local address.
+    pub fn is_link_local(&self) -> bool {
+        self.scope().is_link_local()
+    }
+
+    /// Returns whether the interface's address is a private (RFC 1918) or unique local address.
+    pub fn is_unique_local(&self) -> bool {
+        self.scope().is_unique_local()
+    }
+
+    /// Returns whether the interface's address is globally routable.
+    pub fn is_global(&self) -> bool {
+        self.scope().is_global()
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl super::IpAddressInfo {
+
+    /// Returns the scope of the address.
+    pub fn address_scope(&self) -> AddressScope {
+        AddressScope::of(&self.address)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn scope(address: &str) -> AddressScope {
+        AddressScope::of(&address.parse().unwrap())
+    }
+
+    #[test]
+    fn test_ipv4_scopes() {
+        assert_eq!(scope("0.0.0.0"), AddressScope::Unspecified);
+        assert_eq!(scope("127.0.0.1"), AddressScope::Host);
+        assert_eq!(scope("169.254.10.1"), AddressScope::LinkLocal);
+        assert_eq!(scope("224.0.0.251"), AddressScope::LinkLocal);
+        assert_eq!(scope("10.1.2.3"), AddressScope::Private);
+        assert_eq!(scope("172.20.0.1"), AddressScope::Private);
+        assert_eq!(scope("192.168.178.1"), AddressScope::Private);
+        assert_eq!(scope("100.64.0.1"), AddressScope::Private);
+        assert_eq!(scope("239.255.255.250"), AddressScope::Private);
+        assert_eq!(scope("172.32.0.1"), AddressScope::Global);
+        assert_eq!(scope("8.8.8.8"), AddressScope::Global);
+    }
+
+    #[test]
+    fn test_ipv6_scopes() {
+        assert_eq!(scope("::"), AddressScope::Unspecified);
+        assert_eq!(scope("::1"), AddressScope::Host);
+        assert_eq!(scope("fe80::1"), AddressScope::LinkLocal);
+        assert_eq!(scope("ff02::fb"), AddressScope::LinkLocal);
+        assert_eq!(scope("ff01::1"), AddressScope::Host);
+        assert_eq!(scope("fd12:3456::1"), AddressScope::Private);
+        assert_eq!(scope("fec0::1"), AddressScope::Private);
+        assert_eq!(scope("ff05::2"), AddressScope::Private);
+        assert_eq!(scope("ff0e::181"), AddressScope::Global);
+        assert_eq!(scope("2001:db8::1"), AddressScope::Global);
+        assert_eq!(scope("::ffff:192.168.1.1"), AddressScope::Private);
+    }
+
+    #[test]
+    fn test_scope_predicates() {
+        assert!(scope("fe80::1").is_link_local());
+        assert!(scope("fd00::1").is_unique_local());
+        assert!(scope("2a00::1").is_global());
+        assert!(!scope("10.0.0.1").is_global());
+        assert!(AddressScope::Host < AddressScope::Global);
+    }
+}
@@ -0,0 +1,364 @@
+//! ARP (RFC 826) on packet sockets: gratuitous ARP announcements and address conflict probes
+//! (RFC 5227) for claiming and verifying IPv4 addresses, and host discovery in a subnet.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::TryInto,
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, Ipv4Addr},
+    os::unix::io::AsRawFd,
+    time::{Duration, Instant},
+};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+use super::{IpInterface, IpNet, ioctl::hardware_address, scan::{rate_interval, receive_deadline},
+            sockaddr::sock_addr_from_raw};
+
+/// Ethertype of ARP packets.
+pub const ETHERTYPE_ARP: u16 = 0x0806;
+
+/// Ethernet broadcast address.
+pub const BROADCAST_MAC: [u8; 6] = [0xff; 6];
+
+/// Interval between the probes sent by `probe` (PROBE_MIN of RFC 5227).
+pub const PROBE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Time `probe` waits for a conflict as recommended by RFC 5227 (3 probes).
+pub const DEFAULT_PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Smallest prefix length accepted by `scan` (65534 hosts).
+pub const MIN_SCAN_PREFIX_LEN: u8 = 16;
+
+const ARP_REQUEST: u16 = 1;
+const ARP_REPLY: u16 = 2;
+const ARP_PACKET_LEN: usize = 28;
+const HARDWARE_ETHERNET: u16 = 1;
+const PROTOCOL_IPV4: u16 = 0x0800;
+
+/// ARP packet for ethernet and IPv4.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct ArpPacket {
+    operation: u16,
+    sender_mac: [u8; 6],
+    sender_ip: Ipv4Addr,
+    target_mac: [u8; 6],
+    target_ip: Ipv4Addr,
+}
+
+impl ArpPacket {
+
+    fn parse(packet: &[u8]) -> Option<ArpPacket> {
+        let u16_at = |offset: usize| u16::from_be_bytes([packet[offset], packet[offset + 1]]);
+        let ip_at = |offset: usize| Ipv4Addr::new(packet[offset], packet[offset + 1], packet[offset + 2], packet[offset + 3]);
+        if packet.len() < ARP_PACKET_LEN || u16_at(0) != HARDWARE_ETHERNET || u16_at(2) != PROTOCOL_IPV4
+            || packet[4] != 6 || packet[5] != 4 {
+            return None;
+        }
+        Some(ArpPacket {
+            operation: u16_at(6),
+            sender_mac: packet[8..14].try_into().ok()?,
+            sender_ip: ip_at(14),
+            target_mac: packet[18..24].try_into().ok()?,
+            target_ip: ip_at(24),
+        })
+    }
+
+    fn encode(&self) -> [u8; ARP_PACKET_LEN] {
+        let mut packet = [0_u8; ARP_PACKET_LEN];
+        packet[0..2].copy_from_slice(&HARDWARE_ETHERNET.to_be_bytes());
+        packet[2..4].copy_from_slice(&PROTOCOL_IPV4.to_be_bytes());
+        packet[4] = 6;
+        packet[5] = 4;
+        packet[6..8].copy_from_slice(&self.operation.to_be_bytes());
+        packet[8..14].copy_from_slice(&self.sender_mac);
+        packet[14..18].copy_from_slice(&self.sender_ip.octets());
+        packet[18..24].copy_from_slice(&self.target_mac);
+        packet[24..28].copy_from_slice(&self.target_ip.octets());
+        packet
+    }
+}
+
+/// Packet socket for ARP packets on one interface, the kernel builds the ethernet header.
+struct ArpSocket {
+    socket: Socket,
+    interface_index: u32,
+    mac: [u8; 6],
+}
+
+impl ArpSocket {
+
+    fn open(interface_name: &str) -> Result<ArpSocket> {
+        let (_, mac) = hardware_address(interface_name)?;
+        let name = std::ffi::CString::new(interface_name)
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, "invalid interface name"))?;
+        let interface_index = match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+            0 => return Err(Error::last_os_error()),
+            index => index,
+        };
+        let protocol = ETHERTYPE_ARP.to_be() as libc::c_int;
+        let socket = super::sys_socket::socket(Domain::PACKET, Type::DGRAM, Some(Protocol::from(protocol)))?;
+        let address = link_address(interface_index, &[0; 6]);
+        let address = sock_addr_from_raw(&address, std::mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t);
+        super::sys_socket::bind(&socket, &address)?;
+        Ok(ArpSocket { socket, interface_index, mac })
+    }
+
+    fn send(&self, packet: &ArpPacket, destination: &[u8; 6]) -> Result<()> {
+        let packet = packet.encode();
+        let address = link_address(self.interface_index, destination);
+        if unsafe {
+            libc::sendto(self.socket.as_raw_fd(), packet.as_ptr() as *const libc::c_void, packet.len(), 0,
+                         &address as *const libc::sockaddr_ll as *const libc::sockaddr,
+                         std::mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t)
+        } < 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Receives the next ARP packet from another host until the deadline, None on timeout.
+    fn recv(&self, deadline: Instant) -> Result<Option<ArpPacket>> {
+        let mut buffer = [0_u8; 128];
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(None);
+            }
+            self.socket.set_read_timeout(Some(remaining))?;
+            let mut address: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+            let mut address_len = std::mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t;
+            let len = unsafe {
+                libc::recvfrom(self.socket.as_raw_fd(), buffer.as_mut_ptr() as *mut libc::c_void, buffer.len(), 0,
+                               &mut address as *mut libc::sockaddr_ll as *mut libc::sockaddr, &mut address_len)
+            };
+            if len < 0 {
+                let e = Error::last_os_error();
+                match e.kind() {
+                    ErrorKind::WouldBlock | ErrorKind::Interrupted => continue,
+                    _ => return Err(e),
+                }
+            }
+            if address.sll_pkttype == libc::PACKET_OUTGOING {
+                continue;
+            }
+            if let Some(packet) = ArpPacket::parse(&buffer[..len as usize]) {
+                return Ok(Some(packet));
+            }
+        }
+    }
+}
+
+/// Returns the link layer address of the interface and destination MAC for ARP.
+fn link_address(interface_index: u32, mac: &[u8; 6]) -> libc::sockaddr_ll {
+    let mut address: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+    address.sll_family = libc::AF_PACKET as u16;
+    address.sll_protocol = ETHERTYPE_ARP.to_be();
+    address.sll_ifindex = interface_index as i32;
+    address.sll_halen = 6;
+    address.sll_addr[..6].copy_from_slice(mac);
+    address
+}
+
+/// Broadcasts a gratuitous ARP request (ARP announcement) for the address from the interface
+/// with the given name, so that neighbors update their ARP caches to the interface's MAC, e.g.
+/// after a failover claimed the address. RFC 5227 recommends to announce twice, two seconds apart.
+/// Requires CAP_NET_RAW.
+pub fn announce(interface_name: &str, address: &Ipv4Addr) -> Result<()> {
+    let socket = ArpSocket::open(interface_name)?;
+    let announcement = ArpPacket {
+        operation: ARP_REQUEST,
+        sender_mac: socket.mac,
+        sender_ip: *address,
+        target_mac: [0; 6],
+        target_ip: *address,
+    };
+    socket.send(&announcement, &BROADCAST_MAC)
+}
+
+/// Checks with ARP probes (sender address 0.0.0.0) whether another host on the link of the
+/// interface uses the address. A probe is sent every PROBE_INTERVAL until the timeout elapses.
+/// Returns the MAC address of the conflicting host or None if the address is free to use.
+/// Requires CAP_NET_RAW.
+pub fn probe(interface_name: &str, address: &Ipv4Addr, timeout: Duration) -> Result<Option<[u8; 6]>> {
+    let socket = ArpSocket::open(interface_name)?;
+    let probe = ArpPacket {
+        operation: ARP_REQUEST,
+        sender_mac: socket.mac,
+        sender_ip: Ipv4Addr::UNSPECIFIED,
+        target_mac: [0; 6],
+        target_ip: *address,
+    };
+    let deadline = Instant::now() + timeout;
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            return Ok(None);
+        }
+        socket.send(&probe, &BROADCAST_MAC)?;
+        let next_probe = deadline.min(now + PROBE_INTERVAL);
+        while let Some(packet) = socket.recv(next_probe)? {
+            if let Some(mac) = conflict(&packet, address, &socket.mac) {
+                return Ok(Some(mac));
+            }
+        }
+    }
+}
+
+/// Options of an ARP scan.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ScanOpts {
+    /// maximum number of requests sent per second, 0 for no limit
+    pub rate: u32,
+
+    /// maximum number of requests waiting for a reply at the same time
+    pub concurrency: usize,
+
+    /// time to wait for the reply to a request
+    pub timeout: Duration,
+
+    /// number of times an unanswered request is repeated
+    pub retries: u32,
+}
+
+impl Default for ScanOpts {
+    fn default() -> ScanOpts {
+        ScanOpts { rate: 200, concurrency: 64, timeout: Duration::from_millis(500), retries: 1 }
+    }
+}
+
+/// Sends ARP requests to all host addresses of the subnet `network/prefix_len` from the interface
+/// with the given name and returns the addresses and MAC addresses of the responding hosts sorted
+/// by address. The requests are sent from the interface's IPv4 address in the subnet (or its
+/// first IPv4 address). Requires CAP_NET_RAW.
+pub fn scan(interface_name: &str, network: &Ipv4Addr, prefix_len: u8, opts: &ScanOpts)
+            -> Result<Vec<(Ipv4Addr, [u8; 6])>> {
+    if !(MIN_SCAN_PREFIX_LEN..=32).contains(&prefix_len) {
+        return Err(Error::new(ErrorKind::InvalidInput, format!("invalid scan prefix length {}", prefix_len)));
+    }
+    let mut queue: VecDeque<(Ipv4Addr, u32)> = subnet_hosts(network, prefix_len).map(|a| (a, 0)).collect();
+    let sender_ip = sender_address(interface_name, network, prefix_len)?;
+    let socket = ArpSocket::open(interface_name)?;
+    let send_interval = rate_interval(opts.rate);
+    let concurrency = opts.concurrency.max(1);
+
+    let mut pending: HashMap<Ipv4Addr, (Instant, u32)> = HashMap::new();
+    let mut hosts: Vec<(Ipv4Addr, [u8; 6])> = Vec::new();
+    let mut next_send = Instant::now();
+    while !queue.is_empty() || !pending.is_empty() {
+        let now = Instant::now();
+        if now >= next_send && pending.len() < concurrency {
+            if let Some((target_ip, attempt)) = queue.pop_front() {
+                let request = ArpPacket { operation: ARP_REQUEST, sender_mac: socket.mac, sender_ip, target_mac: [0; 6],
+                                          target_ip };
+                socket.send(&request, &BROADCAST_MAC)?;
+                pending.insert(target_ip, (now + opts.timeout, attempt));
+                next_send = now + send_interval;
+                continue;
+            }
+        }
+        let expired: Vec<Ipv4Addr> = pending.iter().filter(|(_, (d, _))| *d <= now).map(|(a, _)| *a).collect();
+        for address in expired {
+            if let Some((_, attempt)) = pending.remove(&address) {
+                if attempt < opts.retries {
+                    queue.push_front((address, attempt + 1));
+                }
+            }
+        }
+        let may_send = !queue.is_empty() && pending.len() < concurrency;
+        let deadline = receive_deadline(now, pending.values().map(|(d, _)| *d).min(), may_send.then_some(next_send));
+        if deadline <= now {
+            continue;
+        }
+        if let Some(reply) = socket.recv(deadline)? {
+            if reply.operation == ARP_REPLY && pending.remove(&reply.sender_ip).is_some() {
+                hosts.push((reply.sender_ip, reply.sender_mac));
+            }
+        }
+    }
+    hosts.sort();
+    Ok(hosts)
+}
+
+/// Returns the host addresses of the subnet, without network and broadcast addresses for prefix
+/// lengths below 31.
+fn subnet_hosts(network: &Ipv4Addr, prefix_len: u8) -> impl Iterator<Item = Ipv4Addr> {
+    IpNet::new(IpAddr::V4(*network), prefix_len).into_iter().flat_map(|network| network.hosts())
+        .filter_map(|host| match host {
+            IpAddr::V4(host) => Some(host),
+            IpAddr::V6(_) => None,
+        })
+}
+
+/// Returns the IPv4 address of the interface in the subnet, otherwise its first IPv4 address or
+/// UNSPECIFIED if it has none.
+fn sender_address(interface_name: &str, network: &Ipv4Addr, prefix_len: u8) -> Result<Ipv4Addr> {
+    let subnet = IpNet::new(IpAddr::V4(*network), prefix_len)?;
+    let addresses: Vec<Ipv4Addr> = IpInterface::retrieve_ip_interfaces()?.into_iter()
+        .filter(|i| i.name == interface_name)
+        .filter_map(|i| match i.address.ip() {
+            IpAddr::V4(address) => Some(address),
+            IpAddr::V6(_) => None,
+        })
+        .collect();
+    let in_subnet = addresses.iter().find(|a| subnet.contains(&IpAddr::V4(**a)));
+    Ok(in_subnet.or_else(|| addresses.first()).copied().unwrap_or(Ipv4Addr::UNSPECIFIED))
+}
+
+/// Returns the MAC of the sender if the received packet shows that another host uses or probes
+/// the address (RFC 5227 section 2.1.1).
+fn conflict(packet: &ArpPacket, address: &Ipv4Addr, own_mac: &[u8; 6]) -> Option<[u8; 6]> {
+    if packet.sender_mac == *own_mac {
+        return None;
+    }
+    let uses = packet.sender_ip == *address && (packet.operation == ARP_REQUEST || packet.operation == ARP_REPLY);
+    let probes = packet.operation == ARP_REQUEST && packet.sender_ip.is_unspecified() && packet.target_ip == *address;
+    if uses || probes { Some(packet.sender_mac) } else { None }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_arp_packet() {
+        let packet = ArpPacket {
+            operation: ARP_REPLY,
+            sender_mac: [0x02, 0, 0, 0, 0, 1],
+            sender_ip: Ipv4Addr::new(192, 0, 2, 1),
+            target_mac: [0x02, 0, 0, 0, 0, 2],
+            target_ip: Ipv4Addr::new(192, 0, 2, 2),
+        };
+        let encoded = packet.encode();
+        assert_eq!(&encoded[..8], &[0, 1, 8, 0, 6, 4, 0, 2]);
+        assert_eq!(ArpPacket::parse(&encoded), Some(packet));
+        assert_eq!(ArpPacket::parse(&encoded[..27]), None);
+    }
+
+    #[test]
+    fn test_conflict() {
+        let own = [0x02, 0, 0, 0, 0, 1];
+        let other = [0x02, 0, 0, 0, 0, 2];
+        let address = Ipv4Addr::new(192, 0, 2, 10);
+        let packet = |operation, sender_mac, sender_ip, target_ip| ArpPacket {
+            operation, sender_mac, sender_ip, target_mac: [0; 6], target_ip,
+        };
+        assert_eq!(conflict(&packet(ARP_REPLY, other, address, Ipv4Addr::UNSPECIFIED), &address, &own), Some(other));
+        assert_eq!(conflict(&packet(ARP_REQUEST, other, Ipv4Addr::UNSPECIFIED, address), &address, &own), Some(other));
+        assert_eq!(conflict(&packet(ARP_REQUEST, own, Ipv4Addr::UNSPECIFIED, address), &address, &own), None);
+        let unrelated = Ipv4Addr::new(192, 0, 2, 11);
+        assert_eq!(conflict(&packet(ARP_REQUEST, other, unrelated, address), &address, &own), None);
+    }
+
+    #[test]
+    fn test_subnet_hosts() {
+        let hosts: Vec<Ipv4Addr> = subnet_hosts(&Ipv4Addr::new(192, 168, 1, 77), 29).collect();
+        assert_eq!(hosts.first(), Some(&Ipv4Addr::new(192, 168, 1, 73)));
+        assert_eq!(hosts.last(), Some(&Ipv4Addr::new(192, 168, 1, 78)));
+        assert_eq!(hosts.len(), 6);
+        assert_eq!(subnet_hosts(&Ipv4Addr::new(10, 0, 0, 0), 31).count(), 2);
+        assert_eq!(subnet_hosts(&Ipv4Addr::new(10, 0, 0, 9), 32).collect::<Vec<_>>(), vec![Ipv4Addr::new(10, 0, 0, 9)]);
+        assert_eq!(subnet_hosts(&Ipv4Addr::new(10, 0, 0, 0), 16).count(), 65534);
+    }
+}
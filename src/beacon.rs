@@ -0,0 +1,415 @@
+//! Presence beacons for peer discovery on the local network: each node periodically multicasts
+//! a beacon with a random node id and an application defined payload, optionally signed with a
+//! shared key (HMAC-SHA256), and collects the beacons of the other nodes into a peer table.
+//! Peers expire after three missed announcements or leave with a goodbye beacon.
+//!
+//! Beacon format (big endian): magic "NUBC", version 1, flags (bit 0: signed), announcement
+//! interval in milliseconds (u32, 0 for goodbye), node id (u64), sequence number (u32), payload
+//! length (u16), payload, and for signed beacons the HMAC-SHA256 of all preceding bytes.
+
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    io::{ErrorKind, Result},
+    net::{IpAddr, SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use super::{MulticastSocketBuilder, sha256::hmac_sha256};
+
+const MAGIC: &[u8; 4] = b"NUBC";
+const VERSION: u8 = 1;
+const FLAG_SIGNED: u8 = 0x01;
+const HEADER_LEN: usize = 24;
+const TAG_LEN: usize = 32;
+
+/// Default interval between announcements.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Number of announcement intervals after which a silent peer expires.
+pub const EXPIRY_INTERVALS: u32 = 3;
+
+/// A beacon as sent on the wire.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BeaconMessage {
+    /// random id of the sending node
+    pub node_id: u64,
+
+    /// sequence number, incremented with every beacon of the node
+    pub sequence: u32,
+
+    /// interval until the next beacon, zero for a goodbye
+    pub interval: Duration,
+
+    /// application defined payload
+    pub payload: Vec<u8>,
+}
+
+impl BeaconMessage {
+
+    /// Returns the beacon in wire format, signed if a key is given. Fails with InvalidInput if
+    /// the payload is longer than 65535 bytes.
+    pub fn encode(&self, key: Option<&[u8]>) -> Result<Vec<u8>> {
+        let len: u16 = self.payload.len().try_into()
+            .map_err(|_| std::io::Error::new(ErrorKind::InvalidInput, "beacon payload too long"))?;
+        let interval = self.interval.as_millis().min(u32::MAX as u128) as u32;
+        let mut data = Vec::with_capacity(HEADER_LEN + self.payload.len() + TAG_LEN);
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&[VERSION, if key.is_some() { FLAG_SIGNED } else { 0 }]);
+        data.extend_from_slice(&interval.to_be_bytes());
+        data.extend_from_slice(&self.node_id.to_be_bytes());
+        data.extend_from_slice(&self.sequence.to_be_bytes());
+        data.extend_from_slice(&len.to_be_bytes());
+        data.extend_from_slice(&self.payload);
+        if let Some(key) = key {
+            let tag = hmac_sha256(key, &data);
+            data.extend_from_slice(&tag);
+        }
+        Ok(data)
+    }
+
+    /// Parses a beacon, None if it is invalid. With a key only beacons signed with it are
+    /// accepted, without the signature of signed beacons is not verified.
+    pub fn parse(data: &[u8], key: Option<&[u8]>) -> Option<BeaconMessage> {
+        if data.len() < HEADER_LEN || &data[..4] != MAGIC || data[4] != VERSION {
+            return None;
+        }
+        let signed = data[5] & FLAG_SIGNED != 0;
+        let len = u16::from_be_bytes([data[22], data[23]]) as usize;
+        let end = HEADER_LEN + len;
+        if data.len() != end + if signed { TAG_LEN } else { 0 } {
+            return None;
+        }
+        match (key, signed) {
+            (Some(_), false) => return None,
+            (Some(key), true) if !tag_equals(&hmac_sha256(key, &data[..end]), &data[end..]) => return None,
+            _ => {},
+        }
+        Some(BeaconMessage {
+            node_id: u64::from_be_bytes(data[10..18].try_into().unwrap()),
+            sequence: u32::from_be_bytes(data[18..22].try_into().unwrap()),
+            interval: Duration::from_millis(u32::from_be_bytes(data[6..10].try_into().unwrap()) as u64),
+            payload: data[HEADER_LEN..end].to_vec(),
+        })
+    }
+}
+
+/// Compares the tags in constant time.
+fn tag_equals(expected: &[u8], tag: &[u8]) -> bool {
+    expected.len() == tag.len() && expected.iter().zip(tag).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// A node discovered by its beacons.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BeaconPeer {
+    /// random id of the node
+    pub node_id: u64,
+
+    /// source address of the last beacon
+    pub address: SocketAddr,
+
+    /// local address of the interface the beacons are received on
+    pub interface: IpAddr,
+
+    /// payload of the last beacon
+    pub payload: Vec<u8>,
+
+    /// sequence number of the last beacon
+    pub sequence: u32,
+
+    /// reception time of the last beacon
+    pub last_seen: Instant,
+
+    /// time the peer expires without another beacon
+    pub expires: Instant,
+}
+
+/// A change of the peer table of a `Beacon`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BeaconEvent {
+    /// a new peer sent its first beacon
+    Appeared(BeaconPeer),
+
+    /// a known peer changed its payload or address
+    Changed(BeaconPeer),
+
+    /// a peer sent a goodbye
+    Left(BeaconPeer),
+
+    /// a peer missed `EXPIRY_INTERVALS` announcements
+    Expired(BeaconPeer),
+}
+
+/// Builder of a `Beacon`.
+/// ```no_run
+/// use net_utils::beacon::BeaconBuilder;
+/// let mut beacon = BeaconBuilder::new("239.255.42.42:4242".parse().unwrap(), "192.168.1.2".parse().unwrap())
+///     .payload(b"printer;name=office".to_vec())
+///     .key(b"shared secret".to_vec())
+///     .build()
+///     .unwrap();
+/// loop {
+///     for event in beacon.recv().unwrap() {
+///         println!("{:?}", event);
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BeaconBuilder {
+    group: SocketAddr,
+    interface: IpAddr,
+    interval: Duration,
+    payload: Vec<u8>,
+    key: Option<Vec<u8>>,
+    ttl: u32,
+}
+
+impl BeaconBuilder {
+
+    /// Creates a builder for beacons on the group and the interface with the given local
+    /// address, announcing every `DEFAULT_INTERVAL` with an empty payload, unsigned and with TTL 1.
+    pub fn new(group: SocketAddr, interface: IpAddr) -> BeaconBuilder {
+        BeaconBuilder { group, interface, interval: DEFAULT_INTERVAL, payload: Vec::new(), key: None, ttl: 1 }
+    }
+
+    /// Sets the interval between announcements, which are jittered by up to a tenth of it.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets the payload of the beacons.
+    pub fn payload(mut self, payload: Vec<u8>) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    /// Sets the key signing the beacons, beacons of other nodes without a valid signature with
+    /// the key are ignored.
+    pub fn key(mut self, key: Vec<u8>) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    /// Sets the TTL (hop limit) of the beacons.
+    pub fn ttl(mut self, ttl: u32) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Creates the beacon, the first announcement is sent by the first `recv`. Fails with
+    /// InvalidInput for a zero interval, a payload longer than 65535 bytes or the errors of
+    /// `MulticastSocketBuilder::build_std`.
+    pub fn build(self) -> Result<Beacon> {
+        if self.interval.is_zero() {
+            return Err(std::io::Error::new(ErrorKind::InvalidInput, "zero beacon interval"));
+        }
+        if self.payload.len() > u16::MAX as usize {
+            return Err(std::io::Error::new(ErrorKind::InvalidInput, "beacon payload too long"));
+        }
+        let socket = MulticastSocketBuilder::new(self.group, self.interface).ttl(self.ttl).loopback(true).build_std()?;
+        // receive the group only on the joined interface
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::unix::io::AsRawFd;
+            let (level, name) = match self.group {
+                SocketAddr::V4(_) => (libc::IPPROTO_IP, libc::IP_MULTICAST_ALL),
+                SocketAddr::V6(_) => (libc::IPPROTO_IPV6, IPV6_MULTICAST_ALL),
+            };
+            super::sockopt::set_int_option(socket.as_raw_fd(), level, name, 0)?;
+        }
+        let mut id = [0_u8; 8];
+        super::random::random_bytes(&mut id);
+        Ok(Beacon {
+            socket,
+            group: self.group,
+            interface: self.interface,
+            interval: self.interval,
+            key: self.key,
+            message: BeaconMessage { node_id: u64::from_ne_bytes(id), sequence: 0, interval: self.interval,
+                                     payload: self.payload },
+            next_announcement: Instant::now(),
+            peers: HashMap::new(),
+        })
+    }
+}
+
+#[cfg(target_os = "linux")]
+const IPV6_MULTICAST_ALL: libc::c_int = 29;
+
+/// A node announcing its presence and collecting the peers on the group, see `BeaconBuilder`.
+#[derive(Debug)]
+pub struct Beacon {
+    socket: UdpSocket,
+    group: SocketAddr,
+    interface: IpAddr,
+    interval: Duration,
+    key: Option<Vec<u8>>,
+    /// the next beacon to send
+    message: BeaconMessage,
+    next_announcement: Instant,
+    peers: HashMap<u64, BeaconPeer>,
+}
+
+impl Beacon {
+
+    /// Returns the random id of this node.
+    pub fn node_id(&self) -> u64 {
+        self.message.node_id
+    }
+
+    /// Returns the payload of the beacons.
+    pub fn payload(&self) -> &[u8] {
+        &self.message.payload
+    }
+
+    /// Sets the payload of the beacons and announces it immediately. Fails with InvalidInput if
+    /// it is longer than 65535 bytes.
+    pub fn set_payload(&mut self, payload: Vec<u8>) -> Result<()> {
+        if payload.len() > u16::MAX as usize {
+            return Err(std::io::Error::new(ErrorKind::InvalidInput, "beacon payload too long"));
+        }
+        self.message.payload = payload;
+        self.announce()
+    }
+
+    /// Returns the known peers ordered by node id.
+    pub fn peers(&self) -> Vec<&BeaconPeer> {
+        let mut peers: Vec<_> = self.peers.values().collect();
+        peers.sort_by_key(|peer| peer.node_id);
+        peers
+    }
+
+    /// Returns the peer with the node id.
+    pub fn peer(&self, node_id: u64) -> Option<&BeaconPeer> {
+        self.peers.get(&node_id)
+    }
+
+    /// Sends a beacon now and schedules the next one.
+    pub fn announce(&mut self) -> Result<()> {
+        self.send(self.interval)?;
+        let mut jitter = [0_u8; 2];
+        super::random::random_bytes(&mut jitter);
+        let jitter = self.interval / 10 * u16::from_ne_bytes(jitter) as u32 / u16::MAX as u32;
+        self.next_announcement = Instant::now() + self.interval - jitter;
+        Ok(())
+    }
+
+    /// Sends a goodbye, the peers remove this node immediately. Beacons sent by later calls of
+    /// `recv` announce the node again.
+    pub fn goodbye(&mut self) -> Result<()> {
+        self.send(Duration::ZERO)
+    }
+
+    /// Sends announcements when they are due and waits for the next beacon of another node or
+    /// the next announcement, returns the changes of the peer table, which may be none.
+    pub fn recv(&mut self) -> Result<Vec<BeaconEvent>> {
+        let mut buffer = vec![0_u8; 65536];
+        loop {
+            let now = Instant::now();
+            if now >= self.next_announcement {
+                self.announce()?;
+            }
+            let events = self.expire(now);
+            if !events.is_empty() {
+                return Ok(events);
+            }
+            let wakeup = self.peers.values().map(|peer| peer.expires).fold(self.next_announcement, Instant::min);
+            let timeout = wakeup.saturating_duration_since(now).max(Duration::from_millis(1));
+            self.socket.set_read_timeout(Some(timeout))?;
+            match self.socket.recv_from(&mut buffer) {
+                Ok((len, source)) => return Ok(self.receive(&buffer[..len], source, Instant::now()).into_iter().collect()),
+                Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn send(&mut self, interval: Duration) -> Result<()> {
+        self.message.sequence = self.message.sequence.wrapping_add(1);
+        self.message.interval = interval;
+        let data = self.message.encode(self.key.as_deref())?;
+        self.socket.send_to(&data, self.group).map(|_| ())
+    }
+
+    /// Updates the peer table with a received datagram.
+    fn receive(&mut self, data: &[u8], source: SocketAddr, now: Instant) -> Option<BeaconEvent> {
+        let message = BeaconMessage::parse(data, self.key.as_deref())?;
+        if message.node_id == self.message.node_id {
+            return None;
+        }
+        let known = self.peers.get(&message.node_id);
+        // replayed or reordered beacon or goodbye
+        if known.is_some_and(|known| (message.sequence.wrapping_sub(known.sequence) as i32) <= 0) {
+            return None;
+        }
+        if message.interval.is_zero() {
+            return self.peers.remove(&message.node_id).map(BeaconEvent::Left);
+        }
+        let peer = BeaconPeer {
+            node_id: message.node_id, address: source, interface: self.interface, payload: message.payload,
+            sequence: message.sequence, last_seen: now, expires: now + message.interval * EXPIRY_INTERVALS,
+        };
+        match self.peers.get_mut(&message.node_id) {
+            Some(known) => {
+                let changed = known.payload != peer.payload || known.address != peer.address;
+                *known = peer.clone();
+                changed.then_some(BeaconEvent::Changed(peer))
+            },
+            None => {
+                self.peers.insert(message.node_id, peer.clone());
+                Some(BeaconEvent::Appeared(peer))
+            },
+        }
+    }
+
+    /// Removes the expired peers.
+    fn expire(&mut self, now: Instant) -> Vec<BeaconEvent> {
+        let mut expired: Vec<_> = self.peers.values().filter(|peer| peer.expires <= now).map(|peer| peer.node_id).collect();
+        expired.sort_unstable();
+        expired.iter().filter_map(|node_id| self.peers.remove(node_id)).map(BeaconEvent::Expired).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_beacon_message() {
+        let message = BeaconMessage { node_id: 0x0102030405060708, sequence: 7, interval: Duration::from_secs(5),
+                                      payload: b"name=office".to_vec() };
+        let encoded = message.encode(None).unwrap();
+        assert_eq!(encoded.len(), HEADER_LEN + 11);
+        assert_eq!(&encoded[..6], b"NUBC\x01\x00");
+        assert_eq!(BeaconMessage::parse(&encoded, None), Some(message.clone()));
+        // unsigned beacons are ignored with a key
+        assert_eq!(BeaconMessage::parse(&encoded, Some(b"key")), None);
+        assert_eq!(BeaconMessage::parse(&encoded[..encoded.len() - 1], None), None);
+
+        let mut signed = message.encode(Some(b"key")).unwrap();
+        assert_eq!(signed.len(), HEADER_LEN + 11 + TAG_LEN);
+        assert_eq!(BeaconMessage::parse(&signed, Some(b"key")), Some(message.clone()));
+        assert_eq!(BeaconMessage::parse(&signed, Some(b"other key")), None);
+        assert_eq!(BeaconMessage::parse(&signed, None), Some(message));
+        signed[HEADER_LEN] ^= 1;
+        assert_eq!(BeaconMessage::parse(&signed, Some(b"key")), None);
+    }
+
+    #[test]
+    fn test_replayed_goodbye() {
+        let mut beacon = BeaconBuilder::new("239.255.77.11:1917".parse().unwrap(), "127.0.0.1".parse().unwrap())
+            .build().unwrap();
+        let source = "127.0.0.1:1917".parse().unwrap();
+        let now = Instant::now();
+        let message = |sequence, interval| BeaconMessage { node_id: 9, sequence, interval, payload: Vec::new() }
+            .encode(None).unwrap();
+        assert!(matches!(beacon.receive(&message(2, DEFAULT_INTERVAL), source, now), Some(BeaconEvent::Appeared(_))));
+        // a replayed earlier goodbye
+        assert!(beacon.receive(&message(1, Duration::ZERO), source, now).is_none());
+        assert!(beacon.peer(9).is_some());
+        assert!(matches!(beacon.receive(&message(3, Duration::ZERO), source, now), Some(BeaconEvent::Left(_))));
+        assert!(beacon.peer(9).is_none());
+    }
+}
@@ -0,0 +1,243 @@
+//! Command-line companion of the net-utils crate for checking interfaces and multicast paths
+//! with the code applications embed. Requires the feature 'cli'.
+//! ```text
+//! netu if list
+//! netu mc recv 239.1.2.3:5000 [--iface eth0|192.0.2.2] [--count N] [--timeout SECONDS]
+//! netu mc send 239.1.2.3:5000 MESSAGE [--iface eth0|192.0.2.2] [--ttl N] [--count N] [--interval MS]
+//! netu monitor
+//! ```
+
+use std::{
+    io::{Error, ErrorKind, Result},
+    net::SocketAddr,
+    time::Duration,
+};
+#[cfg(unix)]
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+const USAGE: &str = "usage:
+  netu if list
+  netu mc recv GROUP:PORT [--iface NAME|ADDRESS] [--count N] [--timeout SECONDS]
+  netu mc send GROUP:PORT MESSAGE [--iface NAME|ADDRESS] [--ttl N] [--count N] [--interval MS]
+  netu monitor";
+
+/// A parsed command line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Command {
+    ListInterfaces,
+    Receive { group: SocketAddr, interface: Option<String>, count: Option<u64>, timeout: Option<Duration> },
+    Send { group: SocketAddr, message: String, interface: Option<String>, ttl: Option<u32>, count: u64, interval: Duration },
+    Monitor,
+}
+
+fn invalid<T>(message: String) -> Result<T> {
+    Err(Error::new(ErrorKind::InvalidInput, message))
+}
+
+/// Parses the value of an option.
+fn value<T: std::str::FromStr>(option: &str, value: Option<&String>) -> Result<T> {
+    match value.map(|v| v.parse()) {
+        Some(Ok(v)) => Ok(v),
+        _ => invalid(format!("missing or invalid value of {}", option)),
+    }
+}
+
+impl Command {
+
+    fn parse(args: &[String]) -> Result<Command> {
+        let words: Vec<&str> = args.iter().take(2).map(String::as_str).collect();
+        match words.as_slice() {
+            ["if", "list"] if args.len() == 2 => Ok(Command::ListInterfaces),
+            ["monitor"] => Ok(Command::Monitor),
+            ["mc", mode @ ("recv" | "send")] => {
+                let group: SocketAddr = value("GROUP:PORT", args.get(2))?;
+                let mut rest = args[3..].iter();
+                let message = if *mode == "send" {
+                    Some(rest.next().cloned().ok_or_else(|| Error::new(ErrorKind::InvalidInput, "missing MESSAGE"))?)
+                } else {
+                    None
+                };
+                let (mut interface, mut count, mut timeout, mut ttl, mut interval) = (None, None, None, None, None);
+                while let Some(option) = rest.next() {
+                    match (option.as_str(), *mode) {
+                        ("--iface", _) => interface = Some(value(option, rest.next())?),
+                        ("--count", _) => count = Some(value(option, rest.next())?),
+                        ("--timeout", "recv") => timeout = Some(Duration::from_secs(value(option, rest.next())?)),
+                        ("--ttl", "send") => ttl = Some(value(option, rest.next())?),
+                        ("--interval", "send") => interval = Some(Duration::from_millis(value(option, rest.next())?)),
+                        _ => return invalid(format!("unknown option {}", option)),
+                    }
+                }
+                match message {
+                    Some(message) => Ok(Command::Send {
+                        group, message, interface, ttl, count: count.unwrap_or(1),
+                        interval: interval.unwrap_or(Duration::from_secs(1)),
+                    }),
+                    None => Ok(Command::Receive { group, interface, count, timeout: timeout.filter(|t| !t.is_zero()) }),
+                }
+            },
+            _ => invalid("unknown command".to_string()),
+        }
+    }
+}
+
+/// Resolves the --iface value, an interface address or name, to the local address of the
+/// family of the group. Without a value the default interface is used.
+#[cfg(unix)]
+fn interface_address(interface: Option<&str>, group: &SocketAddr) -> Result<IpAddr> {
+    let name = match interface {
+        None => return Ok(if group.is_ipv4() { Ipv4Addr::UNSPECIFIED.into() } else { Ipv6Addr::UNSPECIFIED.into() }),
+        Some(interface) => match interface.parse() {
+            Ok(address) => return Ok(address),
+            Err(_) => interface,
+        },
+    };
+    net_utils::IpInterface::retrieve_ip_interfaces()?.iter()
+        .filter(|i| i.name == name && i.address.is_ipv4() == group.is_ipv4())
+        .map(|i| i.address.ip())
+        .next()
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("no {} address on interface {}",
+                                                                if group.is_ipv4() { "IPv4" } else { "IPv6" }, name)))
+}
+
+#[cfg(unix)]
+fn list_interfaces() -> Result<()> {
+    for interface in net_utils::IpInterface::retrieve_ip_interfaces()? {
+        println!("{:>3}: {:<16} {}/{} <{}>", interface.index, interface.name, interface.address.ip(),
+                 interface.prefix_len(), interface.flag_names().join(","));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn receive(group: SocketAddr, interface: Option<&str>, count: Option<u64>, timeout: Option<Duration>) -> Result<()> {
+    let interface = interface_address(interface, &group)?;
+    let socket = net_utils::MulticastSocketBuilder::new(group, interface).build_std()?;
+    eprintln!("receiving from {} on {}", group, interface);
+    let mut buf = vec![0_u8; 65536];
+    let mut received = 0;
+    while count.is_none_or(|count| received < count) {
+        let (len, source) = match timeout {
+            Some(timeout) => match net_utils::recv_from_timeout(&socket, &mut buf, timeout)? {
+                Some(datagram) => datagram,
+                None => return Err(Error::new(ErrorKind::TimedOut, format!("no datagram within {:?}", timeout))),
+            },
+            None => socket.recv_from(&mut buf)?,
+        };
+        println!("{} bytes from {}: {}", len, source, String::from_utf8_lossy(&buf[..len]));
+        received += 1;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn send(group: SocketAddr, message: &str, interface: Option<&str>, ttl: Option<u32>, count: u64, interval: Duration)
+        -> Result<()> {
+    let interface = interface_address(interface, &group)?;
+    let mut builder = net_utils::MulticastSocketBuilder::new(group, interface).join(false);
+    if let Some(ttl) = ttl {
+        builder = builder.ttl(ttl);
+    }
+    let socket = builder.build_std()?;
+    for n in 0..count {
+        if n > 0 {
+            std::thread::sleep(interval);
+        }
+        let len = socket.send_to(message.as_bytes(), group)?;
+        println!("sent {} bytes to {} via {}", len, group, interface);
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn monitor() -> Result<()> {
+    use net_utils::{IpAddressInfo, netlink::{LinkInfo, NetlinkSocket}};
+    let groups = (libc::RTMGRP_LINK | libc::RTMGRP_IPV4_IFADDR | libc::RTMGRP_IPV6_IFADDR) as u32;
+    let socket = NetlinkSocket::new(libc::NETLINK_ROUTE, groups)?;
+    eprintln!("monitoring link and address changes");
+    loop {
+        for mut msg in socket.receive()? {
+            let action = match msg.msg_type {
+                libc::RTM_NEWLINK | libc::RTM_NEWADDR => "new",
+                libc::RTM_DELLINK | libc::RTM_DELADDR => "del",
+                _ => continue,
+            };
+            // deletions carry the same payload as the corresponding new message
+            if msg.msg_type == libc::RTM_DELLINK || msg.msg_type == libc::RTM_DELADDR {
+                msg.msg_type -= 1;
+            }
+            if let Some(link) = LinkInfo::from_message(&msg) {
+                let up = link.flags & libc::IFF_UP as u32 != 0;
+                println!("{} link {}: {} {}", action, link.index, link.name.as_deref().unwrap_or("?"),
+                         if up { "up" } else { "down" });
+            } else if let Some(address) = IpAddressInfo::from_message(&msg) {
+                println!("{} address {}/{} on {}", action, address.address, address.prefix_len, address.index);
+            }
+        }
+    }
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn monitor() -> Result<()> {
+    Err(Error::new(ErrorKind::Unsupported, "monitor requires linux"))
+}
+
+#[cfg(unix)]
+fn run(command: Command) -> Result<()> {
+    match command {
+        Command::ListInterfaces => list_interfaces(),
+        Command::Receive { group, interface, count, timeout } => receive(group, interface.as_deref(), count, timeout),
+        Command::Send { group, message, interface, ttl, count, interval } =>
+            send(group, &message, interface.as_deref(), ttl, count, interval),
+        Command::Monitor => monitor(),
+    }
+}
+
+#[cfg(not(unix))]
+fn run(_command: Command) -> Result<()> {
+    Err(Error::new(ErrorKind::Unsupported, "netu requires a unix system"))
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = match Command::parse(&args) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(2);
+        },
+    };
+    if let Err(e) = run(command) {
+        eprintln!("netu: {}", e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn parse(line: &str) -> Result<Command> {
+        Command::parse(&line.split_whitespace().map(String::from).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("if list").unwrap(), Command::ListInterfaces);
+        assert_eq!(parse("monitor").unwrap(), Command::Monitor);
+        assert_eq!(parse("mc recv 239.1.2.3:5000 --iface eth0 --timeout 3").unwrap(), Command::Receive {
+            group: "239.1.2.3:5000".parse().unwrap(), interface: Some("eth0".to_string()), count: None,
+            timeout: Some(Duration::from_secs(3)),
+        });
+        assert_eq!(parse("mc send [ff02::1:3]:5355 hello --ttl 4 --count 2").unwrap(), Command::Send {
+            group: "[ff02::1:3]:5355".parse().unwrap(), message: "hello".to_string(), interface: None, ttl: Some(4),
+            count: 2, interval: Duration::from_secs(1),
+        });
+        assert!(parse("mc recv 239.1.2.3").is_err());
+        assert!(parse("mc send 239.1.2.3:5000").is_err());
+        assert!(parse("mc recv 239.1.2.3:5000 --ttl 3").is_err());
+        assert!(parse("if list all").is_err());
+        assert!(parse("").is_err());
+    }
+}
@@ -0,0 +1,232 @@
+//! Linux capabilities required by socket configurations: `required_capabilities` reports which
+//! of CAP_NET_BIND_SERVICE, CAP_NET_ADMIN and CAP_NET_RAW a `socket_factory::SocketSpec` needs
+//! and `check_capabilities` compares them with the effective set of the process, so that a
+//! missing capability is reported with its reason before a system call fails with EPERM.
+
+use std::{
+    fmt,
+    io::{Error, ErrorKind, Result},
+};
+
+use super::socket_factory::SocketSpec;
+
+/// Path of the lowest port which can be bound without CAP_NET_BIND_SERVICE.
+const UNPRIVILEGED_PORT_START: &str = "/proc/sys/net/ipv4/ip_unprivileged_port_start";
+
+/// Kernel version from which SO_BINDTODEVICE does not require CAP_NET_RAW.
+const UNPRIVILEGED_BIND_DEVICE: (u32, u32) = (5, 7);
+
+/// A network related capability.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Capability {
+    /// binding to ports below net.ipv4.ip_unprivileged_port_start
+    NetBindService,
+    /// network administration, e.g. forced buffer sizes and transparent proxying
+    NetAdmin,
+    /// raw and packet sockets, binding to devices on older kernels
+    NetRaw,
+}
+
+impl Capability {
+
+    /// Returns the number of the capability (CAP_NET_*).
+    pub fn number(self) -> u32 {
+        match self {
+            Capability::NetBindService => 10,
+            Capability::NetAdmin => 12,
+            Capability::NetRaw => 13,
+        }
+    }
+
+    /// Returns the name of the capability, e.g. CAP_NET_RAW.
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::NetBindService => "CAP_NET_BIND_SERVICE",
+            Capability::NetAdmin => "CAP_NET_ADMIN",
+            Capability::NetRaw => "CAP_NET_RAW",
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A set of capabilities as the bit mask of their numbers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CapabilitySet(pub u64);
+
+impl CapabilitySet {
+
+    /// Returns whether the set contains the capability.
+    pub fn contains(&self, capability: Capability) -> bool {
+        self.0 & (1 << capability.number()) != 0
+    }
+
+    /// Adds the capability to the set.
+    pub fn insert(&mut self, capability: Capability) {
+        self.0 |= 1 << capability.number();
+    }
+}
+
+/// A capability required by a socket configuration and the reason.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CapabilityRequirement {
+    /// the required capability
+    pub capability: Capability,
+
+    /// the setting which requires it, e.g. the port below the unprivileged port start
+    pub reason: String,
+}
+
+impl fmt::Display for CapabilityRequirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.capability, self.reason)
+    }
+}
+
+/// Returns the effective capabilities of the process (CapEff of /proc/self/status).
+pub fn effective_capabilities() -> Result<CapabilitySet> {
+    let status = std::fs::read_to_string("/proc/self/status")?;
+    parse_effective(&status).ok_or_else(|| Error::new(ErrorKind::InvalidData, "no CapEff in /proc/self/status"))
+}
+
+/// Returns the capabilities the socket configuration requires, with the unprivileged port
+/// start of net.ipv4.ip_unprivileged_port_start (1024 if it cannot be read) and the kernel
+/// version of the running system.
+pub fn required_capabilities(spec: &SocketSpec) -> Vec<CapabilityRequirement> {
+    let port_start = std::fs::read_to_string(UNPRIVILEGED_PORT_START).ok()
+        .and_then(|start| start.trim().parse().ok())
+        .unwrap_or(1024);
+    requirements(spec, port_start, kernel_version())
+}
+
+/// Returns the required capabilities which are not in the effective set of the process.
+pub fn missing_capabilities(spec: &SocketSpec) -> Result<Vec<CapabilityRequirement>> {
+    let effective = effective_capabilities()?;
+    Ok(required_capabilities(spec).into_iter().filter(|required| !effective.contains(required.capability)).collect())
+}
+
+/// Checks that the process has the capabilities the socket configuration requires, fails with
+/// PermissionDenied naming the missing capabilities, their reasons and how to grant them.
+pub fn check_capabilities(spec: &SocketSpec) -> Result<()> {
+    let missing = missing_capabilities(spec)?;
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let list: Vec<String> = missing.iter().map(|required| required.to_string()).collect();
+    let mut names: Vec<&str> = missing.iter().map(|required| required.capability.name()).collect();
+    names.dedup();
+    Err(Error::new(ErrorKind::PermissionDenied, format!(
+        "missing capabilities: {}, grant them with AmbientCapabilities={} in the systemd unit or setcap {}+ep on the binary",
+        list.join(", "), names.join(" "), names.join(",").to_lowercase())))
+}
+
+fn requirements(spec: &SocketSpec, port_start: u16, kernel: Option<(u32, u32)>) -> Vec<CapabilityRequirement> {
+    let mut required = Vec::new();
+    let mut require = |capability, reason: String| required.push(CapabilityRequirement { capability, reason });
+    let (port, device) = match spec {
+        SocketSpec::Udp(spec) => {
+            if spec.forces_buffers() {
+                require(Capability::NetAdmin, "buffer size above net.core.rmem_max / wmem_max".to_string());
+            }
+            (spec.address().port(), spec.device())
+        },
+        SocketSpec::Multicast(builder) => (builder.group().port(), None),
+        SocketSpec::TcpListener(builder) => {
+            if builder.is_transparent() {
+                require(Capability::NetAdmin, "transparent proxy socket".to_string());
+            }
+            (builder.address().port(), builder.device())
+        },
+        SocketSpec::Raw(_) => {
+            require(Capability::NetRaw, "raw socket".to_string());
+            (0, None)
+        },
+    };
+    if port != 0 && port < port_start {
+        require(Capability::NetBindService, format!("port {} below the unprivileged port start {}", port, port_start));
+    }
+    if let Some(device) = device {
+        if kernel.is_none_or(|kernel| kernel < UNPRIVILEGED_BIND_DEVICE) {
+            require(Capability::NetRaw, format!("binding to device {} before linux 5.7", device));
+        }
+    }
+    required.sort_by_key(|required| required.capability);
+    required
+}
+
+/// Returns the major and minor version of the running kernel.
+fn kernel_version() -> Option<(u32, u32)> {
+    let mut name: libc::utsname = unsafe { std::mem::zeroed() };
+    if unsafe { libc::uname(&mut name) } != 0 {
+        return None;
+    }
+    let release = unsafe { std::ffi::CStr::from_ptr(name.release.as_ptr()) };
+    parse_kernel_version(&release.to_string_lossy())
+}
+
+fn parse_kernel_version(release: &str) -> Option<(u32, u32)> {
+    let mut parts = release.split(|c: char| !c.is_ascii_digit());
+    Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+}
+
+/// Returns the effective capabilities of the CapEff line of a status file.
+fn parse_effective(status: &str) -> Option<CapabilitySet> {
+    let line = status.lines().find_map(|line| line.strip_prefix("CapEff:"))?;
+    u64::from_str_radix(line.trim(), 16).ok().map(CapabilitySet)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::{MulticastSocketBuilder, raw::RawSocketBuilder, socket_factory::UdpSpec, tcp::TcpListenerBuilder};
+
+    fn capabilities(spec: SocketSpec, kernel: (u32, u32)) -> Vec<Capability> {
+        requirements(&spec, 1024, Some(kernel)).into_iter().map(|required| required.capability).collect()
+    }
+
+    #[test]
+    fn test_requirements() {
+        let address = "0.0.0.0:123".parse().unwrap();
+        assert_eq!(capabilities(UdpSpec::new(address).into(), (6, 1)), vec![Capability::NetBindService]);
+        let spec = UdpSpec::new("0.0.0.0:0".parse().unwrap()).bind_device("eth0").recv_buffer_size(1 << 24).force_buffers(true);
+        assert_eq!(capabilities(spec.clone().into(), (6, 1)), vec![Capability::NetAdmin]);
+        assert_eq!(capabilities(spec.into(), (5, 4)), vec![Capability::NetAdmin, Capability::NetRaw]);
+        assert!(capabilities(UdpSpec::new("0.0.0.0:0".parse().unwrap()).force_buffers(true).into(), (6, 1)).is_empty());
+
+        let builder = MulticastSocketBuilder::new("239.255.255.250:1900".parse().unwrap(), "0.0.0.0".parse().unwrap());
+        assert!(capabilities(builder.port(427).into(), (6, 1)).contains(&Capability::NetBindService));
+        let builder = TcpListenerBuilder::new("[::]:8080".parse().unwrap()).transparent(true);
+        assert_eq!(capabilities(builder.into(), (6, 1)), vec![Capability::NetAdmin]);
+        assert_eq!(capabilities(RawSocketBuilder::ipv4(112).into(), (6, 1)), vec![Capability::NetRaw]);
+
+        let required = requirements(&UdpSpec::new(address).into(), 100, None);
+        assert!(required.is_empty());
+        let required = requirements(&UdpSpec::new(address).into(), 1024, None);
+        assert_eq!(required[0].to_string(), "CAP_NET_BIND_SERVICE (port 123 below the unprivileged port start 1024)");
+    }
+
+    #[test]
+    fn test_parse() {
+        let status = "Name:\tdaemon\nCapInh:\t0000000000000000\nCapEff:\t0000000000003000\n";
+        let effective = parse_effective(status).unwrap();
+        assert!(effective.contains(Capability::NetAdmin) && effective.contains(Capability::NetRaw));
+        assert!(!effective.contains(Capability::NetBindService));
+        assert_eq!(parse_effective("Name:\tdaemon\n"), None);
+        assert_eq!(parse_kernel_version("6.18.44-fc-v130"), Some((6, 18)));
+        assert_eq!(parse_kernel_version("5.4"), Some((5, 4)));
+        assert_eq!(parse_kernel_version("linux"), None);
+        let mut set = CapabilitySet::default();
+        set.insert(Capability::NetBindService);
+        assert_eq!(set, CapabilitySet(1 << 10));
+    }
+
+    #[test]
+    fn test_check_capabilities() {
+        assert!(check_capabilities(&UdpSpec::new("127.0.0.1:0".parse().unwrap()).into()).is_ok());
+    }
+}
@@ -0,0 +1,208 @@
+//! Internet checksum (RFC 1071) of IP, ICMP, IGMP, UDP and TCP headers including the pseudo
+//! headers of IPv4 and IPv6, and its incremental update (RFC 1624) for rewritten fields, for
+//! packets crafted for or received from raw sockets (see `raw`).
+
+use std::net::IpAddr;
+
+const IPPROTO_TCP: u8 = 6;
+const IPPROTO_UDP: u8 = 17;
+
+/// Incremental computation of the Internet checksum over consecutive parts of the data, which may
+/// have odd lengths.
+/// ```
+/// use net_utils::checksum::{Checksum, internet_checksum};
+/// let mut checksum = Checksum::new();
+/// checksum.add(&[0x45, 0x00, 0x00]);
+/// checksum.add(&[0x1c]);
+/// assert_eq!(checksum.finish(), internet_checksum(&[0x45, 0x00, 0x00, 0x1c]));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Checksum {
+    sum: u64,
+    /// the last byte of odd length data, the high byte of the next word
+    pending: Option<u8>,
+}
+
+impl Checksum {
+
+    /// Creates a checksum of no data.
+    pub fn new() -> Checksum {
+        Checksum::default()
+    }
+
+    /// Creates a checksum starting with the pseudo header of IPv4 (RFC 768) if both addresses are
+    /// IPv4 addresses, of IPv6 (RFC 8200) otherwise, where IPv4 addresses are mapped to IPv6.
+    /// `len` is the length of the upper layer packet, `protocol` its protocol (next header).
+    pub fn with_pseudo_header(source: &IpAddr, destination: &IpAddr, protocol: u8, len: usize) -> Checksum {
+        let mut checksum = Checksum::new();
+        match (source, destination) {
+            (IpAddr::V4(source), IpAddr::V4(destination)) => {
+                checksum.add(&source.octets());
+                checksum.add(&destination.octets());
+                checksum.add(&[0, protocol]);
+                checksum.add(&(len as u16).to_be_bytes());
+            },
+            (source, destination) => {
+                let octets = |address: &IpAddr| match address {
+                    IpAddr::V4(address) => address.to_ipv6_mapped().octets(),
+                    IpAddr::V6(address) => address.octets(),
+                };
+                checksum.add(&octets(source));
+                checksum.add(&octets(destination));
+                checksum.add(&(len as u32).to_be_bytes());
+                checksum.add(&[0, 0, 0, protocol]);
+            },
+        }
+        checksum
+    }
+
+    /// Adds the data, which continue the previously added data.
+    pub fn add(&mut self, data: &[u8]) {
+        let data = match self.pending.take() {
+            Some(high) if !data.is_empty() => {
+                self.sum += u16::from_be_bytes([high, data[0]]) as u64;
+                &data[1..]
+            },
+            pending => {
+                self.pending = pending;
+                data
+            },
+        };
+        let mut words = data.chunks_exact(2);
+        self.sum += words.by_ref().map(|word| u16::from_be_bytes([word[0], word[1]]) as u64).sum::<u64>();
+        if let [last] = words.remainder() {
+            self.pending = Some(*last);
+        }
+    }
+
+    /// Returns the checksum, the data are padded with a zero byte to an even length.
+    pub fn finish(&self) -> u16 {
+        let mut sum = self.sum + self.pending.map_or(0, |high| (high as u64) << 8);
+        while sum >> 16 != 0 {
+            sum = (sum & 0xffff) + (sum >> 16);
+        }
+        !(sum as u16)
+    }
+}
+
+/// Returns the Internet checksum of the data. The checksum of data including a correct checksum
+/// is zero, see `verify`.
+pub fn internet_checksum(data: &[u8]) -> u16 {
+    let mut checksum = Checksum::new();
+    checksum.add(data);
+    checksum.finish()
+}
+
+/// Returns whether the data, which include their checksum, have a correct Internet checksum.
+pub fn verify(data: &[u8]) -> bool {
+    internet_checksum(data) == 0
+}
+
+/// Returns the checksum of the upper layer packet (e.g. a TCP segment or an ICMPv6 message)
+/// with the pseudo header of the protocol, see `Checksum::with_pseudo_header`. The checksum of
+/// a packet including a correct checksum is zero.
+pub fn pseudo_header_checksum(source: &IpAddr, destination: &IpAddr, protocol: u8, packet: &[u8]) -> u16 {
+    let mut checksum = Checksum::with_pseudo_header(source, destination, protocol, packet.len());
+    checksum.add(packet);
+    checksum.finish()
+}
+
+/// Returns the checksum of the UDP datagram (header with zero checksum field and payload) sent
+/// from source to destination. The computed checksum zero is sent as 0xffff, as zero means no
+/// checksum (in IPv4).
+pub fn udp_checksum(source: &IpAddr, destination: &IpAddr, datagram: &[u8]) -> u16 {
+    match pseudo_header_checksum(source, destination, IPPROTO_UDP, datagram) {
+        0 => 0xffff,
+        checksum => checksum,
+    }
+}
+
+/// Returns the checksum of the TCP segment (header with zero checksum field and payload) sent
+/// from source to destination.
+pub fn tcp_checksum(source: &IpAddr, destination: &IpAddr, segment: &[u8]) -> u16 {
+    pseudo_header_checksum(source, destination, IPPROTO_TCP, segment)
+}
+
+/// Updates the checksum of data in which the bytes `old` were replaced by `new` (RFC 1624), e.g.
+/// a rewritten address or a decremented TTL, without summing up the data again. The replaced
+/// bytes must start at an even offset of the checksummed data, `old` and `new` must have the
+/// same length, an odd length is padded with a zero byte.
+pub fn update_checksum(checksum: u16, old: &[u8], new: &[u8]) -> u16 {
+    debug_assert_eq!(old.len(), new.len());
+    // HC' = ~(~HC + ~m + m'), the complement of the sum of the complemented words of m
+    let mut sum = Checksum::new();
+    sum.add(&(!checksum).to_be_bytes());
+    for word in old.chunks(2) {
+        sum.add(&(!u16::from_be_bytes([word[0], *word.get(1).unwrap_or(&0)])).to_be_bytes());
+    }
+    for word in new.chunks(2) {
+        sum.add(&[word[0], *word.get(1).unwrap_or(&0)]);
+    }
+    sum.finish()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    const IPPROTO_ICMPV6: u8 = 58;
+
+    #[test]
+    fn test_internet_checksum() {
+        // example of RFC 1071, 4.1
+        let data = [0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7];
+        assert_eq!(internet_checksum(&data), !0xddf2);
+        let mut checksum = Checksum::new();
+        for part in data.chunks(3) {
+            checksum.add(part);
+        }
+        checksum.add(&[]);
+        assert_eq!(checksum.finish(), !0xddf2);
+        assert_eq!(internet_checksum(&[0x12]), !0x1200);
+        assert_eq!(internet_checksum(&[]), 0xffff);
+
+        // IPv4 header with its checksum
+        let header = [0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0xb8, 0x61,
+                      0xc0, 0xa8, 0x00, 0x01, 0xc0, 0xa8, 0x00, 0xc7];
+        assert!(verify(&header));
+        assert!(!verify(&header[..18]));
+    }
+
+    #[test]
+    fn test_pseudo_header() {
+        let source: IpAddr = "192.0.2.1".parse().unwrap();
+        let destination: IpAddr = "192.0.2.2".parse().unwrap();
+        let mut datagram = vec![0x30, 0x39, 0x00, 0x35, 0x00, 0x0b, 0x00, 0x00, b'a', b'b', b'c'];
+        let checksum = udp_checksum(&source, &destination, &datagram);
+        datagram[6..8].copy_from_slice(&checksum.to_be_bytes());
+        assert_eq!(pseudo_header_checksum(&source, &destination, IPPROTO_UDP, &datagram), 0);
+        assert_ne!(tcp_checksum(&source, &destination, &datagram), 0);
+
+        let source: IpAddr = "fe80::1".parse().unwrap();
+        let destination: IpAddr = "ff02::1".parse().unwrap();
+        let mut message = vec![128, 0, 0, 0, 0, 1, 0, 1];
+        let checksum = pseudo_header_checksum(&source, &destination, IPPROTO_ICMPV6, &message);
+        message[2..4].copy_from_slice(&checksum.to_be_bytes());
+        assert_eq!(pseudo_header_checksum(&source, &destination, IPPROTO_ICMPV6, &message), 0);
+        assert_ne!(pseudo_header_checksum(&destination, &destination, IPPROTO_ICMPV6, &message), 0);
+    }
+
+    #[test]
+    fn test_update_checksum() {
+        let mut header = [0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0xb8, 0x61,
+                          0xc0, 0xa8, 0x00, 0x01, 0xc0, 0xa8, 0x00, 0xc7];
+        // decremented TTL
+        let checksum = update_checksum(0xb861, &header[8..10], &[0x3f, 0x11]);
+        header[8] = 0x3f;
+        header[10..12].copy_from_slice(&checksum.to_be_bytes());
+        assert!(verify(&header));
+        // rewritten destination address
+        let checksum = update_checksum(checksum, &header[16..20], &[10, 0, 0, 1]);
+        header[16..20].copy_from_slice(&[10, 0, 0, 1]);
+        header[10..12].copy_from_slice(&checksum.to_be_bytes());
+        assert!(verify(&header));
+        header[10..12].copy_from_slice(&[0, 0]);
+        assert_eq!(internet_checksum(&header), checksum);
+    }
+}
@@ -0,0 +1,297 @@
+use std::{
+    io::{Error, Result},
+    os::unix::io::RawFd,
+    time::Duration,
+};
+
+use super::{sockaddr::socket_address_from, timestamping::Timestamps};
+
+/// Size of the control message buffer used by `recv_msg`.
+const CONTROL_BUFFER_SIZE: usize = 512;
+
+/// A control (ancillary) message received with a datagram.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum ControlMessage {
+    /// SCM_TIMESTAMPING: software and raw hardware timestamps
+    Timestamping(Timestamps),
+
+    /// IP_RECVERR / IPV6_RECVERR: struct sock_extended_err of the error queue
+    ExtendedError(ExtendedError),
+
+    /// SO_RXQ_OVFL: number of datagrams the socket dropped so far because its receive queue was
+    /// full
+    DropCount(u32),
+
+    /// any other control message
+    Other { level: libc::c_int, msg_type: libc::c_int, data: Vec<u8> },
+}
+
+/// Extended error (struct sock_extended_err) read from the error queue of a socket.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ExtendedError {
+    pub errno: u32,
+    /// SO_EE_ORIGIN_*
+    pub origin: u8,
+    pub ee_type: u8,
+    pub code: u8,
+    pub info: u32,
+    pub data: u32,
+    /// node which reported the error (SO_EE_OFFENDER), e.g. the sender of an ICMP error
+    pub offender: Option<std::net::SocketAddr>,
+}
+
+/// Length of struct sock_extended_err without the offender address.
+const SOCK_EXTENDED_ERR_LEN: usize = 16;
+
+/// A datagram received by `recv_msg`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ReceivedMessage {
+    pub len: usize,
+    pub address: Option<std::net::SocketAddr>,
+    pub flags: libc::c_int,
+    pub control: Vec<ControlMessage>,
+}
+
+/// Receives a datagram with its control messages via recvmsg(2).
+pub(crate) fn recv_msg(fd: RawFd, buf: &mut [u8], flags: libc::c_int) -> Result<ReceivedMessage> {
+    let mut address: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    // u64 elements for the alignment of struct cmsghdr
+    let mut control = [0_u64; CONTROL_BUFFER_SIZE / 8];
+    let mut iov = libc::iovec { iov_base: buf.as_mut_ptr() as *mut libc::c_void, iov_len: buf.len() };
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_name = std::ptr::addr_of_mut!(address) as *mut libc::c_void;
+    msg.msg_namelen = std::mem::size_of_val(&address) as libc::socklen_t;
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = CONTROL_BUFFER_SIZE as _;
+
+    let len = loop {
+        let len = unsafe { libc::recvmsg(fd, &mut msg, flags) };
+        if len >= 0 {
+            break len as usize;
+        }
+        let err = Error::last_os_error();
+        if err.kind() != std::io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    };
+    let address = if msg.msg_namelen > 0 {
+        socket_address_from(std::ptr::addr_of!(address) as *const libc::sockaddr).ok()
+    } else {
+        None
+    };
+    Ok(ReceivedMessage { len, address, flags: msg.msg_flags, control: parse_control_messages(&msg) })
+}
+
+/// Sends a datagram with control messages (level, type, data) via sendmsg(2), to the destination
+/// or for None to the connected peer.
+pub(crate) fn send_msg(fd: RawFd, buf: &[u8], destination: Option<&std::net::SocketAddr>,
+                       control: &[(libc::c_int, libc::c_int, &[u8])], flags: libc::c_int) -> Result<usize> {
+    let destination = destination.map(|destination| socket2::SockAddr::from(*destination));
+    let space: usize = control.iter().map(|(_, _, data)| unsafe { libc::CMSG_SPACE(data.len() as u32) } as usize).sum();
+    if space > CONTROL_BUFFER_SIZE {
+        return Err(Error::new(std::io::ErrorKind::InvalidInput, "control messages too large"));
+    }
+    let mut buffer = [0_u64; CONTROL_BUFFER_SIZE / 8];
+    let mut iov = libc::iovec { iov_base: buf.as_ptr() as *mut libc::c_void, iov_len: buf.len() };
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    if let Some(destination) = &destination {
+        msg.msg_name = destination.as_ptr() as *mut libc::c_void;
+        msg.msg_namelen = destination.len();
+    }
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    if space > 0 {
+        msg.msg_control = buffer.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = space as _;
+        let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+        for (level, msg_type, data) in control {
+            unsafe {
+                (*cmsg).cmsg_level = *level;
+                (*cmsg).cmsg_type = *msg_type;
+                (*cmsg).cmsg_len = libc::CMSG_LEN(data.len() as u32) as _;
+                std::ptr::copy_nonoverlapping(data.as_ptr(), libc::CMSG_DATA(cmsg), data.len());
+                cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+            }
+        }
+    }
+    loop {
+        let len = unsafe { libc::sendmsg(fd, &msg, flags) };
+        if len >= 0 {
+            return Ok(len as usize);
+        }
+        let err = Error::last_os_error();
+        if err.kind() != std::io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+/// Receives up to `bufs.len()` datagrams with their control messages via recvmmsg(2), blocks
+/// until the first one unless flags contain MSG_DONTWAIT.
+pub(crate) fn recv_mmsg(fd: RawFd, bufs: &mut [&mut [u8]], flags: libc::c_int) -> Result<Vec<ReceivedMessage>> {
+    let count = bufs.len();
+    let mut addresses: Vec<libc::sockaddr_storage> = vec![unsafe { std::mem::zeroed() }; count];
+    let mut controls = vec![[0_u64; CONTROL_BUFFER_SIZE / 8]; count];
+    let mut iovs: Vec<libc::iovec> = bufs.iter_mut()
+        .map(|buf| libc::iovec { iov_base: buf.as_mut_ptr() as *mut libc::c_void, iov_len: buf.len() })
+        .collect();
+    let mut msgs: Vec<libc::mmsghdr> = (0..count).map(|i| {
+        let mut msg: libc::mmsghdr = unsafe { std::mem::zeroed() };
+        msg.msg_hdr.msg_name = std::ptr::addr_of_mut!(addresses[i]) as *mut libc::c_void;
+        msg.msg_hdr.msg_namelen = std::mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        msg.msg_hdr.msg_iov = &mut iovs[i];
+        msg.msg_hdr.msg_iovlen = 1;
+        msg.msg_hdr.msg_control = controls[i].as_mut_ptr() as *mut libc::c_void;
+        msg.msg_hdr.msg_controllen = CONTROL_BUFFER_SIZE as _;
+        msg
+    }).collect();
+
+    let received = loop {
+        let received = unsafe {
+            libc::recvmmsg(fd, msgs.as_mut_ptr(), count as libc::c_uint, flags | libc::MSG_WAITFORONE, std::ptr::null_mut())
+        };
+        if received >= 0 {
+            break received as usize;
+        }
+        let err = Error::last_os_error();
+        if err.kind() != std::io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    };
+    Ok(msgs[..received].iter().zip(&addresses).map(|(msg, address)| {
+        let address = if msg.msg_hdr.msg_namelen > 0 {
+            socket_address_from(address as *const libc::sockaddr_storage as *const libc::sockaddr).ok()
+        } else {
+            None
+        };
+        ReceivedMessage {
+            len: msg.msg_len as usize, address, flags: msg.msg_hdr.msg_flags, control: parse_control_messages(&msg.msg_hdr),
+        }
+    }).collect())
+}
+
+/// Parses the control messages of a received msghdr.
+fn parse_control_messages(msg: &libc::msghdr) -> Vec<ControlMessage> {
+    let mut messages = Vec::new();
+    let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(msg) };
+    while !cmsg.is_null() {
+        let header = unsafe { &*cmsg };
+        let data_len = header.cmsg_len as usize - unsafe { libc::CMSG_LEN(0) } as usize;
+        let data = unsafe { std::slice::from_raw_parts(libc::CMSG_DATA(cmsg), data_len) };
+        messages.push(parse_control_message(header.cmsg_level, header.cmsg_type, data));
+        cmsg = unsafe { libc::CMSG_NXTHDR(msg, cmsg) };
+    }
+    messages
+}
+
+fn parse_control_message(level: libc::c_int, msg_type: libc::c_int, data: &[u8]) -> ControlMessage {
+    match (level, msg_type) {
+        (libc::SOL_SOCKET, libc::SO_TIMESTAMPING) if data.len() >= 3 * std::mem::size_of::<libc::timespec>() => {
+            let ts: [libc::timespec; 3] = unsafe { std::ptr::read_unaligned(data.as_ptr() as *const _) };
+            ControlMessage::Timestamping(Timestamps { software: duration_from(&ts[0]), hardware: duration_from(&ts[2]) })
+        },
+        (libc::IPPROTO_IP, libc::IP_RECVERR) | (libc::IPPROTO_IPV6, libc::IPV6_RECVERR)
+            if data.len() >= SOCK_EXTENDED_ERR_LEN => ControlMessage::ExtendedError(extended_error_from(data)),
+        (libc::SOL_SOCKET, libc::SO_RXQ_OVFL) if data.len() >= 4 =>
+            ControlMessage::DropCount(u32::from_ne_bytes([data[0], data[1], data[2], data[3]])),
+        _ => ControlMessage::Other { level, msg_type, data: data.to_vec() },
+    }
+}
+
+fn extended_error_from(data: &[u8]) -> ExtendedError {
+    let u32_at = |offset: usize| u32::from_ne_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]);
+    // the offender address follows the struct, its family is AF_UNSPEC if there is none
+    let mut offender: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let offender_data = &data[SOCK_EXTENDED_ERR_LEN..];
+    let offender_len = offender_data.len().min(std::mem::size_of_val(&offender));
+    unsafe {
+        std::ptr::copy_nonoverlapping(offender_data.as_ptr(), std::ptr::addr_of_mut!(offender) as *mut u8, offender_len);
+    }
+    let offender = if offender_len >= std::mem::size_of::<libc::sa_family_t>() {
+        socket_address_from(std::ptr::addr_of!(offender) as *const libc::sockaddr).ok()
+    } else {
+        None
+    };
+    ExtendedError {
+        errno: u32_at(0), origin: data[4], ee_type: data[5], code: data[6], info: u32_at(8), data: u32_at(12), offender,
+    }
+}
+
+/// Converts a timespec into a duration, None for the zero timestamp of unavailable timestamps.
+fn duration_from(ts: &libc::timespec) -> Option<Duration> {
+    if ts.tv_sec == 0 && ts.tv_nsec == 0 {
+        None
+    } else {
+        Some(Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_parse_control_messages() {
+        let ts = [libc::timespec { tv_sec: 100, tv_nsec: 5 }, libc::timespec { tv_sec: 0, tv_nsec: 0 },
+            libc::timespec { tv_sec: 0, tv_nsec: 0 }];
+        let ts_len = std::mem::size_of_val(&ts);
+        let mut control = [0_u64; 64];
+        let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = unsafe { libc::CMSG_SPACE(ts_len as u32) + libc::CMSG_SPACE(4) } as _;
+        unsafe {
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SO_TIMESTAMPING;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(ts_len as u32) as _;
+            std::ptr::copy_nonoverlapping(ts.as_ptr() as *const u8, libc::CMSG_DATA(cmsg), ts_len);
+            let cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+            (*cmsg).cmsg_level = libc::IPPROTO_IP;
+            (*cmsg).cmsg_type = libc::IP_TTL;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(4) as _;
+            std::ptr::copy_nonoverlapping(64_i32.to_ne_bytes().as_ptr(), libc::CMSG_DATA(cmsg), 4);
+        }
+        let messages = parse_control_messages(&msg);
+        assert_eq!(messages, vec![
+            ControlMessage::Timestamping(Timestamps { software: Some(Duration::new(100, 5)), hardware: None }),
+            ControlMessage::Other { level: libc::IPPROTO_IP, msg_type: libc::IP_TTL, data: 64_i32.to_ne_bytes().to_vec() },
+        ]);
+    }
+
+    #[test]
+    fn test_extended_error() {
+        let mut data = vec![0_u8; SOCK_EXTENDED_ERR_LEN + std::mem::size_of::<libc::sockaddr_in>()];
+        data[0..4].copy_from_slice(&(libc::ECONNREFUSED as u32).to_ne_bytes());
+        data[4] = 2;
+        data[5] = 3;
+        data[6] = 3;
+        let mut offender: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+        offender.sin_family = libc::AF_INET as libc::sa_family_t;
+        offender.sin_addr.s_addr = u32::from_ne_bytes([192, 0, 2, 1]);
+        unsafe {
+            std::ptr::copy_nonoverlapping(std::ptr::addr_of!(offender) as *const u8,
+                                          data[SOCK_EXTENDED_ERR_LEN..].as_mut_ptr(), std::mem::size_of_val(&offender));
+        }
+        match parse_control_message(libc::IPPROTO_IP, libc::IP_RECVERR, &data) {
+            ControlMessage::ExtendedError(error) => {
+                assert_eq!(error.errno, libc::ECONNREFUSED as u32);
+                assert_eq!((error.origin, error.ee_type, error.code), (2, 3, 3));
+                assert_eq!(error.offender, Some("192.0.2.1:0".parse().unwrap()));
+            },
+            message => panic!("{:?}", message),
+        }
+        match parse_control_message(libc::IPPROTO_IP, libc::IP_RECVERR, &data[..SOCK_EXTENDED_ERR_LEN]) {
+            ControlMessage::ExtendedError(error) => assert_eq!(error.offender, None),
+            message => panic!("{:?}", message),
+        }
+    }
+
+    #[test]
+    fn test_drop_count() {
+        assert_eq!(parse_control_message(libc::SOL_SOCKET, libc::SO_RXQ_OVFL, &7_u32.to_ne_bytes()),
+                   ControlMessage::DropCount(7));
+    }
+}
@@ -0,0 +1,429 @@
+//! Typed messages over UDP (e.g. multicast telemetry): `TypedSender` encodes values with a
+//! `Codec` into framed datagrams and `TypedMulticastReceiver` (with the feature 'tokio-net')
+//! decodes them, dropping datagrams of other message types and undecodable ones. Serde values
+//! are sent as JSON with `JsonCodec` (feature 'json') or as bincode with `BincodeCodec` (feature
+//! 'bincode'), e.g. `TypedSender::<Telemetry>::json(socket, destination)`, other serialization
+//! crates plug in with `FnCodec`.
+//! Requires the feature 'codec'.
+//!
+//! Frame format (big endian): magic "NT", message type (u16), length of the encoded value
+//! (u32), followed by the encoded value.
+
+use std::{
+    convert::TryInto,
+    fmt,
+    io::{Error, ErrorKind, Result},
+    marker::PhantomData,
+    net::{SocketAddr, UdpSocket},
+};
+
+use super::stats::{SocketStats, StatsCounter};
+
+const MAGIC: [u8; 2] = *b"NT";
+/// Length of the frame header.
+pub const FRAME_HEADER_LEN: usize = 8;
+/// Maximum length of a UDP payload over IPv6.
+const MAX_DATAGRAM: usize = 65527;
+/// Maximum length of a UDP payload over IPv4, the IPv4 header takes 20 more bytes.
+const MAX_DATAGRAM_V4: usize = 65507;
+
+/// Encoding of values of type `T` to bytes and back.
+pub trait Codec<T> {
+
+    /// Appends the encoded value to the buffer.
+    fn encode(&self, value: &T, buffer: &mut Vec<u8>) -> Result<()>;
+
+    /// Returns the value encoded in the data.
+    fn decode(&self, data: &[u8]) -> Result<T>;
+}
+
+/// Codec of byte vectors as they are.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct RawCodec;
+
+impl Codec<Vec<u8>> for RawCodec {
+
+    fn encode(&self, value: &Vec<u8>, buffer: &mut Vec<u8>) -> Result<()> {
+        buffer.extend_from_slice(value);
+        Ok(())
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Vec<u8>> {
+        Ok(data.to_vec())
+    }
+}
+
+/// Codec of strings as UTF-8, decoding fails with InvalidData for invalid UTF-8.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Utf8Codec;
+
+impl Codec<String> for Utf8Codec {
+
+    fn encode(&self, value: &String, buffer: &mut Vec<u8>) -> Result<()> {
+        buffer.extend_from_slice(value.as_bytes());
+        Ok(())
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<String> {
+        String::from_utf8(data.to_vec()).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+}
+
+/// Codec of an encode and a decode function, e.g. of a serialization crate.
+pub struct FnCodec<E, D> {
+    encode: E,
+    decode: D,
+}
+
+impl<E, D> fmt::Debug for FnCodec<E, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("FnCodec")
+    }
+}
+
+impl<E, D> FnCodec<E, D> {
+
+    /// Creates the codec of the functions.
+    pub fn new<T>(encode: E, decode: D) -> FnCodec<E, D>
+        where E: Fn(&T, &mut Vec<u8>) -> Result<()>, D: Fn(&[u8]) -> Result<T> {
+        FnCodec { encode, decode }
+    }
+}
+
+impl<T, E, D> Codec<T> for FnCodec<E, D> where E: Fn(&T, &mut Vec<u8>) -> Result<()>, D: Fn(&[u8]) -> Result<T> {
+
+    fn encode(&self, value: &T, buffer: &mut Vec<u8>) -> Result<()> {
+        (self.encode)(value, buffer)
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<T> {
+        (self.decode)(data)
+    }
+}
+
+/// Codec of serde values as JSON (serde_json). Requires the feature 'json'.
+#[cfg(feature = "json")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct JsonCodec;
+
+#[cfg(feature = "json")]
+impl<T: serde::Serialize + serde::de::DeserializeOwned> Codec<T> for JsonCodec {
+
+    fn encode(&self, value: &T, buffer: &mut Vec<u8>) -> Result<()> {
+        serde_json::to_writer(buffer, value).map_err(|e| Error::new(ErrorKind::InvalidInput, e))
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<T> {
+        serde_json::from_slice(data).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+}
+
+/// Codec of serde values as bincode in the standard configuration (variable length integers,
+/// little endian), decoding fails with InvalidData for trailing bytes. Requires the feature
+/// 'bincode'.
+#[cfg(feature = "bincode")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BincodeCodec;
+
+#[cfg(feature = "bincode")]
+impl<T: serde::Serialize + serde::de::DeserializeOwned> Codec<T> for BincodeCodec {
+
+    fn encode(&self, value: &T, buffer: &mut Vec<u8>) -> Result<()> {
+        bincode::serde::encode_into_std_write(value, buffer, bincode::config::standard())
+            .map(|_| ())
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, e))
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<T> {
+        match bincode::serde::decode_from_slice(data, bincode::config::standard()) {
+            Ok((value, len)) if len == data.len() => Ok(value),
+            Ok(_) => Err(Error::new(ErrorKind::InvalidData, "trailing bytes after the value")),
+            Err(e) => Err(Error::new(ErrorKind::InvalidData, e)),
+        }
+    }
+}
+
+/// Returns the frame of the value. Fails with InvalidInput if it exceeds a UDP datagram over
+/// IPv6, senders to IPv4 destinations reject frames longer than 65507 bytes.
+pub fn encode_frame<T, C: Codec<T>>(codec: &C, message_type: u16, value: &T) -> Result<Vec<u8>> {
+    let mut frame = MAGIC.to_vec();
+    frame.extend_from_slice(&message_type.to_be_bytes());
+    frame.extend_from_slice(&[0; 4]);
+    codec.encode(value, &mut frame)?;
+    if frame.len() > MAX_DATAGRAM {
+        return Err(Error::new(ErrorKind::InvalidInput, "encoded message exceeds a UDP datagram"));
+    }
+    let len = (frame.len() - FRAME_HEADER_LEN) as u32;
+    frame[4..FRAME_HEADER_LEN].copy_from_slice(&len.to_be_bytes());
+    Ok(frame)
+}
+
+/// Returns the value of a frame of the message type. Fails with InvalidData for a malformed
+/// frame or one of another message type, or the error of the codec.
+pub fn decode_frame<T, C: Codec<T>>(codec: &C, message_type: u16, frame: &[u8]) -> Result<T> {
+    if frame.len() < FRAME_HEADER_LEN || frame[..2] != MAGIC {
+        return Err(Error::new(ErrorKind::InvalidData, "no message frame"));
+    }
+    if u16::from_be_bytes([frame[2], frame[3]]) != message_type {
+        return Err(Error::new(ErrorKind::InvalidData, "other message type"));
+    }
+    let len = u32::from_be_bytes(frame[4..FRAME_HEADER_LEN].try_into().unwrap_or_default()) as usize;
+    if frame.len() != FRAME_HEADER_LEN + len {
+        return Err(Error::new(ErrorKind::InvalidData, "truncated message frame"));
+    }
+    codec.decode(&frame[FRAME_HEADER_LEN..])
+}
+
+/// Sender of typed messages to a destination via a UDP socket (std::net::UdpSocket or, with
+/// the feature 'tokio-net', tokio::net::UdpSocket).
+/// ```no_run
+/// use net_utils::codec::{TypedSender, Utf8Codec};
+/// let socket = std::net::UdpSocket::bind("0.0.0.0:0").unwrap();
+/// let mut sender = TypedSender::new(socket, "239.1.2.3:5000".parse().unwrap(), Utf8Codec);
+/// sender.send(&"temperature 21.5".to_string()).unwrap();
+/// ```
+/// The codec defaults to `RawCodec`, which gives the ready codecs their constructors, e.g.
+/// `TypedSender::<Telemetry>::json(socket, destination)`.
+#[derive(Debug)]
+pub struct TypedSender<T, C = RawCodec, S = UdpSocket> {
+    socket: S,
+    destination: SocketAddr,
+    codec: C,
+    message_type: u16,
+    stats: StatsCounter,
+    value: PhantomData<fn(&T)>,
+}
+
+impl<T> TypedSender<T> {
+
+    /// Wraps the socket for messages of type 0 encoded as JSON. Requires the feature 'json'.
+    #[cfg(feature = "json")]
+    pub fn json<S>(socket: S, destination: SocketAddr) -> TypedSender<T, JsonCodec, S>
+        where T: serde::Serialize + serde::de::DeserializeOwned {
+        TypedSender::new(socket, destination, JsonCodec)
+    }
+
+    /// Wraps the socket for messages of type 0 encoded as bincode. Requires the feature 'bincode'.
+    #[cfg(feature = "bincode")]
+    pub fn bincode<S>(socket: S, destination: SocketAddr) -> TypedSender<T, BincodeCodec, S>
+        where T: serde::Serialize + serde::de::DeserializeOwned {
+        TypedSender::new(socket, destination, BincodeCodec)
+    }
+}
+
+impl<T, C: Codec<T>, S> TypedSender<T, C, S> {
+
+    /// Wraps the socket for messages of type 0.
+    pub fn new(socket: S, destination: SocketAddr, codec: C) -> TypedSender<T, C, S> {
+        TypedSender { socket, destination, codec, message_type: 0, stats: StatsCounter::new(), value: PhantomData }
+    }
+
+    /// Returns the frame of the value for the destination.
+    fn frame(&self, value: &T) -> Result<Vec<u8>> {
+        let frame = encode_frame(&self.codec, self.message_type, value)?;
+        if self.destination.is_ipv4() && frame.len() > MAX_DATAGRAM_V4 {
+            return Err(Error::new(ErrorKind::InvalidInput, "encoded message exceeds an IPv4 UDP datagram"));
+        }
+        Ok(frame)
+    }
+
+    /// Sets the message type, which distinguishes the messages of several types to the same
+    /// destination.
+    pub fn message_type(mut self, message_type: u16) -> Self {
+        self.message_type = message_type;
+        self
+    }
+
+    /// Returns the socket.
+    pub fn get_ref(&self) -> &S {
+        &self.socket
+    }
+
+    /// Returns the socket.
+    pub fn into_inner(self) -> S {
+        self.socket
+    }
+
+    /// Returns the statistics of the messages sent via the wrapper.
+    pub fn stats(&self) -> SocketStats {
+        self.stats.snapshot()
+    }
+}
+
+impl<T, C: Codec<T>> TypedSender<T, C, UdpSocket> {
+
+    /// Sends the message. Fails with InvalidInput if it exceeds a UDP datagram.
+    pub fn send(&mut self, value: &T) -> Result<()> {
+        let frame = self.frame(value)?;
+        self.socket.send_to(&frame, self.destination)?;
+        self.stats.sent(frame.len());
+        Ok(())
+    }
+}
+
+/// Requires the feature 'tokio-net'.
+#[cfg(feature = "tokio-net")]
+impl<T, C: Codec<T>> TypedSender<T, C, tokio::net::UdpSocket> {
+
+    /// Sends the message. Fails with InvalidInput if it exceeds a UDP datagram.
+    pub async fn send(&mut self, value: &T) -> Result<()> {
+        let frame = self.frame(value)?;
+        self.socket.send_to(&frame, self.destination).await?;
+        self.stats.sent(frame.len());
+        Ok(())
+    }
+}
+
+/// Receiver of typed messages on a tokio UDP socket, e.g. of `MulticastSocketBuilder::build_tokio`.
+/// Requires the feature 'tokio-net'.
+/// ```no_run
+/// # async fn run() -> std::io::Result<()> {
+/// use net_utils::{MulticastSocketBuilder, codec::{TypedMulticastReceiver, Utf8Codec}};
+/// let builder = MulticastSocketBuilder::new("239.1.2.3:5000".parse().unwrap(), "192.168.1.3".parse().unwrap());
+/// let mut receiver = TypedMulticastReceiver::new(builder.build_tokio()?, Utf8Codec);
+/// let (message, source): (String, _) = receiver.recv().await?;
+/// # Ok(())
+/// # }
+/// ```
+/// The codec defaults to `RawCodec`, which gives the ready codecs their constructors, e.g.
+/// `TypedMulticastReceiver::<Telemetry>::json(socket)`.
+#[cfg(feature = "tokio-net")]
+#[derive(Debug)]
+pub struct TypedMulticastReceiver<T, C = RawCodec> {
+    socket: tokio::net::UdpSocket,
+    codec: C,
+    message_type: u16,
+    rejected: u64,
+    stats: StatsCounter,
+    value: PhantomData<fn() -> T>,
+}
+
+#[cfg(feature = "tokio-net")]
+impl<T> TypedMulticastReceiver<T> {
+
+    /// Wraps the socket for messages of type 0 encoded as JSON. Requires the feature 'json'.
+    #[cfg(feature = "json")]
+    pub fn json(socket: tokio::net::UdpSocket) -> TypedMulticastReceiver<T, JsonCodec>
+        where T: serde::Serialize + serde::de::DeserializeOwned {
+        TypedMulticastReceiver::new(socket, JsonCodec)
+    }
+
+    /// Wraps the socket for messages of type 0 encoded as bincode. Requires the feature 'bincode'.
+    #[cfg(feature = "bincode")]
+    pub fn bincode(socket: tokio::net::UdpSocket) -> TypedMulticastReceiver<T, BincodeCodec>
+        where T: serde::Serialize + serde::de::DeserializeOwned {
+        TypedMulticastReceiver::new(socket, BincodeCodec)
+    }
+}
+
+#[cfg(feature = "tokio-net")]
+impl<T, C: Codec<T>> TypedMulticastReceiver<T, C> {
+
+    /// Wraps the socket for messages of type 0.
+    pub fn new(socket: tokio::net::UdpSocket, codec: C) -> TypedMulticastReceiver<T, C> {
+        TypedMulticastReceiver {
+            socket, codec, message_type: 0, rejected: 0, stats: StatsCounter::new(), value: PhantomData,
+        }
+    }
+
+    /// Sets the message type to receive, datagrams of other types are dropped.
+    pub fn message_type(mut self, message_type: u16) -> Self {
+        self.message_type = message_type;
+        self
+    }
+
+    /// Returns the socket.
+    pub fn get_ref(&self) -> &tokio::net::UdpSocket {
+        &self.socket
+    }
+
+    /// Returns the socket.
+    pub fn into_inner(self) -> tokio::net::UdpSocket {
+        self.socket
+    }
+
+    /// Returns the number of dropped datagrams.
+    pub fn rejected(&self) -> u64 {
+        self.rejected
+    }
+
+    /// Returns the statistics of the datagrams received via the wrapper, dropped ones included.
+    pub fn stats(&self) -> SocketStats {
+        self.stats.snapshot()
+    }
+
+    /// Receives the next message and returns it with its source.
+    pub async fn recv(&mut self) -> Result<(T, SocketAddr)> {
+        let mut buffer = vec![0_u8; 65536];
+        loop {
+            let (len, source) = self.socket.recv_from(&mut buffer).await?;
+            self.stats.received(len);
+            match decode_frame(&self.codec, self.message_type, &buffer[..len]) {
+                Ok(value) => return Ok((value, source)),
+                Err(_) => self.rejected += 1,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_frames() {
+        let frame = encode_frame(&Utf8Codec, 3, &"abc".to_string()).unwrap();
+        assert_eq!(frame, b"NT\x00\x03\x00\x00\x00\x03abc");
+        assert_eq!(decode_frame(&Utf8Codec, 3, &frame).unwrap(), "abc");
+        assert_eq!(decode_frame(&Utf8Codec, 4, &frame).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(decode_frame(&Utf8Codec, 3, &frame[..10]).unwrap_err().kind(), ErrorKind::InvalidData);
+        let invalid = encode_frame(&RawCodec, 3, &vec![0xff]).unwrap();
+        assert_eq!(decode_frame(&Utf8Codec, 3, &invalid).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(encode_frame(&RawCodec, 0, &vec![0; MAX_DATAGRAM]).unwrap_err().kind(), ErrorKind::InvalidInput);
+        // IPv6 datagrams take 20 bytes more than IPv4 ones
+        let value = vec![0; MAX_DATAGRAM - FRAME_HEADER_LEN];
+        assert_eq!(encode_frame(&RawCodec, 0, &value).unwrap().len(), MAX_DATAGRAM);
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sender = TypedSender::new(socket, "127.0.0.1:9".parse().unwrap(), RawCodec);
+        assert_eq!(sender.frame(&value).unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(sender.frame(&vec![0; MAX_DATAGRAM_V4 - FRAME_HEADER_LEN]).unwrap().len(), MAX_DATAGRAM_V4);
+
+        // a fixed layout codec of (u16, f32)
+        let codec = FnCodec::new(
+            |value: &(u16, f32), buffer: &mut Vec<u8>| {
+                buffer.extend_from_slice(&value.0.to_be_bytes());
+                buffer.extend_from_slice(&value.1.to_be_bytes());
+                Ok(())
+            },
+            |data: &[u8]| match data {
+                [a, b, c, d, e, f] => Ok((u16::from_be_bytes([*a, *b]), f32::from_be_bytes([*c, *d, *e, *f]))),
+                _ => Err(Error::new(ErrorKind::InvalidData, "length")),
+            });
+        let frame = encode_frame(&codec, 0, &(7, 21.5)).unwrap();
+        assert_eq!(decode_frame(&codec, 0, &frame).unwrap(), (7, 21.5));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json() {
+        let value = ("temperature".to_string(), 21.5);
+        let frame = encode_frame(&JsonCodec, 1, &value).unwrap();
+        assert_eq!(&frame[FRAME_HEADER_LEN..], b"[\"temperature\",21.5]");
+        assert_eq!(decode_frame::<(String, f64), _>(&JsonCodec, 1, &frame).unwrap(), value);
+        let invalid = encode_frame(&RawCodec, 1, &b"[1,".to_vec()).unwrap();
+        assert_eq!(decode_frame::<(String, f64), _>(&JsonCodec, 1, &invalid).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn test_bincode() {
+        let value = (7_u16, "eth0".to_string());
+        let frame = encode_frame(&BincodeCodec, 1, &value).unwrap();
+        assert_eq!(&frame[FRAME_HEADER_LEN..], b"\x07\x04eth0");
+        assert_eq!(decode_frame::<(u16, String), _>(&BincodeCodec, 1, &frame).unwrap(), value);
+        let trailing = encode_frame(&RawCodec, 1, &b"\x07\x04eth0\x00".to_vec()).unwrap();
+        let error = decode_frame::<(u16, String), _>(&BincodeCodec, 1, &trailing).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,119 @@
+//! IP address assignment via rtnetlink, without shelling out to `ip address`: adding an address
+//! can wait for its duplicate address detection, as binding sockets to a tentative IPv6 address
+//! fails. All functions require CAP_NET_ADMIN.
+
+use std::{
+    io::{Error, ErrorKind, Result},
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+
+use super::{IpAddressInfo, ip_address::{IFA_ADDRESS, IFA_LOCAL}, link_config::existing_link,
+            netlink::{MessageBuilder, NetlinkSocket}};
+
+/// Outcome of the duplicate address detection (DAD, RFC 4862) of an added address.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DadResult {
+    /// the address is unique (or needs no DAD, e.g. IPv4) and sockets can be bound to it
+    Usable(IpAddressInfo),
+    /// another node uses the address, it stays assigned but cannot be used
+    Failed(IpAddressInfo),
+}
+
+/// Assigns the address with the prefix length to the link. Fails with AlreadyExists if the address
+/// is already assigned. IPv6 addresses are tentative until duplicate address detection completed,
+/// see `add_address_and_wait_dad`.
+pub fn add_address(name: &str, address: IpAddr, prefix_len: u8) -> Result<()> {
+    let request = address_request(existing_link(name)?.index, address, prefix_len)?;
+    NetlinkSocket::new(libc::NETLINK_ROUTE, 0)?.request(
+        libc::RTM_NEWADDR, (libc::NLM_F_ACK | libc::NLM_F_CREATE | libc::NLM_F_EXCL) as u16, &request)?;
+    Ok(())
+}
+
+/// Removes the address with the prefix length from the link.
+pub fn delete_address(name: &str, address: IpAddr, prefix_len: u8) -> Result<()> {
+    let request = address_request(existing_link(name)?.index, address, prefix_len)?;
+    NetlinkSocket::new(libc::NETLINK_ROUTE, 0)?.request(libc::RTM_DELADDR, libc::NLM_F_ACK as u16, &request)?;
+    Ok(())
+}
+
+/// Assigns the address to the link like `add_address` and waits until its duplicate address
+/// detection completed, so that binding sockets to the address does not fail with
+/// EADDRNOTAVAIL. IPv4 addresses and addresses on links with DAD disabled are usable immediately.
+/// Fails with TimedOut if DAD does not complete in time (it does not start before the link is
+/// up) and with NotFound if the address is removed meanwhile.
+pub fn add_address_and_wait_dad(name: &str, address: IpAddr, prefix_len: u8, timeout: Duration) -> Result<DadResult> {
+    let index = existing_link(name)?.index;
+    let deadline = Instant::now() + timeout;
+    // subscribe before adding the address to not miss the notification of the completed DAD
+    let events = NetlinkSocket::new(libc::NETLINK_ROUTE, libc::RTMGRP_IPV6_IFADDR as u32)?;
+    NetlinkSocket::new(libc::NETLINK_ROUTE, 0)?.request(
+        libc::RTM_NEWADDR, (libc::NLM_F_ACK | libc::NLM_F_CREATE | libc::NLM_F_EXCL) as u16,
+        &address_request(index, address, prefix_len)?)?;
+    let mut info = assigned_address(index, address)?;
+    loop {
+        match info {
+            Some(info) if info.is_dad_failed() => return Ok(DadResult::Failed(info)),
+            Some(info) if !info.is_tentative() => return Ok(DadResult::Usable(info)),
+            Some(_) => {},
+            None => return Err(Error::new(ErrorKind::NotFound, format!("address {} was removed", address))),
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining == Duration::from_secs(0) {
+            return Err(Error::new(ErrorKind::TimedOut,
+                                  format!("duplicate address detection of {} not completed", address)));
+        }
+        events.set_read_timeout(Some(remaining))?;
+        match events.receive() {
+            Ok(messages) => if messages.iter().any(|msg| msg.msg_type == libc::RTM_NEWADDR
+                                                   || msg.msg_type == libc::RTM_DELADDR) {
+                info = assigned_address(index, address)?;
+            },
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {},
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Retrieves the kernel attributes of the address if it is assigned to the interface.
+fn assigned_address(index: u32, address: IpAddr) -> Result<Option<IpAddressInfo>> {
+    Ok(IpAddressInfo::retrieve_ip_addresses()?.into_iter().find(|info| info.index == index && info.address == address))
+}
+
+/// Returns a RTM_NEWADDR/RTM_DELADDR payload (struct ifaddrmsg with local address attributes).
+fn address_request(index: u32, address: IpAddr, prefix_len: u8) -> Result<Vec<u8>> {
+    let (family, octets, max_prefix_len) = match address {
+        IpAddr::V4(address) => (libc::AF_INET, address.octets().to_vec(), 32),
+        IpAddr::V6(address) => (libc::AF_INET6, address.octets().to_vec(), 128),
+    };
+    if prefix_len > max_prefix_len {
+        return Err(Error::new(ErrorKind::InvalidInput, format!("invalid prefix length {}", prefix_len)));
+    }
+    let mut header = [0_u8; 8];
+    header[0] = family as u8;
+    header[1] = prefix_len;
+    header[4..8].copy_from_slice(&index.to_ne_bytes());
+    Ok(MessageBuilder::new(&header)
+        .attribute(IFA_LOCAL, &octets)
+        .attribute(IFA_ADDRESS, &octets)
+        .into_payload())
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::netlink::AttributeIter;
+
+    #[test]
+    fn test_address_request() {
+        let request = address_request(3, "2001:db8::1".parse().unwrap(), 64).unwrap();
+        assert_eq!(&request[..8], &[libc::AF_INET6 as u8, 64, 0, 0, 3, 0, 0, 0][..]);
+        let attributes: Vec<_> = AttributeIter::new(&request[8..]).collect();
+        assert_eq!(attributes.len(), 2);
+        assert_eq!(attributes[0].0, IFA_LOCAL);
+        assert_eq!(attributes[0].1.len(), 16);
+        let err = address_request(3, "192.0.2.1".parse().unwrap(), 33).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+}
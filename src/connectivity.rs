@@ -0,0 +1,364 @@
+//! Connectivity check per interface, like the network indicators of desktops and phones: pings
+//! the default gateway (accepting a resolved neighbor entry if it drops pings), resolves a name
+//! via the DNS servers of the interface and optionally fetches an HTTP probe URL expected to
+//! answer 204 No Content, which captive portals intercept. `ConnectivityMonitor` repeats the
+//! checks on link, address and route changes reported by netlink and periodically.
+
+use std::{
+    collections::HashMap,
+    io::{Error, ErrorKind, Read, Result},
+    net::{IpAddr, SocketAddr, SocketAddrV6},
+    time::{Duration, Instant},
+};
+
+use socket2::{Domain, Protocol, SockAddr, Type};
+
+use super::{
+    dns::{DnsOpts, RCODE_NOERROR, RCODE_NXDOMAIN, RecordData, TYPE_A, TYPE_AAAA, query_blocking},
+    http::{Url, request_to},
+    netlink::{self, NetlinkSocket},
+    packet::{Ipv4Packet, IcmpMessage, icmp_message},
+};
+
+const ICMP_ECHO_REPLY: u8 = 0;
+const ICMP_ECHO_REQUEST: u8 = 8;
+const ICMPV6_ECHO_REQUEST: u8 = 128;
+const ICMPV6_ECHO_REPLY: u8 = 129;
+const HTTP_NO_CONTENT: u16 = 204;
+
+/// Time after a netlink notification during which further notifications are collected before
+/// the monitor checks again, as configuration changes come in bursts.
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+/// Connectivity class of an interface.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Connectivity {
+    /// no default route, the link is down or neither the gateway nor a DNS server answers
+    None,
+
+    /// the HTTP probe was answered by a captive portal (redirect or content instead of 204)
+    Portal,
+
+    /// the gateway answers, but DNS or the HTTP probe fail
+    Limited,
+
+    /// DNS works and the HTTP probe (if configured) was answered with 204
+    Full,
+}
+
+/// Options of the connectivity checks.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ConnectivityOpts {
+    /// name resolved to check DNS
+    pub dns_name: String,
+
+    /// http:// URL answering 204 No Content (e.g. "http://connectivitycheck.gstatic.com/generate_204"),
+    /// None to skip the probe, which then cannot detect captive portals
+    pub probe_url: Option<String>,
+
+    /// maximum time of each check
+    pub timeout: Duration,
+}
+
+impl Default for ConnectivityOpts {
+    fn default() -> ConnectivityOpts {
+        ConnectivityOpts { dns_name: String::from("example.com"), probe_url: None, timeout: Duration::from_secs(2) }
+    }
+}
+
+/// Results of the connectivity checks of an interface.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct InterfaceConnectivity {
+    /// interface index
+    pub index: u32,
+
+    /// interface name
+    pub name: String,
+
+    /// gateway of the default route of the interface, IPv4 preferred
+    pub gateway: Option<IpAddr>,
+
+    /// whether the gateway answered a ping or has a reachable neighbor entry
+    pub gateway_reachable: bool,
+
+    /// whether a DNS server of the interface answered the query for the name
+    pub dns_resolved: bool,
+
+    /// HTTP status of the probe, None if it was not run or failed
+    pub probe_status: Option<u16>,
+
+    /// the resulting class
+    pub connectivity: Connectivity,
+}
+
+/// A connectivity change reported by `ConnectivityMonitor`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ConnectivityChange {
+    /// interface index
+    pub index: u32,
+
+    /// interface name
+    pub name: String,
+
+    /// the previous class, None when the interface is checked the first time
+    pub previous: Option<Connectivity>,
+
+    /// the new class, `Connectivity::None` for removed interfaces
+    pub connectivity: Connectivity,
+}
+
+/// Checks the connectivity of the interface with the given name. The checks are run one after
+/// the other, each taking up to the timeout of the options. Fails with NotFound if there is no
+/// such interface and with InvalidInput for an invalid probe URL. Pinging requires
+/// net.ipv4.ping_group_range to include the group of the process or CAP_NET_RAW, without the
+/// gateway is only reachable through its neighbor entry.
+pub fn check_interface(name: &str, opts: &ConnectivityOpts) -> Result<InterfaceConnectivity> {
+    let link = netlink::links()?.into_iter().find(|link| link.name.as_deref() == Some(name))
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("no interface {}", name)))?;
+    check_link(link.index, name, link.flags, &netlink::routes()?, opts)
+}
+
+/// Checks the connectivity of all interfaces except loopback interfaces, see `check_interface`.
+pub fn check_all(opts: &ConnectivityOpts) -> Result<Vec<InterfaceConnectivity>> {
+    let routes = netlink::routes()?;
+    netlink::links()?.into_iter()
+        .filter(|link| link.flags & libc::IFF_LOOPBACK as u32 == 0)
+        .filter_map(|link| Some((link.index, link.name?, link.flags)))
+        .map(|(index, name, flags)| check_link(index, &name, flags, &routes, opts))
+        .collect()
+}
+
+/// Checks the link with the index, name and IFF_* flags.
+fn check_link(index: u32, name: &str, flags: u32, routes: &[netlink::RouteInfo], opts: &ConnectivityOpts)
+              -> Result<InterfaceConnectivity> {
+    let probe_url = opts.probe_url.as_deref().map(Url::parse).transpose()?;
+    let running = (libc::IFF_UP | libc::IFF_LOWER_UP) as u32;
+    let gateway = routes.iter()
+        .filter(|route| route.destination.is_none() && route.output_index == Some(index))
+        .filter_map(|route| route.gateway)
+        .min_by_key(|gateway| gateway.is_ipv6());
+    let mut result = InterfaceConnectivity {
+        index, name: name.to_string(), gateway, gateway_reachable: false, dns_resolved: false, probe_status: None,
+        connectivity: Connectivity::None,
+    };
+    let gateway = match gateway {
+        Some(gateway) if flags & running == running => gateway,
+        _ => return Ok(result),
+    };
+    result.gateway_reachable = ping(name, index, gateway, opts.timeout).unwrap_or(false)
+        || neighbor_reachable(index, gateway)?;
+    let dns_opts = DnsOpts { server: None, interface: Some(name.to_string()), timeout: opts.timeout };
+    let record_type = if gateway.is_ipv6() { TYPE_AAAA } else { TYPE_A };
+    result.dns_resolved = match query_blocking(&opts.dns_name, record_type, &dns_opts) {
+        Ok(response) => response.rcode() == RCODE_NOERROR || response.rcode() == RCODE_NXDOMAIN,
+        Err(_) => false,
+    };
+    if let (Some(url), true) = (&probe_url, result.dns_resolved) {
+        result.probe_status = probe(url, name, record_type, &dns_opts).ok();
+    }
+    result.connectivity = classify(result.gateway_reachable, result.dns_resolved, probe_url.is_some(),
+                                   result.probe_status);
+    Ok(result)
+}
+
+/// Classifies the results of the checks of an interface with default route.
+fn classify(gateway_reachable: bool, dns_resolved: bool, probed: bool, probe_status: Option<u16>) -> Connectivity {
+    match (dns_resolved, probe_status) {
+        (false, _) if gateway_reachable => Connectivity::Limited,
+        (false, _) => Connectivity::None,
+        (true, Some(HTTP_NO_CONTENT)) => Connectivity::Full,
+        (true, Some(_)) => Connectivity::Portal,
+        (true, None) if probed => Connectivity::Limited,
+        (true, None) => Connectivity::Full,
+    }
+}
+
+/// Sends an ICMP echo request to the gateway from the interface, returns whether it answered
+/// within the timeout. Uses an unprivileged ICMP echo socket if allowed, a raw socket otherwise.
+fn ping(interface: &str, index: u32, gateway: IpAddr, timeout: Duration) -> Result<bool> {
+    let (domain, protocol, request, reply) = match gateway {
+        IpAddr::V4(_) => (Domain::IPV4, Protocol::ICMPV4, ICMP_ECHO_REQUEST, ICMP_ECHO_REPLY),
+        IpAddr::V6(_) => (Domain::IPV6, Protocol::ICMPV6, ICMPV6_ECHO_REQUEST, ICMPV6_ECHO_REPLY),
+    };
+    let (socket, raw) = match super::sys_socket::socket(domain, Type::DGRAM, Some(protocol)) {
+        Ok(socket) => (socket, false),
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+            (super::sys_socket::socket(domain, Type::RAW, Some(protocol))?, true)
+        },
+        Err(e) => return Err(e),
+    };
+    socket.bind_device(Some(interface.as_bytes()))?;
+    // identifier (replaced by the kernel for echo sockets), sequence number and a random token
+    let mut body = [0_u8; 12];
+    super::random::random_bytes(&mut body);
+    // the kernel computes the checksum of ICMPv6 messages
+    let message = match gateway {
+        IpAddr::V4(_) => icmp_message(request, 0, &body),
+        IpAddr::V6(_) => [&[request, 0, 0, 0][..], &body].concat(),
+    };
+    let destination = match gateway {
+        IpAddr::V6(gateway) if gateway.is_unicast_link_local() => SocketAddr::V6(SocketAddrV6::new(gateway, 0, 0, index)),
+        gateway => SocketAddr::new(gateway, 0),
+    };
+    socket.send_to(&message, &SockAddr::from(destination))?;
+
+    let deadline = Instant::now() + timeout;
+    let mut buffer = [0_u8; 1500];
+    while let Some(timeout) = deadline.checked_duration_since(Instant::now()).filter(|t| !t.is_zero()) {
+        socket.set_read_timeout(Some(timeout))?;
+        let len = match (&socket).read(&mut buffer) {
+            Ok(len) => len,
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => break,
+            Err(e) => return Err(e),
+        };
+        // raw IPv4 sockets receive the IP header
+        let data = match (raw, gateway) {
+            (true, IpAddr::V4(_)) => match Ipv4Packet::new(&buffer[..len]) {
+                Some(packet) => packet.payload(),
+                None => continue,
+            },
+            _ => &buffer[..len],
+        };
+        if let Some(message) = IcmpMessage::new(data) {
+            if message.msg_type() == reply && message.body().get(2..) == Some(&body[2..]) {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Returns whether the neighbor cache has a reachable or static entry of the gateway.
+fn neighbor_reachable(index: u32, gateway: IpAddr) -> Result<bool> {
+    let states = libc::NUD_REACHABLE | libc::NUD_PERMANENT | libc::NUD_NOARP;
+    Ok(netlink::neighbors()?.iter()
+        .any(|neighbor| neighbor.index == index && neighbor.destination == Some(gateway) && neighbor.state & states != 0))
+}
+
+/// Fetches the probe URL via the interface, resolving its host with the DNS servers of the
+/// interface, and returns the HTTP status.
+fn probe(url: &Url, interface: &str, record_type: u16, dns_opts: &DnsOpts) -> Result<u16> {
+    let address = match url.host.parse::<IpAddr>() {
+        Ok(address) => address,
+        Err(_) => query_blocking(&url.host, record_type, dns_opts)?.answers.iter()
+            .find_map(|record| match record.data {
+                RecordData::A(address) => Some(IpAddr::V4(address)),
+                RecordData::Aaaa(address) => Some(IpAddr::V6(address)),
+                _ => None,
+            })
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("cannot resolve {}", url.host)))?,
+    };
+    Ok(request_to(&SocketAddr::new(address, url.port), Some(interface), "GET", url, &[], &[])?.status)
+}
+
+/// Monitor of the connectivity of all interfaces except loopback interfaces, which checks them
+/// again after link, address and route changes and periodically.
+/// ```no_run
+/// use net_utils::connectivity::{ConnectivityMonitor, ConnectivityOpts};
+/// let mut monitor = ConnectivityMonitor::new(ConnectivityOpts::default(), std::time::Duration::from_secs(60)).unwrap();
+/// loop {
+///     for change in monitor.recv().unwrap() {
+///         println!("{}: {:?}", change.name, change.connectivity);
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ConnectivityMonitor {
+    netlink: NetlinkSocket,
+    opts: ConnectivityOpts,
+    interval: Duration,
+    interfaces: HashMap<u32, InterfaceConnectivity>,
+    checked: Option<Instant>,
+}
+
+impl ConnectivityMonitor {
+
+    /// Creates the monitor checking at least once per interval. No check is run before `recv`.
+    pub fn new(opts: ConnectivityOpts, interval: Duration) -> Result<ConnectivityMonitor> {
+        if interval.is_zero() {
+            return Err(Error::new(ErrorKind::InvalidInput, "zero check interval"));
+        }
+        let groups = (libc::RTMGRP_LINK | libc::RTMGRP_IPV4_IFADDR | libc::RTMGRP_IPV6_IFADDR | libc::RTMGRP_IPV4_ROUTE
+                      | libc::RTMGRP_IPV6_ROUTE) as u32;
+        let netlink = NetlinkSocket::new(libc::NETLINK_ROUTE, groups)?;
+        Ok(ConnectivityMonitor { netlink, opts, interval, interfaces: HashMap::new(), checked: None })
+    }
+
+    /// Returns the results of the last checks, ordered by interface index.
+    pub fn interfaces(&self) -> Vec<&InterfaceConnectivity> {
+        let mut interfaces: Vec<_> = self.interfaces.values().collect();
+        interfaces.sort_by_key(|interface| interface.index);
+        interfaces
+    }
+
+    /// Returns the results of the last check of the interface with the index.
+    pub fn get(&self, index: u32) -> Option<&InterfaceConnectivity> {
+        self.interfaces.get(&index)
+    }
+
+    /// Checks all interfaces and returns the changes of their classes, which may be none. The
+    /// first call checks immediately, later calls wait for a change reported by netlink or until
+    /// the interval since the last check elapsed.
+    pub fn recv(&mut self) -> Result<Vec<ConnectivityChange>> {
+        if let Some(checked) = self.checked {
+            self.wait(checked + self.interval)?;
+        }
+        let results = check_all(&self.opts)?;
+        self.checked = Some(Instant::now());
+        let mut changes = Vec::new();
+        let mut previous = std::mem::take(&mut self.interfaces);
+        for result in results {
+            let before = previous.remove(&result.index).map(|before| before.connectivity);
+            if before != Some(result.connectivity) {
+                changes.push(ConnectivityChange { index: result.index, name: result.name.clone(), previous: before,
+                                                  connectivity: result.connectivity });
+            }
+            self.interfaces.insert(result.index, result);
+        }
+        let mut removed: Vec<_> = previous.into_values().collect();
+        removed.sort_by_key(|interface| interface.index);
+        changes.extend(removed.into_iter()
+            .filter(|interface| interface.connectivity != Connectivity::None)
+            .map(|interface| ConnectivityChange { index: interface.index, name: interface.name,
+                                                  previous: Some(interface.connectivity),
+                                                  connectivity: Connectivity::None }));
+        Ok(changes)
+    }
+
+    /// Waits until the deadline or a netlink notification and the following `SETTLE_TIME`.
+    fn wait(&self, deadline: Instant) -> Result<()> {
+        let mut settle: Option<Instant> = None;
+        loop {
+            let until = settle.map_or(deadline, |settle| settle.min(deadline));
+            let timeout = match until.checked_duration_since(Instant::now()) {
+                Some(timeout) if !timeout.is_zero() => timeout,
+                _ => return Ok(()),
+            };
+            self.netlink.set_read_timeout(Some(timeout))?;
+            match self.netlink.receive() {
+                Ok(_) => settle = settle.or_else(|| Some(Instant::now() + SETTLE_TIME)),
+                Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => return Ok(()),
+                // e.g. ENOBUFS if notifications have been lost
+                Err(_) => settle = settle.or_else(|| Some(Instant::now() + SETTLE_TIME)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify(false, false, false, None), Connectivity::None);
+        assert_eq!(classify(true, false, true, None), Connectivity::Limited);
+        // gateways may drop pings
+        assert_eq!(classify(false, true, false, None), Connectivity::Full);
+        assert_eq!(classify(true, true, true, Some(HTTP_NO_CONTENT)), Connectivity::Full);
+        assert_eq!(classify(true, true, true, Some(302)), Connectivity::Portal);
+        assert_eq!(classify(true, true, true, Some(200)), Connectivity::Portal);
+        assert_eq!(classify(true, true, true, None), Connectivity::Limited);
+    }
+}
@@ -0,0 +1,35 @@
+use std::{
+    io::Result,
+    net::SocketAddr,
+};
+
+use socket2::Socket;
+
+/// Binds the socket to the network device with the given name, so that packets are only sent and
+/// received via this interface. Uses SO_BINDTODEVICE on linux and IP_BOUND_IF/IPV6_BOUND_IF on
+/// apple systems, where the address family of `destination` selects the option.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn bind_to_device(socket: &Socket, device: &str, _destination: &SocketAddr) -> Result<()> {
+    use std::os::unix::io::AsRawFd;
+    super::sockopt::set_bytes_option(socket.as_raw_fd(), libc::SOL_SOCKET, libc::SO_BINDTODEVICE, device.as_bytes())
+}
+
+/// Binds the socket to the network device with the given name, so that packets are only sent and
+/// received via this interface. Uses SO_BINDTODEVICE on linux and IP_BOUND_IF/IPV6_BOUND_IF on
+/// apple systems, where the address family of `destination` selects the option.
+#[cfg(target_vendor = "apple")]
+pub(crate) fn bind_to_device(socket: &Socket, device: &str, destination: &SocketAddr) -> Result<()> {
+    let name = std::ffi::CString::new(device).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let index = std::num::NonZeroU32::new(unsafe { libc::if_nametoindex(name.as_ptr()) })
+        .ok_or_else(std::io::Error::last_os_error)?;
+    match destination {
+        SocketAddr::V4(_) => socket.bind_device_by_index_v4(Some(index)),
+        SocketAddr::V6(_) => socket.bind_device_by_index_v6(Some(index)),
+    }
+}
+
+/// Binding to a device is not supported on this system, always fails with Unsupported.
+#[cfg(not(any(target_os = "linux", target_os = "android", target_vendor = "apple")))]
+pub(crate) fn bind_to_device(_socket: &Socket, device: &str, _destination: &SocketAddr) -> Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, format!("binding to device {} is not supported on this platform", device)))
+}
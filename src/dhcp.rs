@@ -0,0 +1,503 @@
+//! Minimal DHCPv4 (RFC 2131) client: obtains a lease with DISCOVER/OFFER/REQUEST/ACK on an interface,
+//! and reads the current leases of DHCP clients running on the system.
+
+use std::{
+    convert::TryInto,
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, Ipv4Addr, SocketAddrV4, UdpSocket},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use socket2::{Domain, Protocol, SockAddr, Type};
+
+use super::{IpInterface, ioctl::hardware_address, random::random_bytes};
+
+/// UDP port of DHCP servers.
+pub const DHCP_SERVER_PORT: u16 = 67;
+/// UDP port of DHCP clients.
+pub const DHCP_CLIENT_PORT: u16 = 68;
+
+/// Default timeout for obtaining a lease.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Interval between retransmissions of requests without response.
+const RETRANSMIT_INTERVAL: Duration = Duration::from_secs(2);
+
+const BOOTREQUEST: u8 = 1;
+const BOOTREPLY: u8 = 2;
+const HTYPE_ETHERNET: u8 = 1;
+const FLAG_BROADCAST: u16 = 0x8000;
+const MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+const FIXED_LEN: usize = 236;
+/// Minimum BOOTP message length expected by some servers.
+const MIN_LEN: usize = 300;
+
+const DHCPDISCOVER: u8 = 1;
+const DHCPOFFER: u8 = 2;
+const DHCPREQUEST: u8 = 3;
+const DHCPACK: u8 = 5;
+const DHCPNAK: u8 = 6;
+
+const OPT_PAD: u8 = 0;
+const OPT_SUBNET_MASK: u8 = 1;
+const OPT_ROUTER: u8 = 3;
+const OPT_DNS: u8 = 6;
+const OPT_HOSTNAME: u8 = 12;
+const OPT_DOMAIN_NAME: u8 = 15;
+const OPT_REQUESTED_ADDRESS: u8 = 50;
+const OPT_LEASE_TIME: u8 = 51;
+const OPT_MESSAGE_TYPE: u8 = 53;
+const OPT_SERVER_ID: u8 = 54;
+const OPT_PARAMETER_REQUEST: u8 = 55;
+const OPT_RENEWAL_TIME: u8 = 58;
+const OPT_REBINDING_TIME: u8 = 59;
+const OPT_CLIENT_ID: u8 = 61;
+const OPT_END: u8 = 255;
+
+/// Options for obtaining a lease.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DhcpOpts {
+    /// maximum time for the complete exchange
+    pub timeout: Duration,
+
+    /// host name sent to the server (option 12)
+    pub hostname: Option<String>,
+}
+
+impl Default for DhcpOpts {
+    fn default() -> DhcpOpts {
+        DhcpOpts { timeout: DEFAULT_TIMEOUT, hostname: None }
+    }
+}
+
+/// Parameters of a lease acknowledged by a DHCP server.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DhcpLease {
+    /// assigned address
+    pub address: Ipv4Addr,
+
+    /// server identifier of the server that granted the lease
+    pub server: Ipv4Addr,
+
+    /// subnet mask of the assigned address
+    pub subnet_mask: Option<Ipv4Addr>,
+
+    /// routers on the subnet, in order of preference
+    pub routers: Vec<Ipv4Addr>,
+
+    /// DNS servers, in order of preference
+    pub dns_servers: Vec<Ipv4Addr>,
+
+    /// DNS domain name of the client
+    pub domain_name: Option<String>,
+
+    /// lease time, None for an infinite lease
+    pub lease_time: Option<Duration>,
+
+    /// time after which the lease should be renewed (T1)
+    pub renewal_time: Option<Duration>,
+
+    /// time after which the lease should be rebound with any server (T2)
+    pub rebinding_time: Option<Duration>,
+}
+
+/// Performs the DISCOVER/OFFER/REQUEST/ACK exchange on the interface and returns the lease of the
+/// first server that made an offer. The address is not configured on the interface.
+/// Requires CAP_NET_RAW (SO_BINDTODEVICE) and CAP_NET_BIND_SERVICE (port 68), and no other DHCP
+/// client may be running on the interface.
+pub fn obtain_lease(interface_name: &str, opts: &DhcpOpts) -> Result<DhcpLease> {
+    let (_, mac) = hardware_address(interface_name)?;
+    let socket = client_socket(interface_name)?;
+    let mut xid = [0_u8; 4];
+    random_bytes(&mut xid);
+    let xid = u32::from_ne_bytes(xid);
+    let deadline = Instant::now() + opts.timeout;
+
+    let discover = message(DHCPDISCOVER, xid, &mac, None, None, opts.hostname.as_deref());
+    let offer = transact(&socket, &discover, deadline, |m| m.message_type == DHCPOFFER && is_reply(m, xid, &mac))?;
+    let server = offer.server_id.ok_or_else(|| Error::new(ErrorKind::InvalidData, "DHCP offer without server id"))?;
+
+    let request = message(DHCPREQUEST, xid, &mac, Some(offer.your_address), Some(server), opts.hostname.as_deref());
+    let ack = transact(&socket, &request, deadline, |m| {
+        (m.message_type == DHCPACK || m.message_type == DHCPNAK) && is_reply(m, xid, &mac) && m.server_id == Some(server)
+    })?;
+    if ack.message_type == DHCPNAK {
+        return Err(Error::new(ErrorKind::ConnectionRefused, "DHCP request declined (DHCPNAK)"));
+    }
+    Ok(ack.lease(server))
+}
+
+fn client_socket(interface_name: &str) -> Result<UdpSocket> {
+    let socket = super::sys_socket::socket(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.set_broadcast(true)?;
+    socket.bind_device(Some(interface_name.as_bytes()))?;
+    super::sys_socket::bind(&socket, &SockAddr::from(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, DHCP_CLIENT_PORT)))?;
+    Ok(socket.into())
+}
+
+/// Broadcasts the message and retransmits it until a reply accepted by `accept` arrives or the
+/// deadline passes.
+fn transact<F: Fn(&Message) -> bool>(socket: &UdpSocket, message: &[u8], deadline: Instant, accept: F)
+                                     -> Result<Message> {
+    let mut buffer = [0_u8; 1500];
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(Error::new(ErrorKind::TimedOut, "no DHCP response"));
+        }
+        socket.send_to(message, SocketAddrV4::new(Ipv4Addr::BROADCAST, DHCP_SERVER_PORT))?;
+        let retransmit_at = deadline.min(now + RETRANSMIT_INTERVAL);
+        loop {
+            let remaining = retransmit_at.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            socket.set_read_timeout(Some(remaining))?;
+            match socket.recv_from(&mut buffer) {
+                Ok((len, _)) => match parse_message(&buffer[..len]) {
+                    Some(reply) if accept(&reply) => return Ok(reply),
+                    _ => continue,
+                },
+                Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => break,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Builds a client message, `requested` and `server` are the requested address and the server id
+/// of a DHCPREQUEST.
+fn message(message_type: u8, xid: u32, mac: &[u8; 6], requested: Option<Ipv4Addr>, server: Option<Ipv4Addr>,
+           hostname: Option<&str>) -> Vec<u8> {
+    let mut packet = vec![0_u8; FIXED_LEN];
+    packet[0] = BOOTREQUEST;
+    packet[1] = HTYPE_ETHERNET;
+    packet[2] = mac.len() as u8;
+    packet[4..8].copy_from_slice(&xid.to_be_bytes());
+    packet[10..12].copy_from_slice(&FLAG_BROADCAST.to_be_bytes());
+    packet[28..34].copy_from_slice(mac);
+    packet.extend_from_slice(&MAGIC_COOKIE);
+    packet.extend_from_slice(&[OPT_MESSAGE_TYPE, 1, message_type]);
+    let mut client_id = vec![HTYPE_ETHERNET];
+    client_id.extend_from_slice(mac);
+    push_option(&mut packet, OPT_CLIENT_ID, &client_id);
+    if let Some(requested) = requested {
+        push_option(&mut packet, OPT_REQUESTED_ADDRESS, &requested.octets());
+    }
+    if let Some(server) = server {
+        push_option(&mut packet, OPT_SERVER_ID, &server.octets());
+    }
+    if let Some(hostname) = hostname {
+        push_option(&mut packet, OPT_HOSTNAME, &hostname.as_bytes()[..hostname.len().min(255)]);
+    }
+    push_option(&mut packet, OPT_PARAMETER_REQUEST, &[OPT_SUBNET_MASK, OPT_ROUTER, OPT_DNS, OPT_DOMAIN_NAME,
+        OPT_LEASE_TIME, OPT_RENEWAL_TIME, OPT_REBINDING_TIME]);
+    packet.push(OPT_END);
+    if packet.len() < MIN_LEN {
+        packet.resize(MIN_LEN, OPT_PAD);
+    }
+    packet
+}
+
+fn push_option(packet: &mut Vec<u8>, code: u8, value: &[u8]) {
+    packet.push(code);
+    packet.push(value.len() as u8);
+    packet.extend_from_slice(value);
+}
+
+/// A parsed server reply.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Message {
+    xid: u32,
+    client_mac: [u8; 6],
+    your_address: Ipv4Addr,
+    message_type: u8,
+    server_id: Option<Ipv4Addr>,
+    subnet_mask: Option<Ipv4Addr>,
+    routers: Vec<Ipv4Addr>,
+    dns_servers: Vec<Ipv4Addr>,
+    domain_name: Option<String>,
+    lease_time: Option<u32>,
+    renewal_time: Option<u32>,
+    rebinding_time: Option<u32>,
+}
+
+impl Message {
+    fn lease(self, server: Ipv4Addr) -> DhcpLease {
+        let duration = |secs: Option<u32>| secs.filter(|s| *s != u32::MAX).map(|s| Duration::from_secs(s as u64));
+        DhcpLease {
+            address: self.your_address,
+            server,
+            subnet_mask: self.subnet_mask,
+            routers: self.routers,
+            dns_servers: self.dns_servers,
+            domain_name: self.domain_name,
+            lease_time: duration(self.lease_time),
+            renewal_time: duration(self.renewal_time),
+            rebinding_time: duration(self.rebinding_time),
+        }
+    }
+}
+
+fn is_reply(message: &Message, xid: u32, mac: &[u8; 6]) -> bool {
+    message.xid == xid && message.client_mac == *mac
+}
+
+/// Parses a BOOTREPLY message, None if the packet is not a valid DHCP reply.
+fn parse_message(packet: &[u8]) -> Option<Message> {
+    if packet.len() < FIXED_LEN + 4 || packet[0] != BOOTREPLY || packet[FIXED_LEN..FIXED_LEN + 4] != MAGIC_COOKIE {
+        return None;
+    }
+    let mut message = Message {
+        xid: u32::from_be_bytes(packet[4..8].try_into().unwrap()),
+        client_mac: packet[28..34].try_into().unwrap(),
+        your_address: ipv4(&packet[16..20])?,
+        message_type: 0,
+        server_id: None,
+        subnet_mask: None,
+        routers: Vec::new(),
+        dns_servers: Vec::new(),
+        domain_name: None,
+        lease_time: None,
+        renewal_time: None,
+        rebinding_time: None,
+    };
+    let mut options = &packet[FIXED_LEN + 4..];
+    while let Some((&code, rest)) = options.split_first() {
+        match code {
+            OPT_PAD => { options = rest; continue; },
+            OPT_END => break,
+            _ => {},
+        }
+        let (&len, rest) = rest.split_first()?;
+        let value = rest.get(..len as usize)?;
+        options = &rest[len as usize..];
+        match code {
+            OPT_MESSAGE_TYPE => message.message_type = *value.first()?,
+            OPT_SERVER_ID => message.server_id = ipv4(value),
+            OPT_SUBNET_MASK => message.subnet_mask = ipv4(value),
+            OPT_ROUTER => message.routers = value.chunks_exact(4).filter_map(ipv4).collect(),
+            OPT_DNS => message.dns_servers = value.chunks_exact(4).filter_map(ipv4).collect(),
+            OPT_DOMAIN_NAME => message.domain_name = Some(String::from_utf8_lossy(value).trim_end_matches('\0').to_string()),
+            OPT_LEASE_TIME => message.lease_time = u32_from(value),
+            OPT_RENEWAL_TIME => message.renewal_time = u32_from(value),
+            OPT_REBINDING_TIME => message.rebinding_time = u32_from(value),
+            _ => {},
+        }
+    }
+    if message.message_type == 0 { None } else { Some(message) }
+}
+
+fn ipv4(value: &[u8]) -> Option<Ipv4Addr> {
+    Some(Ipv4Addr::from(u32::from_be_bytes(value.try_into().ok()?)))
+}
+
+fn u32_from(value: &[u8]) -> Option<u32> {
+    Some(u32::from_be_bytes(value.try_into().ok()?))
+}
+
+/// Directory of the lease files of systemd-networkd, named by interface index.
+pub const NETWORKD_LEASE_DIR: &str = "/run/systemd/netif/leases";
+
+/// Directories searched for ISC dhclient lease files (*.leases, *.lease).
+pub const DHCLIENT_LEASE_DIRS: [&str; 3] = ["/var/lib/dhcp", "/var/lib/dhclient", "/var/lib/NetworkManager"];
+
+/// Origin of the lease information.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LeaseSource {
+    /// lease file of systemd-networkd
+    Networkd(PathBuf),
+
+    /// lease file of ISC dhclient (or NetworkManager using it)
+    Dhclient(PathBuf),
+
+    /// only the finite kernel address lifetime, the DHCP client is unknown
+    AddressLifetime,
+}
+
+/// Information about the current DHCP lease of an interface address.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LeaseInfo {
+    /// leased address
+    pub address: Ipv4Addr,
+
+    /// server identifier of the server that granted the lease
+    pub server: Option<Ipv4Addr>,
+
+    /// subnet mask of the leased address
+    pub subnet_mask: Option<Ipv4Addr>,
+
+    /// routers on the subnet
+    pub routers: Vec<Ipv4Addr>,
+
+    /// DNS servers
+    pub dns_servers: Vec<Ipv4Addr>,
+
+    /// total lease time, None if unknown or infinite
+    pub lease_time: Option<Duration>,
+
+    /// remaining valid lifetime of the address as maintained by the kernel
+    pub remaining: Option<Duration>,
+
+    /// origin of the information
+    pub source: LeaseSource,
+}
+
+impl IpInterface {
+
+    /// Reads the DHCP lease of this IPv4 interface configuration from the lease files of
+    /// systemd-networkd or dhclient. Without a lease file a finite kernel address lifetime is taken
+    /// as sign of a lease. Returns Ok(None) for IPv6 and statically configured addresses.
+    pub fn dhcp_lease(&self) -> Result<Option<LeaseInfo>> {
+        let address = match self.address.ip() {
+            IpAddr::V4(address) => address,
+            IpAddr::V6(_) => return Ok(None),
+        };
+        let remaining = self.address_info()?.filter(|info| !info.is_permanent()).and_then(|info| info.valid_lifetime);
+        let dhclient_dirs: Vec<&Path> = DHCLIENT_LEASE_DIRS.iter().map(Path::new).collect();
+        let lease = read_lease_files(&self.name, self.index, address, Path::new(NETWORKD_LEASE_DIR), &dhclient_dirs);
+        Ok(match (lease, remaining) {
+            (Some(lease), _) => Some(LeaseInfo { remaining, ..lease }),
+            (None, Some(remaining)) => Some(LeaseInfo {
+                address, server: None, subnet_mask: None, routers: Vec::new(), dns_servers: Vec::new(),
+                lease_time: None, remaining: Some(remaining), source: LeaseSource::AddressLifetime,
+            }),
+            (None, None) => None,
+        })
+    }
+}
+
+/// Searches the networkd and dhclient lease files for a lease of the address on the interface.
+fn read_lease_files(name: &str, index: u32, address: Ipv4Addr, networkd_dir: &Path, dhclient_dirs: &[&Path])
+                    -> Option<LeaseInfo> {
+    let path = networkd_dir.join(index.to_string());
+    if let Ok(text) = std::fs::read_to_string(&path) {
+        if let Some(lease) = parse_networkd_lease(&text, path).filter(|l| l.address == address) {
+            return Some(lease);
+        }
+    }
+    let mut files: Vec<PathBuf> = dhclient_dirs.iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|e| e == "leases" || e == "lease"))
+        .collect();
+    files.sort();
+    files.into_iter().find_map(|path| {
+        let text = std::fs::read_to_string(&path).ok()?;
+        parse_dhclient_leases(&text, name, address, path)
+    })
+}
+
+/// Parses a systemd-networkd lease file (KEY=VALUE lines).
+fn parse_networkd_lease(text: &str, path: PathBuf) -> Option<LeaseInfo> {
+    let value = |key: &str| text.lines().find_map(|l| l.strip_prefix(key)?.strip_prefix('='));
+    let addresses = |key: &str| value(key).map_or_else(Vec::new, |v| v.split_whitespace().filter_map(|a| a.parse().ok()).collect());
+    Some(LeaseInfo {
+        address: value("ADDRESS")?.parse().ok()?,
+        server: value("SERVER_ADDRESS").and_then(|v| v.parse().ok()),
+        subnet_mask: value("NETMASK").and_then(|v| v.parse().ok()),
+        routers: addresses("ROUTER"),
+        dns_servers: addresses("DNS"),
+        lease_time: value("LIFETIME").and_then(|v| v.parse().ok()).map(Duration::from_secs),
+        remaining: None,
+        source: LeaseSource::Networkd(path),
+    })
+}
+
+/// Parses a dhclient lease file and returns the last lease of the address on the interface.
+fn parse_dhclient_leases(text: &str, name: &str, address: Ipv4Addr, path: PathBuf) -> Option<LeaseInfo> {
+    let interface = format!("\"{}\"", name);
+    text.split("lease {").skip(1).filter_map(|block| {
+        let block = block.split('}').next()?;
+        let statements: Vec<Vec<&str>> = block.split(';')
+            .map(|s| s.split_whitespace().collect::<Vec<&str>>())
+            .filter(|s| !s.is_empty())
+            .collect();
+        let value = |key: &[&str]| statements.iter()
+            .find(|s| s.len() > key.len() && s[..key.len()] == *key)
+            .map(|s| s[key.len()..].join(" "));
+        let addresses = |key: &[&str]| value(key)
+            .map_or_else(Vec::new, |v| v.split(',').filter_map(|a| a.trim().parse().ok()).collect());
+        if value(&["interface"]).is_some_and(|i| i != interface) {
+            return None;
+        }
+        Some(LeaseInfo {
+            address: value(&["fixed-address"])?.parse().ok().filter(|a| *a == address)?,
+            server: value(&["option", "dhcp-server-identifier"]).and_then(|v| v.parse().ok()),
+            subnet_mask: value(&["option", "subnet-mask"]).and_then(|v| v.parse().ok()),
+            routers: addresses(&["option", "routers"]),
+            dns_servers: addresses(&["option", "domain-name-servers"]),
+            lease_time: value(&["option", "dhcp-lease-time"]).and_then(|v| v.parse().ok()).map(Duration::from_secs),
+            remaining: None,
+            source: LeaseSource::Dhclient(path.clone()),
+        })
+    }).last()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_messages() {
+        let mac = [0x02, 0, 0, 0, 0, 1];
+        let request = message(DHCPREQUEST, 0x1234, &mac, Some(Ipv4Addr::new(192, 168, 1, 50)),
+                              Some(Ipv4Addr::new(192, 168, 1, 1)), Some("host"));
+        assert_eq!(request.len(), MIN_LEN);
+        assert_eq!(&request[0..12], &[1, 1, 6, 0, 0, 0, 0x12, 0x34, 0, 0, 0x80, 0]);
+        assert_eq!(&request[FIXED_LEN..FIXED_LEN + 7], &[99, 130, 83, 99, 53, 1, 3]);
+        assert!(request.windows(6).any(|w| w == [50, 4, 192, 168, 1, 50]));
+        assert!(request.windows(6).any(|w| w == [54, 4, 192, 168, 1, 1]));
+
+        let mut ack = request.clone();
+        ack[0] = BOOTREPLY;
+        ack[16..20].copy_from_slice(&[192, 168, 1, 50]);
+        ack.truncate(FIXED_LEN + 4);
+        ack.extend_from_slice(&[53, 1, 5, 54, 4, 192, 168, 1, 1, 0, 1, 4, 255, 255, 255, 0,
+            3, 8, 192, 168, 1, 1, 192, 168, 1, 2, 6, 4, 9, 9, 9, 9, 15, 3, b'l', b'a', b'n',
+            51, 4, 0, 0, 0x0e, 0x10, 58, 4, 0, 0, 0x07, 0x08, 59, 4, 0xff, 0xff, 0xff, 0xff, 255]);
+        let reply = parse_message(&ack).unwrap();
+        assert!(is_reply(&reply, 0x1234, &mac));
+        assert_eq!(reply.message_type, DHCPACK);
+        assert_eq!(reply.lease(Ipv4Addr::new(192, 168, 1, 1)), DhcpLease {
+            address: Ipv4Addr::new(192, 168, 1, 50),
+            server: Ipv4Addr::new(192, 168, 1, 1),
+            subnet_mask: Some(Ipv4Addr::new(255, 255, 255, 0)),
+            routers: vec![Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 1, 2)],
+            dns_servers: vec![Ipv4Addr::new(9, 9, 9, 9)],
+            domain_name: Some("lan".to_string()),
+            lease_time: Some(Duration::from_secs(3600)),
+            renewal_time: Some(Duration::from_secs(1800)),
+            rebinding_time: None,
+        });
+        assert!(parse_message(&request).is_none());
+    }
+
+    #[test]
+    fn test_parse_lease_files() {
+        let networkd = "# This is private data. Do not parse.\nADDRESS=192.168.1.50\nNETMASK=255.255.255.0\n\
+            ROUTER=192.168.1.1\nSERVER_ADDRESS=192.168.1.1\nT1=1800\nT2=3150\nLIFETIME=3600\nDNS=9.9.9.9 1.1.1.1\n";
+        let lease = parse_networkd_lease(networkd, PathBuf::from("/run/systemd/netif/leases/2")).unwrap();
+        assert_eq!(lease.address, Ipv4Addr::new(192, 168, 1, 50));
+        assert_eq!(lease.server, Some(Ipv4Addr::new(192, 168, 1, 1)));
+        assert_eq!(lease.subnet_mask, Some(Ipv4Addr::new(255, 255, 255, 0)));
+        assert_eq!(lease.dns_servers, vec![Ipv4Addr::new(9, 9, 9, 9), Ipv4Addr::new(1, 1, 1, 1)]);
+        assert_eq!(lease.lease_time, Some(Duration::from_secs(3600)));
+
+        let dhclient = "lease {\n  interface \"eth0\";\n  fixed-address 10.0.0.5;\n  option dhcp-lease-time 600;\n}\n\
+            lease {\n  interface \"wlan0\";\n  fixed-address 10.0.0.5;\n}\n\
+            lease {\n  interface \"eth0\";\n  fixed-address 10.0.0.5;\n  option subnet-mask 255.0.0.0;\n  \
+            option routers 10.0.0.1;\n  option dhcp-lease-time 86400;\n  option domain-name-servers 10.0.0.2,10.0.0.3;\n  \
+            option dhcp-server-identifier 10.0.0.1;\n  renew 2 2024/01/02 03:04:05;\n}\n";
+        let lease = parse_dhclient_leases(dhclient, "eth0", Ipv4Addr::new(10, 0, 0, 5), PathBuf::new()).unwrap();
+        assert_eq!(lease.lease_time, Some(Duration::from_secs(86400)));
+        assert_eq!(lease.routers, vec![Ipv4Addr::new(10, 0, 0, 1)]);
+        assert_eq!(lease.dns_servers, vec![Ipv4Addr::new(10, 0, 0, 2), Ipv4Addr::new(10, 0, 0, 3)]);
+        assert_eq!(lease.server, Some(Ipv4Addr::new(10, 0, 0, 1)));
+        assert!(parse_dhclient_leases(dhclient, "eth1", Ipv4Addr::new(10, 0, 0, 5), PathBuf::new()).is_none());
+        assert!(parse_dhclient_leases(dhclient, "eth0", Ipv4Addr::new(10, 0, 0, 6), PathBuf::new()).is_none());
+    }
+}
@@ -0,0 +1,814 @@
+//! DNS helpers: discovery of the system's resolvers per interface, DNS messages and queries
+//! pinned to a server and interface.
+
+use std::{
+    convert::{TryFrom, TryInto},
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::{Duration, Instant},
+};
+#[cfg(unix)]
+use std::path::Path;
+
+/// Resolver configuration file of the C library.
+#[cfg(unix)]
+pub const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+/// Directory of the per-link state files of systemd-resolved, named by interface index.
+#[cfg(target_os = "linux")]
+pub const RESOLVED_LINK_DIR: &str = "/run/systemd/resolve/netif";
+
+/// Resolver configuration of systemd-resolved listing its upstream servers instead of the stub.
+#[cfg(target_os = "linux")]
+pub const RESOLVED_RESOLV_CONF: &str = "/run/systemd/resolve/resolv.conf";
+
+/// Addresses of the local stub resolver of systemd-resolved.
+#[cfg(unix)]
+const RESOLVED_STUB_ADDRESSES: [IpAddr; 2] = [
+    IpAddr::V4(Ipv4Addr::new(127, 0, 0, 53)),
+    IpAddr::V4(Ipv4Addr::new(127, 0, 0, 54)),
+];
+
+/// Where a resolver was configured.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ResolverSource {
+    /// nameserver entry of /etc/resolv.conf
+    ResolvConf,
+
+    /// per-link DNS server of systemd-resolved
+    SystemdResolved,
+
+    /// RDNSS option of a Router Advertisement
+    RouterAdvertisement,
+}
+
+/// A DNS resolver (recursive DNS server) of the system.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Resolver {
+    /// address of the DNS server
+    pub address: IpAddr,
+
+    /// index of the interface the server belongs to, None for global servers
+    pub interface_index: Option<u32>,
+
+    /// where the server was configured
+    pub source: ResolverSource,
+}
+
+/// Returns the system's resolvers: the per-link servers of systemd-resolved (linux), its
+/// global upstream servers (of /run/systemd/resolve/resolv.conf, besides the per-link ones)
+/// and the nameservers of /etc/resolv.conf. The stub address of systemd-resolved is left out if
+/// servers of systemd-resolved are known. Duplicates are removed, the order of each source is
+/// kept.
+#[cfg(unix)]
+pub fn system_resolvers() -> Result<Vec<Resolver>> {
+    #[cfg(target_os = "linux")]
+    return resolvers_from(Path::new(RESOLVED_LINK_DIR), Path::new(RESOLVED_RESOLV_CONF), Path::new(RESOLV_CONF));
+    #[cfg(not(target_os = "linux"))]
+    Ok(merge(Vec::new(), read_resolv_conf(Path::new(RESOLV_CONF))?))
+}
+
+/// Returns the system's resolvers like `system_resolvers` with the RDNSS servers of the Router
+/// Advertisements (e.g. received by an `ra::RaListener`) added per interface.
+#[cfg(target_os = "linux")]
+pub fn system_resolvers_with_advertisements(advertisements: &[super::ra::RouterAdvertisement]) -> Result<Vec<Resolver>> {
+    let mut resolvers = system_resolvers()?;
+    for ra in advertisements {
+        add_router_advertisement(&mut resolvers, ra);
+    }
+    Ok(resolvers)
+}
+
+/// Adds the RDNSS servers of a Router Advertisement to a resolver list (e.g. of
+/// `system_resolvers`), skipping servers already in the list for the same interface and the
+/// servers the advertisement withdraws (lifetime zero).
+#[cfg(target_os = "linux")]
+pub fn add_router_advertisement(resolvers: &mut Vec<Resolver>, ra: &super::ra::RouterAdvertisement) {
+    let advertised = ra.dns_servers.iter()
+        .filter(|rdnss| rdnss.lifetime != Some(std::time::Duration::ZERO))
+        .flat_map(|rdnss| rdnss.servers.iter())
+        .map(|server| Resolver { address: IpAddr::V6(*server), interface_index: Some(ra.interface_index),
+            source: ResolverSource::RouterAdvertisement });
+    for resolver in advertised {
+        if !resolvers.iter().any(|r| r.address == resolver.address && r.interface_index == resolver.interface_index) {
+            resolvers.push(resolver);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn resolvers_from(link_dir: &Path, resolved_conf: &Path, resolv_conf: &Path) -> Result<Vec<Resolver>> {
+    let mut resolved = resolved_link_resolvers(link_dir);
+    // the resolv.conf of systemd-resolved lists the per-link servers as well
+    let global: Vec<Resolver> = read_resolv_conf(resolved_conf)?.into_iter()
+        .filter(|server| !resolved.iter().any(|link| link.address == server.address))
+        .map(|server| Resolver { source: ResolverSource::SystemdResolved, ..server })
+        .collect();
+    resolved.extend(global);
+    Ok(merge(resolved, read_resolv_conf(resolv_conf)?))
+}
+
+/// Returns the nameservers of a resolv.conf file, none if it does not exist.
+#[cfg(unix)]
+fn read_resolv_conf(path: &Path) -> Result<Vec<Resolver>> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => Ok(parse_resolv_conf(&text)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Returns the resolvers for the interface: its own servers, or the global servers if the
+/// interface has none.
+pub fn resolvers_for_interface(resolvers: &[Resolver], interface_index: u32) -> Vec<Resolver> {
+    let own: Vec<Resolver> = resolvers.iter().filter(|r| r.interface_index == Some(interface_index)).cloned().collect();
+    if own.is_empty() {
+        resolvers.iter().filter(|r| r.interface_index.is_none()).cloned().collect()
+    } else {
+        own
+    }
+}
+
+#[cfg(unix)]
+fn merge(resolved: Vec<Resolver>, resolv_conf: Vec<Resolver>) -> Vec<Resolver> {
+    let skip_stub = !resolved.is_empty();
+    let mut resolvers: Vec<Resolver> = Vec::new();
+    for resolver in resolved.into_iter().chain(resolv_conf) {
+        if skip_stub && RESOLVED_STUB_ADDRESSES.contains(&resolver.address) {
+            continue;
+        }
+        if !resolvers.iter().any(|r| r.address == resolver.address && r.interface_index == resolver.interface_index) {
+            resolvers.push(resolver);
+        }
+    }
+    resolvers
+}
+
+/// Parses the nameserver entries of resolv.conf, link-local servers may carry a zone
+/// ("fe80::1%eth0").
+#[cfg(unix)]
+fn parse_resolv_conf(text: &str) -> Vec<Resolver> {
+    text.lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            if words.next()? != "nameserver" {
+                return None;
+            }
+            let value = words.next()?;
+            let (address, zone) = match value.split_once('%') {
+                Some((address, zone)) => (address, Some(zone)),
+                None => (value, None),
+            };
+            Some(Resolver { address: address.parse().ok()?, interface_index: zone.and_then(zone_index),
+                source: ResolverSource::ResolvConf })
+        })
+        .collect()
+}
+
+/// Returns the interface index of an IPv6 zone, given as interface name or index.
+#[cfg(unix)]
+fn zone_index(zone: &str) -> Option<u32> {
+    if let Ok(index) = zone.parse() {
+        return Some(index);
+    }
+    let name = std::ffi::CString::new(zone).ok()?;
+    match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+        0 => None,
+        index => Some(index),
+    }
+}
+
+/// Reads the SERVERS of all link state files of systemd-resolved.
+#[cfg(target_os = "linux")]
+fn resolved_link_resolvers(dir: &Path) -> Vec<Resolver> {
+    let mut links: Vec<(u32, String)> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|e| e.ok())
+            .filter_map(|e| Some((e.file_name().to_str()?.parse().ok()?, std::fs::read_to_string(e.path()).ok()?)))
+            .collect(),
+        Err(_) => return Vec::new(),
+    };
+    links.sort();
+    links.iter().flat_map(|(index, text)| parse_resolved_link(text, *index)).collect()
+}
+
+/// Parses the SERVERS line of a link state file of systemd-resolved, servers may be given with
+/// port and server name ("1.1.1.1:853#cloudflare-dns.com", "[2606:4700::1111]:853").
+#[cfg(target_os = "linux")]
+fn parse_resolved_link(text: &str, index: u32) -> Vec<Resolver> {
+    let servers = text.lines().find_map(|l| l.strip_prefix("SERVERS=")).unwrap_or("");
+    servers.split_whitespace()
+        .filter_map(|server| {
+            let server = server.split('#').next()?;
+            let address = server.parse::<IpAddr>().ok()
+                .or_else(|| server.parse::<std::net::SocketAddr>().ok().map(|a| a.ip()))?;
+            Some(Resolver { address, interface_index: Some(index), source: ResolverSource::SystemdResolved })
+        })
+        .collect()
+}
+
+/// Well known DNS port.
+pub const DNS_PORT: u16 = 53;
+
+/// Default timeout for a query.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Record type of IPv4 addresses.
+pub const TYPE_A: u16 = 1;
+/// Record type of name servers.
+pub const TYPE_NS: u16 = 2;
+/// Record type of canonical names.
+pub const TYPE_CNAME: u16 = 5;
+/// Record type of zone authorities.
+pub const TYPE_SOA: u16 = 6;
+/// Record type of domain name pointers (reverse lookup).
+pub const TYPE_PTR: u16 = 12;
+/// Record type of mail exchangers.
+pub const TYPE_MX: u16 = 15;
+/// Record type of text strings.
+pub const TYPE_TXT: u16 = 16;
+/// Record type of IPv6 addresses.
+pub const TYPE_AAAA: u16 = 28;
+/// Record type of service locations.
+pub const TYPE_SRV: u16 = 33;
+/// Query type matching all record types.
+pub const TYPE_ANY: u16 = 255;
+
+/// Internet class.
+pub const CLASS_IN: u16 = 1;
+
+/// Response code: no error.
+pub const RCODE_NOERROR: u8 = 0;
+/// Response code: the server failed to process the query.
+pub const RCODE_SERVFAIL: u8 = 2;
+/// Response code: the name does not exist.
+pub const RCODE_NXDOMAIN: u8 = 3;
+/// Response code: the server refused the query.
+pub const RCODE_REFUSED: u8 = 5;
+
+/// Header flag: the message is a response (QR).
+pub const FLAG_RESPONSE: u16 = 0x8000;
+/// Header flag: the answer is authoritative (AA).
+pub const FLAG_AUTHORITATIVE: u16 = 0x0400;
+/// Header flag: the message was truncated (TC).
+pub const FLAG_TRUNCATED: u16 = 0x0200;
+/// Header flag: recursion desired (RD).
+pub const FLAG_RECURSION_DESIRED: u16 = 0x0100;
+
+/// Interval between retransmissions of a query without response.
+const RETRANSMIT_INTERVAL: Duration = Duration::from_secs(1);
+/// Maximum number of compression pointers followed in a name.
+const MAX_POINTERS: usize = 16;
+const HEADER_LEN: usize = 12;
+
+/// Options for a DNS query.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DnsOpts {
+    /// server to query, None for the first system resolver of the interface (unix)
+    pub server: Option<SocketAddr>,
+
+    /// name of the network device the query is sent from (linux, android and apple systems)
+    pub interface: Option<String>,
+
+    /// maximum time to wait for the response, the query is retransmitted every second
+    pub timeout: Duration,
+}
+
+impl Default for DnsOpts {
+    fn default() -> DnsOpts {
+        DnsOpts { server: None, interface: None, timeout: DEFAULT_TIMEOUT }
+    }
+}
+
+/// Question section entry of a DNS message.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DnsQuestion {
+    /// queried name without trailing dot
+    pub name: String,
+
+    /// queried record type (TYPE_*)
+    pub record_type: u16,
+
+    /// queried class (CLASS_IN)
+    pub class: u16,
+}
+
+/// Data of a resource record.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RecordData {
+    /// IPv4 address (A)
+    A(Ipv4Addr),
+
+    /// IPv6 address (AAAA)
+    Aaaa(Ipv6Addr),
+
+    /// canonical name (CNAME)
+    Cname(String),
+
+    /// name server (NS)
+    Ns(String),
+
+    /// domain name pointer (PTR)
+    Ptr(String),
+
+    /// mail exchanger (MX)
+    Mx { preference: u16, exchange: String },
+
+    /// text strings (TXT)
+    Txt(Vec<Vec<u8>>),
+
+    /// service location (SRV)
+    Srv { priority: u16, weight: u16, port: u16, target: String },
+
+    /// raw data of other record types
+    Other(Vec<u8>),
+}
+
+/// Resource record of a DNS message.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DnsRecord {
+    /// owner name without trailing dot
+    pub name: String,
+
+    /// record type (TYPE_*)
+    pub record_type: u16,
+
+    /// record class (CLASS_IN), for mDNS with the cache flush bit
+    pub class: u16,
+
+    /// time to live in seconds
+    pub ttl: u32,
+
+    /// record data
+    pub data: RecordData,
+}
+
+/// A DNS message (RFC 1035), used for queries and responses.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct DnsMessage {
+    /// message id matching responses to queries
+    pub id: u16,
+
+    /// header flags (FLAG_*), opcode and response code
+    pub flags: u16,
+
+    /// question section
+    pub questions: Vec<DnsQuestion>,
+
+    /// answer section
+    pub answers: Vec<DnsRecord>,
+
+    /// authority section
+    pub authorities: Vec<DnsRecord>,
+
+    /// additional section
+    pub additionals: Vec<DnsRecord>,
+}
+
+impl DnsMessage {
+
+    /// Creates a query with recursion desired for the name and record type.
+    pub fn query(id: u16, name: &str, record_type: u16) -> DnsMessage {
+        DnsMessage {
+            id,
+            flags: FLAG_RECURSION_DESIRED,
+            questions: vec![DnsQuestion { name: name.trim_end_matches('.').to_string(), record_type, class: CLASS_IN }],
+            ..DnsMessage::default()
+        }
+    }
+
+    /// Returns the response code (RCODE_*).
+    pub fn rcode(&self) -> u8 {
+        (self.flags & 0x000f) as u8
+    }
+
+    /// Returns whether the message is a response.
+    pub fn is_response(&self) -> bool {
+        self.flags & FLAG_RESPONSE != 0
+    }
+
+    /// Returns whether the message was truncated and has to be repeated over TCP.
+    pub fn is_truncated(&self) -> bool {
+        self.flags & FLAG_TRUNCATED != 0
+    }
+
+    /// Parses a message in wire format, None if it is malformed.
+    pub fn parse(packet: &[u8]) -> Option<DnsMessage> {
+        let u16_at = |offset: usize| Some(u16::from_be_bytes(packet.get(offset..offset + 2)?.try_into().ok()?));
+        let counts = [u16_at(4)?, u16_at(6)?, u16_at(8)?, u16_at(10)?];
+        let mut message = DnsMessage { id: u16_at(0)?, flags: u16_at(2)?, ..DnsMessage::default() };
+        let mut offset = HEADER_LEN;
+        for _ in 0..counts[0] {
+            let (name, next) = parse_name(packet, offset)?;
+            message.questions.push(DnsQuestion { name, record_type: u16_at(next)?, class: u16_at(next + 2)? });
+            offset = next + 4;
+        }
+        for (section, count) in counts[1..].iter().enumerate() {
+            for _ in 0..*count {
+                let (record, next) = parse_record(packet, offset)?;
+                match section {
+                    0 => message.answers.push(record),
+                    1 => message.authorities.push(record),
+                    _ => message.additionals.push(record),
+                }
+                offset = next;
+            }
+        }
+        Some(message)
+    }
+
+    /// Encodes the message in wire format (without name compression).
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let count = |len: usize| u16::try_from(len).map_err(|_| Error::new(ErrorKind::InvalidInput, "too many records"));
+        let mut packet = Vec::with_capacity(512);
+        for value in [self.id, self.flags, count(self.questions.len())?, count(self.answers.len())?,
+            count(self.authorities.len())?, count(self.additionals.len())?] {
+            packet.extend_from_slice(&value.to_be_bytes());
+        }
+        for question in &self.questions {
+            encode_name(&mut packet, &question.name)?;
+            packet.extend_from_slice(&question.record_type.to_be_bytes());
+            packet.extend_from_slice(&question.class.to_be_bytes());
+        }
+        for record in self.answers.iter().chain(&self.authorities).chain(&self.additionals) {
+            encode_record(&mut packet, record)?;
+        }
+        Ok(packet)
+    }
+}
+
+/// Returns the name of the PTR record of the address in in-addr.arpa or ip6.arpa, e.g.
+/// 4.3.2.1.in-addr.arpa for 1.2.3.4.
+pub fn reverse_name(address: &IpAddr) -> String {
+    match address {
+        IpAddr::V4(address) => {
+            let o = address.octets();
+            format!("{}.{}.{}.{}.in-addr.arpa", o[3], o[2], o[1], o[0])
+        },
+        IpAddr::V6(address) => {
+            let mut name = String::with_capacity(72);
+            for octet in address.octets().iter().rev() {
+                name.push_str(&format!("{:x}.{:x}.", octet & 0x0f, octet >> 4));
+            }
+            name.push_str("ip6.arpa");
+            name
+        },
+    }
+}
+
+/// Sends a query for the name and record type to the server of `opts` and returns the response,
+/// also for response codes other than RCODE_NOERROR. With an interface the query is sent from a
+/// socket bound to it (SO_BINDTODEVICE), so split-horizon DNS of multi-homed hosts answers for
+/// the network of that interface.
+/// Requires the feature 'tokio-net' and must be called within a tokio runtime.
+#[cfg(feature = "tokio-net")]
+pub async fn query(name: &str, record_type: u16, opts: &DnsOpts) -> Result<DnsMessage> {
+    let server = server(opts)?;
+    let socket = query_socket(&server, opts)?;
+    socket.set_nonblocking(true)?;
+    let socket = tokio::net::UdpSocket::from_std(socket.into())?;
+    socket.connect(server).await?;
+
+    let request = random_query(name, record_type);
+    let packet = request.encode()?;
+    let deadline = tokio::time::Instant::now() + opts.timeout;
+    let mut buffer = [0_u8; 4096];
+    loop {
+        let now = tokio::time::Instant::now();
+        if now >= deadline {
+            return Err(Error::new(ErrorKind::TimedOut, "no DNS response"));
+        }
+        socket.send(&packet).await?;
+        let retransmit_at = deadline.min(now + RETRANSMIT_INTERVAL);
+        while let Ok(received) = tokio::time::timeout_at(retransmit_at, socket.recv(&mut buffer)).await {
+            let len = received?;
+            match DnsMessage::parse(&buffer[..len]) {
+                Some(response) if is_response_to(&response, &request) => return Ok(response),
+                _ => continue,
+            }
+        }
+    }
+}
+
+/// Sends a query like `query`, blocking the calling thread instead of requiring a tokio runtime.
+pub fn query_blocking(name: &str, record_type: u16, opts: &DnsOpts) -> Result<DnsMessage> {
+    let server = server(opts)?;
+    let socket: std::net::UdpSocket = query_socket(&server, opts)?.into();
+    socket.connect(server)?;
+
+    let request = random_query(name, record_type);
+    let packet = request.encode()?;
+    let deadline = Instant::now() + opts.timeout;
+    let mut buffer = [0_u8; 4096];
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(Error::new(ErrorKind::TimedOut, "no DNS response"));
+        }
+        socket.send(&packet)?;
+        let retransmit_at = deadline.min(now + RETRANSMIT_INTERVAL);
+        while let Some(timeout) = retransmit_at.checked_duration_since(Instant::now()).filter(|t| !t.is_zero()) {
+            socket.set_read_timeout(Some(timeout))?;
+            match socket.recv(&mut buffer) {
+                Ok(len) => match DnsMessage::parse(&buffer[..len]) {
+                    Some(response) if is_response_to(&response, &request) => return Ok(response),
+                    _ => continue,
+                },
+                Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => break,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Returns the server of the options or the default server of their interface.
+fn server(opts: &DnsOpts) -> Result<SocketAddr> {
+    match opts.server {
+        Some(server) => Ok(server),
+        None => default_server(opts.interface.as_deref()),
+    }
+}
+
+/// Creates the UDP socket of a query to the server, bound to the interface of the options.
+fn query_socket(server: &SocketAddr, opts: &DnsOpts) -> Result<socket2::Socket> {
+    let socket = super::sys_socket::socket(socket2::Domain::for_address(*server), socket2::Type::DGRAM,
+                                      Some(socket2::Protocol::UDP))?;
+    if let Some(interface) = &opts.interface {
+        super::device::bind_to_device(&socket, interface, server)?;
+    }
+    let local: SocketAddr = match server {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    super::sys_socket::bind(&socket, &local.into())?;
+    Ok(socket)
+}
+
+/// Creates a query with a random id.
+fn random_query(name: &str, record_type: u16) -> DnsMessage {
+    let mut id = [0_u8; 2];
+    super::random::random_bytes(&mut id);
+    DnsMessage::query(u16::from_ne_bytes(id), name, record_type)
+}
+
+/// Returns whether the message is the response to the query.
+fn is_response_to(response: &DnsMessage, request: &DnsMessage) -> bool {
+    response.is_response() && response.id == request.id && response.questions.len() == 1
+        && response.questions[0].name.eq_ignore_ascii_case(&request.questions[0].name)
+}
+
+/// Returns the first system resolver of the interface, or the first global resolver.
+#[cfg(unix)]
+fn default_server(interface: Option<&str>) -> Result<SocketAddr> {
+    let resolvers = system_resolvers()?;
+    let resolvers = match interface.and_then(zone_index) {
+        Some(index) => resolvers_for_interface(&resolvers, index),
+        None => resolvers,
+    };
+    let resolver = resolvers.first().ok_or_else(|| Error::new(ErrorKind::NotFound, "no DNS server configured"))?;
+    Ok(match (resolver.address, resolver.interface_index) {
+        (IpAddr::V6(address), Some(index)) => std::net::SocketAddrV6::new(address, DNS_PORT, 0, index).into(),
+        (address, _) => SocketAddr::new(address, DNS_PORT),
+    })
+}
+
+/// The system resolvers are not known on this platform, a server has to be given.
+#[cfg(not(unix))]
+fn default_server(_interface: Option<&str>) -> Result<SocketAddr> {
+    Err(Error::new(ErrorKind::InvalidInput, "no DNS server given"))
+}
+
+/// Parses a possibly compressed name at the offset, returns the name and the offset behind it.
+fn parse_name(packet: &[u8], mut offset: usize) -> Option<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut end = None;
+    let mut pointers = 0;
+    loop {
+        let len = *packet.get(offset)? as usize;
+        match len {
+            0 => break,
+            l if l & 0xc0 == 0xc0 => {
+                pointers += 1;
+                if pointers > MAX_POINTERS {
+                    return None;
+                }
+                end.get_or_insert(offset + 2);
+                offset = ((l & 0x3f) << 8) | *packet.get(offset + 1)? as usize;
+            },
+            l if l < 64 => {
+                labels.push(String::from_utf8_lossy(packet.get(offset + 1..offset + 1 + l)?).into_owned());
+                offset += 1 + l;
+            },
+            _ => return None,
+        }
+    }
+    Some((labels.join("."), end.unwrap_or(offset + 1)))
+}
+
+fn parse_record(packet: &[u8], offset: usize) -> Option<(DnsRecord, usize)> {
+    let (name, offset) = parse_name(packet, offset)?;
+    let fixed = packet.get(offset..offset + 10)?;
+    let record_type = u16::from_be_bytes([fixed[0], fixed[1]]);
+    let class = u16::from_be_bytes([fixed[2], fixed[3]]);
+    let ttl = u32::from_be_bytes(fixed[4..8].try_into().ok()?);
+    let len = u16::from_be_bytes([fixed[8], fixed[9]]) as usize;
+    let start = offset + 10;
+    let rdata = packet.get(start..start + len)?;
+    let name_at = |o: usize| parse_name(packet, start + o).map(|(n, _)| n);
+    let u16_at = |o: usize| rdata.get(o..o + 2).map(|b| u16::from_be_bytes([b[0], b[1]]));
+    let data = match record_type {
+        TYPE_A if len == 4 => RecordData::A(Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3])),
+        TYPE_AAAA if len == 16 => RecordData::Aaaa(Ipv6Addr::from(u128::from_be_bytes(rdata.try_into().ok()?))),
+        TYPE_CNAME => RecordData::Cname(name_at(0)?),
+        TYPE_NS => RecordData::Ns(name_at(0)?),
+        TYPE_PTR => RecordData::Ptr(name_at(0)?),
+        TYPE_MX => RecordData::Mx { preference: u16_at(0)?, exchange: name_at(2)? },
+        TYPE_SRV => RecordData::Srv { priority: u16_at(0)?, weight: u16_at(2)?, port: u16_at(4)?, target: name_at(6)? },
+        TYPE_TXT => {
+            let mut strings = Vec::new();
+            let mut rest = rdata;
+            while let Some((&l, tail)) = rest.split_first() {
+                strings.push(tail.get(..l as usize)?.to_vec());
+                rest = &tail[l as usize..];
+            }
+            RecordData::Txt(strings)
+        },
+        _ => RecordData::Other(rdata.to_vec()),
+    };
+    Some((DnsRecord { name, record_type, class, ttl, data }, start + len))
+}
+
+fn encode_name(packet: &mut Vec<u8>, name: &str) -> Result<()> {
+    let name = name.trim_end_matches('.');
+    if name.len() > 253 {
+        return Err(Error::new(ErrorKind::InvalidInput, "DNS name too long"));
+    }
+    for label in name.split('.').filter(|l| !name.is_empty() || !l.is_empty()) {
+        if label.is_empty() || label.len() > 63 {
+            return Err(Error::new(ErrorKind::InvalidInput, format!("invalid DNS name {}", name)));
+        }
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+    Ok(())
+}
+
+fn encode_record(packet: &mut Vec<u8>, record: &DnsRecord) -> Result<()> {
+    encode_name(packet, &record.name)?;
+    packet.extend_from_slice(&record.record_type.to_be_bytes());
+    packet.extend_from_slice(&record.class.to_be_bytes());
+    packet.extend_from_slice(&record.ttl.to_be_bytes());
+    let length_offset = packet.len();
+    packet.extend_from_slice(&[0, 0]);
+    match &record.data {
+        RecordData::A(address) => packet.extend_from_slice(&address.octets()),
+        RecordData::Aaaa(address) => packet.extend_from_slice(&address.octets()),
+        RecordData::Cname(name) | RecordData::Ns(name) | RecordData::Ptr(name) => encode_name(packet, name)?,
+        RecordData::Mx { preference, exchange } => {
+            packet.extend_from_slice(&preference.to_be_bytes());
+            encode_name(packet, exchange)?;
+        },
+        RecordData::Srv { priority, weight, port, target } => {
+            for value in [priority, weight, port] {
+                packet.extend_from_slice(&value.to_be_bytes());
+            }
+            encode_name(packet, target)?;
+        },
+        RecordData::Txt(strings) => for string in strings {
+            let string = &string[..string.len().min(255)];
+            packet.push(string.len() as u8);
+            packet.extend_from_slice(string);
+        },
+        RecordData::Other(data) => packet.extend_from_slice(data),
+    }
+    let len = u16::try_from(packet.len() - length_offset - 2)
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "record data too long"))?;
+    packet[length_offset..length_offset + 2].copy_from_slice(&len.to_be_bytes());
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_dns_message() {
+        let query = DnsMessage::query(0x1234, "example.com.", TYPE_A);
+        let packet = query.encode().unwrap();
+        assert_eq!(&packet[..12], &[0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(&packet[12..], b"\x07example\x03com\x00\x00\x01\x00\x01");
+        assert_eq!(DnsMessage::parse(&packet).unwrap(), query);
+
+        let mut response = DnsMessage { flags: FLAG_RESPONSE | FLAG_RECURSION_DESIRED | 0x80, ..query.clone() };
+        response.answers = vec![
+            DnsRecord { name: "example.com".to_string(), record_type: TYPE_CNAME, class: CLASS_IN, ttl: 60,
+                data: RecordData::Cname("www.example.net".to_string()) },
+            DnsRecord { name: "www.example.net".to_string(), record_type: TYPE_A, class: CLASS_IN, ttl: 300,
+                data: RecordData::A(Ipv4Addr::new(192, 0, 2, 1)) },
+            DnsRecord { name: "example.com".to_string(), record_type: TYPE_SRV, class: CLASS_IN, ttl: 300,
+                data: RecordData::Srv { priority: 1, weight: 2, port: 443, target: "a.example.com".to_string() } },
+            DnsRecord { name: "example.com".to_string(), record_type: TYPE_TXT, class: CLASS_IN, ttl: 300,
+                data: RecordData::Txt(vec![b"v=1".to_vec(), Vec::new()]) },
+        ];
+        response.flags |= RCODE_NXDOMAIN as u16;
+        let parsed = DnsMessage::parse(&response.encode().unwrap()).unwrap();
+        assert_eq!(parsed, response);
+        assert!(parsed.is_response() && !parsed.is_truncated());
+        assert_eq!(parsed.rcode(), RCODE_NXDOMAIN);
+        assert!(DnsMessage::query(1, "a..b", TYPE_A).encode().is_err());
+    }
+
+    #[test]
+    fn test_reverse_name() {
+        assert_eq!(reverse_name(&"192.0.2.1".parse().unwrap()), "1.2.0.192.in-addr.arpa");
+        assert_eq!(reverse_name(&"2001:db8::567:89ab".parse().unwrap()),
+                   "b.a.9.8.7.6.5.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa");
+    }
+
+    #[test]
+    fn test_compressed_names() {
+        // answer with the owner name as pointer to the question and a compressed MX exchange
+        let mut packet = vec![0, 1, 0x81, 0x80, 0, 1, 0, 1, 0, 0, 0, 0];
+        packet.extend_from_slice(b"\x07example\x03com\x00\x00\x0f\x00\x01");
+        packet.extend_from_slice(&[0xc0, 12, 0, 15, 0, 1, 0, 0, 0, 60, 0, 7, 0, 10, 2, b'm', b'x', 0xc0, 12]);
+        let message = DnsMessage::parse(&packet).unwrap();
+        assert_eq!(message.answers[0].name, "example.com");
+        assert_eq!(message.answers[0].data, RecordData::Mx { preference: 10, exchange: "mx.example.com".to_string() });
+        // pointer loop
+        let last = packet.len() - 1;
+        packet[last] = (last - 1) as u8;
+        assert!(DnsMessage::parse(&packet).is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_resolv_conf() {
+        let text = "# generated\nsearch lan\nnameserver 127.0.0.53\nnameserver 9.9.9.9 \n\
+            nameserver fe80::1%1\noptions edns0\n";
+        let resolvers = parse_resolv_conf(text);
+        assert_eq!(resolvers.len(), 3);
+        assert_eq!(resolvers[1].address, "9.9.9.9".parse::<IpAddr>().unwrap());
+        assert_eq!(resolvers[2].interface_index, Some(1));
+        // the stub is dropped once resolved provides the per-link servers
+        let resolved = vec![Resolver { address: "9.9.9.9".parse().unwrap(), interface_index: Some(2),
+            source: ResolverSource::SystemdResolved }];
+        let merged = merge(resolved, resolvers.clone());
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged[0].source, ResolverSource::SystemdResolved);
+        assert_eq!(resolvers_for_interface(&merged, 2).len(), 1);
+        assert_eq!(resolvers_for_interface(&merged, 5), vec![merged[1].clone()]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_resolvers_from_files() {
+        let dir = std::env::temp_dir().join(format!("net-utils-dns-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("netif")).unwrap();
+        std::fs::write(dir.join("netif/3"), "SERVERS=192.168.1.1 fd00::1\n").unwrap();
+        std::fs::write(dir.join("netif/lo"), "SERVERS=10.0.0.1\n").unwrap();
+        std::fs::write(dir.join("resolved.conf"), "nameserver 192.168.1.1\nnameserver 9.9.9.9\n").unwrap();
+        std::fs::write(dir.join("resolv.conf"), "nameserver 127.0.0.53\nnameserver 9.9.9.9\nnameserver 8.8.8.8\n").unwrap();
+        let resolvers = resolvers_from(&dir.join("netif"), &dir.join("resolved.conf"), &dir.join("resolv.conf")).unwrap();
+        let without_resolved = resolvers_from(&dir.join("none"), &dir.join("none.conf"), &dir.join("resolv.conf")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let summary: Vec<(String, Option<u32>, ResolverSource)> = resolvers.iter()
+            .map(|r| (r.address.to_string(), r.interface_index, r.source)).collect();
+        assert_eq!(summary, vec![
+            ("192.168.1.1".to_string(), Some(3), ResolverSource::SystemdResolved),
+            ("fd00::1".to_string(), Some(3), ResolverSource::SystemdResolved),
+            ("9.9.9.9".to_string(), None, ResolverSource::SystemdResolved),
+            ("8.8.8.8".to_string(), None, ResolverSource::ResolvConf),
+        ]);
+        // without systemd-resolved the stub stays
+        assert_eq!(without_resolved.len(), 3);
+        assert_eq!(without_resolved[0].address, IpAddr::V4(Ipv4Addr::new(127, 0, 0, 53)));
+
+        let mut merged = resolvers;
+        let advertisement = |lifetime| crate::ra::RouterAdvertisement {
+            interface_index: 3, router: "fe80::1".parse().unwrap(), hop_limit: 64, managed: false, other_config: false,
+            router_lifetime: Duration::from_secs(1800), reachable_time: Duration::ZERO, retrans_timer: Duration::ZERO,
+            source_link_address: None, mtu: None, prefixes: Vec::new(),
+            dns_servers: vec![crate::ra::RecursiveDnsServers {
+                servers: vec!["fd00::1".parse().unwrap(), "fd00::53".parse().unwrap()], lifetime,
+            }],
+            search_domains: Vec::new(),
+        };
+        add_router_advertisement(&mut merged, &advertisement(Some(Duration::from_secs(600))));
+        add_router_advertisement(&mut merged, &advertisement(Some(Duration::ZERO)));
+        assert_eq!(merged.len(), 5);
+        assert_eq!((merged[4].address, merged[4].source), ("fd00::53".parse().unwrap(), ResolverSource::RouterAdvertisement));
+        assert_eq!(resolvers_for_interface(&merged, 3).len(), 3);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_resolved_link() {
+        let text = "# This is private data. Do not parse.\nLLMNR=yes\nMDNS=no\n\
+            SERVERS=192.168.1.1 1.1.1.1:853#cloudflare-dns.com [2606:4700::1111]:853 fd00::1\nDOMAINS=lan\n";
+        let addresses: Vec<IpAddr> = parse_resolved_link(text, 3).into_iter().map(|r| r.address).collect();
+        assert_eq!(addresses, vec!["192.168.1.1".parse::<IpAddr>().unwrap(), "1.1.1.1".parse().unwrap(),
+            "2606:4700::1111".parse().unwrap(), "fd00::1".parse().unwrap()]);
+    }
+}
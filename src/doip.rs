@@ -0,0 +1,424 @@
+//! Diagnostics over IP (ISO 13400-2): reception of vehicle announcements, vehicle identification
+//! requests and TCP connections to DoIP entities with routing activation.
+
+use std::{
+    convert::TryInto,
+    io::{Error, ErrorKind, Read, Result, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6, TcpStream, UdpSocket},
+    time::Duration,
+};
+
+use super::tcp::ConnectOpts;
+
+/// Port of DoIP discovery (UDP) and diagnostic connections (TCP).
+pub const DOIP_PORT: u16 = 13400;
+
+/// Protocol version of ISO 13400-2:2012.
+pub const PROTOCOL_VERSION_2012: u8 = 0x02;
+
+/// Protocol version of ISO 13400-2:2019.
+pub const PROTOCOL_VERSION_2019: u8 = 0x03;
+
+/// Protocol version of vehicle identification requests accepted by entities of all versions.
+pub const PROTOCOL_VERSION_DEFAULT: u8 = 0xff;
+
+/// Payload type of a generic negative acknowledge.
+pub const TYPE_GENERIC_NACK: u16 = 0x0000;
+/// Payload type of a vehicle identification request.
+pub const TYPE_VEHICLE_IDENTIFICATION_REQUEST: u16 = 0x0001;
+/// Payload type of a vehicle announcement or vehicle identification response.
+pub const TYPE_VEHICLE_ANNOUNCEMENT: u16 = 0x0004;
+/// Payload type of a routing activation request.
+pub const TYPE_ROUTING_ACTIVATION_REQUEST: u16 = 0x0005;
+/// Payload type of a routing activation response.
+pub const TYPE_ROUTING_ACTIVATION_RESPONSE: u16 = 0x0006;
+/// Payload type of an alive check request.
+pub const TYPE_ALIVE_CHECK_REQUEST: u16 = 0x0007;
+/// Payload type of an alive check response.
+pub const TYPE_ALIVE_CHECK_RESPONSE: u16 = 0x0008;
+/// Payload type of a diagnostic message.
+pub const TYPE_DIAGNOSTIC_MESSAGE: u16 = 0x8001;
+/// Payload type of a diagnostic message positive acknowledge.
+pub const TYPE_DIAGNOSTIC_ACK: u16 = 0x8002;
+/// Payload type of a diagnostic message negative acknowledge.
+pub const TYPE_DIAGNOSTIC_NACK: u16 = 0x8003;
+
+/// Default routing activation type.
+pub const ACTIVATION_DEFAULT: u8 = 0x00;
+/// Routing activation type required by WWH-OBD (ISO 27145).
+pub const ACTIVATION_WWH_OBD: u8 = 0x01;
+/// Routing activation type of central security.
+pub const ACTIVATION_CENTRAL_SECURITY: u8 = 0xe0;
+
+/// Routing activation response code: routing successfully activated.
+pub const ROUTING_SUCCESS: u8 = 0x10;
+/// Routing activation response code: activated, confirmation required.
+pub const ROUTING_CONFIRMATION_REQUIRED: u8 = 0x11;
+
+/// Size of the generic DoIP header.
+pub const HEADER_LEN: usize = 8;
+
+/// Largest payload accepted on diagnostic connections.
+const MAX_PAYLOAD_LEN: usize = 1 << 20;
+
+/// A DoIP message: the generic header and the payload.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DoipMessage {
+    /// protocol version of the header
+    pub protocol_version: u8,
+
+    /// payload type (TYPE_*)
+    pub payload_type: u16,
+
+    /// payload of the type
+    pub payload: Vec<u8>,
+}
+
+impl DoipMessage {
+
+    /// Parses a message at the start of the data, returns it and its length. None if the header
+    /// is invalid or the data is shorter than the message.
+    pub fn parse(data: &[u8]) -> Option<(DoipMessage, usize)> {
+        let (protocol_version, payload_type, len) = parse_header(data.get(..HEADER_LEN)?.try_into().ok()?)?;
+        let payload = data.get(HEADER_LEN..HEADER_LEN + len)?.to_vec();
+        Some((DoipMessage { protocol_version, payload_type, payload }, HEADER_LEN + len))
+    }
+
+    /// Returns the message in wire format.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut message = vec![self.protocol_version, !self.protocol_version];
+        message.extend_from_slice(&self.payload_type.to_be_bytes());
+        message.extend_from_slice(&(self.payload.len() as u32).to_be_bytes());
+        message.extend_from_slice(&self.payload);
+        message
+    }
+}
+
+/// Returns version, payload type and payload length of a generic header, None if the inverse
+/// version does not match.
+fn parse_header(header: &[u8; HEADER_LEN]) -> Option<(u8, u16, usize)> {
+    if header[0] != !header[1] {
+        return None;
+    }
+    let len = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    Some((header[0], u16::from_be_bytes([header[2], header[3]]), len))
+}
+
+/// A vehicle announcement or vehicle identification response of a DoIP entity.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VehicleAnnouncement {
+    /// vehicle identification number (17 ASCII characters)
+    pub vin: String,
+
+    /// logical address of the entity
+    pub logical_address: u16,
+
+    /// entity identification, typically a MAC address
+    pub eid: [u8; 6],
+
+    /// group identification of the vehicle
+    pub gid: [u8; 6],
+
+    /// further action required: 0x00 none, 0x10 routing activation for central security
+    pub further_action: u8,
+
+    /// VIN/GID synchronization status (0x00 synchronized, 0x10 incomplete) if present
+    pub sync_status: Option<u8>,
+}
+
+impl VehicleAnnouncement {
+
+    /// Parses the payload of a TYPE_VEHICLE_ANNOUNCEMENT message.
+    pub fn parse(payload: &[u8]) -> Option<VehicleAnnouncement> {
+        if payload.len() != 32 && payload.len() != 33 {
+            return None;
+        }
+        Some(VehicleAnnouncement {
+            vin: String::from_utf8(payload[..17].to_vec()).ok()?,
+            logical_address: u16::from_be_bytes([payload[17], payload[18]]),
+            eid: payload[19..25].try_into().ok()?,
+            gid: payload[25..31].try_into().ok()?,
+            further_action: payload[31],
+            sync_status: payload.get(32).copied(),
+        })
+    }
+
+    /// Returns the payload of the announcement, fails if the VIN has not 17 bytes.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        if self.vin.len() != 17 {
+            return Err(Error::new(ErrorKind::InvalidInput, "VIN must have 17 characters"));
+        }
+        let mut payload = self.vin.as_bytes().to_vec();
+        payload.extend_from_slice(&self.logical_address.to_be_bytes());
+        payload.extend_from_slice(&self.eid);
+        payload.extend_from_slice(&self.gid);
+        payload.push(self.further_action);
+        payload.extend(self.sync_status);
+        Ok(payload)
+    }
+}
+
+/// UDP socket on the DoIP port receiving vehicle announcements and sending vehicle
+/// identification requests.
+#[derive(Debug)]
+pub struct DiscoverySocket {
+    socket: UdpSocket,
+    broadcast: SocketAddr,
+}
+
+impl DiscoverySocket {
+
+    /// Binds the DoIP port for the interface address: for IPv4 with broadcasts enabled (the
+    /// interface is only used as default for requests via `request_identification`), for IPv6
+    /// joined to the all-nodes group ff02::1 of the interface that has the address.
+    pub fn bind(interface: &IpAddr) -> Result<DiscoverySocket> {
+        match interface {
+            IpAddr::V4(_) => {
+                let socket = super::sys_socket::socket(socket2::Domain::IPV4, socket2::Type::DGRAM,
+                                                  Some(socket2::Protocol::UDP))?;
+                socket.set_reuse_address(true)?;
+                socket.set_broadcast(true)?;
+                super::sys_socket::bind(&socket, &SocketAddr::from((Ipv4Addr::UNSPECIFIED, DOIP_PORT)).into())?;
+                let broadcast = SocketAddr::from((Ipv4Addr::BROADCAST, DOIP_PORT));
+                Ok(DiscoverySocket { socket: socket.into(), broadcast })
+            },
+            IpAddr::V6(interface) => {
+                let group = SocketAddrV6::new(Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1), DOIP_PORT, 0, 0);
+                let socket = super::create_std_multicast_socket_ipv6(&group, interface)?;
+                let index = super::multicast::find_interface_index(interface)?;
+                socket2::SockRef::from(&socket).set_multicast_if_v6(index)?;
+                let broadcast = SocketAddr::V6(SocketAddrV6::new(*group.ip(), DOIP_PORT, 0, index));
+                Ok(DiscoverySocket { socket, broadcast })
+            },
+        }
+    }
+
+    /// Sets the timeout of `recv`, None to wait forever.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        self.socket.set_read_timeout(timeout)
+    }
+
+    /// Sends a vehicle identification request to the destination, or broadcasts (multicasts) it
+    /// if None. Entities answer with vehicle identification responses received by `recv`.
+    pub fn request_identification(&self, destination: Option<SocketAddr>) -> Result<()> {
+        let request = DoipMessage {
+            protocol_version: PROTOCOL_VERSION_DEFAULT,
+            payload_type: TYPE_VEHICLE_IDENTIFICATION_REQUEST,
+            payload: Vec::new(),
+        };
+        self.socket.send_to(&request.encode(), destination.unwrap_or(self.broadcast))?;
+        Ok(())
+    }
+
+    /// Receives the next vehicle announcement or identification response and the address of the
+    /// entity, other datagrams are skipped.
+    pub fn recv(&self) -> Result<(VehicleAnnouncement, SocketAddr)> {
+        let mut buffer = [0_u8; 1500];
+        loop {
+            let (len, from) = self.socket.recv_from(&mut buffer)?;
+            match DoipMessage::parse(&buffer[..len]) {
+                Some((message, _)) if message.payload_type == TYPE_VEHICLE_ANNOUNCEMENT => {
+                    if let Some(announcement) = VehicleAnnouncement::parse(&message.payload) {
+                        return Ok((announcement, from));
+                    }
+                },
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Options of a diagnostic connection.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DoipOpts {
+    /// protocol version of sent messages
+    pub protocol_version: u8,
+
+    /// routing activation type (ACTIVATION_*)
+    pub activation_type: u8,
+
+    /// time to wait for the routing activation response (A_DoIP_Ctrl)
+    pub response_timeout: Duration,
+
+    /// timeout, device and source address of the TCP connection
+    pub connect: ConnectOpts,
+}
+
+impl Default for DoipOpts {
+    fn default() -> Self {
+        DoipOpts {
+            protocol_version: PROTOCOL_VERSION_2012,
+            activation_type: ACTIVATION_DEFAULT,
+            response_timeout: Duration::from_secs(2),
+            connect: ConnectOpts { timeout: Some(Duration::from_secs(2)), ..ConnectOpts::default() },
+        }
+    }
+}
+
+/// A diagnostic message between tester and entity (or one of the ECUs behind the entity).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DiagnosticMessage {
+    /// logical address of the sender
+    pub source: u16,
+
+    /// logical address of the receiver
+    pub target: u16,
+
+    /// diagnostic data, e.g. a UDS request
+    pub data: Vec<u8>,
+}
+
+/// A TCP connection to a DoIP entity with activated routing.
+#[derive(Debug)]
+pub struct DoipConnection {
+    stream: TcpStream,
+    protocol_version: u8,
+    tester_address: u16,
+    entity_address: u16,
+}
+
+impl DoipConnection {
+
+    /// Connects to the entity and activates routing for the logical address of the tester
+    /// (0x0e00..=0x0fff for external testers). Fails with PermissionDenied if the entity denies
+    /// the activation, the response code is part of the message.
+    pub fn connect(entity: SocketAddr, tester_address: u16, opts: &DoipOpts) -> Result<DoipConnection> {
+        let stream = super::tcp::connect_via(entity, &opts.connect)?;
+        stream.set_nodelay(true)?;
+        let mut connection = DoipConnection {
+            stream,
+            protocol_version: opts.protocol_version,
+            tester_address,
+            entity_address: 0,
+        };
+        let mut payload = tester_address.to_be_bytes().to_vec();
+        payload.push(opts.activation_type);
+        payload.extend_from_slice(&[0; 4]);
+        connection.send(TYPE_ROUTING_ACTIVATION_REQUEST, payload)?;
+        connection.stream.set_read_timeout(Some(opts.response_timeout))?;
+        let response = loop {
+            let message = connection.recv()?;
+            match message.payload_type {
+                TYPE_ROUTING_ACTIVATION_RESPONSE if message.payload.len() >= 9 => break message.payload,
+                TYPE_GENERIC_NACK => {
+                    return Err(Error::new(ErrorKind::InvalidData,
+                                          format!("routing activation rejected with NACK {:?}", message.payload)));
+                },
+                _ => (),
+            }
+        };
+        connection.stream.set_read_timeout(None)?;
+        match response[4] {
+            ROUTING_SUCCESS | ROUTING_CONFIRMATION_REQUIRED => {
+                connection.entity_address = u16::from_be_bytes([response[2], response[3]]);
+                Ok(connection)
+            },
+            code => Err(Error::new(ErrorKind::PermissionDenied,
+                                   format!("routing activation denied with response code {:#04x}", code))),
+        }
+    }
+
+    /// Returns the logical address of the entity from the routing activation response.
+    pub fn entity_address(&self) -> u16 {
+        self.entity_address
+    }
+
+    /// Returns the underlying TCP stream, e.g. to set timeouts.
+    pub fn stream(&self) -> &TcpStream {
+        &self.stream
+    }
+
+    /// Sends a message with the payload type.
+    pub fn send(&mut self, payload_type: u16, payload: Vec<u8>) -> Result<()> {
+        let message = DoipMessage { protocol_version: self.protocol_version, payload_type, payload };
+        self.stream.write_all(&message.encode())
+    }
+
+    /// Sends a diagnostic message from the tester to the target address.
+    pub fn send_diagnostic(&mut self, target: u16, data: &[u8]) -> Result<()> {
+        let mut payload = self.tester_address.to_be_bytes().to_vec();
+        payload.extend_from_slice(&target.to_be_bytes());
+        payload.extend_from_slice(data);
+        self.send(TYPE_DIAGNOSTIC_MESSAGE, payload)
+    }
+
+    /// Receives the next message. Alive check requests of the entity are answered and not
+    /// returned.
+    pub fn recv(&mut self) -> Result<DoipMessage> {
+        loop {
+            let mut header = [0_u8; HEADER_LEN];
+            self.stream.read_exact(&mut header)?;
+            let (protocol_version, payload_type, len) = match parse_header(&header) {
+                Some(header) if header.2 <= MAX_PAYLOAD_LEN => header,
+                _ => return Err(Error::new(ErrorKind::InvalidData, "invalid DoIP header")),
+            };
+            let mut payload = vec![0_u8; len];
+            self.stream.read_exact(&mut payload)?;
+            if payload_type == TYPE_ALIVE_CHECK_REQUEST {
+                self.send(TYPE_ALIVE_CHECK_RESPONSE, self.tester_address.to_be_bytes().to_vec())?;
+                continue;
+            }
+            return Ok(DoipMessage { protocol_version, payload_type, payload });
+        }
+    }
+
+    /// Receives the next diagnostic message, acknowledges are skipped and negative acknowledges
+    /// fail with the code.
+    pub fn recv_diagnostic(&mut self) -> Result<DiagnosticMessage> {
+        loop {
+            let message = self.recv()?;
+            match message.payload_type {
+                TYPE_DIAGNOSTIC_MESSAGE if message.payload.len() >= 4 => {
+                    return Ok(DiagnosticMessage {
+                        source: u16::from_be_bytes([message.payload[0], message.payload[1]]),
+                        target: u16::from_be_bytes([message.payload[2], message.payload[3]]),
+                        data: message.payload[4..].to_vec(),
+                    });
+                },
+                TYPE_DIAGNOSTIC_NACK if message.payload.len() >= 5 => {
+                    return Err(Error::new(ErrorKind::InvalidData,
+                                          format!("diagnostic message NACK code {:#04x}", message.payload[4])));
+                },
+                _ => (),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_message() {
+        let message = DoipMessage {
+            protocol_version: PROTOCOL_VERSION_2012,
+            payload_type: TYPE_ALIVE_CHECK_RESPONSE,
+            payload: vec![0x0e, 0x00],
+        };
+        let encoded = message.encode();
+        assert_eq!(encoded, [0x02, 0xfd, 0x00, 0x08, 0, 0, 0, 2, 0x0e, 0x00]);
+        assert_eq!(DoipMessage::parse(&encoded), Some((message, 10)));
+        assert_eq!(DoipMessage::parse(&encoded[..9]), None);
+        assert_eq!(DoipMessage::parse(&[0x02, 0xfc, 0, 8, 0, 0, 0, 0]), None);
+    }
+
+    #[test]
+    fn test_announcement() {
+        let announcement = VehicleAnnouncement {
+            vin: "WVWZZZ1JZXW000001".to_string(),
+            logical_address: 0x1010,
+            eid: [0x02, 0, 0, 0, 0, 1],
+            gid: [0; 6],
+            further_action: 0,
+            sync_status: None,
+        };
+        let payload = announcement.encode().unwrap();
+        assert_eq!(payload.len(), 32);
+        assert_eq!(VehicleAnnouncement::parse(&payload), Some(announcement.clone()));
+        let announcement = VehicleAnnouncement { sync_status: Some(0x10), ..announcement };
+        assert_eq!(VehicleAnnouncement::parse(&announcement.encode().unwrap()), Some(announcement.clone()));
+        assert!(VehicleAnnouncement { vin: "short".to_string(), ..announcement }.encode().is_err());
+    }
+}
@@ -0,0 +1,151 @@
+//! Reading of the socket error queue (MSG_ERRQUEUE): ICMP errors of sockets with IP_RECVERR,
+//! MSG_ZEROCOPY completion notifications and transmit timestamps of SO_TIMESTAMPING, each
+//! reported as struct sock_extended_err.
+
+use std::{
+    io::{ErrorKind, Result},
+    net::SocketAddr,
+    os::unix::io::{AsFd, AsRawFd},
+};
+
+use super::{
+    cmsg::{ControlMessage, ExtendedError, recv_msg},
+    timestamping::Timestamps,
+    udp::IcmpError,
+};
+
+/// SO_EE_ORIGIN_ZEROCOPY: completion notification of MSG_ZEROCOPY sends.
+const SO_EE_ORIGIN_ZEROCOPY: u8 = 5;
+/// SO_EE_ORIGIN_TXTIME: packet dropped because its SO_TXTIME transmit time was missed.
+const SO_EE_ORIGIN_TXTIME: u8 = 6;
+/// ee_code of zerocopy notifications: the kernel copied the data instead.
+const SO_EE_CODE_ZEROCOPY_COPIED: u8 = 1;
+
+/// Transmit timestamp type: the packet was passed to the network adapter (SOF_TIMESTAMPING_TX_SOFTWARE,
+/// SOF_TIMESTAMPING_TX_HARDWARE).
+pub const SCM_TSTAMP_SND: u32 = 0;
+/// Transmit timestamp type: the packet entered the packet scheduler (SOF_TIMESTAMPING_TX_SCHED).
+pub const SCM_TSTAMP_SCHED: u32 = 1;
+/// Transmit timestamp type: all data was acknowledged by the TCP peer (SOF_TIMESTAMPING_TX_ACK).
+pub const SCM_TSTAMP_ACK: u32 = 2;
+
+/// An error or notification read from the error queue of a socket.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QueuedError {
+    /// ICMP or ICMPv6 error of a sent packet (IP_RECVERR, IPV6_RECVERR)
+    Icmp(IcmpError),
+
+    /// error detected by the local host, e.g. EMSGSIZE with the path MTU as info
+    Local { errno: i32, info: u32 },
+
+    /// the MSG_ZEROCOPY sends with the ids first to last (inclusive) completed, their buffers may
+    /// be reused; copied is set if the kernel copied the data instead
+    Zerocopy { first: u32, last: u32, copied: bool },
+
+    /// transmit timestamp of the sent packet with the id key (SOF_TIMESTAMPING_OPT_ID, or the
+    /// byte offset for TCP), tstamp_type is one of the SCM_TSTAMP_* types
+    Timestamp { key: u32, tstamp_type: u32, timestamps: Timestamps },
+
+    /// the packet was dropped as its SO_TXTIME transmit time could not be met, code is
+    /// SO_EE_CODE_TXTIME_*
+    TxTime { errno: i32, code: u8, txtime: u64 },
+
+    /// a sock_extended_err of another origin
+    Other { errno: i32, origin: u8, ee_type: u8, code: u8, info: u32, data: u32 },
+}
+
+/// An entry of the error queue.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueuedMessage {
+    /// the error or notification
+    pub error: QueuedError,
+
+    /// number of bytes of the original packet returned in the buffer (e.g. IP payload of ICMP
+    /// errors, the packet of transmit timestamps without SOF_TIMESTAMPING_OPT_TSONLY)
+    pub len: usize,
+
+    /// destination address of the original packet, if reported
+    pub destination: Option<SocketAddr>,
+}
+
+/// Reads the oldest entry of the error queue of the socket without blocking, Ok(None) if the
+/// queue is empty. The buffer receives the (truncated) original packet. Entries without extended
+/// error are skipped.
+pub fn recv_error_queue<S: AsFd>(socket: &S, buf: &mut [u8]) -> Result<Option<QueuedMessage>> {
+    loop {
+        let message = match recv_msg(socket.as_fd().as_raw_fd(), buf, libc::MSG_ERRQUEUE | libc::MSG_DONTWAIT) {
+            Ok(message) => message,
+            Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let timestamps = message.control.iter().find_map(|control| match control {
+            ControlMessage::Timestamping(timestamps) => Some(*timestamps),
+            _ => None,
+        });
+        let error = message.control.iter().find_map(|control| match control {
+            ControlMessage::ExtendedError(error) => Some(queued_error(error, message.address, timestamps)),
+            _ => None,
+        });
+        if let Some(error) = error {
+            return Ok(Some(QueuedMessage { error, len: message.len, destination: message.address }));
+        }
+    }
+}
+
+/// Reads all entries of the error queue of the socket without blocking.
+pub fn drain_error_queue<S: AsFd>(socket: &S) -> Result<Vec<QueuedMessage>> {
+    let mut buf = [0_u8; 256];
+    let mut messages = Vec::new();
+    while let Some(message) = recv_error_queue(socket, &mut buf)? {
+        messages.push(message);
+    }
+    Ok(messages)
+}
+
+fn queued_error(error: &ExtendedError, destination: Option<SocketAddr>, timestamps: Option<Timestamps>)
+                -> QueuedError {
+    if let Some(icmp) = IcmpError::from_extended(error, destination) {
+        return QueuedError::Icmp(icmp);
+    }
+    let errno = error.errno as i32;
+    match error.origin {
+        libc::SO_EE_ORIGIN_LOCAL => QueuedError::Local { errno, info: error.info },
+        SO_EE_ORIGIN_ZEROCOPY => QueuedError::Zerocopy {
+            first: error.info, last: error.data, copied: error.code == SO_EE_CODE_ZEROCOPY_COPIED,
+        },
+        libc::SO_EE_ORIGIN_TIMESTAMPING => QueuedError::Timestamp {
+            key: error.data, tstamp_type: error.info, timestamps: timestamps.unwrap_or_default(),
+        },
+        SO_EE_ORIGIN_TXTIME => QueuedError::TxTime {
+            errno, code: error.code, txtime: (error.data as u64) << 32 | error.info as u64,
+        },
+        origin => QueuedError::Other {
+            errno, origin, ee_type: error.ee_type, code: error.code, info: error.info, data: error.data,
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn extended(origin: u8, code: u8, info: u32, data: u32) -> ExtendedError {
+        ExtendedError { errno: 0, origin, ee_type: 0, code, info, data, offender: None }
+    }
+
+    #[test]
+    fn test_queued_error() {
+        assert_eq!(queued_error(&extended(SO_EE_ORIGIN_ZEROCOPY, SO_EE_CODE_ZEROCOPY_COPIED, 3, 7), None, None),
+                   QueuedError::Zerocopy { first: 3, last: 7, copied: true });
+        let timestamps = Timestamps { software: Some(std::time::Duration::from_secs(5)), hardware: None };
+        assert_eq!(queued_error(&extended(libc::SO_EE_ORIGIN_TIMESTAMPING, 0, SCM_TSTAMP_SCHED, 9), None,
+                                Some(timestamps)),
+                   QueuedError::Timestamp { key: 9, tstamp_type: SCM_TSTAMP_SCHED, timestamps });
+        assert_eq!(queued_error(&extended(SO_EE_ORIGIN_TXTIME, 1, 2, 1), None, None),
+                   QueuedError::TxTime { errno: 0, code: 1, txtime: (1 << 32) | 2 });
+        assert!(matches!(queued_error(&extended(libc::SO_EE_ORIGIN_ICMP6, 0, 0, 0), None, None),
+                         QueuedError::Icmp(IcmpError { ipv6: true, .. })));
+        assert!(matches!(queued_error(&extended(99, 0, 0, 0), None, None), QueuedError::Other { origin: 99, .. }));
+    }
+}
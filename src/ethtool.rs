@@ -0,0 +1,206 @@
+//! Link settings and offload capability queries via the ethtool ioctl interface (SIOCETHTOOL).
+
+use std::io::{Result, Error};
+
+use super::{Duplex, ioctl::interface_data_ioctl};
+
+const SIOCETHTOOL: libc::c_ulong = 0x8946;
+
+const ETHTOOL_GRXCSUM: u32 = 0x14;
+const ETHTOOL_GTXCSUM: u32 = 0x16;
+const ETHTOOL_GSG: u32 = 0x18;
+const ETHTOOL_GTSO: u32 = 0x1e;
+const ETHTOOL_GGSO: u32 = 0x23;
+const ETHTOOL_GGRO: u32 = 0x2b;
+const ETHTOOL_GET_TS_INFO: u32 = 0x41;
+const ETHTOOL_GLINKSETTINGS: u32 = 0x4c;
+
+const SPEED_UNKNOWN: u32 = 0xffff_ffff;
+const DUPLEX_HALF: u8 = 0x00;
+const DUPLEX_FULL: u8 = 0x01;
+const AUTONEG_ENABLE: u8 = 0x01;
+
+/// Maximum number of 32bit words of a link mode bitmap (link_mode_masks_nwords is an i8).
+const LINK_MODE_MASK_MAX_NWORDS: usize = 127;
+
+/// SOF_TIMESTAMPING_* capability flags as reported in `TimestampingInfo::so_timestamping`.
+pub const SOF_TIMESTAMPING_TX_HARDWARE: u32 = 1 << 0;
+pub const SOF_TIMESTAMPING_TX_SOFTWARE: u32 = 1 << 1;
+pub const SOF_TIMESTAMPING_RX_HARDWARE: u32 = 1 << 2;
+pub const SOF_TIMESTAMPING_RX_SOFTWARE: u32 = 1 << 3;
+pub const SOF_TIMESTAMPING_SOFTWARE: u32 = 1 << 4;
+pub const SOF_TIMESTAMPING_RAW_HARDWARE: u32 = 1 << 6;
+
+#[repr(C)]
+struct EthtoolValue {
+    cmd: u32,
+    data: u32,
+}
+
+#[repr(C)]
+struct EthtoolLinkSettings {
+    cmd: u32,
+    speed: u32,
+    duplex: u8,
+    port: u8,
+    phy_address: u8,
+    autoneg: u8,
+    mdio_support: u8,
+    eth_tp_mdix: u8,
+    eth_tp_mdix_ctrl: u8,
+    link_mode_masks_nwords: i8,
+    transceiver: u8,
+    master_slave_cfg: u8,
+    master_slave_state: u8,
+    rate_matching: u8,
+    reserved: [u32; 7],
+    link_mode_masks: [u32; 3 * LINK_MODE_MASK_MAX_NWORDS],
+}
+
+#[repr(C)]
+struct EthtoolTsInfo {
+    cmd: u32,
+    so_timestamping: u32,
+    phc_index: i32,
+    tx_types: u32,
+    tx_reserved: [u32; 3],
+    rx_filters: u32,
+    rx_reserved: [u32; 3],
+}
+
+/// Negotiated link settings of an interface.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LinkSettings {
+    /// negotiated speed in Mbit/s, None if unknown (e.g. no link)
+    pub speed: Option<u32>,
+
+    /// negotiated duplex mode, None if unknown (e.g. no link)
+    pub duplex: Option<Duplex>,
+
+    /// whether auto negotiation is enabled
+    pub autoneg: bool,
+
+    /// physical connector type (PORT_TP, PORT_FIBRE, ... from linux/ethtool.h)
+    pub port: u8,
+}
+
+/// Offload capabilities of an interface. A value of None means the driver does not
+/// report the feature.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct OffloadFeatures {
+    /// receive checksum offload
+    pub rx_checksum: Option<bool>,
+
+    /// transmit checksum offload
+    pub tx_checksum: Option<bool>,
+
+    /// scatter/gather
+    pub scatter_gather: Option<bool>,
+
+    /// TCP segmentation offload
+    pub tso: Option<bool>,
+
+    /// generic segmentation offload
+    pub gso: Option<bool>,
+
+    /// generic receive offload
+    pub gro: Option<bool>,
+}
+
+/// Timestamping capabilities of an interface.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TimestampingInfo {
+    /// supported SOF_TIMESTAMPING_* flags
+    pub so_timestamping: u32,
+
+    /// index of the PTP hardware clock (/dev/ptp<n>), None if there is no PHC
+    pub phc_index: Option<u32>,
+
+    /// bitmap of supported hwtstamp_tx_types (1 << HWTSTAMP_TX_*)
+    pub tx_types: u32,
+
+    /// bitmap of supported hwtstamp_rx_filters (1 << HWTSTAMP_FILTER_*)
+    pub rx_filters: u32,
+}
+
+impl TimestampingInfo {
+
+    /// Returns whether the interface can generate hardware timestamps for sent and received packets.
+    pub fn supports_hardware_timestamping(&self) -> bool {
+        let required = SOF_TIMESTAMPING_TX_HARDWARE | SOF_TIMESTAMPING_RX_HARDWARE
+            | SOF_TIMESTAMPING_RAW_HARDWARE;
+        (self.so_timestamping & required) == required
+    }
+
+    /// Returns whether the kernel can generate software timestamps for packets of the interface.
+    pub fn supports_software_timestamping(&self) -> bool {
+        let required = SOF_TIMESTAMPING_TX_SOFTWARE | SOF_TIMESTAMPING_RX_SOFTWARE
+            | SOF_TIMESTAMPING_SOFTWARE;
+        (self.so_timestamping & required) == required
+    }
+}
+
+/// Retrieves the negotiated link settings (ETHTOOL_GLINKSETTINGS) of the interface.
+pub fn link_settings(interface_name: &str) -> Result<LinkSettings> {
+    let mut settings: EthtoolLinkSettings = unsafe { std::mem::zeroed() };
+    settings.cmd = ETHTOOL_GLINKSETTINGS;
+    // handshake: the kernel answers a request with zero words with the negated number of words
+    interface_data_ioctl(interface_name, SIOCETHTOOL, &mut settings)?;
+    let nwords = -settings.link_mode_masks_nwords;
+    if nwords <= 0 {
+        return Err(Error::from_raw_os_error(libc::EPROTO));
+    }
+
+    settings = unsafe { std::mem::zeroed() };
+    settings.cmd = ETHTOOL_GLINKSETTINGS;
+    settings.link_mode_masks_nwords = nwords;
+    interface_data_ioctl(interface_name, SIOCETHTOOL, &mut settings)?;
+
+    let speed = if settings.speed == SPEED_UNKNOWN || settings.speed == 0 {
+        None
+    } else {
+        Some(settings.speed)
+    };
+    let duplex = match settings.duplex {
+        DUPLEX_HALF => Some(Duplex::Half),
+        DUPLEX_FULL => Some(Duplex::Full),
+        _ => None,
+    };
+    Ok(LinkSettings { speed, duplex, autoneg: settings.autoneg == AUTONEG_ENABLE, port: settings.port })
+}
+
+/// Retrieves the offload capabilities of the interface. Features whose query is not supported by
+/// the driver are reported as None, other errors (e.g. non-existing interface) are returned.
+pub fn offload_features(interface_name: &str) -> Result<OffloadFeatures> {
+    Ok(OffloadFeatures {
+        rx_checksum: get_value(interface_name, ETHTOOL_GRXCSUM)?,
+        tx_checksum: get_value(interface_name, ETHTOOL_GTXCSUM)?,
+        scatter_gather: get_value(interface_name, ETHTOOL_GSG)?,
+        tso: get_value(interface_name, ETHTOOL_GTSO)?,
+        gso: get_value(interface_name, ETHTOOL_GGSO)?,
+        gro: get_value(interface_name, ETHTOOL_GGRO)?,
+    })
+}
+
+/// Retrieves the timestamping capabilities (ETHTOOL_GET_TS_INFO) of the interface.
+pub fn timestamping_info(interface_name: &str) -> Result<TimestampingInfo> {
+    let mut info: EthtoolTsInfo = unsafe { std::mem::zeroed() };
+    info.cmd = ETHTOOL_GET_TS_INFO;
+    interface_data_ioctl(interface_name, SIOCETHTOOL, &mut info)?;
+    Ok(TimestampingInfo {
+        so_timestamping: info.so_timestamping,
+        phc_index: if info.phc_index < 0 { None } else { Some(info.phc_index as u32) },
+        tx_types: info.tx_types,
+        rx_filters: info.rx_filters,
+    })
+}
+
+/// Queries a boolean ethtool value, returns None if the driver does not support the query.
+fn get_value(interface_name: &str, cmd: u32) -> Result<Option<bool>> {
+    let mut value = EthtoolValue { cmd, data: 0 };
+    match interface_data_ioctl(interface_name, SIOCETHTOOL, &mut value) {
+        Ok(()) => Ok(Some(value.data != 0)),
+        Err(e) if e.raw_os_error() == Some(libc::EOPNOTSUPP) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
@@ -0,0 +1,494 @@
+//! Forward error correction for datagram streams on lossy links, e.g. multicast media or
+//! telemetry over WLAN: the `FecEncoder` adds `n - k` parity packets to every block of `k`
+//! datagrams, from which the `FecDecoder` recovers up to `n - k` lost datagrams of the block
+//! without a retransmission. One parity packet (n = k + 1) is the XOR of the datagrams, more
+//! are Reed-Solomon (Cauchy) codes over GF(2^8). Received datagrams are delivered immediately,
+//! recovered ones when enough packets of their block arrived.
+//!
+//! Packet format (big endian): block number (u32), type (0 data, 1 parity), index within the
+//! data or parity packets (u8), number of data packets of the block (u8, for data packets the
+//! configured k since the block may be flushed early), number of parity packets (u8), followed
+//! by the shard: for data packets the length of the datagram (u16) and the datagram, for parity
+//! packets the code of the data shards padded to the longest one.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::TryInto,
+    io::{Error, ErrorKind, Result},
+    net::{SocketAddr, UdpSocket},
+};
+
+use super::stats::{SocketStats, StatsCounter};
+
+/// Length of the header of a packet.
+pub const FEC_HEADER_LEN: usize = 8;
+
+const TYPE_DATA: u8 = 0;
+const TYPE_PARITY: u8 = 1;
+/// Number of incomplete blocks the decoder keeps.
+const MAX_BLOCKS: usize = 16;
+
+/// Logarithm and exponential tables of GF(2^8) with the polynomial 0x11d.
+const fn gf_tables() -> ([u8; 512], [u8; 256]) {
+    let mut exp = [0_u8; 512];
+    let mut log = [0_u8; 256];
+    let mut x: u16 = 1;
+    let mut i = 0;
+    while i < 255 {
+        exp[i] = x as u8;
+        exp[i + 255] = x as u8;
+        log[x as usize] = i as u8;
+        x <<= 1;
+        if x & 0x100 != 0 {
+            x ^= 0x11d;
+        }
+        i += 1;
+    }
+    (exp, log)
+}
+
+const GF_EXP: [u8; 512] = gf_tables().0;
+const GF_LOG: [u8; 256] = gf_tables().1;
+
+fn gf_mul(a: u8, b: u8) -> u8 {
+    if a == 0 || b == 0 {
+        0
+    } else {
+        GF_EXP[GF_LOG[a as usize] as usize + GF_LOG[b as usize] as usize]
+    }
+}
+
+/// Returns the multiplicative inverse of a non-zero element.
+fn gf_inv(a: u8) -> u8 {
+    GF_EXP[255 - GF_LOG[a as usize] as usize]
+}
+
+/// Returns the coefficient of data shard `data` in parity shard `parity`: 1 for a single
+/// parity shard (XOR), else the Cauchy matrix element 1 / (x + y) with x = 255 - parity and
+/// y = data, which are distinct for k + p <= 255 and do not depend on k.
+fn coefficient(parity: u8, data: u8, parity_count: u8) -> u8 {
+    if parity_count == 1 {
+        1
+    } else {
+        gf_inv((255 - parity) ^ data)
+    }
+}
+
+/// Adds `factor * source` to the target, which is at least as long as the source.
+fn add_scaled(target: &mut [u8], source: &[u8], factor: u8) {
+    match factor {
+        0 => {},
+        1 => target.iter_mut().zip(source).for_each(|(t, s)| *t ^= s),
+        _ => target.iter_mut().zip(source).for_each(|(t, s)| *t ^= gf_mul(factor, *s)),
+    }
+}
+
+fn header(block: u32, packet_type: u8, index: u8, data_count: u8, parity_count: u8) -> Vec<u8> {
+    let mut packet = block.to_be_bytes().to_vec();
+    packet.extend_from_slice(&[packet_type, index, data_count, parity_count]);
+    packet
+}
+
+/// Checks the block configuration, k data packets of n packets.
+fn check_code(k: u8, n: u8) -> Result<()> {
+    if k == 0 || n <= k {
+        return Err(Error::new(ErrorKind::InvalidInput, "FEC needs 0 < k < n <= 255"));
+    }
+    Ok(())
+}
+
+/// Generates the packets of blocks of `k` datagrams and `n - k` parity packets.
+/// ```
+/// use net_utils::fec::{FecDecoder, FecEncoder};
+/// let mut encoder = FecEncoder::new(4, 6).unwrap();
+/// let mut decoder = FecDecoder::new();
+/// let mut packets = Vec::new();
+/// for datagram in [&b"one"[..], b"two", b"three", b"four"] {
+///     packets.extend(encoder.encode(datagram).unwrap());
+/// }
+/// assert_eq!(packets.len(), 6);
+/// // two datagrams are lost, the parity packets recover them
+/// let mut received: Vec<Vec<u8>> = packets.iter().skip(2).flat_map(|packet| decoder.push(packet)).collect();
+/// received.sort();
+/// assert_eq!(received, vec![b"four".to_vec(), b"one".to_vec(), b"three".to_vec(), b"two".to_vec()]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct FecEncoder {
+    k: u8,
+    parity_count: u8,
+    block: u32,
+    shards: Vec<Vec<u8>>,
+}
+
+impl FecEncoder {
+
+    /// Creates an encoder for blocks of `k` datagrams in `n` packets. Fails with InvalidInput
+    /// unless 0 < k < n.
+    pub fn new(k: u8, n: u8) -> Result<FecEncoder> {
+        check_code(k, n)?;
+        Ok(FecEncoder { k, parity_count: n - k, block: 0, shards: Vec::new() })
+    }
+
+    /// Returns the packet of the datagram, followed by the parity packets if it completes the
+    /// block. Fails with InvalidInput for a datagram longer than 65535 bytes.
+    pub fn encode(&mut self, datagram: &[u8]) -> Result<Vec<Vec<u8>>> {
+        let len: u16 = datagram.len().try_into()
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, "datagram too long"))?;
+        let mut shard = len.to_be_bytes().to_vec();
+        shard.extend_from_slice(datagram);
+        let mut packet = header(self.block, TYPE_DATA, self.shards.len() as u8, self.k, self.parity_count);
+        packet.extend_from_slice(&shard);
+        self.shards.push(shard);
+        let mut packets = vec![packet];
+        if self.shards.len() == self.k as usize {
+            packets.extend(self.flush());
+        }
+        Ok(packets)
+    }
+
+    /// Returns the parity packets of the incomplete block and starts the next one, e.g. before
+    /// a pause of the stream. Empty if the block has no datagram.
+    pub fn flush(&mut self) -> Vec<Vec<u8>> {
+        if self.shards.is_empty() {
+            return Vec::new();
+        }
+        let len = self.shards.iter().map(Vec::len).max().unwrap_or(0);
+        let data_count = self.shards.len() as u8;
+        let packets = (0..self.parity_count).map(|parity| {
+            let mut packet = header(self.block, TYPE_PARITY, parity, data_count, self.parity_count);
+            let mut code = vec![0_u8; len];
+            for (data, shard) in self.shards.iter().enumerate() {
+                add_scaled(&mut code, shard, coefficient(parity, data as u8, self.parity_count));
+            }
+            packet.extend_from_slice(&code);
+            packet
+        }).collect();
+        self.shards.clear();
+        self.block = self.block.wrapping_add(1);
+        packets
+    }
+}
+
+/// Received packets of a block.
+#[derive(Debug, Default)]
+struct Block {
+    /// number of data packets, known from the parity packets
+    data_count: Option<u8>,
+    parity_count: u8,
+    data: HashMap<u8, Vec<u8>>,
+    parity: HashMap<u8, Vec<u8>>,
+    complete: bool,
+}
+
+impl Block {
+
+    /// Recovers the missing data shards if enough parity shards were received.
+    fn recover(&mut self) -> Vec<Vec<u8>> {
+        let data_count = match self.data_count {
+            Some(count) if !self.complete => count,
+            _ => return Vec::new(),
+        };
+        let missing: Vec<u8> = (0..data_count).filter(|index| !self.data.contains_key(index)).collect();
+        if missing.is_empty() {
+            self.complete = true;
+            return Vec::new();
+        }
+        if self.parity.len() < missing.len() {
+            return Vec::new();
+        }
+        self.complete = true;
+        let len = self.parity.values().map(Vec::len).max().unwrap_or(0);
+        let mut rows: Vec<(u8, Vec<u8>)> = self.parity.iter().take(missing.len())
+            .map(|(index, code)| {
+                // the code of the missing shards only
+                let mut code = code.clone();
+                code.resize(len, 0);
+                for (data, shard) in self.data.iter() {
+                    add_scaled(&mut code, shard, coefficient(*index, *data, self.parity_count));
+                }
+                (*index, code)
+            })
+            .collect();
+        let mut matrix: Vec<Vec<u8>> = rows.iter()
+            .map(|(index, _)| missing.iter().map(|data| coefficient(*index, *data, self.parity_count)).collect())
+            .collect();
+        // Gauss-Jordan elimination, every square submatrix of a Cauchy matrix is invertible
+        for column in 0..missing.len() {
+            let pivot = match (column..missing.len()).find(|row| matrix[*row][column] != 0) {
+                Some(pivot) => pivot,
+                None => return Vec::new(),
+            };
+            matrix.swap(column, pivot);
+            rows.swap(column, pivot);
+            let inverse = gf_inv(matrix[column][column]);
+            matrix[column].iter_mut().for_each(|value| *value = gf_mul(*value, inverse));
+            rows[column].1.iter_mut().for_each(|value| *value = gf_mul(*value, inverse));
+            for row in 0..missing.len() {
+                let factor = matrix[row][column];
+                if row != column && factor != 0 {
+                    let (pivot_row, pivot_code) = (matrix[column].clone(), rows[column].1.clone());
+                    add_scaled(&mut matrix[row], &pivot_row, factor);
+                    add_scaled(&mut rows[row].1, &pivot_code, factor);
+                }
+            }
+        }
+        rows.into_iter().filter_map(|(_, shard)| {
+            let len = u16::from_be_bytes(shard.get(..2)?.try_into().ok()?) as usize;
+            shard.get(2..2 + len).map(<[u8]>::to_vec)
+        }).collect()
+    }
+}
+
+/// Delivers the datagrams of a stream of `FecEncoder` packets and recovers lost ones.
+#[derive(Debug, Default)]
+pub struct FecDecoder {
+    blocks: VecDeque<(u32, Block)>,
+    recovered: u64,
+}
+
+impl FecDecoder {
+
+    /// Creates a decoder.
+    pub fn new() -> FecDecoder {
+        FecDecoder::default()
+    }
+
+    /// Returns the number of recovered datagrams.
+    pub fn recovered(&self) -> u64 {
+        self.recovered
+    }
+
+    /// Adds a received packet and returns its datagram for a data packet and the datagrams it
+    /// recovers. Invalid and duplicate packets return nothing.
+    pub fn push(&mut self, packet: &[u8]) -> Vec<Vec<u8>> {
+        if packet.len() < FEC_HEADER_LEN {
+            return Vec::new();
+        }
+        let number = u32::from_be_bytes([packet[0], packet[1], packet[2], packet[3]]);
+        let (packet_type, index, data_count, parity_count) = (packet[4], packet[5], packet[6], packet[7]);
+        let shard = &packet[FEC_HEADER_LEN..];
+        if check_code(data_count, data_count.saturating_add(parity_count)).is_err()
+            || data_count as usize + parity_count as usize > 255 {
+            return Vec::new();
+        }
+        let block = match self.blocks.iter().position(|(n, _)| *n == number) {
+            Some(position) => &mut self.blocks[position].1,
+            None => {
+                if self.blocks.len() == MAX_BLOCKS {
+                    self.blocks.pop_front();
+                }
+                self.blocks.push_back((number, Block { parity_count, ..Block::default() }));
+                &mut self.blocks.back_mut().unwrap().1
+            },
+        };
+        if parity_count != block.parity_count {
+            return Vec::new();
+        }
+        let mut datagrams = Vec::new();
+        match packet_type {
+            TYPE_DATA if index < data_count && !block.data.contains_key(&index) => {
+                let len = match shard.get(..2) {
+                    Some(len) => u16::from_be_bytes([len[0], len[1]]) as usize,
+                    None => return Vec::new(),
+                };
+                if shard.len() != 2 + len || block.complete {
+                    return Vec::new();
+                }
+                datagrams.push(shard[2..].to_vec());
+                block.data.insert(index, shard.to_vec());
+            },
+            TYPE_PARITY if index < parity_count && !block.parity.contains_key(&index) => {
+                block.data_count = Some(data_count);
+                block.parity.insert(index, shard.to_vec());
+            },
+            _ => return Vec::new(),
+        }
+        let recovered = block.recover();
+        self.recovered += recovered.len() as u64;
+        datagrams.extend(recovered);
+        datagrams
+    }
+}
+
+/// Wrapper of a UDP socket which sends the datagrams to a destination with FEC parity
+/// packets.
+/// ```no_run
+/// use net_utils::fec::FecSender;
+/// let socket = std::net::UdpSocket::bind("0.0.0.0:0").unwrap();
+/// let mut sender = FecSender::new(socket, "239.1.2.3:5000".parse().unwrap(), 8, 10).unwrap();
+/// sender.send(b"telemetry").unwrap();
+/// sender.flush().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct FecSender {
+    socket: UdpSocket,
+    destination: SocketAddr,
+    encoder: FecEncoder,
+    stats: StatsCounter,
+}
+
+impl FecSender {
+
+    /// Wraps the socket for blocks of `k` datagrams in `n` packets. Fails with InvalidInput
+    /// unless 0 < k < n.
+    pub fn new(socket: UdpSocket, destination: SocketAddr, k: u8, n: u8) -> Result<FecSender> {
+        Ok(FecSender { socket, destination, encoder: FecEncoder::new(k, n)?, stats: StatsCounter::new() })
+    }
+
+    /// Returns the socket.
+    pub fn get_ref(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// Returns the socket.
+    pub fn into_inner(self) -> UdpSocket {
+        self.socket
+    }
+
+    /// Returns the statistics of the packets sent via the wrapper, parity packets included.
+    pub fn stats(&self) -> SocketStats {
+        self.stats.snapshot()
+    }
+
+    /// Sends the datagram, and the parity packets if it completes a block.
+    pub fn send(&mut self, datagram: &[u8]) -> Result<()> {
+        let packets = self.encoder.encode(datagram)?;
+        self.send_packets(packets)
+    }
+
+    /// Sends the parity packets of the incomplete block, e.g. before a pause of the stream.
+    pub fn flush(&mut self) -> Result<()> {
+        let packets = self.encoder.flush();
+        self.send_packets(packets)
+    }
+
+    fn send_packets(&mut self, packets: Vec<Vec<u8>>) -> Result<()> {
+        for packet in packets {
+            self.socket.send_to(&packet, self.destination)?;
+            self.stats.sent(packet.len());
+        }
+        Ok(())
+    }
+}
+
+/// Wrapper of a UDP socket which receives the packets of `FecSender`s and recovers lost
+/// datagrams per sender.
+#[derive(Debug)]
+pub struct FecReceiver {
+    socket: UdpSocket,
+    decoders: HashMap<SocketAddr, FecDecoder>,
+    ready: VecDeque<(Vec<u8>, SocketAddr)>,
+    stats: StatsCounter,
+}
+
+impl FecReceiver {
+
+    /// Wraps the socket.
+    pub fn new(socket: UdpSocket) -> FecReceiver {
+        FecReceiver { socket, decoders: HashMap::new(), ready: VecDeque::new(), stats: StatsCounter::new() }
+    }
+
+    /// Returns the socket, e.g. to set a read timeout.
+    pub fn get_ref(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// Returns the socket.
+    pub fn into_inner(self) -> UdpSocket {
+        self.socket
+    }
+
+    /// Returns the statistics of the packets received via the wrapper, parity packets included.
+    pub fn stats(&self) -> SocketStats {
+        self.stats.snapshot()
+    }
+
+    /// Returns the number of datagrams recovered from all senders.
+    pub fn recovered(&self) -> u64 {
+        self.decoders.values().map(FecDecoder::recovered).sum()
+    }
+
+    /// Receives the next datagram, received or recovered, into the buffer and returns its
+    /// length (truncated to the buffer) and sender.
+    pub fn recv_from(&mut self, buf: &mut [u8]) -> Result<(usize, SocketAddr)> {
+        let mut packet = vec![0_u8; 65536];
+        loop {
+            if let Some((datagram, source)) = self.ready.pop_front() {
+                let len = datagram.len().min(buf.len());
+                buf[..len].copy_from_slice(&datagram[..len]);
+                return Ok((len, source));
+            }
+            let (len, source) = self.socket.recv_from(&mut packet)?;
+            self.stats.received(len);
+            let datagrams = self.decoders.entry(source).or_default().push(&packet[..len]);
+            self.ready.extend(datagrams.into_iter().map(|datagram| (datagram, source)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_gf() {
+        for a in 1..=255_u8 {
+            assert_eq!(gf_mul(a, gf_inv(a)), 1);
+        }
+        assert_eq!(gf_mul(2, 0x80), 0x1d);
+        assert_eq!(gf_mul(0, 7), 0);
+    }
+
+    /// Encodes the datagrams as one block and decodes it without the packets at the given
+    /// positions, returns the sorted datagrams.
+    fn transfer(datagrams: &[&[u8]], k: u8, n: u8, lost: &[usize], flush: bool) -> Vec<Vec<u8>> {
+        let mut encoder = FecEncoder::new(k, n).unwrap();
+        let mut packets: Vec<Vec<u8>> = datagrams.iter().flat_map(|datagram| encoder.encode(datagram).unwrap()).collect();
+        if flush {
+            packets.extend(encoder.flush());
+        }
+        let mut decoder = FecDecoder::new();
+        let mut received: Vec<Vec<u8>> = packets.iter().enumerate()
+            .filter(|(i, _)| !lost.contains(i))
+            .flat_map(|(_, packet)| decoder.push(packet))
+            .collect();
+        received.sort();
+        received
+    }
+
+    #[test]
+    fn test_recovery() {
+        let datagrams: [&[u8]; 4] = [b"a", b"bcd", b"", b"efghij"];
+        let mut sorted: Vec<Vec<u8>> = datagrams.iter().map(|datagram| datagram.to_vec()).collect();
+        sorted.sort();
+        // XOR
+        assert_eq!(transfer(&datagrams, 4, 5, &[1], false), sorted);
+        assert_eq!(transfer(&datagrams, 4, 5, &[1, 3], false).len(), 2);
+        // Reed-Solomon, every combination of two losses
+        for a in 0..7 {
+            for b in a + 1..7 {
+                assert_eq!(transfer(&datagrams, 4, 7, &[a, b], false), sorted, "lost {} and {}", a, b);
+            }
+        }
+        assert_eq!(transfer(&datagrams, 4, 7, &[0, 1, 2], false), sorted);
+        assert_eq!(transfer(&datagrams, 4, 7, &[0, 1, 2, 3], false).len(), 0);
+        // a flushed block of three datagrams
+        let mut sorted: Vec<Vec<u8>> = datagrams[..3].iter().map(|datagram| datagram.to_vec()).collect();
+        sorted.sort();
+        assert_eq!(transfer(&datagrams[..3], 4, 6, &[0, 2], true), sorted);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(FecEncoder::new(4, 4).unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(FecEncoder::new(0, 1).unwrap_err().kind(), ErrorKind::InvalidInput);
+        let mut encoder = FecEncoder::new(2, 3).unwrap();
+        assert!(encoder.flush().is_empty());
+        let packet = encoder.encode(b"abc").unwrap().remove(0);
+        let mut decoder = FecDecoder::new();
+        assert!(decoder.push(&packet[..FEC_HEADER_LEN + 3]).is_empty());
+        assert_eq!(decoder.push(&packet), vec![b"abc".to_vec()]);
+        assert!(decoder.push(&packet).is_empty());
+    }
+}
@@ -0,0 +1,177 @@
+//! C interface for interface enumeration and multicast sockets, declared in
+//! `include/net_utils.h`. build.rs generates the header from this file with cbindgen into
+//! OUT_DIR and `test_header` fails if `include/net_utils.h` differs, printing the path of the
+//! generated one to copy. `cargo build --release --features ffi` builds the shared library
+//! (libnet_utils.so) besides the Rust library.
+//! Functions return 0 (or a file descriptor) on success and a negative errno value on failure.
+//! Requires the feature 'ffi'.
+
+use std::{
+    ffi::CStr,
+    io::{Error, ErrorKind},
+    net::{IpAddr, SocketAddr},
+    os::{raw::{c_char, c_int}, unix::io::IntoRawFd},
+};
+
+use super::{IpInterface, MulticastSocketBuilder};
+
+/// Length of the name buffer of `NetUtilsInterface` including the terminating NUL.
+pub const NET_UTILS_NAME_LEN: usize = 16;
+
+/// IP interface configuration (struct net_utils_interface).
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NetUtilsInterface {
+    /// interface index
+    pub index: u32,
+    /// NUL terminated interface name, truncated to NET_UTILS_NAME_LEN - 1 bytes
+    pub name: [c_char; NET_UTILS_NAME_LEN],
+    /// IFF_* interface flags
+    pub flags: u32,
+    /// AF_INET or AF_INET6
+    pub family: c_int,
+    /// address in network byte order, IPv4 addresses use the first 4 bytes
+    pub address: [u8; 16],
+    /// prefix length of the network
+    pub prefix_len: u8,
+}
+
+impl From<&IpInterface> for NetUtilsInterface {
+    fn from(interface: &IpInterface) -> NetUtilsInterface {
+        let mut name = [0 as c_char; NET_UTILS_NAME_LEN];
+        for (dst, src) in name.iter_mut().zip(interface.name.bytes().take(NET_UTILS_NAME_LEN - 1)) {
+            *dst = src as c_char;
+        }
+        let mut address = [0_u8; 16];
+        let family = match interface.address.ip() {
+            IpAddr::V4(ip) => {
+                address[..4].copy_from_slice(&ip.octets());
+                libc::AF_INET
+            },
+            IpAddr::V6(ip) => {
+                address.copy_from_slice(&ip.octets());
+                libc::AF_INET6
+            },
+        };
+        NetUtilsInterface {
+            index: interface.index, name, flags: interface.flags, family, address,
+            prefix_len: interface.prefix_len(),
+        }
+    }
+}
+
+/// Converts an error into the negative errno value returned by the C functions.
+fn error_code(error: &Error) -> c_int {
+    -error.raw_os_error().unwrap_or(match error.kind() {
+        ErrorKind::InvalidInput => libc::EINVAL,
+        ErrorKind::NotFound => libc::ENOENT,
+        _ => libc::EIO,
+    })
+}
+
+/// Parses a NUL terminated C string into an IP address.
+unsafe fn parse_address(address: *const c_char) -> Result<IpAddr, Error> {
+    if address.is_null() {
+        return Err(Error::new(ErrorKind::InvalidInput, "null address"));
+    }
+    CStr::from_ptr(address).to_str().ok().and_then(|s| s.parse().ok())
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "invalid address"))
+}
+
+/// Retrieves the IP interface configurations of the system into an array allocated by the
+/// library, which has to be released with `net_utils_free_interfaces`.
+/// # Safety
+/// `interfaces` and `count` must be valid pointers.
+#[no_mangle]
+pub unsafe extern "C" fn net_utils_list_interfaces(interfaces: *mut *mut NetUtilsInterface, count: *mut usize)
+                                                   -> c_int {
+    if interfaces.is_null() || count.is_null() {
+        return -libc::EINVAL;
+    }
+    match IpInterface::retrieve_ip_interfaces() {
+        Ok(list) => {
+            let list: Box<[NetUtilsInterface]> = list.iter().map(NetUtilsInterface::from).collect();
+            *count = list.len();
+            *interfaces = Box::into_raw(list) as *mut NetUtilsInterface;
+            0
+        },
+        Err(e) => error_code(&e),
+    }
+}
+
+/// Releases an array returned by `net_utils_list_interfaces`.
+/// # Safety
+/// `interfaces` and `count` must be the values returned by `net_utils_list_interfaces`, each
+/// array must be released once.
+#[no_mangle]
+pub unsafe extern "C" fn net_utils_free_interfaces(interfaces: *mut NetUtilsInterface, count: usize) {
+    if !interfaces.is_null() {
+        drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(interfaces, count)));
+    }
+}
+
+/// Creates a UDP socket bound to the port of the multicast group (textual IPv4 or IPv6
+/// address), which joined the group on the interface with the given local address (NULL for
+/// the default interface), see `MulticastSocketBuilder`. Returns the file descriptor, which
+/// the caller closes.
+/// # Safety
+/// `group` and `interface` (unless NULL) must be NUL terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn net_utils_create_mc_socket(group: *const c_char, port: u16, interface: *const c_char)
+                                                    -> c_int {
+    let group = match parse_address(group) {
+        Ok(group) => group,
+        Err(e) => return error_code(&e),
+    };
+    let interface = if interface.is_null() {
+        match group {
+            IpAddr::V4(_) => IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED),
+            IpAddr::V6(_) => IpAddr::V6(std::net::Ipv6Addr::UNSPECIFIED),
+        }
+    } else {
+        match parse_address(interface) {
+            Ok(interface) => interface,
+            Err(e) => return error_code(&e),
+        }
+    };
+    match MulticastSocketBuilder::new(SocketAddr::new(group, port), interface).build_std() {
+        Ok(socket) => socket.into_raw_fd(),
+        Err(e) => error_code(&e),
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_list_interfaces() {
+        let mut interfaces = std::ptr::null_mut();
+        let mut count = 0;
+        assert_eq!(unsafe { net_utils_list_interfaces(&mut interfaces, &mut count) }, 0);
+        let list = unsafe { std::slice::from_raw_parts(interfaces, count) };
+        let loopback = list.iter().find(|i| i.family == libc::AF_INET && i.address[..4] == [127, 0, 0, 1]);
+        assert_eq!(loopback.map(|i| i.prefix_len), Some(8));
+        unsafe { net_utils_free_interfaces(interfaces, count) };
+        assert_eq!(unsafe { net_utils_list_interfaces(std::ptr::null_mut(), &mut count) }, -libc::EINVAL);
+    }
+
+    #[test]
+    fn test_create_mc_socket() {
+        let fd = unsafe { net_utils_create_mc_socket(b"239.255.77.5\0".as_ptr() as *const c_char, 1911, std::ptr::null()) };
+        assert!(fd >= 0);
+        unsafe { libc::close(fd) };
+        let fd = unsafe { net_utils_create_mc_socket(b"192.0.2.1\0".as_ptr() as *const c_char, 1911, std::ptr::null()) };
+        assert_eq!(fd, -libc::EINVAL);
+        let fd = unsafe { net_utils_create_mc_socket(b"group\0".as_ptr() as *const c_char, 1911, std::ptr::null()) };
+        assert_eq!(fd, -libc::EINVAL);
+    }
+
+    #[test]
+    fn test_header() {
+        let generated = concat!(env!("OUT_DIR"), "/net_utils.h");
+        assert!(include_str!(concat!(env!("OUT_DIR"), "/net_utils.h")) == include_str!("../include/net_utils.h"),
+                "include/net_utils.h is outdated, copy {}", generated);
+    }
+}
@@ -0,0 +1,336 @@
+//! Application-layer fragmentation of messages larger than the path MTU, so that they can be
+//! sent as UDP datagrams (e.g. to multicast groups) without IP fragmentation, which is often
+//! filtered and loses the whole datagram with any fragment. The `Reassembler` collects the
+//! fragments per source and drops incomplete messages after a timeout.
+//!
+//! Fragment format (big endian): message id (u32), fragment index (u16), number of fragments
+//! (u16), followed by the data.
+
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    io::{Error, ErrorKind, Result},
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+/// Length of the header of a fragment.
+pub const FRAGMENT_HEADER_LEN: usize = 8;
+
+/// A parsed fragment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Fragment<'a> {
+    /// id of the message the fragment belongs to, shared by all its fragments
+    pub message_id: u32,
+
+    /// position of the fragment in the message, from 0
+    pub index: u16,
+
+    /// number of fragments of the message
+    pub count: u16,
+
+    /// the part of the message carried by the fragment
+    pub data: &'a [u8],
+}
+
+impl<'a> Fragment<'a> {
+
+    /// Parses a fragment, None if it is too short or the index is not below the count.
+    pub fn parse(data: &'a [u8]) -> Option<Fragment<'a>> {
+        if data.len() < FRAGMENT_HEADER_LEN {
+            return None;
+        }
+        let fragment = Fragment {
+            message_id: u32::from_be_bytes(data[0..4].try_into().ok()?),
+            index: u16::from_be_bytes(data[4..6].try_into().ok()?),
+            count: u16::from_be_bytes(data[6..8].try_into().ok()?),
+            data: &data[FRAGMENT_HEADER_LEN..],
+        };
+        if fragment.index < fragment.count {
+            Some(fragment)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the fragment with header.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(FRAGMENT_HEADER_LEN + self.data.len());
+        data.extend_from_slice(&self.message_id.to_be_bytes());
+        data.extend_from_slice(&self.index.to_be_bytes());
+        data.extend_from_slice(&self.count.to_be_bytes());
+        data.extend_from_slice(self.data);
+        data
+    }
+}
+
+/// Splits the message into fragments of at most `max_len` bytes including the header, an
+/// empty message into one empty fragment. Fails with InvalidInput if `max_len` leaves no room
+/// for data or the message needs more than 65535 fragments.
+pub fn fragment(message_id: u32, message: &[u8], max_len: usize) -> Result<Vec<Vec<u8>>> {
+    if max_len <= FRAGMENT_HEADER_LEN {
+        return Err(Error::new(ErrorKind::InvalidInput, "maximum fragment length too small"));
+    }
+    let chunks: Vec<&[u8]> = if message.is_empty() {
+        vec![message]
+    } else {
+        message.chunks(max_len - FRAGMENT_HEADER_LEN).collect()
+    };
+    let count: u16 = chunks.len().try_into()
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "message needs too many fragments"))?;
+    Ok(chunks.iter().enumerate()
+        .map(|(index, data)| Fragment { message_id, index: index as u16, count, data }.encode())
+        .collect())
+}
+
+/// Splits messages into fragments with consecutive message ids, starting at a random one.
+/// ```
+/// use net_utils::fragment::{Fragmenter, Reassembler};
+/// use std::time::Duration;
+/// let mut fragmenter = Fragmenter::new(1200).unwrap();
+/// let mut reassembler = Reassembler::new(Duration::from_secs(2));
+/// let message = vec![7_u8; 5000];
+/// let source = "192.0.2.1:5000".parse().unwrap();
+/// let mut complete = None;
+/// for fragment in fragmenter.fragment(&message).unwrap() {
+///     complete = reassembler.push(source, &fragment);
+/// }
+/// assert_eq!(complete, Some(message));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Fragmenter {
+    max_len: usize,
+    next_id: u32,
+}
+
+impl Fragmenter {
+
+    /// Creates a fragmenter for fragments of at most `max_len` bytes including the header, e.g.
+    /// the path MTU less the IP and UDP headers. Fails with InvalidInput if it leaves no room
+    /// for data.
+    pub fn new(max_len: usize) -> Result<Fragmenter> {
+        if max_len <= FRAGMENT_HEADER_LEN {
+            return Err(Error::new(ErrorKind::InvalidInput, "maximum fragment length too small"));
+        }
+        let mut id = [0_u8; 4];
+        super::random::random_bytes(&mut id);
+        Ok(Fragmenter { max_len, next_id: u32::from_ne_bytes(id) })
+    }
+
+    /// Returns the maximum length of a fragment.
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    /// Splits the message into fragments under the next message id.
+    pub fn fragment(&mut self, message: &[u8]) -> Result<Vec<Vec<u8>>> {
+        let fragments = fragment(self.next_id, message, self.max_len)?;
+        self.next_id = self.next_id.wrapping_add(1);
+        Ok(fragments)
+    }
+}
+
+/// Fragments of an incomplete message.
+#[derive(Debug)]
+struct Partial {
+    fragments: Vec<Option<Vec<u8>>>,
+    missing: usize,
+    bytes: usize,
+    started: Instant,
+}
+
+/// Reassembles messages from their fragments per source address.
+#[derive(Debug)]
+pub struct Reassembler {
+    timeout: Duration,
+    max_pending: usize,
+    max_bytes: usize,
+    pending: HashMap<(SocketAddr, u32), Partial>,
+    buffered: usize,
+    dropped: u64,
+}
+
+impl Reassembler {
+
+    /// Creates a reassembler which drops incomplete messages `timeout` after their first
+    /// fragment and keeps at most 64 incomplete messages with 1 MiB of fragments.
+    pub fn new(timeout: Duration) -> Reassembler {
+        Reassembler {
+            timeout, max_pending: 64, max_bytes: 1 << 20, pending: HashMap::new(), buffered: 0, dropped: 0,
+        }
+    }
+
+    /// Sets the maximum number of incomplete messages, the oldest one is dropped for another.
+    pub fn set_max_pending(&mut self, max_pending: usize) {
+        self.max_pending = max_pending.max(1);
+    }
+
+    /// Sets the maximum number of bytes of the fragments of all incomplete messages, the oldest
+    /// messages are dropped for the fragments of others. An incomplete message exceeding it
+    /// alone is dropped.
+    pub fn set_max_bytes(&mut self, max_bytes: usize) {
+        self.max_bytes = max_bytes;
+    }
+
+    /// Returns the number of incomplete messages.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Returns the number of incomplete messages dropped so far.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Returns the number of bytes of the fragments of the incomplete messages.
+    pub fn buffered(&self) -> usize {
+        self.buffered
+    }
+
+    /// Adds a fragment received from the source and returns the message it completes. Invalid
+    /// and duplicate fragments and those whose number of fragments differs from the earlier
+    /// fragments of the message are ignored.
+    pub fn push(&mut self, source: SocketAddr, fragment: &[u8]) -> Option<Vec<u8>> {
+        let now = Instant::now();
+        self.expire(now);
+        let fragment = Fragment::parse(fragment)?;
+        if fragment.count == 1 {
+            return Some(fragment.data.to_vec());
+        }
+        let key = (source, fragment.message_id);
+        match self.pending.get(&key) {
+            Some(partial) => {
+                if partial.fragments.len() != fragment.count as usize
+                    || partial.fragments[fragment.index as usize].is_some() {
+                    return None;
+                }
+            },
+            None => {
+                if self.pending.len() >= self.max_pending {
+                    self.drop_oldest(None);
+                }
+            },
+        }
+        while self.buffered + fragment.data.len() > self.max_bytes {
+            if !self.drop_oldest(Some(key)) {
+                self.remove(&key);
+                self.dropped += 1;
+                return None;
+            }
+        }
+        let partial = self.pending.entry(key).or_insert_with(|| Partial {
+            fragments: vec![None; fragment.count as usize], missing: fragment.count as usize, bytes: 0, started: now,
+        });
+        partial.fragments[fragment.index as usize] = Some(fragment.data.to_vec());
+        partial.missing -= 1;
+        partial.bytes += fragment.data.len();
+        self.buffered += fragment.data.len();
+        if partial.missing > 0 {
+            return None;
+        }
+        self.remove(&key).map(|partial| partial.fragments.into_iter().flatten().flatten().collect())
+    }
+
+    /// Drops the incomplete messages which timed out.
+    pub fn expire(&mut self, now: Instant) {
+        let timeout = self.timeout;
+        let before = self.pending.len();
+        let mut buffered = 0;
+        self.pending.retain(|_, partial| {
+            let keep = now.saturating_duration_since(partial.started) < timeout;
+            buffered += if keep { partial.bytes } else { 0 };
+            keep
+        });
+        self.buffered = buffered;
+        self.dropped += (before - self.pending.len()) as u64;
+    }
+
+    /// Drops the oldest incomplete message other than the excepted one, false if there is none.
+    fn drop_oldest(&mut self, except: Option<(SocketAddr, u32)>) -> bool {
+        let oldest = self.pending.iter()
+            .filter(|(key, _)| Some(**key) != except)
+            .min_by_key(|(_, partial)| partial.started)
+            .map(|(key, _)| *key);
+        match oldest {
+            Some(oldest) => {
+                self.remove(&oldest);
+                self.dropped += 1;
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn remove(&mut self, key: &(SocketAddr, u32)) -> Option<Partial> {
+        let partial = self.pending.remove(key)?;
+        self.buffered -= partial.bytes;
+        Some(partial)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_fragment() {
+        let fragments = fragment(9, b"abcdefghij", FRAGMENT_HEADER_LEN + 4).unwrap();
+        assert_eq!(fragments.len(), 3);
+        assert_eq!(Fragment::parse(&fragments[2]),
+                   Some(Fragment { message_id: 9, index: 2, count: 3, data: b"ij" }));
+        assert_eq!(fragment(9, b"", 100).unwrap(), vec![vec![0, 0, 0, 9, 0, 0, 0, 1]]);
+        assert_eq!(fragment(9, b"abc", FRAGMENT_HEADER_LEN).unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(fragment(9, &[0; 70000], FRAGMENT_HEADER_LEN + 1).unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(Fragment::parse(&[0, 0, 0, 9, 0, 3, 0, 3]), None);
+    }
+
+    #[test]
+    fn test_reassembler() {
+        let a: SocketAddr = "192.0.2.1:5000".parse().unwrap();
+        let b: SocketAddr = "192.0.2.2:5000".parse().unwrap();
+        let fragments = fragment(1, b"abcdefghij", FRAGMENT_HEADER_LEN + 4).unwrap();
+        let mut reassembler = Reassembler::new(Duration::from_secs(10));
+        // out of order, interleaved with the same message id from another source
+        assert_eq!(reassembler.push(a, &fragments[2]), None);
+        assert_eq!(reassembler.push(b, &fragments[0]), None);
+        assert_eq!(reassembler.push(a, &fragments[0]), None);
+        assert_eq!(reassembler.push(a, &fragments[0]), None);
+        assert_eq!(reassembler.pending(), 2);
+        assert_eq!(reassembler.push(a, &fragments[1]), Some(b"abcdefghij".to_vec()));
+        assert_eq!(reassembler.pending(), 1);
+
+        reassembler.expire(Instant::now() + Duration::from_secs(10));
+        assert_eq!((reassembler.pending(), reassembler.dropped()), (0, 1));
+
+        reassembler.set_max_pending(1);
+        reassembler.push(a, &fragments[0]);
+        reassembler.push(b, &fragments[0]);
+        assert_eq!((reassembler.pending(), reassembler.dropped()), (1, 2));
+    }
+
+    #[test]
+    fn test_reassembler_limits() {
+        let a: SocketAddr = "192.0.2.1:5000".parse().unwrap();
+        let b: SocketAddr = "192.0.2.2:5000".parse().unwrap();
+        let fragments = fragment(1, b"abcdefghij", FRAGMENT_HEADER_LEN + 4).unwrap();
+        let mut reassembler = Reassembler::new(Duration::from_secs(10));
+        // a fragment of the message claiming another number of fragments
+        let other_count = Fragment { message_id: 1, index: 1, count: 4, data: b"efgh" }.encode();
+        assert_eq!(reassembler.push(a, &fragments[0]), None);
+        assert_eq!(reassembler.push(a, &other_count), None);
+        assert_eq!(reassembler.buffered(), 4);
+        assert_eq!(reassembler.push(a, &fragments[1]), None);
+        assert_eq!(reassembler.push(a, &fragments[2]), Some(b"abcdefghij".to_vec()));
+        assert_eq!(reassembler.buffered(), 0);
+
+        reassembler.set_max_bytes(6);
+        assert_eq!(reassembler.push(a, &fragments[0]), None);
+        // the older message is dropped for the fragment of the other one
+        assert_eq!(reassembler.push(b, &fragments[0]), None);
+        assert_eq!((reassembler.pending(), reassembler.buffered(), reassembler.dropped()), (1, 4, 1));
+        // the message exceeds the limit alone
+        assert_eq!(reassembler.push(b, &fragments[1]), None);
+        assert_eq!((reassembler.pending(), reassembler.buffered(), reassembler.dropped()), (0, 0, 2));
+    }
+}
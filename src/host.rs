@@ -0,0 +1,82 @@
+//! Identity of the local host: host name, fully qualified domain name and reverse DNS lookups of
+//! addresses via the DNS servers of an interface.
+
+use std::io::{Error, Result};
+#[cfg(feature = "tokio-net")]
+use std::net::IpAddr;
+
+/// Returns the host name of the local system as configured (gethostname), possibly including
+/// the domain.
+pub fn hostname() -> Result<String> {
+    let mut buffer = [0_u8; 256];
+    if unsafe { libc::gethostname(buffer.as_mut_ptr() as *mut libc::c_char, buffer.len()) } != 0 {
+        return Err(Error::last_os_error());
+    }
+    let len = buffer.iter().position(|b| *b == 0).unwrap_or(buffer.len());
+    Ok(String::from_utf8_lossy(&buffer[..len]).into_owned())
+}
+
+/// Returns the fully qualified domain name of the local system like `hostname -f`: the host name
+/// if it contains a domain, otherwise the canonical name the C library resolves it to (hosts file,
+/// DNS). The host name is returned if it cannot be resolved.
+pub fn fqdn() -> Result<String> {
+    let hostname = hostname()?;
+    if hostname.contains('.') {
+        return Ok(hostname);
+    }
+    Ok(canonical_name(&hostname).filter(|name| name.contains('.')).unwrap_or(hostname))
+}
+
+/// Resolves the name with getaddrinfo and returns its canonical name.
+fn canonical_name(name: &str) -> Option<String> {
+    let name = std::ffi::CString::new(name).ok()?;
+    let mut hints: libc::addrinfo = unsafe { std::mem::zeroed() };
+    hints.ai_flags = libc::AI_CANONNAME;
+    hints.ai_socktype = libc::SOCK_DGRAM;
+    let mut result: *mut libc::addrinfo = std::ptr::null_mut();
+    if unsafe { libc::getaddrinfo(name.as_ptr(), std::ptr::null(), &hints, &mut result) } != 0 {
+        return None;
+    }
+    let canonical = unsafe {
+        if (*result).ai_canonname.is_null() {
+            None
+        } else {
+            std::ffi::CStr::from_ptr((*result).ai_canonname).to_str().ok().map(String::from)
+        }
+    };
+    unsafe { libc::freeaddrinfo(result) };
+    canonical
+}
+
+/// Looks up the name of the address (PTR record of its reverse name) at the first DNS server of
+/// the interface, or of the system if no interface is given, with the query sent from the
+/// interface. Returns Ok(None) if the address has no name.
+/// Requires the feature 'tokio-net' and must be called within a tokio runtime.
+#[cfg(feature = "tokio-net")]
+pub async fn reverse_lookup(address: &IpAddr, via_interface: Option<&str>) -> Result<Option<String>> {
+    use super::dns::{self, DnsOpts, RecordData};
+
+    let opts = DnsOpts { interface: via_interface.map(String::from), ..DnsOpts::default() };
+    let response = dns::query(&dns::reverse_name(address), dns::TYPE_PTR, &opts).await?;
+    match response.rcode() {
+        dns::RCODE_NOERROR => Ok(response.answers.into_iter().find_map(|record| match record.data {
+            RecordData::Ptr(name) => Some(name),
+            _ => None,
+        })),
+        dns::RCODE_NXDOMAIN => Ok(None),
+        rcode => Err(Error::other(format!("reverse lookup of {} failed with DNS response code {}", address, rcode))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_hostname() {
+        let hostname = hostname().unwrap();
+        assert!(!hostname.is_empty());
+        assert!(fqdn().unwrap().starts_with(hostname.split('.').next().unwrap()));
+    }
+}
@@ -0,0 +1,177 @@
+use std::{
+    io::{Error, ErrorKind, Read, Result, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+/// Timeout for connecting, sending and receiving of an HTTP request.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A plain http:// URL.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Url {
+    pub host: String,
+    pub port: u16,
+    pub path: String,
+}
+
+impl Url {
+
+    /// Parses an absolute http:// URL.
+    pub fn parse(url: &str) -> Result<Url> {
+        let invalid = || Error::new(ErrorKind::InvalidInput, format!("invalid http URL {}", url));
+        let rest = url.strip_prefix("http://").ok_or_else(invalid)?;
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rfind(':') {
+            Some(i) if !authority[i..].contains(']') => {
+                (&authority[..i], authority[i + 1..].parse().map_err(|_| invalid())?)
+            },
+            _ => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Url { host: host.trim_start_matches('[').trim_end_matches(']').to_string(), port, path: path.to_string() })
+    }
+
+    /// Resolves a reference (absolute URL, absolute or relative path) against this URL.
+    pub fn join(&self, reference: &str) -> Result<Url> {
+        if reference.starts_with("http://") {
+            return Url::parse(reference);
+        }
+        let path = if reference.starts_with('/') {
+            reference.to_string()
+        } else {
+            let dir = &self.path[..self.path.rfind('/').map_or(0, |i| i + 1)];
+            format!("{}{}", if dir.is_empty() { "/" } else { dir }, reference)
+        };
+        Ok(Url { host: self.host.clone(), port: self.port, path })
+    }
+
+    /// Returns host and port as used in the Host header.
+    fn authority(&self) -> String {
+        if self.host.contains(':') {
+            format!("[{}]:{}", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+}
+
+/// Response of an HTTP request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Response {
+    pub status: u16,
+    pub body: Vec<u8>,
+    /// local address of the connection the request was sent on
+    pub local_addr: SocketAddr,
+}
+
+/// Sends an HTTP/1.1 request with `Connection: close` and reads the complete response.
+pub(crate) fn request(method: &str, url: &Url, headers: &[(&str, &str)], body: &[u8]) -> Result<Response> {
+    let address = (url.host.as_str(), url.port).to_socket_addrs()?.next()
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("cannot resolve {}", url.host)))?;
+    request_to(&address, None, method, url, headers, body)
+}
+
+/// Sends the request like `request` to the address instead of the resolved host of the URL, from
+/// a socket bound to the network device if given.
+pub(crate) fn request_to(address: &SocketAddr, device: Option<&str>, method: &str, url: &Url, headers: &[(&str, &str)],
+                         body: &[u8]) -> Result<Response> {
+    let socket = super::sys_socket::socket(socket2::Domain::for_address(*address), socket2::Type::STREAM,
+                                      Some(socket2::Protocol::TCP))?;
+    if let Some(device) = device {
+        super::device::bind_to_device(&socket, device, address)?;
+    }
+    socket.connect_timeout(&(*address).into(), TIMEOUT)?;
+    let mut stream: TcpStream = socket.into();
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    let mut request = format!("{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Length: {}\r\n",
+                              method, url.path, url.authority(), body.len());
+    for (name, value) in headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes())?;
+    stream.write_all(body)?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response)?;
+    let (status, body) = parse_response(&response)?;
+    Ok(Response { status, body, local_addr: stream.local_addr()? })
+}
+
+/// Splits a complete HTTP response into status code and (de-chunked) body.
+fn parse_response(response: &[u8]) -> Result<(u16, Vec<u8>)> {
+    let invalid = || Error::new(ErrorKind::InvalidData, "invalid HTTP response");
+    let header_end = response.windows(4).position(|w| w == b"\r\n\r\n").ok_or_else(invalid)?;
+    let head = std::str::from_utf8(&response[..header_end]).map_err(|_| invalid())?;
+    let mut lines = head.split("\r\n");
+    let status = lines.next().and_then(|l| l.split(' ').nth(1)).and_then(|s| s.parse().ok()).ok_or_else(invalid)?;
+    let mut chunked = false;
+    let mut content_length = None;
+    for (name, value) in lines.filter_map(|l| l.split_once(':')) {
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("Transfer-Encoding") {
+            chunked = value.eq_ignore_ascii_case("chunked");
+        } else if name.eq_ignore_ascii_case("Content-Length") {
+            content_length = value.parse::<usize>().ok();
+        }
+    }
+    let body = &response[header_end + 4..];
+    let body = if chunked {
+        dechunk(body).ok_or_else(invalid)?
+    } else {
+        body[..content_length.unwrap_or(body.len()).min(body.len())].to_vec()
+    };
+    Ok((status, body))
+}
+
+/// Decodes a body with chunked transfer encoding.
+fn dechunk(mut body: &[u8]) -> Option<Vec<u8>> {
+    let mut decoded = Vec::new();
+    loop {
+        let line_end = body.windows(2).position(|w| w == b"\r\n")?;
+        let size = std::str::from_utf8(&body[..line_end]).ok()?;
+        let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+        if size == 0 {
+            return Some(decoded);
+        }
+        let chunk = body.get(line_end + 2..line_end + 2 + size)?;
+        decoded.extend_from_slice(chunk);
+        body = body.get(line_end + 4 + size..)?;
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_url() {
+        let url = Url::parse("http://192.168.1.1:49152/rootDesc.xml").unwrap();
+        assert_eq!(url, Url { host: "192.168.1.1".to_string(), port: 49152, path: "/rootDesc.xml".to_string() });
+        assert_eq!(url.join("ctl/IPConn").unwrap().path, "/ctl/IPConn");
+        assert_eq!(url.join("/upnp/control").unwrap().path, "/upnp/control");
+        assert_eq!(url.join("http://10.0.0.1/x").unwrap().host, "10.0.0.1");
+        let url = Url::parse("http://[fe80::1]/").unwrap();
+        assert_eq!((url.host.as_str(), url.port), ("fe80::1", 80));
+        assert_eq!(url.authority(), "[fe80::1]:80");
+        assert!(Url::parse("https://example.com/").is_err());
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+        assert_eq!(parse_response(response).unwrap(), (200, b"hello".to_vec()));
+        let response = b"HTTP/1.1 500 Internal Server Error\r\ntransfer-encoding: chunked\r\n\r\n4\r\nwiki\r\n5\r\npedia\r\n0\r\n\r\n";
+        assert_eq!(parse_response(response).unwrap(), (500, b"wikipedia".to_vec()));
+        assert!(parse_response(b"garbage").is_err());
+    }
+}
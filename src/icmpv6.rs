@@ -0,0 +1,128 @@
+//! ICMPv6 message types and the ICMP6_FILTER option of raw ICMPv6 sockets, which makes the
+//! kernel deliver only the message types a listener handles (e.g. RAs, MLD reports or echo
+//! replies), so that it is not woken by the rest of the ICMPv6 traffic of the link.
+
+use std::{
+    io::Result,
+    os::unix::io::{AsFd, AsRawFd},
+};
+
+use super::sockopt::{get_option, set_option};
+
+/// Destination unreachable
+pub const ICMPV6_DEST_UNREACH: u8 = 1;
+/// Packet too big
+pub const ICMPV6_PACKET_TOO_BIG: u8 = 2;
+/// Time exceeded
+pub const ICMPV6_TIME_EXCEEDED: u8 = 3;
+/// Parameter problem
+pub const ICMPV6_PARAM_PROBLEM: u8 = 4;
+/// Echo request (ping)
+pub const ICMPV6_ECHO_REQUEST: u8 = 128;
+/// Echo reply
+pub const ICMPV6_ECHO_REPLY: u8 = 129;
+/// Multicast listener query (MLD)
+pub const MLD_LISTENER_QUERY: u8 = 130;
+/// Multicast listener report (MLDv1)
+pub const MLD_LISTENER_REPORT: u8 = 131;
+/// Multicast listener done (MLDv1)
+pub const MLD_LISTENER_DONE: u8 = 132;
+/// Router solicitation
+pub const ND_ROUTER_SOLICIT: u8 = 133;
+/// Router advertisement
+pub const ND_ROUTER_ADVERT: u8 = 134;
+/// Neighbor solicitation
+pub const ND_NEIGHBOR_SOLICIT: u8 = 135;
+/// Neighbor advertisement
+pub const ND_NEIGHBOR_ADVERT: u8 = 136;
+/// Redirect
+pub const ND_REDIRECT: u8 = 137;
+/// Version 2 multicast listener report (MLDv2)
+pub const MLD2_LISTENER_REPORT: u8 = 143;
+
+/// Option name of the filter at level IPPROTO_ICMPV6.
+const ICMP6_FILTER: libc::c_int = 1;
+
+/// Filter of the ICMPv6 message types a raw socket receives (struct icmp6_filter). The kernel
+/// default passes all types.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Icmp6Filter {
+    /// a set bit blocks the message type
+    blocked: [u32; 8],
+}
+
+impl Icmp6Filter {
+
+    /// Creates a filter that passes all message types.
+    pub fn pass_all() -> Icmp6Filter {
+        Icmp6Filter { blocked: [0; 8] }
+    }
+
+    /// Creates a filter that blocks all message types.
+    pub fn block_all() -> Icmp6Filter {
+        Icmp6Filter { blocked: [u32::MAX; 8] }
+    }
+
+    /// Creates a filter that passes only the message types.
+    pub fn pass_only(types: &[u8]) -> Icmp6Filter {
+        types.iter().fold(Icmp6Filter::block_all(), |filter, msg_type| filter.pass(*msg_type))
+    }
+
+    /// Passes the message type.
+    pub fn pass(mut self, msg_type: u8) -> Self {
+        self.blocked[(msg_type >> 5) as usize] &= !(1 << (msg_type & 31));
+        self
+    }
+
+    /// Blocks the message type.
+    pub fn block(mut self, msg_type: u8) -> Self {
+        self.blocked[(msg_type >> 5) as usize] |= 1 << (msg_type & 31);
+        self
+    }
+
+    /// Returns whether the message type passes the filter.
+    pub fn passes(&self, msg_type: u8) -> bool {
+        self.blocked[(msg_type >> 5) as usize] & (1 << (msg_type & 31)) == 0
+    }
+}
+
+impl Default for Icmp6Filter {
+    fn default() -> Icmp6Filter {
+        Icmp6Filter::pass_all()
+    }
+}
+
+/// Sets the ICMPv6 filter of a raw ICMPv6 socket.
+pub fn set_icmp6_filter<S: AsFd>(socket: &S, filter: &Icmp6Filter) -> Result<()> {
+    set_option(socket.as_fd().as_raw_fd(), libc::IPPROTO_ICMPV6, ICMP6_FILTER, &filter.blocked)
+}
+
+/// Retrieves the ICMPv6 filter of a raw ICMPv6 socket.
+pub fn icmp6_filter<S: AsFd>(socket: &S) -> Result<Icmp6Filter> {
+    let blocked = get_option(socket.as_fd().as_raw_fd(), libc::IPPROTO_ICMPV6, ICMP6_FILTER)?;
+    Ok(Icmp6Filter { blocked })
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_filter() {
+        let filter = Icmp6Filter::pass_only(&[ND_ROUTER_ADVERT, MLD2_LISTENER_REPORT]);
+        assert!(filter.passes(ND_ROUTER_ADVERT));
+        assert!(filter.passes(MLD2_LISTENER_REPORT));
+        assert!(!filter.passes(ND_ROUTER_SOLICIT));
+        assert!(!filter.passes(0));
+        assert!(!filter.passes(255));
+        // struct icmp6_filter: bit (type & 31) of word (type >> 5), 134 and 143 share word 4
+        assert_eq!(filter.blocked[4], !(1 << 6 | 1 << 15));
+        assert_eq!(filter.blocked[0], u32::MAX);
+        let filter = filter.block(ND_ROUTER_ADVERT).pass(255);
+        assert!(!filter.passes(ND_ROUTER_ADVERT));
+        assert!(filter.passes(255));
+        assert!(Icmp6Filter::default().passes(ICMPV6_ECHO_REPLY));
+        assert_eq!(Icmp6Filter::pass_all().block(1).pass(1), Icmp6Filter::pass_all());
+    }
+}
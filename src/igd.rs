@@ -0,0 +1,199 @@
+//! UPnP Internet Gateway Device client: gateway discovery and port mappings (AddPortMapping,
+//! DeletePortMapping) via SOAP.
+
+use std::{
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, Ipv4Addr, SocketAddrV4},
+    time::Duration,
+};
+
+use super::{http::{self, Url}, ssdp};
+
+/// Service types of the WAN connection services supporting port mappings, in order of preference.
+const WAN_SERVICE_TYPES: [&str; 3] = [
+    "urn:schemas-upnp-org:service:WANIPConnection:2",
+    "urn:schemas-upnp-org:service:WANIPConnection:1",
+    "urn:schemas-upnp-org:service:WANPPPConnection:1",
+];
+
+/// Device type searched for by `discover`.
+const IGD_DEVICE_TYPE: &str = "urn:schemas-upnp-org:device:InternetGatewayDevice:1";
+
+/// Transport protocol of a port mapping.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PortMappingProtocol {
+    /// TCP
+    Tcp,
+
+    /// UDP
+    Udp,
+}
+
+impl PortMappingProtocol {
+    fn as_str(&self) -> &'static str {
+        match self {
+            PortMappingProtocol::Tcp => "TCP",
+            PortMappingProtocol::Udp => "UDP",
+        }
+    }
+}
+
+/// The WAN connection service of an Internet Gateway Device.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Gateway {
+    control_url: Url,
+    service_type: String,
+    local_addr: IpAddr,
+}
+
+/// Searches for an Internet Gateway Device with SSDP from `interface` (UNSPECIFIED for the
+/// default multicast interface) and returns the first one offering a WAN connection service.
+pub fn discover(interface: &Ipv4Addr, timeout: Duration) -> Result<Gateway> {
+    let mut last_error = Error::new(ErrorKind::NotFound, "no Internet Gateway Device found");
+    for response in ssdp::search(IGD_DEVICE_TYPE, interface, timeout)? {
+        match Gateway::from_location(&response.location) {
+            Ok(gateway) => return Ok(gateway),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+impl Gateway {
+
+    /// Fetches the device description from its URL (LOCATION of the SSDP response) and selects
+    /// the WAN connection service.
+    pub fn from_location(location: &str) -> Result<Gateway> {
+        let url = Url::parse(location)?;
+        let response = http::request("GET", &url, &[], &[])?;
+        if response.status != 200 {
+            return Err(Error::other(format!("device description request failed with HTTP {}", response.status)));
+        }
+        let description = String::from_utf8_lossy(&response.body);
+        let base = match element_text(&description, "URLBase") {
+            Some(base) if !base.is_empty() => Url::parse(base)?,
+            _ => url,
+        };
+        let services: Vec<(&str, &str)> = elements(&description, "service")
+            .filter_map(|s| Some((element_text(s, "serviceType")?, element_text(s, "controlURL")?)))
+            .collect();
+        let (service_type, control_url) = WAN_SERVICE_TYPES.iter()
+            .find_map(|t| services.iter().find(|(service_type, _)| service_type == t))
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "device has no WAN connection service"))?;
+        Ok(Gateway {
+            control_url: base.join(control_url)?,
+            service_type: service_type.to_string(),
+            local_addr: response.local_addr.ip(),
+        })
+    }
+
+    /// Returns the service type of the WAN connection service.
+    pub fn service_type(&self) -> &str {
+        &self.service_type
+    }
+
+    /// Returns the local address used to reach the gateway, i.e. the internal address for port
+    /// mappings to this host.
+    pub fn local_addr(&self) -> IpAddr {
+        self.local_addr
+    }
+
+    /// Requests the gateway's external IPv4 address.
+    pub fn external_ip_address(&self) -> Result<Ipv4Addr> {
+        let response = self.call("GetExternalIPAddress", &[])?;
+        element_text(&response, "NewExternalIPAddress")
+            .and_then(|a| a.parse().ok())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "invalid GetExternalIPAddress response"))
+    }
+
+    /// Maps the external port of the gateway to the internal address for `lease_duration` seconds
+    /// (0 for a permanent mapping).
+    pub fn add_port_mapping(&self, protocol: PortMappingProtocol, external_port: u16, internal: &SocketAddrV4,
+                            lease_duration: u32, description: &str) -> Result<()> {
+        self.call("AddPortMapping", &[
+            ("NewRemoteHost", ""),
+            ("NewExternalPort", &external_port.to_string()),
+            ("NewProtocol", protocol.as_str()),
+            ("NewInternalPort", &internal.port().to_string()),
+            ("NewInternalClient", &internal.ip().to_string()),
+            ("NewEnabled", "1"),
+            ("NewPortMappingDescription", &xml_escape(description)),
+            ("NewLeaseDuration", &lease_duration.to_string()),
+        ]).map(|_| ())
+    }
+
+    /// Removes the mapping of the external port.
+    pub fn delete_port_mapping(&self, protocol: PortMappingProtocol, external_port: u16) -> Result<()> {
+        self.call("DeletePortMapping", &[
+            ("NewRemoteHost", ""),
+            ("NewExternalPort", &external_port.to_string()),
+            ("NewProtocol", protocol.as_str()),
+        ]).map(|_| ())
+    }
+
+    /// Invokes the action on the WAN connection service and returns the response body.
+    /// UPnP errors are returned as errors with the UPnP error code and description.
+    fn call(&self, action: &str, arguments: &[(&str, &str)]) -> Result<String> {
+        let mut body = format!("<?xml version=\"1.0\"?>\r\n\
+            <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+            s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\"><s:Body><u:{} xmlns:u=\"{}\">",
+                               action, self.service_type);
+        for (name, value) in arguments {
+            body.push_str(&format!("<{0}>{1}</{0}>", name, value));
+        }
+        body.push_str(&format!("</u:{}></s:Body></s:Envelope>", action));
+        let soap_action = format!("\"{}#{}\"", self.service_type, action);
+        let response = http::request("POST", &self.control_url, &[
+            ("Content-Type", "text/xml; charset=\"utf-8\""),
+            ("SOAPAction", &soap_action),
+        ], body.as_bytes())?;
+        let text = String::from_utf8_lossy(&response.body).into_owned();
+        match response.status {
+            200 => Ok(text),
+            status => Err(match (element_text(&text, "errorCode"), element_text(&text, "errorDescription")) {
+                (Some(code), description) => Error::other(format!("UPnP error {} {}", code, description.unwrap_or(""))),
+                _ => Error::other(format!("{} failed with HTTP {}", action, status)),
+            }),
+        }
+    }
+}
+
+/// Returns the contents of all elements with the tag name.
+fn elements<'a>(xml: &'a str, tag: &str) -> impl Iterator<Item = &'a str> + 'a {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let mut rest = xml;
+    std::iter::from_fn(move || {
+        let start = rest.find(&open)? + open.len();
+        let len = rest[start..].find(&close)?;
+        let content = &rest[start..start + len];
+        rest = &rest[start + len + close.len()..];
+        Some(content)
+    })
+}
+
+/// Returns the trimmed text of the first element with the tag name.
+fn element_text<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    elements(xml, tag).next().map(str::trim)
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_elements() {
+        let xml = "<root><service><serviceType>a</serviceType></service><service><serviceType> b </serviceType>\
+            </service></root>";
+        let services: Vec<&str> = elements(xml, "service").collect();
+        assert_eq!(services.len(), 2);
+        assert_eq!(element_text(services[1], "serviceType"), Some("b"));
+        assert_eq!(element_text(xml, "controlURL"), None);
+        assert_eq!(xml_escape("a<b & c>"), "a&lt;b &amp; c&gt;");
+    }
+}
@@ -0,0 +1,899 @@
+//! IGMP proxy (RFC 4605): collects the memberships of downstream interfaces, joins their groups
+//! on the upstream interface and forwards the multicast traffic of the upstream interface to the
+//! downstream interfaces with members via the kernel multicast routing table.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, Ipv4Addr, UdpSocket},
+    os::unix::io::{AsFd, AsRawFd},
+    time::{Duration, Instant},
+};
+
+use socket2::{InterfaceIndexOrAddress, SockRef};
+
+use super::{checksum::{internet_checksum, verify}, cmsg::ControlMessage, raw::{RawSocket, RawSocketBuilder},
+            shutdown::{ShutdownHandle, is_shutdown_error}, sockopt};
+
+/// Group of all systems, destination of general queries.
+pub const ALL_SYSTEMS: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 1);
+
+/// Group of all multicast routers, destination of leaves.
+pub const ALL_ROUTERS: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 2);
+
+/// Group of all IGMPv3 capable multicast routers, destination of IGMPv3 reports.
+pub const IGMPV3_ROUTERS: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 22);
+
+/// Interval between general queries of the querier.
+pub const QUERY_INTERVAL: Duration = Duration::from_secs(125);
+
+/// Maximum response time of general queries.
+pub const QUERY_RESPONSE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Time after which a membership without new report expires (robustness 2).
+pub const GROUP_MEMBERSHIP_INTERVAL: Duration = Duration::from_secs(260);
+
+/// Time after which a router stops being querier after the last query of a router with lower address.
+pub const OTHER_QUERIER_PRESENT_INTERVAL: Duration = Duration::from_secs(255);
+
+/// Interval (and maximum response time) of group specific queries after a leave.
+pub const LAST_MEMBER_QUERY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Number of group specific queries after a leave and of general queries at startup.
+const ROBUSTNESS: u32 = 2;
+
+/// Interval of the general queries at startup.
+const STARTUP_QUERY_INTERVAL: Duration = Duration::from_secs(31);
+
+/// Number of groups joined per upstream socket, the default of net.ipv4.igmp_max_memberships.
+const GROUPS_PER_SOCKET: usize = 20;
+
+/// Interval between the retries of failed upstream joins.
+const JOIN_RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+const TYPE_QUERY: u8 = 0x11;
+const TYPE_REPORT_V1: u8 = 0x12;
+const TYPE_REPORT_V2: u8 = 0x16;
+const TYPE_LEAVE: u8 = 0x17;
+const TYPE_REPORT_V3: u8 = 0x22;
+
+/// IGMPv3 group record type: the members receive from the sources only.
+pub const MODE_IS_INCLUDE: u8 = 1;
+/// IGMPv3 group record type: the members receive from all but the sources.
+pub const MODE_IS_EXCLUDE: u8 = 2;
+/// IGMPv3 group record type: the filter changed to include the sources only.
+pub const CHANGE_TO_INCLUDE_MODE: u8 = 3;
+/// IGMPv3 group record type: the filter changed to exclude the sources.
+pub const CHANGE_TO_EXCLUDE_MODE: u8 = 4;
+/// IGMPv3 group record type: the sources are added to the filter.
+pub const ALLOW_NEW_SOURCES: u8 = 5;
+/// IGMPv3 group record type: the sources are removed from the filter.
+pub const BLOCK_OLD_SOURCES: u8 = 6;
+
+// linux/mroute.h
+const MRT_INIT: libc::c_int = 200;
+const MRT_ADD_VIF: libc::c_int = 202;
+const MRT_ADD_MFC: libc::c_int = 204;
+const MRT_DEL_MFC: libc::c_int = 205;
+const VIFF_USE_IFINDEX: u8 = 0x08;
+const MAXVIFS: usize = 32;
+const IGMPMSG_NOCACHE: u8 = 1;
+
+/// A group record of an IGMPv3 report.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GroupRecord {
+    /// record type (MODE_IS_INCLUDE, ...)
+    pub record_type: u8,
+
+    /// multicast group
+    pub group: Ipv4Addr,
+
+    /// sources of the record
+    pub sources: Vec<Ipv4Addr>,
+}
+
+/// An IGMP (RFC 1112, RFC 2236, RFC 3376) message.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum IgmpMessage {
+    /// general (unspecified group) or group specific query, with sources for IGMPv3
+    Query { max_response_time: Duration, group: Ipv4Addr, sources: Vec<Ipv4Addr> },
+
+    /// IGMPv1 membership report
+    ReportV1 { group: Ipv4Addr },
+
+    /// IGMPv2 membership report
+    ReportV2 { group: Ipv4Addr },
+
+    /// IGMPv2 leave group
+    Leave { group: Ipv4Addr },
+
+    /// IGMPv3 membership report
+    ReportV3 { records: Vec<GroupRecord> },
+}
+
+impl IgmpMessage {
+
+    /// Parses an IGMP message (without IP header), None if it is invalid or its checksum is wrong.
+    pub fn parse(data: &[u8]) -> Option<IgmpMessage> {
+        if data.len() < 8 || !verify(data) {
+            return None;
+        }
+        let address = |offset: usize| -> Option<Ipv4Addr> {
+            let octets = data.get(offset..offset + 4)?;
+            Some(Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3]))
+        };
+        let group = address(4)?;
+        match data[0] {
+            TYPE_QUERY if data.len() >= 12 => {
+                let count = u16::from_be_bytes([*data.get(10)?, *data.get(11)?]) as usize;
+                let sources = (0..count).map(|i| address(12 + 4 * i)).collect::<Option<Vec<_>>>()?;
+                Some(IgmpMessage::Query { max_response_time: response_time(data[1], true), group, sources })
+            },
+            TYPE_QUERY => {
+                let max_response_time = match data[1] {
+                    // IGMPv1 queries have no maximum response time
+                    0 => QUERY_RESPONSE_INTERVAL,
+                    code => response_time(code, false),
+                };
+                Some(IgmpMessage::Query { max_response_time, group, sources: Vec::new() })
+            },
+            TYPE_REPORT_V1 => Some(IgmpMessage::ReportV1 { group }),
+            TYPE_REPORT_V2 => Some(IgmpMessage::ReportV2 { group }),
+            TYPE_LEAVE => Some(IgmpMessage::Leave { group }),
+            TYPE_REPORT_V3 => {
+                let count = u16::from_be_bytes([data[6], data[7]]);
+                let mut offset = 8;
+                let mut records = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    let header = data.get(offset..offset + 8)?;
+                    let sources_count = u16::from_be_bytes([header[2], header[3]]) as usize;
+                    let sources = (0..sources_count).map(|i| address(offset + 8 + 4 * i))
+                        .collect::<Option<Vec<_>>>()?;
+                    records.push(GroupRecord { record_type: header[0], group: address(offset + 4)?, sources });
+                    offset += 8 + 4 * sources_count + 4 * header[1] as usize;
+                }
+                Some(IgmpMessage::ReportV3 { records })
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns the message in wire format with checksum. Queries without sources are encoded as
+    /// IGMPv2 queries, with sources as IGMPv3 queries.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = match self {
+            IgmpMessage::Query { max_response_time, group, sources } => {
+                let code = (max_response_time.as_millis() / 100).min(if sources.is_empty() { 255 } else { 127 });
+                let mut data = vec![TYPE_QUERY, code as u8, 0, 0];
+                data.extend_from_slice(&group.octets());
+                if !sources.is_empty() {
+                    data.extend_from_slice(&[ROBUSTNESS as u8, QUERY_INTERVAL.as_secs() as u8]);
+                    data.extend_from_slice(&(sources.len() as u16).to_be_bytes());
+                    sources.iter().for_each(|source| data.extend_from_slice(&source.octets()));
+                }
+                data
+            },
+            IgmpMessage::ReportV1 { group } => [&[TYPE_REPORT_V1, 0, 0, 0][..], &group.octets()].concat(),
+            IgmpMessage::ReportV2 { group } => [&[TYPE_REPORT_V2, 0, 0, 0][..], &group.octets()].concat(),
+            IgmpMessage::Leave { group } => [&[TYPE_LEAVE, 0, 0, 0][..], &group.octets()].concat(),
+            IgmpMessage::ReportV3 { records } => {
+                let mut data = vec![TYPE_REPORT_V3, 0, 0, 0, 0, 0];
+                data.extend_from_slice(&(records.len() as u16).to_be_bytes());
+                for record in records {
+                    data.extend_from_slice(&[record.record_type, 0]);
+                    data.extend_from_slice(&(record.sources.len() as u16).to_be_bytes());
+                    data.extend_from_slice(&record.group.octets());
+                    record.sources.iter().for_each(|source| data.extend_from_slice(&source.octets()));
+                }
+                data
+            },
+        };
+        let checksum = internet_checksum(&data);
+        data[2..4].copy_from_slice(&checksum.to_be_bytes());
+        data
+    }
+
+    /// Returns the destination of the message: the group of reports and group specific queries,
+    /// ALL_SYSTEMS for general queries, ALL_ROUTERS for leaves and IGMPV3_ROUTERS for IGMPv3
+    /// reports.
+    pub fn destination(&self) -> Ipv4Addr {
+        match self {
+            IgmpMessage::Query { group, .. } if group.is_unspecified() => ALL_SYSTEMS,
+            IgmpMessage::Query { group, .. } | IgmpMessage::ReportV1 { group } | IgmpMessage::ReportV2 { group } => *group,
+            IgmpMessage::Leave { .. } => ALL_ROUTERS,
+            IgmpMessage::ReportV3 { .. } => IGMPV3_ROUTERS,
+        }
+    }
+}
+
+/// Returns the time of a max response code, IGMPv3 codes above 127 are floating point values.
+fn response_time(code: u8, v3: bool) -> Duration {
+    let tenths = if v3 && code >= 128 {
+        ((code as u64 & 0x0f) | 0x10) << (((code >> 4) & 0x07) + 3)
+    } else {
+        code as u64
+    };
+    Duration::from_millis(100 * tenths)
+}
+
+/// Memberships of the downstream interfaces: the groups with at least one member per interface
+/// (index) and when the membership expires. Source filters of IGMPv3 reports are aggregated to
+/// any-source memberships.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MembershipTable {
+    memberships: HashMap<Ipv4Addr, HashMap<u32, Instant>>,
+}
+
+impl MembershipTable {
+
+    /// Creates an empty table.
+    pub fn new() -> MembershipTable {
+        MembershipTable::default()
+    }
+
+    /// Records a report for the group on the interface, the membership expires after
+    /// GROUP_MEMBERSHIP_INTERVAL. Returns true if the group had no members on any interface.
+    pub fn report(&mut self, interface: u32, group: Ipv4Addr, now: Instant) -> bool {
+        let interfaces = self.memberships.entry(group).or_default();
+        let new = interfaces.is_empty();
+        interfaces.insert(interface, now + GROUP_MEMBERSHIP_INTERVAL);
+        new
+    }
+
+    /// Records a leave (or change to an empty include filter) for the group on the interface: the
+    /// membership expires after the last member queries unless a member reports. Returns true if
+    /// the interface has members of the group, i.e. group specific queries should be sent.
+    pub fn leave(&mut self, interface: u32, group: Ipv4Addr, now: Instant) -> bool {
+        let expiry = now + LAST_MEMBER_QUERY_INTERVAL * ROBUSTNESS;
+        match self.memberships.get_mut(&group).and_then(|interfaces| interfaces.get_mut(&interface)) {
+            Some(membership) => {
+                *membership = (*membership).min(expiry);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Applies the messages of a report received on the interface. Returns the groups that had no
+    /// members on any interface before.
+    pub fn apply(&mut self, interface: u32, message: &IgmpMessage, now: Instant) -> Vec<Ipv4Addr> {
+        let mut joined = Vec::new();
+        let mut join = |table: &mut MembershipTable, group: Ipv4Addr| {
+            if is_routable(&group) && table.report(interface, group, now) {
+                joined.push(group);
+            }
+        };
+        match message {
+            IgmpMessage::ReportV1 { group } | IgmpMessage::ReportV2 { group } => join(self, *group),
+            IgmpMessage::ReportV3 { records } => {
+                for record in records {
+                    match record.record_type {
+                        MODE_IS_EXCLUDE | CHANGE_TO_EXCLUDE_MODE => join(self, record.group),
+                        MODE_IS_INCLUDE | ALLOW_NEW_SOURCES if !record.sources.is_empty() => join(self, record.group),
+                        CHANGE_TO_INCLUDE_MODE if !record.sources.is_empty() => join(self, record.group),
+                        CHANGE_TO_INCLUDE_MODE => {
+                            self.leave(interface, record.group, now);
+                        },
+                        _ => (),
+                    }
+                }
+            },
+            _ => (),
+        }
+        joined
+    }
+
+    /// Removes the expired memberships and returns the groups without members left.
+    pub fn expire(&mut self, now: Instant) -> Vec<Ipv4Addr> {
+        let mut left = Vec::new();
+        self.memberships.retain(|group, interfaces| {
+            interfaces.retain(|_, expiry| *expiry > now);
+            if interfaces.is_empty() {
+                left.push(*group);
+            }
+            !interfaces.is_empty()
+        });
+        left
+    }
+
+    /// Returns the indexes of the interfaces with members of the group.
+    pub fn interfaces(&self, group: &Ipv4Addr) -> Vec<u32> {
+        let mut interfaces: Vec<u32> = self.memberships.get(group).map(|i| i.keys().copied().collect())
+            .unwrap_or_default();
+        interfaces.sort_unstable();
+        interfaces
+    }
+
+    /// Returns the groups with members.
+    pub fn groups(&self) -> Vec<Ipv4Addr> {
+        let mut groups: Vec<Ipv4Addr> = self.memberships.keys().copied().collect();
+        groups.sort_unstable();
+        groups
+    }
+
+    /// Returns when the next membership expires.
+    fn next_expiry(&self) -> Option<Instant> {
+        self.memberships.values().flat_map(|interfaces| interfaces.values()).min().copied()
+    }
+}
+
+/// Groups of the local network control block (224.0.0.0/24) are never proxied.
+fn is_routable(group: &Ipv4Addr) -> bool {
+    group.is_multicast() && group.octets()[..3] != [224, 0, 0]
+}
+
+/// A downstream interface and its querier state.
+#[derive(Debug)]
+struct Downstream {
+    index: u32,
+    vif: u16,
+    address: Ipv4Addr,
+    /// until when another router with a lower address is querier
+    other_querier_until: Option<Instant>,
+    next_query: Instant,
+    startup_queries: u32,
+}
+
+impl Downstream {
+
+    fn is_querier(&self, now: Instant) -> bool {
+        self.other_querier_until.is_none_or(|until| until <= now)
+    }
+}
+
+/// Memberships of the groups with downstream members on the upstream interface, spread over
+/// sockets of GROUPS_PER_SOCKET groups. Failed joins are retried every JOIN_RETRY_INTERVAL.
+#[derive(Debug)]
+struct Upstream {
+    index: u32,
+    sockets: Vec<UdpSocket>,
+    /// socket (position in sockets) of each joined group
+    groups: HashMap<Ipv4Addr, usize>,
+    /// groups whose join failed
+    failed: HashSet<Ipv4Addr>,
+    next_retry: Instant,
+}
+
+impl Upstream {
+
+    fn new(index: u32) -> Upstream {
+        Upstream {
+            index, sockets: Vec::new(), groups: HashMap::new(), failed: HashSet::new(), next_retry: Instant::now(),
+        }
+    }
+
+    /// Joins the group on a socket with room for it, a failed join is retried with `retry`.
+    fn join(&mut self, group: Ipv4Addr) {
+        if self.groups.contains_key(&group) {
+            return;
+        }
+        let result = self.socket_with_room().and_then(|socket| {
+            SockRef::from(&self.sockets[socket])
+                .join_multicast_v4_n(&group, &InterfaceIndexOrAddress::Index(self.index))
+                .map(|_| socket)
+        });
+        trace_event!(crate::trace::TraceEvent::GroupJoined {
+            group: group.into(), interface: crate::trace::TraceInterface::Index(self.index),
+            error: result.as_ref().err(),
+        });
+        match result {
+            Ok(socket) => {
+                self.groups.insert(group, socket);
+                self.failed.remove(&group);
+            },
+            Err(_) => {
+                self.failed.insert(group);
+            },
+        }
+    }
+
+    /// Leaves the group, the membership is dropped even if leaving fails.
+    #[cfg_attr(not(feature = "trace"), allow(unused_variables))]
+    fn leave(&mut self, group: Ipv4Addr) {
+        self.failed.remove(&group);
+        if let Some(socket) = self.groups.remove(&group) {
+            let result = SockRef::from(&self.sockets[socket])
+                .leave_multicast_v4_n(&group, &InterfaceIndexOrAddress::Index(self.index));
+            trace_event!(crate::trace::TraceEvent::GroupLeft {
+                group: group.into(), interface: crate::trace::TraceInterface::Index(self.index),
+                error: result.as_ref().err(),
+            });
+        }
+    }
+
+    /// Joins the groups whose join failed again if the retry is due.
+    fn retry(&mut self, now: Instant) {
+        if self.failed.is_empty() || now < self.next_retry {
+            return;
+        }
+        self.next_retry = now + JOIN_RETRY_INTERVAL;
+        let failed: Vec<Ipv4Addr> = self.failed.iter().copied().collect();
+        for group in failed {
+            self.join(group);
+        }
+    }
+
+    /// Returns the socket with room for another group, opening one if all are full.
+    fn socket_with_room(&mut self) -> Result<usize> {
+        let mut counts = vec![0; self.sockets.len()];
+        for socket in self.groups.values() {
+            counts[*socket] += 1;
+        }
+        if let Some(socket) = counts.iter().position(|count| *count < GROUPS_PER_SOCKET) {
+            return Ok(socket);
+        }
+        self.sockets.push(super::sys_socket::udp_bind((Ipv4Addr::UNSPECIFIED, 0))?);
+        Ok(self.sockets.len() - 1)
+    }
+}
+
+#[repr(C)]
+struct VifCtl {
+    vifi: u16,
+    flags: u8,
+    threshold: u8,
+    rate_limit: u32,
+    /// local address or interface index with VIFF_USE_IFINDEX
+    ifindex: libc::c_int,
+    remote_address: u32,
+}
+
+#[repr(C)]
+struct MfcCtl {
+    origin: u32,
+    group: u32,
+    parent: u16,
+    ttls: [u8; MAXVIFS],
+    pkt_cnt: u32,
+    byte_cnt: u32,
+    wrong_if: u32,
+    expire: libc::c_int,
+}
+
+/// An IGMP proxy between one upstream and up to 31 downstream interfaces. Requires CAP_NET_ADMIN
+/// and that no other multicast routing daemon runs. Forwards traffic from the upstream to the
+/// downstream interfaces only. Upstream joins which fail (traced as GroupJoined with error) do not
+/// stop the proxy, they are retried until the group has no downstream members.
+#[derive(Debug)]
+pub struct IgmpProxy {
+    socket: RawSocket,
+    upstream: Upstream,
+    downstreams: Vec<Downstream>,
+    table: MembershipTable,
+    /// forwarding cache entries (source, group) of upstream traffic to groups with members
+    routes: HashSet<(Ipv4Addr, Ipv4Addr)>,
+    /// pending group specific queries: downstream index, group, time and remaining count
+    group_queries: Vec<(u32, Ipv4Addr, Instant, u32)>,
+    shutdown: ShutdownHandle,
+}
+
+impl IgmpProxy {
+
+    /// Creates the proxy for the upstream and downstream interfaces (names): initializes the
+    /// kernel multicast routing (MRT_INIT) with a virtual interface per interface. The proxy
+    /// queries the downstream interfaces unless a router with a lower address is querier there.
+    pub fn new(upstream: &str, downstreams: &[&str]) -> Result<IgmpProxy> {
+        if downstreams.is_empty() || downstreams.len() >= MAXVIFS {
+            return Err(Error::new(ErrorKind::InvalidInput, "1 to 31 downstream interfaces required"));
+        }
+        let upstream_index = interface_index(upstream)?;
+        let socket = RawSocketBuilder::ipv4(libc::IPPROTO_IGMP as u8).multicast_hops(1).build()?;
+        let fd = socket.as_raw_fd();
+        sockopt::set_int_option(fd, libc::IPPROTO_IP, MRT_INIT, 1)?;
+        sockopt::set_int_option(fd, libc::IPPROTO_IP, libc::IP_PKTINFO, 1)?;
+        SockRef::from(&socket).set_multicast_loop_v4(false)?;
+        // IP router alert option of IGMP messages
+        sockopt::set_option(fd, libc::IPPROTO_IP, libc::IP_OPTIONS, &[0x94_u8, 0x04, 0, 0])?;
+        add_vif(&socket, 0, upstream_index)?;
+
+        let addresses = super::netlink::addresses()?;
+        let now = Instant::now();
+        let mut proxy_downstreams = Vec::with_capacity(downstreams.len());
+        for (i, name) in downstreams.iter().enumerate() {
+            let index = interface_index(name)?;
+            let address = addresses.iter().filter(|a| a.index == index)
+                .find_map(|a| match a.address {
+                    IpAddr::V4(address) => Some(address),
+                    IpAddr::V6(_) => None,
+                })
+                .ok_or_else(|| Error::new(ErrorKind::AddrNotAvailable, format!("{} has no IPv4 address", name)))?;
+            let vif = (i + 1) as u16;
+            add_vif(&socket, vif, index)?;
+            proxy_downstreams.push(Downstream {
+                index,
+                vif,
+                address,
+                other_querier_until: None,
+                next_query: now,
+                startup_queries: ROBUSTNESS,
+            });
+        }
+        Ok(IgmpProxy {
+            socket,
+            upstream: Upstream::new(upstream_index),
+            downstreams: proxy_downstreams,
+            table: MembershipTable::new(),
+            routes: HashSet::new(),
+            group_queries: Vec::new(),
+            shutdown: ShutdownHandle::new(),
+        })
+    }
+
+    /// Returns the memberships of the downstream interfaces.
+    pub fn memberships(&self) -> &MembershipTable {
+        &self.table
+    }
+
+    /// Returns whether the proxy is querier on the downstream interface (index).
+    pub fn is_querier(&self, interface: u32) -> bool {
+        self.downstreams.iter().any(|d| d.index == interface && d.is_querier(Instant::now()))
+    }
+
+    /// Returns a handle which shuts the proxy down from other threads: blocked and later
+    /// `process` calls fail and `run` returns Ok(()), see `shutdown::ShutdownHandle`.
+    pub fn shutdown_handle(&self) -> Result<ShutdownHandle> {
+        self.shutdown.share()
+    }
+
+    /// Waits at most `timeout` for an IGMP message or kernel upcall and processes it, then sends
+    /// due queries and expires memberships.
+    pub fn process(&mut self, timeout: Duration) -> Result<()> {
+        let now = Instant::now();
+        let wait = [Some(now + timeout), self.next_timer()].iter().flatten().min().copied()
+            .map(|until| until.saturating_duration_since(now))
+            .unwrap_or(timeout)
+            .max(Duration::from_millis(1));
+        self.socket.set_read_timeout(Some(wait))?;
+        let mut buffer = [0_u8; 1500];
+        let received = self.shutdown.wait_readable(self.socket.as_fd())
+            .and_then(|_| super::cmsg::recv_msg(self.socket.as_raw_fd(), &mut buffer, 0));
+        match received {
+            Ok(message) => {
+                let index = message.control.iter().find_map(|control| match control {
+                    ControlMessage::Other { level: libc::IPPROTO_IP, msg_type: libc::IP_PKTINFO, data }
+                        if data.len() >= 4 => Some(i32::from_ne_bytes([data[0], data[1], data[2], data[3]]) as u32),
+                    _ => None,
+                });
+                self.handle_packet(&buffer[..message.len], index)?;
+            },
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => (),
+            Err(e) => return Err(e),
+        }
+        self.timers()
+    }
+
+    /// Runs the proxy until an error occurs or it is shut down.
+    pub fn run(&mut self) -> Result<()> {
+        loop {
+            match self.process(Duration::from_secs(1)) {
+                Err(e) if is_shutdown_error(&e) => return Ok(()),
+                result => result?,
+            }
+        }
+    }
+
+    fn next_timer(&self) -> Option<Instant> {
+        let queries = self.downstreams.iter().map(|d| d.next_query);
+        let group_queries = self.group_queries.iter().map(|q| q.2);
+        queries.chain(group_queries).chain(self.table.next_expiry()).min()
+    }
+
+    /// Handles a packet of the raw socket: an IP packet with IGMP message or a kernel upcall.
+    fn handle_packet(&mut self, packet: &[u8], index: Option<u32>) -> Result<()> {
+        if packet.len() < 20 {
+            return Ok(());
+        }
+        // upcalls (struct igmpmsg) have zero in the protocol field of the IP header
+        if packet[9] == 0 {
+            if let Some((source, group)) = add_route(&mut self.routes, &self.table, packet) {
+                self.update_route(&source, &group)?;
+            }
+            return Ok(());
+        }
+        let source = Ipv4Addr::new(packet[12], packet[13], packet[14], packet[15]);
+        let header_len = 4 * (packet[0] & 0x0f) as usize;
+        let message = match packet.get(header_len..).and_then(IgmpMessage::parse) {
+            Some(message) => message,
+            None => return Ok(()),
+        };
+        let now = Instant::now();
+        let downstream = match self.downstreams.iter_mut().find(|d| Some(d.index) == index) {
+            Some(downstream) if source != downstream.address => downstream,
+            _ => return Ok(()),
+        };
+        let (interface, querier) = (downstream.index, downstream.is_querier(now));
+        match &message {
+            IgmpMessage::Query { .. } if u32::from(source) < u32::from(downstream.address) => {
+                downstream.other_querier_until = Some(now + OTHER_QUERIER_PRESENT_INTERVAL);
+            },
+            IgmpMessage::Query { .. } => (),
+            IgmpMessage::Leave { group } => {
+                if self.table.leave(interface, *group, now) && querier {
+                    self.query_group(interface, *group, now);
+                }
+            },
+            _ => {
+                let joined = self.table.apply(interface, &message, now);
+                if let IgmpMessage::ReportV3 { records } = &message {
+                    let leaves = records.iter()
+                        .filter(|r| r.record_type == CHANGE_TO_INCLUDE_MODE && r.sources.is_empty());
+                    for record in leaves {
+                        if querier && self.table.interfaces(&record.group).contains(&interface) {
+                            self.query_group(interface, record.group, now);
+                        }
+                    }
+                }
+                for group in joined {
+                    self.upstream.join(group);
+                }
+                self.update_routes()?;
+            },
+        }
+        Ok(())
+    }
+
+    /// Schedules the group specific queries of the group on the downstream interface.
+    fn query_group(&mut self, interface: u32, group: Ipv4Addr, now: Instant) {
+        self.group_queries.retain(|q| (q.0, q.1) != (interface, group));
+        self.group_queries.push((interface, group, now, ROBUSTNESS));
+    }
+
+    /// Sends the due queries and removes expired memberships, leaving their groups upstream and
+    /// deleting their forwarding entries, and retries failed upstream joins.
+    fn timers(&mut self) -> Result<()> {
+        let now = Instant::now();
+        for i in 0..self.downstreams.len() {
+            let downstream = &self.downstreams[i];
+            if downstream.next_query > now {
+                continue;
+            }
+            let (index, querier) = (downstream.index, downstream.is_querier(now));
+            if querier {
+                let query = IgmpMessage::Query {
+                    max_response_time: QUERY_RESPONSE_INTERVAL,
+                    group: Ipv4Addr::UNSPECIFIED,
+                    sources: Vec::new(),
+                };
+                self.send(index, &ALL_SYSTEMS, &query)?;
+            }
+            let downstream = &mut self.downstreams[i];
+            if downstream.startup_queries > 0 {
+                downstream.startup_queries -= 1;
+                downstream.next_query = now + STARTUP_QUERY_INTERVAL;
+            } else {
+                downstream.next_query = now + QUERY_INTERVAL;
+            }
+        }
+        let due: Vec<(u32, Ipv4Addr)> = self.group_queries.iter().filter(|q| q.2 <= now).map(|q| (q.0, q.1)).collect();
+        for (index, group) in due {
+            let query = IgmpMessage::Query { max_response_time: LAST_MEMBER_QUERY_INTERVAL, group, sources: Vec::new() };
+            self.send(index, &group, &query)?;
+        }
+        self.group_queries.iter_mut().filter(|q| q.2 <= now).for_each(|q| {
+            q.2 = now + LAST_MEMBER_QUERY_INTERVAL;
+            q.3 -= 1;
+        });
+        self.group_queries.retain(|q| q.3 > 0);
+
+        let expired = self.table.next_expiry().is_some_and(|expiry| expiry <= now);
+        let left = self.table.expire(now);
+        for group in &left {
+            self.upstream.leave(*group);
+            let routes: Vec<(Ipv4Addr, Ipv4Addr)> = self.routes.iter().filter(|r| r.1 == *group).copied().collect();
+            for (source, group) in routes {
+                self.routes.remove(&(source, group));
+                self.delete_route(&source, &group)?;
+            }
+        }
+        if expired {
+            self.update_routes()?;
+        }
+        self.upstream.retry(now);
+        Ok(())
+    }
+
+    /// Sends an IGMP message on the downstream interface.
+    fn send(&self, index: u32, destination: &Ipv4Addr, message: &IgmpMessage) -> Result<()> {
+        let interface = libc::ip_mreqn {
+            imr_multiaddr: libc::in_addr { s_addr: 0 },
+            imr_address: libc::in_addr { s_addr: 0 },
+            imr_ifindex: index as libc::c_int,
+        };
+        sockopt::set_option(self.socket.as_raw_fd(), libc::IPPROTO_IP, libc::IP_MULTICAST_IF, &interface)?;
+        self.socket.send_to(&message.encode(), IpAddr::V4(*destination))?;
+        Ok(())
+    }
+
+    fn update_routes(&self) -> Result<()> {
+        for (source, group) in &self.routes {
+            self.update_route(source, group)?;
+        }
+        Ok(())
+    }
+
+    /// Sets the forwarding entry for traffic of the source to the group from the upstream to the
+    /// downstream interfaces with members.
+    fn update_route(&self, source: &Ipv4Addr, group: &Ipv4Addr) -> Result<()> {
+        let members = self.table.interfaces(group);
+        let mut ttls = [0_u8; MAXVIFS];
+        for downstream in self.downstreams.iter().filter(|d| members.contains(&d.index)) {
+            ttls[downstream.vif as usize] = 1;
+        }
+        let mfc = MfcCtl {
+            origin: u32::from_ne_bytes(source.octets()),
+            group: u32::from_ne_bytes(group.octets()),
+            parent: 0,
+            ttls,
+            pkt_cnt: 0,
+            byte_cnt: 0,
+            wrong_if: 0,
+            expire: 0,
+        };
+        sockopt::set_option(self.socket.as_raw_fd(), libc::IPPROTO_IP, MRT_ADD_MFC, &mfc)
+    }
+
+    /// Deletes the forwarding entry for traffic of the source to the group.
+    fn delete_route(&self, source: &Ipv4Addr, group: &Ipv4Addr) -> Result<()> {
+        let mfc = MfcCtl {
+            origin: u32::from_ne_bytes(source.octets()),
+            group: u32::from_ne_bytes(group.octets()),
+            parent: 0,
+            ttls: [0; MAXVIFS],
+            pkt_cnt: 0,
+            byte_cnt: 0,
+            wrong_if: 0,
+            expire: 0,
+        };
+        sockopt::set_option(self.socket.as_raw_fd(), libc::IPPROTO_IP, MRT_DEL_MFC, &mfc)
+    }
+}
+
+/// Records the route (source, group) of a kernel upcall for traffic without forwarding entry and
+/// returns it if the group has members. Without members no entry is added, the kernel drops the
+/// traffic until its unresolved entry expires and repeats the upcall for later traffic.
+fn add_route(routes: &mut HashSet<(Ipv4Addr, Ipv4Addr)>, table: &MembershipTable, upcall: &[u8])
+             -> Option<(Ipv4Addr, Ipv4Addr)> {
+    if upcall.len() < 20 || upcall[8] != IGMPMSG_NOCACHE || upcall[10] != 0 {
+        return None;
+    }
+    let source = Ipv4Addr::new(upcall[12], upcall[13], upcall[14], upcall[15]);
+    let group = Ipv4Addr::new(upcall[16], upcall[17], upcall[18], upcall[19]);
+    if table.interfaces(&group).is_empty() {
+        return None;
+    }
+    routes.insert((source, group));
+    Some((source, group))
+}
+
+fn interface_index(name: &str) -> Result<u32> {
+    let c_name = std::ffi::CString::new(name).map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+    match unsafe { libc::if_nametoindex(c_name.as_ptr()) } {
+        0 => Err(Error::new(ErrorKind::NotFound, format!("no interface {}", name))),
+        index => Ok(index),
+    }
+}
+
+fn add_vif(socket: &RawSocket, vif: u16, index: u32) -> Result<()> {
+    let vif = VifCtl {
+        vifi: vif,
+        flags: VIFF_USE_IFINDEX,
+        threshold: 1,
+        rate_limit: 0,
+        ifindex: index as libc::c_int,
+        remote_address: 0,
+    };
+    sockopt::set_option(socket.as_raw_fd(), libc::IPPROTO_IP, MRT_ADD_VIF, &vif)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_messages() {
+        let group: Ipv4Addr = "239.1.2.3".parse().unwrap();
+        let report = IgmpMessage::ReportV2 { group };
+        let encoded = report.encode();
+        assert_eq!(encoded, [0x16, 0, 0xf8, 0xfa, 239, 1, 2, 3]);
+        assert_eq!(IgmpMessage::parse(&encoded), Some(report));
+
+        let report = IgmpMessage::ReportV3 { records: vec![
+            GroupRecord { record_type: CHANGE_TO_EXCLUDE_MODE, group, sources: Vec::new() },
+            GroupRecord { record_type: ALLOW_NEW_SOURCES, group, sources: vec!["192.0.2.9".parse().unwrap()] },
+        ] };
+        assert_eq!(IgmpMessage::parse(&report.encode()), Some(report));
+
+        let query = IgmpMessage::Query {
+            max_response_time: Duration::from_secs(10),
+            group: Ipv4Addr::UNSPECIFIED,
+            sources: Vec::new(),
+        };
+        assert_eq!(IgmpMessage::parse(&query.encode()), Some(query));
+        // IGMPv3 query with the floating point max response code 0x8a (exponent 0, mantissa 10)
+        let mut query = vec![0x11, 0x8a, 0, 0, 0, 0, 0, 0, 2, 125, 0, 0];
+        let checksum = internet_checksum(&query);
+        query[2..4].copy_from_slice(&checksum.to_be_bytes());
+        assert_eq!(IgmpMessage::parse(&query).unwrap(), IgmpMessage::Query {
+            max_response_time: Duration::from_millis(20800),
+            group: Ipv4Addr::UNSPECIFIED,
+            sources: Vec::new(),
+        });
+        query[5] = 1;
+        assert_eq!(IgmpMessage::parse(&query), None);
+    }
+
+    #[test]
+    fn test_membership_table() {
+        let group: Ipv4Addr = "239.1.2.3".parse().unwrap();
+        let now = Instant::now();
+        let mut table = MembershipTable::new();
+        assert_eq!(table.apply(3, &IgmpMessage::ReportV2 { group }, now), vec![group]);
+        assert_eq!(table.apply(4, &IgmpMessage::ReportV1 { group }, now), Vec::<Ipv4Addr>::new());
+        // link local groups are not proxied
+        assert_eq!(table.apply(3, &IgmpMessage::ReportV2 { group: "224.0.0.251".parse().unwrap() }, now), Vec::<Ipv4Addr>::new());
+        assert_eq!(table.interfaces(&group), vec![3, 4]);
+
+        assert!(table.leave(4, group, now));
+        assert!(!table.leave(5, group, now));
+        assert_eq!(table.expire(now + Duration::from_secs(3)), Vec::<Ipv4Addr>::new());
+        assert_eq!(table.interfaces(&group), vec![3]);
+
+        let leave = IgmpMessage::ReportV3 { records: vec![
+            GroupRecord { record_type: CHANGE_TO_INCLUDE_MODE, group, sources: Vec::new() },
+        ] };
+        table.apply(3, &leave, now + Duration::from_secs(3));
+        assert_eq!(table.expire(now + Duration::from_secs(6)), vec![group]);
+        assert!(table.groups().is_empty());
+    }
+
+    #[test]
+    fn test_add_route() {
+        let group: Ipv4Addr = "239.1.2.3".parse().unwrap();
+        let source: Ipv4Addr = "192.0.2.9".parse().unwrap();
+        // struct igmpmsg of a NOCACHE upcall from vif 0
+        let mut upcall = [0_u8; 20];
+        upcall[8] = IGMPMSG_NOCACHE;
+        upcall[12..16].copy_from_slice(&source.octets());
+        upcall[16..20].copy_from_slice(&group.octets());
+        let mut routes = HashSet::new();
+        let mut table = MembershipTable::new();
+        assert_eq!(add_route(&mut routes, &table, &upcall), None);
+        assert!(routes.is_empty());
+
+        table.apply(3, &IgmpMessage::ReportV2 { group }, Instant::now());
+        assert_eq!(add_route(&mut routes, &table, &upcall), Some((source, group)));
+        assert_eq!(add_route(&mut routes, &table, &upcall), Some((source, group)));
+        assert_eq!(routes.len(), 1);
+    }
+
+    #[test]
+    fn test_upstream() {
+        let mut upstream = Upstream::new(interface_index("lo").unwrap());
+        let groups: Vec<Ipv4Addr> = (1..=GROUPS_PER_SOCKET as u8 + 1).map(|i| Ipv4Addr::new(239, 255, 80, i)).collect();
+        for group in &groups {
+            upstream.join(*group);
+        }
+        assert_eq!((upstream.groups.len(), upstream.sockets.len()), (groups.len(), 2));
+        upstream.leave(groups[0]);
+        // the room of the left group is used again
+        upstream.join(Ipv4Addr::new(239, 255, 81, 1));
+        assert_eq!((upstream.groups.len(), upstream.sockets.len()), (groups.len(), 2));
+    }
+
+    #[test]
+    fn test_failed_upstream_join() {
+        let group = Ipv4Addr::new(239, 255, 80, 1);
+        // no such interface
+        let mut upstream = Upstream::new(u32::MAX);
+        upstream.join(group);
+        assert!(upstream.groups.is_empty());
+        assert!(upstream.failed.contains(&group));
+        let now = Instant::now();
+        upstream.retry(now);
+        assert!(upstream.failed.contains(&group));
+        assert_eq!(upstream.next_retry, now + JOIN_RETRY_INTERVAL);
+        upstream.leave(group);
+        assert!(upstream.failed.is_empty());
+    }
+}
@@ -0,0 +1,255 @@
+/// Largest output of `zlib_decompress`, protects against decompression bombs.
+const MAX_OUTPUT: usize = 1 << 20;
+
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83,
+                                99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769,
+                                  1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11,
+                                  12, 12, 13, 13];
+/// Order of the code length code lengths of a dynamic block.
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/// Decompresses zlib (RFC 1950) data, None if it is invalid or its checksum does not match.
+pub(crate) fn zlib_decompress(data: &[u8]) -> Option<Vec<u8>> {
+    let (cmf, flg) = (*data.first()?, *data.get(1)?);
+    if cmf & 0x0f != 8 || !(((cmf as u16) << 8) | flg as u16).is_multiple_of(31) || flg & 0x20 != 0 {
+        return None;
+    }
+    let (output, consumed) = inflate(&data[2..])?;
+    let checksum = data.get(2 + consumed..2 + consumed + 4)?;
+    if u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]) != adler32(&output) {
+        return None;
+    }
+    Some(output)
+}
+
+/// Decompresses raw deflate (RFC 1951) data, returns the output and the number of bytes consumed.
+pub(crate) fn inflate(data: &[u8]) -> Option<(Vec<u8>, usize)> {
+    let mut bits = Bits { data, position: 0, buffer: 0, count: 0 };
+    let mut output = Vec::new();
+    loop {
+        let last = bits.bits(1)? == 1;
+        match bits.bits(2)? {
+            0 => stored(&mut bits, &mut output)?,
+            1 => {
+                let mut lengths = [0_u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                codes(&mut bits, &mut output, &Huffman::new(&lengths)?, &Huffman::new(&[5; 30])?)?;
+            },
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut bits)?;
+                codes(&mut bits, &mut output, &literals, &distances)?;
+            },
+            _ => return None,
+        }
+        if last {
+            return Some((output, bits.position));
+        }
+    }
+}
+
+/// Reads the bits of deflate data, least significant bit first.
+struct Bits<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl Bits<'_> {
+
+    fn bits(&mut self, n: u32) -> Option<u32> {
+        while self.count < n {
+            self.buffer |= (*self.data.get(self.position)? as u32) << self.count;
+            self.position += 1;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1_u32 << n) - 1);
+        self.buffer >>= n;
+        self.count -= n;
+        Some(value)
+    }
+}
+
+/// Canonical Huffman code given by the number of codes per length and the symbols in code order.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+
+    fn new(lengths: &[u8]) -> Option<Huffman> {
+        let mut counts = [0_u16; 16];
+        for length in lengths {
+            counts[*length as usize] += 1;
+        }
+        let mut offsets = [0_u16; 16];
+        for length in 1..15 {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0_u16; lengths.len()];
+        for (symbol, length) in lengths.iter().enumerate().filter(|(_, l)| **l != 0) {
+            symbols[offsets[*length as usize] as usize] = symbol as u16;
+            offsets[*length as usize] += 1;
+        }
+        Some(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, bits: &mut Bits<'_>) -> Option<u16> {
+        let (mut code, mut first, mut index) = (0_i32, 0_i32, 0_i32);
+        for length in 1..16 {
+            code |= bits.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - count < first {
+                return self.symbols.get((index + code - first) as usize).copied();
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        None
+    }
+}
+
+fn stored(bits: &mut Bits<'_>, output: &mut Vec<u8>) -> Option<()> {
+    // the remaining bits of the current byte are skipped
+    bits.buffer = 0;
+    bits.count = 0;
+    let header = bits.data.get(bits.position..bits.position + 4)?;
+    let len = u16::from_le_bytes([header[0], header[1]]);
+    if len != !u16::from_le_bytes([header[2], header[3]]) || output.len() + len as usize > MAX_OUTPUT {
+        return None;
+    }
+    let start = bits.position + 4;
+    output.extend_from_slice(bits.data.get(start..start + len as usize)?);
+    bits.position = start + len as usize;
+    Some(())
+}
+
+fn dynamic_codes(bits: &mut Bits<'_>) -> Option<(Huffman, Huffman)> {
+    let literal_count = bits.bits(5)? as usize + 257;
+    let distance_count = bits.bits(5)? as usize + 1;
+    let code_length_count = bits.bits(4)? as usize + 4;
+    if literal_count > 286 || distance_count > 30 {
+        return None;
+    }
+    let mut code_lengths = [0_u8; 19];
+    for index in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+        code_lengths[*index] = bits.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths)?;
+    let mut lengths: Vec<u8> = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (value, repeat) = match code_length_code.decode(bits)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => (*lengths.last()?, 3 + bits.bits(2)?),
+            17 => (0, 3 + bits.bits(3)?),
+            18 => (0, 11 + bits.bits(7)?),
+            _ => return None,
+        };
+        if lengths.len() + repeat as usize > literal_count + distance_count {
+            return None;
+        }
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths[256] == 0 {
+        return None;
+    }
+    Some((Huffman::new(&lengths[..literal_count])?, Huffman::new(&lengths[literal_count..])?))
+}
+
+fn codes(bits: &mut Bits<'_>, output: &mut Vec<u8>, literals: &Huffman, distances: &Huffman) -> Option<()> {
+    loop {
+        let symbol = literals.decode(bits)? as usize;
+        if symbol < 256 {
+            output.push(symbol as u8);
+        } else if symbol == 256 {
+            return Some(());
+        } else {
+            let symbol = symbol - 257;
+            let length = *LENGTH_BASE.get(symbol)? as usize + bits.bits(LENGTH_EXTRA[symbol] as u32)? as usize;
+            let symbol = distances.decode(bits)? as usize;
+            let distance = *DISTANCE_BASE.get(symbol)? as usize + bits.bits(DISTANCE_EXTRA[symbol] as u32)? as usize;
+            if distance > output.len() || output.len() + length > MAX_OUTPUT {
+                return None;
+            }
+            for _ in 0..length {
+                output.push(output[output.len() - distance]);
+            }
+        }
+        if output.len() > MAX_OUTPUT {
+            return None;
+        }
+    }
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1_u32, 0_u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+/// Fixtures of the tests of this module and of the modules receiving compressed data.
+#[cfg(test)]
+pub(crate) mod fixture {
+
+    /// Session description of the compressed fixtures.
+    pub(crate) const SDP: &str = "v=0\r\no=- 1311738121 1311738121 IN IP4 192.168.1.10\r\ns=Stage left\r\n\
+        c=IN IP4 239.69.1.10/32\r\nt=0 0\r\nm=audio 5004 RTP/AVP 96\r\na=rtpmap:96 L24/48000/2\r\n";
+
+    pub(crate) fn hex(text: &str) -> Vec<u8> {
+        (0..text.len()).step_by(2).map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap()).collect()
+    }
+
+    /// `SDP` compressed with dynamic Huffman codes (zlib header and Adler-32 checksum).
+    pub(crate) fn compressed_sdp() -> Vec<u8> {
+        hex("78014dcb310a02311040d13e903bcc054c66263126c214960b2241c53ee8ae082ebb68f4fc06b1b0fbc5fb6f\
+            41ad26590039a2958bc4f49fdd0ebaec81121b0ad190a1c69f72a8e5dac3bd1faa5667f92176c984f435d6b15655109a1ea5\
+            bc2eb70996881ef6c76c37a70c296855e451e7b1cceb1460cbdefa8888b68d1fa48d21eb")
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use fixture::{SDP, compressed_sdp, hex};
+
+    #[test]
+    fn test_stored_and_dynamic() {
+        let stored = hex("78010194006bff763d300d0a6f3d2d2031333131373338313231203133313137333831323120494e20495034\
+            203139322e3136382e312e31300d0a733d5374616765206c6566740d0a633d494e20495034203233392e36392e312e31302f\
+            33320d0a743d3020300d0a6d3d617564696f2035303034205254502f4156502039360d0a613d7274706d61703a3936204c32\
+            342f34383030302f320d0aa48d21eb");
+        assert_eq!(zlib_decompress(&stored).unwrap(), SDP.as_bytes());
+        let dynamic = compressed_sdp();
+        assert_eq!(zlib_decompress(&dynamic).unwrap(), SDP.as_bytes());
+        let mut corrupted = dynamic.clone();
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 1;
+        assert_eq!(zlib_decompress(&corrupted), None);
+        assert_eq!(zlib_decompress(&dynamic[..40]), None);
+    }
+
+    #[test]
+    fn test_fixed() {
+        let fixed = hex("78da4b4c4a4e1c45831b31303231b3b0b2b173707271f3f0f2f10b080a098b888a894b484a49cbc8cac92b282a2\
+            9aba8aaa96b686a69ebe8eae91b181a199b989a995b585a59dbd8dad93b383a39bbb8bab97b787a79fbf8faf9070406058784\
+            868547444645c7c4c6c52700ed48494d4bcfc8cccacec9cdcb2f282c2a2e292d2bafa8acaaaea9adab6f686c6a6e696d6befe\
+            8eceaeee9edeb9f3071d2e42953a74d9f3173d6ec3973e7cd5fb070d1e2254b972d5fb172d5ea356bd7addfb071d3e62d5bb7\
+            6ddfb173d7ee3d7bf7ed3f70f0d0e123478f1d3f71f2d4e93367cf9dbf70f1d2e52b57af5dbf71f3d6ed3b77efdd7ff0f0d1e3\
+            274f9f3d7ff1f2d5eb376fdfbdfff0f1d3e72f5fbf7dfff1f3d7ef3f7ffffd0700bc212058");
+        let mut expected = b"abc".repeat(140);
+        expected.extend(0..=255_u8);
+        assert_eq!(zlib_decompress(&fixed).unwrap(), expected);
+    }
+}
@@ -0,0 +1,211 @@
+use std::{
+    io::Result,
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use super::IpInterface;
+#[cfg(target_os = "linux")]
+use super::netlink::NetlinkSocket;
+#[cfg(all(target_os = "linux", feature = "trace"))]
+use super::netlink::{LinkInfo, NetlinkMessage};
+
+/// Time to live of the process wide cache if netlink invalidation is available.
+#[cfg(target_os = "linux")]
+const GLOBAL_TTL_NETLINK: Duration = Duration::from_secs(30);
+
+/// Time to live of the process wide cache without netlink invalidation.
+const GLOBAL_TTL: Duration = Duration::from_secs(1);
+
+/// Memoizes the list of IP interface configurations of the system.
+/// The list is retrieved again when it is older than the configured time to live, when it has been
+/// invalidated explicitly or - with netlink invalidation enabled - when the kernel reported a
+/// change of links or addresses since the last retrieval.
+pub struct InterfaceCache {
+    ttl: Duration,
+    state: Mutex<CacheState>,
+    #[cfg(target_os = "linux")]
+    monitor: Option<NetlinkSocket>,
+    /// last reported state (up) per link index
+    #[cfg(all(target_os = "linux", feature = "trace"))]
+    links: Mutex<std::collections::HashMap<u32, bool>>,
+}
+
+struct CacheState {
+    interfaces: Option<Arc<Vec<IpInterface>>>,
+    retrieved: Instant,
+}
+
+impl InterfaceCache {
+
+    /// Creates a cache whose entries expire after the given time to live.
+    pub fn new(ttl: Duration) -> InterfaceCache {
+        InterfaceCache {
+            ttl,
+            state: Mutex::new(CacheState { interfaces: None, retrieved: Instant::now() }),
+            #[cfg(target_os = "linux")]
+            monitor: None,
+            #[cfg(all(target_os = "linux", feature = "trace"))]
+            links: Mutex::new(std::collections::HashMap::new()),
+        }
+    }
+
+    /// Creates a cache that is additionally invalidated whenever the kernel reports a change of
+    /// links or IPv4/IPv6 addresses.
+    #[cfg(target_os = "linux")]
+    pub fn with_netlink_invalidation(ttl: Duration) -> Result<InterfaceCache> {
+        let groups = (libc::RTMGRP_LINK | libc::RTMGRP_IPV4_IFADDR | libc::RTMGRP_IPV6_IFADDR) as u32;
+        let monitor = NetlinkSocket::new(libc::NETLINK_ROUTE, groups)?;
+        monitor.set_nonblocking(true)?;
+        let mut cache = InterfaceCache::new(ttl);
+        cache.monitor = Some(monitor);
+        Ok(cache)
+    }
+
+    /// Returns the process wide cache used by the crate itself (e.g. for interface index lookups
+    /// when creating IPv6 multicast sockets).
+    pub fn global() -> &'static InterfaceCache {
+        static GLOBAL: OnceLock<InterfaceCache> = OnceLock::new();
+        GLOBAL.get_or_init(|| {
+            #[cfg(target_os = "linux")]
+            if let Ok(cache) = InterfaceCache::with_netlink_invalidation(GLOBAL_TTL_NETLINK) {
+                return cache;
+            }
+            InterfaceCache::new(GLOBAL_TTL)
+        })
+    }
+
+    /// Returns the time to live of the cached list.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Returns the list of IP interface configurations, retrieving it from the system if the
+    /// cached list is missing, expired or invalidated.
+    pub fn interfaces(&self) -> Result<Arc<Vec<IpInterface>>> {
+        let changed = self.drain_change_notifications();
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(interfaces) = &state.interfaces {
+            if !changed && state.retrieved.elapsed() < self.ttl {
+                return Ok(interfaces.clone());
+            }
+        }
+        let interfaces = Arc::new(IpInterface::retrieve_ip_interfaces()?);
+        state.interfaces = Some(interfaces.clone());
+        state.retrieved = Instant::now();
+        Ok(interfaces)
+    }
+
+    /// Drops the cached list, the next access retrieves it from the system again.
+    pub fn invalidate(&self) {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).interfaces = None;
+    }
+
+    /// Reads all pending change notifications, returns whether there were any.
+    #[cfg(target_os = "linux")]
+    fn drain_change_notifications(&self) -> bool {
+        let monitor = match &self.monitor {
+            Some(monitor) => monitor,
+            None => return false,
+        };
+        let mut changed = false;
+        loop {
+            match monitor.receive() {
+                #[cfg(feature = "trace")]
+                Ok(messages) => {
+                    changed = true;
+                    self.trace_link_changes(&messages);
+                },
+                #[cfg(not(feature = "trace"))]
+                Ok(_) => changed = true,
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    if changed {
+                        trace_event!(crate::trace::TraceEvent::InterfacesChanged { lost: false });
+                    }
+                    return changed;
+                },
+                // e.g. ENOBUFS if notifications have been lost, the cache must be refreshed
+                Err(_) => {
+                    trace_event!(crate::trace::TraceEvent::InterfacesChanged { lost: true });
+                    return true;
+                },
+            }
+        }
+    }
+
+    /// Reports the links whose state changed with the notifications, removed links as down.
+    #[cfg(all(target_os = "linux", feature = "trace"))]
+    fn trace_link_changes(&self, messages: &[NetlinkMessage]) {
+        let running = (libc::IFF_UP | libc::IFF_RUNNING) as u32;
+        let mut changes = Vec::new();
+        let mut links = self.links.lock().unwrap_or_else(|e| e.into_inner());
+        for message in messages {
+            let (index, up) = match message.msg_type {
+                libc::RTM_NEWLINK => match LinkInfo::from_message(message) {
+                    Some(link) => (link.index, link.flags & running == running),
+                    None => continue,
+                },
+                libc::RTM_DELLINK if message.payload.len() >= 8 => {
+                    let p = &message.payload;
+                    (u32::from_ne_bytes([p[4], p[5], p[6], p[7]]), false)
+                },
+                _ => continue,
+            };
+            if links.insert(index, up) != Some(up) {
+                changes.push((index, up));
+            }
+        }
+        // the handler may use the cache
+        drop(links);
+        for (index, up) in changes {
+            trace_event!(crate::trace::TraceEvent::LinkChanged { index, up });
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn drain_change_notifications(&self) -> bool {
+        false
+    }
+}
+
+impl std::fmt::Debug for InterfaceCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut d = f.debug_struct("InterfaceCache");
+        d.field("ttl", &self.ttl);
+        #[cfg(target_os = "linux")]
+        d.field("netlink_invalidation", &self.monitor.is_some());
+        d.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::InterfaceProvider;
+
+    #[test]
+    fn test_cache_expiry() {
+        let cache = InterfaceCache::new(Duration::from_secs(3600));
+        let first = cache.interfaces().unwrap();
+        let second = cache.interfaces().unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        cache.invalidate();
+        let third = cache.interfaces().unwrap();
+        assert!(!Arc::ptr_eq(&first, &third));
+
+        let cache = InterfaceCache::new(Duration::from_secs(0));
+        let first = cache.interfaces().unwrap();
+        assert!(!Arc::ptr_eq(&first, &cache.interfaces().unwrap()));
+    }
+
+    #[test]
+    fn test_find_index() {
+        let cache = InterfaceCache::global();
+        let loopback = cache.interfaces().unwrap().iter().find(|i| i.is_loopback()).cloned();
+        if let Some(lo) = loopback {
+            assert_eq!(cache.find_index_by_address(&lo.address.ip(), false).unwrap(), Some(lo.index));
+        }
+        assert_eq!(cache.find_index_by_address(&"192.0.2.99".parse().unwrap(), false).unwrap(), None);
+    }
+}
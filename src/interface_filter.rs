@@ -0,0 +1,229 @@
+use std::{
+    borrow::Borrow,
+    iter::Filter,
+};
+
+use super::{AddressScope, IpInterface};
+
+/// The list of IP interface configurations of the host, see `IpInterface::retrieve_ip_interfaces`.
+/// Its iterators support the selection combinators of `IpInterfaceIterExt`, e.g.
+/// `interfaces.iter().up().multicast_capable().ipv6().named("eth*")`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IpInterfaces {
+    interfaces: Vec<IpInterface>,
+}
+
+impl IpInterfaces {
+
+    /// Retrieves the actual list of IP interface configurations from the system.
+    pub fn retrieve() -> std::io::Result<IpInterfaces> {
+        Ok(IpInterfaces { interfaces: IpInterface::retrieve_ip_interfaces()? })
+    }
+
+    /// Retrieves the list of IP interface configurations from the given provider.
+    pub fn retrieve_from(provider: &dyn super::InterfaceProvider) -> std::io::Result<IpInterfaces> {
+        provider.interfaces()
+    }
+
+    /// Returns an iterator over the interface configurations.
+    pub fn iter(&self) -> std::slice::Iter<'_, IpInterface> {
+        self.interfaces.iter()
+    }
+
+    /// Returns the number of interface configurations.
+    pub fn len(&self) -> usize {
+        self.interfaces.len()
+    }
+
+    /// Returns whether there are no interface configurations at all.
+    pub fn is_empty(&self) -> bool {
+        self.interfaces.is_empty()
+    }
+
+    /// Returns the interface configurations as slice.
+    pub fn as_slice(&self) -> &[IpInterface] {
+        &self.interfaces
+    }
+
+    /// Converts into the vector of interface configurations.
+    pub fn into_vec(self) -> Vec<IpInterface> {
+        self.interfaces
+    }
+}
+
+impl From<Vec<IpInterface>> for IpInterfaces {
+    fn from(interfaces: Vec<IpInterface>) -> IpInterfaces {
+        IpInterfaces { interfaces }
+    }
+}
+
+impl IntoIterator for IpInterfaces {
+    type Item = IpInterface;
+    type IntoIter = std::vec::IntoIter<IpInterface>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.interfaces.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a IpInterfaces {
+    type Item = &'a IpInterface;
+    type IntoIter = std::slice::Iter<'a, IpInterface>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.interfaces.iter()
+    }
+}
+
+/// Filter function type of the flag based `IpInterfaceIterExt` combinators.
+pub type InterfacePredicate<T> = fn(&T) -> bool;
+
+/// Selection combinators for iterators over `IpInterface` or `&IpInterface`. All combinators are
+/// lazy and can be chained arbitrarily.
+pub trait IpInterfaceIterExt: Iterator + Sized where Self::Item: Borrow<IpInterface> {
+
+    /// Keeps only interfaces that are administratively up.
+    fn up(self) -> Filter<Self, InterfacePredicate<Self::Item>> {
+        self.filter((|i| i.borrow().is_up()) as InterfacePredicate<Self::Item>)
+    }
+
+    /// Keeps only interfaces that support multicast.
+    fn multicast_capable(self) -> Filter<Self, InterfacePredicate<Self::Item>> {
+        self.filter((|i| i.borrow().supports_multicast()) as InterfacePredicate<Self::Item>)
+    }
+
+    /// Keeps only interface configurations with an IPv4 address.
+    fn ipv4(self) -> Filter<Self, InterfacePredicate<Self::Item>> {
+        self.filter((|i| i.borrow().address.is_ipv4()) as InterfacePredicate<Self::Item>)
+    }
+
+    /// Keeps only interface configurations with an IPv6 address.
+    fn ipv6(self) -> Filter<Self, InterfacePredicate<Self::Item>> {
+        self.filter((|i| i.borrow().address.is_ipv6()) as InterfacePredicate<Self::Item>)
+    }
+
+    /// Removes loopback interfaces.
+    fn non_loopback(self) -> Filter<Self, InterfacePredicate<Self::Item>> {
+        self.filter((|i| !i.borrow().is_loopback()) as InterfacePredicate<Self::Item>)
+    }
+
+    /// Keeps only interface configurations whose address has the given scope.
+    fn with_scope(self, scope: AddressScope) -> WithScope<Self> {
+        WithScope { iter: self, scope }
+    }
+
+    /// Keeps only interfaces whose name matches the glob pattern, where `*` matches any
+    /// sequence of characters and `?` matches a single character (e.g. "eth*", "wlp?s0").
+    fn named(self, pattern: &str) -> Named<Self> {
+        Named { iter: self, pattern: pattern.to_string() }
+    }
+}
+
+impl<I> IpInterfaceIterExt for I where I: Iterator, I::Item: Borrow<IpInterface> {}
+
+/// Iterator adapter returned by `IpInterfaceIterExt::named`.
+#[derive(Clone, Debug)]
+pub struct Named<I> {
+    iter: I,
+    pattern: String,
+}
+
+impl<I> Iterator for Named<I> where I: Iterator, I::Item: Borrow<IpInterface> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let pattern = self.pattern.as_bytes();
+        self.iter.by_ref().find(|i| glob_match(pattern, i.borrow().name.as_bytes()))
+    }
+}
+
+/// Iterator adapter returned by `IpInterfaceIterExt::with_scope`.
+#[derive(Clone, Debug)]
+pub struct WithScope<I> {
+    iter: I,
+    scope: AddressScope,
+}
+
+impl<I> Iterator for WithScope<I> where I: Iterator, I::Item: Borrow<IpInterface> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let scope = self.scope;
+        self.iter.by_ref().find(|i| i.borrow().scope() == scope)
+    }
+}
+
+/// Matches the name against a glob pattern supporting `*` and `?`.
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // position of the last '*' in the pattern and the name position it was matched against
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == b'?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == b'*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = backtrack {
+            p = star_p + 1;
+            n = star_n + 1;
+            backtrack = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use std::net::SocketAddr;
+
+    fn interface(name: &str, address: &str, flags: i32) -> IpInterface {
+        let address: SocketAddr = address.parse().unwrap();
+        IpInterface { index: 1, name: String::from(name), flags: flags as libc::c_uint, address,
+            net_mask: address, broadcast_address: None, p2p_address: None }
+    }
+
+    fn interfaces() -> IpInterfaces {
+        IpInterfaces::from(vec![
+            interface("lo", "127.0.0.1:0", libc::IFF_UP | libc::IFF_LOOPBACK),
+            interface("eth0", "192.168.1.2:0", libc::IFF_UP | libc::IFF_MULTICAST),
+            interface("eth0", "[fe80::1]:0", libc::IFF_UP | libc::IFF_MULTICAST),
+            interface("eth1", "10.0.0.1:0", libc::IFF_MULTICAST),
+            interface("wlan0", "[2001:db8::1]:0", libc::IFF_UP),
+        ])
+    }
+
+    fn names<'a, I: Iterator<Item = &'a IpInterface>>(iter: I) -> Vec<&'a str> {
+        iter.map(|i| i.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(b"eth*", b"eth0"));
+        assert!(glob_match(b"eth*", b"eth"));
+        assert!(glob_match(b"*", b"wlp3s0"));
+        assert!(glob_match(b"wlp?s0", b"wlp3s0"));
+        assert!(glob_match(b"*s0", b"wlp3s0"));
+        assert!(glob_match(b"e*h*1", b"eth0.1"));
+        assert!(!glob_match(b"eth?", b"eth10"));
+        assert!(!glob_match(b"eth*", b"veth0"));
+        assert!(!glob_match(b"", b"lo"));
+    }
+
+    #[test]
+    fn test_combinators() {
+        let ifs = interfaces();
+        assert_eq!(names(ifs.iter().up()), vec!["lo", "eth0", "eth0", "wlan0"]);
+        assert_eq!(names(ifs.iter().up().multicast_capable()), vec!["eth0", "eth0"]);
+        assert_eq!(names(ifs.iter().ipv4().non_loopback()), vec!["eth0", "eth1"]);
+        assert_eq!(names(ifs.iter().ipv6()), vec!["eth0", "wlan0"]);
+        assert_eq!(names(ifs.iter().named("eth*").up()), vec!["eth0", "eth0"]);
+        assert_eq!(names(ifs.iter().with_scope(AddressScope::LinkLocal)), vec!["eth0"]);
+        assert_eq!(ifs.clone().into_iter().named("wlan?").count(), 1);
+    }
+}
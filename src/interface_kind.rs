@@ -0,0 +1,176 @@
+//! Classification of network interfaces by the kind of their device (loopback, ethernet,
+//! wireless, bridge, VLAN, tunnel, ...) from the ARPHRD type, sysfs attributes and netlink link info.
+
+use std::{
+    io::Result,
+    path::Path,
+};
+
+use super::{IpInterface, link_state::SYSFS_NET};
+
+/// IFF_TAP flag in /sys/class/net/<interface>/tun_flags.
+const IFF_TAP: u32 = 0x0002;
+
+/// Kind of the device behind a network interface.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum InterfaceKind {
+    /// loopback interface
+    Loopback,
+    /// wired ethernet device
+    Ethernet,
+    /// wireless (802.11) device
+    Wireless,
+    /// software bridge
+    Bridge,
+    /// bond (link aggregation) device
+    Bond,
+    /// 802.1Q VLAN sub-interface
+    Vlan,
+    /// layer 3 TUN device
+    Tun,
+    /// layer 2 TAP device
+    Tap,
+    /// WireGuard device
+    Wireguard,
+    /// IP tunnel (VXLAN, GENEVE, GRE, IPIP, SIT, ...)
+    Tunnel,
+    /// virtual ethernet pair
+    Veth,
+    /// macvlan, macvtap or ipvlan sub-interface
+    Macvlan,
+    /// point-to-point protocol link
+    Ppp,
+    /// other virtual device (e.g. dummy)
+    Virtual,
+    /// other hardware device
+    Other,
+}
+
+impl InterfaceKind {
+
+    /// Returns whether the kind is a software device, i.e. neither loopback nor backed by
+    /// hardware.
+    pub fn is_virtual(&self) -> bool {
+        !matches!(self, InterfaceKind::Loopback | InterfaceKind::Ethernet | InterfaceKind::Wireless
+                      | InterfaceKind::Ppp | InterfaceKind::Other)
+    }
+
+    /// Returns whether the kind is a wired hardware device.
+    pub fn is_wired(&self) -> bool {
+        *self == InterfaceKind::Ethernet
+    }
+
+    /// Classifies a device by its ARPHRD type, the DEVTYPE of its sysfs uevent, its netlink link
+    /// kind, its TUN flags (TUN/TAP devices only), whether it is backed by a (bus) device and
+    /// whether it has wireless extensions.
+    fn classify(link_type: u16, devtype: Option<&str>, link_kind: Option<&str>, tun_flags: Option<u32>,
+                has_device: bool, wireless: bool) -> InterfaceKind {
+        if link_type == libc::ARPHRD_LOOPBACK {
+            return InterfaceKind::Loopback;
+        }
+        if wireless || devtype == Some("wlan") {
+            return InterfaceKind::Wireless;
+        }
+        if let Some(flags) = tun_flags {
+            return if flags & IFF_TAP != 0 { InterfaceKind::Tap } else { InterfaceKind::Tun };
+        }
+        match link_kind.or(devtype) {
+            Some("bridge") => return InterfaceKind::Bridge,
+            Some("bond") | Some("team") => return InterfaceKind::Bond,
+            Some("vlan") => return InterfaceKind::Vlan,
+            Some("wireguard") => return InterfaceKind::Wireguard,
+            Some("vxlan") | Some("geneve") | Some("gre") | Some("gretap") | Some("ip6gre") | Some("ip6gretap")
+            | Some("ipip") | Some("sit") | Some("ip6tnl") | Some("vti") | Some("vti6") => return InterfaceKind::Tunnel,
+            Some("veth") => return InterfaceKind::Veth,
+            Some("macvlan") | Some("macvtap") | Some("ipvlan") | Some("ipvtap") => return InterfaceKind::Macvlan,
+            Some("ppp") => return InterfaceKind::Ppp,
+            Some(_) if link_kind.is_some() => return InterfaceKind::Virtual,
+            _ => {},
+        }
+        match link_type {
+            libc::ARPHRD_PPP => InterfaceKind::Ppp,
+            libc::ARPHRD_TUNNEL | libc::ARPHRD_TUNNEL6 | libc::ARPHRD_SIT | libc::ARPHRD_IPGRE => InterfaceKind::Tunnel,
+            libc::ARPHRD_ETHER if has_device => InterfaceKind::Ethernet,
+            _ if has_device => InterfaceKind::Other,
+            _ => InterfaceKind::Virtual,
+        }
+    }
+}
+
+impl IpInterface {
+
+    /// Determines the kind of the device behind the interface from its ARPHRD type, its sysfs
+    /// attributes and its netlink link info, e.g. to prefer wired over wireless interfaces and to
+    /// skip virtual devices.
+    pub fn kind(&self) -> Result<InterfaceKind> {
+        if self.is_loopback() {
+            return Ok(InterfaceKind::Loopback);
+        }
+        let dir = Path::new(SYSFS_NET).join(&self.name);
+        let link_type = std::fs::read_to_string(dir.join("type"))?.trim().parse().unwrap_or(0);
+        let devtype = std::fs::read_to_string(dir.join("uevent")).ok().and_then(|uevent| devtype_from(&uevent));
+        let link_kind = super::link_config::link(&self.name)?.and_then(|link| link.kind);
+        let tun_flags = std::fs::read_to_string(dir.join("tun_flags")).ok()
+            .and_then(|flags| u32::from_str_radix(flags.trim().trim_start_matches("0x"), 16).ok());
+        let wireless = dir.join("wireless").exists() || dir.join("phy80211").exists();
+        Ok(InterfaceKind::classify(link_type, devtype.as_deref(), link_kind.as_deref(), tun_flags,
+                                   dir.join("device").exists(), wireless))
+    }
+
+    /// Returns whether the interface is a wireless (802.11) device.
+    pub fn is_wireless(&self) -> Result<bool> {
+        Ok(self.kind()? == InterfaceKind::Wireless)
+    }
+}
+
+/// Extracts DEVTYPE from the content of a sysfs uevent file.
+fn devtype_from(uevent: &str) -> Option<String> {
+    uevent.lines().find_map(|line| line.strip_prefix("DEVTYPE=")).map(|devtype| devtype.trim().to_string())
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_devtype_from() {
+        assert_eq!(devtype_from("DEVTYPE=wlan\nINTERFACE=wlan0\nIFINDEX=3\n").as_deref(), Some("wlan"));
+        assert_eq!(devtype_from("INTERFACE=eth0\nIFINDEX=2\n"), None);
+    }
+
+    #[test]
+    fn test_classify() {
+        let ether = libc::ARPHRD_ETHER;
+        assert_eq!(InterfaceKind::classify(libc::ARPHRD_LOOPBACK, None, None, None, false, false),
+                   InterfaceKind::Loopback);
+        assert_eq!(InterfaceKind::classify(ether, None, None, None, true, false), InterfaceKind::Ethernet);
+        assert_eq!(InterfaceKind::classify(ether, Some("wlan"), None, None, true, false), InterfaceKind::Wireless);
+        assert_eq!(InterfaceKind::classify(ether, None, None, None, true, true), InterfaceKind::Wireless);
+        assert_eq!(InterfaceKind::classify(ether, Some("bridge"), Some("bridge"), None, false, false),
+                   InterfaceKind::Bridge);
+        assert_eq!(InterfaceKind::classify(ether, Some("vlan"), Some("vlan"), None, false, false), InterfaceKind::Vlan);
+        assert_eq!(InterfaceKind::classify(libc::ARPHRD_NONE, None, Some("tun"), Some(0x1001), false, false),
+                   InterfaceKind::Tun);
+        assert_eq!(InterfaceKind::classify(ether, None, Some("tun"), Some(0x1002), false, false), InterfaceKind::Tap);
+        assert_eq!(InterfaceKind::classify(libc::ARPHRD_NONE, Some("wireguard"), Some("wireguard"), None, false,
+                                           false), InterfaceKind::Wireguard);
+        assert_eq!(InterfaceKind::classify(ether, None, Some("vxlan"), None, false, false), InterfaceKind::Tunnel);
+        assert_eq!(InterfaceKind::classify(libc::ARPHRD_SIT, None, None, None, false, false), InterfaceKind::Tunnel);
+        assert_eq!(InterfaceKind::classify(ether, None, Some("dummy"), None, false, false), InterfaceKind::Virtual);
+        assert_eq!(InterfaceKind::classify(ether, None, None, None, false, false), InterfaceKind::Virtual);
+        assert_eq!(InterfaceKind::classify(libc::ARPHRD_INFINIBAND, None, None, None, true, false),
+                   InterfaceKind::Other);
+    }
+
+    #[test]
+    fn test_is_virtual() {
+        assert!(InterfaceKind::Bridge.is_virtual());
+        assert!(InterfaceKind::Wireguard.is_virtual());
+        assert!(!InterfaceKind::Ethernet.is_virtual());
+        assert!(!InterfaceKind::Wireless.is_virtual());
+        assert!(!InterfaceKind::Loopback.is_virtual());
+        assert!(InterfaceKind::Ethernet.is_wired());
+        assert!(!InterfaceKind::Wireless.is_wired());
+    }
+}
@@ -0,0 +1,128 @@
+use std::{
+    io::Result,
+    net::IpAddr,
+    sync::Arc,
+};
+#[cfg(any(test, feature = "test-util"))]
+use std::sync::Mutex;
+
+use super::{InterfaceCache, IpInterface, IpInterfaces};
+
+/// Source of the IP interface configurations the crate works with (interface index lookups,
+/// interface selection). The default is the operating system, downstream code can substitute
+/// its own implementation (e.g. `MockInterfaceProvider`) to test interface dependent logic.
+pub trait InterfaceProvider {
+
+    /// Returns the actual list of IP interface configurations.
+    fn ip_interfaces(&self) -> Result<Arc<Vec<IpInterface>>>;
+
+    /// Searches for the interface configuration with the given address and returns its index.
+    /// If `multicast` is set only multicast capable interfaces are considered.
+    fn find_index_by_address(&self, address: &IpAddr, multicast: bool) -> Result<Option<u32>> {
+        Ok(self.ip_interfaces()?
+            .iter()
+            .find(|i| i.address.ip() == *address && (!multicast || i.supports_multicast()))
+            .map(|i| i.index))
+    }
+
+    /// Returns the interface configurations as `IpInterfaces` for use with the selection
+    /// combinators.
+    fn interfaces(&self) -> Result<IpInterfaces> {
+        Ok(IpInterfaces::from((*self.ip_interfaces()?).clone()))
+    }
+}
+
+/// InterfaceProvider retrieving the interface configurations from the operating system
+/// on every call.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OsInterfaceProvider;
+
+impl InterfaceProvider for OsInterfaceProvider {
+    fn ip_interfaces(&self) -> Result<Arc<Vec<IpInterface>>> {
+        Ok(Arc::new(IpInterface::retrieve_ip_interfaces()?))
+    }
+}
+
+impl InterfaceProvider for InterfaceCache {
+    fn ip_interfaces(&self) -> Result<Arc<Vec<IpInterface>>> {
+        self.interfaces()
+    }
+}
+
+impl<T: InterfaceProvider + ?Sized> InterfaceProvider for &T {
+    fn ip_interfaces(&self) -> Result<Arc<Vec<IpInterface>>> {
+        (**self).ip_interfaces()
+    }
+}
+
+/// InterfaceProvider returning a configurable list of interface configurations.
+/// Requires the feature 'test-util'.
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Debug, Default)]
+pub struct MockInterfaceProvider {
+    interfaces: Mutex<Arc<Vec<IpInterface>>>,
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl MockInterfaceProvider {
+
+    /// Creates a provider returning the given interface configurations.
+    pub fn new(interfaces: Vec<IpInterface>) -> MockInterfaceProvider {
+        MockInterfaceProvider { interfaces: Mutex::new(Arc::new(interfaces)) }
+    }
+
+    /// Replaces the interface configurations returned by the provider.
+    pub fn set_interfaces(&self, interfaces: Vec<IpInterface>) {
+        *self.interfaces.lock().unwrap_or_else(|e| e.into_inner()) = Arc::new(interfaces);
+    }
+
+    /// Adds an interface configuration to the ones returned by the provider.
+    pub fn add_interface(&self, interface: IpInterface) {
+        let mut interfaces = self.interfaces.lock().unwrap_or_else(|e| e.into_inner());
+        let mut list = (**interfaces).clone();
+        list.push(interface);
+        *interfaces = Arc::new(list);
+    }
+
+    /// Creates an interface configuration with the given properties, no broadcast/p2p address and
+    /// a net mask that has all bits set.
+    pub fn interface(index: u32, name: &str, flags: libc::c_int, address: std::net::SocketAddr)
+        -> IpInterface {
+        let net_mask = match address {
+            std::net::SocketAddr::V4(_) => std::net::SocketAddr::new(IpAddr::V4(std::net::Ipv4Addr::BROADCAST), 0),
+            std::net::SocketAddr::V6(_) => std::net::SocketAddr::new(IpAddr::V6(std::net::Ipv6Addr::from(u128::MAX)), 0),
+        };
+        IpInterface { index, name: String::from(name), flags: flags as libc::c_uint, address, net_mask,
+            broadcast_address: None, p2p_address: None }
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl InterfaceProvider for MockInterfaceProvider {
+    fn ip_interfaces(&self) -> Result<Arc<Vec<IpInterface>>> {
+        Ok(self.interfaces.lock().unwrap_or_else(|e| e.into_inner()).clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_mock_provider() {
+        let provider = MockInterfaceProvider::new(vec![
+            MockInterfaceProvider::interface(1, "lo", libc::IFF_UP | libc::IFF_LOOPBACK, "[::1]:0".parse().unwrap()),
+        ]);
+        provider.add_interface(MockInterfaceProvider::interface(4, "eth0", libc::IFF_UP | libc::IFF_MULTICAST,
+                                                               "[fe80::2]:0".parse().unwrap()));
+        assert_eq!(provider.ip_interfaces().unwrap().len(), 2);
+        let address = "fe80::2".parse().unwrap();
+        assert_eq!(provider.find_index_by_address(&address, true).unwrap(), Some(4));
+        assert_eq!(provider.find_index_by_address(&"::1".parse().unwrap(), true).unwrap(), None);
+        assert_eq!(provider.find_index_by_address(&"::1".parse().unwrap(), false).unwrap(), Some(1));
+
+        provider.set_interfaces(Vec::new());
+        assert!(provider.interfaces().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,54 @@
+use std::{
+    io::{Result, Error, ErrorKind},
+    os::unix::io::AsRawFd,
+};
+
+use socket2::{Domain, Type};
+
+/// Builds an ifreq structure for the interface with the given name.
+pub(crate) fn ifreq_for(interface_name: &str) -> Result<libc::ifreq> {
+    let mut ifr: libc::ifreq = unsafe { std::mem::zeroed() };
+    let name = interface_name.as_bytes();
+    if name.is_empty() || name.len() >= ifr.ifr_name.len() || name.contains(&0) {
+        return Err(Error::new(ErrorKind::InvalidInput, "invalid interface name"));
+    }
+    for (dst, src) in ifr.ifr_name.iter_mut().zip(name.iter()) {
+        *dst = *src as libc::c_char;
+    }
+    Ok(ifr)
+}
+
+/// Issues an interface ioctl whose ifreq carries a pointer to request specific data (ifr_data),
+/// e.g. SIOCETHTOOL or SIOCSHWTSTAMP. A temporary datagram socket is used as ioctl target.
+pub(crate) fn interface_data_ioctl<T>(interface_name: &str, request: libc::c_ulong, data: &mut T)
+    -> Result<()> {
+    let socket = super::sys_socket::socket(Domain::IPV4, Type::DGRAM, None)?;
+    interface_data_ioctl_on(socket.as_raw_fd(), interface_name, request, data)
+}
+
+/// Like `interface_data_ioctl` but uses the given socket as ioctl target.
+pub(crate) fn interface_data_ioctl_on<T>(fd: libc::c_int, interface_name: &str,
+                                         request: libc::c_ulong, data: &mut T) -> Result<()> {
+    let mut ifr = ifreq_for(interface_name)?;
+    ifr.ifr_ifru.ifru_data = data as *mut T as *mut libc::c_char;
+    if unsafe { libc::ioctl(fd, request as _, &mut ifr as *mut libc::ifreq) } < 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Returns the hardware type (ARPHRD_*) and the first six bytes of the hardware address of the
+/// interface (SIOCGIFHWADDR).
+pub(crate) fn hardware_address(interface_name: &str) -> Result<(u16, [u8; 6])> {
+    let mut ifr = ifreq_for(interface_name)?;
+    let socket = super::sys_socket::socket(Domain::IPV4, Type::DGRAM, None)?;
+    if unsafe { libc::ioctl(socket.as_raw_fd(), libc::SIOCGIFHWADDR as _, &mut ifr as *mut libc::ifreq) } < 0 {
+        return Err(Error::last_os_error());
+    }
+    let address = unsafe { ifr.ifr_ifru.ifru_hwaddr };
+    let mut mac = [0_u8; 6];
+    for (dst, src) in mac.iter_mut().zip(address.sa_data.iter()) {
+        *dst = *src as u8;
+    }
+    Ok((address.sa_family, mac))
+}
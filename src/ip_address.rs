@@ -0,0 +1,204 @@
+use std::{
+    convert::TryInto,
+    io::Result,
+    net::IpAddr,
+    time::Duration,
+};
+
+use super::{IpInterface, netlink::{AttributeIter, NetlinkMessage, NetlinkSocket, ip_address_from}};
+
+pub(crate) const IFA_ADDRESS: u16 = 1;
+pub(crate) const IFA_LOCAL: u16 = 2;
+const IFA_LABEL: u16 = 3;
+const IFA_CACHEINFO: u16 = 6;
+const IFA_FLAGS: u16 = 8;
+
+/// IFA_F_* address flags as reported in `IpAddressInfo::flags`.
+pub const IFA_F_TEMPORARY: u32 = 0x01;
+pub const IFA_F_NODAD: u32 = 0x02;
+pub const IFA_F_OPTIMISTIC: u32 = 0x04;
+pub const IFA_F_DADFAILED: u32 = 0x08;
+pub const IFA_F_HOMEADDRESS: u32 = 0x10;
+pub const IFA_F_DEPRECATED: u32 = 0x20;
+pub const IFA_F_TENTATIVE: u32 = 0x40;
+pub const IFA_F_PERMANENT: u32 = 0x80;
+pub const IFA_F_MANAGETEMPADDR: u32 = 0x100;
+pub const IFA_F_NOPREFIXROUTE: u32 = 0x200;
+pub const IFA_F_MCAUTOJOIN: u32 = 0x400;
+pub const IFA_F_STABLE_PRIVACY: u32 = 0x800;
+
+/// Lifetime value the kernel uses for addresses that never expire.
+const INFINITY_LIFE_TIME: u32 = 0xffff_ffff;
+
+/// Struct describing a single IPv4 or IPv6 address assigned to an interface together with the
+/// attributes the kernel maintains for it (flags, scope, lifetimes). Retrieved via RTM_GETADDR.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct IpAddressInfo {
+    /// index of the interface the address is assigned to
+    pub index: u32,
+
+    /// the local address
+    pub address: IpAddr,
+
+    /// prefix length of the address' network
+    pub prefix_len: u8,
+
+    /// address scope (RT_SCOPE_UNIVERSE, RT_SCOPE_LINK, RT_SCOPE_HOST, ...)
+    pub scope: u8,
+
+    /// IFA_F_* flags
+    pub flags: u32,
+
+    /// remaining valid lifetime, None if the address does not expire
+    pub valid_lifetime: Option<Duration>,
+
+    /// remaining preferred lifetime, None if the address does not expire
+    pub preferred_lifetime: Option<Duration>,
+
+    /// address label (IPv4 only, e.g. "eth0:1")
+    pub label: Option<String>,
+}
+
+impl IpAddressInfo {
+
+    /// Retrieves the list of all IPv4 and IPv6 addresses with their attributes from the kernel.
+    pub fn retrieve_ip_addresses() -> Result<Vec<IpAddressInfo>> {
+        let mut socket = NetlinkSocket::new(libc::NETLINK_ROUTE, 0)?;
+        // struct ifaddrmsg with AF_UNSPEC to dump all families
+        let request = [0_u8; 8];
+        let messages = socket.request(libc::RTM_GETADDR, libc::NLM_F_DUMP as u16, &request)?;
+        Ok(messages.iter().filter_map(IpAddressInfo::from_message).collect())
+    }
+
+    /// Parses a RTM_NEWADDR message, returns None for other messages or non IP addresses.
+    pub fn from_message(msg: &NetlinkMessage) -> Option<IpAddressInfo> {
+        if msg.msg_type != libc::RTM_NEWADDR || msg.payload.len() < 8 {
+            return None;
+        }
+        let family = msg.payload[0] as i32;
+        let prefix_len = msg.payload[1];
+        let mut flags = msg.payload[2] as u32;
+        let scope = msg.payload[3];
+        let index = u32::from_ne_bytes(msg.payload[4..8].try_into().unwrap());
+
+        let mut local = None;
+        let mut address = None;
+        let mut label = None;
+        let mut valid_lifetime = None;
+        let mut preferred_lifetime = None;
+        for (attr_type, data) in AttributeIter::new(&msg.payload[8..]) {
+            match attr_type {
+                IFA_ADDRESS => address = ip_address_from(family, data),
+                IFA_LOCAL => local = ip_address_from(family, data),
+                IFA_LABEL => label = std::ffi::CStr::from_bytes_until_nul(data).ok()
+                    .and_then(|s| s.to_str().ok()).map(String::from),
+                IFA_FLAGS if data.len() >= 4 => flags = u32::from_ne_bytes(data[0..4].try_into().unwrap()),
+                IFA_CACHEINFO if data.len() >= 8 => {
+                    preferred_lifetime = lifetime_from(u32::from_ne_bytes(data[0..4].try_into().unwrap()));
+                    valid_lifetime = lifetime_from(u32::from_ne_bytes(data[4..8].try_into().unwrap()));
+                },
+                _ => {},
+            }
+        }
+        // for point-to-point links IFA_ADDRESS is the peer address and IFA_LOCAL the local one
+        let address = local.or(address)?;
+        Some(IpAddressInfo { index, address, prefix_len, scope, flags, valid_lifetime,
+            preferred_lifetime, label })
+    }
+
+    /// Returns whether duplicate address detection for the address has not finished yet.
+    /// Sockets must not be bound to tentative addresses.
+    pub fn is_tentative(&self) -> bool {
+        (self.flags & IFA_F_TENTATIVE) != 0
+    }
+
+    /// Returns whether duplicate address detection for the address failed.
+    pub fn is_dad_failed(&self) -> bool {
+        (self.flags & IFA_F_DADFAILED) != 0
+    }
+
+    /// Returns whether the address is deprecated (preferred lifetime expired). It stays valid for
+    /// existing connections but should not be used for new ones.
+    pub fn is_deprecated(&self) -> bool {
+        (self.flags & IFA_F_DEPRECATED) != 0
+    }
+
+    /// Returns whether the address is a temporary (privacy extension, RFC 8981) address.
+    pub fn is_temporary(&self) -> bool {
+        (self.flags & IFA_F_TEMPORARY) != 0
+    }
+
+    /// Returns whether the address has been configured statically (not by SLAAC or DHCP).
+    pub fn is_permanent(&self) -> bool {
+        (self.flags & IFA_F_PERMANENT) != 0
+    }
+
+    /// Returns whether the address can be used as source/bind address, i.e. it is neither
+    /// tentative nor did its duplicate address detection fail.
+    pub fn is_usable(&self) -> bool {
+        !self.is_tentative() && !self.is_dad_failed()
+    }
+}
+
+impl IpInterface {
+
+    /// Retrieves the kernel address attributes (flags, lifetimes) for the address of this interface
+    /// configuration. Returns Ok(None) if the address is no longer assigned.
+    pub fn address_info(&self) -> Result<Option<IpAddressInfo>> {
+        let address = self.address.ip();
+        Ok(IpAddressInfo::retrieve_ip_addresses()?
+            .into_iter()
+            .find(|info| info.index == self.index && info.address == address))
+    }
+}
+
+fn lifetime_from(seconds: u32) -> Option<Duration> {
+    if seconds == INFINITY_LIFE_TIME {
+        None
+    } else {
+        Some(Duration::from_secs(seconds as u64))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::netlink::push_attribute;
+    use std::net::Ipv6Addr;
+
+    fn address_message(flags: u32, preferred: u32, valid: u32) -> NetlinkMessage {
+        let mut payload = vec![libc::AF_INET6 as u8, 64, 0, 0];
+        payload.extend_from_slice(&3_u32.to_ne_bytes());
+        let address: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        push_attribute(&mut payload, IFA_ADDRESS, &address.octets());
+        let mut cache_info = Vec::new();
+        for v in [preferred, valid, 0, 0].iter() {
+            cache_info.extend_from_slice(&v.to_ne_bytes());
+        }
+        push_attribute(&mut payload, IFA_CACHEINFO, &cache_info);
+        push_attribute(&mut payload, IFA_FLAGS, &flags.to_ne_bytes());
+        NetlinkMessage { msg_type: libc::RTM_NEWADDR, flags: 0, seq: 1, payload }
+    }
+
+    #[test]
+    fn test_from_message() {
+        let info = IpAddressInfo::from_message(&address_message(IFA_F_TENTATIVE | IFA_F_TEMPORARY, 100, 200))
+            .unwrap();
+        assert_eq!(info.index, 3);
+        assert_eq!(info.prefix_len, 64);
+        assert_eq!(info.address, "2001:db8::1".parse::<IpAddr>().unwrap());
+        assert_eq!(info.preferred_lifetime, Some(Duration::from_secs(100)));
+        assert_eq!(info.valid_lifetime, Some(Duration::from_secs(200)));
+        assert!(info.is_tentative());
+        assert!(info.is_temporary());
+        assert!(!info.is_deprecated());
+        assert!(!info.is_usable());
+
+        let info = IpAddressInfo::from_message(&address_message(IFA_F_PERMANENT, INFINITY_LIFE_TIME,
+                                                                INFINITY_LIFE_TIME)).unwrap();
+        assert_eq!(info.valid_lifetime, None);
+        assert!(info.is_permanent());
+        assert!(info.is_usable());
+    }
+}
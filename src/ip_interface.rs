@@ -1,11 +1,10 @@
-use std;
 use std::ptr::null_mut;
 use super::*;
 
 /// Struct describing a single IPv4 or IPv6 capable network interface configuration.
 /// Note that in a typical system a single interface (identified by its name) can have multiple
 /// configurations simultaneously.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct IpInterface {
     /// interface index
     pub index: u32,
@@ -39,53 +38,57 @@ impl IpInterface {
     /// the same interface name. This is because a single interface can have multiple configurations
     /// running simultaneously.
     pub fn retrieve_ip_interfaces() -> std::io::Result<std::vec::Vec<IpInterface>> {
-        let mut p =  null_mut() as *mut libc::ifaddrs;
-        let result = unsafe { libc::getifaddrs(std::ptr::addr_of_mut!(p) as *mut *mut libc::ifaddrs) };
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        return crate::sys_api::call(|api| api.getifaddrs());
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        Ok(IpInterface::iter_ip_interfaces()?.collect())
+    }
+
+    /// Get a lazy iterator over the actual network IP interfaces of the system.
+    /// In contrast to `retrieve_ip_interfaces()` the list returned by the system is walked on
+    /// demand. The iterator yields owned IpInterface elements, but `IpInterfaceIter::next_entry()`
+    /// gives access to borrowed views of the entries which do not allocate at all.
+    pub fn iter_ip_interfaces() -> std::io::Result<IpInterfaceIter> {
+        let mut p: *mut libc::ifaddrs = null_mut();
+        let result = unsafe { libc::getifaddrs(std::ptr::addr_of_mut!(p)) };
         if result < 0 {
             return Err(std::io::Error::last_os_error());
         }
-
-        let mut p_next = p;
-        let mut vec = std::vec::Vec::new();
-
-        while !p_next.is_null() {
-            let if_info = unsafe{ *p_next };
-            if let Ok(netif) = IpInterface::new_from(&if_info) {
-                vec.push(netif);
-            }
-            p_next = if_info.ifa_next;
-        }
-        unsafe { libc::freeifaddrs(p) };
-        Ok(vec)
+        Ok(IpInterfaceIter { head: p, next: p })
     }
 
     /// Creates a new IpInterface from a C-struct ifaddrs.
     pub fn new_from(if_addr: &libc::ifaddrs) -> std::io::Result<IpInterface> {
-        let name = match unsafe { std::ffi::CStr::from_ptr(if_addr.ifa_name.clone()) }.to_str() {
+        let name = match unsafe { std::ffi::CStr::from_ptr(if_addr.ifa_name) }.to_str() {
             Ok(str) => String::from(str),
-            Err(_) => return Err(std::io::Error::new(std::io::ErrorKind::Other,
-                                                     "interface name seems to be invalid UTF8")),
+            Err(_) => return Err(std::io::Error::other("interface name seems to be invalid UTF8")),
         };
+        IpInterface::new_from_with_name(if_addr, name)
+    }
 
+    /// Creates a new IpInterface from a C-struct ifaddrs whose name has already been converted.
+    fn new_from_with_name(if_addr: &libc::ifaddrs, name: String) -> std::io::Result<IpInterface> {
         if if_addr.ifa_addr.is_null() {
-            return  Err(std::io::Error::new(std::io::ErrorKind::Other, "no address for interface"))
+            return  Err(std::io::Error::other("no address for interface"))
         }
         let address = socket_address_from(if_addr.ifa_addr)?;
         if if_addr.ifa_netmask.is_null() {
-            return  Err(std::io::Error::new(std::io::ErrorKind::Other, "no netmask for interface"))
+            return  Err(std::io::Error::other("no netmask for interface"))
         }
-        let net_mask = socket_address_from(if_addr.ifa_netmask)?;
+        let net_mask = socket_address_with_family(if_addr.ifa_netmask,
+                                                  unsafe { (*if_addr.ifa_addr).sa_family } as i32)?;
 
+        let dst_addr = destination_address_of(if_addr);
         let broadcast_address =
-            if (if_addr.ifa_flags & (libc::IFF_BROADCAST as u32)) != 0 && !if_addr.ifa_ifu.is_null() {
-                 Some(socket_address_from(if_addr.ifa_ifu)?)
+            if (if_addr.ifa_flags & (libc::IFF_BROADCAST as u32)) != 0 && !dst_addr.is_null() {
+                 Some(socket_address_from(dst_addr)?)
             } else {
                 None
             };
 
         let p2p_address =
-            if (if_addr.ifa_flags & (libc::IFF_POINTOPOINT as u32)) != 0  && !if_addr.ifa_ifu.is_null() {
-                Some(socket_address_from(if_addr.ifa_ifu)?)
+            if (if_addr.ifa_flags & (libc::IFF_POINTOPOINT as u32)) != 0  && !dst_addr.is_null() {
+                Some(socket_address_from(dst_addr)?)
             } else {
                 None
             };
@@ -101,10 +104,19 @@ impl IpInterface {
     }
 
     /// Returns whether the interface has detected a physical link (layer 1) signal.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
     pub fn is_l1_up(&self) -> bool {
         (self.flags & (libc::IFF_LOWER_UP as u32)) != 0
     }
 
+    /// Returns whether the interface has detected a physical link (layer 1) signal.
+    /// macOS and the BSDs do not report IFF_LOWER_UP, IFF_RUNNING is used instead.
+    #[cfg(any(target_vendor = "apple", target_os = "freebsd", target_os = "dragonfly",
+                  target_os = "openbsd", target_os = "netbsd"))]
+    pub fn is_l1_up(&self) -> bool {
+        (self.flags & (libc::IFF_RUNNING as u32)) != 0
+    }
+
     /// Returns whether this interface is a loopback/virtual interface.
     pub fn is_loopback(&self) -> bool {
         (self.flags & (libc::IFF_LOOPBACK as u32)) != 0
@@ -123,9 +135,248 @@ impl IpInterface {
     /// Returns whether the network interface address (l2-address) is dynamic and lost when the
     /// interface shuts down.
     /// @note: This is not about the IP address!
+    #[cfg(any(target_os = "linux", target_os = "android"))]
     pub fn has_dynamic_address(&self) -> bool {
         (self.flags & (libc::IFF_DYNAMIC as u32)) != 0
     }
+
+    /// Returns whether the network interface address (l2-address) is dynamic and lost when the
+    /// interface shuts down. macOS and the BSDs have no such flag, so this is always false.
+    #[cfg(any(target_vendor = "apple", target_os = "freebsd", target_os = "dragonfly",
+                  target_os = "openbsd", target_os = "netbsd"))]
+    pub fn has_dynamic_address(&self) -> bool {
+        false
+    }
+
+    /// Returns the names of the flags set for the interface in the order used by `ip addr`.
+    pub fn flag_names(&self) -> Vec<&'static str> {
+        INTERFACE_FLAG_NAMES.iter()
+            .filter(|(flag, _)| (self.flags & (*flag as u32)) != 0)
+            .map(|(_, name)| *name)
+            .collect()
+    }
+
+    /// Returns the prefix length of the network mask (number of leading one bits).
+    pub fn prefix_len(&self) -> u8 {
+        match self.net_mask.ip() {
+            std::net::IpAddr::V4(m) => u32::from(m).leading_ones() as u8,
+            std::net::IpAddr::V6(m) => u128::from(m).leading_ones() as u8,
+        }
+    }
+
+    /// Returns the network of the interface address and mask, e.g. 192.168.1.0/24.
+    pub fn network(&self) -> IpNet {
+        IpNet::new(self.address.ip(), self.prefix_len()).unwrap_or_else(|_| IpNet::from(self.address.ip()))
+    }
+}
+
+/// Interface flags and their names in the order `ip addr` displays them.
+const INTERFACE_FLAG_NAMES: &[(libc::c_int, &str)] = &[
+    (libc::IFF_LOOPBACK, "LOOPBACK"),
+    (libc::IFF_BROADCAST, "BROADCAST"),
+    (libc::IFF_POINTOPOINT, "POINTOPOINT"),
+    (libc::IFF_MULTICAST, "MULTICAST"),
+    (libc::IFF_NOARP, "NOARP"),
+    (libc::IFF_ALLMULTI, "ALLMULTI"),
+    (libc::IFF_PROMISC, "PROMISC"),
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    (libc::IFF_MASTER, "MASTER"),
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    (libc::IFF_SLAVE, "SLAVE"),
+    (libc::IFF_DEBUG, "DEBUG"),
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    (libc::IFF_DYNAMIC, "DYNAMIC"),
+    (libc::IFF_UP, "UP"),
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    (libc::IFF_LOWER_UP, "LOWER_UP"),
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    (libc::IFF_DORMANT, "DORMANT"),
+    (libc::IFF_RUNNING, "RUNNING"),
+];
+
+/// Formats the interface configuration like a line of `ip addr`, e.g.
+/// `2: eth0 <BROADCAST,MULTICAST,UP,LOWER_UP> inet 192.168.1.2/24 brd 192.168.1.255 scope private`.
+impl std::fmt::Display for IpInterface {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let family = if self.address.is_ipv4() { "inet" } else { "inet6" };
+        write!(f, "{}: {} <{}> {} {}/{}", self.index, self.name, self.flag_names().join(","), family,
+               self.address.ip(), self.prefix_len())?;
+        if let Some(peer) = self.p2p_address {
+            write!(f, " peer {}", peer.ip())?;
+        }
+        if let Some(brd) = self.broadcast_address {
+            write!(f, " brd {}", brd.ip())?;
+        }
+        let scope = match self.scope() {
+            AddressScope::Unspecified => "unspecified",
+            AddressScope::Host => "host",
+            AddressScope::LinkLocal => "link",
+            AddressScope::Private => "private",
+            AddressScope::Global => "global",
+        };
+        write!(f, " scope {}", scope)
+    }
+}
+
+impl std::fmt::Debug for IpInterface {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        struct Flags<'a>(&'a IpInterface);
+        impl std::fmt::Debug for Flags<'_> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{:#x} [{}]", self.0.flags, self.0.flag_names().join(" | "))
+            }
+        }
+        f.debug_struct("IpInterface")
+            .field("index", &self.index)
+            .field("name", &self.name)
+            .field("flags", &Flags(self))
+            .field("address", &self.address)
+            .field("net_mask", &self.net_mask)
+            .field("broadcast_address", &self.broadcast_address)
+            .field("p2p_address", &self.p2p_address)
+            .finish()
+    }
+}
+
+/// Returns the broadcast or point-to-point destination address of an ifaddrs entry.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn destination_address_of(if_addr: &libc::ifaddrs) -> *const libc::sockaddr {
+    if_addr.ifa_ifu
+}
+
+/// Returns the broadcast or point-to-point destination address of an ifaddrs entry.
+#[cfg(any(target_vendor = "apple", target_os = "freebsd", target_os = "dragonfly",
+              target_os = "openbsd", target_os = "netbsd"))]
+fn destination_address_of(if_addr: &libc::ifaddrs) -> *const libc::sockaddr {
+    if_addr.ifa_dstaddr
+}
+
+/// Lazy iterator over the IP interface configurations of the system, see
+/// `IpInterface::iter_ip_interfaces()`. The list retrieved from the system is released when the
+/// iterator is dropped.
+pub struct IpInterfaceIter {
+    head: *mut libc::ifaddrs,
+    next: *mut libc::ifaddrs,
+}
+
+impl IpInterfaceIter {
+
+    /// Advances the iterator and returns a borrowed view on the next IPv4 or IPv6 entry.
+    /// The view does not allocate, it is valid until the iterator is advanced again.
+    pub fn next_entry(&mut self) -> Option<IfAddrEntry<'_>> {
+        while !self.next.is_null() {
+            let if_addr = unsafe { &*self.next };
+            self.next = if_addr.ifa_next;
+            if let Some(entry) = IfAddrEntry::new(if_addr) {
+                return Some(entry);
+            }
+        }
+        None
+    }
+
+    /// Advances the iterator and stores the next interface configuration in `target`, reusing
+    /// the allocation of its name. Returns false if there are no more entries.
+    pub fn next_into(&mut self, target: &mut IpInterface) -> bool {
+        while let Some(entry) = self.next_entry() {
+            if entry.fill(target).is_ok() {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl Iterator for IpInterfaceIter {
+    type Item = IpInterface;
+
+    fn next(&mut self) -> Option<IpInterface> {
+        while let Some(entry) = self.next_entry() {
+            if let Ok(netif) = entry.to_ip_interface() {
+                return Some(netif);
+            }
+        }
+        None
+    }
+}
+
+impl Drop for IpInterfaceIter {
+    fn drop(&mut self) {
+        if !self.head.is_null() {
+            unsafe { libc::freeifaddrs(self.head) };
+        }
+    }
+}
+
+/// Borrowed view on a single IPv4 or IPv6 entry of the system's interface list.
+#[derive(Clone, Copy)]
+pub struct IfAddrEntry<'a> {
+    if_addr: &'a libc::ifaddrs,
+    name: &'a str,
+    address: std::net::SocketAddr,
+}
+
+impl<'a> IfAddrEntry<'a> {
+
+    fn new(if_addr: &'a libc::ifaddrs) -> Option<IfAddrEntry<'a>> {
+        if if_addr.ifa_addr.is_null() || if_addr.ifa_netmask.is_null() {
+            return None;
+        }
+        let name = unsafe { std::ffi::CStr::from_ptr(if_addr.ifa_name) }.to_str().ok()?;
+        let address = socket_address_from(if_addr.ifa_addr).ok()?;
+        Some(IfAddrEntry { if_addr, name, address })
+    }
+
+    /// name of the interface
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// flags of the interface
+    pub fn flags(&self) -> libc::c_uint {
+        self.if_addr.ifa_flags
+    }
+
+    /// assigned IP address
+    pub fn address(&self) -> std::net::SocketAddr {
+        self.address
+    }
+
+    /// Returns whether the interface supports multicast transmission and reception.
+    pub fn supports_multicast(&self) -> bool {
+        (self.flags() & (libc::IFF_MULTICAST as u32)) != 0
+    }
+
+    /// Looks up the interface index, this requires a system call.
+    pub fn index(&self) -> u32 {
+        unsafe{ libc::if_nametoindex(self.if_addr.ifa_name) }
+    }
+
+    /// Creates an owned IpInterface from the entry.
+    pub fn to_ip_interface(&self) -> std::io::Result<IpInterface> {
+        IpInterface::new_from(self.if_addr)
+    }
+
+    /// Stores the entry in `target`, reusing the allocation of its name. On error `target` is
+    /// left unchanged.
+    pub fn fill(&self, target: &mut IpInterface) -> std::io::Result<()> {
+        let mut netif = IpInterface::new_from_with_name(self.if_addr, String::new())?;
+        let mut name = std::mem::take(&mut target.name);
+        name.clear();
+        name.push_str(self.name);
+        netif.name = name;
+        *target = netif;
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for IfAddrEntry<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IfAddrEntry")
+            .field("name", &self.name)
+            .field("flags", &self.flags())
+            .field("address", &self.address)
+            .finish()
+    }
 }
 
 unsafe impl Send for IpInterface {}
@@ -141,15 +392,22 @@ mod test {
     use super::*;
     use std::net::{SocketAddr, SocketAddrV4, Ipv4Addr};
 
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const L1_UP_FLAG: i32 = libc::IFF_LOWER_UP;
+    #[cfg(any(target_vendor = "apple", target_os = "freebsd", target_os = "dragonfly",
+                  target_os = "openbsd", target_os = "netbsd"))]
+    const L1_UP_FLAG: i32 = libc::IFF_RUNNING;
+
     fn create_ip_with_flags(flags: i32) -> IpInterface {
         let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 4711));
         IpInterface { index: 2, name: String::from("eht0"), flags: flags as libc::c_uint,
-            address: addr.clone(), net_mask: addr.clone(), broadcast_address: None, p2p_address: None }
+            address: addr, net_mask: addr, broadcast_address: None, p2p_address: None }
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_flags() {
-        let ipi = create_ip_with_flags(libc::IFF_LOOPBACK | libc::IFF_UP as i32 );
+        let ipi = create_ip_with_flags(libc::IFF_LOOPBACK | libc::IFF_UP);
         assert_eq!(ipi.is_p2p(), false);
         assert_eq!(ipi.is_loopback(), true);
         assert_eq!(ipi.is_up(), true);
@@ -158,7 +416,7 @@ mod test {
         assert_eq!(ipi.supports_multicast(), false);
 
         let ipi = create_ip_with_flags(libc::IFF_MULTICAST | libc::IFF_UP | libc::IFF_MULTICAST
-            | libc::IFF_LOWER_UP as i32 );
+            | L1_UP_FLAG);
         assert_eq!(ipi.is_p2p(), false);
         assert_eq!(ipi.is_loopback(), false);
         assert_eq!(ipi.is_up(), true);
@@ -166,4 +424,19 @@ mod test {
         assert_eq!(ipi.has_dynamic_address(), false);
         assert_eq!(ipi.supports_multicast(), true);
     }
+
+    #[test]
+    fn test_display() {
+        let mut ipi = create_ip_with_flags(libc::IFF_BROADCAST | libc::IFF_MULTICAST | libc::IFF_UP);
+        ipi.address = "192.168.1.2:0".parse().unwrap();
+        ipi.net_mask = "255.255.255.0:0".parse().unwrap();
+        ipi.broadcast_address = Some("192.168.1.255:0".parse().unwrap());
+        assert_eq!(ipi.prefix_len(), 24);
+        assert_eq!(ipi.to_string(),
+                   "2: eht0 <BROADCAST,MULTICAST,UP> inet 192.168.1.2/24 brd 192.168.1.255 scope private");
+
+        let ipi = create_ip_with_flags(libc::IFF_LOOPBACK | libc::IFF_UP);
+        assert_eq!(ipi.flag_names(), vec!["LOOPBACK", "UP"]);
+        assert!(format!("{:?}", ipi).contains("flags: 0x9 [LOOPBACK | UP]"));
+    }
 }
@@ -0,0 +1,230 @@
+use std::{
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+/// An IPv4 or IPv6 network (CIDR prefix) like 192.168.1.0/24 or 2001:db8::/32. The host bits of
+/// the address are cleared, so 192.168.1.7/24 and 192.168.1.0/24 are the same network.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct IpNet {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNet {
+
+    /// Creates the network of the address with the prefix length. Fails with InvalidInput if the
+    /// prefix length exceeds 32 (IPv4) or 128 (IPv6) bits.
+    pub fn new(address: IpAddr, prefix_len: u8) -> Result<IpNet> {
+        if prefix_len > max_prefix_len(&address) {
+            return Err(Error::new(ErrorKind::InvalidInput, format!("invalid prefix length {} for {}", prefix_len, address)));
+        }
+        let network = from_bits(&address, to_bits(&address) & mask(&address, prefix_len));
+        Ok(IpNet { network, prefix_len })
+    }
+
+    /// Returns the network address (first address of the network).
+    pub fn network(&self) -> IpAddr {
+        self.network
+    }
+
+    /// Returns the prefix length.
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// Returns the network mask, e.g. 255.255.255.0 for a /24 network.
+    pub fn netmask(&self) -> IpAddr {
+        from_bits(&self.network, mask(&self.network, self.prefix_len))
+    }
+
+    /// Returns the last address of the network.
+    pub fn last(&self) -> IpAddr {
+        from_bits(&self.network, to_bits(&self.network) | !mask(&self.network, self.prefix_len))
+    }
+
+    /// Returns the broadcast address of an IPv4 network, None for IPv6 networks and /31 and /32
+    /// networks, which have no broadcast address.
+    pub fn broadcast(&self) -> Option<Ipv4Addr> {
+        match self.last() {
+            IpAddr::V4(last) if self.prefix_len < 31 => Some(last),
+            _ => None,
+        }
+    }
+
+    /// Returns whether this is an IPv4 network.
+    pub fn is_ipv4(&self) -> bool {
+        self.network.is_ipv4()
+    }
+
+    /// Returns whether the address is part of the network. Addresses of the other family are not.
+    pub fn contains(&self, address: &IpAddr) -> bool {
+        address.is_ipv4() == self.is_ipv4()
+            && to_bits(address) & mask(address, self.prefix_len) == to_bits(&self.network)
+    }
+
+    /// Returns whether the other network is part of this network (or equal to it).
+    pub fn contains_net(&self, other: &IpNet) -> bool {
+        other.prefix_len >= self.prefix_len && self.contains(&other.network)
+    }
+
+    /// Returns whether the networks share at least one address, i.e. one contains the other.
+    pub fn overlaps(&self, other: &IpNet) -> bool {
+        self.contains_net(other) || other.contains_net(self)
+    }
+
+    /// Returns the number of addresses of the network, saturated at u128::MAX for ::/0.
+    pub fn size(&self) -> u128 {
+        1_u128.checked_shl((max_prefix_len(&self.network) - self.prefix_len) as u32).unwrap_or(u128::MAX)
+    }
+
+    /// Returns an iterator over the host addresses of the network. The network and broadcast
+    /// addresses of IPv4 networks with prefix lengths below 31 are excluded.
+    pub fn hosts(&self) -> IpNetHosts {
+        let (first, last) = (to_bits(&self.network), to_bits(&self.last()));
+        let (next, last) = if self.is_ipv4() && self.prefix_len < 31 { (first + 1, last - 1) } else { (first, last) };
+        IpNetHosts { network: self.network, next: Some(next), last }
+    }
+}
+
+/// Parses a network in CIDR notation ("192.168.1.0/24", "2001:db8::/32"). An address without
+/// prefix length is a network of the single address (/32 or /128).
+impl FromStr for IpNet {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<IpNet> {
+        let invalid = || Error::new(ErrorKind::InvalidInput, format!("invalid network {:?}", s));
+        let (address, prefix_len) = match s.split_once('/') {
+            Some((address, prefix_len)) => (address, Some(prefix_len)),
+            None => (s, None),
+        };
+        let address: IpAddr = address.parse().map_err(|_| invalid())?;
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len.parse().map_err(|_| invalid())?,
+            None => max_prefix_len(&address),
+        };
+        IpNet::new(address, prefix_len)
+    }
+}
+
+impl std::fmt::Display for IpNet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_len)
+    }
+}
+
+/// The network of the single address (/32 or /128).
+impl From<IpAddr> for IpNet {
+    fn from(address: IpAddr) -> IpNet {
+        IpNet { network: address, prefix_len: max_prefix_len(&address) }
+    }
+}
+
+/// Iterator over the host addresses of a network, see `IpNet::hosts`.
+#[derive(Clone, Debug)]
+pub struct IpNetHosts {
+    network: IpAddr,
+    next: Option<u128>,
+    last: u128,
+}
+
+impl Iterator for IpNetHosts {
+    type Item = IpAddr;
+
+    fn next(&mut self) -> Option<IpAddr> {
+        let next = self.next.filter(|next| *next <= self.last)?;
+        self.next = next.checked_add(1);
+        Some(from_bits(&self.network, next))
+    }
+}
+
+fn max_prefix_len(address: &IpAddr) -> u8 {
+    if address.is_ipv4() { 32 } else { 128 }
+}
+
+/// Returns the network mask of the prefix length for the family of the address.
+fn mask(address: &IpAddr, prefix_len: u8) -> u128 {
+    let bits = max_prefix_len(address) as u32;
+    let all = if bits == 32 { u32::MAX as u128 } else { u128::MAX };
+    all & !(all.checked_shr(prefix_len as u32).unwrap_or(0))
+}
+
+fn to_bits(address: &IpAddr) -> u128 {
+    match address {
+        IpAddr::V4(address) => u32::from(*address) as u128,
+        IpAddr::V6(address) => u128::from(*address),
+    }
+}
+
+/// Converts the bits to an address of the family of the given address.
+fn from_bits(family: &IpAddr, bits: u128) -> IpAddr {
+    match family {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::from(bits as u32)),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::from(bits)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn net(s: &str) -> IpNet {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let n = net("192.168.1.77/24");
+        assert_eq!(n.network(), "192.168.1.0".parse::<IpAddr>().unwrap());
+        assert_eq!(n.prefix_len(), 24);
+        assert_eq!(n.to_string(), "192.168.1.0/24");
+        assert_eq!(net("10.1.2.3"), IpNet::from("10.1.2.3".parse::<IpAddr>().unwrap()));
+        assert_eq!(net("2001:db8:1::7/48").to_string(), "2001:db8:1::/48");
+        assert_eq!(net("0.0.0.0/0").netmask(), "0.0.0.0".parse::<IpAddr>().unwrap());
+        for invalid in ["192.168.1.0/33", "2001:db8::/129", "192.168.1/24", "10.0.0.0/", "10.0.0.0/x"].iter() {
+            assert_eq!(invalid.parse::<IpNet>().unwrap_err().kind(), ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn test_masks() {
+        let n = net("192.168.1.0/24");
+        assert_eq!(n.netmask(), "255.255.255.0".parse::<IpAddr>().unwrap());
+        assert_eq!(n.broadcast(), Some(Ipv4Addr::new(192, 168, 1, 255)));
+        assert_eq!(net("10.0.0.0/31").broadcast(), None);
+        assert_eq!(net("2001:db8::/64").broadcast(), None);
+        assert_eq!(net("2001:db8::/64").last(), "2001:db8::ffff:ffff:ffff:ffff".parse::<IpAddr>().unwrap());
+        assert_eq!(net("2001:db8::/64").netmask(), "ffff:ffff:ffff:ffff::".parse::<IpAddr>().unwrap());
+        assert_eq!(n.size(), 256);
+        assert_eq!(net("::/0").size(), u128::MAX);
+    }
+
+    #[test]
+    fn test_contains_overlaps() {
+        let n = net("10.1.0.0/16");
+        assert!(n.contains(&"10.1.255.1".parse().unwrap()));
+        assert!(!n.contains(&"10.2.0.1".parse().unwrap()));
+        assert!(!n.contains(&"::ffff:10.1.0.1".parse().unwrap()));
+        assert!(net("0.0.0.0/0").contains(&"192.0.2.1".parse().unwrap()));
+        assert!(n.contains_net(&net("10.1.2.0/24")));
+        assert!(!net("10.1.2.0/24").contains_net(&n));
+        assert!(n.overlaps(&net("10.0.0.0/8")));
+        assert!(net("10.0.0.0/8").overlaps(&n));
+        assert!(!n.overlaps(&net("10.2.0.0/16")));
+        assert!(!n.overlaps(&net("::/0")));
+    }
+
+    #[test]
+    fn test_hosts() {
+        let hosts: Vec<IpAddr> = net("192.168.1.77/29").hosts().collect();
+        assert_eq!(hosts.len(), 6);
+        assert_eq!(hosts[0], "192.168.1.73".parse::<IpAddr>().unwrap());
+        assert_eq!(hosts[5], "192.168.1.78".parse::<IpAddr>().unwrap());
+        assert_eq!(net("10.0.0.0/31").hosts().count(), 2);
+        assert_eq!(net("10.0.0.9/32").hosts().collect::<Vec<_>>(), vec!["10.0.0.9".parse::<IpAddr>().unwrap()]);
+        assert_eq!(net("2001:db8::/126").hosts().count(), 4);
+        assert_eq!(net("ffff:ffff:ffff:ffff:ffff:ffff:ffff:fffe/127").hosts().count(), 2);
+    }
+}
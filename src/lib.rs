@@ -1,8 +1,278 @@
+/// Emits a `trace::TraceEvent` to the installed trace handler, the event expression is only
+/// evaluated if a handler is installed and compiled out without the feature 'trace'.
+macro_rules! trace_event {
+    ($event:expr) => {
+        #[cfg(feature = "trace")]
+        $crate::trace::emit(|| $event);
+    };
+}
+
+/// Enters a `tracing` span at level DEBUG with the name and fields until the end of the block,
+/// compiled out without the feature 'trace'.
+macro_rules! trace_span {
+    ($($span:tt)*) => {
+        #[cfg(feature = "trace")]
+        let _span = tracing::debug_span!($($span)*).entered();
+    };
+}
+
+#[cfg(feature = "trace")]
+pub mod trace;
+
+#[cfg(feature = "metrics")]
+pub mod metrics;
+
+#[cfg(unix)]
 mod ip_interface;
+#[cfg(unix)]
 pub use ip_interface::*;
 
+#[cfg(unix)]
+mod interface_cache;
+#[cfg(unix)]
+pub use interface_cache::*;
+
+#[cfg(unix)]
+mod interface_provider;
+#[cfg(unix)]
+pub use interface_provider::*;
+
+#[cfg(unix)]
+mod interface_filter;
+#[cfg(unix)]
+pub use interface_filter::*;
+
+#[cfg(unix)]
 mod sockaddr;
+#[cfg(unix)]
 pub use sockaddr::*;
 
 mod multicast;
 pub use multicast::*;
+
+mod address_scope;
+pub use address_scope::*;
+
+mod ip_net;
+pub use ip_net::*;
+
+mod device;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod sockopt;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod sys_api;
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "test-util"))]
+pub use sys_api::{MockSysApi, OsSysApi, SysApi, SysCall, with_sys_api};
+
+mod sys_socket;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod proc_interfaces;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod udplite;
+
+#[cfg(target_os = "linux")]
+pub mod udp;
+
+#[cfg(target_os = "linux")]
+pub mod errqueue;
+
+#[cfg(target_os = "linux")]
+pub mod pktinfo;
+
+#[cfg(target_os = "linux")]
+pub mod quic;
+
+pub mod rate_limit;
+
+pub mod checksum;
+
+pub mod packet;
+
+pub mod stats;
+
+pub mod socket_set;
+
+pub mod fragment;
+
+pub mod fec;
+
+#[cfg(feature = "secure")]
+pub mod secure;
+
+pub mod stream_analyzer;
+
+pub mod beacon;
+
+#[cfg(feature = "relmcast")]
+pub mod relmcast;
+
+#[cfg(feature = "codec")]
+pub mod codec;
+
+#[cfg(all(target_os = "linux", feature = "systemd"))]
+pub mod systemd;
+
+#[cfg(unix)]
+pub mod shutdown;
+
+pub mod tcp;
+
+pub mod sntp;
+
+mod random;
+
+mod inflate;
+
+mod sha256;
+
+pub mod slaac;
+
+pub mod stun;
+
+mod http;
+
+pub mod ssdp;
+
+pub mod igd;
+
+pub mod pcp;
+
+pub mod dns;
+
+#[cfg(unix)]
+pub mod host;
+
+pub mod llmnr;
+
+pub mod scan;
+
+pub mod rtp;
+
+pub mod sap;
+
+#[cfg(all(unix, feature = "ffi"))]
+pub mod ffi;
+
+pub mod syslog;
+
+pub mod tftp;
+
+pub mod doip;
+
+#[cfg(unix)]
+pub mod unix;
+
+#[cfg(target_os = "linux")]
+mod link_state;
+#[cfg(target_os = "linux")]
+pub use link_state::*;
+
+#[cfg(target_os = "linux")]
+mod ioctl;
+
+#[cfg(target_os = "linux")]
+pub mod netlink;
+
+#[cfg(target_os = "linux")]
+mod ip_address;
+#[cfg(target_os = "linux")]
+pub use ip_address::*;
+
+#[cfg(target_os = "linux")]
+pub mod ethtool;
+
+#[cfg(target_os = "linux")]
+pub mod link_config;
+
+#[cfg(target_os = "linux")]
+pub mod configure;
+
+#[cfg(target_os = "linux")]
+pub mod wireguard;
+
+#[cfg(target_os = "linux")]
+mod interface_kind;
+#[cfg(target_os = "linux")]
+pub use interface_kind::*;
+
+#[cfg(target_os = "linux")]
+pub mod wifi;
+
+#[cfg(target_os = "linux")]
+pub mod sysinfo;
+
+#[cfg(target_os = "linux")]
+pub mod multicast_groups;
+
+#[cfg(target_os = "linux")]
+pub mod peer;
+
+#[cfg(target_os = "linux")]
+pub mod dhcp;
+
+#[cfg(target_os = "linux")]
+pub mod connectivity;
+
+#[cfg(target_os = "linux")]
+pub mod raw;
+
+#[cfg(target_os = "linux")]
+pub mod socket_factory;
+
+#[cfg(target_os = "linux")]
+pub mod capabilities;
+
+#[cfg(target_os = "linux")]
+pub mod syscalls;
+
+#[cfg(target_os = "linux")]
+pub mod icmpv6;
+
+#[cfg(target_os = "linux")]
+pub mod ra;
+
+#[cfg(target_os = "linux")]
+pub mod lldp;
+
+#[cfg(target_os = "linux")]
+pub mod arp;
+
+#[cfg(target_os = "linux")]
+pub mod igmp_proxy;
+
+#[cfg(target_os = "linux")]
+pub mod mld;
+
+#[cfg(target_os = "linux")]
+pub mod membership_report;
+
+#[cfg(target_os = "linux")]
+pub mod snooping;
+
+#[cfg(target_os = "linux")]
+pub mod multicast_diagnosis;
+
+#[cfg(target_os = "linux")]
+pub mod vrrp;
+
+#[cfg(target_os = "linux")]
+mod cmsg;
+
+#[cfg(target_os = "linux")]
+pub mod timestamping;
+
+#[cfg(target_os = "linux")]
+pub mod ptp;
+
+#[cfg(target_os = "linux")]
+pub mod netperf;
+
+#[cfg(target_os = "linux")]
+pub mod vsock;
+
+#[cfg(target_os = "linux")]
+pub mod sctp;
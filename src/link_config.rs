@@ -0,0 +1,585 @@
+//! Creation, deletion and administrative state of virtual network links (bridges, bonds, team
+//! devices, macvlan and ipvlan sub-interfaces, VXLAN and GENEVE tunnels) and bridge port and bond
+//! slave management via rtnetlink, without shelling out to `ip link` or `brctl`. Addresses are
+//! assigned with the `configure` module. All modifying functions require CAP_NET_ADMIN.
+
+use std::{
+    io::{Error, ErrorKind, Result},
+    net::IpAddr,
+    path::Path,
+    time::Duration,
+};
+
+use super::{IpInterface, link_state::SYSFS_NET, netlink::{
+    IFINFOMSG_LEN, IFLA_ADDRESS, IFLA_IFNAME, IFLA_INFO_DATA, IFLA_INFO_KIND, IFLA_LINK, IFLA_LINKINFO, IFLA_MASTER, LinkInfo, MessageBuilder,
+    NLA_F_NESTED, NetlinkSocket,
+}};
+
+/// IANA assigned VXLAN port (Linux uses 8472 if no port is given).
+pub const VXLAN_PORT: u16 = 4789;
+/// IANA assigned GENEVE port.
+pub const GENEVE_PORT: u16 = 6081;
+
+const IFLA_VXLAN_ID: u16 = 1;
+const IFLA_VXLAN_GROUP: u16 = 2;
+const IFLA_VXLAN_LINK: u16 = 3;
+const IFLA_VXLAN_LOCAL: u16 = 4;
+const IFLA_VXLAN_TTL: u16 = 5;
+const IFLA_VXLAN_LEARNING: u16 = 7;
+const IFLA_VXLAN_PORT: u16 = 15;
+const IFLA_VXLAN_GROUP6: u16 = 16;
+const IFLA_VXLAN_LOCAL6: u16 = 17;
+
+const IFLA_GENEVE_ID: u16 = 1;
+const IFLA_GENEVE_REMOTE: u16 = 2;
+const IFLA_GENEVE_TTL: u16 = 3;
+const IFLA_GENEVE_PORT: u16 = 5;
+const IFLA_GENEVE_REMOTE6: u16 = 7;
+
+/// Largest VXLAN and GENEVE network identifier (24 bit).
+pub const MAX_VNI: u32 = 0xff_ffff;
+
+const IFLA_BR_STP_STATE: u16 = 5;
+
+const IFLA_BOND_MODE: u16 = 1;
+const IFLA_BOND_MIIMON: u16 = 3;
+
+const IFLA_MACVLAN_MODE: u16 = 1;
+const IFLA_IPVLAN_MODE: u16 = 1;
+
+/// Retrieves the link with the given name, Ok(None) if there is no such link.
+pub fn link(name: &str) -> Result<Option<LinkInfo>> {
+    let mut socket = NetlinkSocket::new(libc::NETLINK_ROUTE, 0)?;
+    let request = MessageBuilder::new(&[0_u8; IFINFOMSG_LEN]).str_attribute(IFLA_IFNAME, name).into_payload();
+    match socket.request(libc::RTM_GETLINK, 0, &request) {
+        Ok(messages) => Ok(messages.iter().find_map(LinkInfo::from_message)),
+        Err(e) if e.raw_os_error() == Some(libc::ENODEV) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Creates a link of the given kind (e.g. "dummy", "wireguard") without kind specific
+/// configuration and returns it. Fails with AlreadyExists if the name is in use.
+pub fn create_link(name: &str, kind: &str) -> Result<LinkInfo> {
+    create_link_with(name, kind, None, &[])
+}
+
+/// Creates a link of the given kind on top of the parent device (if any) with the kind specific
+/// IFLA_INFO_DATA attributes (none if empty) and returns it.
+pub(crate) fn create_link_with(name: &str, kind: &str, parent: Option<u32>, info_data: &[u8]) -> Result<LinkInfo> {
+    let mut builder = MessageBuilder::new(&[0_u8; IFINFOMSG_LEN]).str_attribute(IFLA_IFNAME, name);
+    if let Some(parent) = parent {
+        builder = builder.u32_attribute(IFLA_LINK, parent);
+    }
+    let request = builder
+        .nested(IFLA_LINKINFO, |b| {
+            let b = b.str_attribute(IFLA_INFO_KIND, kind);
+            if info_data.is_empty() { b } else { b.attribute(IFLA_INFO_DATA | NLA_F_NESTED, info_data) }
+        })
+        .into_payload();
+    let flags = (libc::NLM_F_CREATE | libc::NLM_F_EXCL | libc::NLM_F_ACK) as u16;
+    NetlinkSocket::new(libc::NETLINK_ROUTE, 0)?.request(libc::RTM_NEWLINK, flags, &request)?;
+    link(name)?.ok_or_else(|| Error::new(ErrorKind::NotFound, format!("created link {} vanished", name)))
+}
+
+/// Deletes the link with the given name.
+pub fn delete_link(name: &str) -> Result<()> {
+    let request = MessageBuilder::new(&[0_u8; IFINFOMSG_LEN]).str_attribute(IFLA_IFNAME, name).into_payload();
+    NetlinkSocket::new(libc::NETLINK_ROUTE, 0)?.request(libc::RTM_DELLINK, libc::NLM_F_ACK as u16, &request)?;
+    Ok(())
+}
+
+/// Sets the link administratively up or down (IFF_UP).
+pub fn set_link_up(name: &str, up: bool) -> Result<()> {
+    let link = existing_link(name)?;
+    let request = MessageBuilder::new(&ifinfomsg(link.index, if up { libc::IFF_UP as u32 } else { 0 },
+                                                 libc::IFF_UP as u32))
+        .into_payload();
+    NetlinkSocket::new(libc::NETLINK_ROUTE, 0)?.request(libc::RTM_NEWLINK, libc::NLM_F_ACK as u16, &request)?;
+    Ok(())
+}
+
+/// Creates a bridge with the kernel spanning tree protocol enabled or disabled and returns it.
+pub fn create_bridge(name: &str, stp: bool) -> Result<LinkInfo> {
+    let info_data = MessageBuilder::default().u32_attribute(IFLA_BR_STP_STATE, stp as u32).into_payload();
+    create_link_with(name, "bridge", None, &info_data)
+}
+
+/// Enslaves the link to the master device (bridge, bond or team), releases it from its master if None.
+pub fn set_master(name: &str, master: Option<&str>) -> Result<()> {
+    let master_index = match master {
+        Some(master) => existing_link(master)?.index,
+        None => 0,
+    };
+    let request = MessageBuilder::new(&ifinfomsg(existing_link(name)?.index, 0, 0))
+        .u32_attribute(IFLA_MASTER, master_index)
+        .into_payload();
+    NetlinkSocket::new(libc::NETLINK_ROUTE, 0)?.request(libc::RTM_NEWLINK, libc::NLM_F_ACK as u16, &request)?;
+    Ok(())
+}
+
+/// Spanning tree state of a bridge port (/sys/class/net/<port>/brport/state).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StpState {
+    /// the port is disabled (down or administratively disabled)
+    Disabled,
+    /// the port does neither learn nor forward while the topology is determined
+    Listening,
+    /// the port learns MAC addresses but does not forward yet
+    Learning,
+    /// the port forwards frames
+    Forwarding,
+    /// the port is blocked to avoid a loop
+    Blocking,
+}
+
+impl StpState {
+
+    fn from_sysfs(state: &str) -> Option<StpState> {
+        match state.trim() {
+            "0" => Some(StpState::Disabled),
+            "1" => Some(StpState::Listening),
+            "2" => Some(StpState::Learning),
+            "3" => Some(StpState::Forwarding),
+            "4" => Some(StpState::Blocking),
+            _ => None,
+        }
+    }
+}
+
+/// A port of a bridge with its spanning tree state.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BridgePort {
+    /// the port link
+    pub link: LinkInfo,
+
+    /// spanning tree state
+    pub state: StpState,
+}
+
+/// Reads the spanning tree state of the bridge port, fails with NotFound if the link is no
+/// bridge port.
+pub fn port_state(name: &str) -> Result<StpState> {
+    let path = Path::new(SYSFS_NET).join(name).join("brport").join("state");
+    let state = std::fs::read_to_string(&path)
+        .map_err(|e| Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+    StpState::from_sysfs(&state)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("invalid port state {}", state.trim())))
+}
+
+/// Retrieves the ports of the bridge with their spanning tree state.
+pub fn bridge_ports(bridge: &str) -> Result<Vec<BridgePort>> {
+    let bridge = existing_link(bridge)?;
+    super::netlink::links()?.into_iter()
+        .filter(|link| link.master == Some(bridge.index))
+        .map(|link| {
+            let state = port_state(link.name.as_deref().unwrap_or_default())?;
+            Ok(BridgePort { link, state })
+        })
+        .collect()
+}
+
+impl IpInterface {
+
+    /// Returns the master device (bridge, bond or team) the interface is enslaved to, Ok(None) if there
+    /// is none.
+    pub fn master(&self) -> Result<Option<LinkInfo>> {
+        let links = super::netlink::links()?;
+        Ok(links.iter()
+            .find(|link| link.index == self.index)
+            .and_then(|link| link.master)
+            .and_then(|master| links.iter().find(|link| link.index == master).cloned()))
+    }
+
+    /// Returns the bridge the interface is a port of, Ok(None) if it is no bridge port.
+    pub fn bridge(&self) -> Result<Option<LinkInfo>> {
+        Ok(self.master()?.filter(|master| master.kind.as_deref() == Some("bridge")))
+    }
+
+    /// Returns mode, slaves and active slave if the interface is a bond device, Ok(None)
+    /// otherwise.
+    pub fn bond_info(&self) -> Result<Option<BondInfo>> {
+        bond_info(&self.name)
+    }
+
+    /// Returns the parent (lower) device of stacked interfaces (vlan, macvlan, ipvlan), Ok(None)
+    /// for other interfaces.
+    pub fn parent(&self) -> Result<Option<LinkInfo>> {
+        let links = super::netlink::links()?;
+        Ok(links.iter()
+            .find(|link| link.index == self.index)
+            .and_then(|link| link.parent)
+            .and_then(|parent| links.iter().find(|link| link.index == parent).cloned()))
+    }
+}
+
+/// Bonding mode of a bond device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BondMode {
+    /// round robin transmission over all slaves
+    BalanceRr = 0,
+    /// only one slave is active, another one takes over on failure
+    ActiveBackup = 1,
+    /// transmission over a slave selected by a hash of the packet
+    BalanceXor = 2,
+    /// transmission over all slaves
+    Broadcast = 3,
+    /// IEEE 802.3ad dynamic link aggregation (LACP)
+    Ieee8023ad = 4,
+    /// adaptive transmit load balancing
+    BalanceTlb = 5,
+    /// adaptive transmit and receive load balancing
+    BalanceAlb = 6,
+}
+
+impl BondMode {
+
+    fn from_number(number: u8) -> Option<BondMode> {
+        match number {
+            0 => Some(BondMode::BalanceRr),
+            1 => Some(BondMode::ActiveBackup),
+            2 => Some(BondMode::BalanceXor),
+            3 => Some(BondMode::Broadcast),
+            4 => Some(BondMode::Ieee8023ad),
+            5 => Some(BondMode::BalanceTlb),
+            6 => Some(BondMode::BalanceAlb),
+            _ => None,
+        }
+    }
+}
+
+/// State of a bond device read from /sys/class/net/<bond>/bonding.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BondInfo {
+    /// bonding mode
+    pub mode: BondMode,
+
+    /// names of the slaves
+    pub slaves: Vec<String>,
+
+    /// name of the active slave (active-backup, balance-tlb and balance-alb modes only)
+    pub active_slave: Option<String>,
+}
+
+/// Creates a bond device with the mode and the MII link monitoring interval (None to disable)
+/// and returns it. Slaves are added with `add_bond_slave`.
+pub fn create_bond(name: &str, mode: BondMode, miimon: Option<Duration>) -> Result<LinkInfo> {
+    let info_data = MessageBuilder::default()
+        .u8_attribute(IFLA_BOND_MODE, mode as u8)
+        .u32_attribute(IFLA_BOND_MIIMON, miimon.map(|interval| interval.as_millis() as u32).unwrap_or(0))
+        .into_payload();
+    create_link_with(name, "bond", None, &info_data)
+}
+
+/// Enslaves the link to the bond device, the link is set down before as required by the kernel.
+pub fn add_bond_slave(bond: &str, slave: &str) -> Result<()> {
+    set_link_up(slave, false)?;
+    set_master(slave, Some(bond))
+}
+
+/// Creates a team device (team driver) and returns it. Ports are enslaved with `set_master`, the
+/// runner (e.g. active backup or LACP) is configured by teamd via generic netlink.
+pub fn create_team(name: &str) -> Result<LinkInfo> {
+    create_link_with(name, "team", None, &[])
+}
+
+/// Reads mode, slaves and active slave of the bond device, Ok(None) if the link is no bond device.
+pub fn bond_info(name: &str) -> Result<Option<BondInfo>> {
+    let dir = Path::new(SYSFS_NET).join(name).join("bonding");
+    let mode = match std::fs::read_to_string(dir.join("mode")) {
+        Ok(mode) => mode,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let slaves = std::fs::read_to_string(dir.join("slaves"))?;
+    let active_slave = std::fs::read_to_string(dir.join("active_slave")).unwrap_or_default();
+    parse_bond_info(&mode, &slaves, &active_slave).map(Some)
+}
+
+/// Parses the sysfs bonding attributes mode ("active-backup 1"), slaves and active_slave.
+fn parse_bond_info(mode: &str, slaves: &str, active_slave: &str) -> Result<BondInfo> {
+    let mode = mode.split_whitespace().nth(1)
+        .and_then(|number| number.parse().ok())
+        .and_then(BondMode::from_number)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("invalid bonding mode {}", mode.trim())))?;
+    let active_slave = active_slave.trim();
+    Ok(BondInfo {
+        mode,
+        slaves: slaves.split_whitespace().map(String::from).collect(),
+        active_slave: if active_slave.is_empty() { None } else { Some(active_slave.to_string()) },
+    })
+}
+
+/// Mode of a macvlan device, which determines the traffic between sub-interfaces of the same parent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MacvlanMode {
+    /// no traffic to other sub-interfaces
+    Private = 1,
+    /// traffic to other sub-interfaces is sent to the adjacent (802.1Qbg) switch
+    Vepa = 2,
+    /// traffic to other sub-interfaces is delivered directly
+    Bridge = 4,
+    /// the single sub-interface takes over the parent (e.g. for virtual machines)
+    Passthru = 8,
+    /// traffic is accepted from an explicit list of source MAC addresses only
+    Source = 16,
+}
+
+/// Mode of an ipvlan device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum IpvlanMode {
+    /// sub-interfaces share the MAC address of the parent and process layer 2
+    L2 = 0,
+    /// layer 3 routing between parent and sub-interfaces without broadcast and multicast
+    L3 = 1,
+    /// like L3 with netfilter connection tracking
+    L3s = 2,
+}
+
+/// Creates a macvlan sub-interface with its own MAC address (random unless set with
+/// `set_address`) on the parent link and returns it.
+pub fn create_macvlan(name: &str, parent: &str, mode: MacvlanMode) -> Result<LinkInfo> {
+    let info_data = MessageBuilder::default().u32_attribute(IFLA_MACVLAN_MODE, mode as u32).into_payload();
+    create_link_with(name, "macvlan", Some(existing_link(parent)?.index), &info_data)
+}
+
+/// Creates an ipvlan sub-interface sharing the MAC address of the parent link and returns it.
+pub fn create_ipvlan(name: &str, parent: &str, mode: IpvlanMode) -> Result<LinkInfo> {
+    let info_data = MessageBuilder::default().u16_attribute(IFLA_IPVLAN_MODE, mode as u16).into_payload();
+    create_link_with(name, "ipvlan", Some(existing_link(parent)?.index), &info_data)
+}
+
+/// Configuration of a VXLAN tunnel interface.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VxlanConfig {
+    /// VXLAN network identifier
+    pub vni: u32,
+
+    /// unicast remote endpoint or multicast group, None for a device whose forwarding database
+    /// is maintained externally
+    pub remote: Option<IpAddr>,
+
+    /// local source address
+    pub local: Option<IpAddr>,
+
+    /// index of the underlying interface, required for multicast groups on multi-homed hosts
+    pub device: Option<u32>,
+
+    /// UDP destination port
+    pub port: u16,
+
+    /// TTL of the encapsulated packets, None to inherit
+    pub ttl: Option<u8>,
+
+    /// learning of remote MAC addresses from received packets
+    pub learning: bool,
+}
+
+impl VxlanConfig {
+
+    /// Configuration of a point to point tunnel to the unicast remote endpoint.
+    pub fn unicast(vni: u32, remote: IpAddr) -> VxlanConfig {
+        VxlanConfig { vni, remote: Some(remote), local: None, device: None, port: VXLAN_PORT, ttl: None, learning: true }
+    }
+
+    /// Configuration of a tunnel whose broadcast, unknown unicast and multicast traffic is sent
+    /// to the multicast group on the interface (and whose members join the group there). The local
+    /// address is the address of the interface configuration if of the same family as the group.
+    pub fn multicast(vni: u32, group: IpAddr, interface: &IpInterface) -> VxlanConfig {
+        let address = interface.address.ip();
+        VxlanConfig {
+            vni,
+            remote: Some(group),
+            local: if address.is_ipv4() == group.is_ipv4() { Some(address) } else { None },
+            device: Some(interface.index),
+            port: VXLAN_PORT,
+            ttl: None,
+            learning: true,
+        }
+    }
+}
+
+/// Configuration of a GENEVE tunnel interface.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GeneveConfig {
+    /// virtual network identifier
+    pub vni: u32,
+
+    /// remote endpoint
+    pub remote: IpAddr,
+
+    /// UDP destination port
+    pub port: u16,
+
+    /// TTL of the encapsulated packets, None to inherit
+    pub ttl: Option<u8>,
+}
+
+impl GeneveConfig {
+
+    /// Configuration of a tunnel to the remote endpoint on the IANA port.
+    pub fn new(vni: u32, remote: IpAddr) -> GeneveConfig {
+        GeneveConfig { vni, remote, port: GENEVE_PORT, ttl: None }
+    }
+}
+
+/// Creates a VXLAN interface and returns it. Fails with InvalidInput for a VNI exceeding 24 bits,
+/// remote and local addresses of different families and multicast groups without interface.
+pub fn create_vxlan(name: &str, config: &VxlanConfig) -> Result<LinkInfo> {
+    create_link_with(name, "vxlan", None, &vxlan_info_data(config)?)
+}
+
+/// Creates a GENEVE interface and returns it. Fails with InvalidInput for a VNI exceeding 24 bits.
+pub fn create_geneve(name: &str, config: &GeneveConfig) -> Result<LinkInfo> {
+    create_link_with(name, "geneve", None, &geneve_info_data(config)?)
+}
+
+fn vxlan_info_data(config: &VxlanConfig) -> Result<Vec<u8>> {
+    check_vni(config.vni)?;
+    if let (Some(remote), Some(local)) = (config.remote, config.local) {
+        if remote.is_ipv4() != local.is_ipv4() {
+            return Err(Error::new(ErrorKind::InvalidInput, "remote and local address of different family"));
+        }
+    }
+    if config.remote.is_some_and(|remote| remote.is_multicast()) && config.device.is_none() {
+        return Err(Error::new(ErrorKind::InvalidInput, "multicast group requires an interface"));
+    }
+    let mut builder = MessageBuilder::default()
+        .u32_attribute(IFLA_VXLAN_ID, config.vni)
+        .attribute(IFLA_VXLAN_PORT, &config.port.to_be_bytes())
+        .u8_attribute(IFLA_VXLAN_LEARNING, config.learning as u8);
+    builder = match config.remote {
+        Some(IpAddr::V4(remote)) => builder.attribute(IFLA_VXLAN_GROUP, &remote.octets()),
+        Some(IpAddr::V6(remote)) => builder.attribute(IFLA_VXLAN_GROUP6, &remote.octets()),
+        None => builder,
+    };
+    builder = match config.local {
+        Some(IpAddr::V4(local)) => builder.attribute(IFLA_VXLAN_LOCAL, &local.octets()),
+        Some(IpAddr::V6(local)) => builder.attribute(IFLA_VXLAN_LOCAL6, &local.octets()),
+        None => builder,
+    };
+    if let Some(device) = config.device {
+        builder = builder.u32_attribute(IFLA_VXLAN_LINK, device);
+    }
+    if let Some(ttl) = config.ttl {
+        builder = builder.u8_attribute(IFLA_VXLAN_TTL, ttl);
+    }
+    Ok(builder.into_payload())
+}
+
+fn geneve_info_data(config: &GeneveConfig) -> Result<Vec<u8>> {
+    check_vni(config.vni)?;
+    let mut builder = MessageBuilder::default()
+        .u32_attribute(IFLA_GENEVE_ID, config.vni)
+        .attribute(IFLA_GENEVE_PORT, &config.port.to_be_bytes());
+    builder = match config.remote {
+        IpAddr::V4(remote) => builder.attribute(IFLA_GENEVE_REMOTE, &remote.octets()),
+        IpAddr::V6(remote) => builder.attribute(IFLA_GENEVE_REMOTE6, &remote.octets()),
+    };
+    if let Some(ttl) = config.ttl {
+        builder = builder.u8_attribute(IFLA_GENEVE_TTL, ttl);
+    }
+    Ok(builder.into_payload())
+}
+
+fn check_vni(vni: u32) -> Result<()> {
+    if vni > MAX_VNI {
+        return Err(Error::new(ErrorKind::InvalidInput, format!("VNI {} exceeds 24 bits", vni)));
+    }
+    Ok(())
+}
+
+/// Retrieves the link with the given name, fails with NotFound if there is none.
+pub(crate) fn existing_link(name: &str) -> Result<LinkInfo> {
+    link(name)?.ok_or_else(|| Error::new(ErrorKind::NotFound, format!("no link {}", name)))
+}
+
+/// Sets the hardware address of the link.
+pub fn set_address(name: &str, address: &[u8]) -> Result<()> {
+    let request = MessageBuilder::new(&ifinfomsg(existing_link(name)?.index, 0, 0))
+        .attribute(IFLA_ADDRESS, address)
+        .into_payload();
+    NetlinkSocket::new(libc::NETLINK_ROUTE, 0)?.request(libc::RTM_NEWLINK, libc::NLM_F_ACK as u16, &request)?;
+    Ok(())
+}
+
+/// Returns a struct ifinfomsg for the interface index with the flags to change.
+fn ifinfomsg(index: u32, flags: u32, change: u32) -> [u8; IFINFOMSG_LEN] {
+    let mut header = [0_u8; IFINFOMSG_LEN];
+    header[4..8].copy_from_slice(&index.to_ne_bytes());
+    header[8..12].copy_from_slice(&flags.to_ne_bytes());
+    header[12..16].copy_from_slice(&change.to_ne_bytes());
+    header
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::netlink::AttributeIter;
+
+    #[test]
+    fn test_ifinfomsg() {
+        let header = ifinfomsg(3, libc::IFF_UP as u32, libc::IFF_UP as u32);
+        assert_eq!(&header[0..4], &[0, 0, 0, 0]);
+        assert_eq!(u32::from_ne_bytes([header[4], header[5], header[6], header[7]]), 3);
+        assert_eq!(u32::from_ne_bytes([header[12], header[13], header[14], header[15]]), libc::IFF_UP as u32);
+    }
+
+    #[test]
+    fn test_stp_state_from_sysfs() {
+        assert_eq!(StpState::from_sysfs("3\n"), Some(StpState::Forwarding));
+        assert_eq!(StpState::from_sysfs("4"), Some(StpState::Blocking));
+        assert_eq!(StpState::from_sysfs("0"), Some(StpState::Disabled));
+        assert_eq!(StpState::from_sysfs("9"), None);
+    }
+
+    #[test]
+    fn test_parse_bond_info() {
+        let info = parse_bond_info("active-backup 1\n", "eth0 eth1\n", "eth1\n").unwrap();
+        assert_eq!(info.mode, BondMode::ActiveBackup);
+        assert_eq!(info.slaves, vec!["eth0".to_string(), "eth1".to_string()]);
+        assert_eq!(info.active_slave.as_deref(), Some("eth1"));
+        let info = parse_bond_info("802.3ad 4\n", "\n", "\n").unwrap();
+        assert_eq!(info.mode, BondMode::Ieee8023ad);
+        assert!(info.slaves.is_empty());
+        assert_eq!(info.active_slave, None);
+        assert!(parse_bond_info("unknown", "", "").is_err());
+    }
+
+    #[test]
+    fn test_vxlan_info_data() {
+        let mut config = VxlanConfig::unicast(42, "192.0.2.9".parse().unwrap());
+        config.ttl = Some(16);
+        let data = vxlan_info_data(&config).unwrap();
+        let attrs: Vec<(u16, &[u8])> = AttributeIter::new(&data).collect();
+        assert_eq!(attrs, vec![
+            (IFLA_VXLAN_ID, &42_u32.to_ne_bytes()[..]),
+            (IFLA_VXLAN_PORT, &[0x12, 0xb5][..]),
+            (IFLA_VXLAN_LEARNING, &[1][..]),
+            (IFLA_VXLAN_GROUP, &[192, 0, 2, 9][..]),
+            (IFLA_VXLAN_TTL, &[16][..]),
+        ]);
+
+        config.vni = MAX_VNI + 1;
+        assert_eq!(vxlan_info_data(&config).unwrap_err().kind(), ErrorKind::InvalidInput);
+        config.vni = 1;
+        config.remote = Some("239.1.1.1".parse().unwrap());
+        assert_eq!(vxlan_info_data(&config).unwrap_err().kind(), ErrorKind::InvalidInput);
+        config.device = Some(2);
+        config.local = Some("fd00::1".parse().unwrap());
+        assert_eq!(vxlan_info_data(&config).unwrap_err().kind(), ErrorKind::InvalidInput);
+        config.local = None;
+        let data = vxlan_info_data(&config).unwrap();
+        assert!(AttributeIter::new(&data).any(|(t, d)| t == IFLA_VXLAN_LINK && d == 2_u32.to_ne_bytes()));
+    }
+
+    #[test]
+    fn test_geneve_info_data() {
+        let data = geneve_info_data(&GeneveConfig::new(7, "fd00::9".parse().unwrap())).unwrap();
+        let attrs: Vec<(u16, &[u8])> = AttributeIter::new(&data).collect();
+        assert_eq!(attrs[1], (IFLA_GENEVE_PORT, &[0x17, 0xc1][..]));
+        assert_eq!(attrs[2].0, IFLA_GENEVE_REMOTE6);
+        assert_eq!(attrs[2].1.len(), 16);
+    }
+}
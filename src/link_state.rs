@@ -0,0 +1,168 @@
+use std::{
+    io::{Result, Error, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use super::IpInterface;
+
+/// Base directory of the network interface information exported by the kernel.
+pub(crate) const SYSFS_NET: &str = "/sys/class/net";
+
+/// Operational state of a network interface as defined in RFC 2863 and reported by the kernel
+/// in /sys/class/net/<interface>/operstate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OperState {
+    /// The state cannot be determined (e.g. the driver does not report it or loopback interfaces).
+    Unknown,
+    /// Some component (typically hardware) is missing.
+    NotPresent,
+    /// The interface is down.
+    Down,
+    /// The interface is down because a lower layer interface is down (e.g. no carrier).
+    LowerLayerDown,
+    /// The interface is in a test mode.
+    Testing,
+    /// The interface is up but waits for an external event (e.g. 802.1X authentication).
+    Dormant,
+    /// The interface is up and can pass packets.
+    Up,
+}
+
+impl OperState {
+    /// Converts the content of the sysfs operstate file into an OperState.
+    pub fn from_sysfs(state: &str) -> OperState {
+        match state.trim() {
+            "notpresent" => OperState::NotPresent,
+            "down" => OperState::Down,
+            "lowerlayerdown" => OperState::LowerLayerDown,
+            "testing" => OperState::Testing,
+            "dormant" => OperState::Dormant,
+            "up" => OperState::Up,
+            _ => OperState::Unknown,
+        }
+    }
+}
+
+/// Duplex mode of a link.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Duplex {
+    /// The link sends and receives alternately.
+    Half,
+    /// The link sends and receives at the same time.
+    Full,
+}
+
+/// Layer 1/2 link state of a network interface read from /sys/class/net/<interface>.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LinkState {
+    /// RFC 2863 operational state
+    pub oper_state: OperState,
+
+    /// whether the physical link has a carrier, None if the interface is administratively down
+    pub carrier: Option<bool>,
+
+    /// negotiated link speed in Mbit/s, None if unknown or not applicable (e.g. virtual devices)
+    pub speed: Option<u32>,
+
+    /// negotiated duplex mode, None if unknown or not applicable (e.g. virtual devices)
+    pub duplex: Option<Duplex>,
+}
+
+impl LinkState {
+
+    /// Reads the link state of the interface with the given name from sysfs.
+    pub fn retrieve(interface_name: &str) -> Result<LinkState> {
+        if interface_name.is_empty() || interface_name.contains('/') || interface_name.starts_with('.') {
+            return Err(Error::new(ErrorKind::InvalidInput, "invalid interface name"));
+        }
+        LinkState::retrieve_from(&Path::new(SYSFS_NET).join(interface_name))
+    }
+
+    /// Reads the link state from a sysfs interface directory.
+    fn retrieve_from(dir: &Path) -> Result<LinkState> {
+        let oper_state = OperState::from_sysfs(&std::fs::read_to_string(dir.join("operstate"))?);
+        let carrier = read_attribute(dir, "carrier").map(|c| c == "1");
+        // speed is reported as -1 (or as u32::MAX by some drivers) if unknown
+        let speed = read_attribute(dir, "speed")
+            .and_then(|s| s.parse::<i64>().ok())
+            .filter(|s| *s > 0 && *s < u32::MAX as i64)
+            .map(|s| s as u32);
+        let duplex = match read_attribute(dir, "duplex").as_deref() {
+            Some("full") => Some(Duplex::Full),
+            Some("half") => Some(Duplex::Half),
+            _ => None,
+        };
+        Ok(LinkState { oper_state, carrier, speed, duplex })
+    }
+}
+
+impl IpInterface {
+
+    /// Reads the actual link state (operstate, carrier, speed, duplex) of the interface from sysfs.
+    /// In contrast to `is_l1_up()` the state is read at the time of the call and not at the time
+    /// the interface list has been retrieved.
+    pub fn link_state(&self) -> Result<LinkState> {
+        LinkState::retrieve(&self.name)
+    }
+
+    /// Reads the actual RFC 2863 operational state of the interface from sysfs.
+    pub fn oper_state(&self) -> Result<OperState> {
+        Ok(self.link_state()?.oper_state)
+    }
+
+    /// Returns the MAC address of an ethernet interface, None for interfaces without one
+    /// (loopback, tunnels, ...).
+    pub fn mac_address(&self) -> Result<Option<[u8; 6]>> {
+        let (hardware_type, mac) = super::ioctl::hardware_address(&self.name)?;
+        Ok(if hardware_type == libc::ARPHRD_ETHER { Some(mac) } else { None })
+    }
+}
+
+/// Reads a single sysfs attribute. Attributes which cannot be read in the actual state of the
+/// interface (the kernel returns EINVAL for speed of a down interface) are returned as None.
+fn read_attribute(dir: &Path, name: &str) -> Option<String> {
+    let path: PathBuf = dir.join(name);
+    std::fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_oper_state_from_sysfs() {
+        assert_eq!(OperState::from_sysfs("up\n"), OperState::Up);
+        assert_eq!(OperState::from_sysfs("down\n"), OperState::Down);
+        assert_eq!(OperState::from_sysfs("lowerlayerdown"), OperState::LowerLayerDown);
+        assert_eq!(OperState::from_sysfs("dormant"), OperState::Dormant);
+        assert_eq!(OperState::from_sysfs("unknown"), OperState::Unknown);
+        assert_eq!(OperState::from_sysfs("garbage"), OperState::Unknown);
+    }
+
+    #[test]
+    fn test_retrieve_from() {
+        let dir = std::env::temp_dir().join(format!("net-utils-link-state-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("operstate"), "up\n").unwrap();
+        std::fs::write(dir.join("carrier"), "1\n").unwrap();
+        std::fs::write(dir.join("speed"), "1000\n").unwrap();
+        std::fs::write(dir.join("duplex"), "full\n").unwrap();
+        let state = LinkState::retrieve_from(&dir).unwrap();
+        assert_eq!(state, LinkState { oper_state: OperState::Up, carrier: Some(true),
+            speed: Some(1000), duplex: Some(Duplex::Full) });
+
+        std::fs::write(dir.join("speed"), "-1\n").unwrap();
+        std::fs::remove_file(dir.join("duplex")).unwrap();
+        let state = LinkState::retrieve_from(&dir).unwrap();
+        assert_eq!(state.speed, None);
+        assert_eq!(state.duplex, None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_name() {
+        assert!(LinkState::retrieve("../etc").is_err());
+        assert!(LinkState::retrieve("").is_err());
+    }
+}
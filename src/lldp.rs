@@ -0,0 +1,347 @@
+//! Link Layer Discovery Protocol (IEEE 802.1AB) listener: receives LLDP frames on a packet socket
+//! and parses the neighbor's chassis, port, system name and management addresses.
+
+use std::{
+    convert::TryInto,
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    os::unix::io::{AsFd, AsRawFd, BorrowedFd},
+    time::Duration,
+};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+use super::{packet::EthernetFrame, shutdown::ShutdownHandle, sockaddr::sock_addr_from_raw, sockopt::set_option};
+
+/// Ethertype of LLDP frames.
+pub const LLDP_ETHERTYPE: u16 = 0x88cc;
+
+/// Nearest bridge group address LLDP frames are sent to.
+pub const LLDP_MULTICAST: [u8; 6] = [0x01, 0x80, 0xc2, 0x00, 0x00, 0x0e];
+
+const ARPHRD_ETHER: u16 = 1;
+const TLV_END: u8 = 0;
+const TLV_CHASSIS_ID: u8 = 1;
+const TLV_PORT_ID: u8 = 2;
+const TLV_TTL: u8 = 3;
+const TLV_PORT_DESCRIPTION: u8 = 4;
+const TLV_SYSTEM_NAME: u8 = 5;
+const TLV_SYSTEM_DESCRIPTION: u8 = 6;
+const TLV_MANAGEMENT_ADDRESS: u8 = 8;
+/// IANA address family numbers of network address ids and management addresses.
+const FAMILY_IPV4: u8 = 1;
+const FAMILY_IPV6: u8 = 2;
+
+/// Chassis or port id of an LLDP neighbor.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LldpId {
+    /// MAC address (chassis subtype 4, port subtype 3)
+    MacAddress([u8; 6]),
+
+    /// IPv4 or IPv6 address (chassis subtype 5, port subtype 4)
+    NetworkAddress(IpAddr),
+
+    /// interface name (chassis subtype 6, port subtype 5)
+    InterfaceName(String),
+
+    /// locally assigned string (subtype 7)
+    Local(String),
+
+    /// id of another subtype, e.g. interface alias or component
+    Other { subtype: u8, value: Vec<u8> },
+}
+
+/// Management address TLV of an LLDP neighbor.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ManagementAddress {
+    /// IANA address family of the address (1 IPv4, 2 IPv6, 6 MAC)
+    pub family: u8,
+
+    /// the address in network byte order
+    pub address: Vec<u8>,
+
+    /// interface numbering subtype (2 ifIndex, 3 system port number)
+    pub interface_subtype: u8,
+
+    /// interface number of the management address
+    pub interface_number: u32,
+}
+
+impl ManagementAddress {
+
+    /// Returns the address for the IPv4 and IPv6 families.
+    pub fn ip(&self) -> Option<IpAddr> {
+        ip_address(self.family, &self.address)
+    }
+}
+
+/// Information of a neighbor from a received LLDP frame.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LldpNeighbor {
+    /// index of the interface the frame was received on
+    pub interface_index: u32,
+
+    /// source MAC address of the frame
+    pub source: [u8; 6],
+
+    /// chassis id of the neighbor
+    pub chassis_id: LldpId,
+
+    /// id of the neighbor's port the frame was sent from
+    pub port_id: LldpId,
+
+    /// time the information is valid, zero if the neighbor shuts down LLDP on the port
+    pub ttl: Duration,
+
+    /// description of the neighbor's port
+    pub port_description: Option<String>,
+
+    /// administratively assigned name of the neighbor
+    pub system_name: Option<String>,
+
+    /// description of the neighbor's system, e.g. hardware and software versions
+    pub system_description: Option<String>,
+
+    /// addresses the neighbor can be managed at
+    pub management_addresses: Vec<ManagementAddress>,
+}
+
+/// Packet socket receiving LLDP frames, requires CAP_NET_RAW.
+#[derive(Debug)]
+pub struct LldpListener {
+    socket: Socket,
+    shutdown: ShutdownHandle,
+}
+
+impl LldpListener {
+
+    /// Opens the listener for LLDP frames on the interface with the given index, or on all
+    /// ethernet interfaces for None. The interfaces are joined to the LLDP multicast address.
+    pub fn new(interface_index: Option<u32>) -> Result<LldpListener> {
+        let protocol = LLDP_ETHERTYPE.to_be() as libc::c_int;
+        let socket = super::sys_socket::socket(Domain::PACKET, Type::RAW, Some(Protocol::from(protocol)))?;
+        let mut address: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+        address.sll_family = libc::AF_PACKET as u16;
+        address.sll_protocol = LLDP_ETHERTYPE.to_be();
+        address.sll_ifindex = interface_index.unwrap_or(0) as i32;
+        let address = sock_addr_from_raw(&address, std::mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t);
+        super::sys_socket::bind(&socket, &address)?;
+        super::netlink::join_links(interface_index, |l| l.link_type == ARPHRD_ETHER,
+                                   |index| join_lldp_multicast(&socket, index))?;
+        Ok(LldpListener { socket, shutdown: ShutdownHandle::new() })
+    }
+
+    /// Sets the timeout of `recv`, None blocks indefinitely.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        self.socket.set_read_timeout(timeout)
+    }
+
+    /// Returns a handle which shuts down blocked and later `recv` calls from other threads, see
+    /// `shutdown::ShutdownHandle`.
+    pub fn shutdown_handle(&self) -> Result<ShutdownHandle> {
+        self.shutdown.share()
+    }
+
+    /// Receives the next valid LLDP frame from a neighbor, frames sent by this host are skipped.
+    pub fn recv(&self) -> Result<LldpNeighbor> {
+        let mut buffer = [0_u8; 1518];
+        loop {
+            let mut address: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+            let mut address_len = std::mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t;
+            let received = self.shutdown.wait_readable(self.socket.as_fd()).and_then(|_| {
+                let len = unsafe {
+                    libc::recvfrom(self.socket.as_raw_fd(), buffer.as_mut_ptr() as *mut libc::c_void, buffer.len(), 0,
+                                   &mut address as *mut libc::sockaddr_ll as *mut libc::sockaddr, &mut address_len)
+                };
+                if len < 0 { Err(Error::last_os_error()) } else { Ok(len) }
+            });
+            let len = received.map_err(|e| match e.kind() {
+                ErrorKind::WouldBlock => Error::new(ErrorKind::TimedOut, "no LLDP frame"),
+                _ => e,
+            })?;
+            if address.sll_pkttype == libc::PACKET_OUTGOING {
+                continue;
+            }
+            if let Some(neighbor) = parse_frame(&buffer[..len as usize], address.sll_ifindex as u32) {
+                return Ok(neighbor);
+            }
+        }
+    }
+}
+
+impl AsFd for LldpListener {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.socket.as_fd()
+    }
+}
+
+/// Adds the LLDP multicast address to the receive filter of the interface for the socket.
+fn join_lldp_multicast(socket: &Socket, interface_index: u32) -> Result<()> {
+    let mut membership: libc::packet_mreq = unsafe { std::mem::zeroed() };
+    membership.mr_ifindex = interface_index as i32;
+    membership.mr_type = libc::PACKET_MR_MULTICAST as u16;
+    membership.mr_alen = LLDP_MULTICAST.len() as u16;
+    membership.mr_address[..LLDP_MULTICAST.len()].copy_from_slice(&LLDP_MULTICAST);
+    set_option(socket.as_raw_fd(), libc::SOL_PACKET, libc::PACKET_ADD_MEMBERSHIP, &membership)
+}
+
+/// Parses an ethernet frame with LLDPDU, None if it is no valid LLDPDU (mandatory chassis id,
+/// port id and TTL TLVs in this order).
+fn parse_frame(frame: &[u8], interface_index: u32) -> Option<LldpNeighbor> {
+    let frame = EthernetFrame::new(frame).filter(|frame| frame.ether_type() == LLDP_ETHERTYPE)?;
+    let source = frame.source();
+    let mut tlvs = Tlvs { data: frame.payload() };
+    let chassis_id = match tlvs.next()? {
+        (TLV_CHASSIS_ID, value) => parse_id(value, 4, 5, 6)?,
+        _ => return None,
+    };
+    let port_id = match tlvs.next()? {
+        (TLV_PORT_ID, value) => parse_id(value, 3, 4, 5)?,
+        _ => return None,
+    };
+    let ttl = match tlvs.next()? {
+        (TLV_TTL, value) if value.len() >= 2 => Duration::from_secs(u16::from_be_bytes([value[0], value[1]]) as u64),
+        _ => return None,
+    };
+    let mut neighbor = LldpNeighbor {
+        interface_index,
+        source,
+        chassis_id,
+        port_id,
+        ttl,
+        port_description: None,
+        system_name: None,
+        system_description: None,
+        management_addresses: Vec::new(),
+    };
+    let text = |value: &[u8]| Some(String::from_utf8_lossy(value).into_owned());
+    for (tlv_type, value) in tlvs {
+        match tlv_type {
+            TLV_END => break,
+            TLV_PORT_DESCRIPTION => neighbor.port_description = text(value),
+            TLV_SYSTEM_NAME => neighbor.system_name = text(value),
+            TLV_SYSTEM_DESCRIPTION => neighbor.system_description = text(value),
+            TLV_MANAGEMENT_ADDRESS => {
+                if let Some(address) = parse_management_address(value) {
+                    neighbor.management_addresses.push(address);
+                }
+            },
+            _ => {},
+        }
+    }
+    Some(neighbor)
+}
+
+/// Parses a chassis or port id with the subtypes of MAC address, network address and interface
+/// name given, as they differ between both TLVs.
+fn parse_id(value: &[u8], mac_subtype: u8, network_subtype: u8, name_subtype: u8) -> Option<LldpId> {
+    let (&subtype, id) = value.split_first()?;
+    if id.is_empty() {
+        return None;
+    }
+    Some(match subtype {
+        s if s == mac_subtype && id.len() == 6 => LldpId::MacAddress(id.try_into().ok()?),
+        s if s == network_subtype && ip_address(id[0], &id[1..]).is_some() => {
+            LldpId::NetworkAddress(ip_address(id[0], &id[1..])?)
+        },
+        s if s == name_subtype => LldpId::InterfaceName(String::from_utf8_lossy(id).into_owned()),
+        7 => LldpId::Local(String::from_utf8_lossy(id).into_owned()),
+        _ => LldpId::Other { subtype, value: id.to_vec() },
+    })
+}
+
+fn parse_management_address(value: &[u8]) -> Option<ManagementAddress> {
+    let address_len = *value.first()? as usize;
+    if address_len < 2 {
+        return None;
+    }
+    let family = *value.get(1)?;
+    let address = value.get(2..1 + address_len)?.to_vec();
+    let interface = value.get(1 + address_len..6 + address_len)?;
+    Some(ManagementAddress {
+        family,
+        address,
+        interface_subtype: interface[0],
+        interface_number: u32::from_be_bytes(interface[1..5].try_into().ok()?),
+    })
+}
+
+fn ip_address(family: u8, address: &[u8]) -> Option<IpAddr> {
+    match (family, address.len()) {
+        (FAMILY_IPV4, 4) => Some(IpAddr::V4(Ipv4Addr::new(address[0], address[1], address[2], address[3]))),
+        (FAMILY_IPV6, 16) => Some(IpAddr::V6(Ipv6Addr::from(u128::from_be_bytes(address.try_into().ok()?)))),
+        _ => None,
+    }
+}
+
+/// Iterator over the TLVs (7 bit type, 9 bit length) of an LLDPDU.
+struct Tlvs<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for Tlvs<'a> {
+    type Item = (u8, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let header = u16::from_be_bytes(self.data.get(..2)?.try_into().ok()?);
+        let len = (header & 0x01ff) as usize;
+        let value = self.data.get(2..2 + len)?;
+        self.data = &self.data[2 + len..];
+        Some(((header >> 9) as u8, value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::packet::ETHERNET_HEADER_LEN;
+
+    fn tlv(tlv_type: u8, value: &[u8]) -> Vec<u8> {
+        let header = ((tlv_type as u16) << 9) | value.len() as u16;
+        let mut tlv = header.to_be_bytes().to_vec();
+        tlv.extend_from_slice(value);
+        tlv
+    }
+
+    #[test]
+    fn test_parse_frame() {
+        let mut frame = LLDP_MULTICAST.to_vec();
+        frame.extend_from_slice(&[0x02, 0, 0, 0, 0, 0x01]);
+        frame.extend_from_slice(&LLDP_ETHERTYPE.to_be_bytes());
+        frame.extend(tlv(TLV_CHASSIS_ID, &[4, 0x02, 0, 0, 0, 0, 0x10]));
+        frame.extend(tlv(TLV_PORT_ID, &[5, b'G', b'i', b'0', b'/', b'1']));
+        frame.extend(tlv(TLV_TTL, &[0, 120]));
+        frame.extend(tlv(TLV_PORT_DESCRIPTION, b"uplink"));
+        frame.extend(tlv(TLV_SYSTEM_NAME, b"switch1"));
+        frame.extend(tlv(127, &[0x00, 0x80, 0xc2, 1, 0, 1]));
+        frame.extend(tlv(TLV_MANAGEMENT_ADDRESS, &[5, FAMILY_IPV4, 10, 0, 0, 1, 2, 0, 0, 0, 3, 0]));
+        frame.extend(tlv(TLV_END, &[]));
+
+        let neighbor = parse_frame(&frame, 2).unwrap();
+        assert_eq!(neighbor.interface_index, 2);
+        assert_eq!(neighbor.source, [0x02, 0, 0, 0, 0, 0x01]);
+        assert_eq!(neighbor.chassis_id, LldpId::MacAddress([0x02, 0, 0, 0, 0, 0x10]));
+        assert_eq!(neighbor.port_id, LldpId::InterfaceName("Gi0/1".to_string()));
+        assert_eq!(neighbor.ttl, Duration::from_secs(120));
+        assert_eq!(neighbor.port_description.as_deref(), Some("uplink"));
+        assert_eq!(neighbor.system_name.as_deref(), Some("switch1"));
+        assert_eq!(neighbor.system_description, None);
+        assert_eq!(neighbor.management_addresses.len(), 1);
+        assert_eq!(neighbor.management_addresses[0].ip(), Some("10.0.0.1".parse().unwrap()));
+        assert_eq!(neighbor.management_addresses[0].interface_number, 3);
+
+        // TTL TLV missing
+        let truncated = frame[..ETHERNET_HEADER_LEN + 9 + 8].to_vec();
+        assert!(parse_frame(&truncated, 2).is_none());
+    }
+
+    #[test]
+    fn test_parse_id() {
+        assert_eq!(parse_id(&[5, FAMILY_IPV4, 192, 0, 2, 1], 4, 5, 6),
+                   Some(LldpId::NetworkAddress("192.0.2.1".parse().unwrap())));
+        assert_eq!(parse_id(&[7, b'x'], 3, 4, 5), Some(LldpId::Local("x".to_string())));
+        assert_eq!(parse_id(&[1, b'a'], 4, 5, 6), Some(LldpId::Other { subtype: 1, value: b"a".to_vec() }));
+        assert_eq!(parse_id(&[4], 4, 5, 6), None);
+    }
+}
@@ -0,0 +1,259 @@
+//! Link-Local Multicast Name Resolution (RFC 4795): queries for single-label names on the local
+//! link and a responder answering for the local host name.
+
+use std::{
+    io::{ErrorKind, Result},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use super::dns::{DnsMessage, DnsQuestion, DnsRecord, RecordData, CLASS_IN, FLAG_RESPONSE, TYPE_A, TYPE_AAAA,
+                 TYPE_ANY};
+
+/// IPv4 multicast group of LLMNR.
+pub const LLMNR_MULTICAST_V4: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 252);
+
+/// IPv6 (link-local) multicast group of LLMNR.
+pub const LLMNR_MULTICAST_V6: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 1, 3);
+
+/// Well known LLMNR port.
+pub const LLMNR_PORT: u16 = 5355;
+
+/// Default time to collect responses to a query (LLMNR_TIMEOUT).
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Default time to live of the records sent by a responder.
+pub const DEFAULT_TTL: u32 = 30;
+
+/// Header flag: the name is answered by more than one responder (C).
+pub const FLAG_CONFLICT: u16 = 0x0400;
+
+/// Header flag: the responder has not yet verified that its name is unique (T).
+pub const FLAG_TENTATIVE: u16 = 0x0100;
+
+/// Mask of the opcode in the header flags, only standard queries (0) are answered.
+const OPCODE_MASK: u16 = 0x7800;
+
+/// A response to an LLMNR query.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LlmnrResponse {
+    /// records for the queried name, empty if the responder has none of the queried type
+    pub answers: Vec<DnsRecord>,
+
+    /// whether the responder detected a conflict for the name (C flag)
+    pub conflict: bool,
+
+    /// whether the responder has not yet verified the uniqueness of the name (T flag)
+    pub tentative: bool,
+
+    /// address the response was sent from
+    pub from: SocketAddr,
+}
+
+/// Multicasts a query for the name and record type (typically TYPE_A or TYPE_AAAA) from
+/// `interface` and collects the responses until the timeout elapses. An IPv4 interface address
+/// (UNSPECIFIED for the default multicast interface) queries the IPv4 group, an IPv6 interface
+/// address the IPv6 group on the interface with that address.
+pub fn query(name: &str, record_type: u16, interface: &IpAddr, timeout: Duration) -> Result<Vec<LlmnrResponse>> {
+    let (socket, group) = match interface {
+        IpAddr::V4(interface) => {
+            let socket = super::sys_socket::udp_bind(SocketAddrV4::new(*interface, 0))?;
+            if !interface.is_unspecified() {
+                socket2::SockRef::from(&socket).set_multicast_if_v4(interface)?;
+            }
+            socket.set_multicast_ttl_v4(1)?;
+            (socket, SocketAddr::from((LLMNR_MULTICAST_V4, LLMNR_PORT)))
+        },
+        IpAddr::V6(interface) => {
+            let index = super::multicast::find_interface_index(interface)?;
+            let socket = super::sys_socket::udp_bind(SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, 0, 0, index))?;
+            socket2::SockRef::from(&socket).set_multicast_if_v6(index)?;
+            socket2::SockRef::from(&socket).set_multicast_hops_v6(1)?;
+            (socket, SocketAddr::V6(SocketAddrV6::new(LLMNR_MULTICAST_V6, LLMNR_PORT, 0, index)))
+        },
+    };
+    let mut id = [0_u8; 2];
+    super::random::random_bytes(&mut id);
+    let mut request = DnsMessage::query(u16::from_ne_bytes(id), name, record_type);
+    request.flags = 0;
+    socket.send_to(&request.encode()?, group)?;
+
+    let deadline = Instant::now() + timeout;
+    let mut responses: Vec<LlmnrResponse> = Vec::new();
+    let mut buffer = [0_u8; 1500];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(responses);
+        }
+        socket.set_read_timeout(Some(remaining))?;
+        match socket.recv_from(&mut buffer) {
+            Ok((len, from)) => {
+                let response = match DnsMessage::parse(&buffer[..len]) {
+                    Some(response) if response.is_response() && response.id == request.id
+                        && response.questions == request.questions => response,
+                    _ => continue,
+                };
+                let response = LlmnrResponse {
+                    answers: response.answers,
+                    conflict: response.flags & FLAG_CONFLICT != 0,
+                    tentative: response.flags & FLAG_TENTATIVE != 0,
+                    from,
+                };
+                if !responses.contains(&response) {
+                    responses.push(response);
+                }
+            },
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => return Ok(responses),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Answers LLMNR queries for a host name received on one interface.
+#[derive(Debug)]
+pub struct LlmnrResponder {
+    socket: UdpSocket,
+    hostname: String,
+    addresses: Vec<IpAddr>,
+    ttl: u32,
+}
+
+impl LlmnrResponder {
+
+    /// Creates a responder for the host name joined to the LLMNR group of the interface's address
+    /// family. The responder answers with the interface address unless it is unspecified, further
+    /// addresses (e.g. the IPv6 addresses for an IPv4 responder) are added with `add_address`.
+    pub fn new(hostname: &str, interface: &IpAddr) -> Result<LlmnrResponder> {
+        let socket = match interface {
+            IpAddr::V4(interface) => {
+                let socket = super::create_std_multicast_socket_ipv4(
+                    &SocketAddrV4::new(LLMNR_MULTICAST_V4, LLMNR_PORT), interface)?;
+                socket.set_ttl(1)?;
+                socket
+            },
+            IpAddr::V6(interface) => {
+                let socket = super::create_std_multicast_socket_ipv6(
+                    &SocketAddrV6::new(LLMNR_MULTICAST_V6, LLMNR_PORT, 0, 0), interface)?;
+                socket2::SockRef::from(&socket).set_unicast_hops_v6(1)?;
+                socket
+            },
+        };
+        let addresses = if interface.is_unspecified() { Vec::new() } else { vec![*interface] };
+        Ok(LlmnrResponder { socket, hostname: hostname.trim_end_matches('.').to_string(), addresses, ttl: DEFAULT_TTL })
+    }
+
+    /// Adds an address the host name is answered with.
+    pub fn add_address(&mut self, address: IpAddr) {
+        if !self.addresses.contains(&address) {
+            self.addresses.push(address);
+        }
+    }
+
+    /// Sets the time to live of the answered records in seconds.
+    pub fn set_ttl(&mut self, ttl: u32) {
+        self.ttl = ttl;
+    }
+
+    /// Returns the host name the responder answers for.
+    pub fn hostname(&self) -> &str {
+        &self.hostname
+    }
+
+    /// Sets the timeout of `respond_once`, None to wait for a query forever.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        self.socket.set_read_timeout(timeout)
+    }
+
+    /// Receives one datagram and answers it if it is a query for the host name. Returns the address
+    /// of the querier if a response was sent.
+    pub fn respond_once(&self) -> Result<Option<SocketAddr>> {
+        let mut buffer = [0_u8; 1500];
+        let (len, from) = self.socket.recv_from(&mut buffer)?;
+        let response = match DnsMessage::parse(&buffer[..len]) {
+            Some(query) => self.response(query),
+            None => None,
+        };
+        match response {
+            Some(response) => {
+                self.socket.send_to(&response.encode()?, from)?;
+                Ok(Some(from))
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Answers queries for the host name until receiving fails.
+    pub fn run(&self) -> Result<()> {
+        loop {
+            self.respond_once()?;
+        }
+    }
+
+    /// Returns the response to a query, None if the query is not for the host name.
+    fn response(&self, query: DnsMessage) -> Option<DnsMessage> {
+        if query.is_response() || query.flags & OPCODE_MASK != 0 || query.questions.len() != 1
+            || !query.answers.is_empty() || !query.authorities.is_empty() {
+            return None;
+        }
+        let DnsQuestion { name, record_type, class } = &query.questions[0];
+        if *class != CLASS_IN || !name.eq_ignore_ascii_case(&self.hostname) {
+            return None;
+        }
+        let answers = self.addresses.iter()
+            .filter_map(|address| match address {
+                IpAddr::V4(a) if *record_type == TYPE_A || *record_type == TYPE_ANY => {
+                    Some((TYPE_A, RecordData::A(*a)))
+                },
+                IpAddr::V6(a) if *record_type == TYPE_AAAA || *record_type == TYPE_ANY => {
+                    Some((TYPE_AAAA, RecordData::Aaaa(*a)))
+                },
+                _ => None,
+            })
+            .map(|(record_type, data)| DnsRecord { name: name.clone(), record_type, class: CLASS_IN, ttl: self.ttl, data })
+            .collect();
+        Some(DnsMessage { id: query.id, flags: FLAG_RESPONSE, questions: query.questions, answers, ..DnsMessage::default() })
+    }
+}
+
+/// Returns the host name of the local system (gethostname) without domain.
+#[cfg(unix)]
+pub fn local_hostname() -> Result<String> {
+    let hostname = super::host::hostname()?;
+    Ok(hostname.split('.').next().unwrap_or_default().to_string())
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_response() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let responder = LlmnrResponder {
+            socket,
+            hostname: "printer".to_string(),
+            addresses: vec!["192.168.1.5".parse().unwrap(), "fe80::5".parse().unwrap()],
+            ttl: DEFAULT_TTL,
+        };
+        let mut query = DnsMessage::query(7, "PRINTER", TYPE_A);
+        query.flags = 0;
+        let response = responder.response(query.clone()).unwrap();
+        assert_eq!((response.id, response.flags), (7, FLAG_RESPONSE));
+        assert_eq!(response.answers.len(), 1);
+        assert_eq!(response.answers[0].data, RecordData::A("192.168.1.5".parse().unwrap()));
+        assert_eq!(response.answers[0].name, "PRINTER");
+
+        let mut any = DnsMessage::query(8, "printer", TYPE_ANY);
+        any.flags = 0;
+        assert_eq!(responder.response(any).unwrap().answers.len(), 2);
+        let mut txt = DnsMessage::query(9, "printer", crate::dns::TYPE_TXT);
+        txt.flags = 0;
+        assert!(responder.response(txt).unwrap().answers.is_empty());
+
+        assert!(responder.response(DnsMessage::query(10, "scanner", TYPE_A)).is_none());
+        assert!(responder.response(DnsMessage { flags: FLAG_RESPONSE, ..query.clone() }).is_none());
+        assert!(responder.response(DnsMessage { flags: 0x1000, ..query }).is_none());
+    }
+}
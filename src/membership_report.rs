@@ -0,0 +1,136 @@
+//! Crafted IGMP and MLD messages sent from an interface for test benches and conformance tests,
+//! e.g. to simulate hosts joining and leaving groups: `IgmpReporter` and `MldReporter` send any
+//! `igmp_proxy::IgmpMessage` or `mld::MldMessage` with hop limit 1 and the router alert option
+//! from a chosen source address, independent of the memberships of the host's own sockets.
+//! Requires CAP_NET_RAW.
+
+use std::{
+    io::Result,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+use super::{
+    IpInterface,
+    igmp_proxy::IgmpMessage,
+    mld::MldMessage,
+    packet::{IPPROTO_ICMPV6, IPPROTO_IGMP, Ipv4Builder, Ipv6Builder},
+    raw::{RawSocket, RawSocketBuilder},
+};
+
+/// IP router alert option (RFC 2113) of IGMP messages.
+const ROUTER_ALERT_V4: [u8; 4] = [0x94, 0x04, 0, 0];
+/// IPv6 router alert hop-by-hop option (RFC 2711) with value MLD.
+const ROUTER_ALERT_V6: [u8; 4] = [0x05, 0x02, 0, 0];
+/// Precedence internetwork control of IGMP messages.
+const TOS_INTERNETWORK_CONTROL: u8 = 0xc0;
+const IPPROTO_RAW: u8 = 255;
+
+/// Sender of crafted IGMP messages on an interface.
+/// ```no_run
+/// use net_utils::{igmp_proxy::IgmpMessage, membership_report::IgmpReporter};
+/// let mut reporter = IgmpReporter::new("eth1").unwrap();
+/// // a host with another address joins the group
+/// reporter.set_source("192.0.2.77".parse().unwrap());
+/// reporter.send(&IgmpMessage::ReportV2 { group: "239.1.2.3".parse().unwrap() }).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct IgmpReporter {
+    socket: RawSocket,
+    source: Ipv4Addr,
+}
+
+impl IgmpReporter {
+
+    /// Opens the reporter on the interface with the given name. The source address is the first
+    /// IPv4 address of the interface, 0.0.0.0 if it has none.
+    pub fn new(interface_name: &str) -> Result<IgmpReporter> {
+        let socket = RawSocketBuilder::ipv4(IPPROTO_RAW).header_included(true).bind_device(interface_name).build()?;
+        let source = match first_address(interface_name, true)? {
+            Some(IpAddr::V4(address)) => address,
+            _ => Ipv4Addr::UNSPECIFIED,
+        };
+        Ok(IgmpReporter { socket, source })
+    }
+
+    /// Sets the source address of the sent messages.
+    pub fn set_source(&mut self, source: Ipv4Addr) {
+        self.source = source;
+    }
+
+    /// Returns the source address of the sent messages.
+    pub fn source(&self) -> Ipv4Addr {
+        self.source
+    }
+
+    /// Sends the message to its destination (see `IgmpMessage::destination`).
+    pub fn send(&self, message: &IgmpMessage) -> Result<()> {
+        self.send_to(message, message.destination())
+    }
+
+    /// Sends the message to another destination, e.g. a deliberately misaddressed report.
+    pub fn send_to(&self, message: &IgmpMessage, destination: Ipv4Addr) -> Result<()> {
+        let packet = Ipv4Builder::new(self.source, destination, IPPROTO_IGMP)
+            .ttl(1)
+            .tos(TOS_INTERNETWORK_CONTROL)
+            .options(&ROUTER_ALERT_V4)?
+            .build(&message.encode());
+        self.socket.send_to(&packet, IpAddr::V4(destination)).map(|_| ())
+    }
+}
+
+/// Sender of crafted MLD messages on an interface, see `IgmpReporter`.
+#[derive(Debug)]
+pub struct MldReporter {
+    socket: RawSocket,
+    source: Ipv6Addr,
+}
+
+impl MldReporter {
+
+    /// Opens the reporter on the interface with the given name. The source address is the
+    /// link-local address of the interface, :: if it has none (like hosts during duplicate
+    /// address detection).
+    pub fn new(interface_name: &str) -> Result<MldReporter> {
+        let socket = RawSocketBuilder::ipv6(IPPROTO_RAW).header_included(true).bind_device(interface_name).build()?;
+        let source = match first_address(interface_name, false)? {
+            Some(IpAddr::V6(address)) => address,
+            _ => Ipv6Addr::UNSPECIFIED,
+        };
+        Ok(MldReporter { socket, source })
+    }
+
+    /// Sets the source address of the sent messages, which should be link-local (RFC 3810).
+    pub fn set_source(&mut self, source: Ipv6Addr) {
+        self.source = source;
+    }
+
+    /// Returns the source address of the sent messages.
+    pub fn source(&self) -> Ipv6Addr {
+        self.source
+    }
+
+    /// Sends the message to its destination (see `MldMessage::destination`).
+    pub fn send(&self, message: &MldMessage) -> Result<()> {
+        self.send_to(message, message.destination())
+    }
+
+    /// Sends the message to another destination, e.g. a deliberately misaddressed report.
+    pub fn send_to(&self, message: &MldMessage, destination: Ipv6Addr) -> Result<()> {
+        let packet = Ipv6Builder::new(self.source, destination, IPPROTO_ICMPV6)
+            .hop_limit(1)
+            .hop_by_hop_options(&ROUTER_ALERT_V6)
+            .build(&message.encode(&self.source, &destination));
+        self.socket.send_to(&packet, IpAddr::V6(destination)).map(|_| ())
+    }
+}
+
+/// Returns the first IPv4 or link-local IPv6 address of the interface.
+fn first_address(interface_name: &str, ipv4: bool) -> Result<Option<IpAddr>> {
+    Ok(IpInterface::retrieve_ip_interfaces()?.into_iter()
+        .map(|interface| (interface.name, interface.address.ip()))
+        .find(|(name, address)| name == interface_name && match address {
+            IpAddr::V4(_) => ipv4,
+            IpAddr::V6(address) => !ipv4 && address.is_unicast_link_local(),
+        })
+        .map(|(_, address)| address))
+}
@@ -0,0 +1,100 @@
+//! Process wide health metrics of the crate emitted via the `metrics` facade, so they reach the
+//! recorder (exporter) the application installs, e.g. `metrics-exporter-prometheus`:
+//!
+//! * `net_utils_sockets_created_total{kind}` counter of the sockets created per kind
+//!   ("udp-multicast", "udp-connected", "tcp-listener", ...)
+//! * `net_utils_sockets_open` gauge of the sockets held by the socket wrappers of the crate,
+//!   the raw sockets and the socket sets, decremented when they are dropped
+//! * `net_utils_option_failures_total` counter of the socket options that could not be set
+//! * `net_utils_group_joins_total`, `net_utils_group_join_failures_total` and
+//!   `net_utils_group_leaves_total` counters of the multicast memberships
+//! * `net_utils_socket_moves_total` counter of the sockets that rejoined their group on another
+//!   interface (`SocketSet::apply`)
+//! * `net_utils_interface_changes_total` and `net_utils_lost_notifications_total` counters of
+//!   the interface change notifications seen by the interface cache
+//! * `net_utils_link_transitions_total{state}` counter of the links going "up" or "down"
+//! * `net_utils_datagrams_sent_total`, `net_utils_sent_bytes_total`,
+//!   `net_utils_datagrams_received_total` and `net_utils_received_bytes_total` counters of the
+//!   traffic of the socket wrappers (`stats::SocketStats`)
+//!
+//! Requires the feature 'metrics'.
+
+use ::metrics::{counter, describe_counter, describe_gauge, gauge};
+
+use super::trace::TraceEvent;
+
+/// Registers the descriptions of the metrics with the installed recorder, for exporters that
+/// publish them (e.g. as Prometheus HELP lines). Call after installing the recorder.
+pub fn describe() {
+    describe_counter!("net_utils_sockets_created_total", "Sockets created.");
+    describe_gauge!("net_utils_sockets_open", "Sockets held open by the socket wrappers.");
+    describe_counter!("net_utils_option_failures_total", "Socket options that could not be set.");
+    describe_counter!("net_utils_group_joins_total", "Multicast groups joined.");
+    describe_counter!("net_utils_group_join_failures_total", "Failed multicast group joins.");
+    describe_counter!("net_utils_group_leaves_total", "Multicast groups left.");
+    describe_counter!("net_utils_socket_moves_total", "Sockets that rejoined their group on another interface.");
+    describe_counter!("net_utils_interface_changes_total", "Link and address change notifications.");
+    describe_counter!("net_utils_lost_notifications_total", "Lost interface change notifications.");
+    describe_counter!("net_utils_link_transitions_total", "Links going up or down.");
+    describe_counter!("net_utils_datagrams_sent_total", "Datagrams sent.");
+    describe_counter!("net_utils_sent_bytes_total", "Payload bytes sent.");
+    describe_counter!("net_utils_datagrams_received_total", "Datagrams received.");
+    describe_counter!("net_utils_received_bytes_total", "Payload bytes received.");
+}
+
+/// Updates the metrics for the trace event.
+pub(crate) fn record(event: &TraceEvent) {
+    match *event {
+        TraceEvent::SocketCreated { kind, .. } => {
+            counter!("net_utils_sockets_created_total", "kind" => kind).increment(1)
+        },
+        TraceEvent::OptionSet { error: Some(_), .. } => counter!("net_utils_option_failures_total").increment(1),
+        TraceEvent::GroupJoined { error: None, .. } => counter!("net_utils_group_joins_total").increment(1),
+        TraceEvent::GroupJoined { error: Some(_), .. } => {
+            counter!("net_utils_group_join_failures_total").increment(1)
+        },
+        TraceEvent::GroupLeft { error: None, .. } => counter!("net_utils_group_leaves_total").increment(1),
+        TraceEvent::SocketMoved { .. } => counter!("net_utils_socket_moves_total").increment(1),
+        TraceEvent::InterfacesChanged { lost } => {
+            counter!("net_utils_interface_changes_total").increment(1);
+            if lost {
+                counter!("net_utils_lost_notifications_total").increment(1);
+            }
+        },
+        TraceEvent::LinkChanged { up, .. } => {
+            let state = if up { "up" } else { "down" };
+            counter!("net_utils_link_transitions_total", "state" => state).increment(1)
+        },
+        _ => (),
+    }
+}
+
+/// Counts a datagram of `len` bytes sent via a socket wrapper.
+pub(crate) fn count_sent(len: usize) {
+    counter!("net_utils_datagrams_sent_total").increment(1);
+    counter!("net_utils_sent_bytes_total").increment(len as u64);
+}
+
+/// Counts a datagram of `len` bytes received via a socket wrapper.
+pub(crate) fn count_received(len: usize) {
+    counter!("net_utils_datagrams_received_total").increment(1);
+    counter!("net_utils_received_bytes_total").increment(len as u64);
+}
+
+/// Counts the socket of its owner in the gauge of open sockets until it is dropped.
+#[derive(Debug)]
+pub(crate) struct OpenSocket(());
+
+impl OpenSocket {
+
+    pub(crate) fn new() -> OpenSocket {
+        gauge!("net_utils_sockets_open").increment(1.0);
+        OpenSocket(())
+    }
+}
+
+impl Drop for OpenSocket {
+    fn drop(&mut self) {
+        gauge!("net_utils_sockets_open").decrement(1.0);
+    }
+}
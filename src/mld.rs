@@ -0,0 +1,211 @@
+//! Multicast Listener Discovery (MLDv1 RFC 2710, MLDv2 RFC 3810) messages, the IPv6 counterpart
+//! of IGMP (see `igmp_proxy::IgmpMessage`). MLD messages are ICMPv6 messages, sent with hop
+//! limit 1 and the router alert option from a link-local address.
+
+use std::{
+    convert::TryFrom,
+    net::{IpAddr, Ipv6Addr},
+    time::Duration,
+};
+
+use super::{
+    checksum::pseudo_header_checksum,
+    icmpv6::{MLD2_LISTENER_REPORT, MLD_LISTENER_DONE, MLD_LISTENER_QUERY, MLD_LISTENER_REPORT},
+    packet::IPPROTO_ICMPV6,
+};
+
+/// Link-local group of all nodes, destination of general queries.
+pub const ALL_NODES: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+
+/// Link-local group of all routers, destination of MLDv1 done messages.
+pub const ALL_ROUTERS: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 2);
+
+/// Link-local group of all MLDv2 capable routers, destination of MLDv2 reports.
+pub const MLDV2_ROUTERS: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0x16);
+
+/// Length of MLDv1 messages and MLDv2 queries without sources.
+const MLD_LEN: usize = 24;
+
+/// A multicast address record of an MLDv2 report. The record types are the IGMPv3 group record
+/// types, e.g. `igmp_proxy::MODE_IS_EXCLUDE`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MldRecord {
+    /// record type (MODE_IS_INCLUDE, ...)
+    pub record_type: u8,
+
+    /// multicast group
+    pub group: Ipv6Addr,
+
+    /// sources of the record
+    pub sources: Vec<Ipv6Addr>,
+}
+
+/// An MLD message.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MldMessage {
+    /// general (unspecified group) or group specific query, with sources for MLDv2
+    Query { max_response_delay: Duration, group: Ipv6Addr, sources: Vec<Ipv6Addr> },
+
+    /// MLDv1 report
+    ReportV1 { group: Ipv6Addr },
+
+    /// MLDv1 done (leave)
+    Done { group: Ipv6Addr },
+
+    /// MLDv2 report
+    ReportV2 { records: Vec<MldRecord> },
+}
+
+impl MldMessage {
+
+    /// Parses an MLD message (ICMPv6 message starting with the type), None if it is invalid. The
+    /// checksum is not verified, raw ICMPv6 sockets only receive messages with a correct one.
+    pub fn parse(data: &[u8]) -> Option<MldMessage> {
+        let address = |offset: usize| -> Option<Ipv6Addr> {
+            Some(Ipv6Addr::from(<[u8; 16]>::try_from(data.get(offset..offset + 16)?).ok()?))
+        };
+        match *data.first()? {
+            MLD_LISTENER_QUERY => {
+                let code = u16::from_be_bytes([*data.get(4)?, *data.get(5)?]);
+                let group = address(8)?;
+                if data.len() < MLD_LEN + 4 {
+                    return Some(MldMessage::Query { max_response_delay: Duration::from_millis(code as u64), group,
+                                                    sources: Vec::new() });
+                }
+                let count = u16::from_be_bytes([data[26], data[27]]) as usize;
+                let sources = (0..count).map(|i| address(MLD_LEN + 4 + 16 * i)).collect::<Option<Vec<_>>>()?;
+                Some(MldMessage::Query { max_response_delay: response_delay(code), group, sources })
+            },
+            MLD_LISTENER_REPORT => Some(MldMessage::ReportV1 { group: address(8)? }),
+            MLD_LISTENER_DONE => Some(MldMessage::Done { group: address(8)? }),
+            MLD2_LISTENER_REPORT => {
+                let count = u16::from_be_bytes([*data.get(6)?, *data.get(7)?]);
+                let mut offset = 8;
+                let mut records = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    let header = data.get(offset..offset + 4)?;
+                    let sources_count = u16::from_be_bytes([header[2], header[3]]) as usize;
+                    let sources = (0..sources_count).map(|i| address(offset + 20 + 16 * i))
+                        .collect::<Option<Vec<_>>>()?;
+                    records.push(MldRecord { record_type: header[0], group: address(offset + 4)?, sources });
+                    offset += 20 + 16 * sources_count + 4 * header[1] as usize;
+                }
+                Some(MldMessage::ReportV2 { records })
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns the message in wire format with the checksum of the pseudo header of source and
+    /// destination. Queries without sources are encoded as MLDv1 queries, with sources as MLDv2
+    /// queries.
+    pub fn encode(&self, source: &Ipv6Addr, destination: &Ipv6Addr) -> Vec<u8> {
+        let v1 = |msg_type: u8, code: u16, group: &Ipv6Addr| {
+            let mut data = vec![msg_type, 0, 0, 0];
+            data.extend_from_slice(&code.to_be_bytes());
+            data.extend_from_slice(&[0, 0]);
+            data.extend_from_slice(&group.octets());
+            data
+        };
+        let mut data = match self {
+            MldMessage::Query { max_response_delay, group, sources } => {
+                let code = max_response_delay.as_millis().min(if sources.is_empty() { 0xffff } else { 0x7fff });
+                let mut data = v1(MLD_LISTENER_QUERY, code as u16, group);
+                if !sources.is_empty() {
+                    // robustness 2, query interval 125 seconds
+                    data.extend_from_slice(&[2, 125]);
+                    data.extend_from_slice(&(sources.len() as u16).to_be_bytes());
+                    sources.iter().for_each(|source| data.extend_from_slice(&source.octets()));
+                }
+                data
+            },
+            MldMessage::ReportV1 { group } => v1(MLD_LISTENER_REPORT, 0, group),
+            MldMessage::Done { group } => v1(MLD_LISTENER_DONE, 0, group),
+            MldMessage::ReportV2 { records } => {
+                let mut data = vec![MLD2_LISTENER_REPORT, 0, 0, 0, 0, 0];
+                data.extend_from_slice(&(records.len() as u16).to_be_bytes());
+                for record in records {
+                    data.extend_from_slice(&[record.record_type, 0]);
+                    data.extend_from_slice(&(record.sources.len() as u16).to_be_bytes());
+                    data.extend_from_slice(&record.group.octets());
+                    record.sources.iter().for_each(|source| data.extend_from_slice(&source.octets()));
+                }
+                data
+            },
+        };
+        let checksum = pseudo_header_checksum(&IpAddr::V6(*source), &IpAddr::V6(*destination), IPPROTO_ICMPV6, &data);
+        data[2..4].copy_from_slice(&checksum.to_be_bytes());
+        data
+    }
+
+    /// Returns the destination of the message: the group of reports and group specific queries,
+    /// ALL_NODES for general queries, ALL_ROUTERS for done messages and MLDV2_ROUTERS for MLDv2
+    /// reports.
+    pub fn destination(&self) -> Ipv6Addr {
+        match self {
+            MldMessage::Query { group, .. } if group.is_unspecified() => ALL_NODES,
+            MldMessage::Query { group, .. } | MldMessage::ReportV1 { group } => *group,
+            MldMessage::Done { .. } => ALL_ROUTERS,
+            MldMessage::ReportV2 { .. } => MLDV2_ROUTERS,
+        }
+    }
+}
+
+/// Returns the delay of an MLDv2 max response code, codes from 32768 are floating point values.
+fn response_delay(code: u16) -> Duration {
+    let millis = match code {
+        0..=0x7fff => code as u64,
+        _ => ((code as u64 & 0x0fff) | 0x1000) << (((code >> 12) & 0x07) + 3),
+    };
+    Duration::from_millis(millis)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::{checksum::verify, igmp_proxy::{ALLOW_NEW_SOURCES, CHANGE_TO_EXCLUDE_MODE}};
+
+    #[test]
+    fn test_messages() {
+        let source: Ipv6Addr = "fe80::1".parse().unwrap();
+        let group: Ipv6Addr = "ff15::1234".parse().unwrap();
+        let report = MldMessage::ReportV1 { group };
+        let encoded = report.encode(&source, &report.destination());
+        assert_eq!(encoded.len(), MLD_LEN);
+        assert_eq!(MldMessage::parse(&encoded), Some(report));
+        assert_eq!(MldMessage::Done { group }.destination(), ALL_ROUTERS);
+
+        let report = MldMessage::ReportV2 { records: vec![
+            MldRecord { record_type: CHANGE_TO_EXCLUDE_MODE, group, sources: Vec::new() },
+            MldRecord { record_type: ALLOW_NEW_SOURCES, group, sources: vec!["2001:db8::9".parse().unwrap()] },
+        ] };
+        let encoded = report.encode(&source, &MLDV2_ROUTERS);
+        assert_eq!(encoded.len(), 8 + 20 + 36);
+        assert_eq!(MldMessage::parse(&encoded), Some(report));
+        // the checksum covers the pseudo header
+        let mut pseudo = [source.octets(), MLDV2_ROUTERS.octets()].concat();
+        pseudo.extend_from_slice(&(encoded.len() as u32).to_be_bytes());
+        pseudo.extend_from_slice(&[0, 0, 0, IPPROTO_ICMPV6]);
+        assert!(verify(&[pseudo, encoded].concat()));
+
+        let query = MldMessage::Query { max_response_delay: Duration::from_secs(10), group: Ipv6Addr::UNSPECIFIED,
+                                        sources: Vec::new() };
+        assert_eq!(query.destination(), ALL_NODES);
+        assert_eq!(MldMessage::parse(&query.encode(&source, &ALL_NODES)), Some(query));
+        let query = MldMessage::Query { max_response_delay: Duration::from_secs(1), group,
+                                        sources: vec!["2001:db8::9".parse().unwrap()] };
+        assert_eq!(query.destination(), group);
+        assert_eq!(MldMessage::parse(&query.encode(&source, &group)), Some(query));
+        // MLDv2 query with the floating point max response code 0x8001 (exponent 0, mantissa 1)
+        let mut query = MldMessage::Query { max_response_delay: Duration::ZERO, group, sources: Vec::new() }
+            .encode(&source, &group);
+        query[4..6].copy_from_slice(&0x8001_u16.to_be_bytes());
+        query.extend_from_slice(&[2, 125, 0, 0]);
+        assert_eq!(MldMessage::parse(&query).unwrap(), MldMessage::Query {
+            max_response_delay: Duration::from_millis(0x1001 << 3), group, sources: Vec::new(),
+        });
+        assert_eq!(MldMessage::parse(&query[..20]), None);
+        assert_eq!(MldMessage::parse(&[128, 0, 0, 0]), None);
+    }
+}
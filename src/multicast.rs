@@ -1,69 +1,85 @@
 use std::{
-    net::{SocketAddrV4, SocketAddrV6, Ipv4Addr, Ipv6Addr},
+    net::{IpAddr, SocketAddr, SocketAddrV4, SocketAddrV6, Ipv4Addr, Ipv6Addr},
     io::{Result, Error, ErrorKind},
-    os::unix::io::FromRawFd
+    time::Duration,
 };
 
-use super::IpInterface;
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
 
 /// Creates a std::net::UdpSocket for multicast reception with SO_REUSEADDR set for IPv4.
 /// # Arguments
 /// * mc_address    The multicast IPv4 address. The socket will only receive from this address/port.
+///   On Windows, where it is not possible to bind to a multicast group address, the socket is
+///   bound to the wildcard address with the group's port instead.
 /// * interface     The local address will determine the interface from which multicast messages
-///                 can be received and this address will also be used as source for sent packets.
+///   can be received and this address will also be used as source for sent packets.
 pub fn create_std_multicast_socket_ipv4(mc_address: &SocketAddrV4, interface: &Ipv4Addr)
                                         -> Result<std::net::UdpSocket> {
+    create_multicast_socket_ipv4(mc_address, interface, Protocol::UDP)
+}
+
+/// Creates a multicast socket of the datagram protocol (UDP or UDP-Lite) for IPv4.
+pub(crate) fn create_multicast_socket_ipv4(mc_address: &SocketAddrV4, interface: &Ipv4Addr, protocol: Protocol)
+                                           -> Result<std::net::UdpSocket> {
+    trace_span!("multicast_socket", group = %mc_address, %interface);
     if !mc_address.ip().is_multicast() {
         return Err(Error::new(ErrorKind::InvalidInput, "mc_address is not multicast"));
     }
-    let socket_fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0) };
-    set_socket_reuseaddr(&socket_fd)?;
-
-    let mc_addr = libc::sockaddr_in {
-        sin_family: libc::AF_INET as u16,
-        sin_port: mc_address.port().to_be(),
-        sin_addr: libc::in_addr { s_addr: u32::from(mc_address.ip().clone()).to_be() },
-        sin_zero: [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
-    };
-    bind_socket(&socket_fd, &mc_addr)?;
-
-    let socket = unsafe{ std::net::UdpSocket::from_raw_fd(socket_fd) };
-    if let Err(e) = socket.join_multicast_v4(mc_address.ip(), interface) {
-        return Err(e);
-    }
-    Ok(socket)
+    let socket = super::sys_socket::socket(Domain::IPV4, Type::DGRAM, Some(protocol))?;
+    socket.set_reuse_address(true)?;
+    super::sys_socket::bind(&socket, &SockAddr::from(bind_address_v4(mc_address)))?;
+    join_v4(&socket, mc_address.ip(), interface)?;
+    trace_created(&socket);
+    Ok(socket.into())
 }
 
 /// Creates a std::net::UdpSocket for multicast reception with SO_REUSEADDR set for IPv6.
 /// # Arguments
 /// * mc_address    The multicast IPv6 address. The socket will only receive from this address/port.
-///                 Note that the function ignores the address' scope id and uses the second octet
-///                 from the IP address instead.
+///   Note that the function ignores the address' scope id and uses the second octet
+///   from the IP address instead. On Windows the socket is bound to the wildcard address.
 /// * interface     The local address will determine the interface from which multicast messages
-///                 can be received and this address will also be used as source for sent packets.
+///   can be received and this address will also be used as source for sent packets.
+///
+/// IPV6_V6ONLY is set independent of the system default, so the socket never receives IPv4
+/// datagrams (which a socket bound to the wildcard address would otherwise do on Windows), use
+/// `MulticastSocketBuilder::only_v6` to create a dual-stack socket.
 pub fn create_std_multicast_socket_ipv6(mc_address: &SocketAddrV6, interface: &Ipv6Addr)
                                         -> Result<std::net::UdpSocket> {
+    create_multicast_socket_ipv6(mc_address, interface, find_interface_index, Protocol::UDP)
+}
+
+/// Creates a std::net::UdpSocket for multicast reception with SO_REUSEADDR set for IPv6, the
+/// interface index for `interface` is looked up in the given InterfaceProvider.
+/// See `create_std_multicast_socket_ipv6` for the arguments.
+#[cfg(unix)]
+pub fn create_std_multicast_socket_ipv6_with(mc_address: &SocketAddrV6, interface: &Ipv6Addr,
+                                             provider: &dyn super::InterfaceProvider)
+                                             -> Result<std::net::UdpSocket> {
+    create_multicast_socket_ipv6(mc_address, interface, |addr| {
+        Ok(provider.find_index_by_address(&std::net::IpAddr::V6(*addr), true)?.unwrap_or(0))
+    }, Protocol::UDP)
+}
+
+/// Creates a multicast socket of the datagram protocol (UDP or UDP-Lite) for IPv6, the interface
+/// index is determined by `find_index`.
+pub(crate) fn create_multicast_socket_ipv6<F>(mc_address: &SocketAddrV6, interface: &Ipv6Addr, find_index: F,
+                                              protocol: Protocol) -> Result<std::net::UdpSocket>
+    where F: FnOnce(&Ipv6Addr) -> Result<u32> {
+    trace_span!("multicast_socket", group = %mc_address, %interface);
     if !mc_address.ip().is_multicast() {
         return Err(Error::new(ErrorKind::InvalidInput, "mc_address is not multicast"));
     }
-    let socket_fd = unsafe { libc::socket(libc::AF_INET6, libc::SOCK_DGRAM, 0) };
-    set_socket_reuseaddr(&socket_fd)?;
-
-    let mc_addr = libc::sockaddr_in6 {
-        sin6_family: libc::AF_INET6 as u16,
-        sin6_port: mc_address.port().to_be(),
-        sin6_flowinfo: mc_address.flowinfo().to_be(),
-        sin6_addr: libc::in6_addr { s6_addr: mc_address.ip().octets() },
-        sin6_scope_id: mc_address.ip().octets()[1] as u32,
-    };
-    bind_socket(&socket_fd, &mc_addr)?;
+    let socket = super::sys_socket::socket(Domain::IPV6, Type::DGRAM, Some(protocol))?;
+    socket.set_reuse_address(true)?;
+    socket.set_only_v6(true)?;
+    super::sys_socket::bind(&socket, &SockAddr::from(bind_address_v6(mc_address)))?;
 
-    let socket = unsafe{ std::net::UdpSocket::from_raw_fd(socket_fd) };
-    let intf_idx = find_interface_index(interface)?;
-    if let Err(e) = socket.join_multicast_v6(mc_address.ip(), intf_idx) {
-        return Err(e);
-    }
-    Ok(socket)
+    let intf_idx = find_index(interface)?;
+    trace_event!(crate::trace::TraceEvent::InterfaceResolved { address: (*interface).into(), index: intf_idx });
+    join_v6(&socket, mc_address.ip(), intf_idx)?;
+    trace_created(&socket);
+    Ok(socket.into())
 }
 
 /// Creates a std::tokio::UdpSocket for multicast reception with SO_REUSEADDR set for IPv4.
@@ -71,7 +87,7 @@ pub fn create_std_multicast_socket_ipv6(mc_address: &SocketAddrV6, interface: &I
 /// # Arguments
 /// * mc_address    The multicast IPv4 address. The socket will only receive from this address/port.
 /// * interface     The local address will determine the interface from which multicast messages
-///                 can be received and this address will also be used as source for sent packets.
+///   can be received and this address will also be used as source for sent packets.
 #[cfg(feature = "tokio-net")]
 pub fn create_tokio_multicast_socket_ipv4(mc_address: &SocketAddrV4, interface: &Ipv4Addr)
                                           -> Result<tokio::net::UdpSocket> {
@@ -85,7 +101,7 @@ pub fn create_tokio_multicast_socket_ipv4(mc_address: &SocketAddrV4, interface:
 /// # Arguments
 /// * mc_address    The multicast IPv6 address. The socket will only receive from this address/port.
 /// * interface     The local address will determine the interface from which multicast messages
-///                 can be received and this address will also be used as source for sent packets.
+///   can be received and this address will also be used as source for sent packets.
 #[cfg(feature = "tokio-net")]
 pub fn create_tokio_multicast_socket_ipv6(mc_address: &SocketAddrV6, interface: &Ipv6Addr)
                                           -> Result<tokio::net::UdpSocket> {
@@ -94,36 +110,526 @@ pub fn create_tokio_multicast_socket_ipv6(mc_address: &SocketAddrV6, interface:
     tokio::net::UdpSocket::from_std(std_socket)
 }
 
-/// Sets the SO_REUSEADDR option on the raw socket
-fn set_socket_reuseaddr(socket: &libc::c_int) -> Result<()> {
-    let optval: libc::c_int = 1;
-    if unsafe { libc::setsockopt(*socket, libc::SOL_SOCKET, libc::SO_REUSEADDR,
-                                 &optval as *const _ as *const libc::c_void,
-                                 std::mem::size_of_val(&optval) as libc::socklen_t) } != 0 {
-        unsafe{ libc::close(*socket) };
-        return Err(std::io::Error::last_os_error());
+/// Well known multicast protocols with the group, port, TTL, loopback and reuse settings they
+/// require, see `MulticastSocketBuilder::preset`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MulticastPreset {
+    /// SSDP (UPnP discovery), 239.255.255.250 / ff02::c port 1900, TTL 2 (UPnP device
+    /// architecture), loopback to find devices on the same host
+    Ssdp,
+    /// multicast DNS, 224.0.0.251 / ff02::fb port 5353, TTL 255 (RFC 6762), loopback and
+    /// SO_REUSEPORT to share the port with other responders of the host
+    Mdns,
+    /// LLMNR, 224.0.0.252 / ff02::1:3 port 5355, TTL 1 (RFC 4795)
+    Llmnr,
+    /// PTP event messages, 224.0.1.129 / ff0e::181 port 319, TTL 1 (IEEE 1588 annex D), no
+    /// loopback of the own messages
+    PtpEvent,
+    /// PTP general messages, 224.0.1.129 / ff0e::181 port 320, TTL 1, no loopback
+    PtpGeneral,
+    /// RTP media stream to the group, TTL 32 (site scope as used by SAP/SDP announcements), no
+    /// loopback
+    Rtp(SocketAddr),
+}
+
+impl MulticastPreset {
+
+    /// Returns the group of the protocol for IPv4 or IPv6, for Rtp the given group.
+    pub fn group(&self, ipv6: bool) -> SocketAddr {
+        let (v4, v6, port) = match self {
+            MulticastPreset::Ssdp => (super::ssdp::SSDP_MULTICAST_V4, Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xc),
+                                      super::ssdp::SSDP_PORT),
+            MulticastPreset::Mdns => (Ipv4Addr::new(224, 0, 0, 251), Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xfb), 5353),
+            MulticastPreset::Llmnr => (super::llmnr::LLMNR_MULTICAST_V4, super::llmnr::LLMNR_MULTICAST_V6,
+                                       super::llmnr::LLMNR_PORT),
+            MulticastPreset::PtpEvent | MulticastPreset::PtpGeneral => {
+                let port = if *self == MulticastPreset::PtpEvent { 319 } else { 320 };
+                (Ipv4Addr::new(224, 0, 1, 129), Ipv6Addr::new(0xff0e, 0, 0, 0, 0, 0, 0, 0x181), port)
+            },
+            MulticastPreset::Rtp(group) => return *group,
+        };
+        if ipv6 { SocketAddr::new(v6.into(), port) } else { SocketAddr::new(v4.into(), port) }
     }
-    Ok(())
 }
 
-/// Bind the socket to the given address
-fn bind_socket<T>(socket: &libc::c_int, addr: &T) -> Result<()> {
-    if unsafe{ libc::bind(*socket, std::ptr::addr_of!(*addr) as *const libc::sockaddr,
-                          std::mem::size_of_val(addr) as libc::socklen_t) } != 0 {
-        unsafe{ libc::close(*socket) };
-        return Err(std::io::Error::last_os_error());
+/// Builder for UDP multicast sockets: binds to the group's port with SO_REUSEADDR, joins the
+/// group on the interface and selects the interface for sent packets, with optional TTL (hop
+/// limit) and loopback settings.
+/// ```no_run
+/// use net_utils::{MulticastPreset, MulticastSocketBuilder};
+/// let socket = MulticastSocketBuilder::preset(MulticastPreset::Ssdp, "192.168.1.2".parse().unwrap())
+///     .build_std()
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MulticastSocketBuilder {
+    group: SocketAddr,
+    interface: IpAddr,
+    ttl: Option<u32>,
+    loopback: Option<bool>,
+    reuse_address: bool,
+    #[cfg(unix)]
+    reuse_port: bool,
+    join: bool,
+    only_v6: bool,
+    #[cfg(target_os = "linux")]
+    report_drops: bool,
+    #[cfg(target_os = "linux")]
+    receive_ttl: bool,
+    #[cfg(target_os = "linux")]
+    receive_tos: bool,
+    #[cfg(target_os = "linux")]
+    ecn: Option<super::pktinfo::Ecn>,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+}
+
+impl MulticastSocketBuilder {
+
+    /// Creates a builder for a socket receiving from the group on the interface with the given
+    /// local address (the unspecified address for the system's default interface). TTL and
+    /// loopback keep the system defaults (TTL 1, loopback enabled).
+    pub fn new(group: SocketAddr, interface: IpAddr) -> MulticastSocketBuilder {
+        MulticastSocketBuilder {
+            group,
+            interface,
+            ttl: None,
+            loopback: None,
+            reuse_address: true,
+            #[cfg(unix)]
+            reuse_port: false,
+            join: true,
+            only_v6: true,
+            #[cfg(target_os = "linux")]
+            report_drops: false,
+            #[cfg(target_os = "linux")]
+            receive_ttl: false,
+            #[cfg(target_os = "linux")]
+            receive_tos: false,
+            #[cfg(target_os = "linux")]
+            ecn: None,
+            read_timeout: None,
+            write_timeout: None,
+        }
+    }
+
+    /// Creates a builder with the settings of the protocol, the group is chosen by the address
+    /// family of the interface address.
+    pub fn preset(preset: MulticastPreset, interface: IpAddr) -> MulticastSocketBuilder {
+        let builder = MulticastSocketBuilder::new(preset.group(interface.is_ipv6()), interface);
+        match preset {
+            MulticastPreset::Ssdp => builder.ttl(2).loopback(true),
+            #[cfg(unix)]
+            MulticastPreset::Mdns => builder.ttl(255).loopback(true).reuse_port(true),
+            #[cfg(not(unix))]
+            MulticastPreset::Mdns => builder.ttl(255).loopback(true),
+            MulticastPreset::Llmnr => builder.ttl(1),
+            MulticastPreset::PtpEvent | MulticastPreset::PtpGeneral => builder.ttl(1).loopback(false),
+            MulticastPreset::Rtp(_) => builder.ttl(32).loopback(false),
+        }
     }
-    Ok(())
+
+    /// Returns the group and port.
+    pub fn group(&self) -> SocketAddr {
+        self.group
+    }
+
+    /// Returns the local address of the interface.
+    pub fn interface(&self) -> IpAddr {
+        self.interface
+    }
+
+    /// Sets the port of the group, e.g. to use a preset with another port.
+    pub fn port(mut self, port: u16) -> Self {
+        self.group.set_port(port);
+        self
+    }
+
+    /// Sets the TTL (IP_MULTICAST_TTL) or hop limit (IPV6_MULTICAST_HOPS) of sent packets.
+    pub fn ttl(mut self, ttl: u32) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Sets whether sent packets are looped back to the sockets of the host (IP_MULTICAST_LOOP,
+    /// IPV6_MULTICAST_LOOP).
+    pub fn loopback(mut self, loopback: bool) -> Self {
+        self.loopback = Some(loopback);
+        self
+    }
+
+    /// Sets SO_REUSEADDR, enabled by default so that multiple sockets can receive the group.
+    pub fn reuse_address(mut self, reuse: bool) -> Self {
+        self.reuse_address = reuse;
+        self
+    }
+
+    /// Sets SO_REUSEPORT, required on the BSDs and macOS to share a port with sockets of other
+    /// processes which set it (e.g. the mDNS responder).
+    #[cfg(unix)]
+    pub fn reuse_port(mut self, reuse: bool) -> Self {
+        self.reuse_port = reuse;
+        self
+    }
+
+    /// Sets whether the group is joined, senders which do not receive from the group need not.
+    pub fn join(mut self, join: bool) -> Self {
+        self.join = join;
+        self
+    }
+
+    /// Sets IPV6_V6ONLY of IPv6 sockets, enabled by default independent of the system default
+    /// (net.ipv6.bindv6only on linux). Disabled, a socket bound to the wildcard address (as on
+    /// Windows) also receives IPv4 datagrams of the port, linux treats sockets bound to the
+    /// group address as IPv6 only anyway. Ignored for IPv4 groups.
+    pub fn only_v6(mut self, only_v6: bool) -> Self {
+        self.only_v6 = only_v6;
+        self
+    }
+
+    /// Enables SO_RXQ_OVFL, so that received datagrams report the number of datagrams dropped
+    /// because the receive queue was full, see `udp::recv_from_with_drops`.
+    #[cfg(target_os = "linux")]
+    pub fn report_drops(mut self, report: bool) -> Self {
+        self.report_drops = report;
+        self
+    }
+
+    /// Enables IP_RECVTTL / IPV6_RECVHOPLIMIT, so that received datagrams report the TTL (hop
+    /// limit) they arrived with, see `pktinfo::recv_with_pktinfo`.
+    #[cfg(target_os = "linux")]
+    pub fn receive_ttl(mut self, receive: bool) -> Self {
+        self.receive_ttl = receive;
+        self
+    }
+
+    /// Enables IP_RECVTOS / IPV6_RECVTCLASS, so that received datagrams report their TOS
+    /// (traffic class) with the ECN codepoint, see `pktinfo::PktInfo::ecn`.
+    #[cfg(target_os = "linux")]
+    pub fn receive_tos(mut self, receive: bool) -> Self {
+        self.receive_tos = receive;
+        self
+    }
+
+    /// Sets the ECN codepoint of sent packets, see `udp::set_ecn`.
+    #[cfg(target_os = "linux")]
+    pub fn ecn(mut self, ecn: super::pktinfo::Ecn) -> Self {
+        self.ecn = Some(ecn);
+        self
+    }
+
+    /// Sets the timeout of blocking receive calls (SO_RCVTIMEO), by default they block
+    /// indefinitely.
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the timeout of blocking send calls (SO_SNDTIMEO), by default they block indefinitely.
+    pub fn write_timeout(mut self, timeout: Duration) -> Self {
+        self.write_timeout = Some(timeout);
+        self
+    }
+
+    /// Creates the std::net::UdpSocket. Fails with InvalidInput if the group is no multicast
+    /// address, the interface address is of another family or a timeout is zero.
+    pub fn build_std(&self) -> Result<std::net::UdpSocket> {
+        Ok(self.build_socket()?.into())
+    }
+
+    /// Creates the tokio::net::UdpSocket.
+    /// Requires the feature 'tokio-net'.
+    #[cfg(feature = "tokio-net")]
+    pub fn build_tokio(&self) -> Result<tokio::net::UdpSocket> {
+        let socket = self.build_std()?;
+        socket.set_nonblocking(true)?;
+        tokio::net::UdpSocket::from_std(socket)
+    }
+
+    fn build_socket(&self) -> Result<Socket> {
+        trace_span!("multicast_socket", group = %self.group, interface = %self.interface);
+        if !self.group.ip().is_multicast() {
+            return Err(Error::new(ErrorKind::InvalidInput, format!("{} is not multicast", self.group.ip())));
+        }
+        if [self.read_timeout, self.write_timeout].iter().flatten().any(|timeout| timeout.is_zero()) {
+            return Err(Error::new(ErrorKind::InvalidInput, "zero socket timeout"));
+        }
+        let socket = super::sys_socket::socket(Domain::for_address(self.group), Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_reuse_address(self.reuse_address)?;
+        #[cfg(unix)]
+        if self.reuse_port {
+            socket.set_reuse_port(true)?;
+        }
+        match (&self.group, &self.interface) {
+            (SocketAddr::V4(group), IpAddr::V4(_)) => {
+                super::sys_socket::bind(&socket, &SockAddr::from(bind_address_v4(group)))?
+            },
+            (SocketAddr::V6(group), IpAddr::V6(_)) => {
+                socket.set_only_v6(self.only_v6)?;
+                super::sys_socket::bind(&socket, &SockAddr::from(bind_address_v6(group)))?;
+            },
+            _ => return Err(self.mismatch_error()),
+        }
+        self.configure_socket(&socket)?;
+        trace_created(&socket);
+        Ok(socket)
+    }
+
+    /// Applies the settings after binding to a socket bound to the group's port: timeouts,
+    /// reporting options, group membership, interface, TTL and loopback. Also used for sockets
+    /// created by others, e.g. passed by systemd.
+    pub(crate) fn configure_socket(&self, socket: &Socket) -> Result<()> {
+        socket.set_read_timeout(self.read_timeout)?;
+        socket.set_write_timeout(self.write_timeout)?;
+        #[cfg(target_os = "linux")]
+        if self.report_drops {
+            super::udp::set_drop_reporting(socket, true)?;
+        }
+        #[cfg(target_os = "linux")]
+        if self.receive_ttl {
+            super::pktinfo::set_recv_ttl(socket, true)?;
+        }
+        match (&self.group, &self.interface) {
+            (SocketAddr::V4(group), IpAddr::V4(interface)) => {
+                if self.join {
+                    join_v4(socket, group.ip(), interface)?;
+                }
+                socket.set_multicast_if_v4(interface)?;
+                if let Some(ttl) = self.ttl {
+                    socket.set_multicast_ttl_v4(ttl)?;
+                }
+                if let Some(loopback) = self.loopback {
+                    socket.set_multicast_loop_v4(loopback)?;
+                }
+            },
+            (SocketAddr::V6(group), IpAddr::V6(interface)) => {
+                let index = find_interface_index(interface)?;
+                trace_event!(crate::trace::TraceEvent::InterfaceResolved { address: (*interface).into(), index });
+                if self.join {
+                    join_v6(socket, group.ip(), index)?;
+                }
+                socket.set_multicast_if_v6(index)?;
+                if let Some(ttl) = self.ttl {
+                    socket.set_multicast_hops_v6(ttl)?;
+                }
+                if let Some(loopback) = self.loopback {
+                    socket.set_multicast_loop_v6(loopback)?;
+                }
+            },
+            _ => return Err(self.mismatch_error()),
+        }
+        #[cfg(target_os = "linux")]
+        if self.receive_tos {
+            super::pktinfo::set_recv_tos(socket, true)?;
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(ecn) = self.ecn {
+            super::udp::set_ecn(socket, ecn)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the local address a socket of the builder is bound to.
+    #[cfg(all(target_os = "linux", feature = "systemd"))]
+    pub(crate) fn bind_address(&self) -> SocketAddr {
+        match self.group {
+            SocketAddr::V4(group) => bind_address_v4(&group).into(),
+            SocketAddr::V6(group) => bind_address_v6(&group).into(),
+        }
+    }
+
+    fn mismatch_error(&self) -> Error {
+        Error::new(ErrorKind::InvalidInput, format!("interface address {} does not match group {}", self.interface, self.group))
+    }
+
+    /// Returns whether a socket of this builder joined the group and can be moved to the
+    /// interface of the other builder by `join_socket` and `leave_socket`, which is the case if
+    /// the builders differ only in the interface.
+    pub(crate) fn is_rejoin(&self, other: &MulticastSocketBuilder) -> bool {
+        let mut moved = other.clone();
+        moved.interface = self.interface;
+        self.join && self.interface != other.interface && *self == moved
+    }
+
+    /// Joins the group on the interface of the builder with the socket created by another
+    /// builder and selects the interface for sent packets. If the selection fails the group is
+    /// left again.
+    pub(crate) fn join_socket(&self, socket: &std::net::UdpSocket) -> Result<()> {
+        let sock_ref = socket2::SockRef::from(socket);
+        match (&self.group, &self.interface) {
+            (SocketAddr::V4(group), IpAddr::V4(interface)) => join_v4(&sock_ref, group.ip(), interface)?,
+            (SocketAddr::V6(group), IpAddr::V6(interface)) => join_v6(&sock_ref, group.ip(), find_interface_index(interface)?)?,
+            _ => return Err(self.mismatch_error()),
+        }
+        self.select_interface(socket).inspect_err(|_| { let _ = self.leave_socket(socket); })
+    }
+
+    /// Selects the interface of the builder for the packets sent by the socket.
+    pub(crate) fn select_interface(&self, socket: &std::net::UdpSocket) -> Result<()> {
+        let socket = socket2::SockRef::from(socket);
+        match &self.interface {
+            IpAddr::V4(interface) => socket.set_multicast_if_v4(interface),
+            IpAddr::V6(interface) => socket.set_multicast_if_v6(find_interface_index(interface)?),
+        }
+    }
+
+    /// Leaves the group on the interface of the builder.
+    pub(crate) fn leave_socket(&self, socket: &std::net::UdpSocket) -> Result<()> {
+        let socket = socket2::SockRef::from(socket);
+        match (&self.group, &self.interface) {
+            (SocketAddr::V4(group), IpAddr::V4(interface)) => leave_v4(&socket, group.ip(), interface),
+            (SocketAddr::V6(group), IpAddr::V6(interface)) => leave_v6(&socket, group.ip(), find_interface_index(interface)?),
+            _ => Err(self.mismatch_error()),
+        }
+    }
+}
+
+/// Joins the IPv4 group on the interface with the address.
+fn join_v4(socket: &Socket, group: &Ipv4Addr, interface: &Ipv4Addr) -> Result<()> {
+    let result = super::sys_socket::join_multicast_v4(socket, group, interface);
+    trace_event!(crate::trace::TraceEvent::GroupJoined {
+        group: (*group).into(), interface: crate::trace::TraceInterface::Address((*interface).into()),
+        error: result.as_ref().err(),
+    });
+    result
+}
+
+/// Joins the IPv6 group on the interface with the index.
+fn join_v6(socket: &Socket, group: &Ipv6Addr, index: u32) -> Result<()> {
+    let result = super::sys_socket::join_multicast_v6(socket, group, index);
+    trace_event!(crate::trace::TraceEvent::GroupJoined {
+        group: (*group).into(), interface: crate::trace::TraceInterface::Index(index), error: result.as_ref().err(),
+    });
+    result
+}
+
+/// Leaves the IPv4 group on the interface with the address.
+fn leave_v4(socket: &Socket, group: &Ipv4Addr, interface: &Ipv4Addr) -> Result<()> {
+    let result = super::sys_socket::leave_multicast_v4(socket, group, interface);
+    trace_event!(crate::trace::TraceEvent::GroupLeft {
+        group: (*group).into(), interface: crate::trace::TraceInterface::Address((*interface).into()),
+        error: result.as_ref().err(),
+    });
+    result
+}
+
+/// Leaves the IPv6 group on the interface with the index.
+fn leave_v6(socket: &Socket, group: &Ipv6Addr, index: u32) -> Result<()> {
+    let result = super::sys_socket::leave_multicast_v6(socket, group, index);
+    trace_event!(crate::trace::TraceEvent::GroupLeft {
+        group: (*group).into(), interface: crate::trace::TraceInterface::Index(index), error: result.as_ref().err(),
+    });
+    result
+}
+
+#[cfg_attr(not(feature = "trace"), allow(unused_variables))]
+fn trace_created(socket: &Socket) {
+    trace_event!(crate::trace::TraceEvent::SocketCreated {
+        kind: "udp-multicast", address: socket.local_addr().ok().and_then(|address| address.as_socket()),
+    });
+}
+
+/// Receives a datagram from the blocking socket, waiting at most for the timeout. Returns
+/// Ok(None) if no datagram arrived in time. The read timeout of the socket is restored. Fails
+/// with InvalidInput for a zero timeout.
+pub fn recv_from_timeout(socket: &std::net::UdpSocket, buf: &mut [u8], timeout: Duration)
+                         -> Result<Option<(usize, SocketAddr)>> {
+    let previous = socket.read_timeout()?;
+    socket.set_read_timeout(Some(timeout))?;
+    let result = socket.recv_from(buf);
+    socket.set_read_timeout(previous)?;
+    match result {
+        Ok(received) => Ok(Some(received)),
+        Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Returns the local address a multicast socket for the given IPv4 group has to be bound to.
+#[cfg(not(windows))]
+fn bind_address_v4(mc_address: &SocketAddrV4) -> SocketAddrV4 {
+    *mc_address
+}
+
+/// Returns the local address a multicast socket for the given IPv4 group has to be bound to.
+/// Windows refuses to bind to a multicast address, so the wildcard address is used.
+#[cfg(windows)]
+fn bind_address_v4(mc_address: &SocketAddrV4) -> SocketAddrV4 {
+    SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, mc_address.port())
+}
+
+/// Returns the local address a multicast socket for the given IPv6 group has to be bound to.
+#[cfg(not(windows))]
+fn bind_address_v6(mc_address: &SocketAddrV6) -> SocketAddrV6 {
+    SocketAddrV6::new(*mc_address.ip(), mc_address.port(), mc_address.flowinfo(),
+                      mc_address.ip().octets()[1] as u32)
+}
+
+/// Returns the local address a multicast socket for the given IPv6 group has to be bound to.
+/// Windows refuses to bind to a multicast address, so the wildcard address is used.
+#[cfg(windows)]
+fn bind_address_v6(mc_address: &SocketAddrV6) -> SocketAddrV6 {
+    SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, mc_address.port(), 0, 0)
+}
+
+/// Searches for an IP multicast capable interface with the given address and returns its index.
+/// If no interface is found Ok(0) is returned, where 0 can be used as ANY_INTERFACE.
+#[cfg(unix)]
+pub(crate) fn find_interface_index(addr: &Ipv6Addr) -> Result<u32> {
+    use super::InterfaceProvider;
+    let index = super::InterfaceCache::global()
+        .find_index_by_address(&std::net::IpAddr::V6(*addr), true)?;
+    Ok(index.unwrap_or(0))
 }
 
 /// Searches for an IP multicast capable interface with the given address and returns its index.
 /// If no interface is found Ok(0) is returned, where 0 can be used as ANY_INTERFACE.
-fn find_interface_index(addr: &Ipv6Addr) -> Result<u32> {
-    let intfs = IpInterface::retrieve_ip_interfaces()?;
-    for intf in intfs.iter() {
-        if intf.address.ip() == *addr && intf.supports_multicast() {
-            return Ok(intf.index)
+#[cfg(windows)]
+pub(crate) fn find_interface_index(addr: &Ipv6Addr) -> Result<u32> {
+    use windows_sys::Win32::{
+        Foundation::{ERROR_BUFFER_OVERFLOW, NO_ERROR},
+        NetworkManagement::IpHelper::{GetAdaptersAddresses, GAA_FLAG_SKIP_ANYCAST,
+                                       GAA_FLAG_SKIP_MULTICAST, GAA_FLAG_SKIP_DNS_SERVER,
+                                       IP_ADAPTER_ADDRESSES_LH, IP_ADAPTER_NO_MULTICAST},
+        Networking::WinSock::{AF_INET6, SOCKADDR_IN6},
+    };
+
+    if addr.is_unspecified() {
+        return Ok(0);
+    }
+    let flags = GAA_FLAG_SKIP_ANYCAST | GAA_FLAG_SKIP_MULTICAST | GAA_FLAG_SKIP_DNS_SERVER;
+    let mut size: u32 = 16 * 1024;
+    let mut buffer: Vec<u64> = Vec::new();
+    loop {
+        buffer.resize((size as usize).div_ceil(8), 0);
+        let rc = unsafe {
+            GetAdaptersAddresses(AF_INET6 as u32, flags, std::ptr::null(),
+                                 buffer.as_mut_ptr() as *mut IP_ADAPTER_ADDRESSES_LH, &mut size)
+        };
+        match rc {
+            NO_ERROR => break,
+            ERROR_BUFFER_OVERFLOW => continue,
+            _ => return Err(Error::from_raw_os_error(rc as i32)),
+        }
+    }
+
+    let mut adapter = buffer.as_ptr() as *const IP_ADAPTER_ADDRESSES_LH;
+    while !adapter.is_null() {
+        let a = unsafe { &*adapter };
+        let multicast_capable = unsafe { a.Anonymous2.Flags } & IP_ADAPTER_NO_MULTICAST == 0;
+        let mut unicast = a.FirstUnicastAddress;
+        while multicast_capable && !unicast.is_null() {
+            let u = unsafe { &*unicast };
+            let sa = u.Address.lpSockaddr;
+            if !sa.is_null() && unsafe { (*sa).sa_family } == AF_INET6 {
+                let sa6 = unsafe { &*(sa as *const SOCKADDR_IN6) };
+                if Ipv6Addr::from(unsafe { sa6.sin6_addr.u.Byte }) == *addr {
+                    return Ok(a.Ipv6IfIndex);
+                }
+            }
+            unicast = u.Next;
         }
+        adapter = a.Next;
     }
     Ok(0)
 }
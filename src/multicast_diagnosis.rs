@@ -0,0 +1,396 @@
+//! Self-test of the multicast path of a group on an interface, the checklist everybody works
+//! through when multicast "does not work": interface flags and addresses, reverse path
+//! filtering, the membership in /proc/net/igmp after joining, whether a datagram sent to the
+//! group is looped back, and whether an IGMP or MLD querier is present on the link.
+//! ```no_run
+//! let diagnosis = net_utils::multicast_diagnosis::diagnose_multicast("239.1.2.3".parse().unwrap(), "eth0").unwrap();
+//! print!("{}", diagnosis);
+//! for problem in diagnosis.problems() {
+//!     println!("{}", problem);
+//! }
+//! ```
+
+use std::{
+    fmt,
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use socket2::{Domain, InterfaceIndexOrAddress, Protocol, SockAddr, Type};
+
+use super::{
+    netlink,
+    recv_from_timeout,
+    multicast_groups::is_joined,
+    snooping::{Querier, SnoopingObserver},
+    sysinfo::{RpFilter, effective_rp_filter},
+};
+
+/// Time `diagnose_multicast` listens for queries. Queriers query every 125 seconds by default,
+/// so a missing querier is only a hint unless the wait covers the query interval.
+pub const DEFAULT_QUERIER_WAIT: Duration = Duration::from_secs(3);
+
+/// Time to wait for the looped back datagram.
+const LOOPBACK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A likely problem found by `diagnose_multicast`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MulticastProblem {
+    /// there is no interface with the name, no other check was run
+    InterfaceNotFound,
+
+    /// the interface is administratively down (IFF_UP not set)
+    InterfaceDown,
+
+    /// the interface has no carrier (IFF_LOWER_UP not set)
+    NoCarrier,
+
+    /// the interface is not multicast capable (IFF_MULTICAST not set)
+    MulticastDisabled,
+
+    /// the interface has no address of the group's family, a link-local one for IPv6
+    NoAddress,
+
+    /// strict reverse path filtering drops datagrams from sources not routed via the interface
+    StrictRpFilter,
+
+    /// joining the group failed with the error
+    JoinFailed(String),
+
+    /// the group is not listed as joined on the interface in /proc despite the join
+    NotListed,
+
+    /// sending to the group failed with the error
+    SendFailed(String),
+
+    /// a datagram sent to the group was not looped back
+    NoLoopback,
+
+    /// no IGMP or MLD query was received within the wait time
+    NoQuerier,
+}
+
+impl fmt::Display for MulticastProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MulticastProblem::InterfaceNotFound => write!(f, "interface not found"),
+            MulticastProblem::InterfaceDown => write!(f, "interface is down"),
+            MulticastProblem::NoCarrier => write!(f, "interface has no carrier, check the cable or link partner"),
+            MulticastProblem::MulticastDisabled => write!(f, "interface is not multicast capable (ip link set multicast on)"),
+            MulticastProblem::NoAddress => write!(f, "interface has no address of the group's family"),
+            MulticastProblem::StrictRpFilter =>
+                write!(f, "strict rp_filter drops datagrams from sources not routed via the interface"),
+            MulticastProblem::JoinFailed(error) => write!(f, "joining the group failed: {}", error),
+            MulticastProblem::NotListed => write!(f, "group is not listed as joined in /proc"),
+            MulticastProblem::SendFailed(error) => write!(f, "sending to the group failed: {}", error),
+            MulticastProblem::NoLoopback => write!(f, "datagram sent to the group was not looped back, check the firewall"),
+            MulticastProblem::NoQuerier =>
+                write!(f, "no querier seen, snooping switches may stop forwarding the group after some minutes"),
+        }
+    }
+}
+
+/// The results of the checks of `diagnose_multicast`, interpreted by `problems`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MulticastDiagnosis {
+    /// the tested group
+    pub group: IpAddr,
+
+    /// name of the tested interface
+    pub interface: String,
+
+    /// index of the interface, None if it does not exist
+    pub interface_index: Option<u32>,
+
+    /// IFF_* flags of the interface
+    pub flags: u32,
+
+    /// the first address of the group's family of the interface (the link-local one for IPv6)
+    pub address: Option<IpAddr>,
+
+    /// effective reverse path filter of the interface, None for IPv6 groups
+    pub rp_filter: Option<RpFilter>,
+
+    /// error of joining the group, None if the join succeeded
+    pub join_error: Option<String>,
+
+    /// whether the group was listed as joined on the interface in /proc
+    pub listed: bool,
+
+    /// error of sending to the group, None if the datagram was sent
+    pub send_error: Option<String>,
+
+    /// whether the sent datagram was received
+    pub looped_back: bool,
+
+    /// whether queries were listened for, false without CAP_NET_RAW or for a zero wait
+    pub querier_checked: bool,
+
+    /// the querier of the interface and address family if one was seen
+    pub querier: Option<Querier>,
+}
+
+impl MulticastDiagnosis {
+
+    /// Creates the diagnosis of an interface which does not exist, without results.
+    fn new(group: IpAddr, interface: &str) -> MulticastDiagnosis {
+        MulticastDiagnosis {
+            group, interface: interface.to_string(), interface_index: None, flags: 0, address: None, rp_filter: None,
+            join_error: None, listed: false, send_error: None, looped_back: false, querier_checked: false, querier: None,
+        }
+    }
+
+    /// Returns the likely problems in the order of the checks, empty if all checks passed.
+    pub fn problems(&self) -> Vec<MulticastProblem> {
+        if self.interface_index.is_none() {
+            return vec![MulticastProblem::InterfaceNotFound];
+        }
+        let flag = |flag: libc::c_int| self.flags & flag as u32 != 0;
+        let mut problems = Vec::new();
+        if !flag(libc::IFF_UP) {
+            problems.push(MulticastProblem::InterfaceDown);
+        } else if !flag(libc::IFF_LOWER_UP) {
+            problems.push(MulticastProblem::NoCarrier);
+        }
+        // loopback interfaces loop multicast without the flag
+        if !flag(libc::IFF_MULTICAST) && !flag(libc::IFF_LOOPBACK) {
+            problems.push(MulticastProblem::MulticastDisabled);
+        }
+        if self.address.is_none() {
+            problems.push(MulticastProblem::NoAddress);
+        }
+        if self.rp_filter == Some(RpFilter::Strict) {
+            problems.push(MulticastProblem::StrictRpFilter);
+        }
+        if let Some(error) = &self.join_error {
+            problems.push(MulticastProblem::JoinFailed(error.clone()));
+        } else if !self.listed {
+            problems.push(MulticastProblem::NotListed);
+        }
+        if let Some(error) = &self.send_error {
+            problems.push(MulticastProblem::SendFailed(error.clone()));
+        } else if !self.looped_back {
+            problems.push(MulticastProblem::NoLoopback);
+        }
+        if self.querier_checked && self.querier.is_none() {
+            problems.push(MulticastProblem::NoQuerier);
+        }
+        problems
+    }
+
+    /// Returns whether all checks passed.
+    pub fn is_ok(&self) -> bool {
+        self.problems().is_empty()
+    }
+}
+
+impl fmt::Display for MulticastDiagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let yes_no = |value: bool| if value { "yes" } else { "no" };
+        writeln!(f, "group {} on {}", self.group, self.interface)?;
+        let index = match self.interface_index {
+            Some(index) => index,
+            None => return writeln!(f, "  interface not found"),
+        };
+        writeln!(f, "  index {}, flags {:#x}", index, self.flags)?;
+        match self.address {
+            Some(address) => writeln!(f, "  address {}", address)?,
+            None => writeln!(f, "  address none")?,
+        }
+        if let Some(rp_filter) = self.rp_filter {
+            writeln!(f, "  rp_filter {:?}", rp_filter)?;
+        }
+        match &self.join_error {
+            Some(error) => writeln!(f, "  joined no ({})", error)?,
+            None => writeln!(f, "  joined yes, listed in /proc {}", yes_no(self.listed))?,
+        }
+        match &self.send_error {
+            Some(error) => writeln!(f, "  sent no ({})", error)?,
+            None => writeln!(f, "  sent yes, looped back {}", yes_no(self.looped_back))?,
+        }
+        match (self.querier_checked, &self.querier) {
+            (false, _) => writeln!(f, "  querier not checked"),
+            (true, None) => writeln!(f, "  querier none"),
+            (true, Some(querier)) => writeln!(f, "  querier {} version {}", querier.address, querier.version),
+        }
+    }
+}
+
+/// Runs the checks of the multicast path of the group on the interface with the given name,
+/// listening `DEFAULT_QUERIER_WAIT` for queries, see `diagnose_multicast_with`.
+pub fn diagnose_multicast(group: IpAddr, interface: &str) -> Result<MulticastDiagnosis> {
+    diagnose_multicast_with(group, interface, DEFAULT_QUERIER_WAIT)
+}
+
+/// Runs the checks of the multicast path of the group on the interface with the given name:
+/// reads the interface flags, addresses and rp_filter, joins the group with a UDP socket bound
+/// to the interface, verifies the membership in /proc, sends a datagram to the group and waits
+/// for its loopback, and listens `querier_wait` for queries (which requires CAP_NET_RAW and is
+/// skipped without). Failed checks are reported in the diagnosis, the call only fails if the
+/// group is no multicast address (InvalidInput) or the system cannot be queried.
+pub fn diagnose_multicast_with(group: IpAddr, interface: &str, querier_wait: Duration) -> Result<MulticastDiagnosis> {
+    if !group.is_multicast() {
+        return Err(Error::new(ErrorKind::InvalidInput, format!("{} is not multicast", group)));
+    }
+    let mut diagnosis = MulticastDiagnosis::new(group, interface);
+    let link = match netlink::links()?.into_iter().find(|link| link.name.as_deref() == Some(interface)) {
+        Some(link) => link,
+        None => return Ok(diagnosis),
+    };
+    let ipv6 = group.is_ipv6();
+    diagnosis.interface_index = Some(link.index);
+    diagnosis.flags = link.flags;
+    diagnosis.address = netlink::addresses()?.into_iter()
+        .map(|address| (address.index, address.address))
+        .find(|(index, address)| *index == link.index && match address {
+            IpAddr::V4(_) => !ipv6,
+            IpAddr::V6(address) => ipv6 && address.is_unicast_link_local(),
+        })
+        .map(|(_, address)| address);
+    if !ipv6 {
+        diagnosis.rp_filter = Some(effective_rp_filter(interface)?);
+    }
+
+    // listen for queries while the other checks run
+    let deadline = Instant::now() + querier_wait;
+    let mut observer = match querier_wait.is_zero() {
+        true => None,
+        false => match SnoopingObserver::new(Some(link.index)) {
+            Ok(observer) => Some(observer),
+            Err(e) if e.kind() == ErrorKind::PermissionDenied => None,
+            Err(e) => return Err(e),
+        },
+    };
+
+    let domain = Domain::for_address(SocketAddr::new(group, 0));
+    let socket = super::sys_socket::socket(domain, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.bind_device(Some(interface.as_bytes()))?;
+    let joined = match group {
+        IpAddr::V4(group) => {
+            super::sys_socket::bind(&socket, &SockAddr::from(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)))?;
+            socket.set_multicast_loop_v4(true)?;
+            socket.set_multicast_ttl_v4(1)?;
+            socket.join_multicast_v4_n(&group, &InterfaceIndexOrAddress::Index(link.index))
+        },
+        IpAddr::V6(group) => {
+            socket.set_only_v6(true)?;
+            super::sys_socket::bind(&socket, &SockAddr::from(SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0)))?;
+            socket.set_multicast_if_v6(link.index)?;
+            socket.set_multicast_loop_v6(true)?;
+            socket.set_multicast_hops_v6(1)?;
+            socket.join_multicast_v6(&group, link.index)
+        },
+    };
+    match joined {
+        Ok(()) => diagnosis.listed = is_joined(&group, link.index)?,
+        Err(e) => diagnosis.join_error = Some(e.to_string()),
+    }
+    let socket: UdpSocket = socket.into();
+    diagnosis.looped_back = match send_probe(&socket, group, link.index) {
+        Ok(looped_back) => looped_back,
+        Err(e) => {
+            diagnosis.send_error = Some(e.to_string());
+            false
+        },
+    };
+
+    if let Some(observer) = &mut observer {
+        diagnosis.querier_checked = true;
+        diagnosis.querier = wait_for_querier(observer, link.index, ipv6, deadline)?;
+    }
+    Ok(diagnosis)
+}
+
+/// Sends a probe datagram to the group at the port of the socket, returns whether it was
+/// received within `LOOPBACK_TIMEOUT`.
+fn send_probe(socket: &UdpSocket, group: IpAddr, interface_index: u32) -> Result<bool> {
+    let port = socket.local_addr()?.port();
+    let destination = match group {
+        IpAddr::V4(group) => SocketAddr::new(IpAddr::V4(group), port),
+        IpAddr::V6(group) => SocketAddr::V6(SocketAddrV6::new(group, port, 0, interface_index)),
+    };
+    let mut token = [0_u8; 8];
+    super::random::random_bytes(&mut token);
+    let probe = format!("net-utils multicast probe {} {:016x}", std::process::id(), u64::from_be_bytes(token));
+    socket.send_to(probe.as_bytes(), destination)?;
+    let deadline = Instant::now() + LOOPBACK_TIMEOUT;
+    let mut buffer = [0_u8; 128];
+    while let Some(timeout) = deadline.checked_duration_since(Instant::now()).filter(|timeout| !timeout.is_zero()) {
+        match recv_from_timeout(socket, &mut buffer, timeout)? {
+            Some((len, _)) if buffer[..len] == *probe.as_bytes() => return Ok(true),
+            Some(_) => {},
+            None => break,
+        }
+    }
+    Ok(false)
+}
+
+/// Receives IGMP and MLD messages until a querier of the interface and address family is known
+/// or the deadline passed.
+fn wait_for_querier(observer: &mut SnoopingObserver, interface_index: u32, ipv6: bool, deadline: Instant)
+                    -> Result<Option<Querier>> {
+    while observer.table().querier(interface_index, ipv6).is_none() {
+        let timeout = match deadline.checked_duration_since(Instant::now()) {
+            Some(timeout) if !timeout.is_zero() => timeout,
+            _ => break,
+        };
+        observer.set_read_timeout(Some(timeout))?;
+        match observer.recv() {
+            Ok(_) => {},
+            Err(e) if e.kind() == ErrorKind::TimedOut => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(observer.table().querier(interface_index, ipv6).copied())
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn diagnosis() -> MulticastDiagnosis {
+        MulticastDiagnosis {
+            interface_index: Some(2),
+            flags: (libc::IFF_UP | libc::IFF_LOWER_UP | libc::IFF_MULTICAST) as u32,
+            address: Some("192.0.2.2".parse().unwrap()),
+            rp_filter: Some(RpFilter::Loose),
+            listed: true,
+            looped_back: true,
+            ..MulticastDiagnosis::new("239.1.2.3".parse().unwrap(), "eth0")
+        }
+    }
+
+    #[test]
+    fn test_problems() {
+        assert!(diagnosis().is_ok());
+        assert_eq!(MulticastDiagnosis::new("239.1.2.3".parse().unwrap(), "eth9").problems(),
+                   vec![MulticastProblem::InterfaceNotFound]);
+
+        let diagnosis = MulticastDiagnosis {
+            flags: libc::IFF_UP as u32,
+            rp_filter: Some(RpFilter::Strict),
+            listed: false,
+            querier_checked: true,
+            ..diagnosis()
+        };
+        assert_eq!(diagnosis.problems(), vec![
+            MulticastProblem::NoCarrier, MulticastProblem::MulticastDisabled, MulticastProblem::StrictRpFilter,
+            MulticastProblem::NotListed, MulticastProblem::NoQuerier,
+        ]);
+        assert!(format!("{}", diagnosis).contains("querier none"));
+
+        let diagnosis = MulticastDiagnosis {
+            flags: (libc::IFF_LOOPBACK | libc::IFF_LOWER_UP) as u32,
+            join_error: Some("No such device".to_string()),
+            send_error: Some("Network is unreachable".to_string()),
+            ..diagnosis
+        };
+        assert_eq!(diagnosis.problems(), vec![
+            MulticastProblem::InterfaceDown, MulticastProblem::StrictRpFilter,
+            MulticastProblem::JoinFailed("No such device".to_string()),
+            MulticastProblem::SendFailed("Network is unreachable".to_string()), MulticastProblem::NoQuerier,
+        ]);
+    }
+}
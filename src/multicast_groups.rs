@@ -0,0 +1,151 @@
+//! Inspection of the IPv4 and IPv6 multicast groups the host has joined per interface, read
+//! from /proc/net/igmp and /proc/net/igmp6, e.g. to verify that a join took effect.
+
+use std::{
+    convert::TryInto,
+    io::{ErrorKind, Result},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+use super::IpInterface;
+
+const PROC_NET_IGMP: &str = "/proc/net/igmp";
+const PROC_NET_IGMP6: &str = "/proc/net/igmp6";
+
+/// Membership of an interface in a multicast group.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GroupMembership {
+    /// interface index
+    pub index: u32,
+
+    /// interface name
+    pub interface: String,
+
+    /// multicast group address
+    pub group: IpAddr,
+
+    /// number of memberships (sockets and kernel users) of the group on the interface
+    pub users: u32,
+}
+
+/// Retrieves the multicast group memberships of all interfaces. IPv6 memberships are missing if
+/// IPv6 is disabled.
+pub fn joined_groups() -> Result<Vec<GroupMembership>> {
+    let mut memberships = parse_igmp(&std::fs::read_to_string(PROC_NET_IGMP)?);
+    match std::fs::read_to_string(PROC_NET_IGMP6) {
+        Ok(igmp6) => memberships.extend(parse_igmp6(&igmp6)),
+        Err(e) if e.kind() == ErrorKind::NotFound => {},
+        Err(e) => return Err(e),
+    }
+    Ok(memberships)
+}
+
+/// Retrieves the multicast groups joined on the interface with the given index.
+pub fn joined_groups_on(index: u32) -> Result<Vec<IpAddr>> {
+    Ok(joined_groups()?.into_iter().filter(|m| m.index == index).map(|m| m.group).collect())
+}
+
+/// Returns whether the group is joined on the interface with the given index.
+pub fn is_joined(group: &IpAddr, index: u32) -> Result<bool> {
+    Ok(joined_groups()?.iter().any(|m| m.index == index && m.group == *group))
+}
+
+impl IpInterface {
+
+    /// Retrieves the multicast groups of the address family of this interface configuration
+    /// which are joined on the interface.
+    pub fn joined_groups(&self) -> Result<Vec<IpAddr>> {
+        let ipv4 = self.address.is_ipv4();
+        Ok(joined_groups_on(self.index)?.into_iter().filter(|group| group.is_ipv4() == ipv4).collect())
+    }
+}
+
+/// Parses /proc/net/igmp: an interface line ("1\tlo        :     1      V3") followed by one line
+/// per group ("\t\t\t\t010000E0     1 0:00000000\t\t0") with the group in network byte order
+/// printed as host integer.
+fn parse_igmp(content: &str) -> Vec<GroupMembership> {
+    let mut memberships = Vec::new();
+    let mut interface: Option<(u32, String)> = None;
+    for line in content.lines().skip(1) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if !line.starts_with(char::is_whitespace) {
+            interface = match (fields.first().and_then(|index| index.parse().ok()), fields.get(1)) {
+                (Some(index), Some(name)) => Some((index, name.trim_end_matches(':').to_string())),
+                _ => None,
+            };
+            continue;
+        }
+        let (index, name) = match &interface {
+            Some(interface) => interface,
+            None => continue,
+        };
+        let group = fields.first().and_then(|group| u32::from_str_radix(group, 16).ok());
+        let users = fields.get(1).and_then(|users| users.parse().ok());
+        if let (Some(group), Some(users)) = (group, users) {
+            memberships.push(GroupMembership {
+                index: *index,
+                interface: name.clone(),
+                group: IpAddr::V4(Ipv4Addr::from(group.to_ne_bytes())),
+                users,
+            });
+        }
+    }
+    memberships
+}
+
+/// Parses /proc/net/igmp6: one line per group with index, interface name, group as 32 hex
+/// digits, users, flags and timer.
+fn parse_igmp6(content: &str) -> Vec<GroupMembership> {
+    content.lines().filter_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 4 || fields[2].len() != 32 {
+            return None;
+        }
+        let mut octets = [0_u8; 16];
+        for (i, octet) in octets.iter_mut().enumerate() {
+            *octet = u8::from_str_radix(&fields[2][2 * i..2 * i + 2], 16).ok()?;
+        }
+        Some(GroupMembership {
+            index: fields[0].parse().ok()?,
+            interface: fields[1].to_string(),
+            group: IpAddr::V6(Ipv6Addr::from(u128::from_be_bytes(octets[..].try_into().unwrap()))),
+            users: fields[3].parse().ok()?,
+        })
+    }).collect()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_parse_igmp() {
+        let group = u32::from_ne_bytes([239, 1, 2, 3]);
+        let content = format!("Idx\tDevice    : Count Querier\tGroup    Users Timer\tReporter\n\
+                               1\tlo        :     1      V3\n\
+                               \t\t\t\t010000E0     1 0:00000000\t\t0\n\
+                               4\teth0      :     2      V3\n\
+                               \t\t\t\t{:08X}     2 0:00000000\t\t0\n\
+                               \t\t\t\t010000E0     1 0:00000000\t\t0\n", group);
+        let memberships = parse_igmp(&content);
+        assert_eq!(memberships.len(), 3);
+        assert_eq!(memberships[1], GroupMembership {
+            index: 4, interface: "eth0".to_string(), group: "239.1.2.3".parse().unwrap(), users: 2,
+        });
+        assert_eq!(memberships[0].interface, "lo");
+        assert_eq!(memberships[0].group, "224.0.0.1".parse::<IpAddr>().unwrap());
+    }
+
+    #[test]
+    fn test_parse_igmp6() {
+        let content = "1    lo              ff020000000000000000000000000001     1 0000000C 0\n\
+                       4    eth0            ff0200000000000000000001ff000002     3 00000004 0\n\
+                       garbage\n";
+        let memberships = parse_igmp6(content);
+        assert_eq!(memberships.len(), 2);
+        assert_eq!(memberships[1], GroupMembership {
+            index: 4, interface: "eth0".to_string(), group: "ff02::1:ff00:2".parse().unwrap(), users: 3,
+        });
+    }
+}
@@ -0,0 +1,857 @@
+//! Netlink (rtnetlink) sockets with a message builder and parsers for links, addresses, routes
+//! and neighbors.
+
+use std::{
+    convert::TryInto,
+    io::{Result, Error},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    os::unix::io::{AsRawFd, OwnedFd, RawFd},
+    time::Duration,
+};
+
+use socket2::SockAddr;
+
+use super::{IpAddressInfo, IpNet, sockopt::set_option, sys_api};
+
+/// Length of the netlink message header (struct nlmsghdr).
+pub const NLMSG_HDRLEN: usize = 16;
+/// Length of the route attribute header (struct rtattr).
+pub const RTA_HDRLEN: usize = 4;
+const RECV_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Flag of nested attributes (NLA_F_NESTED).
+pub const NLA_F_NESTED: u16 = 0x8000;
+
+/// Length of the generic netlink header (struct genlmsghdr).
+pub const GENL_HDRLEN: usize = 4;
+const GENL_ID_CTRL: u16 = 0x10;
+const CTRL_CMD_GETFAMILY: u8 = 3;
+const CTRL_ATTR_FAMILY_ID: u16 = 1;
+const CTRL_ATTR_FAMILY_NAME: u16 = 2;
+
+pub(crate) const IFINFOMSG_LEN: usize = 16;
+pub(crate) const IFLA_ADDRESS: u16 = 1;
+pub(crate) const IFLA_IFNAME: u16 = 3;
+const IFLA_MTU: u16 = 4;
+pub(crate) const IFLA_LINK: u16 = 5;
+pub(crate) const IFLA_MASTER: u16 = 10;
+const IFLA_OPERSTATE: u16 = 16;
+pub(crate) const IFLA_LINKINFO: u16 = 18;
+pub(crate) const IFLA_INFO_KIND: u16 = 1;
+pub(crate) const IFLA_INFO_DATA: u16 = 2;
+
+const RTMSG_LEN: usize = 12;
+const RTA_DST: u16 = 1;
+const RTA_SRC: u16 = 2;
+const RTA_OIF: u16 = 4;
+const RTA_GATEWAY: u16 = 5;
+const RTA_PRIORITY: u16 = 6;
+const RTA_PREFSRC: u16 = 7;
+const RTA_TABLE: u16 = 15;
+
+const NDMSG_LEN: usize = 12;
+const NDA_DST: u16 = 1;
+const NDA_LLADDR: u16 = 2;
+
+/// Rounds the length up to the 4 byte alignment of netlink messages and attributes.
+pub const fn nl_align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// A single received netlink message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NetlinkMessage {
+    /// message type (e.g. RTM_NEWLINK)
+    pub msg_type: u16,
+
+    /// NLM_F_* flags
+    pub flags: u16,
+
+    /// sequence number
+    pub seq: u32,
+
+    /// message payload following the header
+    pub payload: Vec<u8>,
+}
+
+/// A blocking netlink socket for request/response exchanges with the kernel.
+#[derive(Debug)]
+pub struct NetlinkSocket {
+    fd: OwnedFd,
+    seq: u32,
+}
+
+impl NetlinkSocket {
+
+    /// Opens and binds a netlink socket for the given protocol (e.g. NETLINK_ROUTE) and
+    /// subscribes to the multicast groups given as bitmask (0 for none).
+    pub fn new(protocol: libc::c_int, groups: u32) -> Result<NetlinkSocket> {
+        let socket = sys_api::socket(libc::AF_NETLINK, libc::SOCK_RAW, protocol)?;
+
+        let mut storage: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+        let addr = unsafe { &mut *(std::ptr::addr_of_mut!(storage) as *mut libc::sockaddr_nl) };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        addr.nl_groups = groups;
+        let address = unsafe { SockAddr::new(storage, std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t) };
+        sys_api::bind(&socket, &address)?;
+        let fd = OwnedFd::from(socket);
+        Ok(NetlinkSocket { fd, seq: 0 })
+    }
+
+    /// Switches the socket into non-blocking mode, `receive` then fails with WouldBlock if there
+    /// are no pending messages.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> Result<()> {
+        let flags = unsafe { libc::fcntl(self.fd.as_raw_fd(), libc::F_GETFL) };
+        if flags < 0 {
+            return Err(Error::last_os_error());
+        }
+        let flags = if nonblocking { flags | libc::O_NONBLOCK } else { flags & !libc::O_NONBLOCK };
+        if unsafe { libc::fcntl(self.fd.as_raw_fd(), libc::F_SETFL, flags) } < 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Sets the timeout of `receive`, which then fails with WouldBlock if no message arrives in
+    /// time. None blocks indefinitely.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        let timeout = match timeout {
+            Some(timeout) if timeout.as_nanos() == 0 =>
+                return Err(Error::new(std::io::ErrorKind::InvalidInput, "zero read timeout")),
+            Some(timeout) => {
+                // a zero timeval disables the timeout, round sub-microsecond timeouts up
+                let timeout = timeout.max(Duration::from_micros(1));
+                libc::timeval {
+                    tv_sec: timeout.as_secs().min(libc::time_t::MAX as u64) as libc::time_t,
+                    tv_usec: timeout.subsec_micros() as libc::suseconds_t,
+                }
+            },
+            None => libc::timeval { tv_sec: 0, tv_usec: 0 },
+        };
+        set_option(self.fd.as_raw_fd(), libc::SOL_SOCKET, libc::SO_RCVTIMEO, &timeout)
+    }
+
+    /// Sends a request with NLM_F_REQUEST (and the additional flags) set and collects all response
+    /// messages until the request is completed. Kernel errors are returned as io::Error.
+    /// For dump requests (NLM_F_DUMP) all messages up to NLMSG_DONE are returned, otherwise the
+    /// single response or an empty vector if only an acknowledgement was received.
+    pub fn request(&mut self, msg_type: u16, flags: u16, payload: &[u8]) -> Result<Vec<NetlinkMessage>> {
+        let mut state = RequestState::new(self.next_seq(), flags);
+        self.send(msg_type, state.flags, state.seq, payload)?;
+        loop {
+            if let Some(result) = state.process(self.receive()?) {
+                return result;
+            }
+        }
+    }
+
+    /// Sends a single netlink message to the kernel.
+    pub fn send(&self, msg_type: u16, flags: u16, seq: u32, payload: &[u8]) -> Result<()> {
+        let buffer = encode_message(msg_type, flags, seq, payload);
+        let mut kernel: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+        kernel.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        let sent = unsafe {
+            libc::sendto(self.fd.as_raw_fd(), buffer.as_ptr() as *const libc::c_void, buffer.len(), 0,
+                         std::ptr::addr_of!(kernel) as *const libc::sockaddr,
+                         std::mem::size_of_val(&kernel) as libc::socklen_t)
+        };
+        if sent < 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Receives one datagram from the socket and splits it into the contained netlink messages.
+    pub fn receive(&self) -> Result<Vec<NetlinkMessage>> {
+        let mut buffer = vec![0_u8; RECV_BUFFER_SIZE];
+        let len = loop {
+            let len = unsafe {
+                libc::recv(self.fd.as_raw_fd(), buffer.as_mut_ptr() as *mut libc::c_void, buffer.len(), 0)
+            };
+            if len >= 0 {
+                break len as usize;
+            }
+            let err = Error::last_os_error();
+            if err.kind() != std::io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        };
+        Ok(parse_messages(&buffer[..len]))
+    }
+
+    /// Resolves the message type (family id) of a generic netlink family (e.g. "wireguard") on a
+    /// NETLINK_GENERIC socket. Fails with NotFound if the family is not available.
+    pub fn generic_family(&mut self, name: &str) -> Result<u16> {
+        let request = MessageBuilder::new(&generic_header(CTRL_CMD_GETFAMILY, 1))
+            .str_attribute(CTRL_ATTR_FAMILY_NAME, name)
+            .into_payload();
+        self.request(GENL_ID_CTRL, 0, &request)?.iter()
+            .filter(|msg| msg.msg_type == GENL_ID_CTRL && msg.payload.len() >= GENL_HDRLEN)
+            .flat_map(|msg| AttributeIter::new(&msg.payload[GENL_HDRLEN..]))
+            .find(|(attr_type, _)| *attr_type == CTRL_ATTR_FAMILY_ID)
+            .and_then(|(_, data)| data.get(0..2))
+            .map(|data| u16::from_ne_bytes(data.try_into().unwrap()))
+            .ok_or_else(|| Error::new(std::io::ErrorKind::NotFound,
+                                      format!("generic netlink family {} not found", name)))
+    }
+
+    fn next_seq(&mut self) -> u32 {
+        self.seq = self.seq.wrapping_add(1);
+        self.seq
+    }
+}
+
+impl AsRawFd for NetlinkSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+/// Non-blocking netlink socket for use with tokio.
+/// Requires the feature 'tokio-net'.
+#[cfg(feature = "tokio-net")]
+#[derive(Debug)]
+pub struct AsyncNetlinkSocket {
+    inner: tokio::io::unix::AsyncFd<NetlinkSocket>,
+    shutdown: super::shutdown::ShutdownHandle,
+}
+
+#[cfg(feature = "tokio-net")]
+impl AsyncNetlinkSocket {
+
+    /// Opens and binds a netlink socket like `NetlinkSocket::new`, must be called within a tokio
+    /// runtime.
+    pub fn new(protocol: libc::c_int, groups: u32) -> Result<AsyncNetlinkSocket> {
+        let socket = NetlinkSocket::new(protocol, groups)?;
+        socket.set_nonblocking(true)?;
+        Ok(AsyncNetlinkSocket {
+            inner: tokio::io::unix::AsyncFd::new(socket)?,
+            shutdown: super::shutdown::ShutdownHandle::new(),
+        })
+    }
+
+    /// Sends a request and collects the response messages like `NetlinkSocket::request`.
+    pub async fn request(&mut self, msg_type: u16, flags: u16, payload: &[u8]) -> Result<Vec<NetlinkMessage>> {
+        let mut state = RequestState::new(self.inner.get_mut().next_seq(), flags);
+        self.send(msg_type, state.flags, state.seq, payload).await?;
+        loop {
+            if let Some(result) = state.process(self.receive().await?) {
+                return result;
+            }
+        }
+    }
+
+    /// Sends a single netlink message to the kernel.
+    pub async fn send(&self, msg_type: u16, flags: u16, seq: u32, payload: &[u8]) -> Result<()> {
+        loop {
+            let mut guard = self.inner.writable().await?;
+            if let Ok(result) = guard.try_io(|inner| inner.get_ref().send(msg_type, flags, seq, payload)) {
+                return result;
+            }
+        }
+    }
+
+    /// Waits for the next datagram and splits it into the contained netlink messages.
+    pub async fn receive(&self) -> Result<Vec<NetlinkMessage>> {
+        loop {
+            let mut guard = self.inner.readable().await?;
+            if let Ok(result) = guard.try_io(|inner| inner.get_ref().receive()) {
+                return result;
+            }
+        }
+    }
+
+    /// Returns a handle which ends the stream of `next` from other threads or tasks, see
+    /// `shutdown::ShutdownHandle`.
+    pub fn shutdown_handle(&self) -> super::shutdown::ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Waits for the next datagram like `receive` (e.g. notifications of a monitor), returns None
+    /// once the socket is shut down, after a drain when no datagram is queued anymore.
+    pub async fn next(&self) -> Option<Result<Vec<NetlinkMessage>>> {
+        if !self.shutdown.is_shutdown() {
+            tokio::select! {
+                result = self.receive() => return Some(result),
+                _ = self.shutdown.signaled() => (),
+            }
+        }
+        if !self.shutdown.is_draining() {
+            return None;
+        }
+        match self.inner.get_ref().receive() {
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => None,
+            result => Some(result),
+        }
+    }
+}
+
+#[cfg(feature = "tokio-net")]
+impl AsRawFd for AsyncNetlinkSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+/// Response collection of a single request.
+struct RequestState {
+    seq: u32,
+    flags: u16,
+    messages: Vec<NetlinkMessage>,
+}
+
+impl RequestState {
+
+    fn new(seq: u32, flags: u16) -> RequestState {
+        RequestState { seq, flags: flags | libc::NLM_F_REQUEST as u16, messages: Vec::new() }
+    }
+
+    /// Processes received messages, returns the result once the request is completed.
+    fn process(&mut self, received: Vec<NetlinkMessage>) -> Option<Result<Vec<NetlinkMessage>>> {
+        let is_dump = (self.flags & libc::NLM_F_DUMP as u16) == libc::NLM_F_DUMP as u16;
+        let wants_ack = (self.flags & libc::NLM_F_ACK as u16) != 0;
+        for msg in received {
+            if msg.seq != self.seq {
+                continue;
+            }
+            match msg.msg_type as libc::c_int {
+                libc::NLMSG_DONE => return Some(Ok(std::mem::take(&mut self.messages))),
+                libc::NLMSG_ERROR => {
+                    let code = error_code(&msg.payload);
+                    if code != 0 {
+                        return Some(Err(Error::from_raw_os_error(-code)));
+                    }
+                    return Some(Ok(std::mem::take(&mut self.messages)));
+                },
+                libc::NLMSG_NOOP => {},
+                _ => {
+                    self.messages.push(msg);
+                    if !is_dump && !wants_ack {
+                        return Some(Ok(std::mem::take(&mut self.messages)));
+                    }
+                },
+            }
+        }
+        None
+    }
+}
+
+/// Builder for netlink message payloads: a fixed size header (e.g. struct ifinfomsg) followed by
+/// route attributes, which can be nested.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MessageBuilder {
+    payload: Vec<u8>,
+}
+
+impl MessageBuilder {
+
+    /// Creates a builder whose payload starts with the given header bytes, padded to the netlink
+    /// alignment.
+    pub fn new(header: &[u8]) -> MessageBuilder {
+        let mut payload = header.to_vec();
+        payload.resize(nl_align(payload.len()), 0);
+        MessageBuilder { payload }
+    }
+
+    /// Appends an attribute with the raw payload.
+    pub fn attribute(mut self, attr_type: u16, data: &[u8]) -> Self {
+        push_attribute(&mut self.payload, attr_type, data);
+        self
+    }
+
+    /// Appends an attribute with an u8 value.
+    pub fn u8_attribute(self, attr_type: u16, value: u8) -> Self {
+        self.attribute(attr_type, &[value])
+    }
+
+    /// Appends an attribute with an u16 value in native byte order.
+    pub fn u16_attribute(self, attr_type: u16, value: u16) -> Self {
+        self.attribute(attr_type, &value.to_ne_bytes())
+    }
+
+    /// Appends an attribute with an u32 value in native byte order.
+    pub fn u32_attribute(self, attr_type: u16, value: u32) -> Self {
+        self.attribute(attr_type, &value.to_ne_bytes())
+    }
+
+    /// Appends an attribute with a NUL terminated string.
+    pub fn str_attribute(self, attr_type: u16, value: &str) -> Self {
+        let mut data = value.as_bytes().to_vec();
+        data.push(0);
+        self.attribute(attr_type, &data)
+    }
+
+    /// Appends a nested attribute (NLA_F_NESTED set) whose content is built by the closure.
+    pub fn nested<F: FnOnce(MessageBuilder) -> MessageBuilder>(self, attr_type: u16, build: F) -> Self {
+        let nested = build(MessageBuilder::default());
+        self.attribute(attr_type | NLA_F_NESTED, &nested.payload)
+    }
+
+    /// Returns the payload built so far.
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    /// Converts into the payload.
+    pub fn into_payload(self) -> Vec<u8> {
+        self.payload
+    }
+}
+
+/// A network link (interface) as reported by RTM_NEWLINK.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LinkInfo {
+    /// interface index
+    pub index: u32,
+
+    /// hardware type (ARPHRD_ETHER, ARPHRD_LOOPBACK, ...)
+    pub link_type: u16,
+
+    /// IFF_* flags
+    pub flags: u32,
+
+    /// interface name
+    pub name: Option<String>,
+
+    /// maximum transmission unit
+    pub mtu: Option<u32>,
+
+    /// hardware (link layer) address
+    pub address: Option<Vec<u8>>,
+
+    /// RFC 2863 operational state (IF_OPER_*)
+    pub oper_state: Option<u8>,
+
+    /// index of the master device (e.g. bridge or bond)
+    pub master: Option<u32>,
+
+    /// index of the parent (lower) device of stacked devices (e.g. vlan or macvlan)
+    pub parent: Option<u32>,
+
+    /// link kind of virtual interfaces (e.g. "bridge", "vlan", "wireguard")
+    pub kind: Option<String>,
+}
+
+impl LinkInfo {
+
+    /// Parses a RTM_NEWLINK message, returns None for other messages.
+    pub fn from_message(msg: &NetlinkMessage) -> Option<LinkInfo> {
+        if msg.msg_type != libc::RTM_NEWLINK || msg.payload.len() < IFINFOMSG_LEN {
+            return None;
+        }
+        let mut link = LinkInfo {
+            index: u32::from_ne_bytes(msg.payload[4..8].try_into().unwrap()),
+            link_type: u16::from_ne_bytes(msg.payload[2..4].try_into().unwrap()),
+            flags: u32::from_ne_bytes(msg.payload[8..12].try_into().unwrap()),
+            name: None, mtu: None, address: None, oper_state: None, master: None, parent: None,
+            kind: None,
+        };
+        for (attr_type, data) in AttributeIter::new(&msg.payload[IFINFOMSG_LEN..]) {
+            match attr_type {
+                IFLA_ADDRESS => link.address = Some(data.to_vec()),
+                IFLA_IFNAME => link.name = string_from(data),
+                IFLA_MTU => link.mtu = u32_from(data),
+                IFLA_MASTER => link.master = u32_from(data),
+                IFLA_LINK => link.parent = u32_from(data).filter(|parent| *parent != link.index),
+                IFLA_OPERSTATE => link.oper_state = data.first().copied(),
+                IFLA_LINKINFO => link.kind = AttributeIter::new(data)
+                    .find(|(t, _)| *t == IFLA_INFO_KIND)
+                    .and_then(|(_, d)| string_from(d)),
+                _ => {},
+            }
+        }
+        Some(link)
+    }
+}
+
+/// A route as reported by RTM_NEWROUTE.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RouteInfo {
+    /// address family (AF_INET or AF_INET6)
+    pub family: u8,
+
+    /// prefix length of the destination
+    pub destination_len: u8,
+
+    /// routing table id
+    pub table: u32,
+
+    /// routing protocol that installed the route (RTPROT_*)
+    pub protocol: u8,
+
+    /// route scope (RT_SCOPE_*)
+    pub scope: u8,
+
+    /// route type (RTN_UNICAST, RTN_LOCAL, ...)
+    pub route_type: u8,
+
+    /// destination network, None for the default route
+    pub destination: Option<IpAddr>,
+
+    /// source network of source routes
+    pub source: Option<IpAddr>,
+
+    /// next hop
+    pub gateway: Option<IpAddr>,
+
+    /// preferred source address
+    pub preferred_source: Option<IpAddr>,
+
+    /// index of the output interface
+    pub output_index: Option<u32>,
+
+    /// route priority (metric)
+    pub priority: Option<u32>,
+}
+
+impl RouteInfo {
+
+    /// Returns the destination network, 0.0.0.0/0 or ::/0 for default routes.
+    pub fn destination_net(&self) -> Option<IpNet> {
+        let destination = match (self.destination, self.family as i32) {
+            (Some(destination), _) => destination,
+            (None, libc::AF_INET) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            (None, libc::AF_INET6) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            (None, _) => return None,
+        };
+        IpNet::new(destination, self.destination_len).ok()
+    }
+
+    /// Parses a RTM_NEWROUTE message, returns None for other messages.
+    pub fn from_message(msg: &NetlinkMessage) -> Option<RouteInfo> {
+        if msg.msg_type != libc::RTM_NEWROUTE || msg.payload.len() < RTMSG_LEN {
+            return None;
+        }
+        let p = &msg.payload;
+        let family = p[0] as i32;
+        let mut route = RouteInfo {
+            family: p[0], destination_len: p[1], table: p[4] as u32, protocol: p[5], scope: p[6],
+            route_type: p[7], destination: None, source: None, gateway: None, preferred_source: None,
+            output_index: None, priority: None,
+        };
+        for (attr_type, data) in AttributeIter::new(&p[RTMSG_LEN..]) {
+            match attr_type {
+                RTA_DST => route.destination = ip_address_from(family, data),
+                RTA_SRC => route.source = ip_address_from(family, data),
+                RTA_GATEWAY => route.gateway = ip_address_from(family, data),
+                RTA_PREFSRC => route.preferred_source = ip_address_from(family, data),
+                RTA_OIF => route.output_index = u32_from(data),
+                RTA_PRIORITY => route.priority = u32_from(data),
+                RTA_TABLE => route.table = u32_from(data).unwrap_or(route.table),
+                _ => {},
+            }
+        }
+        Some(route)
+    }
+}
+
+/// A neighbor cache (ARP / NDP) entry as reported by RTM_NEWNEIGH.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NeighborInfo {
+    /// index of the interface
+    pub index: u32,
+
+    /// NUD_* state (NUD_REACHABLE, NUD_STALE, ...)
+    pub state: u16,
+
+    /// NTF_* flags
+    pub flags: u8,
+
+    /// IP address of the neighbor
+    pub destination: Option<IpAddr>,
+
+    /// link layer address of the neighbor
+    pub link_address: Option<Vec<u8>>,
+}
+
+impl NeighborInfo {
+
+    /// Parses a RTM_NEWNEIGH message, returns None for other messages.
+    pub fn from_message(msg: &NetlinkMessage) -> Option<NeighborInfo> {
+        if msg.msg_type != libc::RTM_NEWNEIGH || msg.payload.len() < NDMSG_LEN {
+            return None;
+        }
+        let p = &msg.payload;
+        let family = p[0] as i32;
+        let mut neighbor = NeighborInfo {
+            index: u32::from_ne_bytes(p[4..8].try_into().unwrap()),
+            state: u16::from_ne_bytes(p[8..10].try_into().unwrap()),
+            flags: p[10],
+            destination: None,
+            link_address: None,
+        };
+        for (attr_type, data) in AttributeIter::new(&p[NDMSG_LEN..]) {
+            match attr_type {
+                NDA_DST => neighbor.destination = ip_address_from(family, data),
+                NDA_LLADDR => neighbor.link_address = Some(data.to_vec()),
+                _ => {},
+            }
+        }
+        Some(neighbor)
+    }
+}
+
+/// Retrieves all network links (RTM_GETLINK dump).
+pub fn links() -> Result<Vec<LinkInfo>> {
+    dump(libc::RTM_GETLINK, IFINFOMSG_LEN, LinkInfo::from_message)
+}
+
+/// Joins on the interface, or on all links accepted by the filter if no interface is given.
+pub(crate) fn join_links<P, F>(interface_index: Option<u32>, filter: P, join: F) -> Result<()>
+    where P: Fn(&LinkInfo) -> bool, F: Fn(u32) -> Result<()> {
+    match interface_index {
+        Some(index) => join(index),
+        None => {
+            for link in links()?.iter().filter(|l| filter(l)) {
+                // interfaces may disappear or refuse the membership, the others still work
+                if let Err(_error) = join(link.index) {
+                    trace_event!(crate::trace::TraceEvent::InterfaceSkipped { index: link.index, error: &_error });
+                }
+            }
+            Ok(())
+        },
+    }
+}
+
+/// Retrieves all IPv4 and IPv6 addresses (RTM_GETADDR dump).
+pub fn addresses() -> Result<Vec<IpAddressInfo>> {
+    IpAddressInfo::retrieve_ip_addresses()
+}
+
+/// Retrieves all IPv4 and IPv6 routes of all routing tables (RTM_GETROUTE dump).
+pub fn routes() -> Result<Vec<RouteInfo>> {
+    dump(libc::RTM_GETROUTE, RTMSG_LEN, RouteInfo::from_message)
+}
+
+/// Retrieves the neighbor cache entries of all interfaces (RTM_GETNEIGH dump).
+pub fn neighbors() -> Result<Vec<NeighborInfo>> {
+    dump(libc::RTM_GETNEIGH, NDMSG_LEN, NeighborInfo::from_message)
+}
+
+/// Dumps all objects of the message type for all address families.
+fn dump<T, F: Fn(&NetlinkMessage) -> Option<T>>(msg_type: u16, header_len: usize, parse: F) -> Result<Vec<T>> {
+    let mut socket = NetlinkSocket::new(libc::NETLINK_ROUTE, 0)?;
+    let request = vec![0_u8; header_len];
+    let messages = socket.request(msg_type, libc::NLM_F_DUMP as u16, &request)?;
+    Ok(messages.iter().filter_map(parse).collect())
+}
+
+/// Returns the generic netlink header (struct genlmsghdr) for the command and family version.
+pub fn generic_header(cmd: u8, version: u8) -> [u8; GENL_HDRLEN] {
+    [cmd, version, 0, 0]
+}
+
+/// Encodes a netlink message with header and payload.
+fn encode_message(msg_type: u16, flags: u16, seq: u32, payload: &[u8]) -> Vec<u8> {
+    let total_len = NLMSG_HDRLEN + payload.len();
+    let mut buffer = Vec::with_capacity(nl_align(total_len));
+    buffer.extend_from_slice(&(total_len as u32).to_ne_bytes());
+    buffer.extend_from_slice(&msg_type.to_ne_bytes());
+    buffer.extend_from_slice(&flags.to_ne_bytes());
+    buffer.extend_from_slice(&seq.to_ne_bytes());
+    buffer.extend_from_slice(&0_u32.to_ne_bytes());
+    buffer.extend_from_slice(payload);
+    buffer
+}
+
+/// Splits a received buffer into netlink messages. Truncated trailing data is ignored.
+pub fn parse_messages(data: &[u8]) -> Vec<NetlinkMessage> {
+    let mut messages = Vec::new();
+    let mut offset = 0;
+    while offset + NLMSG_HDRLEN <= data.len() {
+        let len = u32::from_ne_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+        if len < NLMSG_HDRLEN || offset + len > data.len() {
+            break;
+        }
+        messages.push(NetlinkMessage {
+            msg_type: u16::from_ne_bytes(data[offset + 4..offset + 6].try_into().unwrap()),
+            flags: u16::from_ne_bytes(data[offset + 6..offset + 8].try_into().unwrap()),
+            seq: u32::from_ne_bytes(data[offset + 8..offset + 12].try_into().unwrap()),
+            payload: data[offset + NLMSG_HDRLEN..offset + len].to_vec(),
+        });
+        offset += nl_align(len);
+    }
+    messages
+}
+
+/// Iterator over the route attributes (struct rtattr) in a buffer, yielding (type, payload).
+/// The NLA_F_NESTED flag is removed from the type.
+#[derive(Clone, Debug)]
+pub struct AttributeIter<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> AttributeIter<'a> {
+    pub fn new(data: &'a [u8]) -> AttributeIter<'a> {
+        AttributeIter { data }
+    }
+}
+
+impl<'a> Iterator for AttributeIter<'a> {
+    type Item = (u16, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.len() < RTA_HDRLEN {
+            return None;
+        }
+        let len = u16::from_ne_bytes([self.data[0], self.data[1]]) as usize;
+        let attr_type = u16::from_ne_bytes([self.data[2], self.data[3]]) & !NLA_F_NESTED;
+        if len < RTA_HDRLEN || len > self.data.len() {
+            return None;
+        }
+        let payload = &self.data[RTA_HDRLEN..len];
+        self.data = &self.data[std::cmp::min(nl_align(len), self.data.len())..];
+        Some((attr_type, payload))
+    }
+}
+
+/// Appends a route attribute (struct rtattr) to the buffer.
+pub fn push_attribute(buffer: &mut Vec<u8>, attr_type: u16, payload: &[u8]) {
+    let len = RTA_HDRLEN + payload.len();
+    buffer.extend_from_slice(&(len as u16).to_ne_bytes());
+    buffer.extend_from_slice(&attr_type.to_ne_bytes());
+    buffer.extend_from_slice(payload);
+    buffer.resize(nl_align(buffer.len()), 0);
+}
+
+/// Converts an attribute with an IPv4 or IPv6 address of the given family.
+pub(crate) fn ip_address_from(family: i32, data: &[u8]) -> Option<IpAddr> {
+    match family {
+        libc::AF_INET if data.len() >= 4 => {
+            let octets: [u8; 4] = data[0..4].try_into().unwrap();
+            Some(IpAddr::V4(Ipv4Addr::from(octets)))
+        },
+        libc::AF_INET6 if data.len() >= 16 => {
+            let octets: [u8; 16] = data[0..16].try_into().unwrap();
+            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+        },
+        _ => None,
+    }
+}
+
+pub(crate) fn u32_from(data: &[u8]) -> Option<u32> {
+    data.get(0..4).map(|d| u32::from_ne_bytes(d.try_into().unwrap()))
+}
+
+pub(crate) fn string_from(data: &[u8]) -> Option<String> {
+    let end = data.iter().position(|c| *c == 0).unwrap_or(data.len());
+    std::str::from_utf8(&data[..end]).ok().map(String::from)
+}
+
+/// Extracts the (negative) error code of a NLMSG_ERROR payload.
+fn error_code(payload: &[u8]) -> i32 {
+    if payload.len() < 4 {
+        return -libc::EPROTO;
+    }
+    i32::from_ne_bytes(payload[0..4].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_attributes() {
+        let mut buffer = Vec::new();
+        push_attribute(&mut buffer, 1, &[1, 2, 3]);
+        push_attribute(&mut buffer, 2, &[4, 5, 6, 7]);
+        assert_eq!(buffer.len(), 16);
+        let attrs: Vec<(u16, &[u8])> = AttributeIter::new(&buffer).collect();
+        assert_eq!(attrs, vec![(1, &[1_u8, 2, 3][..]), (2, &[4_u8, 5, 6, 7][..])]);
+    }
+
+    #[test]
+    fn test_parse_messages() {
+        let mut data = Vec::new();
+        for (msg_type, payload) in [(20_u16, vec![1_u8, 2]), (3, vec![0; 4])].iter() {
+            data.extend_from_slice(&encode_message(*msg_type, 2, 7, payload));
+            data.resize(nl_align(data.len()), 0);
+        }
+        let messages = parse_messages(&data);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0], NetlinkMessage { msg_type: 20, flags: 2, seq: 7, payload: vec![1, 2] });
+        assert_eq!(messages[1].msg_type, 3);
+        assert!(parse_messages(&data[..10]).is_empty());
+    }
+
+    #[test]
+    fn test_request_state() {
+        let msg = |msg_type: u16, seq: u32, payload: Vec<u8>| NetlinkMessage { msg_type, flags: 0, seq, payload };
+        let mut state = RequestState::new(5, libc::NLM_F_DUMP as u16);
+        assert!(state.process(vec![msg(libc::RTM_NEWLINK, 5, vec![]), msg(libc::RTM_NEWLINK, 4, vec![])]).is_none());
+        let result = state.process(vec![msg(libc::NLMSG_DONE as u16, 5, vec![])]).unwrap().unwrap();
+        assert_eq!(result.len(), 1);
+
+        let mut state = RequestState::new(6, libc::NLM_F_ACK as u16);
+        let error = (-libc::EPERM).to_ne_bytes().to_vec();
+        let result = state.process(vec![msg(libc::NLMSG_ERROR as u16, 6, error)]).unwrap();
+        assert_eq!(result.unwrap_err().raw_os_error(), Some(libc::EPERM));
+    }
+
+    #[test]
+    fn test_link_from_message() {
+        let mut header = [0_u8; IFINFOMSG_LEN];
+        header[2..4].copy_from_slice(&1_u16.to_ne_bytes());
+        header[4..8].copy_from_slice(&7_u32.to_ne_bytes());
+        header[8..12].copy_from_slice(&(libc::IFF_UP as u32).to_ne_bytes());
+        let payload = MessageBuilder::new(&header)
+            .str_attribute(IFLA_IFNAME, "br0")
+            .u32_attribute(IFLA_MTU, 1500)
+            .attribute(IFLA_ADDRESS, &[2, 0, 0, 0, 0, 1])
+            .u8_attribute(IFLA_OPERSTATE, 6)
+            .u32_attribute(IFLA_LINK, 7)
+            .nested(IFLA_LINKINFO, |b| b.str_attribute(IFLA_INFO_KIND, "bridge"))
+            .into_payload();
+        let link = LinkInfo::from_message(&NetlinkMessage { msg_type: libc::RTM_NEWLINK, flags: 0, seq: 1, payload })
+            .unwrap();
+        assert_eq!(link.index, 7);
+        assert_eq!(link.link_type, 1);
+        assert_eq!(link.flags, libc::IFF_UP as u32);
+        assert_eq!(link.name.as_deref(), Some("br0"));
+        assert_eq!(link.mtu, Some(1500));
+        assert_eq!(link.address, Some(vec![2, 0, 0, 0, 0, 1]));
+        assert_eq!(link.oper_state, Some(6));
+        // some drivers report the device itself as link
+        assert_eq!(link.parent, None);
+        assert_eq!(link.kind.as_deref(), Some("bridge"));
+    }
+
+    #[test]
+    fn test_route_from_message() {
+        let header = [libc::AF_INET as u8, 0, 0, 0, libc::RT_TABLE_MAIN, libc::RTPROT_BOOT, 0, libc::RTN_UNICAST,
+            0, 0, 0, 0];
+        let payload = MessageBuilder::new(&header)
+            .attribute(RTA_GATEWAY, &[192, 168, 1, 1])
+            .u32_attribute(RTA_OIF, 2)
+            .u32_attribute(RTA_PRIORITY, 100)
+            .into_payload();
+        let route = RouteInfo::from_message(&NetlinkMessage { msg_type: libc::RTM_NEWROUTE, flags: 0, seq: 1, payload })
+            .unwrap();
+        assert_eq!(route.destination, None);
+        assert_eq!(route.gateway, Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1))));
+        assert_eq!(route.output_index, Some(2));
+        assert_eq!(route.priority, Some(100));
+        assert_eq!(route.table, libc::RT_TABLE_MAIN as u32);
+        assert_eq!(route.destination_net(), Some("0.0.0.0/0".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_neighbor_from_message() {
+        let mut header = [0_u8; NDMSG_LEN];
+        header[0] = libc::AF_INET6 as u8;
+        header[4..8].copy_from_slice(&3_u32.to_ne_bytes());
+        header[8..10].copy_from_slice(&libc::NUD_REACHABLE.to_ne_bytes());
+        let address: Ipv6Addr = "fe80::1".parse().unwrap();
+        let payload = MessageBuilder::new(&header)
+            .attribute(NDA_DST, &address.octets())
+            .attribute(NDA_LLADDR, &[0, 1, 2, 3, 4, 5])
+            .into_payload();
+        let neighbor = NeighborInfo::from_message(&NetlinkMessage { msg_type: libc::RTM_NEWNEIGH, flags: 0, seq: 1,
+            payload }).unwrap();
+        assert_eq!(neighbor.index, 3);
+        assert_eq!(neighbor.state, libc::NUD_REACHABLE);
+        assert_eq!(neighbor.destination, Some(IpAddr::V6(address)));
+        assert_eq!(neighbor.link_address, Some(vec![0, 1, 2, 3, 4, 5]));
+    }
+}
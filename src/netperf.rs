@@ -0,0 +1,430 @@
+//! Path measurements between two hosts: UDP probes reflected by a peer for round-trip time,
+//! jitter, loss and forward delay with kernel receive timestamps, and TCP bulk throughput.
+
+use std::{
+    convert::TryInto,
+    io::{Error, ErrorKind, Read, Result, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use super::{
+    ethtool::{SOF_TIMESTAMPING_RX_SOFTWARE, SOF_TIMESTAMPING_SOFTWARE},
+    tcp::{ConnectOpts, connect_via},
+    timestamping::{recv_with_timestamps, set_timestamping},
+};
+
+/// Magic number at the start of UDP probes.
+const MAGIC: &[u8; 4] = b"NUPF";
+
+/// Smallest UDP probe: magic, sequence number and the three timestamps.
+pub const MIN_PACKET_SIZE: usize = 36;
+
+/// Time to wait for the reflections of the last probes and for the byte count of a TCP test.
+const LINGER: Duration = Duration::from_secs(1);
+
+/// Options of a UDP measurement.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct UdpTestOpts {
+    /// size of the probe datagrams, at least MIN_PACKET_SIZE
+    pub packet_size: usize,
+
+    /// probes sent per second
+    pub rate: u32,
+
+    /// time probes are sent
+    pub duration: Duration,
+}
+
+impl Default for UdpTestOpts {
+    fn default() -> UdpTestOpts {
+        UdpTestOpts { packet_size: 512, rate: 100, duration: Duration::from_secs(5) }
+    }
+}
+
+/// Result of a UDP measurement for one reflector.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct UdpReport {
+    /// address the reflections were received from
+    pub reflector: SocketAddr,
+
+    /// number of probes sent
+    pub sent: u64,
+
+    /// number of probes reflected, without duplicates
+    pub received: u64,
+
+    /// number of probes reflected more than once
+    pub duplicates: u64,
+
+    /// number of reflections received after one with a higher sequence number
+    pub reordered: u64,
+
+    /// rate of reflected data in bits per second over the sending duration
+    pub throughput: u64,
+
+    /// smallest round-trip time without the reflector's processing time
+    pub rtt_min: Duration,
+
+    /// mean round-trip time without the reflector's processing time
+    pub rtt_avg: Duration,
+
+    /// largest round-trip time without the reflector's processing time
+    pub rtt_max: Duration,
+
+    /// interarrival jitter of the probes at the reflector (RFC 3550)
+    pub jitter: Duration,
+
+    /// mean delay from sender to reflector, only meaningful with synchronized clocks and None if
+    /// the reflector's clock is behind
+    pub forward_delay: Option<Duration>,
+}
+
+impl UdpReport {
+
+    /// Returns the fraction of probes that were not reflected (0.0 to 1.0).
+    pub fn loss(&self) -> f64 {
+        if self.sent == 0 { 0.0 } else { 1.0 - self.received as f64 / self.sent as f64 }
+    }
+}
+
+/// Reflects UDP probes back to their sender with its kernel receive and transmit timestamps.
+#[derive(Debug)]
+pub struct UdpReflector {
+    socket: UdpSocket,
+}
+
+impl UdpReflector {
+
+    /// Binds a reflector to the local address.
+    pub fn bind<A: ToSocketAddrs>(address: A) -> Result<UdpReflector> {
+        UdpReflector::new(super::sys_socket::udp_bind(address)?)
+    }
+
+    /// Creates a reflector on the socket, e.g. a multicast socket of this crate to validate a
+    /// multicast path. Enables software receive timestamps on the socket.
+    pub fn new(socket: UdpSocket) -> Result<UdpReflector> {
+        set_timestamping(&socket, SOF_TIMESTAMPING_RX_SOFTWARE | SOF_TIMESTAMPING_SOFTWARE)?;
+        Ok(UdpReflector { socket })
+    }
+
+    /// Returns the local address of the reflector's socket.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Sets the timeout of `reflect_once`, None blocks indefinitely.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        self.socket.set_read_timeout(timeout)
+    }
+
+    /// Receives datagrams until one is a probe and reflects it, returns the probe's sender.
+    pub fn reflect_once(&self) -> Result<SocketAddr> {
+        let mut buffer = vec![0_u8; 65536];
+        loop {
+            let (len, from, timestamps) = recv_with_timestamps(&self.socket, &mut buffer)?;
+            if len < MIN_PACKET_SIZE || &buffer[..4] != MAGIC {
+                continue;
+            }
+            let received = timestamps.software.unwrap_or_else(now);
+            buffer[20..28].copy_from_slice(&nanos(received).to_be_bytes());
+            buffer[28..36].copy_from_slice(&nanos(now()).to_be_bytes());
+            self.socket.send_to(&buffer[..len], from)?;
+            return Ok(from);
+        }
+    }
+
+    /// Reflects probes until receiving or sending fails.
+    pub fn run(&self) -> Result<()> {
+        loop {
+            self.reflect_once()?;
+        }
+    }
+}
+
+/// Sends probes from the socket to the target (a reflector or multicast group of reflectors) at
+/// the rate of the options and measures the reflections of each reflector that answered. Enables
+/// software receive timestamps on the socket.
+pub fn udp_test(socket: &UdpSocket, target: SocketAddr, opts: &UdpTestOpts) -> Result<Vec<UdpReport>> {
+    if opts.packet_size < MIN_PACKET_SIZE || opts.rate == 0 {
+        return Err(Error::new(ErrorKind::InvalidInput, "packet size below MIN_PACKET_SIZE or rate zero"));
+    }
+    set_timestamping(socket, SOF_TIMESTAMPING_RX_SOFTWARE | SOF_TIMESTAMPING_SOFTWARE)?;
+    let interval = Duration::from_secs(1) / opts.rate;
+    let start = Instant::now();
+    let end_of_sending = start + opts.duration;
+    let mut probe = vec![0_u8; opts.packet_size];
+    probe[..4].copy_from_slice(MAGIC);
+    let mut buffer = vec![0_u8; 65536];
+    let mut statistics: Vec<Statistics> = Vec::new();
+    let mut sent: u64 = 0;
+    let mut next_send = start;
+    loop {
+        let now_instant = Instant::now();
+        let sending = now_instant < end_of_sending;
+        if !sending && now_instant >= end_of_sending + LINGER {
+            break;
+        }
+        if sending && now_instant >= next_send {
+            probe[4..12].copy_from_slice(&sent.to_be_bytes());
+            probe[12..20].copy_from_slice(&nanos(now()).to_be_bytes());
+            socket.send_to(&probe, target)?;
+            sent += 1;
+            next_send += interval;
+            continue;
+        }
+        let wait_until = if sending { next_send } else { end_of_sending + LINGER };
+        socket.set_read_timeout(Some(wait_until.saturating_duration_since(now_instant).max(Duration::from_micros(1))))?;
+        let (len, from, timestamps) = match recv_with_timestamps(socket, &mut buffer) {
+            Ok(received) => received,
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => continue,
+            Err(e) => return Err(e),
+        };
+        if len < MIN_PACKET_SIZE || &buffer[..4] != MAGIC {
+            continue;
+        }
+        let received = timestamps.software.unwrap_or_else(now);
+        let index = match statistics.iter().position(|s| s.reflector == from) {
+            Some(index) => index,
+            None => {
+                statistics.push(Statistics::new(from));
+                statistics.len() - 1
+            },
+        };
+        statistics[index].add(&buffer[..len], nanos(received), sent);
+    }
+    Ok(statistics.into_iter().map(|s| s.report(sent, opts.duration)).collect())
+}
+
+/// Accumulated measurements of the reflections of one reflector.
+struct Statistics {
+    reflector: SocketAddr,
+    seen: Vec<bool>,
+    received: u64,
+    duplicates: u64,
+    reordered: u64,
+    bytes: u64,
+    highest: Option<u64>,
+    rtt_min: u64,
+    rtt_max: u64,
+    rtt_sum: u64,
+    jitter: f64,
+    last_transit: Option<i64>,
+    forward_sum: i128,
+}
+
+impl Statistics {
+
+    fn new(reflector: SocketAddr) -> Statistics {
+        Statistics { reflector, seen: Vec::new(), received: 0, duplicates: 0, reordered: 0, bytes: 0, highest: None,
+                     rtt_min: u64::MAX, rtt_max: 0, rtt_sum: 0, jitter: 0.0, last_transit: None, forward_sum: 0 }
+    }
+
+    /// Adds a reflection received at the time (ns), `sent` is the number of probes sent so far.
+    fn add(&mut self, reflection: &[u8], received: u64, sent: u64) {
+        let u64_at = |offset: usize| u64::from_be_bytes(reflection[offset..offset + 8].try_into().unwrap());
+        let (sequence, sent_at, reflected_at, reflector_sent_at) = (u64_at(4), u64_at(12), u64_at(20), u64_at(28));
+        if sequence >= sent {
+            return;
+        }
+        if self.seen.len() <= sequence as usize {
+            self.seen.resize(sequence as usize + 1, false);
+        }
+        if self.seen[sequence as usize] {
+            self.duplicates += 1;
+            return;
+        }
+        self.seen[sequence as usize] = true;
+        self.received += 1;
+        self.bytes += reflection.len() as u64;
+        match self.highest {
+            Some(highest) if sequence < highest => self.reordered += 1,
+            _ => self.highest = Some(sequence),
+        }
+        let processing = reflector_sent_at.saturating_sub(reflected_at);
+        let rtt = received.saturating_sub(sent_at).saturating_sub(processing);
+        self.rtt_min = self.rtt_min.min(rtt);
+        self.rtt_max = self.rtt_max.max(rtt);
+        self.rtt_sum += rtt;
+        let transit = reflected_at as i64 - sent_at as i64;
+        self.forward_sum += transit as i128;
+        if let Some(last) = self.last_transit {
+            self.jitter += ((transit - last).abs() as f64 - self.jitter) / 16.0;
+        }
+        self.last_transit = Some(transit);
+    }
+
+    fn report(self, sent: u64, duration: Duration) -> UdpReport {
+        let received = self.received.max(1);
+        let forward = self.forward_sum / received as i128;
+        UdpReport {
+            reflector: self.reflector,
+            sent,
+            received: self.received,
+            duplicates: self.duplicates,
+            reordered: self.reordered,
+            throughput: (self.bytes as f64 * 8.0 / duration.as_secs_f64().max(1e-9)) as u64,
+            rtt_min: Duration::from_nanos(if self.received == 0 { 0 } else { self.rtt_min }),
+            rtt_avg: Duration::from_nanos(self.rtt_sum / received),
+            rtt_max: Duration::from_nanos(self.rtt_max),
+            jitter: Duration::from_nanos(self.jitter as u64),
+            forward_delay: if forward >= 0 { Some(Duration::from_nanos(forward as u64)) } else { None },
+        }
+    }
+}
+
+/// Options of a TCP throughput measurement.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TcpTestOpts {
+    /// time data is sent
+    pub duration: Duration,
+
+    /// size of the buffer written at once
+    pub write_size: usize,
+
+    /// options to connect to the reflector, e.g. the device or source address
+    pub connect: ConnectOpts,
+}
+
+impl Default for TcpTestOpts {
+    fn default() -> TcpTestOpts {
+        TcpTestOpts { duration: Duration::from_secs(5), write_size: 128 * 1024, connect: ConnectOpts::default() }
+    }
+}
+
+/// Result of a TCP throughput measurement.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TcpReport {
+    /// bytes written to the connection
+    pub bytes_sent: u64,
+
+    /// bytes the reflector received
+    pub bytes_received: u64,
+
+    /// time from connecting until the reflector reported the received bytes
+    pub elapsed: Duration,
+
+    /// received bits per second
+    pub throughput: u64,
+}
+
+/// Counts the bytes of TCP connections and reports the count to the sender when it shuts down
+/// its sending direction.
+#[derive(Debug)]
+pub struct TcpReflector {
+    listener: TcpListener,
+}
+
+impl TcpReflector {
+
+    /// Binds a reflector to the local address.
+    pub fn bind<A: ToSocketAddrs>(address: A) -> Result<TcpReflector> {
+        Ok(TcpReflector { listener: super::sys_socket::tcp_listen(address)? })
+    }
+
+    /// Creates a reflector on the listener, e.g. one of `TcpListenerBuilder`.
+    pub fn new(listener: TcpListener) -> TcpReflector {
+        TcpReflector { listener }
+    }
+
+    /// Returns the local address of the reflector's listener.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accepts one connection and serves it, returns the peer and the number of bytes received.
+    pub fn serve_once(&self) -> Result<(SocketAddr, u64)> {
+        let (stream, peer) = self.listener.accept()?;
+        Ok((peer, serve(stream)?))
+    }
+
+    /// Accepts connections and serves each on its own thread until accepting fails.
+    pub fn run(&self) -> Result<()> {
+        loop {
+            let (stream, _) = self.listener.accept()?;
+            std::thread::spawn(move || serve(stream));
+        }
+    }
+}
+
+fn serve(mut stream: TcpStream) -> Result<u64> {
+    let mut buffer = vec![0_u8; 128 * 1024];
+    let mut received: u64 = 0;
+    loop {
+        match stream.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(len) => received += len as u64,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    stream.write_all(&received.to_be_bytes())?;
+    Ok(received)
+}
+
+/// Sends data to a TCP reflector for the duration of the options and returns the throughput
+/// measured by the bytes the reflector received.
+pub fn tcp_test(target: SocketAddr, opts: &TcpTestOpts) -> Result<TcpReport> {
+    let start = Instant::now();
+    let mut stream = connect_via(target, &opts.connect)?;
+    let buffer = vec![0x5a_u8; opts.write_size.max(1)];
+    let mut bytes_sent: u64 = 0;
+    while start.elapsed() < opts.duration {
+        stream.write_all(&buffer)?;
+        bytes_sent += buffer.len() as u64;
+    }
+    stream.shutdown(std::net::Shutdown::Write)?;
+    stream.set_read_timeout(Some(LINGER + opts.duration))?;
+    let mut count = [0_u8; 8];
+    stream.read_exact(&mut count)?;
+    let elapsed = start.elapsed();
+    let bytes_received = u64::from_be_bytes(count);
+    Ok(TcpReport {
+        bytes_sent,
+        bytes_received,
+        elapsed,
+        throughput: (bytes_received as f64 * 8.0 / elapsed.as_secs_f64().max(1e-9)) as u64,
+    })
+}
+
+fn now() -> Duration {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default()
+}
+
+fn nanos(time: Duration) -> u64 {
+    time.as_nanos() as u64
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn reflection(sequence: u64, sent_at: u64, reflected_at: u64, reflector_sent_at: u64) -> Vec<u8> {
+        let mut packet = MAGIC.to_vec();
+        for value in [sequence, sent_at, reflected_at, reflector_sent_at] {
+            packet.extend_from_slice(&value.to_be_bytes());
+        }
+        packet
+    }
+
+    #[test]
+    fn test_statistics() {
+        let mut statistics = Statistics::new("192.0.2.1:5001".parse().unwrap());
+        statistics.add(&reflection(0, 1_000, 1_500, 1_600), 2_100, 4);
+        statistics.add(&reflection(2, 3_000, 3_700, 3_700), 4_400, 4);
+        statistics.add(&reflection(1, 2_000, 2_500, 2_500), 5_000, 4);
+        statistics.add(&reflection(1, 2_000, 2_500, 2_500), 5_000, 4);
+        statistics.add(&reflection(9, 0, 0, 0), 5_000, 4);
+        let report = statistics.report(4, Duration::from_secs(1));
+        assert_eq!((report.sent, report.received, report.duplicates, report.reordered), (4, 3, 1, 1));
+        assert_eq!(report.loss(), 0.25);
+        assert_eq!(report.rtt_min, Duration::from_nanos(1_000));
+        assert_eq!(report.rtt_max, Duration::from_nanos(3_000));
+        assert_eq!(report.rtt_avg, Duration::from_nanos(1_800));
+        assert_eq!(report.forward_delay, Some(Duration::from_nanos(566)));
+        // transits 500, 700, 500: jitter 200/16 then (200 - 12.5)/16 more
+        assert_eq!(report.jitter, Duration::from_nanos(24));
+        assert_eq!(report.throughput, 3 * 36 * 8);
+    }
+}
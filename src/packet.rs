@@ -0,0 +1,682 @@
+//! Zero-copy views and builders of Ethernet, IPv4, IPv6, UDP, ICMP (ICMPv6) and IGMP headers for
+//! the frames and packets of raw (`raw`) and AF_PACKET sockets. The views check the lengths on
+//! creation and borrow the received data, the builders return the packet in wire format with
+//! lengths and checksums filled in.
+
+use std::{
+    convert::{TryFrom, TryInto},
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+use super::checksum::{internet_checksum, pseudo_header_checksum, udp_checksum, verify};
+
+/// Ethernet type of IPv4.
+pub const ETHERTYPE_IPV4: u16 = 0x0800;
+/// Ethernet type of ARP.
+pub const ETHERTYPE_ARP: u16 = 0x0806;
+/// Ethernet type of IEEE 802.1Q VLAN tags.
+pub const ETHERTYPE_VLAN: u16 = 0x8100;
+/// Ethernet type of IPv6.
+pub const ETHERTYPE_IPV6: u16 = 0x86dd;
+
+/// IP protocol number of ICMP.
+pub const IPPROTO_ICMP: u8 = 1;
+/// IP protocol number of IGMP.
+pub const IPPROTO_IGMP: u8 = 2;
+/// IP protocol number of UDP.
+pub const IPPROTO_UDP: u8 = 17;
+/// IPv6 next header of the hop-by-hop options.
+pub const IPPROTO_HOPOPTS: u8 = 0;
+/// IPv6 next header of the routing header.
+pub const IPPROTO_ROUTING: u8 = 43;
+/// IPv6 next header of the fragment header.
+pub const IPPROTO_FRAGMENT: u8 = 44;
+/// IP protocol number (IPv6 next header) of ICMPv6.
+pub const IPPROTO_ICMPV6: u8 = 58;
+/// IPv6 next header of the destination options.
+pub const IPPROTO_DSTOPTS: u8 = 60;
+
+/// Length of an Ethernet header without VLAN tag.
+pub const ETHERNET_HEADER_LEN: usize = 14;
+/// Maximum length of the options of an IPv4 header (4 bit header length in words).
+const MAX_IPV4_OPTIONS_LEN: usize = 40;
+/// Length of an IPv4 header without options.
+pub const IPV4_HEADER_LEN: usize = 20;
+/// Length of the IPv6 header.
+pub const IPV6_HEADER_LEN: usize = 40;
+/// Length of the UDP header.
+pub const UDP_HEADER_LEN: usize = 8;
+
+fn be16(data: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([data[offset], data[offset + 1]])
+}
+
+fn ipv4_address(data: &[u8], offset: usize) -> Ipv4Addr {
+    Ipv4Addr::new(data[offset], data[offset + 1], data[offset + 2], data[offset + 3])
+}
+
+/// An Ethernet II frame (without frame check sequence), optionally with one 802.1Q VLAN tag.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EthernetFrame<'a> {
+    data: &'a [u8],
+    /// length of the header including the VLAN tag
+    header_len: usize,
+}
+
+impl<'a> EthernetFrame<'a> {
+
+    /// Creates the view of the frame, None if it is shorter than its header.
+    pub fn new(data: &'a [u8]) -> Option<EthernetFrame<'a>> {
+        if data.len() < ETHERNET_HEADER_LEN {
+            return None;
+        }
+        let header_len = match be16(data, 12) {
+            ETHERTYPE_VLAN => ETHERNET_HEADER_LEN + 4,
+            _ => ETHERNET_HEADER_LEN,
+        };
+        if data.len() < header_len {
+            return None;
+        }
+        Some(EthernetFrame { data, header_len })
+    }
+
+    /// Returns the destination MAC address.
+    pub fn destination(&self) -> [u8; 6] {
+        self.data[..6].try_into().unwrap()
+    }
+
+    /// Returns the source MAC address.
+    pub fn source(&self) -> [u8; 6] {
+        self.data[6..12].try_into().unwrap()
+    }
+
+    /// Returns the VLAN id of a tagged frame.
+    pub fn vlan(&self) -> Option<u16> {
+        match self.header_len {
+            ETHERNET_HEADER_LEN => None,
+            _ => Some(be16(self.data, 14) & 0x0fff),
+        }
+    }
+
+    /// Returns the Ethernet type of the payload (after the VLAN tag).
+    pub fn ether_type(&self) -> u16 {
+        be16(self.data, self.header_len - 2)
+    }
+
+    /// Returns the payload, which may include padding to the minimum frame length.
+    pub fn payload(&self) -> &'a [u8] {
+        &self.data[self.header_len..]
+    }
+}
+
+/// Returns an Ethernet frame (without VLAN tag and frame check sequence) of the payload.
+pub fn ethernet_frame(destination: &[u8; 6], source: &[u8; 6], ether_type: u16, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(ETHERNET_HEADER_LEN + payload.len());
+    frame.extend_from_slice(destination);
+    frame.extend_from_slice(source);
+    frame.extend_from_slice(&ether_type.to_be_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// An IPv4 packet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Ipv4Packet<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Ipv4Packet<'a> {
+
+    /// Creates the view of the packet, None if it is no IPv4 packet or shorter than its header or
+    /// total length. Data after the total length (e.g. Ethernet padding) are ignored.
+    pub fn new(data: &'a [u8]) -> Option<Ipv4Packet<'a>> {
+        let first = *data.first()?;
+        let header_len = ((first & 0x0f) as usize) * 4;
+        if first >> 4 != 4 || header_len < IPV4_HEADER_LEN || data.len() < header_len {
+            return None;
+        }
+        let total_len = be16(data, 2) as usize;
+        if total_len < header_len || data.len() < total_len {
+            return None;
+        }
+        Some(Ipv4Packet { data: &data[..total_len] })
+    }
+
+    /// Returns the length of the header including the options.
+    pub fn header_len(&self) -> usize {
+        ((self.data[0] & 0x0f) as usize) * 4
+    }
+
+    /// Returns the TOS byte (DSCP and ECN).
+    pub fn tos(&self) -> u8 {
+        self.data[1]
+    }
+
+    /// Returns the identification.
+    pub fn identification(&self) -> u16 {
+        be16(self.data, 4)
+    }
+
+    /// Returns whether the don't fragment flag is set.
+    pub fn dont_fragment(&self) -> bool {
+        self.data[6] & 0x40 != 0
+    }
+
+    /// Returns whether the more fragments flag is set.
+    pub fn more_fragments(&self) -> bool {
+        self.data[6] & 0x20 != 0
+    }
+
+    /// Returns the fragment offset in bytes.
+    pub fn fragment_offset(&self) -> usize {
+        ((be16(self.data, 6) & 0x1fff) as usize) * 8
+    }
+
+    /// Returns the TTL.
+    pub fn ttl(&self) -> u8 {
+        self.data[8]
+    }
+
+    /// Returns the protocol of the payload.
+    pub fn protocol(&self) -> u8 {
+        self.data[9]
+    }
+
+    /// Returns the source address.
+    pub fn source(&self) -> Ipv4Addr {
+        ipv4_address(self.data, 12)
+    }
+
+    /// Returns the destination address.
+    pub fn destination(&self) -> Ipv4Addr {
+        ipv4_address(self.data, 16)
+    }
+
+    /// Returns the options.
+    pub fn options(&self) -> &'a [u8] {
+        &self.data[IPV4_HEADER_LEN..self.header_len()]
+    }
+
+    /// Returns whether the header checksum is correct.
+    pub fn verify_checksum(&self) -> bool {
+        verify(&self.data[..self.header_len()])
+    }
+
+    /// Returns the payload.
+    pub fn payload(&self) -> &'a [u8] {
+        &self.data[self.header_len()..]
+    }
+}
+
+/// Builder of IPv4 packets.
+/// ```
+/// use net_utils::packet::{IPPROTO_UDP, Ipv4Builder, Ipv4Packet};
+/// let packet = Ipv4Builder::new("192.0.2.1".parse().unwrap(), "192.0.2.2".parse().unwrap(), IPPROTO_UDP)
+///     .ttl(1)
+///     .build(b"payload");
+/// assert!(Ipv4Packet::new(&packet).unwrap().verify_checksum());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Ipv4Builder {
+    source: Ipv4Addr,
+    destination: Ipv4Addr,
+    protocol: u8,
+    tos: u8,
+    ttl: u8,
+    identification: u16,
+    dont_fragment: bool,
+    options: Vec<u8>,
+}
+
+impl Ipv4Builder {
+
+    /// Creates a builder of packets from source to destination of the protocol, with TTL 64 and
+    /// the don't fragment flag.
+    pub fn new(source: Ipv4Addr, destination: Ipv4Addr, protocol: u8) -> Ipv4Builder {
+        Ipv4Builder { source, destination, protocol, tos: 0, ttl: 64, identification: 0, dont_fragment: true,
+                      options: Vec::new() }
+    }
+
+    /// Sets the TOS byte (DSCP and ECN).
+    pub fn tos(mut self, tos: u8) -> Self {
+        self.tos = tos;
+        self
+    }
+
+    /// Sets the TTL.
+    pub fn ttl(mut self, ttl: u8) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Sets the identification.
+    pub fn identification(mut self, identification: u16) -> Self {
+        self.identification = identification;
+        self
+    }
+
+    /// Sets the don't fragment flag.
+    pub fn dont_fragment(mut self, dont_fragment: bool) -> Self {
+        self.dont_fragment = dont_fragment;
+        self
+    }
+
+    /// Sets the options, e.g. the router alert option [0x94, 0x04, 0, 0]. Fails with InvalidInput
+    /// if they are longer than 40 bytes or not padded (with end of options) to a multiple of four
+    /// bytes.
+    pub fn options(mut self, options: &[u8]) -> Result<Self> {
+        if options.len() > MAX_IPV4_OPTIONS_LEN || !options.len().is_multiple_of(4) {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  format!("invalid IPv4 options length {}", options.len())));
+        }
+        self.options = options.to_vec();
+        Ok(self)
+    }
+
+    /// Returns the packet of the payload with header checksum.
+    pub fn build(&self, payload: &[u8]) -> Vec<u8> {
+        let header_len = IPV4_HEADER_LEN + self.options.len();
+        let mut packet = Vec::with_capacity(header_len + payload.len());
+        packet.extend_from_slice(&[0x40 | (header_len / 4) as u8, self.tos]);
+        packet.extend_from_slice(&((header_len + payload.len()) as u16).to_be_bytes());
+        packet.extend_from_slice(&self.identification.to_be_bytes());
+        packet.extend_from_slice(&[if self.dont_fragment { 0x40 } else { 0 }, 0, self.ttl, self.protocol, 0, 0]);
+        packet.extend_from_slice(&self.source.octets());
+        packet.extend_from_slice(&self.destination.octets());
+        packet.extend_from_slice(&self.options);
+        let checksum = internet_checksum(&packet);
+        packet[10..12].copy_from_slice(&checksum.to_be_bytes());
+        packet.extend_from_slice(payload);
+        packet
+    }
+}
+
+/// An IPv6 packet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Ipv6Packet<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Ipv6Packet<'a> {
+
+    /// Creates the view of the packet, None if it is no IPv6 packet or shorter than its header and
+    /// payload length. Data after the payload (e.g. Ethernet padding) are ignored.
+    pub fn new(data: &'a [u8]) -> Option<Ipv6Packet<'a>> {
+        if data.len() < IPV6_HEADER_LEN || data[0] >> 4 != 6 {
+            return None;
+        }
+        let len = IPV6_HEADER_LEN + be16(data, 4) as usize;
+        Some(Ipv6Packet { data: data.get(..len)? })
+    }
+
+    /// Returns the traffic class (DSCP and ECN).
+    pub fn traffic_class(&self) -> u8 {
+        self.data[0] << 4 | self.data[1] >> 4
+    }
+
+    /// Returns the flow label.
+    pub fn flow_label(&self) -> u32 {
+        u32::from_be_bytes([0, self.data[1] & 0x0f, self.data[2], self.data[3]])
+    }
+
+    /// Returns the next header, the protocol of the payload or an extension header.
+    pub fn next_header(&self) -> u8 {
+        self.data[6]
+    }
+
+    /// Returns the hop limit.
+    pub fn hop_limit(&self) -> u8 {
+        self.data[7]
+    }
+
+    /// Returns the source address.
+    pub fn source(&self) -> Ipv6Addr {
+        Ipv6Addr::from(<[u8; 16]>::try_from(&self.data[8..24]).unwrap())
+    }
+
+    /// Returns the destination address.
+    pub fn destination(&self) -> Ipv6Addr {
+        Ipv6Addr::from(<[u8; 16]>::try_from(&self.data[24..40]).unwrap())
+    }
+
+    /// Returns the payload including the extension headers.
+    pub fn payload(&self) -> &'a [u8] {
+        &self.data[IPV6_HEADER_LEN..]
+    }
+
+    /// Returns the protocol and data after the hop-by-hop, routing and destination options
+    /// headers, None if they are truncated. Fragments are returned with IPPROTO_FRAGMENT.
+    pub fn upper_layer(&self) -> Option<(u8, &'a [u8])> {
+        let mut next_header = self.next_header();
+        let mut data = self.payload();
+        while let IPPROTO_HOPOPTS | IPPROTO_ROUTING | IPPROTO_DSTOPTS = next_header {
+            let len = (*data.get(1)? as usize + 1) * 8;
+            next_header = data[0];
+            data = data.get(len..)?;
+        }
+        Some((next_header, data))
+    }
+}
+
+/// Builder of IPv6 packets.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Ipv6Builder {
+    source: Ipv6Addr,
+    destination: Ipv6Addr,
+    next_header: u8,
+    traffic_class: u8,
+    flow_label: u32,
+    hop_limit: u8,
+    hop_by_hop: Vec<u8>,
+}
+
+impl Ipv6Builder {
+
+    /// Creates a builder of packets from source to destination with the next header (protocol)
+    /// and hop limit 64.
+    pub fn new(source: Ipv6Addr, destination: Ipv6Addr, next_header: u8) -> Ipv6Builder {
+        Ipv6Builder { source, destination, next_header, traffic_class: 0, flow_label: 0, hop_limit: 64,
+                      hop_by_hop: Vec::new() }
+    }
+
+    /// Sets the traffic class (DSCP and ECN).
+    pub fn traffic_class(mut self, traffic_class: u8) -> Self {
+        self.traffic_class = traffic_class;
+        self
+    }
+
+    /// Sets the flow label (20 bit).
+    pub fn flow_label(mut self, flow_label: u32) -> Self {
+        self.flow_label = flow_label & 0x000f_ffff;
+        self
+    }
+
+    /// Sets the hop limit.
+    pub fn hop_limit(mut self, hop_limit: u8) -> Self {
+        self.hop_limit = hop_limit;
+        self
+    }
+
+    /// Adds a hop-by-hop options header with the options, e.g. the router alert option
+    /// [5, 2, 0, 0] of MLD, padded (PadN) to a multiple of eight bytes.
+    pub fn hop_by_hop_options(mut self, options: &[u8]) -> Self {
+        let len = (options.len() + 2).div_ceil(8) * 8;
+        self.hop_by_hop = vec![self.next_header, (len / 8 - 1) as u8];
+        self.hop_by_hop.extend_from_slice(options);
+        match len - self.hop_by_hop.len() {
+            0 => (),
+            1 => self.hop_by_hop.push(0),
+            padding => {
+                self.hop_by_hop.extend_from_slice(&[1, (padding - 2) as u8]);
+                self.hop_by_hop.resize(len, 0);
+            },
+        }
+        self
+    }
+
+    /// Returns the packet of the payload.
+    pub fn build(&self, payload: &[u8]) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(IPV6_HEADER_LEN + self.hop_by_hop.len() + payload.len());
+        let first = 6 << 28 | (self.traffic_class as u32) << 20 | self.flow_label;
+        packet.extend_from_slice(&first.to_be_bytes());
+        packet.extend_from_slice(&((self.hop_by_hop.len() + payload.len()) as u16).to_be_bytes());
+        let next_header = if self.hop_by_hop.is_empty() { self.next_header } else { IPPROTO_HOPOPTS };
+        packet.extend_from_slice(&[next_header, self.hop_limit]);
+        packet.extend_from_slice(&self.source.octets());
+        packet.extend_from_slice(&self.destination.octets());
+        packet.extend_from_slice(&self.hop_by_hop);
+        packet.extend_from_slice(payload);
+        packet
+    }
+}
+
+/// A UDP datagram.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct UdpDatagram<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> UdpDatagram<'a> {
+
+    /// Creates the view of the datagram, None if it is shorter than its header or length.
+    pub fn new(data: &'a [u8]) -> Option<UdpDatagram<'a>> {
+        let len = be16(data.get(..UDP_HEADER_LEN)?, 4) as usize;
+        if len < UDP_HEADER_LEN {
+            return None;
+        }
+        Some(UdpDatagram { data: data.get(..len)? })
+    }
+
+    /// Returns the source port.
+    pub fn source_port(&self) -> u16 {
+        be16(self.data, 0)
+    }
+
+    /// Returns the destination port.
+    pub fn destination_port(&self) -> u16 {
+        be16(self.data, 2)
+    }
+
+    /// Returns the checksum, zero if the sender did not compute it.
+    pub fn checksum(&self) -> u16 {
+        be16(self.data, 6)
+    }
+
+    /// Returns whether the checksum is correct or not computed (IPv4 only) for the datagram sent
+    /// from source to destination.
+    pub fn verify_checksum(&self, source: &IpAddr, destination: &IpAddr) -> bool {
+        (self.checksum() == 0 && source.is_ipv4())
+            || pseudo_header_checksum(source, destination, IPPROTO_UDP, self.data) == 0
+    }
+
+    /// Returns the payload.
+    pub fn payload(&self) -> &'a [u8] {
+        &self.data[UDP_HEADER_LEN..]
+    }
+}
+
+/// Returns the UDP datagram of the payload with checksum, sent from source to destination.
+pub fn udp_datagram(source: &std::net::SocketAddr, destination: &std::net::SocketAddr, payload: &[u8]) -> Vec<u8> {
+    let mut datagram = Vec::with_capacity(UDP_HEADER_LEN + payload.len());
+    datagram.extend_from_slice(&source.port().to_be_bytes());
+    datagram.extend_from_slice(&destination.port().to_be_bytes());
+    datagram.extend_from_slice(&((UDP_HEADER_LEN + payload.len()) as u16).to_be_bytes());
+    datagram.extend_from_slice(&[0, 0]);
+    datagram.extend_from_slice(payload);
+    let checksum = udp_checksum(&source.ip(), &destination.ip(), &datagram);
+    datagram[6..8].copy_from_slice(&checksum.to_be_bytes());
+    datagram
+}
+
+/// An ICMP or ICMPv6 message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct IcmpMessage<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> IcmpMessage<'a> {
+
+    /// Creates the view of the message, None if it is shorter than its header.
+    pub fn new(data: &'a [u8]) -> Option<IcmpMessage<'a>> {
+        match data.len() {
+            0..=3 => None,
+            _ => Some(IcmpMessage { data }),
+        }
+    }
+
+    /// Returns the message type, e.g. `icmpv6::ND_ROUTER_ADVERT` for ICMPv6.
+    pub fn msg_type(&self) -> u8 {
+        self.data[0]
+    }
+
+    /// Returns the code.
+    pub fn code(&self) -> u8 {
+        self.data[1]
+    }
+
+    /// Returns the checksum.
+    pub fn checksum(&self) -> u16 {
+        be16(self.data, 2)
+    }
+
+    /// Returns whether the checksum of an ICMP message is correct.
+    pub fn verify_checksum(&self) -> bool {
+        verify(self.data)
+    }
+
+    /// Returns whether the checksum of an ICMPv6 message sent from source to destination is
+    /// correct.
+    pub fn verify_checksum_v6(&self, source: &Ipv6Addr, destination: &Ipv6Addr) -> bool {
+        pseudo_header_checksum(&IpAddr::V6(*source), &IpAddr::V6(*destination), IPPROTO_ICMPV6, self.data) == 0
+    }
+
+    /// Returns the message body after type, code and checksum.
+    pub fn body(&self) -> &'a [u8] {
+        &self.data[4..]
+    }
+}
+
+/// Returns the ICMP message with checksum.
+pub fn icmp_message(msg_type: u8, code: u8, body: &[u8]) -> Vec<u8> {
+    let mut message = [&[msg_type, code, 0, 0][..], body].concat();
+    let checksum = internet_checksum(&message);
+    message[2..4].copy_from_slice(&checksum.to_be_bytes());
+    message
+}
+
+/// Returns the ICMPv6 message with the checksum of the pseudo header of source and destination.
+pub fn icmpv6_message(source: &Ipv6Addr, destination: &Ipv6Addr, msg_type: u8, code: u8, body: &[u8]) -> Vec<u8> {
+    let mut message = [&[msg_type, code, 0, 0][..], body].concat();
+    let checksum = pseudo_header_checksum(&IpAddr::V6(*source), &IpAddr::V6(*destination), IPPROTO_ICMPV6, &message);
+    message[2..4].copy_from_slice(&checksum.to_be_bytes());
+    message
+}
+
+/// An IGMP message, the common header of all versions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct IgmpHeader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> IgmpHeader<'a> {
+
+    /// Creates the view of the message, None if it is shorter than eight bytes.
+    pub fn new(data: &'a [u8]) -> Option<IgmpHeader<'a>> {
+        match data.len() {
+            0..=7 => None,
+            _ => Some(IgmpHeader { data }),
+        }
+    }
+
+    /// Returns the message type.
+    pub fn msg_type(&self) -> u8 {
+        self.data[0]
+    }
+
+    /// Returns the max response code of queries.
+    pub fn max_response_code(&self) -> u8 {
+        self.data[1]
+    }
+
+    /// Returns whether the checksum is correct.
+    pub fn verify_checksum(&self) -> bool {
+        verify(self.data)
+    }
+
+    /// Returns the group address, reserved (zero) in IGMPv3 reports.
+    pub fn group(&self) -> Ipv4Addr {
+        ipv4_address(self.data, 4)
+    }
+
+    /// Returns the whole message including the header.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_ethernet() {
+        let frame = ethernet_frame(&[0xff; 6], &[2, 0, 0, 0, 0, 1], ETHERTYPE_IPV4, &[0x45]);
+        let view = EthernetFrame::new(&frame).unwrap();
+        assert_eq!((view.destination(), view.source()), ([0xff; 6], [2, 0, 0, 0, 0, 1]));
+        assert_eq!((view.vlan(), view.ether_type(), view.payload()), (None, ETHERTYPE_IPV4, &[0x45][..]));
+
+        let mut tagged = frame[..12].to_vec();
+        tagged.extend_from_slice(&[0x81, 0x00, 0x20, 0x2a, 0x86, 0xdd]);
+        let view = EthernetFrame::new(&tagged).unwrap();
+        assert_eq!((view.vlan(), view.ether_type(), view.payload().len()), (Some(42), ETHERTYPE_IPV6, 0));
+        assert!(EthernetFrame::new(&tagged[..16]).is_none());
+        assert!(EthernetFrame::new(&frame[..13]).is_none());
+    }
+
+    #[test]
+    fn test_ipv4_udp() {
+        let source = "192.0.2.1:5000".parse().unwrap();
+        let destination = "239.1.2.3:6000".parse().unwrap();
+        let datagram = udp_datagram(&source, &destination, b"hello");
+        let mut packet = Ipv4Builder::new("192.0.2.1".parse().unwrap(), "239.1.2.3".parse().unwrap(), IPPROTO_UDP)
+            .ttl(1).tos(0xb8).identification(7).options(&[0x94, 0x04, 0, 0]).unwrap().build(&datagram);
+        // Ethernet padding
+        packet.extend_from_slice(&[0; 4]);
+        let ip = Ipv4Packet::new(&packet).unwrap();
+        assert!(ip.verify_checksum());
+        assert_eq!((ip.header_len(), ip.tos(), ip.ttl(), ip.protocol()), (24, 0xb8, 1, IPPROTO_UDP));
+        assert_eq!((ip.identification(), ip.dont_fragment(), ip.more_fragments(), ip.fragment_offset()), (7, true, false, 0));
+        assert_eq!((ip.source(), ip.destination()), (Ipv4Addr::new(192, 0, 2, 1), Ipv4Addr::new(239, 1, 2, 3)));
+        assert_eq!(ip.options(), &[0x94, 0x04, 0, 0]);
+        let builder = Ipv4Builder::new(Ipv4Addr::LOCALHOST, Ipv4Addr::LOCALHOST, IPPROTO_UDP);
+        assert_eq!(builder.clone().options(&[0x94, 0x04]).unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(builder.clone().options(&[1; 44]).unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(Ipv4Packet::new(&builder.options(&[1; 40]).unwrap().build(&[])).unwrap().header_len(), 60);
+        let udp = UdpDatagram::new(ip.payload()).unwrap();
+        assert_eq!((udp.source_port(), udp.destination_port(), udp.payload()), (5000, 6000, &b"hello"[..]));
+        assert!(udp.verify_checksum(&source.ip(), &destination.ip()));
+        assert!(!udp.verify_checksum(&destination.ip(), &destination.ip()));
+
+        assert!(Ipv4Packet::new(&packet[..30]).is_none());
+        assert!(Ipv4Packet::new(&[0x65; 20]).is_none());
+        assert!(UdpDatagram::new(&datagram[..12]).is_none());
+    }
+
+    #[test]
+    fn test_ipv6_icmpv6() {
+        let source: Ipv6Addr = "fe80::1".parse().unwrap();
+        let destination: Ipv6Addr = "ff02::16".parse().unwrap();
+        let message = icmpv6_message(&source, &destination, 143, 0, &[0, 0, 0, 0]);
+        let packet = Ipv6Builder::new(source, destination, IPPROTO_ICMPV6)
+            .hop_limit(1).traffic_class(0xb8).flow_label(0x12345).hop_by_hop_options(&[5, 2, 0, 0]).build(&message);
+        let ip = Ipv6Packet::new(&packet).unwrap();
+        assert_eq!((ip.traffic_class(), ip.flow_label(), ip.hop_limit()), (0xb8, 0x12345, 1));
+        assert_eq!((ip.next_header(), ip.source(), ip.destination()), (IPPROTO_HOPOPTS, source, destination));
+        assert_eq!(ip.payload().len(), 8 + message.len());
+        assert_eq!(&ip.payload()[..8], &[IPPROTO_ICMPV6, 0, 5, 2, 0, 0, 1, 0]);
+        let (protocol, data) = ip.upper_layer().unwrap();
+        assert_eq!(protocol, IPPROTO_ICMPV6);
+        let icmp = IcmpMessage::new(data).unwrap();
+        assert_eq!((icmp.msg_type(), icmp.code(), icmp.body()), (143, 0, &[0, 0, 0, 0][..]));
+        assert!(icmp.verify_checksum_v6(&source, &destination));
+        assert!(Ipv6Packet::new(&packet[..packet.len() - 1]).is_none());
+
+        let echo = icmp_message(8, 0, &[0, 1, 0, 1]);
+        assert!(IcmpMessage::new(&echo).unwrap().verify_checksum());
+        assert!(IcmpMessage::new(&echo[..3]).is_none());
+    }
+
+    #[test]
+    fn test_igmp() {
+        let mut report = vec![0x16, 0, 0, 0, 239, 1, 2, 3];
+        let checksum = internet_checksum(&report);
+        report[2..4].copy_from_slice(&checksum.to_be_bytes());
+        let igmp = IgmpHeader::new(&report).unwrap();
+        assert_eq!((igmp.msg_type(), igmp.max_response_code(), igmp.group()), (0x16, 0, Ipv4Addr::new(239, 1, 2, 3)));
+        assert!(igmp.verify_checksum());
+        assert_eq!(igmp.data(), &report[..]);
+        assert!(IgmpHeader::new(&report[..7]).is_none());
+    }
+}
@@ -0,0 +1,293 @@
+//! NAT-PMP (RFC 6886) and PCP (RFC 6887) clients: port mappings on the default gateway.
+
+use std::{
+    convert::TryInto,
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use super::{igd::PortMappingProtocol, random::random_bytes};
+
+/// Server port of NAT-PMP and PCP.
+pub const PCP_PORT: u16 = 5351;
+
+const NAT_PMP_VERSION: u8 = 0;
+const PCP_VERSION: u8 = 2;
+const OPCODE_RESPONSE: u8 = 0x80;
+const NAT_PMP_OP_EXTERNAL_ADDRESS: u8 = 0;
+const PCP_OP_MAP: u8 = 1;
+const PCP_OP_PEER: u8 = 2;
+const PCP_HEADER_LEN: usize = 24;
+const PCP_MAP_LEN: usize = 36;
+const PCP_PEER_LEN: usize = 56;
+
+/// Initial retransmission timeout, doubled with every retransmission.
+const INITIAL_RTO: Duration = Duration::from_millis(250);
+/// Number of transmissions of a request before giving up.
+const MAX_TRANSMISSIONS: u32 = 5;
+
+/// A port mapping granted by the gateway.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PortMapping {
+    /// transport protocol
+    pub protocol: PortMappingProtocol,
+
+    /// internal port of this host
+    pub internal_port: u16,
+
+    /// assigned external port
+    pub external_port: u16,
+
+    /// assigned external address, None for NAT-PMP mappings (see `nat_pmp_external_address`)
+    pub external_address: Option<IpAddr>,
+
+    /// lifetime of the mapping, the mapping has to be renewed before it expires
+    pub lifetime: Duration,
+}
+
+/// Returns the gateway of the IPv4 default route of the main routing table, or of the IPv6
+/// default route if there is no IPv4 default route.
+#[cfg(target_os = "linux")]
+pub fn default_gateway() -> Result<IpAddr> {
+    let mut gateways: Vec<(IpAddr, u32)> = super::netlink::routes()?.into_iter()
+        .filter(|r| r.table == libc::RT_TABLE_MAIN as u32 && r.destination_len == 0)
+        .filter_map(|r| Some((r.gateway?, r.priority.unwrap_or(0))))
+        .collect();
+    gateways.sort_by_key(|(gateway, priority)| (gateway.is_ipv6(), *priority));
+    gateways.first().map(|(gateway, _)| *gateway)
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "no default gateway"))
+}
+
+/// Requests the external IPv4 address of the NAT-PMP gateway.
+pub fn nat_pmp_external_address(gateway: Ipv4Addr) -> Result<Ipv4Addr> {
+    let request = [NAT_PMP_VERSION, NAT_PMP_OP_EXTERNAL_ADDRESS];
+    let response = transact(SocketAddr::new(gateway.into(), PCP_PORT), &request, |r| {
+        r.len() >= 12 && r[0] == NAT_PMP_VERSION && r[1] == OPCODE_RESPONSE | NAT_PMP_OP_EXTERNAL_ADDRESS
+    })?;
+    nat_pmp_result(u16::from_be_bytes([response[2], response[3]]))?;
+    Ok(Ipv4Addr::new(response[8], response[9], response[10], response[11]))
+}
+
+/// Requests a mapping of the internal port from the NAT-PMP gateway, the gateway may assign
+/// another external port than the suggested one (0 for any). A lifetime of zero removes the
+/// mapping.
+pub fn nat_pmp_map(gateway: Ipv4Addr, protocol: PortMappingProtocol, internal_port: u16, suggested_external_port: u16,
+                   lifetime: Duration) -> Result<PortMapping> {
+    let opcode = match protocol {
+        PortMappingProtocol::Udp => 1,
+        PortMappingProtocol::Tcp => 2,
+    };
+    let mut request = [0_u8; 12];
+    request[1] = opcode;
+    request[4..6].copy_from_slice(&internal_port.to_be_bytes());
+    request[6..8].copy_from_slice(&suggested_external_port.to_be_bytes());
+    request[8..12].copy_from_slice(&lifetime_secs(lifetime).to_be_bytes());
+    let response = transact(SocketAddr::new(gateway.into(), PCP_PORT), &request, |r| {
+        r.len() >= 16 && r[0] == NAT_PMP_VERSION && r[1] == OPCODE_RESPONSE | opcode && r[8..10] == request[4..6]
+    })?;
+    nat_pmp_result(u16::from_be_bytes([response[2], response[3]]))?;
+    Ok(PortMapping {
+        protocol,
+        internal_port,
+        external_port: u16::from_be_bytes([response[10], response[11]]),
+        external_address: None,
+        lifetime: Duration::from_secs(u32::from_be_bytes(response[12..16].try_into().unwrap()) as u64),
+    })
+}
+
+/// Requests a mapping of the internal port from the PCP server (MAP opcode) for inbound
+/// connections. The suggested external address and port may be given to recreate a previous
+/// mapping. A lifetime of zero removes the mapping.
+pub fn pcp_map(gateway: IpAddr, protocol: PortMappingProtocol, internal_port: u16, suggested_external: Option<SocketAddr>,
+               lifetime: Duration) -> Result<PortMapping> {
+    pcp_request(gateway, PCP_OP_MAP, protocol, internal_port, suggested_external, None, lifetime)
+}
+
+/// Requests a mapping of the internal port for the connection to the remote peer from the PCP
+/// server (PEER opcode), e.g. to learn the external address and port of an outbound connection
+/// or to extend the lifetime of its mapping.
+pub fn pcp_peer(gateway: IpAddr, protocol: PortMappingProtocol, internal_port: u16, remote_peer: SocketAddr,
+                suggested_external: Option<SocketAddr>, lifetime: Duration) -> Result<PortMapping> {
+    pcp_request(gateway, PCP_OP_PEER, protocol, internal_port, suggested_external, Some(remote_peer), lifetime)
+}
+
+fn pcp_request(gateway: IpAddr, opcode: u8, protocol: PortMappingProtocol, internal_port: u16,
+               suggested_external: Option<SocketAddr>, remote_peer: Option<SocketAddr>, lifetime: Duration)
+               -> Result<PortMapping> {
+    let mut nonce = [0_u8; 12];
+    random_bytes(&mut nonce);
+    let server = SocketAddr::new(gateway, PCP_PORT);
+    let client = local_address(&server)?;
+    let request = pcp_request_packet(opcode, &nonce, client, protocol, internal_port, suggested_external, remote_peer,
+                                     lifetime);
+    let response = transact(server, &request, |r| {
+        r.len() >= PCP_HEADER_LEN + PCP_MAP_LEN && r[0] == PCP_VERSION && r[1] == OPCODE_RESPONSE | opcode
+            && r[PCP_HEADER_LEN..PCP_HEADER_LEN + 12] == nonce
+    })?;
+    parse_pcp_response(&response, protocol)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn pcp_request_packet(opcode: u8, nonce: &[u8; 12], client: IpAddr, protocol: PortMappingProtocol, internal_port: u16,
+                      suggested_external: Option<SocketAddr>, remote_peer: Option<SocketAddr>, lifetime: Duration)
+                      -> Vec<u8> {
+    let mut packet = vec![0_u8; PCP_HEADER_LEN + if remote_peer.is_some() { PCP_PEER_LEN } else { PCP_MAP_LEN }];
+    packet[0] = PCP_VERSION;
+    packet[1] = opcode;
+    packet[4..8].copy_from_slice(&lifetime_secs(lifetime).to_be_bytes());
+    packet[8..24].copy_from_slice(&ipv6_octets(client));
+    let data = &mut packet[PCP_HEADER_LEN..];
+    data[0..12].copy_from_slice(nonce);
+    data[12] = match protocol {
+        PortMappingProtocol::Tcp => 6,
+        PortMappingProtocol::Udp => 17,
+    };
+    data[16..18].copy_from_slice(&internal_port.to_be_bytes());
+    let unspecified = match client {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+    let suggested = suggested_external.unwrap_or_else(|| SocketAddr::new(unspecified, 0));
+    data[18..20].copy_from_slice(&suggested.port().to_be_bytes());
+    data[20..36].copy_from_slice(&ipv6_octets(suggested.ip()));
+    if let Some(peer) = remote_peer {
+        data[36..38].copy_from_slice(&peer.port().to_be_bytes());
+        data[40..56].copy_from_slice(&ipv6_octets(peer.ip()));
+    }
+    packet
+}
+
+fn parse_pcp_response(response: &[u8], protocol: PortMappingProtocol) -> Result<PortMapping> {
+    let result = response[3];
+    if result != 0 {
+        return Err(pcp_error(result));
+    }
+    let data = &response[PCP_HEADER_LEN..];
+    let address = Ipv6Addr::from(u128::from_be_bytes(data[20..36].try_into().unwrap()));
+    Ok(PortMapping {
+        protocol,
+        internal_port: u16::from_be_bytes([data[16], data[17]]),
+        external_port: u16::from_be_bytes([data[18], data[19]]),
+        external_address: Some(address.to_ipv4_mapped().map_or(IpAddr::V6(address), IpAddr::V4)),
+        lifetime: Duration::from_secs(u32::from_be_bytes(response[4..8].try_into().unwrap()) as u64),
+    })
+}
+
+/// Sends the request to the server and retransmits it until a response accepted by `is_response`
+/// arrives.
+fn transact<F: Fn(&[u8]) -> bool>(server: SocketAddr, request: &[u8], is_response: F) -> Result<Vec<u8>> {
+    let socket = super::sys_socket::udp_bind(unspecified_address(&server))?;
+    socket.connect(server)?;
+    let mut rto = INITIAL_RTO;
+    let mut response = [0_u8; 1100];
+    for _ in 0..MAX_TRANSMISSIONS {
+        socket.send(request)?;
+        socket.set_read_timeout(Some(rto))?;
+        rto *= 2;
+        loop {
+            match socket.recv(&mut response) {
+                Ok(len) if is_response(&response[..len]) => return Ok(response[..len].to_vec()),
+                Ok(_) => continue,
+                Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => break,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    Err(Error::new(ErrorKind::TimedOut, "no response from the gateway"))
+}
+
+/// Returns the local address used to reach the server.
+fn local_address(server: &SocketAddr) -> Result<IpAddr> {
+    let socket = super::sys_socket::udp_bind(unspecified_address(server))?;
+    socket.connect(server)?;
+    Ok(socket.local_addr()?.ip())
+}
+
+fn unspecified_address(server: &SocketAddr) -> SocketAddr {
+    match server {
+        SocketAddr::V4(_) => SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0),
+        SocketAddr::V6(_) => SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0),
+    }
+}
+
+/// Returns the 16 byte representation of the address used by PCP, IPv4 addresses as IPv4-mapped
+/// IPv6 addresses.
+fn ipv6_octets(address: IpAddr) -> [u8; 16] {
+    match address {
+        IpAddr::V4(a) => a.to_ipv6_mapped().octets(),
+        IpAddr::V6(a) => a.octets(),
+    }
+}
+
+fn lifetime_secs(lifetime: Duration) -> u32 {
+    lifetime.as_secs().min(u32::MAX as u64) as u32
+}
+
+fn nat_pmp_result(code: u16) -> Result<()> {
+    let (kind, text) = match code {
+        0 => return Ok(()),
+        1 => (ErrorKind::Unsupported, "unsupported version"),
+        2 => (ErrorKind::PermissionDenied, "not authorized or refused"),
+        3 => (ErrorKind::Other, "network failure"),
+        4 => (ErrorKind::Other, "out of resources"),
+        5 => (ErrorKind::Unsupported, "unsupported opcode"),
+        _ => (ErrorKind::Other, "unknown result code"),
+    };
+    Err(Error::new(kind, format!("NAT-PMP error {} ({})", code, text)))
+}
+
+fn pcp_error(code: u8) -> Error {
+    let (kind, text) = match code {
+        1 => (ErrorKind::Unsupported, "UNSUPP_VERSION"),
+        2 => (ErrorKind::PermissionDenied, "NOT_AUTHORIZED"),
+        3 => (ErrorKind::InvalidInput, "MALFORMED_REQUEST"),
+        4 => (ErrorKind::Unsupported, "UNSUPP_OPCODE"),
+        5 => (ErrorKind::Unsupported, "UNSUPP_OPTION"),
+        6 => (ErrorKind::InvalidInput, "MALFORMED_OPTION"),
+        7 => (ErrorKind::Other, "NETWORK_FAILURE"),
+        8 => (ErrorKind::Other, "NO_RESOURCES"),
+        9 => (ErrorKind::Unsupported, "UNSUPP_PROTOCOL"),
+        10 => (ErrorKind::Other, "USER_EX_QUOTA"),
+        11 => (ErrorKind::AddrNotAvailable, "CANNOT_PROVIDE_EXTERNAL"),
+        12 => (ErrorKind::InvalidInput, "ADDRESS_MISMATCH"),
+        13 => (ErrorKind::Other, "EXCESSIVE_REMOTE_PEERS"),
+        _ => (ErrorKind::Other, "unknown result code"),
+    };
+    Error::new(kind, format!("PCP error {} ({})", code, text))
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_pcp_packets() {
+        let nonce = [9_u8; 12];
+        let client = "192.168.1.10".parse().unwrap();
+        let peer = "203.0.113.5:443".parse().unwrap();
+        let request = pcp_request_packet(PCP_OP_PEER, &nonce, client, PortMappingProtocol::Tcp, 5000, None, Some(peer),
+                                         Duration::from_secs(7200));
+        assert_eq!(request.len(), PCP_HEADER_LEN + PCP_PEER_LEN);
+        assert_eq!(&request[..8], &[2, 2, 0, 0, 0, 0, 0x1c, 0x20]);
+        assert_eq!(&request[8..24], &ipv6_octets(client));
+        assert_eq!(&request[24..36], &nonce);
+        assert_eq!(request[36], 6);
+        assert_eq!(&request[40..44], &[0x13, 0x88, 0, 0]);
+        assert_eq!(&request[44..60], &Ipv4Addr::UNSPECIFIED.to_ipv6_mapped().octets());
+        assert_eq!(&request[60..62], &[0x01, 0xbb]);
+        assert_eq!(&request[64..80], &ipv6_octets(peer.ip()));
+
+        let mut response = request.clone();
+        response[1] |= OPCODE_RESPONSE;
+        response[42..44].copy_from_slice(&6000_u16.to_be_bytes());
+        response[44..60].copy_from_slice(&ipv6_octets("198.51.100.1".parse().unwrap()));
+        let mapping = parse_pcp_response(&response, PortMappingProtocol::Tcp).unwrap();
+        assert_eq!(mapping, PortMapping { protocol: PortMappingProtocol::Tcp, internal_port: 5000, external_port: 6000,
+            external_address: Some("198.51.100.1".parse().unwrap()), lifetime: Duration::from_secs(7200) });
+        response[3] = 2;
+        assert_eq!(parse_pcp_response(&response, PortMappingProtocol::Tcp).unwrap_err().kind(),
+                   ErrorKind::PermissionDenied);
+    }
+}
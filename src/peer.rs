@@ -0,0 +1,123 @@
+//! Classification of a peer address as the host itself, on the link of an interface, behind a
+//! gateway or unreachable, combining the interface addresses, the neighbor cache and the main
+//! routing table, e.g. to explain why a peer cannot be reached.
+
+use std::{
+    io::Result,
+    net::IpAddr,
+};
+
+use super::{IpInterface, netlink::{self, NeighborInfo, RouteInfo}};
+
+/// Where a peer address is located relative to the host.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PeerLocation {
+    /// the address is assigned to the interface of the host
+    Local(IpInterface),
+    /// the peer is directly reachable on the link of the interface
+    OnLink(IpInterface),
+    /// the peer is reached via the gateway of the route
+    ViaGateway(RouteInfo),
+    /// there is no route to the peer
+    Unreachable,
+}
+
+/// Classifies the peer address. Local addresses are recognized by the interface addresses,
+/// on-link peers by their neighbor cache entries, the networks of the interfaces and direct
+/// routes, other peers by the longest matching gateway route of the main routing table. IPv6
+/// link-local peers without neighbor cache entry are assigned to the first interface with a
+/// link-local address.
+pub fn classify_peer(address: &IpAddr) -> Result<PeerLocation> {
+    Ok(classify(address, &IpInterface::retrieve_ip_interfaces()?, &netlink::neighbors()?, &netlink::routes()?))
+}
+
+fn classify(address: &IpAddr, interfaces: &[IpInterface], neighbors: &[NeighborInfo], routes: &[RouteInfo])
+            -> PeerLocation {
+    let same_family = |interface: &&IpInterface| interface.address.is_ipv4() == address.is_ipv4();
+    if let Some(interface) = interfaces.iter().find(|i| i.address.ip() == *address) {
+        return PeerLocation::Local(interface.clone());
+    }
+    if address.is_loopback() {
+        if let Some(interface) = interfaces.iter().filter(same_family).find(|i| i.is_loopback()) {
+            return PeerLocation::Local(interface.clone());
+        }
+    }
+    let on_interface = |index: u32| interfaces.iter().filter(same_family).find(|i| i.index == index).cloned();
+    let neighbor = neighbors.iter().find(|n| n.destination.as_ref() == Some(address)).and_then(|n| on_interface(n.index));
+    if let Some(interface) = neighbor {
+        return PeerLocation::OnLink(interface);
+    }
+    let on_link = interfaces.iter().filter(same_family).find(|i| !i.is_loopback() && i.is_on_link(address));
+    if let Some(interface) = on_link {
+        return PeerLocation::OnLink(interface.clone());
+    }
+    let route = routes.iter()
+        .filter(|r| r.table == libc::RT_TABLE_MAIN as u32 && r.route_type == libc::RTN_UNICAST)
+        .filter(|r| r.destination_net().map(|network| network.contains(address)).unwrap_or(false))
+        .max_by_key(|r| (r.destination_len, std::cmp::Reverse(r.priority.unwrap_or(0))));
+    match route {
+        Some(route) if route.gateway.is_some() => PeerLocation::ViaGateway(route.clone()),
+        Some(route) => route.output_index.and_then(on_interface).map(PeerLocation::OnLink)
+            .unwrap_or(PeerLocation::Unreachable),
+        None => PeerLocation::Unreachable,
+    }
+}
+
+impl IpInterface {
+
+    /// Returns whether the peer address is on the link of this interface configuration.
+    pub fn is_on_link(&self, address: &IpAddr) -> bool {
+        match self.p2p_address {
+            Some(peer) => peer.ip() == *address,
+            None => self.network().contains(address),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::MockInterfaceProvider;
+
+    fn route(destination: Option<&str>, destination_len: u8, gateway: Option<&str>, output_index: u32) -> RouteInfo {
+        RouteInfo {
+            family: libc::AF_INET as u8, destination_len, table: libc::RT_TABLE_MAIN as u32,
+            protocol: libc::RTPROT_BOOT, scope: 0, route_type: libc::RTN_UNICAST,
+            destination: destination.map(|d| d.parse().unwrap()), source: None,
+            gateway: gateway.map(|g| g.parse().unwrap()), preferred_source: None,
+            output_index: Some(output_index), priority: None,
+        }
+    }
+
+    #[test]
+    fn test_classify() {
+        let lo = IpInterface {
+            net_mask: "255.0.0.0:0".parse().unwrap(),
+            ..MockInterfaceProvider::interface(1, "lo", libc::IFF_LOOPBACK | libc::IFF_UP, "127.0.0.1:0".parse().unwrap())
+        };
+        let eth0 = IpInterface {
+            net_mask: "255.255.255.0:0".parse().unwrap(),
+            ..MockInterfaceProvider::interface(2, "eth0", libc::IFF_UP, "192.168.1.2:0".parse().unwrap())
+        };
+        let interfaces = vec![lo.clone(), eth0.clone()];
+        let neighbors = vec![NeighborInfo {
+            index: 2, state: libc::NUD_REACHABLE, flags: 0, destination: Some("10.9.9.9".parse().unwrap()),
+            link_address: None,
+        }];
+        let default = route(None, 0, Some("192.168.1.1"), 2);
+        let direct = route(Some("10.1.0.0"), 16, None, 2);
+        let routes = vec![default.clone(), direct];
+        let classify = |address: &str| classify(&address.parse().unwrap(), &interfaces, &neighbors, &routes);
+
+        assert_eq!(classify("192.168.1.2"), PeerLocation::Local(eth0.clone()));
+        assert_eq!(classify("127.0.0.53"), PeerLocation::Local(lo));
+        assert_eq!(classify("192.168.1.77"), PeerLocation::OnLink(eth0.clone()));
+        assert_eq!(classify("10.9.9.9"), PeerLocation::OnLink(eth0.clone()));
+        assert_eq!(classify("10.1.2.3"), PeerLocation::OnLink(eth0.clone()));
+        assert_eq!(classify("8.8.8.8"), PeerLocation::ViaGateway(default));
+        assert_eq!(super::classify(&"8.8.8.8".parse().unwrap(), &interfaces, &neighbors, &[]), PeerLocation::Unreachable);
+        assert_eq!(classify("2001:db8::1"), PeerLocation::Unreachable);
+        assert!(eth0.is_on_link(&"192.168.1.200".parse().unwrap()));
+    }
+}
@@ -0,0 +1,242 @@
+//! Per datagram packet information (destination address, interface, TTL, TOS, receive time)
+//! from the control messages of received datagrams, returned as `PktInfo` by the single, batch
+//! (recvmmsg) and async receive functions alike.
+
+use std::{
+    convert::TryInto,
+    io::{Error, Result},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    os::unix::io::{AsFd, AsRawFd},
+    time::{Duration, SystemTime},
+};
+
+use super::{
+    cmsg::{ControlMessage, ReceivedMessage, recv_mmsg, recv_msg},
+    sockopt::set_int_option,
+};
+
+/// Packet information of a received datagram, fields are None if the kernel did not report them
+/// (e.g. because the socket option is not enabled).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PktInfo {
+    /// destination address of the datagram, e.g. the multicast group or the broadcast address
+    /// (IP_PKTINFO, IPV6_PKTINFO)
+    pub dst_addr: Option<IpAddr>,
+
+    /// index of the interface the datagram was received on (IP_PKTINFO, IPV6_PKTINFO)
+    pub if_index: Option<u32>,
+
+    /// TTL or hop limit of the packet (IP_TTL, IPV6_HOPLIMIT), see `set_recv_ttl`
+    pub ttl: Option<u8>,
+
+    /// TOS or traffic class of the packet including the ECN bits (IP_TOS, IPV6_TCLASS)
+    pub tos: Option<u8>,
+
+    /// time the kernel received the datagram (SO_TIMESTAMPNS or the software timestamp of
+    /// SO_TIMESTAMPING)
+    pub timestamp: Option<SystemTime>,
+}
+
+/// Explicit Congestion Notification codepoint of the two low bits of TOS / traffic class
+/// (RFC 3168).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Ecn {
+    /// Not-ECT: the transport does not support ECN
+    NotEct = 0,
+    /// ECT(1): ECN capable transport, also the L4S identifier (RFC 9331)
+    Ect1 = 1,
+    /// ECT(0): ECN capable transport
+    Ect0 = 2,
+    /// CE: congestion experienced, set by routers instead of dropping the packet
+    Ce = 3,
+}
+
+impl Ecn {
+
+    /// Returns the codepoint of the ECN bits of a TOS or traffic class byte.
+    pub fn from_tos(tos: u8) -> Ecn {
+        match tos & 0x03 {
+            0 => Ecn::NotEct,
+            1 => Ecn::Ect1,
+            2 => Ecn::Ect0,
+            _ => Ecn::Ce,
+        }
+    }
+
+    /// Returns whether the codepoint marks an ECN capable transport, i.e. it is not Not-ECT.
+    pub fn is_ect(self) -> bool {
+        self != Ecn::NotEct
+    }
+}
+
+impl PktInfo {
+
+    /// Returns the ECN codepoint of the TOS or traffic class, None if it is not reported.
+    pub fn ecn(&self) -> Option<Ecn> {
+        self.tos.map(Ecn::from_tos)
+    }
+
+    /// Returns whether the datagram reports a TTL (hop limit) of at least `min`, false if the TTL
+    /// is not reported. With 255 this is the check of the Generalized TTL Security Mechanism
+    /// (RFC 5082) for protocols between neighbors, whose packets cannot be sent from further away.
+    pub fn ttl_at_least(&self, min: u8) -> bool {
+        self.ttl.is_some_and(|ttl| ttl >= min)
+    }
+
+    /// Collects the packet information of the control messages of a datagram.
+    pub(crate) fn from_control(control: &[ControlMessage]) -> PktInfo {
+        let mut info = PktInfo::default();
+        for message in control {
+            let (level, msg_type, data) = match message {
+                ControlMessage::Timestamping(timestamps) => {
+                    if let Some(software) = timestamps.software {
+                        info.timestamp = info.timestamp.or(Some(SystemTime::UNIX_EPOCH + software));
+                    }
+                    continue;
+                },
+                ControlMessage::Other { level, msg_type, data } => (*level, *msg_type, data.as_slice()),
+                _ => continue,
+            };
+            let int = || data.get(..4).map(|int| i32::from_ne_bytes(int.try_into().unwrap()));
+            match (level, msg_type) {
+                // struct in_pktinfo: ipi_ifindex, ipi_spec_dst, ipi_addr
+                (libc::IPPROTO_IP, libc::IP_PKTINFO) if data.len() >= 12 => {
+                    info.if_index = Some(u32::from_ne_bytes(data[..4].try_into().unwrap()));
+                    info.dst_addr = Some(IpAddr::V4(Ipv4Addr::new(data[8], data[9], data[10], data[11])));
+                },
+                // struct in6_pktinfo: ipi6_addr, ipi6_ifindex
+                (libc::IPPROTO_IPV6, libc::IPV6_PKTINFO) if data.len() >= 20 => {
+                    let address: [u8; 16] = data[..16].try_into().unwrap();
+                    info.dst_addr = Some(IpAddr::V6(Ipv6Addr::from(address)));
+                    info.if_index = Some(u32::from_ne_bytes(data[16..20].try_into().unwrap()));
+                },
+                // IP_TTL is an int, IP_TOS a single byte
+                (libc::IPPROTO_IP, libc::IP_TTL) => info.ttl = int().map(|ttl| ttl as u8),
+                (libc::IPPROTO_IP, libc::IP_TOS) => info.tos = data.first().copied(),
+                (libc::IPPROTO_IPV6, libc::IPV6_HOPLIMIT) => info.ttl = int().map(|hops| hops as u8),
+                (libc::IPPROTO_IPV6, libc::IPV6_TCLASS) => info.tos = int().map(|class| class as u8),
+                (libc::SOL_SOCKET, libc::SCM_TIMESTAMPNS) if data.len() >= std::mem::size_of::<libc::timespec>() => {
+                    let ts: libc::timespec = unsafe { std::ptr::read_unaligned(data.as_ptr() as *const _) };
+                    info.timestamp = Some(SystemTime::UNIX_EPOCH + Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32));
+                },
+                _ => (),
+            }
+        }
+        info
+    }
+}
+
+/// Enables or disables the destination address and interface (IP_PKTINFO, IPV6_RECVPKTINFO),
+/// TOS (IP_RECVTOS, IPV6_RECVTCLASS) and receive time (SO_TIMESTAMPNS) of received datagrams.
+/// IPv6 sockets also report them for IPv4 datagrams if they are not IPv6 only.
+pub fn set_pktinfo<S: AsFd>(socket: &S, enable: bool) -> Result<()> {
+    set_int_option(socket.as_fd().as_raw_fd(), libc::SOL_SOCKET, libc::SO_TIMESTAMPNS, enable as libc::c_int)?;
+    set_ip_options(socket, enable, &[libc::IP_PKTINFO, libc::IP_RECVTOS],
+                   &[libc::IPV6_RECVPKTINFO, libc::IPV6_RECVTCLASS])
+}
+
+/// Enables or disables the TTL (IP_RECVTTL) or hop limit (IPV6_RECVHOPLIMIT) of received
+/// datagrams, e.g. to accept only packets sent with TTL 255 by a neighbor (GTSM, RFC 5082), see
+/// `PktInfo::ttl_at_least`. IPv6 sockets also report it for IPv4 datagrams if they are not IPv6
+/// only.
+pub fn set_recv_ttl<S: AsFd>(socket: &S, enable: bool) -> Result<()> {
+    set_ip_options(socket, enable, &[libc::IP_RECVTTL], &[libc::IPV6_RECVHOPLIMIT])
+}
+
+/// Enables or disables the TOS (IP_RECVTOS) or traffic class (IPV6_RECVTCLASS) of received
+/// datagrams only, which carries the ECN codepoint, see `PktInfo::ecn`. `set_pktinfo` enables it
+/// as well.
+pub fn set_recv_tos<S: AsFd>(socket: &S, enable: bool) -> Result<()> {
+    set_ip_options(socket, enable, &[libc::IP_RECVTOS], &[libc::IPV6_RECVTCLASS])
+}
+
+/// Sets the IPv4 and, for IPv6 sockets, the IPv6 options. The IPv4 options fail for IPv6 only
+/// sockets, which is ignored.
+fn set_ip_options<S: AsFd>(socket: &S, enable: bool, ipv4: &[libc::c_int], ipv6: &[libc::c_int]) -> Result<()> {
+    let fd = socket.as_fd().as_raw_fd();
+    let enable = enable as libc::c_int;
+    let is_ipv6 = socket2::SockRef::from(socket).local_addr()?.is_ipv6();
+    if is_ipv6 {
+        for name in ipv6 {
+            set_int_option(fd, libc::IPPROTO_IPV6, *name, enable)?;
+        }
+    }
+    let result = ipv4.iter().try_for_each(|name| set_int_option(fd, libc::IPPROTO_IP, *name, enable));
+    match result {
+        Err(e) if !is_ipv6 => Err(e),
+        _ => Ok(()),
+    }
+}
+
+fn datagram(message: ReceivedMessage) -> Result<(usize, SocketAddr, PktInfo)> {
+    let address = message.address.ok_or_else(|| Error::other("datagram without IP source address"))?;
+    Ok((message.len, address, PktInfo::from_control(&message.control)))
+}
+
+/// Receives a datagram, returns its length, the sender's address and the packet information,
+/// see `set_pktinfo`.
+pub fn recv_with_pktinfo<S: AsFd>(socket: &S, buf: &mut [u8]) -> Result<(usize, SocketAddr, PktInfo)> {
+    datagram(recv_msg(socket.as_fd().as_raw_fd(), buf, 0)?)
+}
+
+/// Receives up to one datagram per buffer with a single system call (recvmmsg), waits for the
+/// first datagram only. Returns length, sender's address and packet information of each
+/// datagram in the order of the buffers.
+pub fn recv_batch_with_pktinfo<S: AsFd>(socket: &S, bufs: &mut [&mut [u8]]) -> Result<Vec<(usize, SocketAddr, PktInfo)>> {
+    recv_mmsg(socket.as_fd().as_raw_fd(), bufs, 0)?.into_iter().map(datagram).collect()
+}
+
+/// Receives a datagram from the tokio socket like `recv_with_pktinfo`.
+/// Requires the feature 'tokio-net'.
+#[cfg(feature = "tokio-net")]
+pub async fn recv_with_pktinfo_async(socket: &tokio::net::UdpSocket, buf: &mut [u8])
+                                     -> Result<(usize, SocketAddr, PktInfo)> {
+    let fd = socket.as_raw_fd();
+    let message = socket.async_io(tokio::io::Interest::READABLE, || recv_msg(fd, buf, libc::MSG_DONTWAIT)).await?;
+    datagram(message)
+}
+
+/// Receives a batch of datagrams from the tokio socket like `recv_batch_with_pktinfo`.
+/// Requires the feature 'tokio-net'.
+#[cfg(feature = "tokio-net")]
+pub async fn recv_batch_with_pktinfo_async(socket: &tokio::net::UdpSocket, bufs: &mut [&mut [u8]])
+                                           -> Result<Vec<(usize, SocketAddr, PktInfo)>> {
+    let fd = socket.as_raw_fd();
+    let messages = socket.async_io(tokio::io::Interest::READABLE, || recv_mmsg(fd, bufs, libc::MSG_DONTWAIT)).await?;
+    messages.into_iter().map(datagram).collect()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_from_control() {
+        let mut in_pktinfo = vec![0_u8; 12];
+        in_pktinfo[..4].copy_from_slice(&3_u32.to_ne_bytes());
+        in_pktinfo[8..].copy_from_slice(&[239, 1, 2, 3]);
+        let control = vec![
+            ControlMessage::Other { level: libc::IPPROTO_IP, msg_type: libc::IP_PKTINFO, data: in_pktinfo },
+            ControlMessage::Other { level: libc::IPPROTO_IP, msg_type: libc::IP_TTL, data: 64_i32.to_ne_bytes().to_vec() },
+            ControlMessage::Other { level: libc::IPPROTO_IP, msg_type: libc::IP_TOS, data: vec![0xb8] },
+            ControlMessage::DropCount(2),
+        ];
+        assert_eq!(PktInfo::from_control(&control), PktInfo {
+            dst_addr: Some("239.1.2.3".parse().unwrap()), if_index: Some(3), ttl: Some(64), tos: Some(0xb8), timestamp: None,
+        });
+
+        let mut in6_pktinfo = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xfb).octets().to_vec();
+        in6_pktinfo.extend_from_slice(&7_u32.to_ne_bytes());
+        let control = vec![
+            ControlMessage::Other { level: libc::IPPROTO_IPV6, msg_type: libc::IPV6_PKTINFO, data: in6_pktinfo },
+            ControlMessage::Other { level: libc::IPPROTO_IPV6, msg_type: libc::IPV6_HOPLIMIT, data: 255_i32.to_ne_bytes().to_vec() },
+            ControlMessage::Other { level: libc::IPPROTO_IPV6, msg_type: libc::IPV6_TCLASS, data: 2_i32.to_ne_bytes().to_vec() },
+        ];
+        let info = PktInfo::from_control(&control);
+        assert_eq!((info.dst_addr, info.if_index), (Some("ff02::fb".parse().unwrap()), Some(7)));
+        assert_eq!((info.ttl, info.tos), (Some(255), Some(2)));
+        assert_eq!(info.ecn(), Some(Ecn::Ect0));
+        assert_eq!(PktInfo::from_control(&[]), PktInfo::default());
+    }
+}
@@ -0,0 +1,310 @@
+//! Interface enumeration without getifaddrs, for chroots and containers where the C library
+//! call is not available or its netlink socket is filtered: the IP interface configurations are
+//! read from /proc/net/if_inet6 (IPv6 addresses), /proc/net/fib_trie and /proc/net/route (IPv4
+//! addresses and their networks), /proc/net/dev (interfaces) and /sys/class/net (index and
+//! flags). `set_interface_backend` selects the backend of `IpInterface::retrieve_ip_interfaces`
+//! at runtime. IPv4 addresses are assigned to the interface of the directly connected route of
+//! their network (loopback addresses to the loopback interface), addresses without such a route
+//! and point-to-point destinations are not reported.
+//!
+//! ```no_run
+//! use net_utils::proc_interfaces::{InterfaceBackend, set_interface_backend};
+//! set_interface_backend(InterfaceBackend::Fallback);
+//! let interfaces = net_utils::IpInterface::retrieve_ip_interfaces().unwrap();
+//! ```
+
+use std::{
+    collections::HashMap,
+    io::Result,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6},
+    path::{Path, PathBuf},
+    sync::{Arc, atomic::{AtomicU8, Ordering}},
+};
+
+use super::{InterfaceProvider, IpInterface};
+
+/// Scope of link-local addresses in /proc/net/if_inet6 (IPV6_ADDR_LINKLOCAL).
+const SCOPE_LINK: u32 = 0x20;
+
+/// Route flag of routes via a gateway (RTF_GATEWAY).
+const ROUTE_GATEWAY: u32 = 0x2;
+
+/// Source of the interface configurations of `IpInterface::retrieve_ip_interfaces`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum InterfaceBackend {
+    /// getifaddrs of the C library
+    #[default]
+    Getifaddrs,
+    /// /proc and /sys, see `proc_ip_interfaces`
+    Proc,
+    /// getifaddrs, /proc and /sys if it fails
+    Fallback,
+}
+
+static BACKEND: AtomicU8 = AtomicU8::new(0);
+
+/// Selects the backend of `IpInterface::retrieve_ip_interfaces` (and thereby the interface
+/// lookups of the crate) for the process.
+pub fn set_interface_backend(backend: InterfaceBackend) {
+    BACKEND.store(backend as u8, Ordering::Relaxed);
+}
+
+/// Returns the backend of `IpInterface::retrieve_ip_interfaces`.
+pub fn interface_backend() -> InterfaceBackend {
+    match BACKEND.load(Ordering::Relaxed) {
+        1 => InterfaceBackend::Proc,
+        2 => InterfaceBackend::Fallback,
+        _ => InterfaceBackend::Getifaddrs,
+    }
+}
+
+/// Returns the IPv4 and IPv6 interface configurations of the host from /proc and /sys.
+pub fn proc_ip_interfaces() -> Result<Vec<IpInterface>> {
+    read_interfaces(Path::new("/"))
+}
+
+/// InterfaceProvider reading the interface configurations from /proc and /sys on every call,
+/// optionally below another root directory, e.g. where the proc and sys file systems of a
+/// chroot are mounted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProcInterfaceProvider {
+    root: PathBuf,
+}
+
+impl Default for ProcInterfaceProvider {
+    fn default() -> Self {
+        ProcInterfaceProvider::new()
+    }
+}
+
+impl ProcInterfaceProvider {
+
+    /// Creates a provider reading /proc and /sys.
+    pub fn new() -> ProcInterfaceProvider {
+        ProcInterfaceProvider::with_root(Path::new("/"))
+    }
+
+    /// Creates a provider reading proc and sys below the root directory.
+    pub fn with_root(root: &Path) -> ProcInterfaceProvider {
+        ProcInterfaceProvider { root: root.to_path_buf() }
+    }
+}
+
+impl InterfaceProvider for ProcInterfaceProvider {
+    fn ip_interfaces(&self) -> Result<Arc<Vec<IpInterface>>> {
+        Ok(Arc::new(read_interfaces(&self.root)?))
+    }
+}
+
+/// Index and flags of an interface.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Link {
+    index: u32,
+    flags: libc::c_uint,
+}
+
+/// A directly connected IPv4 route of /proc/net/route.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Route {
+    interface: String,
+    destination: Ipv4Addr,
+    mask: Ipv4Addr,
+}
+
+/// An entry of /proc/net/if_inet6.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Inet6Address {
+    address: Ipv6Addr,
+    index: u32,
+    prefix_len: u32,
+    scope: u32,
+    name: String,
+}
+
+fn read_interfaces(root: &Path) -> Result<Vec<IpInterface>> {
+    let read = |path: &str| std::fs::read_to_string(root.join(path));
+    let names = parse_dev(&read("proc/net/dev")?);
+    let links: HashMap<&str, Link> = names.iter().map(|name| (name.as_str(), read_link(root, name))).collect();
+    // interfaces created after reading /proc/net/dev are read on demand
+    let link = |name: &str| links.get(name).copied().unwrap_or_else(|| read_link(root, name));
+
+    let mut interfaces = Vec::new();
+    // without IPv4 /proc/net/route and fib_trie do not exist
+    if let (Ok(trie), Ok(routes)) = (read("proc/net/fib_trie"), read("proc/net/route")) {
+        let routes = parse_route(&routes);
+        for address in parse_fib_trie(&trie) {
+            let (name, mask) = match interface_of(address, &routes, &names, &links) {
+                Some(found) => found,
+                None => continue,
+            };
+            let Link { index, flags } = link(name);
+            let mask_bits = u32::from(mask);
+            let broadcast_address = Some(SocketAddr::new(IpAddr::V4(Ipv4Addr::from(u32::from(address) | !mask_bits)), 0))
+                .filter(|_| flags & libc::IFF_BROADCAST as libc::c_uint != 0 && mask_bits != u32::MAX);
+            interfaces.push(IpInterface {
+                index, name: name.to_string(), flags, address: SocketAddr::new(IpAddr::V4(address), 0),
+                net_mask: SocketAddr::new(IpAddr::V4(mask), 0), broadcast_address, p2p_address: None,
+            });
+        }
+    }
+    // without IPv6 /proc/net/if_inet6 does not exist
+    if let Ok(inet6) = read("proc/net/if_inet6") {
+        for entry in parse_if_inet6(&inet6) {
+            let flags = link(&entry.name).flags;
+            let scope_id = if entry.scope == SCOPE_LINK { entry.index } else { 0 };
+            let mask = u128::MAX.checked_shl(128 - entry.prefix_len.min(128)).unwrap_or(0);
+            interfaces.push(IpInterface {
+                index: entry.index, name: entry.name, flags,
+                address: SocketAddr::V6(SocketAddrV6::new(entry.address, 0, 0, scope_id)),
+                net_mask: SocketAddr::new(IpAddr::V6(Ipv6Addr::from(mask)), 0), broadcast_address: None, p2p_address: None,
+            });
+        }
+    }
+    Ok(interfaces)
+}
+
+/// Reads index and flags of the interface from sysfs, IFF_RUNNING and IFF_LOWER_UP are set
+/// with carrier. Missing files (e.g. without sysfs) read as zero.
+fn read_link(root: &Path, name: &str) -> Link {
+    let read = |file: &str| std::fs::read_to_string(root.join("sys/class/net").join(name).join(file)).unwrap_or_default();
+    let index = read("ifindex").trim().parse().unwrap_or(0);
+    let mut flags = libc::c_uint::from_str_radix(read("flags").trim().trim_start_matches("0x"), 16).unwrap_or(0);
+    if read("carrier").trim() == "1" {
+        flags |= (libc::IFF_RUNNING | libc::IFF_LOWER_UP) as libc::c_uint;
+    }
+    Link { index, flags }
+}
+
+/// Returns the interface and net mask of a local IPv4 address, the one of the most specific
+/// directly connected route of its network.
+fn interface_of<'a>(address: Ipv4Addr, routes: &'a [Route], names: &'a [String], links: &HashMap<&str, Link>)
+    -> Option<(&'a str, Ipv4Addr)> {
+    if address.is_loopback() {
+        let loopback = names.iter().find(|name| links.get(name.as_str())
+            .is_some_and(|link| link.flags & libc::IFF_LOOPBACK as libc::c_uint != 0))?;
+        return Some((loopback.as_str(), Ipv4Addr::new(255, 0, 0, 0)));
+    }
+    routes.iter()
+        .filter(|route| u32::from(address) & u32::from(route.mask) == u32::from(route.destination))
+        .max_by_key(|route| u32::from(route.mask).count_ones())
+        .map(|route| (route.interface.as_str(), route.mask))
+}
+
+/// Returns the interface names of /proc/net/dev.
+fn parse_dev(dev: &str) -> Vec<String> {
+    dev.lines().skip(2).filter_map(|line| Some(line.split_once(':')?.0.trim().to_string())).collect()
+}
+
+/// Returns the local addresses (/32 host LOCAL leaves) of /proc/net/fib_trie.
+fn parse_fib_trie(trie: &str) -> Vec<Ipv4Addr> {
+    let mut addresses = Vec::new();
+    let mut leaf = None;
+    for line in trie.lines().map(str::trim) {
+        if let Some(address) = line.strip_prefix("|-- ") {
+            leaf = address.parse::<Ipv4Addr>().ok();
+        } else if line == "/32 host LOCAL" {
+            if let Some(address) = leaf.filter(|address| !addresses.contains(address)) {
+                addresses.push(address);
+            }
+        }
+    }
+    addresses
+}
+
+/// Returns the directly connected routes of /proc/net/route, whose addresses are printed as
+/// hexadecimal numbers in host byte order.
+fn parse_route(routes: &str) -> Vec<Route> {
+    let address = |hex: &str| u32::from_str_radix(hex, 16).ok().map(|value| Ipv4Addr::from(value.to_ne_bytes()));
+    routes.lines().skip(1).filter_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let flags = u32::from_str_radix(fields.get(3)?, 16).ok()?;
+        Some(Route { interface: fields[0].to_string(), destination: address(fields[1])?, mask: address(fields.get(7)?)? })
+            .filter(|_| flags & ROUTE_GATEWAY == 0)
+    }).collect()
+}
+
+/// Returns the entries of /proc/net/if_inet6.
+fn parse_if_inet6(inet6: &str) -> Vec<Inet6Address> {
+    inet6.lines().filter_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let hex = |index: usize| fields.get(index).and_then(|field| u32::from_str_radix(field, 16).ok());
+        Some(Inet6Address {
+            address: Ipv6Addr::from(u128::from_str_radix(fields.first()?, 16).ok()?),
+            index: hex(1)?,
+            prefix_len: hex(2)?,
+            scope: hex(3)?,
+            name: fields.get(5)?.to_string(),
+        })
+    }).collect()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    const DEV: &str = "Inter-|   Receive    |  Transmit\n face |bytes    packets|bytes    packets\n    lo: 1 2 3\n  eth0: 4 5 6\n";
+    const ROUTE: &str = "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+                         eth0\t00000000\t010200C0\t0003\t0\t0\t0\t00000000\t0\t0\t0\n\
+                         eth0\t000200C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\t0\t0\t0\n";
+    const FIB_TRIE: &str = "Main:\n  +-- 0.0.0.0/0 3 0 5\n     |-- 0.0.0.0\n        /0 universe UNICAST\n\
+                            \x20          |-- 127.0.0.0\n              /8 host LOCAL\n\
+                            \x20          |-- 127.0.0.1\n              /32 host LOCAL\n\
+                            \x20          |-- 192.0.2.2\n              /32 host LOCAL\n\
+                            \x20       |-- 192.0.2.255\n           /32 link BROADCAST\n\
+                            Local:\n           |-- 192.0.2.2\n              /32 host LOCAL\n\
+                            \x20          |-- 198.51.100.9\n              /32 host LOCAL\n";
+    const IF_INET6: &str = "00000000000000000000000000000001 01 80 10 80       lo\n\
+                            fe8000000000000000fc00fffe000001 04 40 20 80     eth0\n";
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse_dev(DEV), vec!["lo", "eth0"]);
+        assert_eq!(parse_route(ROUTE), vec![Route {
+            interface: "eth0".to_string(), destination: Ipv4Addr::new(192, 0, 2, 0), mask: Ipv4Addr::new(255, 255, 255, 0),
+        }]);
+        assert_eq!(parse_fib_trie(FIB_TRIE), vec![Ipv4Addr::LOCALHOST, Ipv4Addr::new(192, 0, 2, 2), Ipv4Addr::new(198, 51, 100, 9)]);
+        let entries = parse_if_inet6(IF_INET6);
+        assert_eq!(entries[1], Inet6Address {
+            address: "fe80::fc:ff:fe00:1".parse().unwrap(), index: 4, prefix_len: 64, scope: SCOPE_LINK, name: "eth0".to_string(),
+        });
+    }
+
+    #[test]
+    fn test_read_interfaces() {
+        let root = std::env::temp_dir().join(format!("net-utils-proc-interfaces-{}", std::process::id()));
+        // veth9 was created after /proc/net/dev was read
+        let if_inet6 = format!("{}fe8000000000000000fc00fffe000009 09 40 20 80    veth9\n", IF_INET6);
+        for (path, content) in [
+            ("proc/net/dev", DEV), ("proc/net/route", ROUTE), ("proc/net/fib_trie", FIB_TRIE), ("proc/net/if_inet6", &if_inet6),
+            ("sys/class/net/lo/ifindex", "1\n"), ("sys/class/net/lo/flags", "0x9\n"), ("sys/class/net/lo/carrier", "1\n"),
+            ("sys/class/net/eth0/ifindex", "4\n"), ("sys/class/net/eth0/flags", "0x1003\n"),
+            ("sys/class/net/veth9/flags", "0x1003\n"),
+        ] {
+            std::fs::create_dir_all(root.join(path).parent().unwrap()).unwrap();
+            std::fs::write(root.join(path), content).unwrap();
+        }
+        let interfaces = ProcInterfaceProvider::with_root(&root).ip_interfaces().unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        // 198.51.100.9 has no route
+        assert_eq!(interfaces.len(), 5);
+        assert_eq!((interfaces[0].name.as_str(), interfaces[0].net_mask.ip()), ("lo", "255.0.0.0".parse().unwrap()));
+        assert!(interfaces[0].is_loopback() && interfaces[0].is_l1_up());
+        let eth0 = &interfaces[1];
+        assert_eq!((eth0.index, eth0.address.ip()), (4, "192.0.2.2".parse().unwrap()));
+        assert_eq!(eth0.broadcast_address, Some("192.0.2.255:0".parse().unwrap()));
+        assert!(eth0.supports_multicast() && !eth0.is_l1_up());
+        assert_eq!(interfaces[3].address, SocketAddr::V6(SocketAddrV6::new("fe80::fc:ff:fe00:1".parse().unwrap(), 0, 0, 4)));
+        assert_eq!(interfaces[3].net_mask.ip(), "ffff:ffff:ffff:ffff::".parse::<IpAddr>().unwrap());
+        assert_eq!((interfaces[4].index, interfaces[4].name.as_str()), (9, "veth9"));
+        assert!(interfaces[4].supports_multicast());
+    }
+
+    #[test]
+    fn test_backend() {
+        assert_eq!(interface_backend(), InterfaceBackend::Getifaddrs);
+        let interfaces = proc_ip_interfaces().unwrap();
+        assert!(interfaces.iter().any(|interface| interface.address.ip() == IpAddr::V4(Ipv4Addr::LOCALHOST)));
+    }
+}
@@ -0,0 +1,160 @@
+//! PTP (IEEE 1588) event and general message sockets for PTP over UDP with packet timestamping.
+
+use std::{
+    io::{Error, Result},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+};
+
+use socket2::{Domain, InterfaceIndexOrAddress, Protocol, SockAddr, Type};
+
+use super::timestamping::{self, HardwareTimestampingConfig, Timestamps, HARDWARE_TIMESTAMPING,
+                          HWTSTAMP_FILTER_PTP_V2_EVENT, HWTSTAMP_TX_ON, SOFTWARE_TIMESTAMPING};
+
+/// UDP port of PTP event messages (Sync, Delay_Req, Pdelay_Req, Pdelay_Resp).
+pub const PTP_EVENT_PORT: u16 = 319;
+/// UDP port of PTP general messages (Announce, Follow_Up, Delay_Resp, ...).
+pub const PTP_GENERAL_PORT: u16 = 320;
+
+/// IPv4 multicast group of all PTP messages except peer delay messages.
+pub const PTP_PRIMARY_MULTICAST_V4: Ipv4Addr = Ipv4Addr::new(224, 0, 1, 129);
+/// IPv4 multicast group of peer delay messages.
+pub const PTP_PDELAY_MULTICAST_V4: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 107);
+/// IPv6 multicast group (global scope) of all PTP messages except peer delay messages.
+pub const PTP_PRIMARY_MULTICAST_V6: Ipv6Addr = Ipv6Addr::new(0xff0e, 0, 0, 0, 0, 0, 0, 0x181);
+/// IPv6 multicast group (link-local scope) of peer delay messages.
+pub const PTP_PDELAY_MULTICAST_V6: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0x6b);
+
+/// Network protocol used to transport PTP messages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PtpTransport {
+    /// UDP over IPv4 (IEEE 1588 annex C)
+    Ipv4,
+
+    /// UDP over IPv6 (IEEE 1588 annex D)
+    Ipv6,
+}
+
+/// Source of the timestamps of PTP event messages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TimestampSource {
+    /// timestamps of the network adapter's PTP hardware clock, requires CAP_NET_ADMIN and a
+    /// capable adapter
+    Hardware,
+
+    /// timestamps generated by the kernel
+    Software,
+}
+
+/// The event and general message sockets of a PTP port on one interface. Both sockets are bound to
+/// the interface and joined to the primary and peer delay multicast groups of the transport.
+#[derive(Debug)]
+pub struct PtpSockets {
+    event: UdpSocket,
+    general: UdpSocket,
+    transport: PtpTransport,
+    interface_index: u32,
+}
+
+impl PtpSockets {
+
+    /// Opens the event (port 319) and general (port 320) sockets on the interface. For hardware
+    /// timestamps the interface is configured to timestamp PTP v2 event messages first.
+    /// Binding to the PTP ports requires CAP_NET_BIND_SERVICE.
+    pub fn open(interface_name: &str, transport: PtpTransport, source: TimestampSource) -> Result<PtpSockets> {
+        let interface_index = interface_index(interface_name)?;
+        let event = open_socket(interface_name, interface_index, transport, PTP_EVENT_PORT)?;
+        let general = open_socket(interface_name, interface_index, transport, PTP_GENERAL_PORT)?;
+        match source {
+            TimestampSource::Hardware => {
+                let config = HardwareTimestampingConfig { tx_type: HWTSTAMP_TX_ON,
+                    rx_filter: HWTSTAMP_FILTER_PTP_V2_EVENT };
+                timestamping::enable_hardware_timestamping(interface_name, &config)?;
+                timestamping::set_timestamping(&event, HARDWARE_TIMESTAMPING)?;
+            },
+            TimestampSource::Software => timestamping::set_timestamping(&event, SOFTWARE_TIMESTAMPING)?,
+        }
+        Ok(PtpSockets { event, general, transport, interface_index })
+    }
+
+    /// Returns the socket for event messages.
+    pub fn event_socket(&self) -> &UdpSocket {
+        &self.event
+    }
+
+    /// Returns the socket for general messages.
+    pub fn general_socket(&self) -> &UdpSocket {
+        &self.general
+    }
+
+    /// Returns the index of the interface the sockets are bound to.
+    pub fn interface_index(&self) -> u32 {
+        self.interface_index
+    }
+
+    /// Receives an event message, returns its length, the sender's address and its timestamps.
+    pub fn recv_event(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr, Timestamps)> {
+        timestamping::recv_with_timestamps(&self.event, buf)
+    }
+
+    /// Receives a general message, returns its length and the sender's address.
+    pub fn recv_general(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr)> {
+        self.general.recv_from(buf)
+    }
+
+    /// Sends an event message to the primary or, for peer delay messages, the peer delay
+    /// multicast group.
+    pub fn send_event(&self, buf: &[u8], peer_delay: bool) -> Result<usize> {
+        self.event.send_to(buf, self.group_address(peer_delay, PTP_EVENT_PORT))
+    }
+
+    /// Sends a general message to the primary or, for peer delay messages, the peer delay
+    /// multicast group.
+    pub fn send_general(&self, buf: &[u8], peer_delay: bool) -> Result<usize> {
+        self.general.send_to(buf, self.group_address(peer_delay, PTP_GENERAL_PORT))
+    }
+
+    fn group_address(&self, peer_delay: bool, port: u16) -> SocketAddr {
+        match (self.transport, peer_delay) {
+            (PtpTransport::Ipv4, false) => SocketAddr::new(PTP_PRIMARY_MULTICAST_V4.into(), port),
+            (PtpTransport::Ipv4, true) => SocketAddr::new(PTP_PDELAY_MULTICAST_V4.into(), port),
+            (PtpTransport::Ipv6, false) => SocketAddr::new(PTP_PRIMARY_MULTICAST_V6.into(), port),
+            (PtpTransport::Ipv6, true) => SocketAddr::new(PTP_PDELAY_MULTICAST_V6.into(), port),
+        }
+    }
+}
+
+fn open_socket(interface_name: &str, index: u32, transport: PtpTransport, port: u16) -> Result<UdpSocket> {
+    let domain = match transport {
+        PtpTransport::Ipv4 => Domain::IPV4,
+        PtpTransport::Ipv6 => Domain::IPV6,
+    };
+    let socket = super::sys_socket::socket(domain, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.bind_device(Some(interface_name.as_bytes()))?;
+    match transport {
+        PtpTransport::Ipv4 => {
+            super::sys_socket::bind(&socket, &SockAddr::from(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port)))?;
+            for group in [PTP_PRIMARY_MULTICAST_V4, PTP_PDELAY_MULTICAST_V4].iter() {
+                socket.join_multicast_v4_n(group, &InterfaceIndexOrAddress::Index(index))?;
+            }
+        },
+        PtpTransport::Ipv6 => {
+            socket.set_only_v6(true)?;
+            super::sys_socket::bind(&socket, &SockAddr::from(SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), port)))?;
+            for group in [PTP_PRIMARY_MULTICAST_V6, PTP_PDELAY_MULTICAST_V6].iter() {
+                socket.join_multicast_v6(group, index)?;
+            }
+            socket.set_multicast_if_v6(index)?;
+        },
+    }
+    Ok(socket.into())
+}
+
+fn interface_index(interface_name: &str) -> Result<u32> {
+    let name = std::ffi::CString::new(interface_name)
+        .map_err(|e| Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+        0 => Err(Error::last_os_error()),
+        index => Ok(index),
+    }
+}
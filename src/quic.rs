@@ -0,0 +1,363 @@
+//! UDP sockets prepared the way QUIC stacks expect them: ECN and packet information of received
+//! datagrams, segmentation offload (UDP_SEGMENT) and receive offload (UDP_GRO), large buffers and
+//! the don't fragment bit for path MTU probing. `create_quic_socket` creates an optionally
+//! interface bound socket, `send_transmit` and `recv_meta` are the control message based send
+//! and receive hooks of an endpoint's socket abstraction (e.g. an `AsyncUdpSocket` of quinn).
+
+use std::{
+    convert::{TryFrom, TryInto},
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, SocketAddr, UdpSocket},
+    os::unix::io::{AsFd, AsRawFd, RawFd},
+};
+
+use socket2::{Domain, Protocol, SockAddr, SockRef, Type};
+
+use super::{
+    cmsg::{ControlMessage, ReceivedMessage, recv_msg, send_msg},
+    device::bind_to_device,
+    pktinfo::{Ecn, PktInfo, set_pktinfo},
+    sockopt::{get_option, set_int_option},
+    stats::{SocketStats, StatsCounter},
+    udp::tos_control,
+};
+
+/// Maximum number of segments of one segmentation offload send (UDP_MAX_SEGMENTS).
+pub const MAX_GSO_SEGMENTS: usize = 64;
+
+/// Options of `create_quic_socket` and `prepare_quic_socket`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QuicSocketOpts {
+    /// requested receive buffer size (SO_RCVBUF), the kernel limits it to net.core.rmem_max
+    pub recv_buffer_size: usize,
+
+    /// requested send buffer size (SO_SNDBUF), the kernel limits it to net.core.wmem_max
+    pub send_buffer_size: usize,
+
+    /// whether to enable receive offload (UDP_GRO), receive buffers must then hold 64 KiB
+    pub gro: bool,
+}
+
+impl Default for QuicSocketOpts {
+    fn default() -> Self {
+        QuicSocketOpts { recv_buffer_size: 2 << 20, send_buffer_size: 2 << 20, gro: true }
+    }
+}
+
+/// Offloads and buffer sizes of a prepared socket.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct QuicCapabilities {
+    /// maximum number of segments of a `Transmit`, 1 if the kernel does not support UDP_SEGMENT
+    pub max_gso_segments: usize,
+
+    /// maximum number of segments the kernel coalesces to a received datagram, 1 without UDP_GRO
+    pub gro_segments: usize,
+
+    /// receive buffer size granted by the kernel
+    pub recv_buffer_size: usize,
+
+    /// send buffer size granted by the kernel
+    pub send_buffer_size: usize,
+}
+
+/// A datagram or, with `segment_size`, a batch of equally sized datagrams to send.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Transmit<'a> {
+    /// address of the receiver
+    pub destination: SocketAddr,
+
+    /// payload, split into datagrams of `segment_size` bytes, the last one may be shorter
+    pub contents: &'a [u8],
+
+    /// ECN codepoint of the datagrams, None sends the TOS / traffic class of the socket
+    pub ecn: Option<Ecn>,
+
+    /// size of the datagrams for segmentation offload (UDP_SEGMENT), None sends one datagram
+    pub segment_size: Option<usize>,
+
+    /// source address of the datagrams (IP_PKTINFO, IPV6_PKTINFO), e.g. the destination address
+    /// of the datagram answered by a server bound to the unspecified address
+    pub src_ip: Option<IpAddr>,
+}
+
+impl<'a> Transmit<'a> {
+
+    /// Returns a transmit of a single datagram without ECN codepoint and source address.
+    pub fn new(destination: SocketAddr, contents: &'a [u8]) -> Transmit<'a> {
+        Transmit { destination, contents, ecn: None, segment_size: None, src_ip: None }
+    }
+}
+
+/// A datagram or, with receive offload, a batch of coalesced datagrams received by `recv_meta`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RecvMeta {
+    /// number of received bytes
+    pub len: usize,
+
+    /// address of the sender
+    pub source: SocketAddr,
+
+    /// size of the coalesced datagrams, the last one may be shorter, equals `len` for a single
+    /// datagram
+    pub stride: usize,
+
+    /// destination address, interface, TOS with the ECN bits and receive time
+    pub info: PktInfo,
+}
+
+impl RecvMeta {
+
+    /// Returns the ECN codepoint of the datagrams, None if it is not reported.
+    pub fn ecn(&self) -> Option<Ecn> {
+        self.info.ecn()
+    }
+
+    /// Returns the number of coalesced datagrams.
+    pub fn segments(&self) -> usize {
+        if self.stride == 0 { 0 } else { self.len.div_ceil(self.stride) }
+    }
+}
+
+/// Prepares a UDP socket for QUIC: sets the buffer sizes, enables packet information and TOS /
+/// traffic class reporting (see `pktinfo::set_pktinfo`), sets the don't fragment bit without
+/// using the path MTU of the kernel (IP_PMTUDISC_PROBE), as QUIC probes the path MTU itself, and
+/// detects the segmentation offloads. IPv6 sockets do this for IPv4 datagrams as well unless
+/// they are IPv6 only.
+pub fn prepare_quic_socket<S: AsFd>(socket: &S, opts: &QuicSocketOpts) -> Result<QuicCapabilities> {
+    let fd = socket.as_fd().as_raw_fd();
+    let sock = SockRef::from(socket);
+    sock.set_recv_buffer_size(opts.recv_buffer_size)?;
+    sock.set_send_buffer_size(opts.send_buffer_size)?;
+    set_pktinfo(socket, true)?;
+    let is_ipv6 = sock.local_addr()?.is_ipv6();
+    if is_ipv6 {
+        set_int_option(fd, libc::IPPROTO_IPV6, libc::IPV6_MTU_DISCOVER, libc::IPV6_PMTUDISC_PROBE)?;
+    }
+    match set_int_option(fd, libc::IPPROTO_IP, libc::IP_MTU_DISCOVER, libc::IP_PMTUDISC_PROBE) {
+        Err(e) if !is_ipv6 => return Err(e),
+        _ => (),
+    }
+    let gso = get_option::<libc::c_int>(fd, libc::IPPROTO_UDP, libc::UDP_SEGMENT).is_ok();
+    let gro = opts.gro && set_int_option(fd, libc::IPPROTO_UDP, libc::UDP_GRO, 1).is_ok();
+    Ok(QuicCapabilities {
+        max_gso_segments: if gso { MAX_GSO_SEGMENTS } else { 1 },
+        gro_segments: if gro { MAX_GSO_SEGMENTS } else { 1 },
+        recv_buffer_size: sock.recv_buffer_size()?,
+        send_buffer_size: sock.send_buffer_size()?,
+    })
+}
+
+/// A UDP socket prepared by `prepare_quic_socket`.
+#[derive(Debug)]
+pub struct QuicSocket {
+    socket: UdpSocket,
+    capabilities: QuicCapabilities,
+    stats: StatsCounter,
+}
+
+/// Creates a UDP socket bound to the address and optionally to the network device with the
+/// given name, prepared for QUIC by `prepare_quic_socket`. A socket bound to the unspecified
+/// IPv6 address also sends and receives IPv4 datagrams (to and from IPv4 mapped addresses).
+pub fn create_quic_socket(address: SocketAddr, interface: Option<&str>, opts: &QuicSocketOpts) -> Result<QuicSocket> {
+    let socket = super::sys_socket::socket(Domain::for_address(address), Type::DGRAM, Some(Protocol::UDP))?;
+    if let SocketAddr::V6(v6) = address {
+        if v6.ip().is_unspecified() {
+            socket.set_only_v6(false)?;
+        }
+    }
+    if let Some(interface) = interface {
+        bind_to_device(&socket, interface, &address)?;
+    }
+    super::sys_socket::bind(&socket, &SockAddr::from(address))?;
+    let capabilities = prepare_quic_socket(&socket, opts)?;
+    trace_event!(crate::trace::TraceEvent::SocketCreated {
+        kind: "udp-quic", address: socket.local_addr().ok().and_then(|address| address.as_socket()),
+    });
+    Ok(QuicSocket { socket: socket.into(), capabilities, stats: StatsCounter::new() })
+}
+
+impl QuicSocket {
+
+    /// Returns the underlying socket.
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// Returns the underlying socket, e.g. to hand it to a QUIC endpoint, the options stay
+    /// enabled.
+    pub fn into_inner(self) -> UdpSocket {
+        self.socket
+    }
+
+    /// Returns the offloads and buffer sizes of the socket.
+    pub fn capabilities(&self) -> QuicCapabilities {
+        self.capabilities
+    }
+
+    /// Returns the traffic statistics of the socket, which count the segments of offloaded
+    /// sends and receives as datagrams.
+    pub fn stats(&self) -> SocketStats {
+        self.stats.snapshot()
+    }
+
+    /// Sends the transmit, see `send_transmit`. Fails with InvalidInput if it has more segments
+    /// than the socket supports.
+    pub fn send(&self, transmit: &Transmit) -> Result<usize> {
+        let segment_size = transmit.segment_size.unwrap_or(transmit.contents.len()).max(1);
+        if transmit.contents.len().div_ceil(segment_size) > self.capabilities.max_gso_segments {
+            return Err(Error::new(ErrorKind::InvalidInput, "too many segments"));
+        }
+        let len = send_transmit(&self.socket, transmit)?;
+        transmit.contents[..len].chunks(segment_size).for_each(|segment| self.stats.sent(segment.len()));
+        Ok(len)
+    }
+
+    /// Receives a datagram or a batch of coalesced datagrams, see `recv_meta`.
+    pub fn recv(&self, buf: &mut [u8]) -> Result<RecvMeta> {
+        let meta = recv_meta(&self.socket, buf)?;
+        buf[..meta.len].chunks(meta.stride.max(1)).for_each(|segment| self.stats.received(segment.len()));
+        Ok(meta)
+    }
+}
+
+impl AsRawFd for QuicSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+}
+
+/// Sends the transmit with its ECN codepoint (IP_TOS, IPV6_TCLASS), segment size (UDP_SEGMENT)
+/// and source address (IP_PKTINFO, IPV6_PKTINFO) control messages. IPv4 datagrams of IPv6
+/// sockets use the IPv4 control messages. A segmented send fails with EIO if the outgoing
+/// device does not support checksum offload, the transmit can then be sent segment by segment.
+pub fn send_transmit<S: AsFd>(socket: &S, transmit: &Transmit) -> Result<usize> {
+    send_transmit_fd(socket.as_fd().as_raw_fd(), transmit, 0)
+}
+
+/// Sends the transmit from the tokio socket like `send_transmit`.
+/// Requires the feature 'tokio-net'.
+#[cfg(feature = "tokio-net")]
+pub async fn send_transmit_async(socket: &tokio::net::UdpSocket, transmit: &Transmit<'_>) -> Result<usize> {
+    let fd = socket.as_raw_fd();
+    socket.async_io(tokio::io::Interest::WRITABLE, || send_transmit_fd(fd, transmit, libc::MSG_DONTWAIT)).await
+}
+
+fn send_transmit_fd(fd: RawFd, transmit: &Transmit, flags: libc::c_int) -> Result<usize> {
+    let ipv4 = match transmit.destination {
+        SocketAddr::V4(_) => true,
+        SocketAddr::V6(v6) => v6.ip().to_ipv4_mapped().is_some(),
+    };
+    let ecn = transmit.ecn.map(|ecn| (ecn as libc::c_int).to_ne_bytes());
+    let segment_size = match transmit.segment_size {
+        Some(size) => Some(u16::try_from(size).map_err(|_| Error::new(ErrorKind::InvalidInput, "segment size too large"))?
+            .to_ne_bytes()),
+        None => None,
+    };
+    let src_ip = transmit.src_ip.map(|src_ip| pktinfo_control(src_ip, ipv4));
+
+    let mut control: Vec<(libc::c_int, libc::c_int, &[u8])> = Vec::new();
+    if let Some(ecn) = &ecn {
+        control.push(tos_control(&transmit.destination, ecn));
+    }
+    if let Some(segment_size) = &segment_size {
+        control.push((libc::IPPROTO_UDP, libc::UDP_SEGMENT, segment_size));
+    }
+    if let Some((level, msg_type, data)) = &src_ip {
+        control.push((*level, *msg_type, data));
+    }
+    send_msg(fd, transmit.contents, Some(&transmit.destination), &control, flags)
+}
+
+/// Returns the packet information control message which sets the source address.
+fn pktinfo_control(src_ip: IpAddr, ipv4: bool) -> (libc::c_int, libc::c_int, Vec<u8>) {
+    let src_v4 = match src_ip {
+        IpAddr::V4(v4) => Some(v4),
+        IpAddr::V6(v6) => v6.to_ipv4_mapped(),
+    };
+    match src_v4 {
+        // struct in_pktinfo: ipi_ifindex, ipi_spec_dst, ipi_addr
+        Some(v4) if ipv4 => {
+            let mut data = vec![0_u8; 12];
+            data[4..8].copy_from_slice(&v4.octets());
+            (libc::IPPROTO_IP, libc::IP_PKTINFO, data)
+        },
+        // struct in6_pktinfo: ipi6_addr, ipi6_ifindex
+        _ => {
+            let v6 = match src_ip {
+                IpAddr::V4(v4) => v4.to_ipv6_mapped(),
+                IpAddr::V6(v6) => v6,
+            };
+            let mut data = v6.octets().to_vec();
+            data.extend_from_slice(&0_u32.to_ne_bytes());
+            (libc::IPPROTO_IPV6, libc::IPV6_PKTINFO, data)
+        },
+    }
+}
+
+/// Receives a datagram or, with UDP_GRO enabled, a batch of datagrams the kernel coalesced, and
+/// returns its length, the sender's address, the size of the coalesced datagrams and the packet
+/// information with the ECN bits. Coalesced datagrams which do not fit the buffer are truncated.
+pub fn recv_meta<S: AsFd>(socket: &S, buf: &mut [u8]) -> Result<RecvMeta> {
+    meta(recv_msg(socket.as_fd().as_raw_fd(), buf, 0)?)
+}
+
+/// Receives a datagram from the tokio socket like `recv_meta`.
+/// Requires the feature 'tokio-net'.
+#[cfg(feature = "tokio-net")]
+pub async fn recv_meta_async(socket: &tokio::net::UdpSocket, buf: &mut [u8]) -> Result<RecvMeta> {
+    let fd = socket.as_raw_fd();
+    let message = socket.async_io(tokio::io::Interest::READABLE, || recv_msg(fd, buf, libc::MSG_DONTWAIT)).await?;
+    meta(message)
+}
+
+fn meta(message: ReceivedMessage) -> Result<RecvMeta> {
+    let source = message.address.ok_or_else(|| Error::other("datagram without IP source address"))?;
+    Ok(RecvMeta {
+        len: message.len,
+        source,
+        stride: gro_segment_size(&message.control).unwrap_or(message.len),
+        info: PktInfo::from_control(&message.control),
+    })
+}
+
+/// Returns the segment size of coalesced datagrams (UDP_GRO control message).
+fn gro_segment_size(control: &[ControlMessage]) -> Option<usize> {
+    control.iter().find_map(|control| match control {
+        ControlMessage::Other { level: libc::IPPROTO_UDP, msg_type: libc::UDP_GRO, data } if data.len() >= 4 => {
+            Some(i32::from_ne_bytes(data[..4].try_into().unwrap()) as usize)
+        },
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_meta() {
+        let control = vec![
+            ControlMessage::Other { level: libc::IPPROTO_UDP, msg_type: libc::UDP_GRO, data: 1200_i32.to_ne_bytes().to_vec() },
+            ControlMessage::Other { level: libc::IPPROTO_IP, msg_type: libc::IP_TOS, data: vec![0x03] },
+        ];
+        let message = ReceivedMessage { len: 3000, address: Some("192.0.2.1:443".parse().unwrap()), flags: 0, control };
+        let received = meta(message).unwrap();
+        assert_eq!((received.stride, received.segments()), (1200, 3));
+        assert_eq!(received.ecn(), Some(Ecn::Ce));
+
+        let message = ReceivedMessage { len: 80, address: Some("192.0.2.1:443".parse().unwrap()), flags: 0, control: vec![] };
+        let received = meta(message).unwrap();
+        assert_eq!((received.stride, received.segments(), received.ecn()), (80, 1, None));
+    }
+
+    #[test]
+    fn test_pktinfo_control() {
+        let (level, msg_type, data) = pktinfo_control("192.0.2.7".parse().unwrap(), true);
+        assert_eq!((level, msg_type), (libc::IPPROTO_IP, libc::IP_PKTINFO));
+        assert_eq!(data, [0, 0, 0, 0, 192, 0, 2, 7, 0, 0, 0, 0]);
+        let (level, msg_type, data) = pktinfo_control("192.0.2.7".parse().unwrap(), false);
+        assert_eq!((level, msg_type), (libc::IPPROTO_IPV6, libc::IPV6_PKTINFO));
+        assert_eq!(&data[..16], "::ffff:192.0.2.7".parse::<std::net::Ipv6Addr>().unwrap().octets());
+    }
+}
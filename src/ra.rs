@@ -0,0 +1,334 @@
+//! IPv6 Router Advertisement (RFC 4861) listener: receives and parses RAs with their prefixes,
+//! RDNSS (RFC 8106) and default router lifetime.
+
+use std::{
+    convert::TryInto,
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, Ipv6Addr, SocketAddr},
+    os::unix::io::{AsFd, AsRawFd, BorrowedFd},
+    time::Duration,
+};
+
+use socket2::SockRef;
+
+use super::{IpInterface, IpNet, cmsg::recv_msg, pktinfo::PktInfo, raw::{RawSocket, RawSocketBuilder}, shutdown::ShutdownHandle,
+            sockopt::set_int_option,
+            icmpv6::{Icmp6Filter, ND_ROUTER_ADVERT, ND_ROUTER_SOLICIT, set_icmp6_filter}};
+
+/// All-nodes link-local multicast group RAs are sent to.
+pub const ALL_NODES_MULTICAST: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+/// All-routers link-local multicast group router solicitations are sent to.
+pub const ALL_ROUTERS_MULTICAST: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 2);
+
+const RA_HEADER_LEN: usize = 16;
+const OPT_SOURCE_LINK_ADDRESS: u8 = 1;
+const OPT_PREFIX_INFORMATION: u8 = 3;
+const OPT_MTU: u8 = 5;
+const OPT_RDNSS: u8 = 25;
+const OPT_DNSSL: u8 = 31;
+
+/// Prefix information option of an RA.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PrefixInformation {
+    /// the prefix
+    pub prefix: Ipv6Addr,
+
+    /// prefix length
+    pub prefix_len: u8,
+
+    /// the prefix can be used for on-link determination (L flag)
+    pub on_link: bool,
+
+    /// the prefix can be used for stateless address autoconfiguration (A flag)
+    pub autonomous: bool,
+
+    /// valid lifetime, None for infinity
+    pub valid_lifetime: Option<Duration>,
+
+    /// preferred lifetime, None for infinity
+    pub preferred_lifetime: Option<Duration>,
+}
+
+/// Recursive DNS server option of an RA.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RecursiveDnsServers {
+    /// addresses of the DNS servers
+    pub servers: Vec<Ipv6Addr>,
+
+    /// lifetime of the servers, None for infinity
+    pub lifetime: Option<Duration>,
+}
+
+/// A received Router Advertisement.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RouterAdvertisement {
+    /// index of the interface the RA was received on
+    pub interface_index: u32,
+
+    /// link-local address of the router
+    pub router: Ipv6Addr,
+
+    /// hop limit the router recommends (0 unspecified)
+    pub hop_limit: u8,
+
+    /// addresses are available via DHCPv6 (M flag)
+    pub managed: bool,
+
+    /// other configuration is available via DHCPv6 (O flag)
+    pub other_config: bool,
+
+    /// lifetime as default router, zero if the router is no default router
+    pub router_lifetime: Duration,
+
+    /// reachable time for neighbor unreachability detection (0 unspecified)
+    pub reachable_time: Duration,
+
+    /// time between retransmitted neighbor solicitations (0 unspecified)
+    pub retrans_timer: Duration,
+
+    /// link layer address of the router
+    pub source_link_address: Option<Vec<u8>>,
+
+    /// link MTU
+    pub mtu: Option<u32>,
+
+    /// advertised prefixes
+    pub prefixes: Vec<PrefixInformation>,
+
+    /// advertised recursive DNS servers
+    pub dns_servers: Vec<RecursiveDnsServers>,
+
+    /// advertised DNS search domains
+    pub search_domains: Vec<String>,
+}
+
+/// Raw ICMPv6 socket receiving only Router Advertisements, requires CAP_NET_RAW.
+#[derive(Debug)]
+pub struct RaListener {
+    socket: RawSocket,
+    interface_index: Option<u32>,
+    shutdown: ShutdownHandle,
+}
+
+impl RaListener {
+
+    /// Opens the listener for RAs on the interface with the given index, or on all IPv6
+    /// multicast capable interfaces for None. The socket is joined to the all-nodes group.
+    pub fn new(interface_index: Option<u32>) -> Result<RaListener> {
+        let socket = RawSocketBuilder::ipv6(libc::IPPROTO_ICMPV6 as u8).hops(255).multicast_hops(255).build()?;
+        let fd = socket.as_raw_fd();
+        set_icmp6_filter(&socket, &Icmp6Filter::pass_only(&[ND_ROUTER_ADVERT]))?;
+        set_int_option(fd, libc::IPPROTO_IPV6, libc::IPV6_RECVHOPLIMIT, 1)?;
+        set_int_option(fd, libc::IPPROTO_IPV6, libc::IPV6_RECVPKTINFO, 1)?;
+        let indexes = match interface_index {
+            Some(index) => vec![index],
+            None => {
+                let mut indexes: Vec<u32> = IpInterface::retrieve_ip_interfaces()?.iter()
+                    .filter(|i| i.address.is_ipv6() && i.supports_multicast())
+                    .map(|i| i.index)
+                    .collect();
+                indexes.sort_unstable();
+                indexes.dedup();
+                indexes
+            },
+        };
+        for index in indexes {
+            match SockRef::from(&socket).join_multicast_v6(&ALL_NODES_MULTICAST, index) {
+                Err(e) if e.raw_os_error() != Some(libc::EADDRINUSE) => return Err(e),
+                _ => {},
+            }
+        }
+        Ok(RaListener { socket, interface_index, shutdown: ShutdownHandle::new() })
+    }
+
+    /// Sets the timeout of `recv`, None blocks indefinitely.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        self.socket.set_read_timeout(timeout)
+    }
+
+    /// Sends a Router Solicitation to the all-routers group on the interface to trigger RAs
+    /// without waiting for the next periodic one.
+    pub fn solicit(&self, interface_index: u32) -> Result<()> {
+        let solicitation = [ND_ROUTER_SOLICIT, 0, 0, 0, 0, 0, 0, 0];
+        SockRef::from(&self.socket).set_multicast_if_v6(interface_index)?;
+        self.socket.send_to(&solicitation, IpAddr::V6(ALL_ROUTERS_MULTICAST)).map(|_| ())
+    }
+
+    /// Returns a handle which shuts down blocked and later `recv` calls from other threads, see
+    /// `shutdown::ShutdownHandle`.
+    pub fn shutdown_handle(&self) -> Result<ShutdownHandle> {
+        self.shutdown.share()
+    }
+
+    /// Receives the next valid RA, i.e. sent with hop limit 255 from a link-local address, on the
+    /// listener's interface.
+    pub fn recv(&self) -> Result<RouterAdvertisement> {
+        let mut buffer = [0_u8; 1500];
+        loop {
+            let received = self.shutdown.wait_readable(self.socket.as_fd())
+                .and_then(|_| recv_msg(self.socket.as_raw_fd(), &mut buffer, 0));
+            let msg = received.map_err(|e| match e.kind() {
+                ErrorKind::WouldBlock => Error::new(ErrorKind::TimedOut, "no router advertisement"),
+                _ => e,
+            })?;
+            let info = PktInfo::from_control(&msg.control);
+            let router = match msg.address {
+                Some(SocketAddr::V6(address)) => *address.ip(),
+                _ => continue,
+            };
+            let index = info.if_index.unwrap_or(0);
+            if info.ttl != Some(255) || !router.is_unicast_link_local()
+                || self.interface_index.is_some_and(|i| i != index) {
+                continue;
+            }
+            if let Some(ra) = parse_router_advertisement(&buffer[..msg.len], router, index) {
+                return Ok(ra);
+            }
+        }
+    }
+}
+
+impl AsFd for RaListener {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.socket.as_fd()
+    }
+}
+
+/// Parses the ICMPv6 message, None if it is not a valid RA.
+fn parse_router_advertisement(packet: &[u8], router: Ipv6Addr, interface_index: u32) -> Option<RouterAdvertisement> {
+    if packet.len() < RA_HEADER_LEN || packet[0] != ND_ROUTER_ADVERT || packet[1] != 0 {
+        return None;
+    }
+    let u32_at = |data: &[u8], offset: usize| u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap());
+    let mut ra = RouterAdvertisement {
+        interface_index,
+        router,
+        hop_limit: packet[4],
+        managed: packet[5] & 0x80 != 0,
+        other_config: packet[5] & 0x40 != 0,
+        router_lifetime: Duration::from_secs(u16::from_be_bytes([packet[6], packet[7]]) as u64),
+        reachable_time: Duration::from_millis(u32_at(packet, 8) as u64),
+        retrans_timer: Duration::from_millis(u32_at(packet, 12) as u64),
+        source_link_address: None,
+        mtu: None,
+        prefixes: Vec::new(),
+        dns_servers: Vec::new(),
+        search_domains: Vec::new(),
+    };
+    let mut options = &packet[RA_HEADER_LEN..];
+    while options.len() >= 8 {
+        let len = options[1] as usize * 8;
+        if len == 0 || len > options.len() {
+            return None;
+        }
+        let option = &options[..len];
+        match option[0] {
+            OPT_SOURCE_LINK_ADDRESS => ra.source_link_address = Some(option[2..].to_vec()),
+            OPT_MTU => ra.mtu = Some(u32_at(option, 4)),
+            OPT_PREFIX_INFORMATION if len == 32 => ra.prefixes.push(PrefixInformation {
+                prefix: Ipv6Addr::from(u128::from_be_bytes(option[16..32].try_into().unwrap())),
+                prefix_len: option[2],
+                on_link: option[3] & 0x80 != 0,
+                autonomous: option[3] & 0x40 != 0,
+                valid_lifetime: lifetime(u32_at(option, 4)),
+                preferred_lifetime: lifetime(u32_at(option, 8)),
+            }),
+            OPT_RDNSS if len >= 24 => ra.dns_servers.push(RecursiveDnsServers {
+                servers: option[8..].chunks_exact(16)
+                    .map(|a| Ipv6Addr::from(u128::from_be_bytes(a.try_into().unwrap())))
+                    .collect(),
+                lifetime: lifetime(u32_at(option, 4)),
+            }),
+            OPT_DNSSL => ra.search_domains.extend(parse_domain_names(&option[8..])),
+            _ => {},
+        }
+        options = &options[len..];
+    }
+    Some(ra)
+}
+
+/// Decodes the DNS encoded (uncompressed) domain names of a DNSSL option.
+fn parse_domain_names(mut data: &[u8]) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut labels: Vec<String> = Vec::new();
+    while let Some((&len, rest)) = data.split_first() {
+        if len == 0 {
+            if !labels.is_empty() {
+                names.push(labels.join("."));
+                labels.clear();
+            }
+            data = rest;
+            continue;
+        }
+        match rest.get(..len as usize) {
+            Some(label) => labels.push(String::from_utf8_lossy(label).into_owned()),
+            None => break,
+        }
+        data = &rest[len as usize..];
+    }
+    names
+}
+
+fn lifetime(seconds: u32) -> Option<Duration> {
+    if seconds == u32::MAX {
+        None
+    } else {
+        Some(Duration::from_secs(seconds as u64))
+    }
+}
+
+impl RouterAdvertisement {
+
+    /// Returns the autonomous prefix the address was configured from, if any.
+    pub fn autoconfiguration_prefix(&self, address: &IpAddr) -> Option<&PrefixInformation> {
+        self.prefixes.iter()
+            .filter(|p| p.autonomous)
+            .find(|p| p.network().map(|network| network.contains(address)).unwrap_or(false))
+    }
+}
+
+impl PrefixInformation {
+
+    /// Returns the advertised network, None if the prefix length is invalid.
+    pub fn network(&self) -> Option<IpNet> {
+        IpNet::new(IpAddr::V6(self.prefix), self.prefix_len).ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_parse_router_advertisement() {
+        let mut packet = vec![134, 0, 0, 0, 64, 0x40, 0x07, 0x08, 0, 0, 0, 0, 0, 0, 0, 0];
+        packet.extend_from_slice(&[1, 1, 0x02, 0, 0, 0, 0, 0x01]);
+        packet.extend_from_slice(&[5, 1, 0, 0, 0, 0, 0x05, 0xdc]);
+        packet.extend_from_slice(&[3, 4, 64, 0xc0, 0, 0, 0x0e, 0x10, 0, 0, 0x07, 0x08, 0, 0, 0, 0]);
+        packet.extend_from_slice(&"2001:db8:1::".parse::<Ipv6Addr>().unwrap().octets());
+        packet.extend_from_slice(&[25, 3, 0, 0, 0xff, 0xff, 0xff, 0xff]);
+        packet.extend_from_slice(&"2001:db8::53".parse::<Ipv6Addr>().unwrap().octets());
+        packet.extend_from_slice(&[31, 3, 0, 0, 0, 0, 0x02, 0x58, 7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0]);
+        packet.extend_from_slice(&[0; 7]);
+        let router: Ipv6Addr = "fe80::1".parse().unwrap();
+        let ra = parse_router_advertisement(&packet, router, 2).unwrap();
+        assert_eq!(ra.hop_limit, 64);
+        assert!(!ra.managed && ra.other_config);
+        assert_eq!(ra.router_lifetime, Duration::from_secs(1800));
+        assert_eq!(ra.source_link_address, Some(vec![0x02, 0, 0, 0, 0, 0x01]));
+        assert_eq!(ra.mtu, Some(1500));
+        assert_eq!(ra.prefixes, vec![PrefixInformation {
+            prefix: "2001:db8:1::".parse().unwrap(), prefix_len: 64, on_link: true, autonomous: true,
+            valid_lifetime: Some(Duration::from_secs(3600)), preferred_lifetime: Some(Duration::from_secs(1800)),
+        }]);
+        assert_eq!(ra.dns_servers, vec![RecursiveDnsServers { servers: vec!["2001:db8::53".parse().unwrap()],
+            lifetime: None }]);
+        assert_eq!(ra.search_domains, vec!["example".to_string()]);
+        assert!(ra.autoconfiguration_prefix(&"2001:db8:1::abcd".parse().unwrap()).is_some());
+        assert!(ra.autoconfiguration_prefix(&"2001:db8:2::abcd".parse().unwrap()).is_none());
+
+        packet[1] = 1;
+        assert!(parse_router_advertisement(&packet, router, 2).is_none());
+    }
+}
@@ -0,0 +1,60 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    io::Result,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Fills the buffer with random bytes from the randomly keyed SipHash of the standard library,
+/// suitable for protocol transaction ids and nonces but not for cryptographic keys.
+pub(crate) fn random_bytes(buf: &mut [u8]) {
+    let state = RandomState::new();
+    for (i, chunk) in buf.chunks_mut(8).enumerate() {
+        let mut hasher = state.build_hasher();
+        hasher.write_usize(i);
+        hasher.write_u128(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos());
+        chunk.copy_from_slice(&hasher.finish().to_ne_bytes()[..chunk.len()]);
+    }
+}
+
+/// Fills the buffer with random bytes of the cryptographically secure generator of the operating
+/// system (getrandom on linux, /dev/urandom on other unix systems, BCryptGenRandom on windows),
+/// for keys and secrets.
+pub(crate) fn secure_random_bytes(buf: &mut [u8]) -> Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        let mut filled = 0;
+        while filled < buf.len() {
+            let rest = &mut buf[filled..];
+            match unsafe { libc::getrandom(rest.as_mut_ptr() as *mut libc::c_void, rest.len(), 0) } {
+                -1 => {
+                    let error = std::io::Error::last_os_error();
+                    if error.kind() != std::io::ErrorKind::Interrupted {
+                        return Err(error);
+                    }
+                },
+                len => filled += len as usize,
+            }
+        }
+        Ok(())
+    }
+    #[cfg(all(unix, not(target_os = "linux")))]
+    {
+        use std::io::Read;
+        std::fs::File::open("/dev/urandom")?.read_exact(buf)
+    }
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::Security::Cryptography::{BCryptGenRandom, BCRYPT_USE_SYSTEM_PREFERRED_RNG};
+        for chunk in buf.chunks_mut(u32::MAX as usize) {
+            let status = unsafe {
+                BCryptGenRandom(std::ptr::null_mut(), chunk.as_mut_ptr(), chunk.len() as u32,
+                                BCRYPT_USE_SYSTEM_PREFERRED_RNG)
+            };
+            if status < 0 {
+                return Err(std::io::Error::other(format!("BCryptGenRandom failed with status {:#x}", status)));
+            }
+        }
+        Ok(())
+    }
+}
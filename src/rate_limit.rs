@@ -0,0 +1,202 @@
+//! Rate limiting of sent datagrams by token buckets for the number of datagrams and bytes per
+//! second, e.g. for announcements and relays on links with little bandwidth.
+
+use std::{
+    io::Result,
+    net::{SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use super::stats::{SocketStats, StatsCounter};
+
+/// Limits of a `RateLimitedSender`. The default limits nothing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct RateLimit {
+    /// maximum average number of datagrams per second, None (or 0) for no limit
+    pub packets_per_second: Option<u32>,
+
+    /// maximum average number of payload bytes per second, None (or 0) for no limit
+    pub bytes_per_second: Option<u64>,
+
+    /// number of datagrams that may be sent back-to-back after an idle period, None for the
+    /// number of one second (packets_per_second)
+    pub burst_packets: Option<u32>,
+
+    /// number of bytes that may be sent back-to-back after an idle period, None for the number
+    /// of one second (bytes_per_second)
+    pub burst_bytes: Option<u64>,
+}
+
+/// Token bucket filled with `rate` tokens per second up to `capacity`. The tokens of a datagram
+/// larger than the capacity are taken from a full bucket, which is in debt thereafter.
+#[derive(Clone, Debug)]
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+
+    fn new(rate: u64, burst: Option<u64>, now: Instant) -> TokenBucket {
+        let capacity = burst.unwrap_or(rate).max(1) as f64;
+        TokenBucket { rate: rate as f64, capacity, tokens: capacity, updated: now }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// Returns how long to wait until `count` tokens can be taken.
+    fn delay(&mut self, count: u64, now: Instant) -> Duration {
+        self.refill(now);
+        let missing = (count as f64).min(self.capacity) - self.tokens;
+        if missing <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(missing / self.rate)
+        }
+    }
+
+    fn take(&mut self, count: u64, now: Instant) {
+        self.refill(now);
+        self.tokens -= count as f64;
+    }
+}
+
+/// Wrapper of a UDP socket (std::net::UdpSocket or, with the feature 'tokio-net',
+/// tokio::net::UdpSocket) which delays sent datagrams to keep within the `RateLimit`.
+/// ```no_run
+/// use net_utils::rate_limit::{RateLimit, RateLimitedSender};
+/// let socket = std::net::UdpSocket::bind("0.0.0.0:0").unwrap();
+/// let limit = RateLimit { packets_per_second: Some(10), bytes_per_second: Some(16_000), ..RateLimit::default() };
+/// let mut sender = RateLimitedSender::new(socket, limit);
+/// sender.send_to(b"announcement", "192.0.2.255:30490".parse().unwrap()).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct RateLimitedSender<S> {
+    socket: S,
+    packets: Option<TokenBucket>,
+    bytes: Option<TokenBucket>,
+    stats: StatsCounter,
+}
+
+impl<S> RateLimitedSender<S> {
+
+    /// Wraps the socket, the buckets start full.
+    pub fn new(socket: S, limit: RateLimit) -> RateLimitedSender<S> {
+        let now = Instant::now();
+        RateLimitedSender {
+            socket,
+            packets: limit.packets_per_second.filter(|rate| *rate > 0)
+                .map(|rate| TokenBucket::new(rate as u64, limit.burst_packets.map(u64::from), now)),
+            bytes: limit.bytes_per_second.filter(|rate| *rate > 0)
+                .map(|rate| TokenBucket::new(rate, limit.burst_bytes, now)),
+            stats: StatsCounter::new(),
+        }
+    }
+
+    /// Returns the socket.
+    pub fn get_ref(&self) -> &S {
+        &self.socket
+    }
+
+    /// Returns the socket.
+    pub fn into_inner(self) -> S {
+        self.socket
+    }
+
+    /// Returns the statistics of the datagrams sent via the wrapper.
+    pub fn stats(&self) -> SocketStats {
+        self.stats.snapshot()
+    }
+
+    /// Returns how long a datagram of `len` bytes has to wait before it may be sent.
+    pub fn delay(&mut self, len: usize) -> Duration {
+        let now = Instant::now();
+        let packets = self.packets.as_mut().map(|bucket| bucket.delay(1, now)).unwrap_or_default();
+        let bytes = self.bytes.as_mut().map(|bucket| bucket.delay(len as u64, now)).unwrap_or_default();
+        packets.max(bytes)
+    }
+
+    /// Takes the tokens of a datagram of `len` bytes which is sent now.
+    fn consume(&mut self, len: usize) {
+        let now = Instant::now();
+        if let Some(bucket) = self.packets.as_mut() {
+            bucket.take(1, now);
+        }
+        if let Some(bucket) = self.bytes.as_mut() {
+            bucket.take(len as u64, now);
+        }
+        self.stats.sent(len);
+    }
+}
+
+impl RateLimitedSender<UdpSocket> {
+
+    /// Sends the datagram to the connected peer, blocks until the limit permits it.
+    pub fn send(&mut self, buf: &[u8]) -> Result<usize> {
+        std::thread::sleep(self.delay(buf.len()));
+        let len = self.socket.send(buf)?;
+        self.consume(buf.len());
+        Ok(len)
+    }
+
+    /// Sends the datagram to the address, blocks until the limit permits it.
+    pub fn send_to(&mut self, buf: &[u8], target: SocketAddr) -> Result<usize> {
+        std::thread::sleep(self.delay(buf.len()));
+        let len = self.socket.send_to(buf, target)?;
+        self.consume(buf.len());
+        Ok(len)
+    }
+}
+
+/// Requires the feature 'tokio-net'.
+#[cfg(feature = "tokio-net")]
+impl RateLimitedSender<tokio::net::UdpSocket> {
+
+    /// Sends the datagram to the connected peer when the limit permits it.
+    pub async fn send(&mut self, buf: &[u8]) -> Result<usize> {
+        tokio::time::sleep(self.delay(buf.len())).await;
+        let len = self.socket.send(buf).await?;
+        self.consume(buf.len());
+        Ok(len)
+    }
+
+    /// Sends the datagram to the address when the limit permits it.
+    pub async fn send_to(&mut self, buf: &[u8], target: SocketAddr) -> Result<usize> {
+        tokio::time::sleep(self.delay(buf.len())).await;
+        let len = self.socket.send_to(buf, target).await?;
+        self.consume(buf.len());
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(10, Some(2), start);
+        assert_eq!(bucket.delay(1, start), Duration::ZERO);
+        bucket.take(1, start);
+        bucket.take(1, start);
+        assert_eq!(bucket.delay(1, start), Duration::from_millis(100));
+        assert_eq!(bucket.delay(1, start + Duration::from_millis(100)), Duration::ZERO);
+        // refill is capped at the capacity
+        assert_eq!(bucket.delay(5, start + Duration::from_secs(10)), Duration::ZERO);
+        assert_eq!(bucket.tokens, 2.0);
+
+        // a datagram larger than the capacity waits for a full bucket and leaves a debt
+        let mut bytes = TokenBucket::new(1000, Some(500), start);
+        assert_eq!(bytes.delay(1500, start), Duration::ZERO);
+        bytes.take(1500, start);
+        assert_eq!(bytes.delay(100, start), Duration::from_millis(1100));
+    }
+}
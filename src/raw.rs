@@ -0,0 +1,223 @@
+//! Raw IPv4 and IPv6 sockets for protocols the kernel does not implement (e.g. ICMP, IGMP, VRRP
+//! or custom protocol numbers): `RawSocketBuilder` creates them with header inclusion
+//! (IP_HDRINCL, IPV6_HDRINCL), interface binding, hop limits and kernel computed checksums
+//! (IPV6_CHECKSUM). Raw sockets require CAP_NET_RAW.
+
+use std::{
+    convert::TryFrom,
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, SocketAddr},
+    os::unix::io::{AsFd, AsRawFd, BorrowedFd, RawFd},
+    time::Duration,
+};
+
+use socket2::{SockAddr, Socket};
+
+use super::{cmsg::recv_msg, sockopt::{get_option, set_bytes_option, set_int_option}, sys_api};
+
+/// Builder of a `RawSocket`.
+/// ```no_run
+/// use net_utils::raw::RawSocketBuilder;
+/// // experimental protocol number 253 (RFC 3692)
+/// let socket = RawSocketBuilder::ipv4(253).bind_device("eth0").hops(1).build().unwrap();
+/// socket.send_to(b"payload", "192.0.2.1".parse().unwrap()).unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RawSocketBuilder {
+    ipv6: bool,
+    protocol: u8,
+    header_included: bool,
+    local_address: Option<IpAddr>,
+    device: Option<String>,
+    hops: Option<u32>,
+    multicast_hops: Option<u32>,
+    checksum_offset: Option<usize>,
+}
+
+impl RawSocketBuilder {
+
+    /// Creates a builder for a raw IPv4 socket of the IP protocol number. IPPROTO_RAW (255)
+    /// creates a send only socket with IP_HDRINCL.
+    pub fn ipv4(protocol: u8) -> RawSocketBuilder {
+        RawSocketBuilder::new(false, protocol)
+    }
+
+    /// Creates a builder for a raw IPv6 socket of the next header number.
+    pub fn ipv6(protocol: u8) -> RawSocketBuilder {
+        RawSocketBuilder::new(true, protocol)
+    }
+
+    fn new(ipv6: bool, protocol: u8) -> RawSocketBuilder {
+        RawSocketBuilder {
+            ipv6,
+            protocol,
+            header_included: false,
+            local_address: None,
+            device: None,
+            hops: None,
+            multicast_hops: None,
+            checksum_offset: None,
+        }
+    }
+
+    /// Sets IP_HDRINCL (IPV6_HDRINCL): the sent data start with the IP header, of which the
+    /// kernel fills the total (payload) length, the IPv4 header checksum and, if zero, the
+    /// source address and the IPv4 identification. Received IPv4 packets always start with the
+    /// IP header, received IPv6 packets never do.
+    pub fn header_included(mut self, included: bool) -> Self {
+        self.header_included = included;
+        self
+    }
+
+    /// Binds the socket to the local address, which is the source address of sent packets
+    /// (without an included header) and limits the received packets to this destination.
+    pub fn local_address(mut self, address: IpAddr) -> Self {
+        self.local_address = Some(address);
+        self
+    }
+
+    /// Binds the socket to the network device with the given name (SO_BINDTODEVICE), so that
+    /// packets are only sent and received via this interface.
+    pub fn bind_device(mut self, device: &str) -> Self {
+        self.device = Some(String::from(device));
+        self
+    }
+
+    /// Sets the TTL (hop limit) of sent unicast packets.
+    pub fn hops(mut self, hops: u32) -> Self {
+        self.hops = Some(hops);
+        self
+    }
+
+    /// Sets the TTL (hop limit) of sent multicast packets.
+    pub fn multicast_hops(mut self, hops: u32) -> Self {
+        self.multicast_hops = Some(hops);
+        self
+    }
+
+    /// Makes the kernel compute the Internet checksum (with the IPv6 pseudo header) of sent and
+    /// verify it for received packets (IPV6_CHECKSUM), the offset is the position of the 16 bit
+    /// checksum in the payload. ICMPv6 sockets always compute the checksum at offset 2. IPv6
+    /// only, `build` fails with InvalidInput for IPv4 sockets.
+    pub fn checksum_offset(mut self, offset: usize) -> Self {
+        self.checksum_offset = Some(offset);
+        self
+    }
+
+    /// Creates the socket.
+    pub fn build(&self) -> Result<RawSocket> {
+        trace_span!("raw_socket", protocol = self.protocol, ipv6 = self.ipv6);
+        if self.checksum_offset.is_some() && !self.ipv6 {
+            return Err(Error::new(ErrorKind::InvalidInput, "checksum offset requires an IPv6 socket"));
+        }
+        if self.local_address.is_some_and(|address| address.is_ipv6() != self.ipv6) {
+            return Err(Error::new(ErrorKind::InvalidInput, "local address of other address family"));
+        }
+        let domain = if self.ipv6 { libc::AF_INET6 } else { libc::AF_INET };
+        let socket = sys_api::socket(domain, libc::SOCK_RAW, self.protocol as libc::c_int)?;
+        let raw = RawSocket {
+            socket, ipv6: self.ipv6, #[cfg(feature = "metrics")] _open: super::metrics::OpenSocket::new(),
+        };
+        if self.header_included {
+            raw.set_header_included(true)?;
+        }
+        if let Some(device) = &self.device {
+            set_bytes_option(raw.as_raw_fd(), libc::SOL_SOCKET, libc::SO_BINDTODEVICE, device.as_bytes())?;
+        }
+        let hop_options = [
+            (self.hops, libc::IPPROTO_IP, libc::IP_TTL, libc::IPV6_UNICAST_HOPS),
+            (self.multicast_hops, libc::IPPROTO_IP, libc::IP_MULTICAST_TTL, libc::IPV6_MULTICAST_HOPS),
+        ];
+        for (hops, level, name, name_v6) in hop_options {
+            if let Some(hops) = hops {
+                let hops = libc::c_int::try_from(hops).map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+                match self.ipv6 {
+                    true => set_int_option(raw.as_raw_fd(), libc::IPPROTO_IPV6, name_v6, hops)?,
+                    false => set_int_option(raw.as_raw_fd(), level, name, hops)?,
+                }
+            }
+        }
+        if let Some(offset) = self.checksum_offset {
+            let offset = libc::c_int::try_from(offset).map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+            set_int_option(raw.as_raw_fd(), libc::IPPROTO_IPV6, libc::IPV6_CHECKSUM, offset)?;
+        }
+        if let Some(address) = self.local_address {
+            sys_api::bind(&raw.socket, &SockAddr::from(SocketAddr::new(address, 0)))?;
+        }
+        trace_event!(crate::trace::TraceEvent::SocketCreated { kind: "raw", address: self.local_address.map(|a| SocketAddr::new(a, 0)) });
+        Ok(raw)
+    }
+}
+
+/// A raw IPv4 or IPv6 socket of one IP protocol, see `RawSocketBuilder`. The socket can be
+/// used with the functions of the crate taking `AsFd` sockets, e.g. `icmpv6::set_icmp6_filter`
+/// or `pktinfo::recv_with_pktinfo`.
+#[derive(Debug)]
+pub struct RawSocket {
+    socket: Socket,
+    ipv6: bool,
+    #[cfg(feature = "metrics")]
+    _open: super::metrics::OpenSocket,
+}
+
+impl RawSocket {
+
+    /// Sends the packet to the destination, returns the number of bytes sent.
+    pub fn send_to(&self, packet: &[u8], destination: IpAddr) -> Result<usize> {
+        self.socket.send_to(packet, &SockAddr::from(SocketAddr::new(destination, 0)))
+    }
+
+    /// Receives a packet, returns its length and the source address. IPv4 packets include the IP
+    /// header.
+    pub fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, IpAddr)> {
+        let message = recv_msg(self.as_raw_fd(), buf, 0)?;
+        let address = message.address.ok_or_else(|| Error::other("packet without IP source address"))?;
+        Ok((message.len, address.ip()))
+    }
+
+    /// Sets or clears IP_HDRINCL (IPV6_HDRINCL), see `RawSocketBuilder::header_included`.
+    pub fn set_header_included(&self, included: bool) -> Result<()> {
+        let (level, name) = self.header_option();
+        set_int_option(self.as_raw_fd(), level, name, included as libc::c_int)
+    }
+
+    /// Returns whether IP_HDRINCL (IPV6_HDRINCL) is set.
+    pub fn header_included(&self) -> Result<bool> {
+        let (level, name) = self.header_option();
+        get_option::<libc::c_int>(self.as_raw_fd(), level, name).map(|included| included != 0)
+    }
+
+    fn header_option(&self) -> (libc::c_int, libc::c_int) {
+        match self.ipv6 {
+            true => (libc::IPPROTO_IPV6, libc::IPV6_HDRINCL),
+            false => (libc::IPPROTO_IP, libc::IP_HDRINCL),
+        }
+    }
+
+    /// Returns whether the socket is an IPv6 socket.
+    pub fn is_ipv6(&self) -> bool {
+        self.ipv6
+    }
+
+    /// Sets the timeout of `recv_from`, None blocks indefinitely.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        self.socket.set_read_timeout(timeout)
+    }
+
+    /// Sets the socket to non-blocking mode.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> Result<()> {
+        self.socket.set_nonblocking(nonblocking)
+    }
+}
+
+impl AsFd for RawSocket {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.socket.as_fd()
+    }
+}
+
+impl AsRawFd for RawSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+}
@@ -0,0 +1,758 @@
+//! Reliable multicast with negative acknowledgements, a simplified PGM (RFC 3208): the
+//! `ReliableSender` numbers the datagrams of its session, keeps the last ones for
+//! retransmission and sends heartbeats with the last and the oldest retransmittable sequence
+//! number. The `ReliableReceiver` delivers the datagrams of every sender in order, requests
+//! missing ones with NACKs unicast to the sender, which retransmits them to the group, and
+//! reports datagrams which cannot be recovered anymore. For configuration distribution to many
+//! receivers which need loss recovery without a TCP connection each. Messages larger than a
+//! datagram are sent as fragments (see `fragment`) with consecutive sequence numbers.
+//! Requires the feature 'relmcast'.
+//!
+//! Packet format (big endian): version 1, type (1 data, 2 heartbeat, 3 NACK, 4 fragment),
+//! session id (u32), sequence number (u32), followed by the payload of data packets, the oldest
+//! retransmittable sequence number (u32) of heartbeats, the number (u16) and the missing sequence
+//! numbers (u32 each) of NACKs, or a fragment with the sequence number of the first fragment as
+//! message id.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::TryInto,
+    io::{Error, ErrorKind, Result},
+    net::{SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use super::{fragment::{self, Fragment, FRAGMENT_HEADER_LEN}, stats::{SocketStats, StatsCounter}};
+
+const VERSION: u8 = 1;
+const TYPE_DATA: u8 = 1;
+const TYPE_HEARTBEAT: u8 = 2;
+const TYPE_NACK: u8 = 3;
+const TYPE_FRAGMENT: u8 = 4;
+const HEADER_LEN: usize = 10;
+/// Maximum number of sequence numbers of a NACK.
+const MAX_NACK_SEQUENCES: usize = 256;
+
+/// A packet of the protocol.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Packet {
+    Data { session: u32, sequence: u32, fragment: bool, payload: Vec<u8> },
+    Heartbeat { session: u32, lead: u32, trail: u32 },
+    Nack { session: u32, sequences: Vec<u32> },
+}
+
+impl Packet {
+
+    fn encode(&self) -> Vec<u8> {
+        let header = |packet_type: u8, session: u32, sequence: u32| {
+            let mut data = vec![VERSION, packet_type];
+            data.extend_from_slice(&session.to_be_bytes());
+            data.extend_from_slice(&sequence.to_be_bytes());
+            data
+        };
+        match self {
+            Packet::Data { session, sequence, fragment, payload } => {
+                let mut data = header(if *fragment { TYPE_FRAGMENT } else { TYPE_DATA }, *session, *sequence);
+                data.extend_from_slice(payload);
+                data
+            },
+            Packet::Heartbeat { session, lead, trail } => {
+                let mut data = header(TYPE_HEARTBEAT, *session, *lead);
+                data.extend_from_slice(&trail.to_be_bytes());
+                data
+            },
+            Packet::Nack { session, sequences } => {
+                let mut data = header(TYPE_NACK, *session, 0);
+                data.extend_from_slice(&(sequences.len() as u16).to_be_bytes());
+                sequences.iter().for_each(|sequence| data.extend_from_slice(&sequence.to_be_bytes()));
+                data
+            },
+        }
+    }
+
+    fn parse(data: &[u8]) -> Option<Packet> {
+        if data.len() < HEADER_LEN || data[0] != VERSION {
+            return None;
+        }
+        let u32_at = |offset: usize| Some(u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?));
+        let session = u32_at(2)?;
+        match data[1] {
+            TYPE_DATA | TYPE_FRAGMENT => Some(Packet::Data {
+                session, sequence: u32_at(6)?, fragment: data[1] == TYPE_FRAGMENT, payload: data[HEADER_LEN..].to_vec(),
+            }),
+            TYPE_HEARTBEAT => Some(Packet::Heartbeat { session, lead: u32_at(6)?, trail: u32_at(HEADER_LEN)? }),
+            TYPE_NACK => {
+                let count = u16::from_be_bytes(data.get(HEADER_LEN..HEADER_LEN + 2)?.try_into().ok()?) as usize;
+                let sequences = (0..count).map(|i| u32_at(HEADER_LEN + 2 + 4 * i)).collect::<Option<Vec<_>>>()?;
+                Some(Packet::Nack { session, sequences })
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Returns the distance from one sequence number to another, negative if `to` is older.
+fn offset(from: u32, to: u32) -> i32 {
+    to.wrapping_sub(from) as i32
+}
+
+/// Returns a random duration up to `max`.
+fn random_delay(max: Duration) -> Duration {
+    let mut random = [0_u8; 2];
+    super::random::random_bytes(&mut random);
+    max * u16::from_ne_bytes(random) as u32 / u16::MAX as u32
+}
+
+/// Options of a `ReliableSender`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ReliableSenderOpts {
+    /// number of sent datagrams kept for retransmission
+    pub window: usize,
+
+    /// interval between heartbeats, which let receivers detect the loss of the last datagrams
+    pub heartbeat_interval: Duration,
+
+    /// minimum time between two retransmissions of a datagram, so that the NACKs of many
+    /// receivers for the same loss cause one retransmission
+    pub retransmit_holdoff: Duration,
+
+    /// maximum length of a data packet, larger messages are fragmented; the path MTU less the
+    /// IP and UDP headers
+    pub max_datagram: usize,
+}
+
+impl Default for ReliableSenderOpts {
+    fn default() -> ReliableSenderOpts {
+        ReliableSenderOpts { window: 1024, heartbeat_interval: Duration::from_secs(1),
+                             retransmit_holdoff: Duration::from_millis(50), max_datagram: 1400 }
+    }
+}
+
+/// A datagram kept for retransmission.
+#[derive(Debug)]
+struct Sent {
+    sequence: u32,
+    packet: Vec<u8>,
+    retransmitted: Option<Instant>,
+}
+
+/// Sender of a reliable multicast session. The socket should be bound to a unicast address
+/// (not to the group), receivers send their NACKs to it.
+/// ```no_run
+/// use net_utils::relmcast::{ReliableSender, ReliableSenderOpts};
+/// use std::time::Duration;
+/// let socket = std::net::UdpSocket::bind("192.168.1.2:0").unwrap();
+/// let mut sender = ReliableSender::new(socket, "239.1.2.3:5000".parse().unwrap(), ReliableSenderOpts::default()).unwrap();
+/// sender.send(b"config version 7").unwrap();
+/// loop {
+///     // retransmit requested datagrams and send heartbeats
+///     sender.poll(Duration::from_secs(1)).unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ReliableSender {
+    socket: UdpSocket,
+    destination: SocketAddr,
+    opts: ReliableSenderOpts,
+    session: u32,
+    /// the last sent sequence number
+    sequence: u32,
+    window: VecDeque<Sent>,
+    next_heartbeat: Instant,
+    retransmissions: u64,
+    stats: StatsCounter,
+}
+
+impl ReliableSender {
+
+    /// Creates a sender of a new session with a random id to the destination (the group and
+    /// port). Fails with InvalidInput for an empty window, a zero heartbeat interval or a maximum
+    /// datagram length without room for a fragment.
+    pub fn new(socket: UdpSocket, destination: SocketAddr, opts: ReliableSenderOpts) -> Result<ReliableSender> {
+        if opts.window == 0 || opts.heartbeat_interval.is_zero() {
+            return Err(Error::new(ErrorKind::InvalidInput, "empty window or zero heartbeat interval"));
+        }
+        if opts.max_datagram <= HEADER_LEN + FRAGMENT_HEADER_LEN {
+            return Err(Error::new(ErrorKind::InvalidInput, "maximum datagram length too small"));
+        }
+        let mut session = [0_u8; 4];
+        super::random::random_bytes(&mut session);
+        Ok(ReliableSender {
+            socket, destination, opts, session: u32::from_ne_bytes(session), sequence: 0, window: VecDeque::new(),
+            next_heartbeat: Instant::now() + opts.heartbeat_interval, retransmissions: 0, stats: StatsCounter::new(),
+        })
+    }
+
+    /// Returns the session id.
+    pub fn session(&self) -> u32 {
+        self.session
+    }
+
+    /// Returns the sequence number of the last sent datagram (or fragment), 0 before the first.
+    pub fn sequence(&self) -> u32 {
+        self.sequence
+    }
+
+    /// Returns the socket.
+    pub fn get_ref(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// Returns the number of retransmitted datagrams.
+    pub fn retransmissions(&self) -> u64 {
+        self.retransmissions
+    }
+
+    /// Returns the statistics of the packets sent and received by the sender.
+    pub fn stats(&self) -> SocketStats {
+        self.stats.snapshot()
+    }
+
+    /// Sends the message, in fragments if it is longer than fits into a datagram, and returns
+    /// its (first) sequence number. Fails with InvalidInput if it needs more than 65535 fragments.
+    pub fn send(&mut self, message: &[u8]) -> Result<u32> {
+        let first = self.sequence.wrapping_add(1);
+        if HEADER_LEN + message.len() <= self.opts.max_datagram {
+            self.send_data(false, message.to_vec())?;
+        } else {
+            for fragment in fragment::fragment(first, message, self.opts.max_datagram - HEADER_LEN)? {
+                self.send_data(true, fragment)?;
+            }
+        }
+        Ok(first)
+    }
+
+    fn send_data(&mut self, fragment: bool, payload: Vec<u8>) -> Result<()> {
+        let sequence = self.sequence.wrapping_add(1);
+        let packet = Packet::Data { session: self.session, sequence, fragment, payload }.encode();
+        self.socket.send_to(&packet, self.destination)?;
+        self.stats.sent(packet.len());
+        self.sequence = sequence;
+        self.window.push_back(Sent { sequence, packet, retransmitted: None });
+        if self.window.len() > self.opts.window {
+            self.window.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Receives NACKs and retransmits the requested datagrams, and sends heartbeats when they
+    /// are due, for the given time.
+    pub fn poll(&mut self, timeout: Duration) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+        let mut buffer = vec![0_u8; 65536];
+        loop {
+            let now = Instant::now();
+            if now >= self.next_heartbeat {
+                self.heartbeat()?;
+            }
+            if now >= deadline {
+                return Ok(());
+            }
+            let wait = deadline.min(self.next_heartbeat).saturating_duration_since(now).max(Duration::from_millis(1));
+            self.socket.set_read_timeout(Some(wait))?;
+            match self.socket.recv_from(&mut buffer) {
+                Ok((len, _)) => {
+                    self.stats.received(len);
+                    if let Some(Packet::Nack { session, sequences }) = Packet::parse(&buffer[..len]) {
+                        if session == self.session {
+                            self.retransmit(&sequences)?;
+                        }
+                    }
+                },
+                Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {},
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Sends a heartbeat now and schedules the next one.
+    pub fn heartbeat(&mut self) -> Result<()> {
+        let trail = self.window.front().map_or(self.sequence.wrapping_add(1), |sent| sent.sequence);
+        let packet = Packet::Heartbeat { session: self.session, lead: self.sequence, trail }.encode();
+        self.socket.send_to(&packet, self.destination)?;
+        self.stats.sent(packet.len());
+        self.next_heartbeat = Instant::now() + self.opts.heartbeat_interval;
+        Ok(())
+    }
+
+    /// Retransmits the requested datagrams, a heartbeat tells the receivers about requested
+    /// datagrams which already left the window.
+    fn retransmit(&mut self, sequences: &[u32]) -> Result<()> {
+        let now = Instant::now();
+        let first = match self.window.front() {
+            Some(sent) => sent.sequence,
+            None => return self.heartbeat(),
+        };
+        let holdoff = self.opts.retransmit_holdoff;
+        let mut expired = false;
+        for sequence in sequences {
+            let index = offset(first, *sequence);
+            if index < 0 {
+                expired = true;
+                continue;
+            }
+            let sent = match self.window.get_mut(index as usize) {
+                Some(sent) => sent,
+                None => continue,
+            };
+            if sent.retransmitted.is_some_and(|at| now.saturating_duration_since(at) < holdoff) {
+                continue;
+            }
+            self.socket.send_to(&sent.packet, self.destination)?;
+            self.stats.sent(sent.packet.len());
+            sent.retransmitted = Some(now);
+            self.retransmissions += 1;
+        }
+        if expired {
+            self.heartbeat()?;
+        }
+        Ok(())
+    }
+}
+
+/// Options of a `ReliableReceiver`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ReliableReceiverOpts {
+    /// maximum random delay of the first NACK for a missing datagram, which spreads the NACKs
+    /// of many receivers for the same loss
+    pub nack_delay: Duration,
+
+    /// interval between NACKs for a datagram which is still missing
+    pub nack_interval: Duration,
+
+    /// number of NACKs for a datagram before it is reported lost
+    pub nack_retries: u32,
+
+    /// maximum number of missing datagrams of a sender, a larger gap is reported lost
+    pub max_gap: u32,
+
+    /// time after which the state of a silent sender is dropped
+    pub sender_timeout: Duration,
+}
+
+impl Default for ReliableReceiverOpts {
+    fn default() -> ReliableReceiverOpts {
+        ReliableReceiverOpts {
+            nack_delay: Duration::from_millis(10),
+            nack_interval: Duration::from_millis(200),
+            nack_retries: 5,
+            max_gap: 1024,
+            sender_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+/// A result of `ReliableReceiver::recv`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Delivery {
+    /// the next message of the session of the sender with its (first) sequence number
+    Data { sender: SocketAddr, session: u32, sequence: u32, payload: Vec<u8> },
+
+    /// `count` datagrams of the session starting at the sequence number could not be recovered,
+    /// a message with a lost fragment is dropped
+    Lost { sender: SocketAddr, session: u32, sequence: u32, count: u32 },
+}
+
+/// A missing datagram of a sender.
+#[derive(Clone, Copy, Debug)]
+struct Missing {
+    nack_at: Instant,
+    retries: u32,
+    abandoned: bool,
+}
+
+/// A message of which the first fragments were delivered.
+#[derive(Debug)]
+struct Message {
+    sequence: u32,
+    next_index: u16,
+    count: u16,
+    data: Vec<u8>,
+}
+
+/// Reception state of a session of a sender.
+#[derive(Debug)]
+struct Session {
+    /// the next sequence number to deliver
+    next: u32,
+    /// the highest sequence number known to be sent
+    highest: u32,
+    /// received data packets ahead of `next`, whether they are fragments and their payload
+    received: HashMap<u32, (bool, Vec<u8>)>,
+    missing: HashMap<u32, Missing>,
+    message: Option<Message>,
+    last_heard: Instant,
+}
+
+impl Session {
+
+    fn new(next: u32, now: Instant) -> Session {
+        Session { next, highest: next.wrapping_sub(1), received: HashMap::new(), missing: HashMap::new(),
+                  message: None, last_heard: now }
+    }
+
+    /// Delivers a data packet, or adds the fragment to its message and delivers the message
+    /// with the last one.
+    fn output(&mut self, (sender, session): (SocketAddr, u32), sequence: u32, (fragment, payload): (bool, Vec<u8>),
+              ready: &mut VecDeque<Delivery>) {
+        if !fragment {
+            self.message = None;
+            ready.push_back(Delivery::Data { sender, session, sequence, payload });
+            return;
+        }
+        let fragment = match Fragment::parse(&payload) {
+            Some(fragment) => fragment,
+            None => {
+                self.message = None;
+                return;
+            },
+        };
+        if fragment.index == 0 {
+            self.message = Some(Message { sequence: fragment.message_id, next_index: 0, count: fragment.count,
+                                          data: Vec::new() });
+        }
+        match self.message.as_mut() {
+            Some(message) if message.sequence == fragment.message_id && message.next_index == fragment.index
+                && message.count == fragment.count => {
+                message.data.extend_from_slice(fragment.data);
+                message.next_index += 1;
+                if message.next_index == message.count {
+                    let sequence = message.sequence;
+                    let payload = std::mem::take(&mut message.data);
+                    self.message = None;
+                    ready.push_back(Delivery::Data { sender, session, sequence, payload });
+                }
+            },
+            _ => self.message = None,
+        }
+    }
+
+    /// Reports datagrams lost, which drops the message of a lost fragment.
+    fn lose(&mut self, (sender, session): (SocketAddr, u32), sequence: u32, count: u32,
+            ready: &mut VecDeque<Delivery>) {
+        self.message = None;
+        ready.push_back(Delivery::Lost { sender, session, sequence, count });
+    }
+
+    /// Records the datagrams up to `sequence` as sent, those not received as missing.
+    fn advance(&mut self, sequence: u32, opts: &ReliableReceiverOpts, key: (SocketAddr, u32), now: Instant,
+               ready: &mut VecDeque<Delivery>) {
+        if offset(self.next, sequence) >= opts.max_gap as i32 {
+            self.skip_to(sequence.wrapping_sub(opts.max_gap / 2), key, ready);
+        }
+        while offset(self.highest, sequence) > 0 {
+            self.highest = self.highest.wrapping_add(1);
+            if !self.received.contains_key(&self.highest) && offset(self.next, self.highest) >= 0 {
+                let nack_at = now + random_delay(opts.nack_delay);
+                self.missing.insert(self.highest, Missing { nack_at, retries: 0, abandoned: false });
+            }
+        }
+    }
+
+    fn receive(&mut self, sequence: u32, payload: (bool, Vec<u8>), opts: &ReliableReceiverOpts, key: (SocketAddr, u32),
+               now: Instant, ready: &mut VecDeque<Delivery>) {
+        if offset(self.next, sequence) < 0 || self.received.contains_key(&sequence) {
+            return;
+        }
+        self.advance(sequence, opts, key, now, ready);
+        if offset(self.next, sequence) >= 0 {
+            self.missing.remove(&sequence);
+            self.received.insert(sequence, payload);
+        }
+        self.deliver(key, ready);
+    }
+
+    /// Delivers the received datagrams in order and reports abandoned ones lost.
+    fn deliver(&mut self, key: (SocketAddr, u32), ready: &mut VecDeque<Delivery>) {
+        loop {
+            if let Some(payload) = self.received.remove(&self.next) {
+                self.output(key, self.next, payload, ready);
+                self.next = self.next.wrapping_add(1);
+                continue;
+            }
+            let sequence = self.next;
+            while self.missing.get(&self.next).is_some_and(|missing| missing.abandoned) {
+                self.missing.remove(&self.next);
+                self.next = self.next.wrapping_add(1);
+            }
+            match self.next.wrapping_sub(sequence) {
+                0 => return,
+                count => self.lose(key, sequence, count, ready),
+            }
+        }
+    }
+
+    /// Gives up the datagrams before `target`: delivers the received ones and reports the
+    /// others lost.
+    fn skip_to(&mut self, target: u32, key: (SocketAddr, u32), ready: &mut VecDeque<Delivery>) {
+        let end = offset(self.next, target);
+        if end <= 0 {
+            return;
+        }
+        let mut received: Vec<u32> = self.received.keys().copied().filter(|s| offset(self.next, *s) < end).collect();
+        received.sort_by_key(|s| offset(self.next, *s));
+        let mut sequence = self.next;
+        for s in received {
+            let count = s.wrapping_sub(sequence);
+            if count > 0 {
+                self.lose(key, sequence, count, ready);
+            }
+            if let Some(payload) = self.received.remove(&s) {
+                self.output(key, s, payload, ready);
+            }
+            sequence = s.wrapping_add(1);
+        }
+        let count = target.wrapping_sub(sequence);
+        if count > 0 {
+            self.lose(key, sequence, count, ready);
+        }
+        let next = self.next;
+        self.missing.retain(|s, _| offset(next, *s) >= end);
+        self.next = target;
+        if offset(self.highest, target) > 0 {
+            self.highest = target.wrapping_sub(1);
+        }
+    }
+}
+
+/// Receiver of reliable multicast sessions, e.g. on a socket of `MulticastSocketBuilder`.
+/// ```no_run
+/// use net_utils::{MulticastSocketBuilder, relmcast::{Delivery, ReliableReceiver, ReliableReceiverOpts}};
+/// let socket = MulticastSocketBuilder::new("239.1.2.3:5000".parse().unwrap(), "192.168.1.3".parse().unwrap())
+///     .build_std()
+///     .unwrap();
+/// let mut receiver = ReliableReceiver::new(socket, ReliableReceiverOpts::default());
+/// loop {
+///     match receiver.recv().unwrap() {
+///         Delivery::Data { payload, .. } => println!("{:?}", payload),
+///         Delivery::Lost { count, .. } => println!("{} datagrams lost", count),
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ReliableReceiver {
+    socket: UdpSocket,
+    opts: ReliableReceiverOpts,
+    sessions: HashMap<(SocketAddr, u32), Session>,
+    ready: VecDeque<Delivery>,
+    read_timeout: Option<Duration>,
+    stats: StatsCounter,
+}
+
+impl ReliableReceiver {
+
+    /// Wraps the socket receiving from the group.
+    pub fn new(socket: UdpSocket, opts: ReliableReceiverOpts) -> ReliableReceiver {
+        ReliableReceiver { socket, opts, sessions: HashMap::new(), ready: VecDeque::new(), read_timeout: None,
+                           stats: StatsCounter::new() }
+    }
+
+    /// Returns the socket.
+    pub fn get_ref(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// Returns the statistics of the packets received and sent (NACKs) by the receiver.
+    pub fn stats(&self) -> SocketStats {
+        self.stats.snapshot()
+    }
+
+    /// Sets the timeout of `recv`, None blocks indefinitely.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.read_timeout = timeout;
+    }
+
+    /// Returns the next datagram of a sender in order or the report of lost datagrams, sending
+    /// NACKs for missing datagrams meanwhile. Fails with TimedOut after the read timeout.
+    pub fn recv(&mut self) -> Result<Delivery> {
+        let deadline = self.read_timeout.map(|timeout| Instant::now() + timeout);
+        let mut buffer = vec![0_u8; 65536];
+        loop {
+            if let Some(delivery) = self.ready.pop_front() {
+                return Ok(delivery);
+            }
+            let now = Instant::now();
+            self.send_nacks(now)?;
+            self.forget_silent(now);
+            if !self.ready.is_empty() {
+                continue;
+            }
+            if deadline.is_some_and(|deadline| now >= deadline) {
+                return Err(Error::new(ErrorKind::TimedOut, "no datagram"));
+            }
+            let wakeup = self.sessions.values()
+                .flat_map(|session| session.missing.values().filter(|missing| !missing.abandoned)
+                    .map(|missing| missing.nack_at)
+                    .chain(std::iter::once(session.last_heard + self.opts.sender_timeout)))
+                .chain(deadline)
+                .min();
+            let timeout = wakeup.map(|wakeup| wakeup.saturating_duration_since(now).max(Duration::from_millis(1)));
+            self.socket.set_read_timeout(timeout)?;
+            match self.socket.recv_from(&mut buffer) {
+                Ok((len, source)) => {
+                    self.stats.received(len);
+                    if let Some(packet) = Packet::parse(&buffer[..len]) {
+                        self.process(packet, source, Instant::now());
+                    }
+                },
+                Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {},
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn process(&mut self, packet: Packet, source: SocketAddr, now: Instant) {
+        match packet {
+            Packet::Data { session, sequence, fragment, payload } => {
+                let key = (source, session);
+                let state = self.sessions.entry(key).or_insert_with(|| Session::new(sequence, now));
+                state.last_heard = now;
+                state.receive(sequence, (fragment, payload), &self.opts, key, now, &mut self.ready);
+            },
+            Packet::Heartbeat { session, lead, trail } => {
+                let key = (source, session);
+                let state = self.sessions.entry(key).or_insert_with(|| Session::new(lead.wrapping_add(1), now));
+                state.last_heard = now;
+                state.advance(lead, &self.opts, key, now, &mut self.ready);
+                state.skip_to(trail, key, &mut self.ready);
+                state.deliver(key, &mut self.ready);
+            },
+            Packet::Nack { .. } => {},
+        }
+    }
+
+    /// Sends the due NACKs and abandons datagrams after the last retry.
+    fn send_nacks(&mut self, now: Instant) -> Result<()> {
+        for (key, state) in self.sessions.iter_mut() {
+            let mut sequences = Vec::new();
+            for (sequence, missing) in state.missing.iter_mut().filter(|(_, m)| !m.abandoned && m.nack_at <= now) {
+                if missing.retries >= self.opts.nack_retries {
+                    missing.abandoned = true;
+                } else {
+                    missing.retries += 1;
+                    missing.nack_at = now + self.opts.nack_interval;
+                    sequences.push(*sequence);
+                }
+            }
+            sequences.sort_by_key(|sequence| offset(state.next, *sequence));
+            for chunk in sequences.chunks(MAX_NACK_SEQUENCES) {
+                let packet = Packet::Nack { session: key.1, sequences: chunk.to_vec() }.encode();
+                self.socket.send_to(&packet, key.0)?;
+                self.stats.sent(packet.len());
+            }
+            state.deliver(*key, &mut self.ready);
+        }
+        Ok(())
+    }
+
+    /// Drops the state of senders silent for the sender timeout, reporting their missing
+    /// datagrams lost.
+    fn forget_silent(&mut self, now: Instant) {
+        let timeout = self.opts.sender_timeout;
+        let silent: Vec<_> = self.sessions.iter()
+            .filter(|(_, state)| now.saturating_duration_since(state.last_heard) >= timeout)
+            .map(|(key, _)| *key)
+            .collect();
+        for key in silent {
+            if let Some(mut state) = self.sessions.remove(&key) {
+                let end = state.highest.wrapping_add(1);
+                state.skip_to(end, key, &mut self.ready);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_packets() {
+        let packets = [
+            Packet::Data { session: 7, sequence: 1, fragment: false, payload: b"abc".to_vec() },
+            Packet::Data { session: 7, sequence: 2, fragment: true, payload: b"abc".to_vec() },
+            Packet::Heartbeat { session: 7, lead: 10, trail: 3 },
+            Packet::Nack { session: 7, sequences: vec![4, 6] },
+        ];
+        for packet in packets.iter() {
+            assert_eq!(Packet::parse(&packet.encode()).as_ref(), Some(packet));
+        }
+        assert_eq!(packets[3].encode().len(), HEADER_LEN + 2 + 8);
+        assert_eq!(Packet::parse(&packets[3].encode()[..HEADER_LEN + 5]), None);
+        assert_eq!(Packet::parse(&[2, 1, 0, 0, 0, 7, 0, 0, 0, 1]), None);
+    }
+
+    #[test]
+    fn test_session() {
+        let opts = ReliableReceiverOpts { nack_delay: Duration::ZERO, ..ReliableReceiverOpts::default() };
+        let key: (SocketAddr, u32) = ("192.0.2.1:5000".parse().unwrap(), 7);
+        let now = Instant::now();
+        let mut ready = VecDeque::new();
+        let mut session = Session::new(u32::MAX, now);
+        let data = |sequence: u32| Delivery::Data { sender: key.0, session: 7, sequence, payload: vec![sequence as u8] };
+
+        // in order across the wrap of the sequence numbers
+        session.receive(u32::MAX, (false, vec![255]), &opts, key, now, &mut ready);
+        session.receive(0, (false, vec![0]), &opts, key, now, &mut ready);
+        assert_eq!(ready.drain(..).collect::<Vec<_>>(), vec![data(u32::MAX), data(0)]);
+
+        // 1 and 2 are missing, 3 is held back until they arrive or are given up
+        session.receive(3, (false, vec![3]), &opts, key, now, &mut ready);
+        assert!(ready.is_empty());
+        let mut missing: Vec<_> = session.missing.keys().copied().collect();
+        missing.sort_unstable();
+        assert_eq!(missing, vec![1, 2]);
+        session.receive(1, (false, vec![1]), &opts, key, now, &mut ready);
+        assert_eq!(ready.drain(..).collect::<Vec<_>>(), vec![data(1)]);
+        session.missing.get_mut(&2).unwrap().abandoned = true;
+        session.deliver(key, &mut ready);
+        assert_eq!(ready.drain(..).collect::<Vec<_>>(), vec![
+            Delivery::Lost { sender: key.0, session: 7, sequence: 2, count: 1 }, data(3),
+        ]);
+
+        // a heartbeat announces the loss of 4 and 5, of which 4 is no longer retransmittable
+        session.advance(5, &opts, key, now, &mut ready);
+        session.skip_to(5, key, &mut ready);
+        assert_eq!(ready.drain(..).collect::<Vec<_>>(), vec![
+            Delivery::Lost { sender: key.0, session: 7, sequence: 4, count: 1 },
+        ]);
+        assert_eq!(session.missing.keys().copied().collect::<Vec<_>>(), vec![5]);
+        // duplicates are ignored
+        session.receive(3, (false, vec![3]), &opts, key, now, &mut ready);
+        session.receive(5, (false, vec![5]), &opts, key, now, &mut ready);
+        session.receive(5, (false, vec![5]), &opts, key, now, &mut ready);
+        assert_eq!(ready.drain(..).collect::<Vec<_>>(), vec![data(5)]);
+    }
+
+    #[test]
+    fn test_fragments() {
+        let opts = ReliableReceiverOpts::default();
+        let key: (SocketAddr, u32) = ("192.0.2.1:5000".parse().unwrap(), 7);
+        let now = Instant::now();
+        let mut ready = VecDeque::new();
+        let mut session = Session::new(1, now);
+        let data = |sequence: u32, payload: &[u8]| Delivery::Data { sender: key.0, session: 7, sequence,
+                                                                    payload: payload.to_vec() };
+        let fragments = |sequence: u32| fragment::fragment(sequence, b"abcdef", FRAGMENT_HEADER_LEN + 2).unwrap();
+
+        // a message is delivered with its last fragment
+        for (i, fragment) in fragments(1).into_iter().enumerate().rev() {
+            session.receive(1 + i as u32, (true, fragment), &opts, key, now, &mut ready);
+        }
+        assert_eq!(ready.drain(..).collect::<Vec<_>>(), vec![data(1, b"abcdef")]);
+
+        // the message of a lost fragment is dropped, the next one is delivered
+        for (sequence, fragment) in (4..).zip(fragments(4).into_iter().chain(fragments(7))) {
+            if sequence != 5 {
+                session.receive(sequence, (true, fragment), &opts, key, now, &mut ready);
+            }
+        }
+        session.receive(10, (false, b"x".to_vec()), &opts, key, now, &mut ready);
+        session.missing.get_mut(&5).unwrap().abandoned = true;
+        session.deliver(key, &mut ready);
+        assert_eq!(ready.drain(..).collect::<Vec<_>>(), vec![
+            Delivery::Lost { sender: key.0, session: 7, sequence: 5, count: 1 }, data(7, b"abcdef"), data(10, b"x"),
+        ]);
+    }
+}
@@ -0,0 +1,254 @@
+//! RTP (RFC 3550) multicast sessions: the RTP and RTCP socket pair on an even and the following
+//! odd port with TTL and DSCP for media streams, and parsing and building of RTP headers.
+
+use std::{
+    convert::TryInto,
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, SocketAddr, UdpSocket},
+};
+
+/// RTP version of the header.
+pub const RTP_VERSION: u8 = 2;
+
+/// Size of the fixed RTP header without CSRCs and extension.
+pub const RTP_HEADER_LEN: usize = 12;
+
+/// DSCP expedited forwarding, recommended for audio.
+pub const DSCP_EF: u8 = 46;
+
+/// DSCP assured forwarding class 4 low drop, recommended for interactive video.
+pub const DSCP_AF41: u8 = 34;
+
+/// DSCP class selector 5, recommended for signaling and broadcast video.
+pub const DSCP_CS5: u8 = 40;
+
+/// Header extension of an RTP packet.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RtpExtension {
+    /// profile specific identifier (e.g. 0xbede for RFC 8285 one-byte headers)
+    pub profile: u16,
+
+    /// extension data, a multiple of four bytes
+    pub data: Vec<u8>,
+}
+
+/// Header of an RTP packet.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct RtpHeader {
+    /// the packet is the last of a frame or talkspurt, depending on the profile (M bit)
+    pub marker: bool,
+
+    /// payload type (7 bits)
+    pub payload_type: u8,
+
+    /// sequence number
+    pub sequence: u16,
+
+    /// media timestamp in units of the payload's clock rate
+    pub timestamp: u32,
+
+    /// synchronization source
+    pub ssrc: u32,
+
+    /// contributing sources (at most 15)
+    pub csrcs: Vec<u32>,
+
+    /// header extension
+    pub extension: Option<RtpExtension>,
+}
+
+impl RtpHeader {
+
+    /// Parses an RTP packet, returns the header and the payload without padding. None if the
+    /// packet is no valid RTP version 2 packet.
+    pub fn parse(packet: &[u8]) -> Option<(RtpHeader, &[u8])> {
+        let u32_at = |offset: usize| Some(u32::from_be_bytes(packet.get(offset..offset + 4)?.try_into().ok()?));
+        if packet.len() < RTP_HEADER_LEN || packet[0] >> 6 != RTP_VERSION {
+            return None;
+        }
+        let csrc_count = (packet[0] & 0x0f) as usize;
+        let mut header = RtpHeader {
+            marker: packet[1] & 0x80 != 0,
+            payload_type: packet[1] & 0x7f,
+            sequence: u16::from_be_bytes([packet[2], packet[3]]),
+            timestamp: u32_at(4)?,
+            ssrc: u32_at(8)?,
+            csrcs: (0..csrc_count).map(|i| u32_at(RTP_HEADER_LEN + 4 * i)).collect::<Option<Vec<u32>>>()?,
+            extension: None,
+        };
+        let mut offset = RTP_HEADER_LEN + 4 * csrc_count;
+        if packet[0] & 0x10 != 0 {
+            let extension = packet.get(offset..offset + 4)?;
+            let len = 4 * u16::from_be_bytes([extension[2], extension[3]]) as usize;
+            header.extension = Some(RtpExtension {
+                profile: u16::from_be_bytes([extension[0], extension[1]]),
+                data: packet.get(offset + 4..offset + 4 + len)?.to_vec(),
+            });
+            offset += 4 + len;
+        }
+        let mut end = packet.len();
+        if packet[0] & 0x20 != 0 {
+            let padding = *packet.last()? as usize;
+            if padding == 0 || padding > end - offset {
+                return None;
+            }
+            end -= padding;
+        }
+        Some((header, &packet[offset..end]))
+    }
+
+    /// Appends the header in wire format to the buffer, followed by the payload it is the header of.
+    pub fn encode(&self, buffer: &mut Vec<u8>) -> Result<()> {
+        if self.csrcs.len() > 15 || self.payload_type > 0x7f {
+            return Err(Error::new(ErrorKind::InvalidInput, "more than 15 CSRCs or payload type above 127"));
+        }
+        let extension = self.extension.is_some() as u8;
+        buffer.push(RTP_VERSION << 6 | extension << 4 | self.csrcs.len() as u8);
+        buffer.push((self.marker as u8) << 7 | self.payload_type);
+        buffer.extend_from_slice(&self.sequence.to_be_bytes());
+        buffer.extend_from_slice(&self.timestamp.to_be_bytes());
+        buffer.extend_from_slice(&self.ssrc.to_be_bytes());
+        for csrc in &self.csrcs {
+            buffer.extend_from_slice(&csrc.to_be_bytes());
+        }
+        if let Some(extension) = &self.extension {
+            if !extension.data.len().is_multiple_of(4) || extension.data.len() / 4 > u16::MAX as usize {
+                return Err(Error::new(ErrorKind::InvalidInput, "RTP extension length is no multiple of four"));
+            }
+            buffer.extend_from_slice(&extension.profile.to_be_bytes());
+            buffer.extend_from_slice(&((extension.data.len() / 4) as u16).to_be_bytes());
+            buffer.extend_from_slice(&extension.data);
+        }
+        Ok(())
+    }
+}
+
+/// The RTP and RTCP sockets of a multicast session, RTP on the even port of the group and RTCP
+/// on the following odd port.
+#[derive(Debug)]
+pub struct RtpSession {
+    rtp: UdpSocket,
+    rtcp: UdpSocket,
+    group: SocketAddr,
+}
+
+impl RtpSession {
+
+    /// Joins the group on the interface (address of the group's family, unspecified for the
+    /// default multicast interface) with sockets for RTP at the group's even port and RTCP at the
+    /// next port. Sent packets use the interface, the multicast TTL (hop limit) and the DSCP.
+    pub fn join(group: SocketAddr, interface: &IpAddr, ttl: u32, dscp: u8) -> Result<RtpSession> {
+        if !group.port().is_multiple_of(2) {
+            return Err(Error::new(ErrorKind::InvalidInput, "RTP port must be even"));
+        }
+        if dscp > 63 {
+            return Err(Error::new(ErrorKind::InvalidInput, "DSCP above 63"));
+        }
+        let rtcp_group = SocketAddr::new(group.ip(), group.port() + 1);
+        let rtp = session_socket(&group, interface, ttl, dscp)?;
+        let rtcp = session_socket(&rtcp_group, interface, ttl, dscp)?;
+        Ok(RtpSession { rtp, rtcp, group })
+    }
+
+    /// Returns the socket receiving and sending RTP packets.
+    pub fn rtp_socket(&self) -> &UdpSocket {
+        &self.rtp
+    }
+
+    /// Returns the socket receiving and sending RTCP packets.
+    pub fn rtcp_socket(&self) -> &UdpSocket {
+        &self.rtcp
+    }
+
+    /// Returns the group and port RTP packets are sent to.
+    pub fn rtp_destination(&self) -> SocketAddr {
+        self.group
+    }
+
+    /// Returns the group and port RTCP packets are sent to.
+    pub fn rtcp_destination(&self) -> SocketAddr {
+        SocketAddr::new(self.group.ip(), self.group.port() + 1)
+    }
+
+    /// Sends an RTP packet to the group.
+    pub fn send_rtp(&self, packet: &[u8]) -> Result<usize> {
+        self.rtp.send_to(packet, self.rtp_destination())
+    }
+
+    /// Sends an RTCP packet to the group.
+    pub fn send_rtcp(&self, packet: &[u8]) -> Result<usize> {
+        self.rtcp.send_to(packet, self.rtcp_destination())
+    }
+}
+
+/// Creates a multicast socket of the session for the group and port.
+fn session_socket(group: &SocketAddr, interface: &IpAddr, ttl: u32, dscp: u8) -> Result<UdpSocket> {
+    match (group, interface) {
+        (SocketAddr::V4(group), IpAddr::V4(interface)) => {
+            let socket = super::create_std_multicast_socket_ipv4(group, interface)?;
+            let socket_ref = socket2::SockRef::from(&socket);
+            if !interface.is_unspecified() {
+                socket_ref.set_multicast_if_v4(interface)?;
+            }
+            socket_ref.set_multicast_ttl_v4(ttl)?;
+            socket_ref.set_tos((dscp << 2) as u32)?;
+            Ok(socket)
+        },
+        (SocketAddr::V6(group), IpAddr::V6(interface)) => {
+            let socket = super::create_std_multicast_socket_ipv6(group, interface)?;
+            let socket_ref = socket2::SockRef::from(&socket);
+            socket_ref.set_multicast_if_v6(super::multicast::find_interface_index(interface)?)?;
+            socket_ref.set_multicast_hops_v6(ttl)?;
+            set_traffic_class(&socket_ref, dscp)?;
+            Ok(socket)
+        },
+        _ => Err(Error::new(ErrorKind::InvalidInput,
+                            format!("interface {} is not of the address family of group {}", interface, group))),
+    }
+}
+
+#[cfg(not(windows))]
+fn set_traffic_class(socket: &socket2::SockRef<'_>, dscp: u8) -> Result<()> {
+    socket.set_tclass_v6((dscp << 2) as u32)
+}
+
+/// Windows sets the traffic class only with the QoS API (qWAVE), it is left unchanged.
+#[cfg(windows)]
+fn set_traffic_class(_socket: &socket2::SockRef<'_>, _dscp: u8) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_rtp_header() {
+        let header = RtpHeader {
+            marker: true,
+            payload_type: 96,
+            sequence: 0x1234,
+            timestamp: 90000,
+            ssrc: 0xdeadbeef,
+            csrcs: vec![7],
+            extension: Some(RtpExtension { profile: 0xbede, data: vec![0x10, 0xaa, 0, 0] }),
+        };
+        let mut packet = Vec::new();
+        header.encode(&mut packet).unwrap();
+        assert_eq!(&packet[..4], &[0x91, 0xe0, 0x12, 0x34]);
+        packet.extend_from_slice(b"payload");
+        assert_eq!(RtpHeader::parse(&packet), Some((header.clone(), &b"payload"[..])));
+
+        // padding
+        packet[0] |= 0x20;
+        packet.extend_from_slice(&[0, 0, 3]);
+        assert_eq!(RtpHeader::parse(&packet).unwrap().1, b"payload");
+        let last = packet.len() - 1;
+        packet[last] = 100;
+        assert_eq!(RtpHeader::parse(&packet), None);
+
+        assert_eq!(RtpHeader::parse(&[0x40; 12]), None);
+        assert!(RtpHeader { csrcs: vec![0; 16], ..header }.encode(&mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,503 @@
+//! Session Announcement Protocol (RFC 2974) with Session Description Protocol (RFC 8866) payloads:
+//! discovery of multicast media sessions (e.g. AES67 streams) and announcement of own sessions.
+
+use std::{
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, UdpSocket},
+    time::Duration,
+};
+
+#[cfg(unix)]
+use super::shutdown::ShutdownHandle;
+
+/// IPv4 multicast group of global scope SAP announcements, also used by AES67 devices.
+pub const SAP_MULTICAST_V4: Ipv4Addr = Ipv4Addr::new(224, 2, 127, 254);
+
+/// Well known SAP port.
+pub const SAP_PORT: u16 = 9875;
+
+/// Payload type of session descriptions.
+pub const SDP_PAYLOAD_TYPE: &str = "application/sdp";
+
+/// Announcement interval commonly used by AES67 devices, RFC 2974 derives it from the bandwidth.
+pub const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// SAP version of the header.
+const SAP_VERSION: u8 = 1;
+
+const FLAG_IPV6: u8 = 0x10;
+const FLAG_DELETION: u8 = 0x04;
+const FLAG_ENCRYPTED: u8 = 0x02;
+const FLAG_COMPRESSED: u8 = 0x01;
+
+/// A SAP announcement or deletion of a session.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SapAnnouncement {
+    /// the session is deleted instead of announced (T bit)
+    pub deletion: bool,
+
+    /// identifies the version of the session together with the origin
+    pub message_id_hash: u16,
+
+    /// address of the announcer
+    pub origin: IpAddr,
+
+    /// MIME type of the payload, SDP_PAYLOAD_TYPE for session descriptions
+    pub payload_type: String,
+
+    /// payload, the session description text for SDP
+    pub payload: String,
+}
+
+impl SapAnnouncement {
+
+    /// Creates the announcement of a session description from the origin, the message id hash is
+    /// derived from the description so that it changes with the description.
+    pub fn new(origin: IpAddr, sdp: &str) -> SapAnnouncement {
+        // FNV-1a folded to 16 bits, zero would mean "no hash"
+        let hash = sdp.bytes().fold(0x811c9dc5_u32, |hash, b| (hash ^ b as u32).wrapping_mul(0x01000193));
+        let message_id_hash = match (hash >> 16) as u16 ^ hash as u16 {
+            0 => 1,
+            hash => hash,
+        };
+        SapAnnouncement {
+            deletion: false,
+            message_id_hash,
+            origin,
+            payload_type: SDP_PAYLOAD_TYPE.to_string(),
+            payload: sdp.to_string(),
+        }
+    }
+
+    /// Parses a SAP packet, decompressing a zlib compressed payload. None if the packet is invalid
+    /// or encrypted.
+    pub fn parse(packet: &[u8]) -> Option<SapAnnouncement> {
+        let flags = *packet.first()?;
+        if flags >> 5 != SAP_VERSION || flags & FLAG_ENCRYPTED != 0 {
+            return None;
+        }
+        let auth_len = 4 * *packet.get(1)? as usize;
+        let message_id_hash = u16::from_be_bytes([*packet.get(2)?, *packet.get(3)?]);
+        let (origin, offset) = if flags & FLAG_IPV6 != 0 {
+            let mut address = [0_u8; 16];
+            address.copy_from_slice(packet.get(4..20)?);
+            (IpAddr::V6(Ipv6Addr::from(address)), 20)
+        } else {
+            let address = packet.get(4..8)?;
+            (IpAddr::V4(Ipv4Addr::new(address[0], address[1], address[2], address[3])), 8)
+        };
+        let data = packet.get(offset + auth_len..)?;
+        let decompressed;
+        let data = if flags & FLAG_COMPRESSED != 0 {
+            decompressed = super::inflate::zlib_decompress(data)?;
+            &decompressed[..]
+        } else {
+            data
+        };
+        // the payload type is optional if the payload is a session description
+        let (payload_type, payload) = if data.starts_with(b"v=0") {
+            (SDP_PAYLOAD_TYPE, data)
+        } else {
+            let end = data.iter().position(|b| *b == 0)?;
+            (std::str::from_utf8(&data[..end]).ok()?, &data[end + 1..])
+        };
+        Some(SapAnnouncement {
+            deletion: flags & FLAG_DELETION != 0,
+            message_id_hash,
+            origin,
+            payload_type: payload_type.to_string(),
+            payload: String::from_utf8(payload.to_vec()).ok()?,
+        })
+    }
+
+    /// Returns the announcement in wire format, uncompressed and without authentication.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(24 + self.payload_type.len() + self.payload.len());
+        let mut flags = SAP_VERSION << 5;
+        if self.origin.is_ipv6() {
+            flags |= FLAG_IPV6;
+        }
+        if self.deletion {
+            flags |= FLAG_DELETION;
+        }
+        packet.extend_from_slice(&[flags, 0]);
+        packet.extend_from_slice(&self.message_id_hash.to_be_bytes());
+        match self.origin {
+            IpAddr::V4(origin) => packet.extend_from_slice(&origin.octets()),
+            IpAddr::V6(origin) => packet.extend_from_slice(&origin.octets()),
+        }
+        packet.extend_from_slice(self.payload_type.as_bytes());
+        packet.push(0);
+        packet.extend_from_slice(self.payload.as_bytes());
+        packet
+    }
+
+    /// Parses the payload as session description, None if it is no SDP payload or invalid.
+    pub fn session(&self) -> Option<SessionDescription> {
+        if self.payload_type != SDP_PAYLOAD_TYPE {
+            return None;
+        }
+        SessionDescription::parse(&self.payload)
+    }
+}
+
+/// Originator and identifier of a session (o= line).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SdpOrigin {
+    /// login of the originating user, "-" if none
+    pub username: String,
+
+    /// session id, unique together with username and address
+    pub session_id: String,
+
+    /// version of the session description
+    pub session_version: String,
+
+    /// address (or host name) of the originating host
+    pub address: String,
+}
+
+/// Connection data of a session or media (c= line).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SdpConnection {
+    /// (first) connection address
+    pub address: IpAddr,
+
+    /// TTL of an IPv4 multicast address
+    pub ttl: Option<u8>,
+
+    /// number of consecutive multicast addresses
+    pub count: u32,
+}
+
+/// A media description (m= line and the lines following it).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MediaDescription {
+    /// media type, e.g. "audio" or "video"
+    pub media: String,
+
+    /// transport port
+    pub port: u16,
+
+    /// transport protocol, e.g. "RTP/AVP"
+    pub protocol: String,
+
+    /// media formats, the RTP payload types for RTP
+    pub formats: Vec<String>,
+
+    /// connection data of the media, the session's if None
+    pub connection: Option<SdpConnection>,
+
+    /// attributes (a= lines) as name and value
+    pub attributes: Vec<(String, Option<String>)>,
+}
+
+impl MediaDescription {
+
+    /// Returns the value of the first attribute with the name, Some("") for property attributes.
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        find_attribute(&self.attributes, name)
+    }
+}
+
+/// A session description, the lines not covered by the fields are ignored.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SessionDescription {
+    /// originator of the session
+    pub origin: Option<SdpOrigin>,
+
+    /// session name
+    pub name: String,
+
+    /// session information
+    pub information: Option<String>,
+
+    /// connection data for all media without own connection data
+    pub connection: Option<SdpConnection>,
+
+    /// start and stop time of the first t= line as NTP seconds, zero for unbounded
+    pub timing: Option<(u64, u64)>,
+
+    /// session level attributes as name and value
+    pub attributes: Vec<(String, Option<String>)>,
+
+    /// media of the session
+    pub media: Vec<MediaDescription>,
+}
+
+impl SessionDescription {
+
+    /// Parses a session description with CRLF or LF line endings, None if it does not start with
+    /// the version line or a known line is malformed.
+    pub fn parse(text: &str) -> Option<SessionDescription> {
+        let mut lines = text.lines().map(|line| line.trim_end_matches('\r')).filter(|line| !line.is_empty());
+        if lines.next()? != "v=0" {
+            return None;
+        }
+        let mut session = SessionDescription {
+            origin: None,
+            name: String::new(),
+            information: None,
+            connection: None,
+            timing: None,
+            attributes: Vec::new(),
+            media: Vec::new(),
+        };
+        for line in lines {
+            let (kind, value) = match line.split_once('=') {
+                Some((kind, value)) if kind.len() == 1 => (kind, value),
+                _ => return None,
+            };
+            match (kind, session.media.last_mut()) {
+                ("m", _) => session.media.push(parse_media(value)?),
+                ("c", Some(media)) => media.connection = Some(parse_connection(value)?),
+                ("a", Some(media)) => media.attributes.push(parse_attribute(value)),
+                ("i", Some(_)) => (),
+                ("o", None) => session.origin = Some(parse_origin(value)?),
+                ("s", None) => session.name = value.to_string(),
+                ("i", None) => session.information = Some(value.to_string()),
+                ("c", None) => session.connection = Some(parse_connection(value)?),
+                ("t", None) if session.timing.is_none() => {
+                    let mut times = value.split(' ').map(|time| time.parse::<u64>().ok());
+                    session.timing = Some((times.next()??, times.next()??));
+                },
+                ("a", None) => session.attributes.push(parse_attribute(value)),
+                _ => (),
+            }
+        }
+        Some(session)
+    }
+
+    /// Returns the value of the first session level attribute with the name, Some("") for
+    /// property attributes.
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        find_attribute(&self.attributes, name)
+    }
+
+    /// Returns the connection address and port of the media, None if the session has no
+    /// connection data for it.
+    pub fn media_destination(&self, media: &MediaDescription) -> Option<SocketAddr> {
+        let connection = media.connection.as_ref().or(self.connection.as_ref())?;
+        Some(SocketAddr::new(connection.address, media.port))
+    }
+}
+
+fn parse_origin(value: &str) -> Option<SdpOrigin> {
+    let fields: Vec<&str> = value.split(' ').collect();
+    match fields[..] {
+        [username, session_id, session_version, "IN", _, address] => Some(SdpOrigin {
+            username: username.to_string(),
+            session_id: session_id.to_string(),
+            session_version: session_version.to_string(),
+            address: address.to_string(),
+        }),
+        _ => None,
+    }
+}
+
+/// Parses "IN IP4 <address>[/<ttl>[/<count>]]" or "IN IP6 <address>[/<count>]".
+fn parse_connection(value: &str) -> Option<SdpConnection> {
+    let fields: Vec<&str> = value.split(' ').collect();
+    let (address_type, address) = match fields[..] {
+        ["IN", address_type, address] => (address_type, address),
+        _ => return None,
+    };
+    let mut parts = address.split('/');
+    let address: IpAddr = parts.next()?.parse().ok()?;
+    let numbers = parts.map(|part| part.parse::<u32>().ok()).collect::<Option<Vec<u32>>>()?;
+    match (address_type, address, &numbers[..]) {
+        ("IP4", IpAddr::V4(_), []) => Some(SdpConnection { address, ttl: None, count: 1 }),
+        ("IP4", IpAddr::V4(_), [ttl]) => Some(SdpConnection { address, ttl: Some(*ttl as u8), count: 1 }),
+        ("IP4", IpAddr::V4(_), [ttl, count]) => Some(SdpConnection { address, ttl: Some(*ttl as u8), count: *count }),
+        ("IP6", IpAddr::V6(_), []) => Some(SdpConnection { address, ttl: None, count: 1 }),
+        ("IP6", IpAddr::V6(_), [count]) => Some(SdpConnection { address, ttl: None, count: *count }),
+        _ => None,
+    }
+}
+
+/// Parses "<media> <port>[/<count>] <protocol> <format> ...".
+fn parse_media(value: &str) -> Option<MediaDescription> {
+    let mut fields = value.split(' ');
+    let media = fields.next()?.to_string();
+    let port = fields.next()?.split('/').next()?.parse().ok()?;
+    let protocol = fields.next()?.to_string();
+    Some(MediaDescription {
+        media,
+        port,
+        protocol,
+        formats: fields.map(|format| format.to_string()).collect(),
+        connection: None,
+        attributes: Vec::new(),
+    })
+}
+
+fn parse_attribute(value: &str) -> (String, Option<String>) {
+    match value.split_once(':') {
+        Some((name, value)) => (name.to_string(), Some(value.to_string())),
+        None => (value.to_string(), None),
+    }
+}
+
+fn find_attribute<'a>(attributes: &'a [(String, Option<String>)], name: &str) -> Option<&'a str> {
+    attributes.iter().find(|(n, _)| n == name).map(|(_, value)| value.as_deref().unwrap_or(""))
+}
+
+/// Receives the SAP announcements of the global scope group.
+#[derive(Debug)]
+pub struct SapListener {
+    socket: UdpSocket,
+    #[cfg(unix)]
+    shutdown: ShutdownHandle,
+}
+
+impl SapListener {
+
+    /// Joins SAP_MULTICAST_V4 on the interface, UNSPECIFIED for the default multicast interface.
+    pub fn join(interface: &Ipv4Addr) -> Result<SapListener> {
+        SapListener::join_group(&SAP_MULTICAST_V4, interface)
+    }
+
+    /// Joins the SAP group of another scope (e.g. 239.255.255.255 for the local scope) on the
+    /// interface.
+    pub fn join_group(group: &Ipv4Addr, interface: &Ipv4Addr) -> Result<SapListener> {
+        let socket = super::create_std_multicast_socket_ipv4(&SocketAddrV4::new(*group, SAP_PORT), interface)?;
+        Ok(SapListener {
+            socket,
+            #[cfg(unix)]
+            shutdown: ShutdownHandle::new(),
+        })
+    }
+
+    /// Sets the timeout of `recv`, None to wait for an announcement forever.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        self.socket.set_read_timeout(timeout)
+    }
+
+    /// Returns a handle which shuts down blocked and later `recv` calls from other threads, see
+    /// `shutdown::ShutdownHandle`.
+    #[cfg(unix)]
+    pub fn shutdown_handle(&self) -> Result<ShutdownHandle> {
+        self.shutdown.share()
+    }
+
+    /// Receives the next valid unencrypted announcement and the address it was sent from.
+    pub fn recv(&self) -> Result<(SapAnnouncement, SocketAddr)> {
+        let mut buffer = [0_u8; 65536];
+        loop {
+            #[cfg(unix)]
+            self.shutdown.wait_readable(std::os::unix::io::AsFd::as_fd(&self.socket))?;
+            let (len, from) = self.socket.recv_from(&mut buffer)?;
+            if let Some(announcement) = SapAnnouncement::parse(&buffer[..len]) {
+                return Ok((announcement, from));
+            }
+        }
+    }
+}
+
+/// Sends SAP announcements to a SAP group.
+#[derive(Debug)]
+pub struct SapAnnouncer {
+    socket: UdpSocket,
+    group: SocketAddr,
+}
+
+impl SapAnnouncer {
+
+    /// Creates an announcer sending to SAP_MULTICAST_V4 from the interface (UNSPECIFIED for the
+    /// default multicast interface) with the multicast TTL.
+    pub fn new(interface: &Ipv4Addr, ttl: u32) -> Result<SapAnnouncer> {
+        SapAnnouncer::with_group(&SAP_MULTICAST_V4, interface, ttl)
+    }
+
+    /// Creates an announcer sending to the SAP group of another scope.
+    pub fn with_group(group: &Ipv4Addr, interface: &Ipv4Addr, ttl: u32) -> Result<SapAnnouncer> {
+        if !group.is_multicast() {
+            return Err(Error::new(ErrorKind::InvalidInput, "SAP group is not multicast"));
+        }
+        let socket = super::sys_socket::udp_bind(SocketAddrV4::new(*interface, 0))?;
+        if !interface.is_unspecified() {
+            socket2::SockRef::from(&socket).set_multicast_if_v4(interface)?;
+        }
+        socket.set_multicast_ttl_v4(ttl)?;
+        Ok(SapAnnouncer { socket, group: SocketAddr::from((*group, SAP_PORT)) })
+    }
+
+    /// Sends the announcement (or deletion) once, announcements are repeated every
+    /// ANNOUNCE_INTERVAL while the session exists.
+    pub fn send(&self, announcement: &SapAnnouncement) -> Result<()> {
+        self.socket.send_to(&announcement.encode(), self.group)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::inflate::fixture::{SDP, compressed_sdp};
+
+    #[test]
+    fn test_announcement() {
+        let announcement = SapAnnouncement::new("192.168.1.10".parse().unwrap(), SDP);
+        let packet = announcement.encode();
+        assert_eq!(&packet[..2], &[0x20, 0]);
+        assert_eq!(&packet[4..8], &[192, 168, 1, 10]);
+        assert_eq!(&packet[8..24], b"application/sdp\0");
+        assert_eq!(SapAnnouncement::parse(&packet), Some(announcement.clone()));
+
+        let deletion = SapAnnouncement { deletion: true, origin: "fd00::1".parse().unwrap(), ..announcement };
+        assert_eq!(SapAnnouncement::parse(&deletion.encode()), Some(deletion.clone()));
+
+        // without payload type and with authentication data
+        let mut packet = vec![0x20, 1, 0x12, 0x34, 10, 0, 0, 1, 0xaa, 0xbb, 0xcc, 0xdd];
+        packet.extend_from_slice(SDP.as_bytes());
+        let announcement = SapAnnouncement::parse(&packet).unwrap();
+        assert_eq!(announcement.message_id_hash, 0x1234);
+        assert_eq!(announcement.payload_type, SDP_PAYLOAD_TYPE);
+        assert_eq!(announcement.payload, SDP);
+
+        // encrypted
+        packet[0] |= FLAG_ENCRYPTED;
+        assert_eq!(SapAnnouncement::parse(&packet), None);
+    }
+
+    #[test]
+    fn test_compressed_announcement() {
+        let compressed = compressed_sdp();
+        let mut packet = vec![0x21, 0, 0, 1, 192, 168, 1, 10];
+        packet.extend_from_slice(&compressed);
+        let announcement = SapAnnouncement::parse(&packet).unwrap();
+        assert_eq!(announcement.payload, SDP);
+        packet.pop();
+        assert_eq!(SapAnnouncement::parse(&packet), None);
+    }
+
+    #[test]
+    fn test_session_description() {
+        let session = SessionDescription::parse(SDP).unwrap();
+        assert_eq!(session.name, "Stage left");
+        assert_eq!(session.origin.as_ref().unwrap().session_id, "1311738121");
+        assert_eq!(session.origin.as_ref().unwrap().address, "192.168.1.10");
+        assert_eq!(session.connection, Some(SdpConnection {
+            address: "239.69.1.10".parse().unwrap(),
+            ttl: Some(32),
+            count: 1,
+        }));
+        assert_eq!(session.timing, Some((0, 0)));
+        assert_eq!(session.media.len(), 1);
+        let media = &session.media[0];
+        assert_eq!((media.media.as_str(), media.port, media.protocol.as_str()), ("audio", 5004, "RTP/AVP"));
+        assert_eq!(media.formats, vec!["96"]);
+        assert_eq!(media.attribute("rtpmap"), Some("96 L24/48000/2"));
+        assert_eq!(session.media_destination(media), Some("239.69.1.10:5004".parse().unwrap()));
+
+        let session = SessionDescription::parse("v=0\ns=x\nm=video 5000 RTP/AVP 97\nc=IN IP6 ff0e::1/2\n\
+                                                 a=recvonly\n").unwrap();
+        assert_eq!(session.media[0].connection.as_ref().unwrap().count, 2);
+        assert_eq!(session.media[0].attribute("recvonly"), Some(""));
+        assert_eq!(session.media_destination(&session.media[0]), Some("[ff0e::1]:5000".parse().unwrap()));
+
+        assert_eq!(SessionDescription::parse("s=no version\r\n"), None);
+        assert_eq!(SessionDescription::parse("v=0\r\nc=IN IP6 239.0.0.1\r\n"), None);
+    }
+}
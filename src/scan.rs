@@ -0,0 +1,380 @@
+//! Port scanning: asynchronous TCP connect and UDP scans and privileged TCP SYN scans on raw
+//! sockets (linux), with concurrency and rate limits and binding to a device or source address.
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+#[cfg(any(feature = "tokio-net", target_os = "linux"))]
+use std::io::Result;
+
+/// State of a scanned port.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PortState {
+    /// the port accepted the connection or answered the datagram
+    Open,
+
+    /// the host rejected the connection (RST) or datagram (ICMP port unreachable)
+    Closed,
+
+    /// no answer within the timeout or an ICMP error other than port unreachable
+    Filtered,
+
+    /// no answer to the datagram, the port is open or the datagram was filtered (UDP)
+    OpenOrFiltered,
+}
+
+/// Result of a scanned port.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PortResult {
+    /// scanned address and port
+    pub target: SocketAddr,
+
+    /// state of the port
+    pub state: PortState,
+}
+
+/// Options of a port scan.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ScanOpts {
+    /// maximum number of ports probed at the same time
+    pub concurrency: usize,
+
+    /// maximum number of probes started per second, 0 for no limit
+    pub rate: u32,
+
+    /// time to wait for the answer of a port
+    pub timeout: Duration,
+
+    /// name of the network device the probes are sent from (linux, android and apple systems)
+    pub device: Option<String>,
+
+    /// local address the probes of targets of the same address family are sent from
+    pub source: Option<IpAddr>,
+}
+
+impl Default for ScanOpts {
+    fn default() -> ScanOpts {
+        ScanOpts { concurrency: 256, rate: 1000, timeout: Duration::from_secs(1), device: None, source: None }
+    }
+}
+
+/// Scans the ports of the targets by connecting to them. Open ports accepted the connection,
+/// closed ports rejected it and filtered ports did not answer within the timeout (or some router
+/// returned an ICMP error). The results are sorted by target.
+/// Requires the feature 'tokio-net' and must be called within a tokio runtime.
+#[cfg(feature = "tokio-net")]
+pub async fn tcp_connect_scan(targets: &[IpAddr], ports: &[u16], opts: &ScanOpts) -> Result<Vec<PortResult>> {
+    run(targets, ports, opts, |target, opts| async move {
+        let socket = tokio::net::TcpSocket::from_std_stream(new_socket(&target, socket2::Type::STREAM, &opts)?.into());
+        Ok(match tokio::time::timeout(opts.timeout, socket.connect(target)).await {
+            Ok(Ok(_)) => PortState::Open,
+            Ok(Err(e)) if e.kind() == std::io::ErrorKind::ConnectionRefused => PortState::Closed,
+            _ => PortState::Filtered,
+        })
+    }).await
+}
+
+/// Scans the UDP ports of the targets by sending the payload (e.g. a protocol specific request,
+/// many services ignore empty datagrams) once to each port. Ports which answered are open, ports
+/// for which an ICMP port unreachable was received are closed, all others open or filtered.
+/// The results are sorted by target.
+/// Requires the feature 'tokio-net' and must be called within a tokio runtime.
+#[cfg(feature = "tokio-net")]
+pub async fn udp_scan(targets: &[IpAddr], ports: &[u16], payload: &[u8], opts: &ScanOpts) -> Result<Vec<PortResult>> {
+    let payload = std::sync::Arc::new(payload.to_vec());
+    run(targets, ports, opts, |target, opts| {
+        let payload = payload.clone();
+        async move {
+            let socket = new_socket(&target, socket2::Type::DGRAM, &opts)?;
+            let socket = tokio::net::UdpSocket::from_std(socket.into())?;
+            socket.connect(target).await?;
+            let refused = |e: &std::io::Error| matches!(e.kind(),
+                std::io::ErrorKind::ConnectionRefused | std::io::ErrorKind::ConnectionReset);
+            match socket.send(&payload).await {
+                Err(e) if refused(&e) => return Ok(PortState::Closed),
+                result => result?,
+            };
+            let mut buffer = [0_u8; 1];
+            Ok(match tokio::time::timeout(opts.timeout, socket.recv(&mut buffer)).await {
+                Ok(Ok(_)) => PortState::Open,
+                Ok(Err(e)) if refused(&e) => PortState::Closed,
+                Ok(Err(_)) => PortState::Filtered,
+                Err(_) => PortState::OpenOrFiltered,
+            })
+        }
+    }).await
+}
+
+/// Probes all ports of all targets with the concurrency and rate of the options.
+#[cfg(feature = "tokio-net")]
+async fn run<F, Fut>(targets: &[IpAddr], ports: &[u16], opts: &ScanOpts, probe: F) -> Result<Vec<PortResult>>
+    where F: Fn(SocketAddr, ScanOpts) -> Fut,
+          Fut: std::future::Future<Output = Result<PortState>> + Send + 'static {
+    let interval = rate_interval(opts.rate);
+    let mut tasks = tokio::task::JoinSet::new();
+    let mut results: Vec<PortResult> = Vec::with_capacity(targets.len() * ports.len());
+    let mut next_start = tokio::time::Instant::now();
+    let collect = |joined: std::result::Result<(SocketAddr, Result<PortState>), tokio::task::JoinError>,
+                   results: &mut Vec<PortResult>| -> Result<()> {
+        let (target, state) = joined.map_err(std::io::Error::other)?;
+        results.push(PortResult { target, state: state? });
+        Ok(())
+    };
+    for target in targets.iter().flat_map(|ip| ports.iter().map(move |port| SocketAddr::new(*ip, *port))) {
+        while tasks.len() >= opts.concurrency.max(1) {
+            if let Some(joined) = tasks.join_next().await {
+                collect(joined, &mut results)?;
+            }
+        }
+        tokio::time::sleep_until(next_start).await;
+        next_start = tokio::time::Instant::now() + interval;
+        let probe = probe(target, opts.clone());
+        tasks.spawn(async move { (target, probe.await) });
+    }
+    while let Some(joined) = tasks.join_next().await {
+        collect(joined, &mut results)?;
+    }
+    results.sort_by_key(|r| r.target);
+    Ok(results)
+}
+
+/// Creates a non-blocking socket for the target bound to the device and source of the options.
+#[cfg(feature = "tokio-net")]
+fn new_socket(target: &SocketAddr, socket_type: socket2::Type, opts: &ScanOpts) -> Result<socket2::Socket> {
+    let socket = super::sys_socket::socket(socket2::Domain::for_address(*target), socket_type, None)?;
+    if let Some(device) = &opts.device {
+        super::device::bind_to_device(&socket, device, target)?;
+    }
+    match opts.source {
+        Some(source) if source.is_ipv4() == target.is_ipv4() => {
+            super::sys_socket::bind(&socket, &SocketAddr::new(source, 0).into())?;
+        },
+        _ if socket_type == socket2::Type::DGRAM => {
+            let any: IpAddr = if target.is_ipv4() { std::net::Ipv4Addr::UNSPECIFIED.into() }
+                              else { std::net::Ipv6Addr::UNSPECIFIED.into() };
+            super::sys_socket::bind(&socket, &SocketAddr::new(any, 0).into())?;
+        },
+        _ => {},
+    }
+    socket.set_nonblocking(true)?;
+    Ok(socket)
+}
+
+#[cfg(any(feature = "tokio-net", target_os = "linux"))]
+pub(crate) fn rate_interval(rate: u32) -> Duration {
+    match rate {
+        0 => Duration::ZERO,
+        rate => Duration::from_secs(1) / rate,
+    }
+}
+
+/// Returns until when a scanner waits for answers: the earliest timeout of the pending probes or
+/// the time of the next probe if one may be sent, `now` if there is nothing to wait for.
+#[cfg(target_os = "linux")]
+pub(crate) fn receive_deadline(now: std::time::Instant, timeout: Option<std::time::Instant>,
+                               next_send: Option<std::time::Instant>) -> std::time::Instant {
+    match (timeout, next_send) {
+        (Some(timeout), Some(next_send)) => timeout.min(next_send),
+        (Some(deadline), None) | (None, Some(deadline)) => deadline,
+        (None, None) => now,
+    }
+}
+
+/// Scans the TCP ports of IPv4 targets with SYN packets on a raw socket without completing the
+/// handshake. Ports answering with SYN-ACK are open (the kernel resets the half-open connection),
+/// ports answering with RST closed and all others filtered. The results are sorted by target.
+/// Requires CAP_NET_RAW.
+#[cfg(target_os = "linux")]
+pub fn syn_scan(targets: &[std::net::Ipv4Addr], ports: &[u16], opts: &ScanOpts) -> Result<Vec<PortResult>> {
+    syn::scan(targets, ports, opts)
+}
+
+#[cfg(target_os = "linux")]
+mod syn {
+
+    use std::{
+        collections::{HashMap, VecDeque},
+        io::{ErrorKind, Read, Result},
+        net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4},
+        time::Instant,
+    };
+
+    use socket2::{Domain, Protocol, Type};
+
+    use super::{PortResult, PortState, ScanOpts, rate_interval, receive_deadline};
+    use crate::checksum::tcp_checksum;
+
+    const TCP_HEADER_LEN: usize = 20;
+    const FLAG_RST: u8 = 0x04;
+    const FLAG_SYN: u8 = 0x02;
+    const FLAG_ACK: u8 = 0x10;
+
+    pub(super) fn scan(targets: &[Ipv4Addr], ports: &[u16], opts: &ScanOpts) -> Result<Vec<PortResult>> {
+        let socket = crate::sys_socket::socket(Domain::IPV4, Type::RAW, Some(Protocol::TCP))?;
+        if let Some(device) = &opts.device {
+            socket.bind_device(Some(device.as_bytes()))?;
+        }
+        let mut random = [0_u8; 6];
+        crate::random::random_bytes(&mut random);
+        let source_port = 40000 + u16::from_ne_bytes([random[0], random[1]]) % 20000;
+        let sequence = u32::from_ne_bytes([random[2], random[3], random[4], random[5]]);
+
+        let mut sources: HashMap<Ipv4Addr, Ipv4Addr> = HashMap::new();
+        let mut queue: VecDeque<SocketAddrV4> = targets.iter()
+            .flat_map(|ip| ports.iter().map(move |port| SocketAddrV4::new(*ip, *port)))
+            .collect();
+        let mut pending: HashMap<SocketAddrV4, Instant> = HashMap::new();
+        let mut results: Vec<PortResult> = Vec::with_capacity(queue.len());
+        let interval = rate_interval(opts.rate);
+        let concurrency = opts.concurrency.max(1);
+        let mut next_send = Instant::now();
+        let mut buffer = [0_u8; 1500];
+        while !queue.is_empty() || !pending.is_empty() {
+            let now = Instant::now();
+            if now >= next_send && pending.len() < concurrency {
+                if let Some(target) = queue.pop_front() {
+                    let source = match sources.get(target.ip()) {
+                        Some(source) => *source,
+                        None => {
+                            let source = source_address(target.ip(), opts)?;
+                            sources.insert(*target.ip(), source);
+                            source
+                        },
+                    };
+                    let segment = syn_segment(&source, &target, source_port, sequence);
+                    socket.send_to(&segment, &SocketAddr::V4(SocketAddrV4::new(*target.ip(), 0)).into())?;
+                    pending.insert(target, now + opts.timeout);
+                    next_send = now + interval;
+                    continue;
+                }
+            }
+            let expired: Vec<SocketAddrV4> = pending.iter().filter(|(_, d)| **d <= now).map(|(t, _)| *t).collect();
+            for target in expired {
+                pending.remove(&target);
+                results.push(PortResult { target: target.into(), state: PortState::Filtered });
+            }
+            let may_send = !queue.is_empty() && pending.len() < concurrency;
+            let deadline = receive_deadline(now, pending.values().min().copied(), may_send.then_some(next_send));
+            let remaining = deadline.saturating_duration_since(now);
+            if remaining.is_zero() {
+                continue;
+            }
+            socket.set_read_timeout(Some(remaining))?;
+            let len = match (&socket).read(&mut buffer) {
+                Ok(len) => len,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted) => continue,
+                Err(e) => return Err(e),
+            };
+            if let Some((target, state)) = parse_answer(&buffer[..len], source_port) {
+                if pending.remove(&target).is_some() {
+                    results.push(PortResult { target: target.into(), state });
+                }
+            }
+        }
+        results.sort_by_key(|r| r.target);
+        Ok(results)
+    }
+
+    /// Returns the source address of the options or the one the routing table chooses.
+    fn source_address(target: &Ipv4Addr, opts: &ScanOpts) -> Result<Ipv4Addr> {
+        if let Some(IpAddr::V4(source)) = opts.source {
+            return Ok(source);
+        }
+        let socket = crate::sys_socket::udp_bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        if let Some(device) = &opts.device {
+            socket2::SockRef::from(&socket).bind_device(Some(device.as_bytes()))?;
+        }
+        socket.connect((*target, 9))?;
+        match socket.local_addr()?.ip() {
+            IpAddr::V4(source) => Ok(source),
+            IpAddr::V6(_) => unreachable!(),
+        }
+    }
+
+    /// Builds a TCP SYN segment with checksum, the kernel adds the IP header.
+    fn syn_segment(source: &Ipv4Addr, target: &SocketAddrV4, source_port: u16, sequence: u32) -> [u8; TCP_HEADER_LEN] {
+        let mut segment = [0_u8; TCP_HEADER_LEN];
+        segment[0..2].copy_from_slice(&source_port.to_be_bytes());
+        segment[2..4].copy_from_slice(&target.port().to_be_bytes());
+        segment[4..8].copy_from_slice(&sequence.to_be_bytes());
+        segment[12] = ((TCP_HEADER_LEN / 4) as u8) << 4;
+        segment[13] = FLAG_SYN;
+        segment[14..16].copy_from_slice(&1024_u16.to_be_bytes());
+        let checksum = tcp_checksum(&IpAddr::V4(*source), &IpAddr::V4(*target.ip()), &segment);
+        segment[16..18].copy_from_slice(&checksum.to_be_bytes());
+        segment
+    }
+
+    /// Parses a received IPv4 packet, returns the target and its state for answers to our SYNs.
+    fn parse_answer(packet: &[u8], source_port: u16) -> Option<(SocketAddrV4, PortState)> {
+        let header_len = ((*packet.first()? & 0x0f) as usize) * 4;
+        if packet[0] >> 4 != 4 || *packet.get(9)? != libc::IPPROTO_TCP as u8 {
+            return None;
+        }
+        let from = Ipv4Addr::new(packet[12], packet[13], packet[14], packet[15]);
+        let segment = packet.get(header_len..header_len + TCP_HEADER_LEN)?;
+        if u16::from_be_bytes([segment[2], segment[3]]) != source_port {
+            return None;
+        }
+        let target = SocketAddrV4::new(from, u16::from_be_bytes([segment[0], segment[1]]));
+        let flags = segment[13];
+        if flags & (FLAG_SYN | FLAG_ACK) == FLAG_SYN | FLAG_ACK {
+            Some((target, PortState::Open))
+        } else if flags & FLAG_RST != 0 {
+            Some((target, PortState::Closed))
+        } else {
+            None
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+
+        use super::*;
+
+        #[test]
+        fn test_syn_segment() {
+            let source = Ipv4Addr::new(192, 0, 2, 1);
+            let target = SocketAddrV4::new(Ipv4Addr::new(192, 0, 2, 2), 80);
+            let segment = syn_segment(&source, &target, 40000, 1);
+            assert_eq!(&segment[..4], &[0x9c, 0x40, 0, 80]);
+            assert_eq!((segment[12], segment[13]), (0x50, FLAG_SYN));
+            // the checksum over a segment including its checksum is zero
+            assert_eq!(tcp_checksum(&source.into(), &(*target.ip()).into(), &segment), 0);
+        }
+
+        #[test]
+        fn test_parse_answer() {
+            let mut packet = vec![0x45, 0, 0, 40, 0, 0, 0x40, 0, 64, 6, 0, 0, 192, 0, 2, 2, 192, 0, 2, 1];
+            packet.extend_from_slice(&[0, 80, 0x9c, 0x40, 0, 0, 0, 0, 0, 0, 0, 2, 0x50, FLAG_SYN | FLAG_ACK, 0, 0,
+                                       0, 0, 0, 0]);
+            let target = SocketAddrV4::new(Ipv4Addr::new(192, 0, 2, 2), 80);
+            assert_eq!(parse_answer(&packet, 40000), Some((target, PortState::Open)));
+            packet[33] = FLAG_RST | FLAG_ACK;
+            assert_eq!(parse_answer(&packet, 40000), Some((target, PortState::Closed)));
+            assert_eq!(parse_answer(&packet, 40001), None);
+            packet[33] = FLAG_SYN;
+            assert_eq!(parse_answer(&packet, 40000), None);
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod test {
+
+    use std::time::Instant;
+
+    use super::*;
+
+    #[test]
+    fn test_receive_deadline() {
+        let now = Instant::now();
+        let (early, late) = (now + Duration::from_millis(10), now + Duration::from_millis(20));
+        assert_eq!(receive_deadline(now, Some(late), Some(early)), early);
+        assert_eq!(receive_deadline(now, Some(early), None), early);
+        // nothing pending, the scanner waits for the next probe instead of spinning
+        assert_eq!(receive_deadline(now, None, Some(late)), late);
+        assert_eq!(receive_deadline(now, None, None), now);
+    }
+}
@@ -0,0 +1,219 @@
+//! SCTP sockets (one-to-one and one-to-many style) with multi-homing via sctp_bindx semantics.
+
+use std::{
+    borrow::Borrow,
+    io::{Error, ErrorKind, Result},
+    net::SocketAddr,
+    os::unix::io::{AsFd, AsRawFd, BorrowedFd, RawFd},
+};
+
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+
+use super::{IpInterface, sockopt::{set_bytes_option, set_int_option, set_option}};
+
+const SOL_SCTP: libc::c_int = 132;
+const SCTP_NODELAY: libc::c_int = 3;
+const SCTP_INITMSG: libc::c_int = 2;
+const SCTP_SOCKOPT_BINDX_ADD: libc::c_int = 100;
+const SCTP_SOCKOPT_BINDX_REM: libc::c_int = 101;
+const SCTP_SOCKOPT_CONNECTX: libc::c_int = 110;
+
+/// Socket style of an SCTP socket.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SctpStyle {
+    /// one association per socket (SOCK_STREAM), TCP like
+    OneToOne,
+
+    /// many associations on one socket (SOCK_SEQPACKET), UDP like
+    OneToMany,
+}
+
+#[repr(C)]
+struct SctpInitMsg {
+    num_ostreams: u16,
+    max_instreams: u16,
+    max_attempts: u16,
+    max_init_timeo: u16,
+}
+
+/// An SCTP socket.
+#[derive(Debug)]
+pub struct SctpSocket {
+    socket: Socket,
+}
+
+impl SctpSocket {
+
+    /// Creates an unbound SCTP socket of the style for IPv4 or IPv6. IPv6 sockets can be bound
+    /// to IPv4 addresses as well unless IPV6_V6ONLY is set.
+    pub fn new(style: SctpStyle, ipv6: bool) -> Result<SctpSocket> {
+        let domain = if ipv6 { Domain::IPV6 } else { Domain::IPV4 };
+        let socket_type = match style {
+            SctpStyle::OneToOne => Type::STREAM,
+            SctpStyle::OneToMany => Type::SEQPACKET,
+        };
+        Ok(SctpSocket { socket: super::sys_socket::socket(domain, socket_type, Some(Protocol::SCTP))? })
+    }
+
+    /// Creates a socket of the style bound to all addresses (multi-homing). All addresses must
+    /// use the same port, the family of the first address determines the socket's family.
+    pub fn bind(style: SctpStyle, addresses: &[SocketAddr]) -> Result<SctpSocket> {
+        let first = addresses.first()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "no SCTP bind address"))?;
+        let socket = SctpSocket::new(style, first.is_ipv6())?;
+        socket.bind_add(addresses)?;
+        Ok(socket)
+    }
+
+    /// Creates a socket of the style bound to the addresses of the interfaces with the port, e.g.
+    /// `SctpSocket::bind_interfaces(style, interfaces.iter().up().non_loopback(), 2905)`.
+    /// Link-local IPv6 addresses are skipped as they are not usable for multi-homing.
+    pub fn bind_interfaces<I>(style: SctpStyle, interfaces: I, port: u16) -> Result<SctpSocket>
+        where I: IntoIterator, I::Item: Borrow<IpInterface> {
+        SctpSocket::bind(style, &interface_addresses(interfaces, port))
+    }
+
+    /// Adds the addresses to the bound addresses (sctp_bindx with SCTP_BINDX_ADD_ADDR). An unbound
+    /// socket is bound to the first address.
+    pub fn bind_add(&self, addresses: &[SocketAddr]) -> Result<()> {
+        self.bindx(addresses, SCTP_SOCKOPT_BINDX_ADD)
+    }
+
+    /// Removes the addresses from the bound addresses (sctp_bindx with SCTP_BINDX_REM_ADDR).
+    pub fn bind_remove(&self, addresses: &[SocketAddr]) -> Result<()> {
+        self.bindx(addresses, SCTP_SOCKOPT_BINDX_REM)
+    }
+
+    /// Starts listening for associations.
+    pub fn listen(&self, backlog: i32) -> Result<()> {
+        super::sys_socket::listen(&self.socket, backlog)
+    }
+
+    /// Accepts an association of a one-to-one style socket.
+    pub fn accept(&self) -> Result<(SctpSocket, SocketAddr)> {
+        let (socket, address) = self.socket.accept()?;
+        let address = address.as_socket()
+            .ok_or_else(|| Error::other("not an IP or IP6 address"))?;
+        Ok((SctpSocket { socket }, address))
+    }
+
+    /// Connects to the peer, which is reachable by all of the addresses (sctp_connectx).
+    pub fn connect(&self, addresses: &[SocketAddr]) -> Result<()> {
+        let packed = pack_addresses(addresses)?;
+        match set_bytes_option(self.socket.as_raw_fd(), SOL_SCTP, SCTP_SOCKOPT_CONNECTX, &packed) {
+            Err(e) if e.raw_os_error() != Some(libc::EINPROGRESS) => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Sets SCTP_NODELAY, which disables the Nagle like bundling of small messages.
+    pub fn set_nodelay(&self, nodelay: bool) -> Result<()> {
+        set_int_option(self.socket.as_raw_fd(), SOL_SCTP, SCTP_NODELAY, nodelay as libc::c_int)
+    }
+
+    /// Sets the number of outbound streams requested and inbound streams accepted for new
+    /// associations (SCTP_INITMSG).
+    pub fn set_streams(&self, outbound: u16, max_inbound: u16) -> Result<()> {
+        let init = SctpInitMsg { num_ostreams: outbound, max_instreams: max_inbound, max_attempts: 0,
+            max_init_timeo: 0 };
+        set_option(self.socket.as_raw_fd(), SOL_SCTP, SCTP_INITMSG, &init)
+    }
+
+    /// Sends a message on a one-to-one socket.
+    pub fn send(&self, buf: &[u8]) -> Result<usize> {
+        self.socket.send(buf)
+    }
+
+    /// Sends a message to the peer, which sets up an association implicitly on one-to-many
+    /// sockets.
+    pub fn send_to(&self, buf: &[u8], address: &SocketAddr) -> Result<usize> {
+        self.socket.send_to(buf, &SockAddr::from(*address))
+    }
+
+    /// Receives a message, returns its length and the peer's primary address.
+    pub fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr)> {
+        // recv_from only writes initialized bytes into the buffer
+        let uninit = unsafe { &mut *(buf as *mut [u8] as *mut [std::mem::MaybeUninit<u8>]) };
+        let (len, address) = self.socket.recv_from(uninit)?;
+        let address = address.as_socket()
+            .ok_or_else(|| Error::other("not an IP or IP6 address"))?;
+        Ok((len, address))
+    }
+
+    /// Returns the primary local address.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.socket.local_addr()?.as_socket()
+            .ok_or_else(|| Error::other("not an IP or IP6 address"))
+    }
+
+    fn bindx(&self, addresses: &[SocketAddr], option: libc::c_int) -> Result<()> {
+        let packed = pack_addresses(addresses)?;
+        set_bytes_option(self.socket.as_raw_fd(), SOL_SCTP, option, &packed)
+    }
+}
+
+impl AsFd for SctpSocket {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.socket.as_fd()
+    }
+}
+
+impl AsRawFd for SctpSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+}
+
+/// Returns the multi-homing addresses of the interfaces with the port.
+fn interface_addresses<I>(interfaces: I, port: u16) -> Vec<SocketAddr>
+    where I: IntoIterator, I::Item: Borrow<IpInterface> {
+    interfaces.into_iter()
+        .filter(|i| !(i.borrow().address.is_ipv6() && i.borrow().is_link_local()))
+        .map(|i| SocketAddr::new(i.borrow().address.ip(), port))
+        .collect()
+}
+
+/// Packs the addresses as consecutive sockaddr_in / sockaddr_in6 structs, as expected by the
+/// bindx and connectx socket options.
+fn pack_addresses(addresses: &[SocketAddr]) -> Result<Vec<u8>> {
+    if addresses.is_empty() {
+        return Err(Error::new(ErrorKind::InvalidInput, "no SCTP address"));
+    }
+    let mut packed = Vec::new();
+    for address in addresses {
+        let address = SockAddr::from(*address);
+        let bytes = unsafe { std::slice::from_raw_parts(address.as_ptr() as *const u8, address.len() as usize) };
+        packed.extend_from_slice(bytes);
+    }
+    Ok(packed)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_pack_addresses() {
+        let addresses: Vec<SocketAddr> = ["10.0.0.1:2905", "[2001:db8::1]:2905"].iter()
+            .map(|a| a.parse().unwrap()).collect();
+        let packed = pack_addresses(&addresses).unwrap();
+        assert_eq!(packed.len(), std::mem::size_of::<libc::sockaddr_in>() + std::mem::size_of::<libc::sockaddr_in6>());
+        assert_eq!(u16::from_ne_bytes([packed[0], packed[1]]), libc::AF_INET as u16);
+        assert_eq!(u16::from_be_bytes([packed[2], packed[3]]), 2905);
+        assert_eq!(&packed[4..8], &[10, 0, 0, 1]);
+        assert!(pack_addresses(&[]).is_err());
+    }
+
+    #[test]
+    fn test_interface_addresses() {
+        let interface = |address: &str| {
+            let address: SocketAddr = address.parse().unwrap();
+            IpInterface { index: 2, name: String::from("eth0"), flags: libc::IFF_UP as libc::c_uint, address,
+                net_mask: address, broadcast_address: None, p2p_address: None }
+        };
+        let interfaces = vec![interface("192.168.1.2:0"), interface("[fe80::1]:0"), interface("[2001:db8::2]:0")];
+        assert_eq!(interface_addresses(&interfaces, 2905),
+                   vec!["192.168.1.2:2905".parse::<SocketAddr>().unwrap(), "[2001:db8::2]:2905".parse().unwrap()]);
+    }
+}
@@ -0,0 +1,408 @@
+//! Authenticated encryption of datagrams with a pre-shared key, for multicast traffic which
+//! cannot use TLS or DTLS: XChaCha20-Poly1305 with the random 128 bit salt of the sender
+//! session and the sequence number as 192 bit nonce, and a replay window per session. The
+//! `Sealer` and `Opener` compose with the other datagram layers, e.g. sealing the packets of a
+//! `fec::FecEncoder` and opening them before the `fec::FecDecoder`.
+//!
+//! Each `Sealer` draws a new salt, so the nonces stay unique across senders and restarts, two
+//! of `n` sealers share a salt with a probability of about n^2 / 2^129.
+//!
+//! Packet format (big endian): version 1, sender id (u32), salt (16 bytes), sequence number
+//! (u64), followed by the ciphertext and the 16 byte tag, which also authenticates the header.
+//!
+//! Requires the feature 'secure'.
+
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    fmt,
+    io::{Error, ErrorKind, Result},
+    net::{SocketAddr, UdpSocket},
+};
+
+use chacha20poly1305::{KeyInit, XChaCha20Poly1305, XNonce, aead::{Aead, Payload}};
+
+use super::stats::{SocketStats, StatsCounter};
+
+const VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const HEADER_LEN: usize = 5 + SALT_LEN + 8;
+const TAG_LEN: usize = 16;
+/// Number of bytes a sealed packet is longer than its payload.
+pub const SECURE_OVERHEAD: usize = HEADER_LEN + TAG_LEN;
+/// Number of sequence numbers below the highest received one of a sender that are accepted
+/// once, for reordered packets.
+pub const REPLAY_WINDOW: u64 = 64;
+/// Minimum length of a pre-shared key.
+pub const MIN_KEY_LEN: usize = 16;
+/// Maximum number of sender sessions an `Opener` tracks.
+const MAX_SENDERS: usize = 1024;
+
+/// Returns the cipher with the key derived from the pre-shared key.
+fn cipher(psk: &[u8]) -> Result<XChaCha20Poly1305> {
+    if psk.len() < MIN_KEY_LEN {
+        return Err(Error::new(ErrorKind::InvalidInput, "pre-shared key shorter than 16 bytes"));
+    }
+    Ok(XChaCha20Poly1305::new(&super::sha256::hmac_sha256(psk, b"net-utils secure datagram key").into()))
+}
+
+fn nonce(salt: &[u8; SALT_LEN], sequence: u64) -> XNonce {
+    let mut nonce = [0_u8; 24];
+    nonce[..SALT_LEN].copy_from_slice(salt);
+    nonce[SALT_LEN..].copy_from_slice(&sequence.to_be_bytes());
+    nonce.into()
+}
+
+/// Encrypts and authenticates the datagrams of a sender.
+/// ```
+/// use net_utils::secure::{Opener, Sealer};
+/// let mut sealer = Sealer::new(b"shared secret of the group").unwrap();
+/// let mut opener = Opener::new(b"shared secret of the group").unwrap();
+/// let packet = sealer.seal(b"telemetry");
+/// assert_eq!(opener.open(&packet).unwrap(), b"telemetry");
+/// // the same packet again is a replay
+/// assert!(opener.open(&packet).is_err());
+/// ```
+#[derive(Clone)]
+pub struct Sealer {
+    cipher: XChaCha20Poly1305,
+    sender_id: u32,
+    salt: [u8; SALT_LEN],
+    sequence: u64,
+}
+
+impl fmt::Debug for Sealer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sealer").field("sender_id", &self.sender_id).field("sequence", &self.sequence).finish()
+    }
+}
+
+impl Sealer {
+
+    /// Creates a sealer with a sender id from the random generator of the operating system.
+    /// Fails with InvalidInput for a key shorter than `MIN_KEY_LEN`.
+    pub fn new(psk: &[u8]) -> Result<Sealer> {
+        let mut id = [0_u8; 4];
+        super::random::secure_random_bytes(&mut id)?;
+        Sealer::with_sender_id(psk, u32::from_ne_bytes(id))
+    }
+
+    /// Creates a sealer with the sender id, which tells the senders with the key apart (e.g.
+    /// derived from a configured node number). The salt of the session is random, so a
+    /// restarted sender keeps its id while its sequence numbers start at 0 again.
+    pub fn with_sender_id(psk: &[u8], sender_id: u32) -> Result<Sealer> {
+        let mut salt = [0_u8; SALT_LEN];
+        super::random::secure_random_bytes(&mut salt)?;
+        Ok(Sealer { cipher: cipher(psk)?, sender_id, salt, sequence: 0 })
+    }
+
+    /// Returns the sender id.
+    pub fn sender_id(&self) -> u32 {
+        self.sender_id
+    }
+
+    /// Returns the sequence number of the next packet.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Returns the sealed packet of the payload.
+    pub fn seal(&mut self, payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![VERSION];
+        packet.extend_from_slice(&self.sender_id.to_be_bytes());
+        packet.extend_from_slice(&self.salt);
+        packet.extend_from_slice(&self.sequence.to_be_bytes());
+        let sealed = self.cipher.encrypt(&nonce(&self.salt, self.sequence), Payload { msg: payload, aad: &packet })
+            .expect("datagram payloads are below the message limit of XChaCha20-Poly1305");
+        packet.extend_from_slice(&sealed);
+        self.sequence = self.sequence.wrapping_add(1);
+        packet
+    }
+}
+
+/// Accepted sequence numbers of a sender: the highest one and a bitmap of the ones below it.
+#[derive(Clone, Copy, Debug)]
+struct ReplayWindow {
+    highest: u64,
+    seen: u64,
+}
+
+impl ReplayWindow {
+
+    fn is_replay(&self, sequence: u64) -> bool {
+        sequence <= self.highest
+            && (self.highest - sequence >= REPLAY_WINDOW || self.seen & (1 << (self.highest - sequence)) != 0)
+    }
+
+    fn accept(&mut self, sequence: u64) {
+        if sequence > self.highest {
+            let shift = sequence - self.highest;
+            self.seen = if shift >= REPLAY_WINDOW { 0 } else { self.seen << shift };
+            self.highest = sequence;
+        }
+        self.seen |= 1 << (self.highest - sequence);
+    }
+}
+
+/// Replay window of a sender session and when it last accepted a packet.
+#[derive(Clone, Copy, Debug)]
+struct Session {
+    window: ReplayWindow,
+    last_accepted: u64,
+}
+
+/// Authenticates and decrypts the packets of `Sealer`s with the same key and rejects replayed
+/// ones. It tracks the sessions of up to 1024 senders (a `Sealer` each), beyond that it forgets
+/// the session which accepted a packet least recently, the earlier packets of a forgotten
+/// session are accepted again.
+#[derive(Clone)]
+pub struct Opener {
+    cipher: XChaCha20Poly1305,
+    sessions: HashMap<(u32, [u8; SALT_LEN]), Session>,
+    /// number of accepted packets, orders the sessions by their last accepted packet
+    accepted: u64,
+}
+
+impl fmt::Debug for Opener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Opener").field("sessions", &self.sessions.len()).finish()
+    }
+}
+
+impl Opener {
+
+    /// Creates an opener. Fails with InvalidInput for a key shorter than `MIN_KEY_LEN`.
+    pub fn new(psk: &[u8]) -> Result<Opener> {
+        Ok(Opener { cipher: cipher(psk)?, sessions: HashMap::new(), accepted: 0 })
+    }
+
+    /// Returns the ids of the senders of which packets were accepted.
+    pub fn senders(&self) -> Vec<u32> {
+        let mut senders: Vec<u32> = self.sessions.keys().map(|(sender_id, _)| *sender_id).collect();
+        senders.sort_unstable();
+        senders.dedup();
+        senders
+    }
+
+    /// Returns the payload of an authentic packet and the sender id. Fails with InvalidData for
+    /// malformed, forged and replayed packets.
+    pub fn open_from(&mut self, packet: &[u8]) -> Result<(u32, Vec<u8>)> {
+        if packet.len() < SECURE_OVERHEAD || packet[0] != VERSION {
+            return Err(Error::new(ErrorKind::InvalidData, "no sealed packet"));
+        }
+        let sender_id = u32::from_be_bytes(packet[1..5].try_into().unwrap_or_default());
+        let salt: [u8; SALT_LEN] = packet[5..5 + SALT_LEN].try_into().unwrap_or_default();
+        let sequence = u64::from_be_bytes(packet[5 + SALT_LEN..HEADER_LEN].try_into().unwrap_or_default());
+        if self.sessions.get(&(sender_id, salt)).is_some_and(|session| session.window.is_replay(sequence)) {
+            return Err(Error::new(ErrorKind::InvalidData, "replayed packet"));
+        }
+        let (header, sealed) = packet.split_at(HEADER_LEN);
+        let payload = self.cipher.decrypt(&nonce(&salt, sequence), Payload { msg: sealed, aad: header })
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "packet not authentic"))?;
+        if !self.sessions.contains_key(&(sender_id, salt)) && self.sessions.len() >= MAX_SENDERS {
+            let oldest = self.sessions.iter().min_by_key(|(_, session)| session.last_accepted).map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                self.sessions.remove(&oldest);
+            }
+        }
+        self.accepted += 1;
+        let session = self.sessions.entry((sender_id, salt)).or_insert(Session {
+            window: ReplayWindow { highest: sequence, seen: 0 }, last_accepted: 0,
+        });
+        session.window.accept(sequence);
+        session.last_accepted = self.accepted;
+        Ok((sender_id, payload))
+    }
+
+    /// Returns the payload of an authentic packet, see `open_from`.
+    pub fn open(&mut self, packet: &[u8]) -> Result<Vec<u8>> {
+        self.open_from(packet).map(|(_, payload)| payload)
+    }
+}
+
+/// Wrapper of a UDP socket which sends sealed datagrams to a destination.
+/// ```no_run
+/// use net_utils::secure::SecureSender;
+/// let socket = std::net::UdpSocket::bind("0.0.0.0:0").unwrap();
+/// let mut sender = SecureSender::new(socket, "239.1.2.3:5000".parse().unwrap(), b"shared secret of the group").unwrap();
+/// sender.send(b"telemetry").unwrap();
+/// ```
+#[derive(Debug)]
+pub struct SecureSender {
+    socket: UdpSocket,
+    destination: SocketAddr,
+    sealer: Sealer,
+    stats: StatsCounter,
+}
+
+impl SecureSender {
+
+    /// Wraps the socket. Fails with InvalidInput for a key shorter than `MIN_KEY_LEN`.
+    pub fn new(socket: UdpSocket, destination: SocketAddr, psk: &[u8]) -> Result<SecureSender> {
+        Ok(SecureSender { socket, destination, sealer: Sealer::new(psk)?, stats: StatsCounter::new() })
+    }
+
+    /// Returns the socket.
+    pub fn get_ref(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// Returns the socket.
+    pub fn into_inner(self) -> UdpSocket {
+        self.socket
+    }
+
+    /// Returns the sender id.
+    pub fn sender_id(&self) -> u32 {
+        self.sealer.sender_id()
+    }
+
+    /// Returns the statistics of the packets sent via the wrapper.
+    pub fn stats(&self) -> SocketStats {
+        self.stats.snapshot()
+    }
+
+    /// Sends the datagram sealed.
+    pub fn send(&mut self, datagram: &[u8]) -> Result<()> {
+        let packet = self.sealer.seal(datagram);
+        self.socket.send_to(&packet, self.destination)?;
+        self.stats.sent(packet.len());
+        Ok(())
+    }
+}
+
+/// Wrapper of a UDP socket which receives the datagrams of `SecureSender`s and drops
+/// malformed, forged and replayed packets.
+#[derive(Debug)]
+pub struct SecureReceiver {
+    socket: UdpSocket,
+    opener: Opener,
+    rejected: u64,
+    stats: StatsCounter,
+}
+
+impl SecureReceiver {
+
+    /// Wraps the socket. Fails with InvalidInput for a key shorter than `MIN_KEY_LEN`.
+    pub fn new(socket: UdpSocket, psk: &[u8]) -> Result<SecureReceiver> {
+        Ok(SecureReceiver { socket, opener: Opener::new(psk)?, rejected: 0, stats: StatsCounter::new() })
+    }
+
+    /// Returns the socket, e.g. to set a read timeout.
+    pub fn get_ref(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// Returns the socket.
+    pub fn into_inner(self) -> UdpSocket {
+        self.socket
+    }
+
+    /// Returns the number of dropped packets.
+    pub fn rejected(&self) -> u64 {
+        self.rejected
+    }
+
+    /// Returns the statistics of the packets received via the wrapper, dropped ones included.
+    pub fn stats(&self) -> SocketStats {
+        self.stats.snapshot()
+    }
+
+    /// Receives the next authentic datagram into the buffer and returns its length (truncated
+    /// to the buffer), the source address and the sender id.
+    pub fn recv_from(&mut self, buf: &mut [u8]) -> Result<(usize, SocketAddr, u32)> {
+        let mut packet = vec![0_u8; 65536];
+        loop {
+            let (len, source) = self.socket.recv_from(&mut packet)?;
+            self.stats.received(len);
+            match self.opener.open_from(&packet[..len]) {
+                Ok((sender_id, payload)) => {
+                    let len = payload.len().min(buf.len());
+                    buf[..len].copy_from_slice(&payload[..len]);
+                    return Ok((len, source, sender_id));
+                },
+                Err(_) => self.rejected += 1,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_replay_window() {
+        let mut window = ReplayWindow { highest: 10, seen: 0 };
+        window.accept(10);
+        assert!(window.is_replay(10));
+        assert!(!window.is_replay(9));
+        window.accept(12);
+        assert!(window.is_replay(10) && window.is_replay(12));
+        assert!(!window.is_replay(11) && !window.is_replay(13));
+        window.accept(11);
+        assert!(window.is_replay(11));
+        window.accept(12 + REPLAY_WINDOW);
+        assert!(window.is_replay(12) && !window.is_replay(13));
+        window.accept(1000);
+        assert!(window.is_replay(1000 - REPLAY_WINDOW) && !window.is_replay(1001 - REPLAY_WINDOW));
+    }
+
+    #[test]
+    fn test_seal() {
+        let psk = b"0123456789abcdef";
+        assert_eq!(Sealer::new(b"short").unwrap_err().kind(), ErrorKind::InvalidInput);
+        let mut sealer = Sealer::with_sender_id(psk, 7).unwrap();
+        let mut opener = Opener::new(psk).unwrap();
+        let first = sealer.seal(b"one");
+        let second = sealer.seal(b"two");
+        assert_eq!(first.len(), 3 + SECURE_OVERHEAD);
+        assert_eq!(&first[..5], &[1, 0, 0, 0, 7]);
+        assert_eq!(&first[5..5 + SALT_LEN], &second[5..5 + SALT_LEN]);
+        assert_eq!(&first[5 + SALT_LEN..HEADER_LEN], &[0; 8]);
+        assert_ne!(&first[HEADER_LEN..HEADER_LEN + 3], b"one");
+        // reordered
+        assert_eq!(opener.open_from(&second).unwrap(), (7, b"two".to_vec()));
+        assert_eq!(opener.open(&first).unwrap(), b"one");
+        assert_eq!(opener.open(&first).unwrap_err().kind(), ErrorKind::InvalidData);
+
+        // a forged header does not open and does not advance the replay window
+        let mut forged = sealer.seal(b"six");
+        forged[HEADER_LEN - 1] = 100;
+        assert_eq!(opener.open(&forged).unwrap_err().kind(), ErrorKind::InvalidData);
+        forged[HEADER_LEN - 1] = 2;
+        assert_eq!(opener.open(&forged).unwrap(), b"six");
+        assert_eq!(Opener::new(b"fedcba9876543210").unwrap().open(&sealer.seal(b"x")).unwrap_err().kind(),
+                   ErrorKind::InvalidData);
+
+        // a restarted sender starts a new session with the same id
+        let mut restarted = Sealer::with_sender_id(psk, 7).unwrap();
+        assert_ne!(&restarted.seal(b"")[5..HEADER_LEN], &first[5..HEADER_LEN]);
+        assert_eq!(opener.open(&restarted.seal(b"one")).unwrap(), b"one");
+        assert_eq!(opener.senders(), vec![7]);
+    }
+
+    #[test]
+    fn test_evicted_senders() {
+        let psk = b"0123456789abcdef";
+        let mut opener = Opener::new(psk).unwrap();
+        let mut sealers: Vec<Sealer> = (0..MAX_SENDERS as u32 + 100).map(|id| Sealer::with_sender_id(psk, id).unwrap())
+            .collect();
+        let first: Vec<Vec<u8>> = sealers.iter_mut().map(|sealer| sealer.seal(b"first")).collect();
+        // every new sender is accepted, beyond MAX_SENDERS the least recently active ones are forgotten
+        for packet in &first {
+            opener.open(packet).unwrap();
+        }
+        assert_eq!(opener.senders().len(), MAX_SENDERS);
+        assert!(!opener.senders().contains(&0) && opener.senders().contains(&(MAX_SENDERS as u32 + 99)));
+        for packet in &first[100..] {
+            assert_eq!(opener.open(packet).unwrap_err().kind(), ErrorKind::InvalidData);
+        }
+        // a tracked sender stays tracked while it is active, the evicted sender is accepted again
+        opener.open(&sealers[100].seal(b"second")).unwrap();
+        opener.open(&first[0]).unwrap();
+        assert!(opener.senders().contains(&100) && !opener.senders().contains(&101));
+        assert_eq!(opener.open(&sealers[100].seal(b"third")).unwrap(), b"third");
+    }
+}
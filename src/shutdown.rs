@@ -0,0 +1,259 @@
+//! Cooperative shutdown of receivers: the listeners of the crate (e.g. `lldp::LldpListener`,
+//! `udp::ConnectedUdpSocket`, `igmp_proxy::IgmpProxy`) hand out a `ShutdownHandle`, which other
+//! threads or tasks use to wake blocked receive calls. These fail with an error recognized by
+//! `is_shutdown_error` thereafter, async streams end with None. The socket pair waking blocked
+//! calls is created when a listener hands out its first handle, until then and for non-blocking
+//! sockets receive calls are not polled.
+
+use std::{
+    future::Future,
+    io::{Error, ErrorKind, Result, Write},
+    os::unix::{io::{AsRawFd, BorrowedFd}, net::UnixStream},
+    pin::Pin,
+    sync::{Arc, Mutex, OnceLock, atomic::{AtomicU8, Ordering}},
+    task::{Context, Poll, Waker},
+};
+
+const RUNNING: u8 = 0;
+const DRAINING: u8 = 1;
+const SHUTDOWN: u8 = 2;
+
+#[derive(Debug)]
+struct Signal {
+    state: AtomicU8,
+    wakers: Mutex<Vec<Waker>>,
+    /// reader and writer, the reader becomes readable when the receiver is shut down and wakes
+    /// blocked receive calls via poll, created by `share`
+    wakeup: OnceLock<(UnixStream, UnixStream)>,
+}
+
+/// Handle to shut down a receiver, clones refer to the same receiver.
+#[derive(Clone, Debug)]
+pub struct ShutdownHandle {
+    signal: Arc<Signal>,
+}
+
+/// Payload of the error returned by receive calls after the shutdown.
+#[derive(Debug)]
+struct ShutdownError;
+
+impl std::fmt::Display for ShutdownError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "receiver shut down")
+    }
+}
+
+impl std::error::Error for ShutdownError {}
+
+/// Returns whether the error was returned by a receive call because the receiver was shut down.
+pub fn is_shutdown_error(error: &Error) -> bool {
+    error.get_ref().is_some_and(|inner| inner.is::<ShutdownError>())
+}
+
+impl ShutdownHandle {
+
+    pub(crate) fn new() -> ShutdownHandle {
+        ShutdownHandle {
+            signal: Arc::new(Signal { state: AtomicU8::new(RUNNING), wakers: Mutex::new(Vec::new()), wakeup: OnceLock::new() }),
+        }
+    }
+
+    /// Returns a handle for other threads, creating the socket pair which wakes blocked receive
+    /// calls.
+    pub(crate) fn share(&self) -> Result<ShutdownHandle> {
+        if self.signal.wakeup.get().is_none() {
+            let _ = self.signal.wakeup.set(UnixStream::pair()?);
+            // a shutdown meanwhile found no writer
+            if self.is_shutdown() {
+                self.wake_blocked();
+            }
+        }
+        Ok(self.clone())
+    }
+
+    /// Shuts the receiver down immediately: blocked and later receive calls fail, datagrams
+    /// still queued in the socket are discarded.
+    pub fn shutdown(&self) {
+        self.signal(SHUTDOWN);
+    }
+
+    /// Shuts the receiver down after the datagrams queued in the socket have been received:
+    /// receive calls only fail once the socket has nothing more to read.
+    pub fn drain(&self) {
+        self.signal(DRAINING);
+    }
+
+    /// Returns whether `shutdown` or `drain` was called.
+    pub fn is_shutdown(&self) -> bool {
+        self.signal.state.load(Ordering::Acquire) != RUNNING
+    }
+
+    fn signal(&self, state: u8) {
+        if self.signal.state.fetch_max(state, Ordering::AcqRel) == RUNNING {
+            self.wake_blocked();
+        }
+        for waker in self.signal.wakers.lock().unwrap_or_else(|e| e.into_inner()).drain(..) {
+            waker.wake();
+        }
+    }
+
+    fn wake_blocked(&self) {
+        if let Some((_, writer)) = self.signal.wakeup.get() {
+            let _ = (&*writer).write_all(&[0]);
+        }
+    }
+
+    fn error() -> Error {
+        Error::new(ErrorKind::ConnectionAborted, ShutdownError)
+    }
+
+    /// Waits until the socket is readable (or reports an error), observing its read timeout
+    /// (SO_RCVTIMEO) with WouldBlock. Fails with the shutdown error after the shutdown, after a
+    /// drain only if the socket has nothing to read. Returns immediately for non-blocking sockets
+    /// and if no handle was shared, the receive call reports WouldBlock or blocks itself.
+    pub(crate) fn wait_readable(&self, socket: BorrowedFd<'_>) -> Result<()> {
+        // without a shared handle nothing can signal, non-blocking sockets must not block here
+        let reader = match self.signal.wakeup.get() {
+            Some((reader, _)) if !is_nonblocking(socket)? => reader.as_raw_fd(),
+            _ => -1,
+        };
+        let timeout = match socket2::SockRef::from(&socket).read_timeout()? {
+            Some(timeout) => timeout.as_millis().clamp(1, i32::MAX as u128) as libc::c_int,
+            None => -1,
+        };
+        let mut fds = [
+            libc::pollfd { fd: socket.as_raw_fd(), events: libc::POLLIN, revents: 0 },
+            libc::pollfd { fd: reader, events: libc::POLLIN, revents: 0 },
+        ];
+        loop {
+            match self.signal.state.load(Ordering::Acquire) {
+                SHUTDOWN => return Err(ShutdownHandle::error()),
+                DRAINING => {
+                    fds[1].fd = -1;
+                    return match poll(&mut fds, 0)? {
+                        0 => Err(ShutdownHandle::error()),
+                        _ => Ok(()),
+                    };
+                },
+                _ if reader == -1 => return Ok(()),
+                _ => (),
+            }
+            match poll(&mut fds, timeout)? {
+                0 => return Err(Error::from(ErrorKind::WouldBlock)),
+                _ if fds[0].revents != 0 => return Ok(()),
+                // signaled, the state decides
+                _ => (),
+            }
+        }
+    }
+
+    /// Returns a future which resolves once `shutdown` or `drain` is called, e.g. to end the
+    /// loops of async applications.
+    pub fn signaled(&self) -> Signaled<'_> {
+        Signaled { handle: self }
+    }
+
+    /// Returns whether `drain` (rather than `shutdown`) was called.
+    pub fn is_draining(&self) -> bool {
+        self.signal.state.load(Ordering::Acquire) == DRAINING
+    }
+}
+
+/// Returns whether O_NONBLOCK is set on the socket.
+fn is_nonblocking(socket: BorrowedFd<'_>) -> Result<bool> {
+    match unsafe { libc::fcntl(socket.as_raw_fd(), libc::F_GETFL) } {
+        -1 => Err(Error::last_os_error()),
+        flags => Ok(flags & libc::O_NONBLOCK != 0),
+    }
+}
+
+/// Calls poll(2), retrying on EINTR, returns the number of ready descriptors.
+fn poll(fds: &mut [libc::pollfd], timeout: libc::c_int) -> Result<libc::c_int> {
+    loop {
+        let ready = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) };
+        if ready >= 0 {
+            return Ok(ready);
+        }
+        let e = Error::last_os_error();
+        if e.kind() != ErrorKind::Interrupted {
+            return Err(e);
+        }
+    }
+}
+
+/// Future of `ShutdownHandle::signaled`, independent of the async runtime.
+#[derive(Debug)]
+pub struct Signaled<'a> {
+    handle: &'a ShutdownHandle,
+}
+
+impl Future for Signaled<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.handle.is_shutdown() {
+            return Poll::Ready(());
+        }
+        let mut wakers = self.handle.signal.wakers.lock().unwrap_or_else(|e| e.into_inner());
+        // checked again under the lock, signal() takes the wakers after setting the state
+        if self.handle.is_shutdown() {
+            return Poll::Ready(());
+        }
+        if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use std::{net::UdpSocket, os::unix::io::AsFd, thread, time::{Duration, Instant}};
+
+    #[test]
+    fn test_wait_readable() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let handle = ShutdownHandle::new();
+        // nothing to wait for before a handle is shared
+        handle.wait_readable(socket.as_fd()).unwrap();
+        let other = handle.share().unwrap();
+        socket.set_read_timeout(Some(Duration::from_millis(20))).unwrap();
+        assert_eq!(handle.wait_readable(socket.as_fd()).unwrap_err().kind(), ErrorKind::WouldBlock);
+        socket.set_read_timeout(None).unwrap();
+
+        // a blocked wait is woken by the shutdown
+        let started = Instant::now();
+        let waker = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            other.shutdown();
+        });
+        let error = handle.wait_readable(socket.as_fd()).unwrap_err();
+        assert!(is_shutdown_error(&error));
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        waker.join().unwrap();
+        assert!(!is_shutdown_error(&Error::from(ErrorKind::ConnectionAborted)));
+
+        // non-blocking sockets are not polled
+        let handle = ShutdownHandle::new().share().unwrap();
+        socket.set_nonblocking(true).unwrap();
+        let started = Instant::now();
+        handle.wait_readable(socket.as_fd()).unwrap();
+        assert!(started.elapsed() < Duration::from_millis(50));
+        socket.set_nonblocking(false).unwrap();
+
+        // a drain delivers the queued datagrams first
+        socket.send_to(b"queued", socket.local_addr().unwrap()).unwrap();
+        handle.drain();
+        assert!(handle.is_shutdown());
+        handle.wait_readable(socket.as_fd()).unwrap();
+        let mut buf = [0_u8; 16];
+        socket.recv(&mut buf).unwrap();
+        assert!(is_shutdown_error(&handle.wait_readable(socket.as_fd()).unwrap_err()));
+        // a shutdown after a drain discards the rest
+        socket.send_to(b"queued", socket.local_addr().unwrap()).unwrap();
+        handle.shutdown();
+        assert!(is_shutdown_error(&handle.wait_readable(socket.as_fd()).unwrap_err()));
+    }
+}
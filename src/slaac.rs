@@ -0,0 +1,143 @@
+//! IPv6 interface identifiers for stateless address autoconfiguration: modified EUI-64
+//! identifiers derived from MAC addresses (RFC 4291) with the reverse check whether an address
+//! belongs to a MAC, and RFC 7217 stable, semantically opaque identifiers.
+
+use std::net::Ipv6Addr;
+
+/// Returns the modified EUI-64 interface identifier of the MAC address: ff:fe inserted in the
+/// middle and the universal/local bit inverted.
+pub fn eui64_identifier(mac: &[u8; 6]) -> [u8; 8] {
+    [mac[0] ^ 0x02, mac[1], mac[2], 0xff, 0xfe, mac[3], mac[4], mac[5]]
+}
+
+/// Returns the address of the /64 prefix with the modified EUI-64 identifier of the MAC address.
+pub fn eui64_address(prefix: &Ipv6Addr, mac: &[u8; 6]) -> Ipv6Addr {
+    with_identifier(prefix, &eui64_identifier(mac))
+}
+
+/// Returns the fe80::/64 link-local address with the modified EUI-64 identifier of the MAC address.
+pub fn eui64_link_local(mac: &[u8; 6]) -> Ipv6Addr {
+    eui64_address(&Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 0), mac)
+}
+
+/// Returns the MAC address encoded in an address with modified EUI-64 identifier, None if the
+/// identifier has no ff:fe in the middle.
+pub fn mac_from_eui64(address: &Ipv6Addr) -> Option<[u8; 6]> {
+    let octets = address.octets();
+    if octets[11] != 0xff || octets[12] != 0xfe {
+        return None;
+    }
+    Some([octets[8] ^ 0x02, octets[9], octets[10], octets[13], octets[14], octets[15]])
+}
+
+/// Returns whether the address has the modified EUI-64 identifier of the MAC address, i.e. was
+/// autoconfigured by the host with this MAC (independent of the prefix).
+pub fn belongs_to_mac(address: &Ipv6Addr, mac: &[u8; 6]) -> bool {
+    mac_from_eui64(address).as_ref() == Some(mac)
+}
+
+/// Returns the RFC 7217 stable address of the /64 prefix: the identifier is the leftmost 64 bits
+/// of SHA-256(prefix | interface | network id | DAD counter | secret key). The address is stable for
+/// the same inputs but differs between prefixes (networks), so it cannot be used to track the
+/// host. The interface is a stable interface name or index, the optional network id e.g. the SSID
+/// of a wireless network and the DAD counter is incremented after a duplicate address has been
+/// detected. Identifiers reserved by RFC 5453 are skipped by incrementing the counter.
+/// The secret key should be at least 128 bits, see `generate_secret`.
+pub fn stable_privacy_address(prefix: &Ipv6Addr, interface: &[u8], network_id: &[u8], dad_counter: u8,
+                              secret: &[u8]) -> Ipv6Addr {
+    let prefix_octets = prefix.octets();
+    let mut counter = dad_counter;
+    loop {
+        let mut input = Vec::with_capacity(8 + interface.len() + network_id.len() + 1 + secret.len());
+        input.extend_from_slice(&prefix_octets[..8]);
+        input.extend_from_slice(interface);
+        input.extend_from_slice(network_id);
+        input.push(counter);
+        input.extend_from_slice(secret);
+        let digest = super::sha256::sha256(&input);
+        let mut identifier = [0_u8; 8];
+        identifier.copy_from_slice(&digest[..8]);
+        if !is_reserved_identifier(&identifier) {
+            return with_identifier(prefix, &identifier);
+        }
+        counter = counter.wrapping_add(1);
+    }
+}
+
+/// Generates a 128 bit secret key for `stable_privacy_address` from the random generator of the
+/// operating system, which has to be stored persistently to keep the addresses stable.
+pub fn generate_secret() -> std::io::Result<[u8; 16]> {
+    let mut secret = [0_u8; 16];
+    super::random::secure_random_bytes(&mut secret)?;
+    Ok(secret)
+}
+
+/// Returns whether the interface identifier is reserved (RFC 5453): the subnet-router anycast
+/// identifier, the reserved identifiers 0200:5eff:fe00:0000 to 0200:5eff:feff:ffff and the
+/// subnet anycast identifiers fdff:ffff:ffff:ff80 to fdff:ffff:ffff:ffff.
+pub fn is_reserved_identifier(identifier: &[u8; 8]) -> bool {
+    let value = u64::from_be_bytes(*identifier);
+    value == 0 || (0x0200_5eff_fe00_0000..=0x0200_5eff_feff_ffff).contains(&value)
+        || value >= 0xfdff_ffff_ffff_ff80
+}
+
+/// Combines the upper 64 bits of the prefix with the interface identifier.
+fn with_identifier(prefix: &Ipv6Addr, identifier: &[u8; 8]) -> Ipv6Addr {
+    let mut octets = prefix.octets();
+    octets[8..].copy_from_slice(identifier);
+    Ipv6Addr::from(octets)
+}
+
+#[cfg(target_os = "linux")]
+impl super::IpInterface {
+
+    /// Returns the EUI-64 link-local address of the interface's MAC address, None for interfaces
+    /// without MAC address.
+    pub fn eui64_link_local(&self) -> std::io::Result<Option<Ipv6Addr>> {
+        Ok(self.mac_address()?.map(|mac| eui64_link_local(&mac)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::convert::TryInto;
+
+    use super::*;
+
+    #[test]
+    fn test_eui64() {
+        let mac = [0x00, 0x1b, 0x21, 0x3c, 0x4d, 0x5e];
+        assert_eq!(eui64_identifier(&mac), [0x02, 0x1b, 0x21, 0xff, 0xfe, 0x3c, 0x4d, 0x5e]);
+        assert_eq!(eui64_link_local(&mac), "fe80::21b:21ff:fe3c:4d5e".parse::<Ipv6Addr>().unwrap());
+        let global = eui64_address(&"2001:db8:1:2::".parse().unwrap(), &mac);
+        assert_eq!(global, "2001:db8:1:2:21b:21ff:fe3c:4d5e".parse::<Ipv6Addr>().unwrap());
+        assert_eq!(mac_from_eui64(&global), Some(mac));
+        assert!(belongs_to_mac(&global, &mac));
+        assert!(!belongs_to_mac(&global, &[0x02, 0x1b, 0x21, 0x3c, 0x4d, 0x5e]));
+        assert_eq!(mac_from_eui64(&"fe80::1".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn test_stable_privacy_address() {
+        let prefix: Ipv6Addr = "2001:db8:1:2::".parse().unwrap();
+        let secret = [7_u8; 16];
+        let address = stable_privacy_address(&prefix, b"eth0", b"", 0, &secret);
+        assert_eq!(address.segments()[..4], prefix.segments()[..4]);
+        assert_eq!(address, stable_privacy_address(&prefix, b"eth0", b"", 0, &secret));
+        assert_ne!(address, stable_privacy_address(&prefix, b"eth0", b"", 1, &secret));
+        assert_ne!(address, stable_privacy_address(&prefix, b"eth1", b"", 0, &secret));
+        assert_ne!(address, stable_privacy_address(&"2001:db8:1:3::".parse().unwrap(), b"eth0", b"", 0, &secret));
+        assert_ne!(address, stable_privacy_address(&prefix, b"eth0", b"", 0, &[8_u8; 16]));
+        assert!(!is_reserved_identifier(&address.octets()[8..].try_into().unwrap()));
+    }
+
+    #[test]
+    fn test_reserved_identifier() {
+        assert!(is_reserved_identifier(&[0; 8]));
+        assert!(is_reserved_identifier(&[0x02, 0x00, 0x5e, 0xff, 0xfe, 0x00, 0x52, 0x13]));
+        assert!(is_reserved_identifier(&[0xfd, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x80]));
+        assert!(!is_reserved_identifier(&[0xfd, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f]));
+        assert!(!is_reserved_identifier(&[0x02, 0x1b, 0x21, 0xff, 0xfe, 0x3c, 0x4d, 0x5e]));
+    }
+}
@@ -0,0 +1,438 @@
+//! Passive observer of IGMP and MLD on the links of the host: tracks the querier elected per
+//! interface and address family (the router with the lowest address, RFC 3376 and RFC 3810)
+//! and the groups with their reporters, with change events. A link without querier makes IGMP
+//! snooping switches drop the memberships after the membership interval, the typical cause of
+//! multicast streams stopping after a few minutes.
+
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    io::{Error, ErrorKind, Result},
+    net::IpAddr,
+    os::unix::io::{AsFd, AsRawFd, BorrowedFd},
+    time::{Duration, Instant},
+};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+use super::{
+    igmp_proxy::{BLOCK_OLD_SOURCES, CHANGE_TO_INCLUDE_MODE, GROUP_MEMBERSHIP_INTERVAL, IgmpMessage, MODE_IS_INCLUDE,
+                 OTHER_QUERIER_PRESENT_INTERVAL},
+    icmpv6::MLD_LISTENER_QUERY,
+    mld::MldMessage,
+    packet::{ETHERTYPE_IPV4, ETHERTYPE_IPV6, IPPROTO_ICMPV6, IPPROTO_IGMP, IcmpMessage, Ipv4Packet, Ipv6Packet},
+    shutdown::ShutdownHandle,
+    sockaddr::sock_addr_from_raw,
+    sockopt::set_option,
+};
+
+/// A change observed by the `SnoopingTable`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SnoopingEvent {
+    /// another router became querier of the interface and address family, or the querier stopped
+    /// querying (None)
+    QuerierChanged { interface_index: u32, ipv6: bool, previous: Option<IpAddr>, querier: Option<IpAddr> },
+
+    /// the first reporter of the group appeared on the interface
+    GroupJoined { interface_index: u32, group: IpAddr },
+
+    /// the last reporter of the group left or its membership expired
+    GroupLeft { interface_index: u32, group: IpAddr },
+}
+
+/// The querier of an interface and address family.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Querier {
+    /// address of the querier
+    pub address: IpAddr,
+
+    /// link layer address of the querier if known
+    pub link_address: Option<[u8; 6]>,
+
+    /// IGMP (1 to 3) or MLD (1 or 2) version of the queries
+    pub version: u8,
+
+    /// time of the last query
+    pub last_query: Instant,
+}
+
+/// State of the queriers and groups observed on the interfaces.
+#[derive(Clone, Debug, Default)]
+pub struct SnoopingTable {
+    /// querier per interface and address family (true for IPv6)
+    queriers: HashMap<(u32, bool), Querier>,
+    /// reporters of the groups per interface and when their membership expires
+    groups: HashMap<(u32, IpAddr), HashMap<IpAddr, Instant>>,
+}
+
+impl SnoopingTable {
+
+    /// Creates a table without queriers and groups.
+    pub fn new() -> SnoopingTable {
+        SnoopingTable::default()
+    }
+
+    /// Processes a query, the querier is replaced if the query comes from a lower address or the
+    /// querier itself.
+    pub fn query(&mut self, interface_index: u32, querier: Querier) -> Vec<SnoopingEvent> {
+        let key = (interface_index, querier.address.is_ipv6());
+        let previous = self.queriers.get(&key).map(|current| current.address);
+        if previous.is_some_and(|previous| less(&previous, &querier.address)) {
+            return Vec::new();
+        }
+        self.queriers.insert(key, querier);
+        match previous {
+            Some(previous) if previous == querier.address => Vec::new(),
+            _ => vec![SnoopingEvent::QuerierChanged { interface_index, ipv6: key.1, previous, querier: Some(querier.address) }],
+        }
+    }
+
+    /// Processes a report of the group from the reporter.
+    pub fn report(&mut self, interface_index: u32, group: IpAddr, reporter: IpAddr, now: Instant) -> Vec<SnoopingEvent> {
+        let reporters = self.groups.entry((interface_index, group)).or_default();
+        let joined = reporters.is_empty();
+        reporters.insert(reporter, now + GROUP_MEMBERSHIP_INTERVAL);
+        match joined {
+            true => vec![SnoopingEvent::GroupJoined { interface_index, group }],
+            false => Vec::new(),
+        }
+    }
+
+    /// Processes a leave (done) of the group from the reporter.
+    pub fn leave(&mut self, interface_index: u32, group: IpAddr, reporter: IpAddr) -> Vec<SnoopingEvent> {
+        let key = (interface_index, group);
+        let left = match self.groups.get_mut(&key) {
+            Some(reporters) => reporters.remove(&reporter).is_some() && reporters.is_empty(),
+            None => false,
+        };
+        if !left {
+            return Vec::new();
+        }
+        self.groups.remove(&key);
+        vec![SnoopingEvent::GroupLeft { interface_index, group }]
+    }
+
+    /// Processes an IGMP message received on the interface from the source, `len` is the length
+    /// of the message which distinguishes IGMPv2 from IGMPv3 queries.
+    pub fn apply_igmp(&mut self, interface_index: u32, source: IpAddr, link_address: Option<[u8; 6]>,
+                      message: &IgmpMessage, len: usize, now: Instant) -> Vec<SnoopingEvent> {
+        match message {
+            IgmpMessage::Query { max_response_time, .. } => {
+                let version = match (len, max_response_time.is_zero()) {
+                    (12.., _) => 3,
+                    (_, true) => 1,
+                    _ => 2,
+                };
+                self.query(interface_index, Querier { address: source, link_address, version, last_query: now })
+            },
+            IgmpMessage::ReportV1 { group } | IgmpMessage::ReportV2 { group } =>
+                self.report(interface_index, IpAddr::V4(*group), source, now),
+            IgmpMessage::Leave { group } => self.leave(interface_index, IpAddr::V4(*group), source),
+            IgmpMessage::ReportV3 { records } => records.iter()
+                .flat_map(|record| self.record(interface_index, IpAddr::V4(record.group), record.record_type,
+                                               record.sources.is_empty(), source, now))
+                .collect(),
+        }
+    }
+
+    /// Processes an MLD message received on the interface from the source, `len` is the length
+    /// of the message which distinguishes MLDv1 from MLDv2 queries.
+    pub fn apply_mld(&mut self, interface_index: u32, source: IpAddr, link_address: Option<[u8; 6]>,
+                     message: &MldMessage, len: usize, now: Instant) -> Vec<SnoopingEvent> {
+        match message {
+            MldMessage::Query { .. } => {
+                let version = if len >= 28 { 2 } else { 1 };
+                self.query(interface_index, Querier { address: source, link_address, version, last_query: now })
+            },
+            MldMessage::ReportV1 { group } => self.report(interface_index, IpAddr::V6(*group), source, now),
+            MldMessage::Done { group } => self.leave(interface_index, IpAddr::V6(*group), source),
+            MldMessage::ReportV2 { records } => records.iter()
+                .flat_map(|record| self.record(interface_index, IpAddr::V6(record.group), record.record_type,
+                                               record.sources.is_empty(), source, now))
+                .collect(),
+        }
+    }
+
+    /// Processes a group record of an IGMPv3 or MLDv2 report: an include filter without sources
+    /// leaves the group, changes of the sources are ignored, other records report the group.
+    fn record(&mut self, interface_index: u32, group: IpAddr, record_type: u8, no_sources: bool, reporter: IpAddr,
+              now: Instant) -> Vec<SnoopingEvent> {
+        match record_type {
+            MODE_IS_INCLUDE | CHANGE_TO_INCLUDE_MODE if no_sources => self.leave(interface_index, group, reporter),
+            BLOCK_OLD_SOURCES => Vec::new(),
+            _ => self.report(interface_index, group, reporter, now),
+        }
+    }
+
+    /// Removes the queriers that did not query within the other querier present interval and
+    /// the expired memberships, returns the changes.
+    pub fn expire(&mut self, now: Instant) -> Vec<SnoopingEvent> {
+        let mut events = Vec::new();
+        self.queriers.retain(|(interface_index, ipv6), querier| {
+            if querier.last_query + OTHER_QUERIER_PRESENT_INTERVAL > now {
+                return true;
+            }
+            events.push(SnoopingEvent::QuerierChanged {
+                interface_index: *interface_index, ipv6: *ipv6, previous: Some(querier.address), querier: None,
+            });
+            false
+        });
+        self.groups.retain(|(interface_index, group), reporters| {
+            reporters.retain(|_, expires| *expires > now);
+            if !reporters.is_empty() {
+                return true;
+            }
+            events.push(SnoopingEvent::GroupLeft { interface_index: *interface_index, group: *group });
+            false
+        });
+        events
+    }
+
+    /// Returns the querier of the interface and address family.
+    pub fn querier(&self, interface_index: u32, ipv6: bool) -> Option<&Querier> {
+        self.queriers.get(&(interface_index, ipv6))
+    }
+
+    /// Returns the groups with reporters on the interface, sorted.
+    pub fn groups(&self, interface_index: u32) -> Vec<IpAddr> {
+        let mut groups: Vec<IpAddr> = self.groups.keys()
+            .filter(|(index, _)| *index == interface_index)
+            .map(|(_, group)| *group)
+            .collect();
+        groups.sort();
+        groups
+    }
+
+    /// Returns the reporters of the group on the interface, sorted.
+    pub fn reporters(&self, interface_index: u32, group: &IpAddr) -> Vec<IpAddr> {
+        let mut reporters: Vec<IpAddr> = self.groups.get(&(interface_index, *group))
+            .map(|reporters| reporters.keys().copied().collect())
+            .unwrap_or_default();
+        reporters.sort();
+        reporters
+    }
+
+    /// Returns the time remaining until the memberships on interfaces without querier expire,
+    /// i.e. when snooping switches stop forwarding the groups: the latest expiry of a report per
+    /// interface and address family without querier.
+    pub fn unqueried(&self, now: Instant) -> Vec<(u32, bool, Duration)> {
+        let mut remaining: HashMap<(u32, bool), Duration> = HashMap::new();
+        for ((interface_index, group), reporters) in &self.groups {
+            let key = (*interface_index, group.is_ipv6());
+            if self.queriers.contains_key(&key) {
+                continue;
+            }
+            let latest = reporters.values().max().map(|expires| expires.saturating_duration_since(now));
+            let entry = remaining.entry(key).or_default();
+            *entry = (*entry).max(latest.unwrap_or_default());
+        }
+        let mut remaining: Vec<(u32, bool, Duration)> = remaining.into_iter().map(|((i, v6), d)| (i, v6, d)).collect();
+        remaining.sort();
+        remaining
+    }
+}
+
+/// Returns whether the address `a` is lower than `b` for the querier election.
+fn less(a: &IpAddr, b: &IpAddr) -> bool {
+    match (a, b) {
+        (IpAddr::V4(a), IpAddr::V4(b)) => a < b,
+        (IpAddr::V6(a), IpAddr::V6(b)) => a < b,
+        _ => false,
+    }
+}
+
+/// Packet socket observing the IGMP and MLD messages on the interfaces (including those sent by
+/// this host) into a `SnoopingTable`, requires CAP_NET_RAW. The interfaces receive all multicast
+/// frames (PACKET_MR_ALLMULTI) while the observer is open.
+/// ```no_run
+/// use net_utils::snooping::SnoopingObserver;
+/// let mut observer = SnoopingObserver::new(None).unwrap();
+/// loop {
+///     for event in observer.recv().unwrap() {
+///         println!("{:?}", event);
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct SnoopingObserver {
+    socket: Socket,
+    table: SnoopingTable,
+    shutdown: ShutdownHandle,
+}
+
+impl SnoopingObserver {
+
+    /// Opens the observer on the interface with the given index, or on all interfaces for None.
+    pub fn new(interface_index: Option<u32>) -> Result<SnoopingObserver> {
+        let protocol = (libc::ETH_P_ALL as u16).to_be() as libc::c_int;
+        let socket = super::sys_socket::socket(Domain::PACKET, Type::DGRAM, Some(Protocol::from(protocol)))?;
+        let mut address: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+        address.sll_family = libc::AF_PACKET as u16;
+        address.sll_protocol = protocol as u16;
+        address.sll_ifindex = interface_index.unwrap_or(0) as i32;
+        let address = sock_addr_from_raw(&address, std::mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t);
+        super::sys_socket::bind(&socket, &address)?;
+        super::netlink::join_links(interface_index, |_| true, |index| receive_all_multicast(&socket, index))?;
+        Ok(SnoopingObserver { socket, table: SnoopingTable::new(), shutdown: ShutdownHandle::new() })
+    }
+
+    /// Returns the observed state.
+    pub fn table(&self) -> &SnoopingTable {
+        &self.table
+    }
+
+    /// Sets the timeout of `recv`, None blocks indefinitely.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        self.socket.set_read_timeout(timeout)
+    }
+
+    /// Returns a handle which shuts down blocked and later `recv` calls from other threads, see
+    /// `shutdown::ShutdownHandle`.
+    pub fn shutdown_handle(&self) -> Result<ShutdownHandle> {
+        self.shutdown.share()
+    }
+
+    /// Receives packets until the next IGMP or MLD message and returns the changes it and the
+    /// expiry of the table caused, which may be none. Fails with TimedOut after the read timeout.
+    pub fn recv(&mut self) -> Result<Vec<SnoopingEvent>> {
+        let mut buffer = vec![0_u8; 65536];
+        loop {
+            let mut address: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+            let mut address_len = std::mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t;
+            let received = self.shutdown.wait_readable(self.socket.as_fd()).and_then(|_| {
+                let len = unsafe {
+                    libc::recvfrom(self.socket.as_raw_fd(), buffer.as_mut_ptr() as *mut libc::c_void, buffer.len(), 0,
+                                   &mut address as *mut libc::sockaddr_ll as *mut libc::sockaddr, &mut address_len)
+                };
+                if len < 0 { Err(Error::last_os_error()) } else { Ok(len as usize) }
+            });
+            let len = received.map_err(|e| match e.kind() {
+                ErrorKind::WouldBlock => Error::new(ErrorKind::TimedOut, "no IGMP or MLD message"),
+                _ => e,
+            })?;
+            let link_address = match address.sll_halen {
+                6 => Some(address.sll_addr[..6].try_into().unwrap()),
+                _ => None,
+            };
+            let now = Instant::now();
+            let interface_index = address.sll_ifindex as u32;
+            let mut events = match u16::from_be(address.sll_protocol) {
+                ETHERTYPE_IPV4 => self.observe_ipv4(interface_index, link_address, &buffer[..len], now),
+                ETHERTYPE_IPV6 => self.observe_ipv6(interface_index, link_address, &buffer[..len], now),
+                _ => None,
+            };
+            if let Some(events) = events.as_mut() {
+                events.extend(self.table.expire(now));
+                return Ok(std::mem::take(events));
+            }
+        }
+    }
+
+    fn observe_ipv4(&mut self, interface_index: u32, link_address: Option<[u8; 6]>, packet: &[u8], now: Instant)
+                    -> Option<Vec<SnoopingEvent>> {
+        let packet = Ipv4Packet::new(packet).filter(|packet| packet.protocol() == IPPROTO_IGMP)?;
+        let message = IgmpMessage::parse(packet.payload())?;
+        Some(self.table.apply_igmp(interface_index, IpAddr::V4(packet.source()), link_address, &message,
+                                   packet.payload().len(), now))
+    }
+
+    fn observe_ipv6(&mut self, interface_index: u32, link_address: Option<[u8; 6]>, packet: &[u8], now: Instant)
+                    -> Option<Vec<SnoopingEvent>> {
+        let packet = Ipv6Packet::new(packet)?;
+        let (protocol, data) = packet.upper_layer()?;
+        if protocol != IPPROTO_ICMPV6 {
+            return None;
+        }
+        let icmp = IcmpMessage::new(data).filter(|icmp| icmp.verify_checksum_v6(&packet.source(), &packet.destination()))?;
+        // MLD messages are sent from link-local addresses, queries from :: are invalid
+        if icmp.msg_type() == MLD_LISTENER_QUERY && packet.source().is_unspecified() {
+            return None;
+        }
+        let message = MldMessage::parse(data)?;
+        Some(self.table.apply_mld(interface_index, IpAddr::V6(packet.source()), link_address, &message, data.len(), now))
+    }
+}
+
+impl AsFd for SnoopingObserver {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.socket.as_fd()
+    }
+}
+
+/// Makes the interface receive all multicast frames for the socket.
+fn receive_all_multicast(socket: &Socket, interface_index: u32) -> Result<()> {
+    let mut membership: libc::packet_mreq = unsafe { std::mem::zeroed() };
+    membership.mr_ifindex = interface_index as i32;
+    membership.mr_type = libc::PACKET_MR_ALLMULTI as u16;
+    set_option(socket.as_raw_fd(), libc::SOL_PACKET, libc::PACKET_ADD_MEMBERSHIP, &membership)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::igmp_proxy::GroupRecord;
+
+    #[test]
+    fn test_querier_election() {
+        let now = Instant::now();
+        let mut table = SnoopingTable::new();
+        let querier = |address: &str, version| Querier {
+            address: address.parse().unwrap(), link_address: None, version, last_query: now,
+        };
+        assert_eq!(table.query(2, querier("192.0.2.9", 2)), vec![SnoopingEvent::QuerierChanged {
+            interface_index: 2, ipv6: false, previous: None, querier: Some("192.0.2.9".parse().unwrap()),
+        }]);
+        // higher addresses lose, lower ones win, the querier itself refreshes
+        assert_eq!(table.query(2, querier("192.0.2.10", 2)), vec![]);
+        assert_eq!(table.query(2, querier("192.0.2.1", 3)).len(), 1);
+        assert_eq!(table.query(2, querier("192.0.2.1", 3)), vec![]);
+        assert_eq!(table.query(2, querier("fe80::1", 2)).len(), 1);
+        assert_eq!(table.querier(2, false).map(|q| (q.address, q.version)), Some(("192.0.2.1".parse().unwrap(), 3)));
+        assert_eq!(table.expire(now + Duration::from_secs(10)), vec![]);
+        let mut events = table.expire(now + OTHER_QUERIER_PRESENT_INTERVAL);
+        events.sort_by_key(|event| format!("{:?}", event));
+        assert_eq!(events, vec![
+            SnoopingEvent::QuerierChanged { interface_index: 2, ipv6: false, previous: Some("192.0.2.1".parse().unwrap()),
+                                            querier: None },
+            SnoopingEvent::QuerierChanged { interface_index: 2, ipv6: true, previous: Some("fe80::1".parse().unwrap()),
+                                            querier: None },
+        ]);
+        assert!(table.querier(2, false).is_none());
+    }
+
+    #[test]
+    fn test_groups() {
+        let now = Instant::now();
+        let mut table = SnoopingTable::new();
+        let group = "239.1.2.3".parse().unwrap();
+        let (host1, host2) = ("192.0.2.5".parse().unwrap(), "192.0.2.6".parse().unwrap());
+        let report = IgmpMessage::ReportV2 { group };
+        assert_eq!(table.apply_igmp(2, host1, None, &report, 8, now),
+                   vec![SnoopingEvent::GroupJoined { interface_index: 2, group: IpAddr::V4(group) }]);
+        assert_eq!(table.apply_igmp(2, host2, None, &report, 8, now), vec![]);
+        assert_eq!(table.reporters(2, &IpAddr::V4(group)), vec![host1, host2]);
+        assert_eq!(table.apply_igmp(2, host1, None, &IgmpMessage::Leave { group }, 8, now), vec![]);
+        // an IGMPv3 include without sources is a leave
+        let leave = IgmpMessage::ReportV3 { records: vec![
+            GroupRecord { record_type: CHANGE_TO_INCLUDE_MODE, group, sources: Vec::new() },
+        ] };
+        assert_eq!(table.apply_igmp(2, host2, None, &leave, 16, now),
+                   vec![SnoopingEvent::GroupLeft { interface_index: 2, group: IpAddr::V4(group) }]);
+        assert!(table.groups(2).is_empty());
+
+        // memberships without querier expire
+        table.apply_igmp(2, host1, None, &report, 8, now);
+        assert_eq!(table.unqueried(now + Duration::from_secs(60)), vec![(2, false, GROUP_MEMBERSHIP_INTERVAL - Duration::from_secs(60))]);
+        let query = IgmpMessage::Query { max_response_time: Duration::from_secs(10), group: "0.0.0.0".parse().unwrap(), sources: vec![] };
+        table.apply_igmp(2, "192.0.2.1".parse().unwrap(), Some([2, 0, 0, 0, 0, 1]), &query, 12, now);
+        assert_eq!(table.querier(2, false).unwrap().version, 3);
+        assert!(table.unqueried(now).is_empty());
+        // the querier stops querying before the membership expires
+        assert_eq!(table.expire(now + GROUP_MEMBERSHIP_INTERVAL), vec![
+            SnoopingEvent::QuerierChanged { interface_index: 2, ipv6: false, previous: Some("192.0.2.1".parse().unwrap()),
+                                            querier: None },
+            SnoopingEvent::GroupLeft { interface_index: 2, group: IpAddr::V4(group) },
+        ]);
+    }
+}
@@ -0,0 +1,228 @@
+//! SNTP (RFC 4330) client: queries the clock offset and round trip delay to an NTP server.
+
+use std::{
+    convert::TryInto,
+    io::{Error, ErrorKind, Result},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use socket2::{Domain, Protocol, SockAddr, Type};
+
+use super::device::bind_to_device;
+
+/// Well known NTP port.
+pub const NTP_PORT: u16 = 123;
+
+/// Default timeout for the server response.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+const PACKET_LEN: usize = 48;
+/// LI = 0 (no warning), VN = 4, mode = 3 (client)
+const CLIENT_HEADER: u8 = 0x23;
+const MODE_SERVER: u8 = 4;
+const MODE_BROADCAST: u8 = 5;
+/// Seconds between the NTP epoch (1900) and the unix epoch (1970).
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+
+/// Options for an SNTP query.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SntpOpts {
+    /// maximum time to wait for the response
+    pub timeout: Duration,
+
+    /// local address (and port, 0 for any) of the query socket
+    pub local_addr: Option<SocketAddr>,
+
+    /// name of the network device the query is sent from (linux, android and apple systems)
+    pub device: Option<String>,
+}
+
+impl Default for SntpOpts {
+    fn default() -> SntpOpts {
+        SntpOpts { timeout: DEFAULT_TIMEOUT, local_addr: None, device: None }
+    }
+}
+
+/// Result of an SNTP query.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SntpResult {
+    /// offset of the local clock in seconds, to be added to the local time to get the server's
+    /// time (positive if the local clock is behind)
+    pub offset: f64,
+
+    /// round trip delay of the request without the server's processing time
+    pub delay: Duration,
+
+    /// stratum of the server (1 = primary reference)
+    pub stratum: u8,
+
+    /// reference identifier of the server (e.g. "GPS\0" for stratum 1 or the IPv4 address of the
+    /// upstream server)
+    pub reference_id: [u8; 4],
+
+    /// time the server sent the response
+    pub server_time: SystemTime,
+}
+
+/// Sends an SNTP request to the server and waits for the response.
+pub fn query(server: SocketAddr, opts: &SntpOpts) -> Result<SntpResult> {
+    let socket = create_socket(&server, opts)?;
+    socket.set_read_timeout(Some(opts.timeout))?;
+    socket.connect(server)?;
+
+    let sent_at = SystemTime::now();
+    let request = request_packet(sent_at);
+    socket.send(&request)?;
+    let mut response = [0_u8; PACKET_LEN * 2];
+    let len = socket.recv(&mut response).map_err(|e| match e.kind() {
+        ErrorKind::WouldBlock => Error::new(ErrorKind::TimedOut, "no SNTP response"),
+        _ => e,
+    })?;
+    parse_response(&request, &response[..len], SystemTime::now())
+}
+
+/// Sends an SNTP request to the server and waits for the response.
+/// Requires the feature 'tokio-net' and must be called within a tokio runtime.
+#[cfg(feature = "tokio-net")]
+pub async fn query_async(server: SocketAddr, opts: &SntpOpts) -> Result<SntpResult> {
+    let socket = create_socket(&server, opts)?;
+    socket.set_nonblocking(true)?;
+    let socket = tokio::net::UdpSocket::from_std(socket)?;
+    socket.connect(server).await?;
+
+    let sent_at = SystemTime::now();
+    let request = request_packet(sent_at);
+    socket.send(&request).await?;
+    let mut response = [0_u8; PACKET_LEN * 2];
+    let len = tokio::time::timeout(opts.timeout, socket.recv(&mut response)).await
+        .map_err(|_| Error::new(ErrorKind::TimedOut, "no SNTP response"))??;
+    parse_response(&request, &response[..len], SystemTime::now())
+}
+
+fn create_socket(server: &SocketAddr, opts: &SntpOpts) -> Result<UdpSocket> {
+    let socket = super::sys_socket::socket(Domain::for_address(*server), Type::DGRAM, Some(Protocol::UDP))?;
+    if let Some(device) = &opts.device {
+        bind_to_device(&socket, device, server)?;
+    }
+    let local = opts.local_addr.unwrap_or_else(|| match server {
+        SocketAddr::V4(_) => SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0),
+        SocketAddr::V6(_) => SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0),
+    });
+    super::sys_socket::bind(&socket, &SockAddr::from(local))?;
+    Ok(socket.into())
+}
+
+/// Creates a client request, the transmit timestamp is used to match the response.
+fn request_packet(now: SystemTime) -> [u8; PACKET_LEN] {
+    let mut packet = [0_u8; PACKET_LEN];
+    packet[0] = CLIENT_HEADER;
+    packet[40..48].copy_from_slice(&to_ntp_timestamp(now).to_be_bytes());
+    packet
+}
+
+/// Validates the response to the request and calculates offset and delay from the timestamps
+/// T1 (request sent), T2 (request received by the server), T3 (response sent) and T4 (response
+/// received).
+fn parse_response(request: &[u8; PACKET_LEN], response: &[u8], received_at: SystemTime) -> Result<SntpResult> {
+    if response.len() < PACKET_LEN {
+        return Err(Error::new(ErrorKind::InvalidData, "SNTP response too short"));
+    }
+    let mode = response[0] & 0x07;
+    if mode != MODE_SERVER && mode != MODE_BROADCAST {
+        return Err(Error::new(ErrorKind::InvalidData, "not an SNTP server response"));
+    }
+    let stratum = response[1];
+    let reference_id: [u8; 4] = response[12..16].try_into().unwrap();
+    if stratum == 0 {
+        return Err(Error::new(ErrorKind::ConnectionRefused,
+                              format!("SNTP kiss-o'-death {}", String::from_utf8_lossy(&reference_id))));
+    }
+    if response[24..32] != request[40..48] {
+        return Err(Error::new(ErrorKind::InvalidData, "SNTP response does not match the request"));
+    }
+    let timestamp = |offset: usize| u64::from_be_bytes(response[offset..offset + 8].try_into().unwrap());
+    let t1 = ntp_seconds(timestamp(24));
+    let t2 = ntp_seconds(timestamp(32));
+    let t3 = ntp_seconds(timestamp(40));
+    let t4 = ntp_seconds(to_ntp_timestamp(received_at));
+    let delay = ((t4 - t1) - (t3 - t2)).max(0.0);
+    Ok(SntpResult {
+        offset: ((t2 - t1) + (t3 - t4)) / 2.0,
+        delay: Duration::from_secs_f64(delay),
+        stratum,
+        reference_id,
+        server_time: from_ntp_timestamp(timestamp(40)),
+    })
+}
+
+/// Converts the system time into a 64 bit NTP timestamp (32 bit seconds, 32 bit fraction).
+fn to_ntp_timestamp(time: SystemTime) -> u64 {
+    let since_unix = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = (since_unix.as_secs() + NTP_UNIX_OFFSET) as u32 as u64;
+    let fraction = ((since_unix.subsec_nanos() as u64) << 32) / 1_000_000_000;
+    (seconds << 32) | fraction
+}
+
+/// Converts a 64 bit NTP timestamp into the system time, timestamps with the most significant
+/// bit cleared are taken as era 1 (starting 2036) as recommended by RFC 4330.
+fn from_ntp_timestamp(timestamp: u64) -> SystemTime {
+    let mut seconds = timestamp >> 32;
+    if seconds & 0x8000_0000 == 0 {
+        seconds += 1 << 32;
+    }
+    let nanos = ((timestamp & 0xffff_ffff) * 1_000_000_000) >> 32;
+    UNIX_EPOCH + Duration::new(seconds - NTP_UNIX_OFFSET, nanos as u32)
+}
+
+/// Returns the NTP timestamp as seconds since the unix epoch.
+fn ntp_seconds(timestamp: u64) -> f64 {
+    let time = from_ntp_timestamp(timestamp);
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_ntp_timestamp() {
+        let time = UNIX_EPOCH + Duration::new(1_700_000_000, 250_000_000);
+        let timestamp = to_ntp_timestamp(time);
+        assert_eq!(timestamp >> 32, 1_700_000_000 + NTP_UNIX_OFFSET);
+        assert_eq!(timestamp & 0xffff_ffff, 0x4000_0000);
+        assert_eq!(from_ntp_timestamp(timestamp), time);
+        // era 1: 2036-02-07 06:28:16 UTC and later
+        let era1 = UNIX_EPOCH + Duration::from_secs((1 << 32) - NTP_UNIX_OFFSET + 10);
+        assert_eq!(from_ntp_timestamp(to_ntp_timestamp(era1)), era1);
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let t1 = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let request = request_packet(t1);
+        let mut response = [0_u8; PACKET_LEN];
+        response[0] = 0x24;
+        response[1] = 2;
+        response[12..16].copy_from_slice(&[192, 168, 1, 1]);
+        response[24..32].copy_from_slice(&request[40..48]);
+        // server clock is 10s ahead, 20ms network delay each way, 10ms processing
+        let t2 = t1 + Duration::from_millis(10_020);
+        let t3 = t2 + Duration::from_millis(10);
+        response[32..40].copy_from_slice(&to_ntp_timestamp(t2).to_be_bytes());
+        response[40..48].copy_from_slice(&to_ntp_timestamp(t3).to_be_bytes());
+        let t4 = t1 + Duration::from_millis(50);
+        let result = parse_response(&request, &response, t4).unwrap();
+        assert!((result.offset - 10.0).abs() < 1e-6);
+        assert!((result.delay.as_secs_f64() - 0.04).abs() < 1e-6);
+        assert_eq!(result.stratum, 2);
+        assert_eq!(result.reference_id, [192, 168, 1, 1]);
+
+        response[24] ^= 1;
+        assert!(parse_response(&request, &response, t4).is_err());
+        response[24] ^= 1;
+        response[1] = 0;
+        assert_eq!(parse_response(&request, &response, t4).unwrap_err().kind(), ErrorKind::ConnectionRefused);
+    }
+}
@@ -1,46 +1,193 @@
-use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::{
+    ffi::OsStr,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    os::unix::ffi::OsStrExt,
+    path::PathBuf,
+};
+
+use super::unix::UnixSocketName;
+#[cfg(target_os = "linux")]
+use super::vsock::VsockAddr;
 
 /// Creates a new SocketAddr from a libc::sockaddr for IPv4 or IPv6 addresses.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub fn socket_address_from(sockad_raw: *const libc::sockaddr) -> std::io::Result<std::net::SocketAddr> {
-    let sockad = unsafe{ *sockad_raw };
-    match sockad.sa_family as i32 {
+    socket_address_with_family(sockad_raw, libc::AF_UNSPEC)
+}
+
+/// Creates a new SocketAddr from a libc::sockaddr, using `family` as fallback if the sockaddr
+/// itself carries no address family.
+/// The BSDs return netmasks from getifaddrs that are truncated to their significant bytes (sa_len)
+/// and often have no address family set, the family of the interface address has to be used then.
+pub(crate) fn socket_address_with_family(sockad_raw: *const libc::sockaddr, family: libc::c_int)
+    -> std::io::Result<std::net::SocketAddr> {
+    let storage = copy_sockaddr(sockad_raw);
+    let family = match storage.ss_family as i32 {
+        libc::AF_UNSPEC => family,
+        f => f,
+    };
+    let storage_ptr = std::ptr::addr_of!(storage);
+    match family {
         libc::AF_INET   => {
-            let addr4 = unsafe{ *(sockad_raw as *const libc::sockaddr_in) };
+            let addr4 = unsafe{ *(storage_ptr as *const libc::sockaddr_in) };
             Ok( SocketAddr::V4( std::net::SocketAddrV4::new(
                 Ipv4Addr::from(u32::from_be(addr4.sin_addr.s_addr)), u16::from_be(addr4.sin_port)
             ) ) )
         },
         libc::AF_INET6  => {
-            let addr6 = unsafe{ *(sockad_raw as *const libc::sockaddr_in6) };
+            let addr6 = unsafe{ *(storage_ptr as *const libc::sockaddr_in6) };
             Ok( SocketAddr::V6( std::net::SocketAddrV6::new(
-                Ipv6Addr::from(addr6.sin6_addr.s6_addr), u16::from_be(addr6.sin6_port as u16),
+                Ipv6Addr::from(addr6.sin6_addr.s6_addr), u16::from_be(addr6.sin6_port),
                 u32::from_be(addr6.sin6_flowinfo), u32::from_be(addr6.sin6_scope_id)
             ) ) )
         },
-        _ => { Err(std::io::Error::new(std::io::ErrorKind::Other, "not an IP or IP6 address")) },
+        _ => { Err(std::io::Error::other("not an IP or IP6 address")) },
+    }
+}
+
+/// Copies the raw sockaddr into a zero initialized sockaddr_storage. Linux has no sa_len field,
+/// so the number of bytes to copy is derived from the address family.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn copy_sockaddr(sockad_raw: *const libc::sockaddr) -> libc::sockaddr_storage {
+    let len = match unsafe{ (*sockad_raw).sa_family } as i32 {
+        libc::AF_INET => std::mem::size_of::<libc::sockaddr_in>(),
+        libc::AF_INET6 => std::mem::size_of::<libc::sockaddr_in6>(),
+        _ => std::mem::size_of::<libc::sockaddr>(),
+    };
+    copy_sockaddr_bytes(sockad_raw, len)
+}
+
+/// Copies the raw sockaddr into a zero initialized sockaddr_storage, honoring its sa_len field.
+#[cfg(any(target_vendor = "apple", target_os = "freebsd", target_os = "dragonfly",
+          target_os = "openbsd", target_os = "netbsd"))]
+fn copy_sockaddr(sockad_raw: *const libc::sockaddr) -> libc::sockaddr_storage {
+    let len = unsafe{ (*sockad_raw).sa_len } as usize;
+    copy_sockaddr_bytes(sockad_raw, len)
+}
+
+fn copy_sockaddr_bytes(sockad_raw: *const libc::sockaddr, len: usize) -> libc::sockaddr_storage {
+    let mut storage: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let len = std::cmp::min(len, std::mem::size_of::<libc::sockaddr_storage>());
+    unsafe {
+        std::ptr::copy_nonoverlapping(sockad_raw as *const u8,
+                                      std::ptr::addr_of_mut!(storage) as *mut u8, len);
+    }
+    storage
+}
+
+/// Creates a sockaddr_un for the unix socket name and returns it with its length.
+/// Paths must be shorter than sun_path, abstract names are only supported on linux and android.
+pub(crate) fn unix_socket_address(name: &UnixSocketName) -> std::io::Result<(libc::sockaddr_un, libc::socklen_t)> {
+    let mut addr: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+    addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+    // abstract names start with a NUL byte and are not NUL terminated, paths are
+    let (bytes, start, terminator) = match name {
+        UnixSocketName::Path(path) => (path.as_os_str().as_bytes(), 0, 1),
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        UnixSocketName::Abstract(name) => (&name[..], 1, 0),
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        UnixSocketName::Abstract(_) => return Err(std::io::Error::new(std::io::ErrorKind::Unsupported,
+            "abstract unix socket names are not supported on this platform")),
+        UnixSocketName::Unnamed => (&[][..], 0, 0),
+    };
+    if start + bytes.len() + terminator > addr.sun_path.len() {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "unix socket name too long"));
+    }
+    for (dst, src) in addr.sun_path[start..].iter_mut().zip(bytes) {
+        *dst = *src as libc::c_char;
+    }
+    let len = sun_path_offset() + start + bytes.len() + terminator;
+    #[cfg(any(target_vendor = "apple", target_os = "freebsd", target_os = "dragonfly",
+              target_os = "openbsd", target_os = "netbsd"))]
+    { addr.sun_len = len as u8; }
+    Ok((addr, len as libc::socklen_t))
+}
+
+/// Parses a sockaddr_un of the given length as returned by getsockname/getpeername/recvfrom.
+pub(crate) fn unix_socket_name_from(addr: &libc::sockaddr_un, len: libc::socklen_t) -> UnixSocketName {
+    let path_len = std::cmp::min((len as usize).saturating_sub(sun_path_offset()), addr.sun_path.len());
+    let path: Vec<u8> = addr.sun_path[..path_len].iter().map(|c| *c as u8).collect();
+    match path.first() {
+        None => UnixSocketName::Unnamed,
+        Some(0) if path_len > 1 && cfg!(any(target_os = "linux", target_os = "android")) =>
+            UnixSocketName::Abstract(path[1..].to_vec()),
+        Some(0) => UnixSocketName::Unnamed,
+        Some(_) => {
+            let end = path.iter().position(|c| *c == 0).unwrap_or(path.len());
+            UnixSocketName::Path(PathBuf::from(OsStr::from_bytes(&path[..end])))
+        },
     }
 }
 
+/// Creates a socket2::SockAddr from a raw socket address struct (sockaddr_un, sockaddr_vm, ...)
+/// of the given length.
+pub(crate) fn sock_addr_from_raw<T>(addr: &T, len: libc::socklen_t) -> socket2::SockAddr {
+    let mut storage: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let size = std::cmp::min(std::mem::size_of::<T>(), std::mem::size_of::<libc::sockaddr_storage>());
+    unsafe {
+        std::ptr::copy_nonoverlapping(addr as *const T as *const u8,
+                                      std::ptr::addr_of_mut!(storage) as *mut u8, size);
+        socket2::SockAddr::new(storage, len)
+    }
+}
+
+/// Creates a sockaddr_vm for the vsock address.
+#[cfg(target_os = "linux")]
+pub(crate) fn vsock_socket_address(address: &VsockAddr) -> libc::sockaddr_vm {
+    let mut addr: libc::sockaddr_vm = unsafe { std::mem::zeroed() };
+    addr.svm_family = libc::AF_VSOCK as libc::sa_family_t;
+    addr.svm_cid = address.cid;
+    addr.svm_port = address.port;
+    addr
+}
+
+/// Converts a socket address of family AF_VSOCK, returns None for other families.
+#[cfg(target_os = "linux")]
+pub(crate) fn vsock_addr_from(address: &socket2::SockAddr) -> Option<VsockAddr> {
+    if address.family() != libc::AF_VSOCK as libc::sa_family_t
+        || (address.len() as usize) < std::mem::size_of::<libc::sockaddr_vm>() {
+        return None;
+    }
+    let addr = unsafe { *(address.as_ptr() as *const libc::sockaddr_vm) };
+    Some(VsockAddr { cid: addr.svm_cid, port: addr.svm_port })
+}
+
+/// Returns the offset of sun_path within sockaddr_un.
+fn sun_path_offset() -> usize {
+    let addr: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+    std::ptr::addr_of!(addr.sun_path) as usize - std::ptr::addr_of!(addr) as usize
+}
+
 #[cfg(test)]
 mod test {
 
     use super::*;
     use std::net::{SocketAddrV4, SocketAddrV6};
 
+    /// Sets the sa_len field of the first (and only) sockaddr header in `addr`.
+    #[cfg(any(target_vendor = "apple", target_os = "freebsd", target_os = "dragonfly",
+              target_os = "openbsd", target_os = "netbsd"))]
+    fn set_sa_len<T>(addr: &mut T) {
+        let sa = addr as *mut T as *mut libc::sockaddr;
+        unsafe { (*sa).sa_len = std::mem::size_of::<T>() as u8 };
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn set_sa_len<T>(_addr: &mut T) {}
+
     #[test]
     fn test_ipv4() {
         let data = [
-            (4711 as u16, 0x11223344 as u32),
-            ( 501 as u16, 0x01000080 as u32)
+            (4711_u16, 0x11223344_u32),
+            ( 501_u16, 0x01000080_u32)
         ];
 
         for d in data.iter() {
-            let ad = libc::sockaddr_in {
-                sin_family: libc::AF_INET as u16,
-                sin_port: d.0.to_be(),
-                sin_addr: libc::in_addr { s_addr: d.1.to_be() },
-                sin_zero: [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
-            };
+            let mut ad: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+            ad.sin_family = libc::AF_INET as libc::sa_family_t;
+            set_sa_len(&mut ad);
+            ad.sin_port = d.0.to_be();
+            ad.sin_addr = libc::in_addr { s_addr: d.1.to_be() };
             let address_result = socket_address_from(std::ptr::addr_of!(ad) as *const libc::sockaddr);
             assert!(address_result.is_ok());
             if let Ok(address) = address_result {
@@ -52,17 +199,17 @@ mod test {
     #[test]
     fn test_ipv6() {
         let data = [
-            (5433 as u16, 0 as u32, 12 as u32, [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x00]),
-            (80 as u16, 1230 as u32, 98400 as u32, [0x21, 0x22, 0x23, 0x34, 0x35, 0x36, 0x47, 0x48, 0x49, 0x5a, 0x5b, 0x5c, 0x6d, 0x6e, 0x7f, 0x80]),
+            (5433_u16, 0_u32, 12_u32, [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x00]),
+            (80_u16, 1230_u32, 98400_u32, [0x21, 0x22, 0x23, 0x34, 0x35, 0x36, 0x47, 0x48, 0x49, 0x5a, 0x5b, 0x5c, 0x6d, 0x6e, 0x7f, 0x80]),
         ];
         for d in data.iter() {
-            let ad = libc::sockaddr_in6 {
-                sin6_family: libc::AF_INET6 as u16,
-                sin6_port: d.0.to_be(),
-                sin6_flowinfo: d.1.to_be(),
-                sin6_addr: libc::in6_addr{ s6_addr: d.3 },
-                sin6_scope_id: d.2.to_be(),
-            };
+            let mut ad: libc::sockaddr_in6 = unsafe { std::mem::zeroed() };
+            ad.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            set_sa_len(&mut ad);
+            ad.sin6_port = d.0.to_be();
+            ad.sin6_flowinfo = d.1.to_be();
+            ad.sin6_addr = libc::in6_addr{ s6_addr: d.3 };
+            ad.sin6_scope_id = d.2.to_be();
             let address_result = socket_address_from(std::ptr::addr_of!(ad) as *const libc::sockaddr);
             assert!(address_result.is_ok());
             if let Ok(address) = address_result {
@@ -70,4 +217,50 @@ mod test {
             };
         }
     }
+
+    #[test]
+    fn test_netmask_without_family() {
+        let mut ad: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+        set_sa_len(&mut ad);
+        ad.sin_addr = libc::in_addr { s_addr: 0xffffff00_u32.to_be() };
+        let sa = std::ptr::addr_of!(ad) as *const libc::sockaddr;
+        assert!(socket_address_from(sa).is_err());
+        let address = socket_address_with_family(sa, libc::AF_INET).unwrap();
+        assert_eq!(address, SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(255, 255, 255, 0), 0)));
+    }
+
+    #[test]
+    fn test_unix_socket_address() {
+        let name = UnixSocketName::Path(PathBuf::from("/run/test.sock"));
+        let (addr, len) = unix_socket_address(&name).unwrap();
+        assert_eq!(len as usize, sun_path_offset() + 15);
+        assert_eq!(unix_socket_name_from(&addr, len), name);
+
+        let (addr, len) = unix_socket_address(&UnixSocketName::Unnamed).unwrap();
+        assert_eq!(unix_socket_name_from(&addr, len), UnixSocketName::Unnamed);
+
+        let long = UnixSocketName::Path(PathBuf::from("/".repeat(200)));
+        assert!(unix_socket_address(&long).is_err());
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn test_abstract_unix_socket_address() {
+        let name = UnixSocketName::Abstract(b"net-utils\0test".to_vec());
+        let (addr, len) = unix_socket_address(&name).unwrap();
+        assert_eq!(len as usize, sun_path_offset() + 15);
+        assert_eq!(addr.sun_path[0], 0);
+        assert_eq!(unix_socket_name_from(&addr, len), name);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_vsock_address() {
+        let address = VsockAddr::new(3, 5000);
+        let raw = vsock_socket_address(&address);
+        let sock_addr = sock_addr_from_raw(&raw, std::mem::size_of_val(&raw) as libc::socklen_t);
+        assert_eq!(vsock_addr_from(&sock_addr), Some(address));
+        let ip: socket2::SockAddr = "127.0.0.1:80".parse::<SocketAddr>().unwrap().into();
+        assert_eq!(vsock_addr_from(&ip), None);
+    }
 }
@@ -0,0 +1,285 @@
+//! Privilege separation for socket creation: a `SocketFactory` opens all sockets which need
+//! privileges (raw sockets, device binding, buffer sizes above the system limits, ports below
+//! 1024) while the process still has its capabilities, is sealed once they are dropped and then
+//! hands out the pre-opened sockets on demand.
+//!
+//! ```no_run
+//! use net_utils::socket_factory::{SocketFactory, UdpSpec};
+//! let mut factory = SocketFactory::new();
+//! factory.register("ntp", UdpSpec::new("0.0.0.0:123".parse().unwrap()).bind_device("eth0"), 1).unwrap();
+//! factory.seal();
+//! // drop capabilities, e.g. setuid/setgid
+//! let socket = factory.take_udp("ntp").unwrap();
+//! ```
+
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::TryFrom,
+    io::{Error, ErrorKind, Result},
+    net::{SocketAddr, TcpListener, UdpSocket},
+    os::unix::io::AsRawFd,
+};
+
+use socket2::SockAddr;
+
+use super::{
+    MulticastSocketBuilder,
+    capabilities::check_capabilities,
+    device::bind_to_device,
+    raw::{RawSocket, RawSocketBuilder},
+    sockopt::set_int_option,
+    sys_api,
+    tcp::TcpListenerBuilder,
+};
+
+/// A UDP socket to create, optionally bound to a device and with forced buffer sizes.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct UdpSpec {
+    address: SocketAddr,
+    device: Option<String>,
+    recv_buffer_size: Option<usize>,
+    send_buffer_size: Option<usize>,
+    force_buffers: bool,
+}
+
+impl UdpSpec {
+
+    /// Creates the spec of a UDP socket bound to the address.
+    pub fn new(address: SocketAddr) -> UdpSpec {
+        UdpSpec { address, device: None, recv_buffer_size: None, send_buffer_size: None, force_buffers: false }
+    }
+
+    /// Returns the local address of the socket.
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Binds the socket to the network device with the given name (SO_BINDTODEVICE), which
+    /// requires CAP_NET_RAW on kernels before 5.7.
+    pub fn bind_device(mut self, device: &str) -> Self {
+        self.device = Some(device.to_string());
+        self
+    }
+
+    /// Returns the device the socket is bound to.
+    pub fn device(&self) -> Option<&str> {
+        self.device.as_deref()
+    }
+
+    /// Sets the receive buffer size (SO_RCVBUF).
+    pub fn recv_buffer_size(mut self, size: usize) -> Self {
+        self.recv_buffer_size = Some(size);
+        self
+    }
+
+    /// Sets the send buffer size (SO_SNDBUF).
+    pub fn send_buffer_size(mut self, size: usize) -> Self {
+        self.send_buffer_size = Some(size);
+        self
+    }
+
+    /// Sets the buffer sizes with SO_RCVBUFFORCE / SO_SNDBUFFORCE, which exceed
+    /// net.core.rmem_max / wmem_max and require CAP_NET_ADMIN.
+    pub fn force_buffers(mut self, force: bool) -> Self {
+        self.force_buffers = force;
+        self
+    }
+
+    /// Returns whether the buffer sizes are forced and at least one is set.
+    pub fn forces_buffers(&self) -> bool {
+        self.force_buffers && (self.recv_buffer_size.is_some() || self.send_buffer_size.is_some())
+    }
+
+    /// Creates the bound socket.
+    pub fn build(&self) -> Result<UdpSocket> {
+        trace_span!("udp_socket", address = %self.address);
+        let domain = match self.address {
+            SocketAddr::V4(_) => libc::AF_INET,
+            SocketAddr::V6(_) => libc::AF_INET6,
+        };
+        let socket = sys_api::socket(domain, libc::SOCK_DGRAM, libc::IPPROTO_UDP)?;
+        if let Some(device) = &self.device {
+            bind_to_device(&socket, device, &self.address)?;
+        }
+        let buffers = [
+            (self.recv_buffer_size, libc::SO_RCVBUF, libc::SO_RCVBUFFORCE),
+            (self.send_buffer_size, libc::SO_SNDBUF, libc::SO_SNDBUFFORCE),
+        ];
+        for (size, name, force_name) in buffers {
+            if let Some(size) = size {
+                let size = libc::c_int::try_from(size).map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+                let name = if self.force_buffers { force_name } else { name };
+                set_int_option(socket.as_raw_fd(), libc::SOL_SOCKET, name, size)?;
+            }
+        }
+        sys_api::bind(&socket, &SockAddr::from(self.address))?;
+        trace_event!(crate::trace::TraceEvent::SocketCreated {
+            kind: "udp", address: socket.local_addr().ok().and_then(|address| address.as_socket()),
+        });
+        Ok(socket.into())
+    }
+}
+
+/// A socket the factory creates.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SocketSpec {
+    /// a UDP socket, taken with `take_udp`
+    Udp(UdpSpec),
+    /// a multicast socket, taken with `take_udp`
+    Multicast(MulticastSocketBuilder),
+    /// a listening TCP socket, taken with `take_tcp_listener`
+    TcpListener(TcpListenerBuilder),
+    /// a raw socket, taken with `take_raw`
+    Raw(RawSocketBuilder),
+}
+
+impl From<UdpSpec> for SocketSpec {
+    fn from(spec: UdpSpec) -> Self {
+        SocketSpec::Udp(spec)
+    }
+}
+
+impl From<MulticastSocketBuilder> for SocketSpec {
+    fn from(builder: MulticastSocketBuilder) -> Self {
+        SocketSpec::Multicast(builder)
+    }
+}
+
+impl From<TcpListenerBuilder> for SocketSpec {
+    fn from(builder: TcpListenerBuilder) -> Self {
+        SocketSpec::TcpListener(builder)
+    }
+}
+
+impl From<RawSocketBuilder> for SocketSpec {
+    fn from(builder: RawSocketBuilder) -> Self {
+        SocketSpec::Raw(builder)
+    }
+}
+
+/// A socket created by the factory.
+#[derive(Debug)]
+enum PooledSocket {
+    Udp(UdpSocket),
+    TcpListener(TcpListener),
+    Raw(RawSocket),
+}
+
+impl SocketSpec {
+
+    fn build(&self) -> Result<PooledSocket> {
+        Ok(match self {
+            SocketSpec::Udp(spec) => PooledSocket::Udp(spec.build()?),
+            SocketSpec::Multicast(builder) => PooledSocket::Udp(builder.build_std()?),
+            SocketSpec::TcpListener(builder) => PooledSocket::TcpListener(builder.build_std()?),
+            SocketSpec::Raw(builder) => PooledSocket::Raw(builder.build()?),
+        })
+    }
+}
+
+/// Pools of pre-opened sockets by name. Before `seal` sockets are created by `register` and,
+/// if the pool of a name is exhausted, by `take_*` on demand. After `seal` creating sockets
+/// fails with PermissionDenied, so that a missing socket is reported as a configuration error
+/// instead of a failing system call without privileges.
+#[derive(Debug, Default)]
+pub struct SocketFactory {
+    pools: HashMap<String, (SocketSpec, VecDeque<PooledSocket>)>,
+    sealed: bool,
+}
+
+impl SocketFactory {
+
+    /// Creates an unsealed factory without sockets.
+    pub fn new() -> SocketFactory {
+        SocketFactory::default()
+    }
+
+    /// Creates `count` sockets of the spec for the name, further sockets are added to the pool
+    /// of a name registered before. Fails with PermissionDenied after `seal` or if the process
+    /// lacks a capability the spec requires (see `capabilities::check_capabilities`), with
+    /// InvalidInput if the name is registered with another spec and with the error of the first
+    /// socket which cannot be created, whose pool stays unchanged.
+    pub fn register<T: Into<SocketSpec>>(&mut self, name: &str, spec: T, count: usize) -> Result<()> {
+        if self.sealed {
+            return Err(Error::new(ErrorKind::PermissionDenied, format!("cannot create socket {} after sealing the factory", name)));
+        }
+        let spec = spec.into();
+        if self.pools.get(name).is_some_and(|(registered, _)| *registered != spec) {
+            return Err(Error::new(ErrorKind::InvalidInput, format!("socket {} is registered with another spec", name)));
+        }
+        match check_capabilities(&spec) {
+            Err(e) if e.kind() == ErrorKind::PermissionDenied => return Err(e),
+            _ => (),
+        }
+        let sockets = (0..count).map(|_| spec.build()).collect::<Result<Vec<_>>>()?;
+        self.pools.entry(name.to_string()).or_insert_with(|| (spec, VecDeque::new())).1.extend(sockets);
+        Ok(())
+    }
+
+    /// Seals the factory before the process drops its capabilities, sockets are then only taken
+    /// from the pools.
+    pub fn seal(&mut self) {
+        self.sealed = true;
+    }
+
+    /// Returns whether the factory is sealed.
+    pub fn is_sealed(&self) -> bool {
+        self.sealed
+    }
+
+    /// Returns the number of pre-opened sockets of the name.
+    pub fn available(&self, name: &str) -> usize {
+        self.pools.get(name).map_or(0, |(_, sockets)| sockets.len())
+    }
+
+    /// Returns the spec the name is registered with.
+    pub fn spec(&self, name: &str) -> Option<&SocketSpec> {
+        self.pools.get(name).map(|(spec, _)| spec)
+    }
+
+    /// Takes a UDP or multicast socket of the name.
+    pub fn take_udp(&mut self, name: &str) -> Result<UdpSocket> {
+        match self.take(name)? {
+            PooledSocket::Udp(socket) => Ok(socket),
+            socket => Err(self.put_back(name, socket, "UDP")),
+        }
+    }
+
+    /// Takes a listening TCP socket of the name.
+    pub fn take_tcp_listener(&mut self, name: &str) -> Result<TcpListener> {
+        match self.take(name)? {
+            PooledSocket::TcpListener(listener) => Ok(listener),
+            socket => Err(self.put_back(name, socket, "TCP listener")),
+        }
+    }
+
+    /// Takes a raw socket of the name.
+    pub fn take_raw(&mut self, name: &str) -> Result<RawSocket> {
+        match self.take(name)? {
+            PooledSocket::Raw(socket) => Ok(socket),
+            socket => Err(self.put_back(name, socket, "raw")),
+        }
+    }
+
+    /// Takes a pre-opened socket or, before sealing, creates one. Fails with NotFound for names
+    /// which are not registered.
+    fn take(&mut self, name: &str) -> Result<PooledSocket> {
+        let sealed = self.sealed;
+        let (spec, sockets) = self.pools.get_mut(name)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("no socket {} registered", name)))?;
+        match sockets.pop_front() {
+            Some(socket) => Ok(socket),
+            None if sealed => Err(Error::new(ErrorKind::PermissionDenied,
+                                             format!("sockets {} exhausted after sealing the factory", name))),
+            None => spec.build(),
+        }
+    }
+
+    /// Returns a socket taken with the wrong type to its pool and the error for taking it.
+    fn put_back(&mut self, name: &str, socket: PooledSocket, expected: &str) -> Error {
+        if let Some((_, sockets)) = self.pools.get_mut(name) {
+            sockets.push_front(socket);
+        }
+        Error::new(ErrorKind::InvalidInput, format!("socket {} is no {} socket", name, expected))
+    }
+}
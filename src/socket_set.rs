@@ -0,0 +1,177 @@
+//! Declarative management of a set of multicast sockets for daemons with reloadable
+//! configurations: the configuration names the sockets and their `MulticastSocketBuilder`s,
+//! `SocketSet::apply` compares it with the open sockets and opens, closes, reopens or moves
+//! sockets to other interfaces as required, either completely or not at all.
+
+use std::{
+    collections::HashSet,
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, UdpSocket},
+};
+
+use super::MulticastSocketBuilder;
+
+/// A change of the sockets applied by `SocketSet::apply`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SocketSetEvent {
+    /// a socket was opened for a new configuration entry
+    Opened(String),
+
+    /// the socket of a removed configuration entry was closed
+    Closed(String),
+
+    /// the socket was replaced by a new one because its options changed
+    Reopened(String),
+
+    /// the socket joined the group on another interface (from, to) and left it on the previous one
+    Rejoined { name: String, from: IpAddr, to: IpAddr },
+}
+
+#[derive(Debug)]
+struct Entry {
+    name: String,
+    builder: MulticastSocketBuilder,
+    socket: UdpSocket,
+    #[cfg(feature = "metrics")]
+    _open: super::metrics::OpenSocket,
+}
+
+/// Set of named std::net::UdpSocket multicast sockets following a declarative configuration.
+/// ```no_run
+/// use net_utils::{MulticastPreset, MulticastSocketBuilder, socket_set::SocketSet};
+/// let mut sockets = SocketSet::new();
+/// let interface = "192.168.1.2".parse().unwrap();
+/// let config = vec![("ssdp".to_string(), MulticastSocketBuilder::preset(MulticastPreset::Ssdp, interface))];
+/// for event in sockets.apply(&config).unwrap() {
+///     println!("{:?}", event);
+/// }
+/// let socket = sockets.get("ssdp").unwrap();
+/// ```
+#[derive(Debug, Default)]
+pub struct SocketSet {
+    entries: Vec<Entry>,
+}
+
+/// Change of an entry planned by `apply`.
+enum Change {
+    Keep,
+    Open(UdpSocket),
+    Rejoin,
+}
+
+impl SocketSet {
+
+    /// Creates an empty set.
+    pub fn new() -> SocketSet {
+        SocketSet::default()
+    }
+
+    /// Applies the configuration of (name, builder) entries, the sockets are kept in its order.
+    /// Unchanged entries keep their socket, entries differing only in the interface move the
+    /// group membership of their socket, otherwise changed entries get a new socket. The new
+    /// sockets are created and the groups joined on the new interfaces before any socket is
+    /// closed or any membership on a previous interface is left. If one of these steps fails the
+    /// moved sockets leave the groups on the new interfaces and select their previous interfaces
+    /// again, the set remains unchanged and the error is returned. Fails with InvalidInput for
+    /// duplicate names.
+    pub fn apply(&mut self, config: &[(String, MulticastSocketBuilder)]) -> Result<Vec<SocketSetEvent>> {
+        trace_span!("socket_set_apply", entries = config.len());
+        let mut names = HashSet::new();
+        if let Some((name, _)) = config.iter().find(|(name, _)| !names.insert(name)) {
+            return Err(Error::new(ErrorKind::InvalidInput, format!("duplicate socket name {}", name)));
+        }
+        let mut changes = Vec::with_capacity(config.len());
+        for (name, builder) in config {
+            let change = match self.position(name) {
+                Some(i) if self.entries[i].builder == *builder => Change::Keep,
+                Some(i) if self.entries[i].builder.is_rejoin(builder) => Change::Rejoin,
+                _ => Change::Open(builder.build_std()?),
+            };
+            changes.push(change);
+        }
+        let mut joined: Vec<(&MulticastSocketBuilder, usize)> = Vec::new();
+        for ((name, builder), change) in config.iter().zip(&changes) {
+            if let Change::Rejoin = change {
+                let i = self.position(name).unwrap_or_default();
+                if let Err(e) = builder.join_socket(&self.entries[i].socket) {
+                    // the memberships on the previous interfaces are still in place
+                    for (builder, i) in joined {
+                        let entry = &self.entries[i];
+                        let _ = builder.leave_socket(&entry.socket);
+                        let _ = entry.builder.select_interface(&entry.socket);
+                    }
+                    return Err(e);
+                }
+                joined.push((builder, i));
+            }
+        }
+
+        let mut events = Vec::new();
+        let mut previous = std::mem::take(&mut self.entries);
+        for ((name, builder), change) in config.iter().zip(changes) {
+            let existing = previous.iter().position(|entry| entry.name == *name).map(|i| previous.remove(i));
+            let socket = match (change, existing) {
+                (Change::Open(socket), existing) => {
+                    events.push(match existing {
+                        Some(_) => SocketSetEvent::Reopened(name.clone()),
+                        None => SocketSetEvent::Opened(name.clone()),
+                    });
+                    socket
+                },
+                (Change::Rejoin, Some(entry)) => {
+                    // the membership on the previous interface is dropped with the socket anyway
+                    let _ = entry.builder.leave_socket(&entry.socket);
+                    trace_event!(crate::trace::TraceEvent::SocketMoved {
+                        group: builder.group().ip(), from: entry.builder.interface(), to: builder.interface(),
+                    });
+                    events.push(SocketSetEvent::Rejoined {
+                        name: name.clone(), from: entry.builder.interface(), to: builder.interface(),
+                    });
+                    entry.socket
+                },
+                (_, Some(entry)) => entry.socket,
+                (_, None) => unreachable!("kept entry {} without socket", name),
+            };
+            self.entries.push(Entry {
+                name: name.clone(), builder: builder.clone(), socket,
+                #[cfg(feature = "metrics")] _open: super::metrics::OpenSocket::new(),
+            });
+        }
+        events.extend(previous.into_iter().map(|entry| SocketSetEvent::Closed(entry.name)));
+        Ok(events)
+    }
+
+    /// Returns the socket of the named entry.
+    pub fn get(&self, name: &str) -> Option<&UdpSocket> {
+        self.position(name).map(|i| &self.entries[i].socket)
+    }
+
+    /// Returns the builder the socket of the named entry was created with or moved to.
+    pub fn builder(&self, name: &str) -> Option<&MulticastSocketBuilder> {
+        self.position(name).map(|i| &self.entries[i].builder)
+    }
+
+    /// Returns the names and sockets in the order of the configuration.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &UdpSocket)> {
+        self.entries.iter().map(|entry| (entry.name.as_str(), &entry.socket))
+    }
+
+    /// Returns the number of sockets.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the set has no sockets.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Closes all sockets, returns the Closed events.
+    pub fn clear(&mut self) -> Vec<SocketSetEvent> {
+        self.entries.drain(..).map(|entry| SocketSetEvent::Closed(entry.name)).collect()
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.entries.iter().position(|entry| entry.name == name)
+    }
+}
@@ -0,0 +1,32 @@
+use std::{
+    io::Result,
+    os::unix::io::RawFd,
+};
+
+use super::sys_api;
+
+/// Sets a socket option whose value is a plain C struct or integer.
+pub(crate) fn set_option<T>(fd: RawFd, level: libc::c_int, name: libc::c_int, value: &T) -> Result<()> {
+    let value = unsafe { std::slice::from_raw_parts(value as *const T as *const u8, std::mem::size_of::<T>()) };
+    set_bytes_option(fd, level, name, value)
+}
+
+/// Sets a socket option whose value is a byte string, e.g. a device name.
+pub(crate) fn set_bytes_option(fd: RawFd, level: libc::c_int, name: libc::c_int, value: &[u8]) -> Result<()> {
+    sys_api::call(|api| api.setsockopt(fd, level, name, value))
+}
+
+/// Sets a socket option with an int value.
+pub(crate) fn set_int_option(fd: RawFd, level: libc::c_int, name: libc::c_int, value: libc::c_int) -> Result<()> {
+    let result = set_option(fd, level, name, &value);
+    trace_event!(crate::trace::TraceEvent::OptionSet { level, name, value, error: result.as_ref().err() });
+    result
+}
+
+/// Retrieves a socket option whose value is a plain C struct or integer.
+pub(crate) fn get_option<T>(fd: RawFd, level: libc::c_int, name: libc::c_int) -> Result<T> {
+    let mut value: T = unsafe { std::mem::zeroed() };
+    let bytes = unsafe { std::slice::from_raw_parts_mut(&mut value as *mut T as *mut u8, std::mem::size_of::<T>()) };
+    sys_api::call(|api| api.getsockopt(fd, level, name, bytes))?;
+    Ok(value)
+}
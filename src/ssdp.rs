@@ -0,0 +1,114 @@
+//! SSDP (UPnP device architecture) discovery: M-SEARCH requests and their unicast responses.
+
+use std::{
+    io::{ErrorKind, Result},
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    time::{Duration, Instant},
+};
+
+/// IPv4 multicast group of SSDP.
+pub const SSDP_MULTICAST_V4: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 250);
+
+/// Well known SSDP port.
+pub const SSDP_PORT: u16 = 1900;
+
+/// Search target matching all devices and services.
+pub const SEARCH_ALL: &str = "ssdp:all";
+
+/// A response to an M-SEARCH request.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SsdpResponse {
+    /// URL of the device description (LOCATION header)
+    pub location: String,
+
+    /// search target the response matches (ST header)
+    pub search_target: String,
+
+    /// unique service name of the device or service (USN header)
+    pub usn: String,
+
+    /// operating system and UPnP version of the device (SERVER header)
+    pub server: Option<String>,
+
+    /// address the response was sent from
+    pub from: SocketAddr,
+}
+
+/// Multicasts an M-SEARCH request for the search target (e.g. SEARCH_ALL or a device or service
+/// type URN) from `interface` (UNSPECIFIED for the default multicast interface) and collects the
+/// responses until the timeout elapses. Devices are asked to answer within the timeout (MX).
+pub fn search(search_target: &str, interface: &Ipv4Addr, timeout: Duration) -> Result<Vec<SsdpResponse>> {
+    let socket = super::sys_socket::udp_bind(SocketAddrV4::new(*interface, 0))?;
+    if !interface.is_unspecified() {
+        socket2::SockRef::from(&socket).set_multicast_if_v4(interface)?;
+    }
+    let request = format!("M-SEARCH * HTTP/1.1\r\nHOST: {}:{}\r\nMAN: \"ssdp:discover\"\r\nMX: {}\r\nST: {}\r\n\r\n",
+                          SSDP_MULTICAST_V4, SSDP_PORT, timeout.as_secs().clamp(1, 5), search_target);
+    socket.send_to(request.as_bytes(), SocketAddrV4::new(SSDP_MULTICAST_V4, SSDP_PORT))?;
+
+    let deadline = Instant::now() + timeout;
+    let mut responses: Vec<SsdpResponse> = Vec::new();
+    let mut buffer = [0_u8; 2048];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(responses);
+        }
+        socket.set_read_timeout(Some(remaining))?;
+        match socket.recv_from(&mut buffer) {
+            Ok((len, from)) => {
+                if let Some(response) = parse_response(&buffer[..len], from) {
+                    if !responses.contains(&response) {
+                        responses.push(response);
+                    }
+                }
+            },
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => return Ok(responses),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Parses an M-SEARCH response, None if the datagram is not a valid response.
+fn parse_response(datagram: &[u8], from: SocketAddr) -> Option<SsdpResponse> {
+    let text = std::str::from_utf8(datagram).ok()?;
+    let mut lines = text.split("\r\n");
+    if !lines.next()?.starts_with("HTTP/1.1 200") {
+        return None;
+    }
+    let (mut location, mut search_target, mut usn, mut server) = (None, None, None, None);
+    for (name, value) in lines.filter_map(|l| l.split_once(':')) {
+        let value = Some(value.trim().to_string());
+        match name.trim().to_ascii_uppercase().as_str() {
+            "LOCATION" => location = value,
+            "ST" => search_target = value,
+            "USN" => usn = value,
+            "SERVER" => server = value,
+            _ => {},
+        }
+    }
+    Some(SsdpResponse { location: location?, search_target: search_target?, usn: usn.unwrap_or_default(), server, from })
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_parse_response() {
+        let from: SocketAddr = "192.168.1.1:1900".parse().unwrap();
+        let datagram = b"HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=120\r\nST: upnp:rootdevice\r\n\
+            USN: uuid:1234::upnp:rootdevice\r\nEXT:\r\nServer: Linux UPnP/1.1 MiniUPnPd/2.3\r\n\
+            Location: http://192.168.1.1:5000/rootDesc.xml\r\n\r\n";
+        assert_eq!(parse_response(datagram, from), Some(SsdpResponse {
+            location: "http://192.168.1.1:5000/rootDesc.xml".to_string(),
+            search_target: "upnp:rootdevice".to_string(),
+            usn: "uuid:1234::upnp:rootdevice".to_string(),
+            server: Some("Linux UPnP/1.1 MiniUPnPd/2.3".to_string()),
+            from,
+        }));
+        assert_eq!(parse_response(b"NOTIFY * HTTP/1.1\r\nLOCATION: x\r\nST: y\r\n\r\n", from), None);
+        assert_eq!(parse_response(b"HTTP/1.1 200 OK\r\nST: y\r\n\r\n", from), None);
+    }
+}
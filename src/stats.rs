@@ -0,0 +1,136 @@
+//! Traffic statistics of the socket wrappers of the crate (e.g. `udp::ConnectedUdpSocket`,
+//! `rate_limit::RateLimitedSender`): datagrams and bytes sent and received, datagrams dropped by
+//! the kernel and the time of the last activity.
+
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+/// Snapshot of the traffic statistics of a socket, see the `stats()` methods of the wrappers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SocketStats {
+    /// number of datagrams sent
+    pub datagrams_sent: u64,
+
+    /// number of payload bytes sent
+    pub bytes_sent: u64,
+
+    /// number of datagrams received
+    pub datagrams_received: u64,
+
+    /// number of payload bytes received
+    pub bytes_received: u64,
+
+    /// number of received datagrams the kernel dropped because the receive queue of the socket
+    /// was full (SO_RXQ_OVFL), None if the socket does not report it or no datagram was received
+    pub drops: Option<u64>,
+
+    /// time the last datagram was sent
+    pub last_sent: Option<Instant>,
+
+    /// time the last datagram was received
+    pub last_received: Option<Instant>,
+}
+
+/// Counters behind `SocketStats` which are updated via shared references. Optional values are
+/// stored incremented by one, 0 is None.
+#[derive(Debug)]
+pub(crate) struct StatsCounter {
+    created: Instant,
+    datagrams_sent: AtomicU64,
+    bytes_sent: AtomicU64,
+    datagrams_received: AtomicU64,
+    bytes_received: AtomicU64,
+    drops: AtomicU64,
+    last_sent: AtomicU64,
+    last_received: AtomicU64,
+    #[cfg(feature = "metrics")]
+    _open: super::metrics::OpenSocket,
+}
+
+impl StatsCounter {
+
+    pub(crate) fn new() -> StatsCounter {
+        StatsCounter {
+            created: Instant::now(),
+            datagrams_sent: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            datagrams_received: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            drops: AtomicU64::new(0),
+            last_sent: AtomicU64::new(0),
+            last_received: AtomicU64::new(0),
+            #[cfg(feature = "metrics")]
+            _open: super::metrics::OpenSocket::new(),
+        }
+    }
+
+    /// Counts a sent datagram of `len` bytes.
+    pub(crate) fn sent(&self, len: usize) {
+        self.datagrams_sent.fetch_add(1, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(len as u64, Ordering::Relaxed);
+        self.last_sent.store(self.since_created(), Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        super::metrics::count_sent(len);
+    }
+
+    /// Counts a received datagram of `len` bytes.
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub(crate) fn received(&self, len: usize) {
+        self.datagrams_received.fetch_add(1, Ordering::Relaxed);
+        self.bytes_received.fetch_add(len as u64, Ordering::Relaxed);
+        self.last_received.store(self.since_created(), Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        super::metrics::count_received(len);
+    }
+
+    /// Records the drop counter of the socket (SO_RXQ_OVFL) reported with a received datagram,
+    /// datagrams queued before the latest drops report lower values.
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub(crate) fn drops(&self, drops: u32) {
+        self.drops.fetch_max(drops as u64 + 1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> SocketStats {
+        let optional = |value: &AtomicU64| value.load(Ordering::Relaxed).checked_sub(1);
+        let instant = |value: &AtomicU64| optional(value).map(|nanos| self.created + Duration::from_nanos(nanos));
+        SocketStats {
+            datagrams_sent: self.datagrams_sent.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            datagrams_received: self.datagrams_received.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            drops: optional(&self.drops),
+            last_sent: instant(&self.last_sent),
+            last_received: instant(&self.last_received),
+        }
+    }
+
+    /// Returns the nanoseconds since creation incremented by one.
+    fn since_created(&self) -> u64 {
+        self.created.elapsed().as_nanos() as u64 + 1
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_stats_counter() {
+        let counter = StatsCounter::new();
+        assert_eq!(counter.snapshot(), SocketStats::default());
+        counter.sent(100);
+        counter.sent(20);
+        counter.received(7);
+        counter.drops(3);
+        counter.drops(1);
+        let stats = counter.snapshot();
+        assert_eq!((stats.datagrams_sent, stats.bytes_sent), (2, 120));
+        assert_eq!((stats.datagrams_received, stats.bytes_received), (1, 7));
+        assert_eq!(stats.drops, Some(3));
+        assert!(stats.last_sent.unwrap() <= stats.last_received.unwrap());
+        assert!(stats.last_received.unwrap() <= Instant::now());
+    }
+}
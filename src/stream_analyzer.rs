@@ -0,0 +1,347 @@
+//! Sequence number and timing analysis of received datagram streams per source, for validating
+//! multicast distribution networks: loss (with the longest burst), reordering, duplication and
+//! the jitter of the inter-arrival times. The sequence number of a datagram is taken by an
+//! extractor function, e.g. from an RTP header or an application header, and extended over
+//! wraps of its width. Reports of the period since the previous one are passed to a handler.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+/// Number of sequence numbers below the highest one for which duplicates are detected.
+const DUPLICATE_WINDOW: u64 = 1024;
+
+/// Statistics of the stream of a source, since the first datagram (`StreamAnalyzer::stats`) or
+/// of a report period (`StreamReport`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StreamStats {
+    pub source: SocketAddr,
+
+    /// number of received datagrams, duplicates included
+    pub received: u64,
+
+    /// number of received datagrams with the sequence number of an earlier one
+    pub duplicates: u64,
+
+    /// number of datagrams received after one with a higher sequence number
+    pub reordered: u64,
+
+    /// number of missing sequence numbers, late datagrams reduce it
+    pub lost: u64,
+
+    /// maximum number of consecutive missing sequence numbers
+    pub max_burst_loss: u64,
+
+    /// smoothed variation of the time between consecutive datagrams, RFC 3550 style with a gain
+    /// of 1/16
+    pub jitter: Duration,
+
+    /// highest sequence number, extended over wraps
+    pub highest: u64,
+
+    /// time the last datagram was received
+    pub last_received: Option<Instant>,
+}
+
+/// The statistics of all sources of a report period.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StreamReport {
+    pub start: Instant,
+    pub end: Instant,
+    /// sources sorted by address
+    pub sources: Vec<StreamStats>,
+}
+
+/// Analysis state of a source.
+#[derive(Debug)]
+struct SourceState {
+    first: u64,
+    unique: u64,
+    stats: StreamStats,
+    recent: BTreeSet<u64>,
+    last_interval: Option<Duration>,
+    jitter: f64,
+    /// statistics and number of unique datagrams at the start of the report period
+    period_start: (StreamStats, u64, u64),
+    period_max_burst_loss: u64,
+}
+
+impl SourceState {
+
+    fn new(source: SocketAddr, sequence: u64) -> SourceState {
+        let stats = StreamStats {
+            source, received: 0, duplicates: 0, reordered: 0, lost: 0, max_burst_loss: 0, jitter: Duration::ZERO,
+            highest: sequence, last_received: None,
+        };
+        SourceState {
+            first: sequence, unique: 0, stats, recent: BTreeSet::new(), last_interval: None, jitter: 0.0,
+            period_start: (stats, 0, sequence), period_max_burst_loss: 0,
+        }
+    }
+
+    /// Returns the sequence number extended relative to the highest one.
+    fn extend(&self, sequence: u64, mask: u64) -> Option<u64> {
+        let highest = self.stats.highest;
+        let delta = sequence.wrapping_sub(highest) & mask;
+        if delta <= mask / 2 {
+            Some(highest.wrapping_add(delta))
+        } else {
+            highest.checked_sub((mask - delta).wrapping_add(1))
+        }
+    }
+
+    fn push(&mut self, sequence: u64, at: Instant) {
+        self.stats.received += 1;
+        if let Some(last) = self.stats.last_received {
+            let interval = at.saturating_duration_since(last);
+            if let Some(previous) = self.last_interval {
+                let variation = interval.abs_diff(previous).as_secs_f64();
+                self.jitter += (variation - self.jitter) / 16.0;
+                self.stats.jitter = Duration::from_secs_f64(self.jitter);
+            }
+            self.last_interval = Some(interval);
+        }
+        self.stats.last_received = Some(at);
+
+        let highest = self.stats.highest;
+        if self.unique > 0 && sequence <= highest {
+            if self.recent.contains(&sequence) {
+                self.stats.duplicates += 1;
+                return;
+            }
+            self.stats.reordered += 1;
+            if highest - sequence >= DUPLICATE_WINDOW {
+                return;
+            }
+            self.first = self.first.min(sequence);
+        } else if self.unique > 0 {
+            let gap = sequence - highest - 1;
+            self.stats.max_burst_loss = self.stats.max_burst_loss.max(gap);
+            self.period_max_burst_loss = self.period_max_burst_loss.max(gap);
+            self.stats.highest = sequence;
+        }
+        self.unique += 1;
+        self.recent.insert(sequence);
+        let oldest = self.stats.highest.saturating_sub(DUPLICATE_WINDOW);
+        while self.recent.first().is_some_and(|first| *first < oldest) {
+            self.recent.pop_first();
+        }
+        self.stats.lost = (self.stats.highest - self.first + 1).saturating_sub(self.unique);
+    }
+
+    /// Returns the statistics of the report period and starts the next one.
+    fn period(&mut self) -> StreamStats {
+        let (start, start_unique, start_highest) = self.period_start;
+        let expected = self.stats.highest - start_highest + if start.received == 0 { 1 } else { 0 };
+        let stats = StreamStats {
+            received: self.stats.received - start.received,
+            duplicates: self.stats.duplicates - start.duplicates,
+            reordered: self.stats.reordered - start.reordered,
+            lost: expected.saturating_sub(self.unique - start_unique),
+            max_burst_loss: self.period_max_burst_loss,
+            ..self.stats
+        };
+        self.period_start = (self.stats, self.unique, self.stats.highest);
+        self.period_max_burst_loss = 0;
+        stats
+    }
+}
+
+type ReportHandler = Box<dyn FnMut(&StreamReport)>;
+
+/// Analyzes the streams of all sources of received datagrams.
+/// ```
+/// use net_utils::{rtp::RtpHeader, stream_analyzer::StreamAnalyzer};
+/// use std::time::Duration;
+/// let mut analyzer = StreamAnalyzer::new(16, |datagram| RtpHeader::parse(datagram).map(|(h, _)| h.sequence as u64));
+/// analyzer.set_report_handler(Duration::from_secs(10), |report| {
+///     for source in &report.sources {
+///         println!("{}: {} received, {} lost", source.source, source.received, source.lost);
+///     }
+/// });
+/// // for every datagram received, e.g. from a multicast socket
+/// analyzer.push("192.0.2.1:5004".parse().unwrap(), &[0x80, 96, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1]);
+/// ```
+pub struct StreamAnalyzer<F> {
+    extract: F,
+    mask: u64,
+    sources: HashMap<SocketAddr, SourceState>,
+    unparsable: u64,
+    report: Option<(Duration, ReportHandler)>,
+    period_start: Instant,
+}
+
+impl<F> fmt::Debug for StreamAnalyzer<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamAnalyzer")
+            .field("mask", &self.mask)
+            .field("sources", &self.sources)
+            .field("unparsable", &self.unparsable)
+            .field("report_interval", &self.report.as_ref().map(|(interval, _)| interval))
+            .finish()
+    }
+}
+
+impl<F: Fn(&[u8]) -> Option<u64>> StreamAnalyzer<F> {
+
+    /// Creates an analyzer of sequence numbers with the width (1 to 64 bits, e.g. 16 for RTP)
+    /// returned by the extractor, None for datagrams of no stream.
+    pub fn new(sequence_bits: u32, extract: F) -> StreamAnalyzer<F> {
+        let bits = sequence_bits.clamp(1, 64);
+        StreamAnalyzer {
+            extract, mask: u64::MAX >> (64 - bits), sources: HashMap::new(), unparsable: 0, report: None,
+            period_start: Instant::now(),
+        }
+    }
+
+    /// Installs the handler of the reports, which is called from `push` or `poll` every
+    /// interval with the statistics of the period.
+    pub fn set_report_handler<R>(&mut self, interval: Duration, handler: R) where R: FnMut(&StreamReport) + 'static {
+        self.report = Some((interval, Box::new(handler)));
+        self.period_start = Instant::now();
+    }
+
+    /// Returns the number of datagrams without sequence number.
+    pub fn unparsable(&self) -> u64 {
+        self.unparsable
+    }
+
+    /// Returns the statistics of the source since its first datagram.
+    pub fn stats(&self, source: &SocketAddr) -> Option<StreamStats> {
+        self.sources.get(source).map(|state| state.stats)
+    }
+
+    /// Returns the statistics of all sources sorted by address.
+    pub fn sources(&self) -> Vec<StreamStats> {
+        let mut sources: Vec<_> = self.sources.values().map(|state| state.stats).collect();
+        sources.sort_by_key(|stats| stats.source);
+        sources
+    }
+
+    /// Forgets the source, e.g. after its stream restarted.
+    pub fn remove(&mut self, source: &SocketAddr) {
+        self.sources.remove(source);
+    }
+
+    /// Adds a datagram received now, see `push_at`.
+    pub fn push(&mut self, source: SocketAddr, datagram: &[u8]) -> Option<u64> {
+        self.push_at(source, datagram, Instant::now())
+    }
+
+    /// Adds a datagram received from the source at the time, e.g. a kernel receive timestamp,
+    /// and returns its extended sequence number.
+    pub fn push_at(&mut self, source: SocketAddr, datagram: &[u8], at: Instant) -> Option<u64> {
+        self.poll(at);
+        let sequence = match (self.extract)(datagram) {
+            Some(sequence) => sequence & self.mask,
+            None => {
+                self.unparsable += 1;
+                return None;
+            },
+        };
+        let state = self.sources.entry(source).or_insert_with(|| SourceState::new(source, sequence));
+        let sequence = state.extend(sequence, self.mask);
+        match sequence {
+            Some(sequence) => state.push(sequence, at),
+            // older than the first sequence number of the first wrap
+            None => {
+                state.stats.received += 1;
+                state.stats.reordered += 1;
+            },
+        }
+        sequence
+    }
+
+    /// Calls the report handler if the report is due.
+    pub fn poll(&mut self, now: Instant) {
+        let (interval, handler) = match self.report.as_mut() {
+            Some(report) => report,
+            None => return,
+        };
+        if now.saturating_duration_since(self.period_start) < *interval {
+            return;
+        }
+        let mut sources: Vec<_> = self.sources.values_mut().map(SourceState::period).collect();
+        sources.sort_by_key(|stats| stats.source);
+        handler(&StreamReport { start: self.period_start, end: now, sources });
+        self.period_start = now;
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    fn analyzer() -> StreamAnalyzer<impl Fn(&[u8]) -> Option<u64>> {
+        StreamAnalyzer::new(16, |datagram: &[u8]| Some(u16::from_be_bytes([*datagram.first()?, *datagram.get(1)?]) as u64))
+    }
+
+    #[test]
+    fn test_sequences() {
+        let source: SocketAddr = "192.0.2.1:5004".parse().unwrap();
+        let mut analyzer = analyzer();
+        let start = Instant::now();
+        // wraps, 3 is lost, 1 reordered, 2 duplicated
+        for (i, sequence) in [65534_u16, 65535, 0, 2, 1, 2, 4, 6, 7].iter().enumerate() {
+            analyzer.push_at(source, &sequence.to_be_bytes(), start + Duration::from_millis(10 * i as u64));
+        }
+        assert_eq!(analyzer.push(source, b"x"), None);
+        let stats = analyzer.stats(&source).unwrap();
+        assert_eq!((stats.received, stats.duplicates, stats.reordered), (9, 1, 1));
+        assert_eq!((stats.lost, stats.max_burst_loss, stats.highest), (2, 1, 65536 + 7));
+        assert_eq!(stats.jitter, Duration::ZERO);
+        assert_eq!(analyzer.unparsable(), 1);
+
+        // a late datagram reduces the loss
+        analyzer.push(source, &3_u16.to_be_bytes());
+        assert_eq!(analyzer.stats(&source).unwrap().lost, 1);
+    }
+
+    #[test]
+    fn test_jitter() {
+        let source: SocketAddr = "192.0.2.1:5004".parse().unwrap();
+        let mut analyzer = analyzer();
+        let start = Instant::now();
+        for (sequence, millis) in [(0_u16, 0), (1, 10), (2, 30)].iter() {
+            analyzer.push_at(source, &sequence.to_be_bytes(), start + Duration::from_millis(*millis));
+        }
+        // the intervals differ by 10 ms
+        assert_eq!(analyzer.stats(&source).unwrap().jitter.as_micros(), 625);
+    }
+
+    #[test]
+    fn test_reports() {
+        let a: SocketAddr = "192.0.2.1:5004".parse().unwrap();
+        let b: SocketAddr = "192.0.2.2:5004".parse().unwrap();
+        let reports = Rc::new(RefCell::new(Vec::new()));
+        let mut analyzer = analyzer();
+        let sink = reports.clone();
+        analyzer.set_report_handler(Duration::from_secs(1), move |report: &StreamReport| {
+            sink.borrow_mut().push(report.clone())
+        });
+        let start = analyzer.period_start;
+        for sequence in [10_u16, 11, 13].iter() {
+            analyzer.push_at(a, &sequence.to_be_bytes(), start);
+        }
+        analyzer.push_at(b, &[0, 1], start);
+        analyzer.poll(start + Duration::from_millis(500));
+        assert!(reports.borrow().is_empty());
+
+        analyzer.push_at(a, &16_u16.to_be_bytes(), start + Duration::from_secs(1));
+        analyzer.push_at(a, &17_u16.to_be_bytes(), start + Duration::from_secs(1));
+        analyzer.poll(start + Duration::from_secs(2));
+        let reports = reports.borrow();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].sources.iter().map(|s| (s.source, s.received, s.lost)).collect::<Vec<_>>(),
+                   vec![(a, 3, 1), (b, 1, 0)]);
+        assert_eq!((reports[1].sources[0].received, reports[1].sources[0].lost, reports[1].sources[0].max_burst_loss),
+                   (2, 2, 2));
+        assert_eq!(reports[1].sources[1].received, 0);
+        assert_eq!(analyzer.stats(&a).unwrap().lost, 3);
+    }
+}
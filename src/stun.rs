@@ -0,0 +1,257 @@
+//! STUN (RFC 5389) client: discovers the public (server reflexive) address of a UDP socket behind a NAT.
+
+use std::{
+    convert::TryInto,
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use super::random::random_bytes;
+
+/// Well known STUN port.
+pub const STUN_PORT: u16 = 3478;
+
+/// Default timeout for the binding response including all retransmissions.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(39_500);
+
+/// Initial retransmission timeout, doubled with every retransmission.
+const INITIAL_RTO: Duration = Duration::from_millis(500);
+
+const HEADER_LEN: usize = 20;
+const MAGIC_COOKIE: u32 = 0x2112_a442;
+const BINDING_REQUEST: u16 = 0x0001;
+const BINDING_SUCCESS: u16 = 0x0101;
+const BINDING_ERROR: u16 = 0x0111;
+const ATTR_MAPPED_ADDRESS: u16 = 0x0001;
+const ATTR_ERROR_CODE: u16 = 0x0009;
+const ATTR_XOR_MAPPED_ADDRESS: u16 = 0x0020;
+const FAMILY_IPV4: u8 = 0x01;
+const FAMILY_IPV6: u8 = 0x02;
+
+type TransactionId = [u8; 12];
+
+/// Sends a binding request from the socket to the STUN server and returns the reflexive address,
+/// i.e. the socket's address as seen by the server. The request is retransmitted as specified by
+/// RFC 5389 until a response arrives or the timeout elapses.
+/// Datagrams received on the socket that are not the response are discarded. The socket's read
+/// timeout is restored afterwards.
+pub fn binding_request(socket: &UdpSocket, server: SocketAddr, timeout: Duration) -> Result<SocketAddr> {
+    let previous_timeout = socket.read_timeout()?;
+    let result = binding_request_blocking(socket, server, timeout);
+    socket.set_read_timeout(previous_timeout)?;
+    result
+}
+
+fn binding_request_blocking(socket: &UdpSocket, server: SocketAddr, timeout: Duration) -> Result<SocketAddr> {
+    let transaction_id = transaction_id();
+    let request = request_packet(&transaction_id);
+    let deadline = Instant::now() + timeout;
+    let mut rto = INITIAL_RTO;
+    let mut response = [0_u8; 576];
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(Error::new(ErrorKind::TimedOut, "no STUN response"));
+        }
+        socket.send_to(&request, server)?;
+        let retransmit_at = deadline.min(now + rto);
+        rto *= 2;
+        loop {
+            let remaining = retransmit_at.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            socket.set_read_timeout(Some(remaining))?;
+            let (len, from) = match socket.recv_from(&mut response) {
+                Ok(received) => received,
+                Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => break,
+                Err(e) => return Err(e),
+            };
+            if from == server {
+                if let Some(result) = parse_response(&transaction_id, &response[..len]) {
+                    return result;
+                }
+            }
+        }
+    }
+}
+
+/// Sends a binding request from the socket to the STUN server and returns the reflexive address.
+/// See `binding_request` for details.
+/// Requires the feature 'tokio-net' and must be called within a tokio runtime.
+#[cfg(feature = "tokio-net")]
+pub async fn binding_request_async(socket: &tokio::net::UdpSocket, server: SocketAddr, timeout: Duration)
+                                   -> Result<SocketAddr> {
+    let transaction_id = transaction_id();
+    let request = request_packet(&transaction_id);
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut rto = INITIAL_RTO;
+    let mut response = [0_u8; 576];
+    loop {
+        let now = tokio::time::Instant::now();
+        if now >= deadline {
+            return Err(Error::new(ErrorKind::TimedOut, "no STUN response"));
+        }
+        socket.send_to(&request, server).await?;
+        let retransmit_at = deadline.min(now + rto);
+        rto *= 2;
+        while let Ok(received) = tokio::time::timeout_at(retransmit_at, socket.recv_from(&mut response)).await {
+            let (len, from) = received?;
+            if from == server {
+                if let Some(result) = parse_response(&transaction_id, &response[..len]) {
+                    return result;
+                }
+            }
+        }
+    }
+}
+
+/// Creates a random 96 bit transaction id.
+fn transaction_id() -> TransactionId {
+    let mut id = [0_u8; 12];
+    random_bytes(&mut id);
+    id
+}
+
+fn request_packet(transaction_id: &TransactionId) -> [u8; HEADER_LEN] {
+    let mut packet = [0_u8; HEADER_LEN];
+    packet[0..2].copy_from_slice(&BINDING_REQUEST.to_be_bytes());
+    packet[4..8].copy_from_slice(&MAGIC_COOKIE.to_be_bytes());
+    packet[8..20].copy_from_slice(transaction_id);
+    packet
+}
+
+/// Parses a binding response, returns None if the datagram is not a response to the transaction.
+fn parse_response(transaction_id: &TransactionId, packet: &[u8]) -> Option<Result<SocketAddr>> {
+    if packet.len() < HEADER_LEN || packet[4..8] != MAGIC_COOKIE.to_be_bytes() || packet[8..20] != transaction_id[..] {
+        return None;
+    }
+    let message_type = u16::from_be_bytes([packet[0], packet[1]]);
+    let length = u16::from_be_bytes([packet[2], packet[3]]) as usize;
+    if message_type != BINDING_SUCCESS && message_type != BINDING_ERROR {
+        return None;
+    }
+    let attributes = match packet.get(HEADER_LEN..HEADER_LEN + length) {
+        Some(attributes) => attributes,
+        None => return Some(Err(Error::new(ErrorKind::InvalidData, "truncated STUN response"))),
+    };
+
+    let mut mapped = None;
+    for (attr_type, value) in StunAttributes(attributes) {
+        match attr_type {
+            ATTR_XOR_MAPPED_ADDRESS => return Some(xor_address(value, transaction_id)),
+            ATTR_MAPPED_ADDRESS => mapped = Some(address(value)),
+            ATTR_ERROR_CODE if message_type == BINDING_ERROR && value.len() >= 4 => {
+                let code = (value[2] & 0x07) as u16 * 100 + value[3] as u16;
+                let reason = String::from_utf8_lossy(&value[4..]);
+                return Some(Err(Error::other(format!("STUN error {} {}", code, reason))));
+            },
+            _ => {},
+        }
+    }
+    Some(match (message_type, mapped) {
+        (BINDING_ERROR, _) => Err(Error::other("STUN error response")),
+        (_, Some(address)) => address,
+        (_, None) => Err(Error::new(ErrorKind::InvalidData, "STUN response without mapped address")),
+    })
+}
+
+/// Decodes a MAPPED-ADDRESS value.
+fn address(value: &[u8]) -> Result<SocketAddr> {
+    let invalid = || Error::new(ErrorKind::InvalidData, "invalid STUN address attribute");
+    if value.len() < 4 {
+        return Err(invalid());
+    }
+    let port = u16::from_be_bytes([value[2], value[3]]);
+    let ip = match (value[1], value.len()) {
+        (FAMILY_IPV4, 8) => IpAddr::V4(Ipv4Addr::from(u32::from_be_bytes(value[4..8].try_into().unwrap()))),
+        (FAMILY_IPV6, 20) => IpAddr::V6(Ipv6Addr::from(u128::from_be_bytes(value[4..20].try_into().unwrap()))),
+        _ => return Err(invalid()),
+    };
+    Ok(SocketAddr::new(ip, port))
+}
+
+/// Decodes an XOR-MAPPED-ADDRESS value, the port is xor'ed with the upper half of the magic
+/// cookie, the address with the magic cookie followed by the transaction id.
+fn xor_address(value: &[u8], transaction_id: &TransactionId) -> Result<SocketAddr> {
+    let mut key = [0_u8; 16];
+    key[0..4].copy_from_slice(&MAGIC_COOKIE.to_be_bytes());
+    key[4..16].copy_from_slice(transaction_id);
+    let mut decoded = value.to_vec();
+    for (i, byte) in decoded.iter_mut().enumerate() {
+        match i {
+            2 | 3 => *byte ^= key[i - 2],
+            4..=19 => *byte ^= key[i - 4],
+            _ => {},
+        }
+    }
+    address(&decoded)
+}
+
+/// Iterator over the (type, value) pairs of the attributes of a STUN message.
+struct StunAttributes<'a>(&'a [u8]);
+
+impl<'a> Iterator for StunAttributes<'a> {
+    type Item = (u16, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0.len() < 4 {
+            return None;
+        }
+        let attr_type = u16::from_be_bytes(self.0[0..2].try_into().unwrap());
+        let len = u16::from_be_bytes(self.0[2..4].try_into().unwrap()) as usize;
+        let value = self.0.get(4..4 + len)?;
+        // values are padded to a multiple of 4 bytes
+        self.0 = self.0.get(4 + ((len + 3) & !3)..).unwrap_or(&[]);
+        Some((attr_type, value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn response(message_type: u16, transaction_id: &TransactionId, attributes: &[u8]) -> Vec<u8> {
+        let mut packet = request_packet(transaction_id).to_vec();
+        packet[0..2].copy_from_slice(&message_type.to_be_bytes());
+        packet[2..4].copy_from_slice(&(attributes.len() as u16).to_be_bytes());
+        packet.extend_from_slice(attributes);
+        packet
+    }
+
+    #[test]
+    fn test_xor_mapped_address() {
+        let id = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
+        // 192.0.2.1:32853 as in the test vectors of RFC 5769
+        let v4 = [0x00, 0x20, 0x00, 0x08, 0x00, 0x01, 0xa1, 0x47, 0xe1, 0x12, 0xa6, 0x43];
+        assert_eq!(parse_response(&id, &response(BINDING_SUCCESS, &id, &v4)).unwrap().unwrap(),
+                   "192.0.2.1:32853".parse().unwrap());
+
+        let mut v6 = vec![0x00, 0x20, 0x00, 0x14, 0x00, 0x02, 0xa1, 0x47];
+        let ip: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let mut key = MAGIC_COOKIE.to_be_bytes().to_vec();
+        key.extend_from_slice(&id);
+        v6.extend(ip.octets().iter().zip(key.iter()).map(|(a, k)| a ^ k));
+        assert_eq!(parse_response(&id, &response(BINDING_SUCCESS, &id, &v6)).unwrap().unwrap(),
+                   "[2001:db8::1]:32853".parse().unwrap());
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let id = [7; 12];
+        // unknown attribute with padding, then MAPPED-ADDRESS 10.0.0.1:4000
+        let attributes = [0x80, 0x22, 0x00, 0x03, b'a', b'b', b'c', 0x00,
+            0x00, 0x01, 0x00, 0x08, 0x00, 0x01, 0x0f, 0xa0, 10, 0, 0, 1];
+        assert_eq!(parse_response(&id, &response(BINDING_SUCCESS, &id, &attributes)).unwrap().unwrap(),
+                   "10.0.0.1:4000".parse().unwrap());
+        assert!(parse_response(&[8; 12], &response(BINDING_SUCCESS, &id, &attributes)).is_none());
+        assert!(parse_response(&id, &response(BINDING_REQUEST, &id, &[])).is_none());
+        assert!(parse_response(&id, &response(BINDING_SUCCESS, &id, &[])).unwrap().is_err());
+
+        let error = [0x00, 0x09, 0x00, 0x0c, 0x00, 0x00, 0x04, 0x00, b'B', b'a', b'd', b' ', b'R', b'e', b'q', 0x00];
+        let err = parse_response(&id, &response(BINDING_ERROR, &id, &error)).unwrap().unwrap_err();
+        assert!(err.to_string().starts_with("STUN error 400 Bad Req"));
+    }
+}
@@ -0,0 +1,414 @@
+//! The system calls of the crate behind the `SysApi` trait: socket creation, binding and
+//! listening, socket options and the interface enumeration. The crate performs them via the
+//! SysApi installed on the calling thread, with the feature 'test-util' tests install a
+//! `MockSysApi` to run without network access or privileges.
+
+use std::{
+    io::{Error, Result},
+    os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+};
+
+use socket2::{SockAddr, Socket};
+
+use super::{
+    IpInterface,
+    proc_interfaces::{InterfaceBackend, interface_backend, proc_ip_interfaces},
+};
+
+#[cfg(any(test, feature = "test-util"))]
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    convert::TryInto,
+    sync::{Arc, Mutex},
+};
+
+/// The system calls the crate uses to create and configure sockets and to enumerate the IP
+/// interfaces: socket creation, binding and listening of all sockets the crate creates, the
+/// socket options set or read by the crate including the group memberships of the multicast
+/// builders (but not the options socket2 and the standard library set, e.g. the memberships by
+/// interface index of the protocol modules) and `IpInterface::retrieve_ip_interfaces`. The
+/// connections the crate opens are created and bound via the SysApi, the connect itself is not
+/// part of it. `OsSysApi` performs them (the
+/// interface enumeration with the backend of `proc_interfaces::set_interface_backend`), with the
+/// feature 'test-util' `with_sys_api` replaces them on the calling thread, e.g. by a
+/// `MockSysApi`.
+pub trait SysApi: Send + Sync {
+    /// Creates a socket (socket(2)), the type includes flags such as SOCK_CLOEXEC.
+    fn socket(&self, domain: libc::c_int, socket_type: libc::c_int, protocol: libc::c_int) -> Result<OwnedFd>;
+
+    /// Binds the socket to the address (bind(2)).
+    fn bind(&self, fd: RawFd, address: &SockAddr) -> Result<()>;
+
+    /// Lets the stream socket accept connections with the backlog (listen(2)).
+    fn listen(&self, fd: RawFd, backlog: libc::c_int) -> Result<()>;
+
+    /// Sets a socket option to the raw value (setsockopt(2)).
+    fn setsockopt(&self, fd: RawFd, level: libc::c_int, name: libc::c_int, value: &[u8]) -> Result<()>;
+
+    /// Reads a socket option into the buffer, returns the length of the value (getsockopt(2)).
+    fn getsockopt(&self, fd: RawFd, level: libc::c_int, name: libc::c_int, value: &mut [u8]) -> Result<usize>;
+
+    /// Returns the IP interface configurations of the host (getifaddrs(3)).
+    fn getifaddrs(&self) -> Result<Vec<IpInterface>>;
+}
+
+/// SysApi performing the system calls.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OsSysApi;
+
+impl SysApi for OsSysApi {
+    fn socket(&self, domain: libc::c_int, socket_type: libc::c_int, protocol: libc::c_int) -> Result<OwnedFd> {
+        let fd = unsafe { libc::socket(domain, socket_type, protocol) };
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+
+    fn bind(&self, fd: RawFd, address: &SockAddr) -> Result<()> {
+        if unsafe { libc::bind(fd, address.as_ptr(), address.len()) } != 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn listen(&self, fd: RawFd, backlog: libc::c_int) -> Result<()> {
+        if unsafe { libc::listen(fd, backlog) } != 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn setsockopt(&self, fd: RawFd, level: libc::c_int, name: libc::c_int, value: &[u8]) -> Result<()> {
+        if unsafe { libc::setsockopt(fd, level, name, value.as_ptr() as *const libc::c_void,
+                                     value.len() as libc::socklen_t) } != 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn getsockopt(&self, fd: RawFd, level: libc::c_int, name: libc::c_int, value: &mut [u8]) -> Result<usize> {
+        let mut len = value.len() as libc::socklen_t;
+        if unsafe { libc::getsockopt(fd, level, name, value.as_mut_ptr() as *mut libc::c_void, &mut len) } != 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(len as usize)
+    }
+
+    fn getifaddrs(&self) -> Result<Vec<IpInterface>> {
+        match interface_backend() {
+            InterfaceBackend::Getifaddrs => Ok(IpInterface::iter_ip_interfaces()?.collect()),
+            InterfaceBackend::Proc => proc_ip_interfaces(),
+            InterfaceBackend::Fallback => IpInterface::iter_ip_interfaces().map(Iterator::collect)
+                .or_else(|_| proc_ip_interfaces()),
+        }
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+thread_local! {
+    static INSTALLED: RefCell<Option<Arc<dyn SysApi>>> = const { RefCell::new(None) };
+}
+
+/// Runs the closure with the SysApi installed on the calling thread, `OsSysApi` if none is.
+pub(crate) fn call<R>(f: impl FnOnce(&dyn SysApi) -> R) -> R {
+    #[cfg(any(test, feature = "test-util"))]
+    if let Some(api) = INSTALLED.with(|installed| installed.borrow().clone()) {
+        return f(&*api);
+    }
+    f(&OsSysApi)
+}
+
+/// Creates a socket with SOCK_CLOEXEC.
+pub(crate) fn socket(domain: libc::c_int, socket_type: libc::c_int, protocol: libc::c_int) -> Result<Socket> {
+    call(|api| api.socket(domain, socket_type | libc::SOCK_CLOEXEC, protocol)).map(Socket::from)
+}
+
+/// Binds the socket to the address.
+pub(crate) fn bind(socket: &Socket, address: &SockAddr) -> Result<()> {
+    call(|api| api.bind(socket.as_raw_fd(), address))
+}
+
+/// Lets the stream socket accept connections.
+pub(crate) fn listen(socket: &Socket, backlog: libc::c_int) -> Result<()> {
+    call(|api| api.listen(socket.as_raw_fd(), backlog))
+}
+
+/// Runs the closure with the SysApi replacing the system calls of the crate on the calling
+/// thread, the SysApi installed before is restored afterwards (also if the closure panics).
+/// Sockets created on other threads, e.g. by tokio worker threads, are not affected.
+/// Requires the feature 'test-util'.
+#[cfg(any(test, feature = "test-util"))]
+pub fn with_sys_api<R>(api: Arc<dyn SysApi>, f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<Arc<dyn SysApi>>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            INSTALLED.with(|installed| *installed.borrow_mut() = previous);
+        }
+    }
+    let _restore = Restore(INSTALLED.with(|installed| installed.borrow_mut().replace(api)));
+    f()
+}
+
+/// A system call received by a `MockSysApi`.
+/// Requires the feature 'test-util'.
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SysCall {
+    /// socket creation, the type includes SOCK_CLOEXEC
+    Socket { domain: libc::c_int, socket_type: libc::c_int, protocol: libc::c_int },
+    /// binding of a socket
+    Bind { fd: RawFd, address: SockAddr },
+    /// listening of a stream socket
+    Listen { fd: RawFd, backlog: libc::c_int },
+    /// socket option set
+    SetSockOpt { fd: RawFd, level: libc::c_int, name: libc::c_int, value: Vec<u8> },
+    /// socket option read
+    GetSockOpt { fd: RawFd, level: libc::c_int, name: libc::c_int },
+    /// interface enumeration
+    GetIfAddrs,
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl SysCall {
+
+    /// Returns the name of the system call, e.g. "setsockopt".
+    pub fn name(&self) -> &'static str {
+        match self {
+            SysCall::Socket { .. } => "socket",
+            SysCall::Bind { .. } => "bind",
+            SysCall::Listen { .. } => "listen",
+            SysCall::SetSockOpt { .. } => "setsockopt",
+            SysCall::GetSockOpt { .. } => "getsockopt",
+            SysCall::GetIfAddrs => "getifaddrs",
+        }
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Debug, Default)]
+struct MockState {
+    calls: Vec<SysCall>,
+    options: HashMap<(RawFd, libc::c_int, libc::c_int), Vec<u8>>,
+    interfaces: Vec<IpInterface>,
+    errors: HashMap<String, i32>,
+}
+
+/// SysApi without network access or privileges: it records the calls, creates unbound sockets
+/// of the requested type for IPv4 and IPv6 datagram and stream sockets (which need no
+/// privileges) and unix datagram sockets instead of the others, accepts all addresses, listens
+/// and options, returns the options set before (zero for others) and a configurable list of
+/// interface configurations. Calls can be made to fail with an errno.
+/// Requires the feature 'test-util'.
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Debug, Default)]
+pub struct MockSysApi {
+    state: Mutex<MockState>,
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl MockSysApi {
+
+    /// Creates a mock without interfaces.
+    pub fn new() -> MockSysApi {
+        MockSysApi::default()
+    }
+
+    /// Replaces the interface configurations returned by `getifaddrs`, see
+    /// `MockInterfaceProvider::interface` to create them.
+    pub fn set_interfaces(&self, interfaces: Vec<IpInterface>) {
+        self.state().interfaces = interfaces;
+    }
+
+    /// Lets all further calls of the system call with the name (e.g. "bind") fail with the errno.
+    pub fn fail(&self, syscall: &str, errno: i32) {
+        self.state().errors.insert(syscall.to_string(), errno);
+    }
+
+    /// Lets further calls of the system call with the name succeed again.
+    pub fn succeed(&self, syscall: &str) {
+        self.state().errors.remove(syscall);
+    }
+
+    /// Returns the calls received so far, failed ones included.
+    pub fn calls(&self) -> Vec<SysCall> {
+        self.state().calls.clone()
+    }
+
+    /// Returns the value of the socket option last set on the file descriptor.
+    pub fn option(&self, fd: RawFd, level: libc::c_int, name: libc::c_int) -> Option<Vec<u8>> {
+        self.state().options.get(&(fd, level, name)).cloned()
+    }
+
+    /// Returns the value of an int socket option last set on the file descriptor.
+    pub fn int_option(&self, fd: RawFd, level: libc::c_int, name: libc::c_int) -> Option<libc::c_int> {
+        let value = self.option(fd, level, name)?;
+        Some(libc::c_int::from_ne_bytes(value.get(..std::mem::size_of::<libc::c_int>())?.try_into().ok()?))
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Records the call and returns the state or the error to fail with.
+    fn record(&self, call: SysCall) -> Result<std::sync::MutexGuard<'_, MockState>> {
+        let mut state = self.state();
+        let error = state.errors.get(call.name()).copied();
+        state.calls.push(call);
+        match error {
+            Some(errno) => Err(Error::from_raw_os_error(errno)),
+            None => Ok(state),
+        }
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl SysApi for MockSysApi {
+    fn socket(&self, domain: libc::c_int, socket_type: libc::c_int, protocol: libc::c_int) -> Result<OwnedFd> {
+        drop(self.record(SysCall::Socket { domain, socket_type, protocol })?);
+        let kind = socket_type & !(libc::SOCK_CLOEXEC | libc::SOCK_NONBLOCK);
+        if [libc::AF_INET, libc::AF_INET6].contains(&domain) && [libc::SOCK_DGRAM, libc::SOCK_STREAM].contains(&kind) {
+            return OsSysApi.socket(domain, socket_type, 0);
+        }
+        Ok(std::os::unix::net::UnixDatagram::unbound()?.into())
+    }
+
+    fn bind(&self, fd: RawFd, address: &SockAddr) -> Result<()> {
+        self.record(SysCall::Bind { fd, address: address.clone() }).map(drop)
+    }
+
+    fn listen(&self, fd: RawFd, backlog: libc::c_int) -> Result<()> {
+        self.record(SysCall::Listen { fd, backlog }).map(drop)
+    }
+
+    fn setsockopt(&self, fd: RawFd, level: libc::c_int, name: libc::c_int, value: &[u8]) -> Result<()> {
+        let mut state = self.record(SysCall::SetSockOpt { fd, level, name, value: value.to_vec() })?;
+        state.options.insert((fd, level, name), value.to_vec());
+        Ok(())
+    }
+
+    fn getsockopt(&self, fd: RawFd, level: libc::c_int, name: libc::c_int, value: &mut [u8]) -> Result<usize> {
+        let state = self.record(SysCall::GetSockOpt { fd, level, name })?;
+        value.fill(0);
+        Ok(match state.options.get(&(fd, level, name)) {
+            Some(set) => {
+                let len = set.len().min(value.len());
+                value[..len].copy_from_slice(&set[..len]);
+                len
+            },
+            None => value.len(),
+        })
+    }
+
+    fn getifaddrs(&self) -> Result<Vec<IpInterface>> {
+        Ok(self.record(SysCall::GetIfAddrs)?.interfaces.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::{MockInterfaceProvider, MulticastSocketBuilder, netlink::NetlinkSocket, raw::RawSocketBuilder,
+                socket_factory::UdpSpec, tcp::TcpListenerBuilder};
+    use std::{
+        io::ErrorKind,
+        net::SocketAddr,
+    };
+
+    fn mocked<R>(f: impl FnOnce(&MockSysApi) -> R) -> R {
+        let mock = Arc::new(MockSysApi::new());
+        with_sys_api(mock.clone(), || f(&mock))
+    }
+
+    #[test]
+    fn test_udp_spec() {
+        mocked(|mock| {
+            let address: SocketAddr = "192.0.2.1:123".parse().unwrap();
+            let spec = UdpSpec::new(address).bind_device("eth7").recv_buffer_size(1 << 24).force_buffers(true);
+            let socket = spec.build().unwrap();
+            let fd = socket.as_raw_fd();
+            assert_eq!(mock.int_option(fd, libc::SOL_SOCKET, libc::SO_RCVBUFFORCE), Some(1 << 24));
+            assert_eq!(mock.option(fd, libc::SOL_SOCKET, libc::SO_BINDTODEVICE), Some(b"eth7".to_vec()));
+            let calls = mock.calls();
+            assert_eq!(calls[0], SysCall::Socket {
+                domain: libc::AF_INET, socket_type: libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, protocol: libc::IPPROTO_UDP,
+            });
+            assert_eq!(calls.last(), Some(&SysCall::Bind { fd, address: SockAddr::from(address) }));
+        });
+    }
+
+    #[test]
+    fn test_raw_socket() {
+        mocked(|mock| {
+            let raw = RawSocketBuilder::ipv4(112).header_included(true).hops(1).build().unwrap();
+            assert!(raw.header_included().unwrap());
+            assert_eq!(mock.int_option(raw.as_raw_fd(), libc::IPPROTO_IP, libc::IP_TTL), Some(1));
+            assert_eq!(mock.calls()[0].name(), "socket");
+
+            mock.fail("socket", libc::EPERM);
+            assert_eq!(RawSocketBuilder::ipv4(112).build().unwrap_err().kind(), ErrorKind::PermissionDenied);
+            mock.succeed("socket");
+            assert!(RawSocketBuilder::ipv4(112).build().is_ok());
+        });
+    }
+
+    #[test]
+    fn test_multicast_builder() {
+        mocked(|mock| {
+            let group: SocketAddr = "239.1.2.3:5000".parse().unwrap();
+            let socket = MulticastSocketBuilder::new(group, "127.0.0.1".parse().unwrap()).build_std().unwrap();
+            let calls = mock.calls();
+            assert_eq!(calls[0], SysCall::Socket {
+                domain: libc::AF_INET, socket_type: libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, protocol: libc::IPPROTO_UDP,
+            });
+            assert!(calls.contains(&SysCall::Bind { fd: socket.as_raw_fd(), address: SockAddr::from(group) }));
+
+            mock.fail("bind", libc::EADDRINUSE);
+            let error = MulticastSocketBuilder::new(group, "127.0.0.1".parse().unwrap()).build_std().unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::AddrInUse);
+        });
+    }
+
+    #[test]
+    fn test_tcp_listener_builder() {
+        mocked(|mock| {
+            let address: SocketAddr = "192.0.2.1:8080".parse().unwrap();
+            let listener = TcpListenerBuilder::new(address).backlog(16).defer_accept(5).freebind(true).build_std().unwrap();
+            let fd = listener.as_raw_fd();
+            assert_eq!(mock.int_option(fd, libc::IPPROTO_TCP, libc::TCP_DEFER_ACCEPT), Some(5));
+            assert_eq!(mock.int_option(fd, libc::IPPROTO_IP, libc::IP_FREEBIND), Some(1));
+            let calls = mock.calls();
+            assert_eq!(calls[0].name(), "socket");
+            assert_eq!(&calls[calls.len() - 2..], [
+                SysCall::Bind { fd, address: SockAddr::from(address) }, SysCall::Listen { fd, backlog: 16 },
+            ]);
+
+            mock.fail("listen", libc::EADDRINUSE);
+            assert_eq!(TcpListenerBuilder::new(address).build_std().unwrap_err().kind(), ErrorKind::AddrInUse);
+        });
+    }
+
+    #[test]
+    fn test_interfaces_and_netlink() {
+        mocked(|mock| {
+            mock.set_interfaces(vec![
+                MockInterfaceProvider::interface(3, "eth0", libc::IFF_UP, "192.0.2.7:0".parse().unwrap()),
+            ]);
+            let interfaces = IpInterface::retrieve_ip_interfaces().unwrap();
+            assert_eq!((interfaces.len(), interfaces[0].name.as_str()), (1, "eth0"));
+
+            NetlinkSocket::new(libc::NETLINK_ROUTE, 1).unwrap();
+            match &mock.calls()[2] {
+                SysCall::Bind { address, .. } => assert_eq!(address.family(), libc::AF_NETLINK as libc::sa_family_t),
+                call => panic!("unexpected call {:?}", call),
+            }
+            mock.fail("getifaddrs", libc::EACCES);
+            assert!(IpInterface::retrieve_ip_interfaces().is_err());
+        });
+        // the system calls are restored
+        assert!(IpInterface::retrieve_ip_interfaces().unwrap().iter().any(|interface| interface.name == "lo"));
+    }
+}
@@ -0,0 +1,149 @@
+//! Socket creation, binding, listening and multicast membership of the crate: via the `SysApi`
+//! of the calling thread on linux and android (see `sys_api`), via socket2 on other systems.
+
+use std::{
+    io::{Error, ErrorKind, Result},
+    net::{Ipv4Addr, Ipv6Addr, ToSocketAddrs, UdpSocket},
+};
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::os::unix::io::AsRawFd;
+
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+
+/// Creates a socket of the domain, type and protocol (the default one of the type for None).
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn socket(domain: Domain, socket_type: Type, protocol: Option<Protocol>) -> Result<Socket> {
+    super::sys_api::socket(domain.into(), socket_type.into(), protocol.map_or(0, Into::into))
+}
+
+/// Creates a socket of the domain, type and protocol (the default one of the type for None).
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) fn socket(domain: Domain, socket_type: Type, protocol: Option<Protocol>) -> Result<Socket> {
+    Socket::new(domain, socket_type, protocol)
+}
+
+/// Binds the socket to the address.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn bind(socket: &Socket, address: &SockAddr) -> Result<()> {
+    super::sys_api::bind(socket, address)
+}
+
+/// Binds the socket to the address.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) fn bind(socket: &Socket, address: &SockAddr) -> Result<()> {
+    socket.bind(address)
+}
+
+/// Lets the stream socket accept connections with the backlog.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn listen(socket: &Socket, backlog: i32) -> Result<()> {
+    super::sys_api::listen(socket, backlog)
+}
+
+/// Lets the stream socket accept connections with the backlog.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) fn listen(socket: &Socket, backlog: i32) -> Result<()> {
+    socket.listen(backlog)
+}
+
+/// Creates a UDP socket bound to the first of the addresses it can be bound to, like
+/// `std::net::UdpSocket::bind`.
+pub(crate) fn udp_bind<A: ToSocketAddrs>(addresses: A) -> Result<UdpSocket> {
+    each_address(addresses, |address| {
+        let socket = socket(Domain::for_address(address), Type::DGRAM, Some(Protocol::UDP))?;
+        bind(&socket, &address.into())?;
+        Ok(socket.into())
+    })
+}
+
+/// Creates a TCP socket listening on the first of the addresses it can be bound to, like
+/// `std::net::TcpListener::bind` (SO_REUSEADDR on unix, backlog 128).
+#[cfg(target_os = "linux")]
+pub(crate) fn tcp_listen<A: ToSocketAddrs>(addresses: A) -> Result<std::net::TcpListener> {
+    each_address(addresses, |address| {
+        let socket = socket(Domain::for_address(address), Type::STREAM, Some(Protocol::TCP))?;
+        #[cfg(unix)]
+        socket.set_reuse_address(true)?;
+        bind(&socket, &address.into())?;
+        listen(&socket, 128)?;
+        Ok(socket.into())
+    })
+}
+
+/// Returns the first result of `f` for the addresses which is Ok, or the last error.
+fn each_address<A: ToSocketAddrs, T>(addresses: A, mut f: impl FnMut(std::net::SocketAddr) -> Result<T>) -> Result<T> {
+    let mut last_error = None;
+    for address in addresses.to_socket_addrs()? {
+        match f(address) {
+            Ok(value) => return Ok(value),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| Error::new(ErrorKind::InvalidInput, "could not resolve to any addresses")))
+}
+
+/// Joins the IPv4 group on the interface with the local address (IP_ADD_MEMBERSHIP).
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn join_multicast_v4(socket: &Socket, group: &Ipv4Addr, interface: &Ipv4Addr) -> Result<()> {
+    let fd = socket.as_raw_fd();
+    super::sockopt::set_option(fd, libc::IPPROTO_IP, libc::IP_ADD_MEMBERSHIP, &ip_mreq(group, interface))
+}
+
+/// Joins the IPv4 group on the interface with the local address (IP_ADD_MEMBERSHIP).
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) fn join_multicast_v4(socket: &Socket, group: &Ipv4Addr, interface: &Ipv4Addr) -> Result<()> {
+    socket.join_multicast_v4(group, interface)
+}
+
+/// Leaves the IPv4 group on the interface with the local address (IP_DROP_MEMBERSHIP).
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn leave_multicast_v4(socket: &Socket, group: &Ipv4Addr, interface: &Ipv4Addr) -> Result<()> {
+    let fd = socket.as_raw_fd();
+    super::sockopt::set_option(fd, libc::IPPROTO_IP, libc::IP_DROP_MEMBERSHIP, &ip_mreq(group, interface))
+}
+
+/// Leaves the IPv4 group on the interface with the local address (IP_DROP_MEMBERSHIP).
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) fn leave_multicast_v4(socket: &Socket, group: &Ipv4Addr, interface: &Ipv4Addr) -> Result<()> {
+    socket.leave_multicast_v4(group, interface)
+}
+
+/// Joins the IPv6 group on the interface with the index (IPV6_ADD_MEMBERSHIP).
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn join_multicast_v6(socket: &Socket, group: &Ipv6Addr, index: u32) -> Result<()> {
+    let fd = socket.as_raw_fd();
+    super::sockopt::set_option(fd, libc::IPPROTO_IPV6, libc::IPV6_ADD_MEMBERSHIP, &ipv6_mreq(group, index))
+}
+
+/// Joins the IPv6 group on the interface with the index (IPV6_ADD_MEMBERSHIP).
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) fn join_multicast_v6(socket: &Socket, group: &Ipv6Addr, index: u32) -> Result<()> {
+    socket.join_multicast_v6(group, index)
+}
+
+/// Leaves the IPv6 group on the interface with the index (IPV6_DROP_MEMBERSHIP).
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn leave_multicast_v6(socket: &Socket, group: &Ipv6Addr, index: u32) -> Result<()> {
+    let fd = socket.as_raw_fd();
+    super::sockopt::set_option(fd, libc::IPPROTO_IPV6, libc::IPV6_DROP_MEMBERSHIP, &ipv6_mreq(group, index))
+}
+
+/// Leaves the IPv6 group on the interface with the index (IPV6_DROP_MEMBERSHIP).
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) fn leave_multicast_v6(socket: &Socket, group: &Ipv6Addr, index: u32) -> Result<()> {
+    socket.leave_multicast_v6(group, index)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn ip_mreq(group: &Ipv4Addr, interface: &Ipv4Addr) -> libc::ip_mreq {
+    libc::ip_mreq {
+        imr_multiaddr: libc::in_addr { s_addr: u32::from_ne_bytes(group.octets()) },
+        imr_interface: libc::in_addr { s_addr: u32::from_ne_bytes(interface.octets()) },
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn ipv6_mreq(group: &Ipv6Addr, index: u32) -> libc::ipv6_mreq {
+    libc::ipv6_mreq { ipv6mr_multiaddr: libc::in6_addr { s6_addr: group.octets() }, ipv6mr_interface: index as _ }
+}
@@ -0,0 +1,330 @@
+//! System calls of the crate for seccomp allow lists: `SYSCALL_GROUPS` lists the system calls
+//! each area of the crate performs, directly, via the C library (e.g. getifaddrs, getaddrinfo)
+//! or via the standard library and socket2, and `self_test` exercises the paths, e.g. to run
+//! under a new filter in log mode (SECCOMP_RET_LOG) before enforcing it. The names are the
+//! x86_64 ones, other architectures have only some of their successors (e.g. ppoll instead of
+//! poll, epoll_pwait instead of epoll_wait). Tests check that every module performing I/O is in
+//! a group and the direct libc calls of all modules against the tables. Memory management and
+//! thread creation of the runtime are not listed.
+
+use std::{
+    io::{Error, ErrorKind, Result},
+    net::{Ipv4Addr, UdpSocket},
+    time::Duration,
+};
+
+/// An area of the crate and the system calls its modules perform.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SyscallGroup {
+    /// name of the area, e.g. "netlink"
+    pub name: &'static str,
+
+    /// feature the area requires, None if it is always available
+    pub feature: Option<&'static str>,
+
+    /// modules performing the system calls
+    pub modules: &'static [&'static str],
+
+    /// the system calls
+    pub syscalls: &'static [&'static str],
+}
+
+/// The system calls by area, "base" is performed by all areas.
+pub const SYSCALL_GROUPS: &[SyscallGroup] = &[
+    SyscallGroup {
+        name: "base",
+        feature: None,
+        modules: &["random", "stats", "trace"],
+        // random keys of the standard library and secure random bytes, locks, file descriptors
+        syscalls: &["close", "fcntl", "futex", "getrandom"],
+    },
+    SyscallGroup {
+        name: "sockets",
+        feature: None,
+        modules: &["arp", "beacon", "codec", "connectivity", "device", "dns", "doip", "fec", "http", "icmpv6", "igd",
+                   "igmp_proxy", "lldp", "llmnr", "membership_report", "multicast", "multicast_diagnosis", "netperf",
+                   "pcp", "ptp", "quic", "ra", "rate_limit", "raw", "relmcast", "rtp", "sap", "scan", "sctp", "secure",
+                   "snooping", "sntp", "socket_factory", "socket_set", "sockopt", "ssdp", "stun", "sys_api", "sys_socket",
+                   "syslog", "tcp", "tftp", "udp", "udplite", "unix", "vrrp", "vsock"],
+        // if_nametoindex uses socket and ioctl, std sets O_NONBLOCK with ioctl(FIONBIO)
+        syscalls: &["accept4", "bind", "close", "connect", "getpeername", "getsockname", "getsockopt", "ioctl",
+                    "listen", "recvfrom", "sendto", "setsockopt", "shutdown", "socket"],
+    },
+    SyscallGroup {
+        name: "control_messages",
+        feature: None,
+        modules: &["cmsg", "errqueue", "igmp_proxy", "pktinfo", "ptp", "quic", "ra", "raw", "timestamping", "udp",
+                   "vrrp"],
+        syscalls: &["recvmmsg", "recvmsg", "sendmsg"],
+    },
+    SyscallGroup {
+        name: "interfaces",
+        feature: None,
+        modules: &["interface_cache", "interface_provider", "ip_interface", "sys_api"],
+        // getifaddrs of the C library queries links and addresses via netlink
+        syscalls: &["bind", "close", "getsockname", "ioctl", "recvmsg", "sendto", "socket"],
+    },
+    SyscallGroup {
+        name: "netlink",
+        feature: None,
+        modules: &["configure", "connectivity", "ethtool", "igmp_proxy", "interface_cache", "interface_kind",
+                   "ip_address", "link_config", "link_state", "lldp", "multicast_diagnosis", "netlink", "pcp", "peer",
+                   "snooping", "vrrp", "wifi", "wireguard"],
+        syscalls: &["bind", "close", "fcntl", "getsockname", "recvfrom", "sendto", "setsockopt", "socket"],
+    },
+    SyscallGroup {
+        name: "ioctl",
+        feature: None,
+        modules: &["interface_kind", "ioctl", "timestamping", "vsock"],
+        syscalls: &["close", "ioctl", "socket"],
+    },
+    SyscallGroup {
+        name: "shutdown",
+        feature: None,
+        modules: &["shutdown"],
+        syscalls: &["poll", "socketpair", "write"],
+    },
+    SyscallGroup {
+        name: "process",
+        feature: None,
+        modules: &["multicast_diagnosis", "syslog", "systemd"],
+        // std::process::id
+        syscalls: &["getpid"],
+    },
+    SyscallGroup {
+        name: "files",
+        feature: None,
+        modules: &["capabilities", "dhcp", "dns", "interface_kind", "link_config", "link_state", "multicast_groups",
+                   "proc_interfaces", "sysinfo", "vsock"],
+        // /proc and /sys, uname for the kernel version
+        syscalls: &["close", "lseek", "newfstatat", "openat", "read", "statx", "uname", "write"],
+    },
+    SyscallGroup {
+        name: "resolver",
+        feature: None,
+        modules: &["host"],
+        // gethostname uses uname, getaddrinfo reads /etc and queries the DNS servers
+        syscalls: &["close", "connect", "newfstatat", "openat", "poll", "read", "recvfrom", "sendto", "socket", "uname"],
+    },
+    SyscallGroup {
+        name: "tokio",
+        feature: Some("tokio-net"),
+        modules: &[],
+        syscalls: &["epoll_create1", "epoll_ctl", "epoll_wait", "eventfd2", "read", "write"],
+    },
+    SyscallGroup {
+        name: "systemd",
+        feature: Some("systemd"),
+        modules: &["systemd"],
+        syscalls: &["fcntl", "getsockname", "getsockopt"],
+    },
+    SyscallGroup {
+        name: "ffi",
+        feature: Some("ffi"),
+        modules: &["ffi"],
+        syscalls: &["close"],
+    },
+];
+
+/// Returns the sorted system calls of the groups with the names and of "base". Fails with
+/// NotFound for unknown names.
+pub fn syscalls(groups: &[&str]) -> Result<Vec<&'static str>> {
+    let mut syscalls = Vec::new();
+    for name in std::iter::once(&"base").chain(groups) {
+        let group = SYSCALL_GROUPS.iter().find(|group| group.name == *name)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("no syscall group {}", name)))?;
+        syscalls.extend_from_slice(group.syscalls);
+    }
+    syscalls.sort_unstable();
+    syscalls.dedup();
+    Ok(syscalls)
+}
+
+/// Result of the self test of a group.
+#[derive(Debug)]
+pub struct SelfTestResult {
+    /// name of the group
+    pub group: &'static str,
+
+    /// error of the first failing call
+    pub result: Result<()>,
+}
+
+/// Exercises the system calls of a group.
+type GroupTest = fn() -> Result<()>;
+
+/// Exercises the paths of the groups which do not require privileges, features or network
+/// access (besides loopback), a blocked system call shows up as failed group (or kills the
+/// process, depending on the filter action).
+pub fn self_test() -> Vec<SelfTestResult> {
+    let tests: [(&'static str, GroupTest); 9] = [
+        ("sockets", test_sockets),
+        ("control_messages", test_control_messages),
+        ("interfaces", || super::IpInterface::retrieve_ip_interfaces().map(drop)),
+        ("netlink", || super::netlink::links().map(drop)),
+        ("ioctl", || super::ioctl::hardware_address("lo").map(drop)),
+        ("shutdown", test_shutdown),
+        ("process", || match std::process::id() { 0 => Err(Error::other("process id 0")), _ => Ok(()) }),
+        ("files", || super::sysinfo::ip_forwarding(super::sysinfo::Family::Ipv4, None).map(drop)),
+        ("resolver", || super::host::hostname().map(drop)),
+    ];
+    tests.iter().map(|(group, test)| SelfTestResult { group, result: test() }).collect()
+}
+
+/// Loopback socket with options, connect, send and receive.
+fn test_sockets() -> Result<()> {
+    let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))?;
+    socket.set_read_timeout(Some(Duration::from_secs(1)))?;
+    socket.connect(socket.local_addr()?)?;
+    socket.send(b"audit")?;
+    socket.recv(&mut [0_u8; 8])?;
+    std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).map(drop)
+}
+
+fn test_control_messages() -> Result<()> {
+    let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))?;
+    socket.set_read_timeout(Some(Duration::from_secs(1)))?;
+    super::pktinfo::set_pktinfo(&socket, true)?;
+    super::udp::send_to_with_ttl(&socket, b"audit", socket.local_addr()?, 1)?;
+    super::pktinfo::recv_batch_with_pktinfo(&socket, &mut [&mut [0_u8; 8][..]])?;
+    socket.send_to(b"audit", socket.local_addr()?)?;
+    super::pktinfo::recv_with_pktinfo(&socket, &mut [0_u8; 8]).map(drop)
+}
+
+fn test_shutdown() -> Result<()> {
+    let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))?;
+    let handle = super::shutdown::ShutdownHandle::new().share()?;
+    handle.shutdown();
+    match handle.wait_readable(std::os::unix::io::AsFd::as_fd(&socket)) {
+        Err(e) if super::shutdown::is_shutdown_error(&e) => Ok(()),
+        Err(e) => Err(e),
+        Ok(()) => Err(Error::other("socket readable after shutdown")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    /// System calls of the C library functions called by the crate.
+    const LIBC_FUNCTIONS: &[(&str, &[&str])] = &[
+        ("bind", &["bind"]),
+        ("close", &["close"]),
+        ("fcntl", &["fcntl"]),
+        ("freeaddrinfo", &[]),
+        ("freeifaddrs", &[]),
+        ("getaddrinfo", &["socket", "connect", "sendto", "recvfrom", "poll", "openat", "read", "close"]),
+        ("gethostname", &["uname"]),
+        ("getifaddrs", &["socket", "bind", "getsockname", "sendto", "recvmsg", "close"]),
+        ("getpeereid", &["getsockopt"]),
+        ("getrandom", &["getrandom"]),
+        ("getsockname", &["getsockname"]),
+        ("getsockopt", &["getsockopt"]),
+        ("if_nametoindex", &["socket", "ioctl", "close"]),
+        ("ioctl", &["ioctl"]),
+        ("listen", &["listen"]),
+        ("poll", &["poll"]),
+        ("recv", &["recvfrom"]),
+        ("recvfrom", &["recvfrom"]),
+        ("recvmmsg", &["recvmmsg"]),
+        ("recvmsg", &["recvmsg"]),
+        ("sendmsg", &["sendmsg"]),
+        ("sendto", &["sendto"]),
+        ("setsockopt", &["setsockopt"]),
+        ("socket", &["socket"]),
+        ("uname", &["uname"]),
+    ];
+
+    /// Returns the libc functions called in the source, i.e. `libc::name(` of lower case names.
+    fn libc_calls(source: &str) -> Vec<&str> {
+        source.match_indices("libc::").filter_map(|(index, _)| {
+            let rest = &source[index + 6..];
+            let end = rest.find(|c: char| !(c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_'))?;
+            Some(&rest[..end]).filter(|name| !name.is_empty() && rest[end..].starts_with('('))
+        }).filter(|name| !name.starts_with("c_") && !name.starts_with("CMSG")).collect()
+    }
+
+    /// Markers of I/O besides libc calls: sockets, files, the process id and other I/O modules.
+    const IO_MARKERS: &[&str] = &[
+        "Socket::new(", "UdpSocket::bind(", "TcpStream::connect", "TcpListener::bind(", ".send_to(", ".recv_from(",
+        "recv_msg(", "sockopt::", "join_socket(", "build_std(", "fs::", "process::id()", "netlink::", "NetlinkSocket",
+        "link_config::", "sys_api::", "http::request",
+    ];
+
+    /// Returns the code of the source without comments and test modules.
+    fn code(source: &str) -> String {
+        let end = ["#[cfg(test)]", "#[cfg(all(test"].iter().filter_map(|marker| source.find(marker)).min();
+        source[..end.unwrap_or(source.len())].lines()
+            .filter(|line| !line.trim_start().starts_with("//"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn test_modules_in_groups() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        let mut missing = Vec::new();
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|extension| extension != "rs") {
+                continue;
+            }
+            let module = path.file_stem().unwrap().to_str().unwrap().to_string();
+            if module == "lib" || module == "syscalls" {
+                continue;
+            }
+            let code = code(&std::fs::read_to_string(&path).unwrap());
+            let performs_io = !libc_calls(&code).is_empty() || IO_MARKERS.iter().any(|marker| code.contains(marker));
+            if performs_io && !SYSCALL_GROUPS.iter().any(|group| group.modules.contains(&module.as_str())) {
+                missing.push(module);
+            }
+        }
+        missing.sort_unstable();
+        assert!(missing.is_empty(), "modules performing I/O in no group: {:?}", missing);
+    }
+
+    #[test]
+    fn test_tables_cover_sources() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|extension| extension != "rs") {
+                continue;
+            }
+            let module = path.file_stem().unwrap().to_str().unwrap();
+            // the examples of this module are no calls
+            if module == "syscalls" {
+                continue;
+            }
+            let source = std::fs::read_to_string(&path).unwrap();
+            let allowed: Vec<&str> = SYSCALL_GROUPS.iter()
+                .filter(|group| group.name == "base" || group.modules.contains(&module))
+                .flat_map(|group| group.syscalls.iter().copied())
+                .collect();
+            for function in libc_calls(&source) {
+                let syscalls = LIBC_FUNCTIONS.iter().find(|(name, _)| *name == function)
+                    .unwrap_or_else(|| panic!("libc::{} of module {} is not in LIBC_FUNCTIONS", function, module)).1;
+                for syscall in syscalls {
+                    assert!(allowed.contains(syscall), "{} (libc::{}) of module {} is in no group of the module",
+                            syscall, function, module);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_syscalls() {
+        let syscalls = syscalls(&["control_messages"]).unwrap();
+        assert_eq!(syscalls, vec!["close", "fcntl", "futex", "getrandom", "recvmmsg", "recvmsg", "sendmsg"]);
+        assert_eq!(super::syscalls(&["seccomp"]).unwrap_err().kind(), ErrorKind::NotFound);
+        assert_eq!(libc_calls("unsafe { libc::recv(fd, libc::MSG_DONTWAIT) } as libc::c_int; libc::CMSG_LEN(4)"), vec!["recv"]);
+    }
+
+    #[test]
+    fn test_self_test() {
+        for result in self_test() {
+            assert!(result.result.is_ok(), "self test of {} failed: {:?}", result.group, result.result);
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! Typed access to the IP forwarding status and the per-interface IPv4 and IPv6 sysctl settings
+//! in /proc/sys/net/{ipv4,ipv6}/conf/<interface> which affect multicast and routing behavior.
+//! The pseudo interfaces "all" and "default" address the settings of all interfaces and the
+//! default for new interfaces. Setting values requires CAP_NET_ADMIN.
+
+use std::{
+    io::{Error, ErrorKind, Result},
+    path::{Path, PathBuf},
+};
+
+/// Base directory of the network sysctl settings.
+const PROC_SYS_NET: &str = "/proc/sys/net";
+
+/// IP version of a setting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Family {
+    /// IPv4 (/proc/sys/net/ipv4)
+    Ipv4,
+    /// IPv6 (/proc/sys/net/ipv6)
+    Ipv6,
+}
+
+impl Family {
+
+    fn dir(&self) -> &'static str {
+        match self {
+            Family::Ipv4 => "ipv4",
+            Family::Ipv6 => "ipv6",
+        }
+    }
+}
+
+/// Reverse path filtering mode (RFC 3704) of IPv4 packets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum RpFilter {
+    /// no source validation
+    Off = 0,
+    /// packets are dropped unless their source is reachable via the receiving interface
+    Strict = 1,
+    /// packets are dropped unless their source is reachable via any interface
+    Loose = 2,
+}
+
+/// Processing of IPv6 router advertisements.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AcceptRa {
+    /// router advertisements are ignored
+    Off = 0,
+    /// router advertisements are accepted unless forwarding is enabled
+    On = 1,
+    /// router advertisements are accepted even if forwarding is enabled
+    Always = 2,
+}
+
+/// Reads the raw value of a per-interface setting (e.g. "rp_filter").
+pub fn interface_setting(family: Family, interface: &str, name: &str) -> Result<String> {
+    read_setting(&setting_path(family, interface, name)?)
+}
+
+/// Writes the raw value of a per-interface setting.
+pub fn set_interface_setting(family: Family, interface: &str, name: &str, value: &str) -> Result<()> {
+    write_setting(&setting_path(family, interface, name)?, value)
+}
+
+/// Reads the configured reverse path filter of the interface. The kernel applies the maximum of
+/// the value of the interface and of "all", see `effective_rp_filter`.
+pub fn rp_filter(interface: &str) -> Result<RpFilter> {
+    match parse_number(&interface_setting(Family::Ipv4, interface, "rp_filter")?)? {
+        0 => Ok(RpFilter::Off),
+        1 => Ok(RpFilter::Strict),
+        2 => Ok(RpFilter::Loose),
+        value => Err(Error::new(ErrorKind::InvalidData, format!("invalid rp_filter {}", value))),
+    }
+}
+
+/// Returns the reverse path filter the kernel applies to packets received on the interface, the
+/// maximum of the value of the interface and of "all". Multicast sources from other subnets are
+/// dropped in strict mode.
+pub fn effective_rp_filter(interface: &str) -> Result<RpFilter> {
+    Ok(rp_filter(interface)?.max(rp_filter("all")?))
+}
+
+/// Sets the reverse path filter of the interface.
+pub fn set_rp_filter(interface: &str, filter: RpFilter) -> Result<()> {
+    set_interface_setting(Family::Ipv4, interface, "rp_filter", &(filter as u8).to_string())
+}
+
+/// Reads whether packets are forwarded between the interface and other interfaces.
+pub fn forwarding(family: Family, interface: &str) -> Result<bool> {
+    parse_bool(&interface_setting(family, interface, "forwarding")?)
+}
+
+/// Enables or disables forwarding on the interface. For IPv6 forwarding also changes the host/router
+/// behavior of the interface, e.g. router advertisements are no longer accepted.
+pub fn set_forwarding(family: Family, interface: &str, enabled: bool) -> Result<()> {
+    set_interface_setting(family, interface, "forwarding", if enabled { "1" } else { "0" })
+}
+
+/// Reads whether IP forwarding (routing) is enabled globally (None) or on the interface. The
+/// global IPv4 setting is /proc/sys/net/ipv4/ip_forward, the global IPv6 setting the one of "all".
+/// Relays and proxies forwarding unicast traffic between interfaces need it enabled.
+pub fn ip_forwarding(family: Family, interface: Option<&str>) -> Result<bool> {
+    match (family, interface) {
+        (Family::Ipv4, None) => parse_bool(&read_setting(&PathBuf::from(PROC_SYS_NET).join("ipv4").join("ip_forward"))?),
+        (_, interface) => forwarding(family, interface.unwrap_or("all")),
+    }
+}
+
+/// Enables or disables IP forwarding globally (None) or on the interface. Changing the global
+/// setting also changes the setting of all interfaces.
+pub fn set_ip_forwarding(family: Family, interface: Option<&str>, enabled: bool) -> Result<()> {
+    match (family, interface) {
+        (Family::Ipv4, None) => write_setting(&PathBuf::from(PROC_SYS_NET).join("ipv4").join("ip_forward"),
+                                              if enabled { "1" } else { "0" }),
+        (_, interface) => set_forwarding(family, interface.unwrap_or("all"), enabled),
+    }
+}
+
+/// Reads whether multicast routing is active on the interface. The setting is read-only, the
+/// kernel sets it while a multicast routing daemon (e.g. the IGMP proxy) has registered the
+/// interface.
+pub fn mc_forwarding(family: Family, interface: &str) -> Result<bool> {
+    parse_bool(&interface_setting(family, interface, "mc_forwarding")?)
+}
+
+/// Reads the processing of IPv6 router advertisements on the interface.
+pub fn accept_ra(interface: &str) -> Result<AcceptRa> {
+    match parse_number(&interface_setting(Family::Ipv6, interface, "accept_ra")?)? {
+        0 => Ok(AcceptRa::Off),
+        1 => Ok(AcceptRa::On),
+        2 => Ok(AcceptRa::Always),
+        value => Err(Error::new(ErrorKind::InvalidData, format!("invalid accept_ra {}", value))),
+    }
+}
+
+/// Sets the processing of IPv6 router advertisements on the interface.
+pub fn set_accept_ra(interface: &str, accept_ra: AcceptRa) -> Result<()> {
+    set_interface_setting(Family::Ipv6, interface, "accept_ra", &(accept_ra as u8).to_string())
+}
+
+/// Returns the path of a per-interface setting, rejecting names which would leave the directory.
+fn setting_path(family: Family, interface: &str, name: &str) -> Result<PathBuf> {
+    for part in [interface, name].iter() {
+        if part.is_empty() || part.contains('/') || part.starts_with('.') {
+            return Err(Error::new(ErrorKind::InvalidInput, format!("invalid setting path element {:?}", part)));
+        }
+    }
+    // the directories of VLAN interfaces keep the dot, only the sysctl notation replaces it by a slash
+    Ok(PathBuf::from(PROC_SYS_NET).join(family.dir()).join("conf").join(interface).join(name))
+}
+
+fn read_setting(path: &Path) -> Result<String> {
+    let value = std::fs::read_to_string(path).map_err(|e| Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+    Ok(value.trim().to_string())
+}
+
+fn write_setting(path: &Path, value: &str) -> Result<()> {
+    std::fs::write(path, value).map_err(|e| Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+}
+
+fn parse_number(value: &str) -> Result<i64> {
+    value.trim().parse().map_err(|_| Error::new(ErrorKind::InvalidData, format!("invalid number {}", value)))
+}
+
+fn parse_bool(value: &str) -> Result<bool> {
+    Ok(parse_number(value)? != 0)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_setting_path() {
+        assert_eq!(setting_path(Family::Ipv6, "eth0.10", "accept_ra").unwrap(),
+                   PathBuf::from("/proc/sys/net/ipv6/conf/eth0.10/accept_ra"));
+        assert_eq!(setting_path(Family::Ipv4, "../../kernel", "x").unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(setting_path(Family::Ipv4, "eth0", "").unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(parse_bool("1\n").unwrap());
+        assert!(!parse_bool("0").unwrap());
+        assert_eq!(parse_number("-1").unwrap(), -1);
+        assert_eq!(parse_number("on").unwrap_err().kind(), ErrorKind::InvalidData);
+        assert!(RpFilter::Loose > RpFilter::Strict);
+    }
+}
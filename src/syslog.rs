@@ -0,0 +1,225 @@
+//! Syslog (RFC 5424) sender over UDP (RFC 5426) from a socket bound to a source address and/or
+//! interface, e.g. to emit logs only on the management network.
+
+use std::{
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Well known syslog port.
+pub const SYSLOG_PORT: u16 = 514;
+
+/// Message size every receiver must accept for IPv4 (RFC 5426), larger messages may be truncated.
+pub const MIN_MESSAGE_LEN_V4: usize = 480;
+
+/// Facility of a message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Facility {
+    /// kernel messages
+    Kern = 0,
+    /// user-level messages
+    User = 1,
+    /// mail system
+    Mail = 2,
+    /// system daemons
+    Daemon = 3,
+    /// security/authorization messages
+    Auth = 4,
+    /// messages generated internally by syslogd
+    Syslog = 5,
+    /// line printer subsystem
+    Lpr = 6,
+    /// network news subsystem
+    News = 7,
+    /// UUCP subsystem
+    Uucp = 8,
+    /// clock daemon
+    Cron = 9,
+    /// private security/authorization messages
+    AuthPriv = 10,
+    /// FTP daemon
+    Ftp = 11,
+    /// NTP subsystem
+    Ntp = 12,
+    /// log audit
+    Audit = 13,
+    /// log alert
+    Alert = 14,
+    /// clock daemon (note 2)
+    Clock = 15,
+    /// local use 0
+    Local0 = 16,
+    /// local use 1
+    Local1 = 17,
+    /// local use 2
+    Local2 = 18,
+    /// local use 3
+    Local3 = 19,
+    /// local use 4
+    Local4 = 20,
+    /// local use 5
+    Local5 = 21,
+    /// local use 6
+    Local6 = 22,
+    /// local use 7
+    Local7 = 23,
+}
+
+/// Severity of a message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Severity {
+    /// system is unusable
+    Emergency = 0,
+    /// action must be taken immediately
+    Alert = 1,
+    /// critical conditions
+    Critical = 2,
+    /// error conditions
+    Error = 3,
+    /// warning conditions
+    Warning = 4,
+    /// normal but significant condition
+    Notice = 5,
+    /// informational messages
+    Informational = 6,
+    /// debug-level messages
+    Debug = 7,
+}
+
+/// Sends syslog messages to one collector.
+#[derive(Debug)]
+pub struct SyslogSender {
+    socket: UdpSocket,
+    hostname: String,
+    app_name: String,
+    procid: String,
+}
+
+impl SyslogSender {
+
+    /// Creates a sender for the collector whose socket is bound to the source address and to the
+    /// interface (SO_BINDTODEVICE) if given. The HOSTNAME of the messages is the source address
+    /// if given and NILVALUE otherwise, the PROCID is the id of the process.
+    pub fn new(collector: SocketAddr, source: Option<IpAddr>, interface: Option<&str>) -> Result<SyslogSender> {
+        let socket = super::sys_socket::socket(socket2::Domain::for_address(collector), socket2::Type::DGRAM,
+                                          Some(socket2::Protocol::UDP))?;
+        if let Some(interface) = interface {
+            super::device::bind_to_device(&socket, interface, &collector)?;
+        }
+        let local = match (source, collector) {
+            (Some(source), _) if source.is_ipv4() != collector.is_ipv4() => {
+                return Err(Error::new(ErrorKind::InvalidInput,
+                                      format!("source {} is not of the address family of {}", source, collector)));
+            },
+            (Some(source), _) => SocketAddr::new(source, 0),
+            (None, SocketAddr::V4(_)) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+            (None, SocketAddr::V6(_)) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+        };
+        super::sys_socket::bind(&socket, &local.into())?;
+        socket.connect(&collector.into())?;
+        Ok(SyslogSender {
+            socket: socket.into(),
+            hostname: source.map(|source| source.to_string()).unwrap_or_else(|| "-".to_string()),
+            app_name: "-".to_string(),
+            procid: std::process::id().to_string(),
+        })
+    }
+
+    /// Sets the HOSTNAME of the messages (preferably the FQDN), characters other than printable
+    /// ASCII are replaced and it is truncated to 255 characters.
+    pub fn set_hostname(&mut self, hostname: &str) {
+        self.hostname = header_field(hostname, 255);
+    }
+
+    /// Sets the APP-NAME of the messages, truncated to 48 characters.
+    pub fn set_app_name(&mut self, app_name: &str) {
+        self.app_name = header_field(app_name, 48);
+    }
+
+    /// Sets the PROCID of the messages, truncated to 128 characters.
+    pub fn set_procid(&mut self, procid: &str) {
+        self.procid = header_field(procid, 128);
+    }
+
+    /// Returns the local address of the socket.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Sends a message with the current time stamp and an optional MSGID (at most 32 characters)
+    /// in one datagram.
+    pub fn send(&self, facility: Facility, severity: Severity, msgid: Option<&str>, message: &str) -> Result<()> {
+        self.socket.send(&self.format(facility, severity, msgid, message, SystemTime::now()))?;
+        Ok(())
+    }
+
+    /// Returns the message in RFC 5424 format without structured data.
+    fn format(&self, facility: Facility, severity: Severity, msgid: Option<&str>, message: &str,
+              time: SystemTime) -> Vec<u8> {
+        let msgid = match msgid {
+            Some(msgid) => header_field(msgid, 32),
+            None => "-".to_string(),
+        };
+        let mut formatted = format!("<{}>1 {} {} {} {} {} - ", (facility as u8) << 3 | severity as u8,
+                                    timestamp(time), self.hostname, self.app_name, self.procid, msgid).into_bytes();
+        formatted.extend_from_slice(message.as_bytes());
+        formatted
+    }
+}
+
+/// Returns the value as header field: printable ASCII without space, NILVALUE if empty.
+fn header_field(value: &str, max_len: usize) -> String {
+    let field: String = value.chars().take(max_len)
+        .map(|c| if c.is_ascii_graphic() { c } else { '_' })
+        .collect();
+    if field.is_empty() { "-".to_string() } else { field }
+}
+
+/// Formats the time as RFC 3339 UTC time stamp with microseconds, NILVALUE before 1970.
+fn timestamp(time: SystemTime) -> String {
+    let since_epoch = match time.duration_since(UNIX_EPOCH) {
+        Ok(since_epoch) => since_epoch,
+        Err(_) => return "-".to_string(),
+    };
+    let seconds = since_epoch.as_secs();
+    let (days, seconds_of_day) = ((seconds / 86400) as i64, seconds % 86400);
+    // civil from days (H. Hinnant)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z", year, month, day, seconds_of_day / 3600,
+            seconds_of_day / 60 % 60, seconds_of_day % 60, since_epoch.subsec_micros())
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000000Z");
+        assert_eq!(timestamp(UNIX_EPOCH + Duration::from_micros(951_782_400_000_001)), "2000-02-29T00:00:00.000001Z");
+        assert_eq!(timestamp(UNIX_EPOCH + Duration::from_secs(1_791_980_999)), "2026-10-14T12:29:59.000000Z");
+    }
+
+    #[test]
+    fn test_format() {
+        let mut sender = SyslogSender::new("127.0.0.1:514".parse().unwrap(), None, None).unwrap();
+        sender.set_hostname("switch 1.example.com");
+        sender.set_app_name("");
+        sender.set_procid("42");
+        let message = sender.format(Facility::Local4, Severity::Notice, Some("LINK"), "eth0 up",
+                                    UNIX_EPOCH + Duration::from_secs(1));
+        assert_eq!(String::from_utf8(message).unwrap(),
+                   "<165>1 1970-01-01T00:00:01.000000Z switch_1.example.com - 42 LINK - eth0 up");
+    }
+}
@@ -0,0 +1,228 @@
+//! Socket activation: adoption of the sockets systemd (or another service manager implementing
+//! its protocol) passes via LISTEN_FDS and LISTEN_FDNAMES, e.g. to let a daemon without
+//! privileges serve a port below 1024. Each passed socket is checked against the socket the
+//! daemon expects, which it creates itself if it was started without socket activation.
+//! Requires the feature 'systemd'.
+//!
+//! ```no_run
+//! use net_utils::{MulticastSocketBuilder, systemd::ActivatedSockets};
+//! let mut sockets = ActivatedSockets::from_env().unwrap();
+//! let builder = MulticastSocketBuilder::new("239.255.0.1:5000".parse().unwrap(), "192.168.1.2".parse().unwrap());
+//! let socket = sockets.multicast(Some("events"), &builder).unwrap();
+//! ```
+
+use std::{
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, UdpSocket},
+    os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+};
+
+use socket2::{SockRef, Socket};
+
+use super::{MulticastSocketBuilder, sockopt::get_option, tcp::TcpListenerBuilder};
+
+/// First file descriptor passed by socket activation (SD_LISTEN_FDS_START).
+pub const LISTEN_FDS_START: RawFd = 3;
+
+/// A socket passed by socket activation.
+#[derive(Debug)]
+struct PassedSocket {
+    fd: OwnedFd,
+    name: Option<String>,
+}
+
+/// Kind of an expected socket.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    Datagram,
+    Listener,
+}
+
+/// The sockets passed by socket activation which are not claimed yet. The claiming functions
+/// return the socket of the name, or, without name, the first socket matching the expected
+/// kind and local address. They fail with InvalidInput if the socket of the name does not match
+/// and create the socket if there is none. Sockets are returned as passed, i.e. blocking unless
+/// the socket unit sets NonBlocking. Unclaimed sockets are closed on drop.
+#[derive(Debug, Default)]
+pub struct ActivatedSockets {
+    sockets: Vec<PassedSocket>,
+}
+
+impl ActivatedSockets {
+
+    /// Takes the sockets passed to the process by socket activation and removes LISTEN_PID,
+    /// LISTEN_FDS and LISTEN_FDNAMES from the environment, so that child processes do not take
+    /// them again. Returns no sockets if LISTEN_PID is not set or not the process id. Fails with
+    /// InvalidData for malformed variables. Must only be called once, before the process opens
+    /// other files.
+    pub fn from_env() -> Result<ActivatedSockets> {
+        let result = parse_listen_env(std::env::var("LISTEN_PID").ok().as_deref(),
+                                      std::env::var("LISTEN_FDS").ok().as_deref(),
+                                      std::env::var("LISTEN_FDNAMES").ok().as_deref(), std::process::id());
+        for var in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+            std::env::remove_var(var);
+        }
+        let mut sockets = Vec::new();
+        for (fd, name) in (LISTEN_FDS_START..).zip(result?) {
+            if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } != 0 {
+                return Err(Error::last_os_error());
+            }
+            sockets.push(PassedSocket { fd: unsafe { OwnedFd::from_raw_fd(fd) }, name });
+        }
+        Ok(ActivatedSockets { sockets })
+    }
+
+    /// Creates the sockets from file descriptors and their names, e.g. passed by another service
+    /// manager.
+    pub fn from_fds(fds: Vec<(OwnedFd, Option<String>)>) -> ActivatedSockets {
+        ActivatedSockets { sockets: fds.into_iter().map(|(fd, name)| PassedSocket { fd, name }).collect() }
+    }
+
+    /// Returns the number of sockets not claimed yet.
+    pub fn len(&self) -> usize {
+        self.sockets.len()
+    }
+
+    /// Returns whether all sockets are claimed or none was passed.
+    pub fn is_empty(&self) -> bool {
+        self.sockets.is_empty()
+    }
+
+    /// Returns the names of the sockets not claimed yet (FileDescriptorName of the socket unit).
+    pub fn names(&self) -> Vec<&str> {
+        self.sockets.iter().filter_map(|socket| socket.name.as_deref()).collect()
+    }
+
+    /// Returns the passed UDP socket bound to the address or binds a new one.
+    pub fn udp(&mut self, name: Option<&str>, address: SocketAddr) -> Result<UdpSocket> {
+        match self.claim(name, Kind::Datagram, &[address])? {
+            Some(socket) => Ok(socket.into()),
+            None => super::sys_socket::udp_bind(address),
+        }
+    }
+
+    /// Returns the passed UDP socket bound to the group's address or to the unspecified address
+    /// with the group's port, configured by the builder (group membership, interface, TTL etc.,
+    /// but not the options set before binding), or creates the socket with the builder.
+    pub fn multicast(&mut self, name: Option<&str>, builder: &MulticastSocketBuilder) -> Result<UdpSocket> {
+        let bound = builder.bind_address();
+        let unspecified = match bound {
+            SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        match self.claim(name, Kind::Datagram, &[bound, SocketAddr::new(unspecified, bound.port())])? {
+            Some(socket) => {
+                builder.configure_socket(&socket)?;
+                Ok(socket.into())
+            },
+            None => builder.build_std(),
+        }
+    }
+
+    /// Returns the passed listening TCP socket bound to the builder's address, whose options are
+    /// set by the socket unit, or creates the listener with the builder.
+    pub fn tcp_listener(&mut self, name: Option<&str>, builder: &TcpListenerBuilder) -> Result<TcpListener> {
+        match self.claim(name, Kind::Listener, &[builder.address()])? {
+            Some(socket) => Ok(socket.into()),
+            None => builder.build_std(),
+        }
+    }
+
+    /// Removes and returns the socket of the name or the first matching socket.
+    fn claim(&mut self, name: Option<&str>, kind: Kind, addresses: &[SocketAddr]) -> Result<Option<Socket>> {
+        let matches = |socket: &PassedSocket| local_socket(&socket.fd)
+            .is_some_and(|(actual, address)| actual == kind && addresses.contains(&address));
+        let position = match name {
+            Some(name) => self.sockets.iter().position(|socket| socket.name.as_deref() == Some(name)),
+            None => self.sockets.iter().position(matches),
+        };
+        let position = match position {
+            Some(position) => position,
+            None => return Ok(None),
+        };
+        if !matches(&self.sockets[position]) {
+            let socket = &self.sockets[position];
+            return Err(Error::new(ErrorKind::InvalidInput, format!(
+                "passed socket {} ({}) is {}, expected a {} socket on {}", socket.fd.as_raw_fd(),
+                socket.name.as_deref().unwrap_or("unnamed"), describe(&socket.fd), describe_kind(kind), addresses[0])));
+        }
+        let socket = Socket::from(self.sockets.remove(position).fd);
+        trace_event!(crate::trace::TraceEvent::SocketCreated {
+            kind: "systemd-activated", address: socket.local_addr().ok().and_then(|address| address.as_socket()),
+        });
+        Ok(Some(socket))
+    }
+}
+
+/// Returns the kind and local address of an IP socket, None for other sockets and kinds.
+fn local_socket(fd: &OwnedFd) -> Option<(Kind, SocketAddr)> {
+    let address = SockRef::from(fd).local_addr().ok()?.as_socket()?;
+    let kind = match get_option::<libc::c_int>(fd.as_raw_fd(), libc::SOL_SOCKET, libc::SO_TYPE).ok()? {
+        libc::SOCK_DGRAM => Kind::Datagram,
+        libc::SOCK_STREAM if get_option::<libc::c_int>(fd.as_raw_fd(), libc::SOL_SOCKET, libc::SO_ACCEPTCONN).ok()? != 0 => {
+            Kind::Listener
+        },
+        _ => return None,
+    };
+    Some((kind, address))
+}
+
+fn describe(fd: &OwnedFd) -> String {
+    match local_socket(fd) {
+        Some((kind, address)) => format!("a {} socket on {}", describe_kind(kind), address),
+        None => "no UDP or listening TCP socket".to_string(),
+    }
+}
+
+fn describe_kind(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Datagram => "datagram",
+        Kind::Listener => "listening stream",
+    }
+}
+
+/// Returns the names of the passed file descriptors of the LISTEN_PID, LISTEN_FDS and
+/// LISTEN_FDNAMES variables, none if they are not set or for another process.
+fn parse_listen_env(pid: Option<&str>, fds: Option<&str>, names: Option<&str>, own_pid: u32) -> Result<Vec<Option<String>>> {
+    let invalid = |var: &str, value: &str| Error::new(ErrorKind::InvalidData, format!("invalid {}: {}", var, value));
+    let (pid, fds) = match (pid, fds) {
+        (Some(pid), Some(fds)) => (pid, fds),
+        _ => return Ok(Vec::new()),
+    };
+    if pid.parse::<u32>().map_err(|_| invalid("LISTEN_PID", pid))? != own_pid {
+        return Ok(Vec::new());
+    }
+    let count = fds.parse::<usize>().map_err(|_| invalid("LISTEN_FDS", fds))?;
+    if count > (RawFd::MAX - LISTEN_FDS_START) as usize {
+        return Err(invalid("LISTEN_FDS", fds));
+    }
+    match names {
+        Some(names) => {
+            let names: Vec<_> = names.split(':').map(|name| Some(name.to_string()).filter(|name| !name.is_empty())).collect();
+            if names.len() != count {
+                return Err(invalid("LISTEN_FDNAMES", "number of names does not match LISTEN_FDS"));
+            }
+            Ok(names)
+        },
+        None => Ok(vec![None; count]),
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_parse_listen_env() {
+        assert_eq!(parse_listen_env(Some("42"), Some("2"), Some("dns:"), 42).unwrap(), vec![Some("dns".to_string()), None]);
+        assert_eq!(parse_listen_env(Some("42"), Some("1"), None, 42).unwrap(), vec![None]);
+        assert!(parse_listen_env(Some("43"), Some("1"), None, 42).unwrap().is_empty());
+        assert!(parse_listen_env(None, Some("1"), None, 42).unwrap().is_empty());
+        assert!(parse_listen_env(Some("42"), Some("0"), None, 42).unwrap().is_empty());
+        for (pid, fds, names) in [("x", "1", None), ("42", "-1", None), ("42", "2", Some("dns"))] {
+            let error = parse_listen_env(Some(pid), Some(fds), names, 42).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+        }
+    }
+}
@@ -0,0 +1,521 @@
+//! TCP socket helpers: listener creation with advanced socket options, connection setup with
+//! egress selection, Happy Eyeballs dual-stack connect, keepalive configuration.
+
+use std::{
+    io::Result,
+    net::SocketAddr,
+    time::Duration,
+};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::os::unix::io::AsRawFd;
+#[cfg(unix)]
+use std::os::unix::io::AsFd;
+#[cfg(windows)]
+use std::os::windows::io::AsSocket;
+
+use socket2::{Domain, Protocol, SockAddr, SockRef, Socket, Type, TcpKeepalive};
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use super::sockopt::{get_option, set_int_option};
+
+use super::device::bind_to_device;
+
+/// Default length of the queue of pending connections.
+pub const DEFAULT_BACKLOG: i32 = 128;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const IPPROTO_MPTCP: libc::c_int = 262;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const SOL_MPTCP: libc::c_int = 284;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const MPTCP_INFO: libc::c_int = 1;
+
+/// Builder for TCP listeners with socket options that have to be set before bind/listen.
+/// ```no_run
+/// use net_utils::tcp::TcpListenerBuilder;
+/// let listener = TcpListenerBuilder::new("[::]:8080".parse().unwrap())
+///     .reuse_port(true)
+///     .only_v6(false)
+///     .backlog(1024)
+///     .build_std()
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TcpListenerBuilder {
+    address: SocketAddr,
+    reuse_address: bool,
+    #[cfg(unix)]
+    reuse_port: bool,
+    backlog: i32,
+    only_v6: Option<bool>,
+    mptcp: bool,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    device: Option<String>,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    defer_accept: Option<u32>,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fast_open_queue: Option<u32>,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    freebind: bool,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    transparent: bool,
+}
+
+impl TcpListenerBuilder {
+
+    /// Creates a builder for a listener on the given local address. SO_REUSEADDR is enabled on
+    /// unix systems by default (like std::net::TcpListener::bind does), the backlog is
+    /// DEFAULT_BACKLOG.
+    pub fn new(address: SocketAddr) -> TcpListenerBuilder {
+        TcpListenerBuilder {
+            address,
+            reuse_address: cfg!(unix),
+            #[cfg(unix)]
+            reuse_port: false,
+            backlog: DEFAULT_BACKLOG,
+            only_v6: None,
+            mptcp: false,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            device: None,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            defer_accept: None,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            fast_open_queue: None,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            freebind: false,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            transparent: false,
+        }
+    }
+
+    /// Sets SO_REUSEADDR.
+    pub fn reuse_address(mut self, reuse: bool) -> Self {
+        self.reuse_address = reuse;
+        self
+    }
+
+    /// Sets SO_REUSEPORT which allows multiple listeners (e.g. one per thread) on the same port.
+    #[cfg(unix)]
+    pub fn reuse_port(mut self, reuse: bool) -> Self {
+        self.reuse_port = reuse;
+        self
+    }
+
+    /// Sets the length of the queue of pending connections.
+    pub fn backlog(mut self, backlog: i32) -> Self {
+        self.backlog = backlog;
+        self
+    }
+
+    /// Sets IPV6_V6ONLY for IPv6 listeners. If not set the system default applies, which differs
+    /// between systems (net.ipv6.bindv6only on linux). Ignored for IPv4 addresses.
+    pub fn only_v6(mut self, only_v6: bool) -> Self {
+        self.only_v6 = Some(only_v6);
+        self
+    }
+
+    /// Creates a Multipath TCP (IPPROTO_MPTCP) listener. On systems or kernels without MPTCP
+    /// support a regular TCP listener is created, clients without MPTCP support are served via
+    /// plain TCP by the kernel anyway.
+    pub fn mptcp(mut self, mptcp: bool) -> Self {
+        self.mptcp = mptcp;
+        self
+    }
+
+    /// Binds the listener to the network device with the given name (SO_BINDTODEVICE), so that
+    /// only connections received via this interface are accepted. Requires CAP_NET_RAW on older
+    /// kernels.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn bind_device(mut self, device: &str) -> Self {
+        self.device = Some(String::from(device));
+        self
+    }
+
+    /// Sets TCP_DEFER_ACCEPT: connections are only accepted when data arrived, the value is the
+    /// number of seconds to wait for data.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn defer_accept(mut self, seconds: u32) -> Self {
+        self.defer_accept = Some(seconds);
+        self
+    }
+
+    /// Enables TCP Fast Open (TCP_FASTOPEN) with the given maximum queue length of pending
+    /// fast open requests.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn fast_open(mut self, queue_length: u32) -> Self {
+        self.fast_open_queue = Some(queue_length);
+        self
+    }
+
+    /// Sets IP_FREEBIND (IPV6_FREEBIND) which allows binding to an address that is not (yet)
+    /// assigned to an interface, e.g. the virtual address of a VRRP backup or an IPv6 address
+    /// during duplicate address detection.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn freebind(mut self, freebind: bool) -> Self {
+        self.freebind = freebind;
+        self
+    }
+
+    /// Sets IP_TRANSPARENT (IPV6_TRANSPARENT) for transparent proxies, which accept connections
+    /// to foreign addresses redirected by TPROXY rules. Requires CAP_NET_ADMIN.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn transparent(mut self, transparent: bool) -> Self {
+        self.transparent = transparent;
+        self
+    }
+
+    /// Returns the local address of the listener.
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Returns the device the listener is bound to.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn device(&self) -> Option<&str> {
+        self.device.as_deref()
+    }
+
+    /// Returns whether IP_TRANSPARENT is set.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn is_transparent(&self) -> bool {
+        self.transparent
+    }
+
+    /// Creates a bound and listening std::net::TcpListener.
+    pub fn build_std(&self) -> Result<std::net::TcpListener> {
+        Ok(self.build_socket()?.into())
+    }
+
+    /// Creates a bound and listening tokio::net::TcpListener.
+    /// Requires the feature 'tokio-net'.
+    #[cfg(feature = "tokio-net")]
+    pub fn build_tokio(&self) -> Result<tokio::net::TcpListener> {
+        let listener = self.build_std()?;
+        listener.set_nonblocking(true)?;
+        tokio::net::TcpListener::from_std(listener)
+    }
+
+    fn build_socket(&self) -> Result<Socket> {
+        trace_span!("tcp_listener", address = %self.address);
+        let socket = new_stream_socket(&self.address, self.mptcp)?;
+        socket.set_reuse_address(self.reuse_address)?;
+        #[cfg(unix)]
+        if self.reuse_port {
+            socket.set_reuse_port(true)?;
+        }
+        if let (Some(only_v6), SocketAddr::V6(_)) = (self.only_v6, self.address) {
+            socket.set_only_v6(only_v6)?;
+        }
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        self.set_linux_options(&socket)?;
+        super::sys_socket::bind(&socket, &SockAddr::from(self.address))?;
+        super::sys_socket::listen(&socket, self.backlog)?;
+        trace_event!(crate::trace::TraceEvent::SocketCreated {
+            kind: "tcp-listener", address: socket.local_addr().ok().and_then(|address| address.as_socket()),
+        });
+        Ok(socket)
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn set_linux_options(&self, socket: &Socket) -> Result<()> {
+        if let Some(device) = &self.device {
+            socket.bind_device(Some(device.as_bytes()))?;
+        }
+        if let Some(seconds) = self.defer_accept {
+            set_int_option(socket.as_raw_fd(), libc::IPPROTO_TCP, libc::TCP_DEFER_ACCEPT, seconds as libc::c_int)?;
+        }
+        if let Some(queue_length) = self.fast_open_queue {
+            set_int_option(socket.as_raw_fd(), libc::IPPROTO_TCP, libc::TCP_FASTOPEN, queue_length as libc::c_int)?;
+        }
+        let (level, freebind, transparent) = match self.address {
+            SocketAddr::V4(_) => (libc::IPPROTO_IP, libc::IP_FREEBIND, libc::IP_TRANSPARENT),
+            SocketAddr::V6(_) => (libc::IPPROTO_IPV6, libc::IPV6_FREEBIND, libc::IPV6_TRANSPARENT),
+        };
+        if self.freebind {
+            set_int_option(socket.as_raw_fd(), level, freebind, 1)?;
+        }
+        if self.transparent {
+            set_int_option(socket.as_raw_fd(), level, transparent, 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Options for `connect_via`. The default options connect without timeout via the interface
+/// and source address chosen by the routing table.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ConnectOpts {
+    /// maximum time to wait for the connection to be established, None to wait for the system
+    /// connect timeout
+    pub timeout: Option<Duration>,
+
+    /// name of the network device the connection is bound to (SO_BINDTODEVICE on linux,
+    /// IP_BOUND_IF/IPV6_BOUND_IF on apple systems), not supported on other systems
+    pub device: Option<String>,
+
+    /// local address (and port, 0 for any) the socket is bound to before connecting
+    pub source_addr: Option<SocketAddr>,
+
+    /// create a Multipath TCP socket, falls back to TCP if the system does not support MPTCP
+    pub mptcp: bool,
+}
+
+/// Connects a TCP stream to the destination, binding it to the device and source address of the
+/// options first. With a timeout the connect is performed non-blocking and fails with an error of
+/// kind TimedOut if the connection is not established in time.
+pub fn connect_via(destination: SocketAddr, opts: &ConnectOpts) -> Result<std::net::TcpStream> {
+    trace_span!("tcp_connect", %destination);
+    let socket = new_stream_socket(&destination, opts.mptcp)?;
+    if let Some(device) = &opts.device {
+        bind_to_device(&socket, device, &destination)?;
+    }
+    if let Some(source) = opts.source_addr {
+        if source.is_ipv4() != destination.is_ipv4() {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput,
+                format!("source address {} and destination {} differ in address family", source, destination)));
+        }
+        super::sys_socket::bind(&socket, &SockAddr::from(source))?;
+    }
+    match opts.timeout {
+        Some(timeout) => socket.connect_timeout(&SockAddr::from(destination), timeout)?,
+        None => socket.connect(&SockAddr::from(destination))?,
+    }
+    Ok(socket.into())
+}
+
+/// Returns whether the connection uses Multipath TCP, i.e. it was created as MPTCP socket and did
+/// not fall back to plain TCP because the peer (or a middlebox) does not support MPTCP.
+/// Always false on systems other than linux and android.
+#[cfg(unix)]
+pub fn is_mptcp<S: AsFd>(socket: &S) -> Result<bool> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        let fd = socket.as_fd().as_raw_fd();
+        let protocol: libc::c_int = get_option(fd, libc::SOL_SOCKET, libc::SO_PROTOCOL)?;
+        if protocol != IPPROTO_MPTCP {
+            return Ok(false);
+        }
+        // MPTCP_INFO fails with EOPNOTSUPP after a fallback to TCP
+        match get_option::<[u8; 256]>(fd, SOL_MPTCP, MPTCP_INFO) {
+            Ok(_) => Ok(true),
+            Err(e) if e.raw_os_error() == Some(libc::EOPNOTSUPP) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    {
+        let _ = socket;
+        Ok(false)
+    }
+}
+
+/// Returns whether the connection uses Multipath TCP, always false on windows.
+#[cfg(windows)]
+pub fn is_mptcp<S: AsSocket>(_socket: &S) -> Result<bool> {
+    Ok(false)
+}
+
+/// Creates a TCP or, if requested and supported, MPTCP stream socket for the address' family.
+fn new_stream_socket(address: &SocketAddr, mptcp: bool) -> Result<Socket> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if mptcp {
+        let protocol = Protocol::from(IPPROTO_MPTCP);
+        match super::sys_socket::socket(Domain::for_address(*address), Type::STREAM, Some(protocol)) {
+            Ok(socket) => return Ok(socket),
+            // kernel without MPTCP support or MPTCP disabled (net.mptcp.enabled)
+            Err(e) if matches!(e.raw_os_error(), Some(libc::EPROTONOSUPPORT) | Some(libc::EINVAL)
+                | Some(libc::ENOPROTOOPT)) => {},
+            Err(e) => return Err(e),
+        }
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    let _ = mptcp;
+    super::sys_socket::socket(Domain::for_address(*address), Type::STREAM, Some(Protocol::TCP))
+}
+
+/// Delay between the start of two connection attempts of `connect_happy_eyeballs`, the value
+/// recommended by RFC 8305.
+pub const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Resolves the host and connects to it as described by Happy Eyeballs (RFC 8305): the resolved
+/// addresses are ordered alternating between IPv6 and IPv4 and a new connection attempt is started
+/// whenever the previous one failed or did not succeed within CONNECTION_ATTEMPT_DELAY. The first
+/// established connection is returned, all other attempts are cancelled.
+/// IPv6 addresses are tried first unless the host has no IPv6 address beyond link-local scope.
+/// Requires the feature 'tokio-net' and must be called within a tokio runtime.
+#[cfg(feature = "tokio-net")]
+pub async fn connect_happy_eyeballs(host: &str, port: u16) -> Result<tokio::net::TcpStream> {
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host, port)).await?.collect();
+    connect_candidates(sort_candidates(addresses, prefer_ipv6()), CONNECTION_ATTEMPT_DELAY).await
+}
+
+#[cfg(feature = "tokio-net")]
+async fn connect_candidates(candidates: Vec<SocketAddr>, delay: Duration) -> Result<tokio::net::TcpStream> {
+    let mut pending = candidates.into_iter();
+    let mut attempts = tokio::task::JoinSet::new();
+    let mut last_error = None;
+    loop {
+        if attempts.is_empty() {
+            match pending.next() {
+                Some(address) => { attempts.spawn(connect_attempt(address)); },
+                None => return Err(last_error.unwrap_or_else(|| std::io::Error::new(
+                    std::io::ErrorKind::NotFound, "host name did not resolve to any address"))),
+            }
+        }
+        tokio::select! {
+            Some(result) = attempts.join_next() => match result {
+                Ok(Ok(stream)) => return Ok(stream),
+                Ok(Err(e)) => {
+                    last_error = Some(e);
+                    if let Some(address) = pending.next() {
+                        attempts.spawn(connect_attempt(address));
+                    }
+                },
+                Err(e) => last_error = Some(std::io::Error::other(e)),
+            },
+            _ = tokio::time::sleep(delay), if pending.len() > 0 => {
+                if let Some(address) = pending.next() {
+                    attempts.spawn(connect_attempt(address));
+                }
+            },
+        }
+    }
+}
+
+/// Returns a connection attempt to the address, the socket is created on the calling thread.
+#[cfg(feature = "tokio-net")]
+fn connect_attempt(address: SocketAddr) -> impl std::future::Future<Output = Result<tokio::net::TcpStream>> {
+    let socket = new_stream_socket(&address, false).and_then(|socket| {
+        socket.set_nonblocking(true)?;
+        Ok(tokio::net::TcpSocket::from_std_stream(socket.into()))
+    });
+    async move { socket?.connect(address).await }
+}
+
+/// Orders the addresses alternating between the address families (RFC 8305 section 4), starting
+/// with IPv6 if preferred. The resolver order within each family is kept.
+#[cfg(any(test, feature = "tokio-net"))]
+fn sort_candidates(addresses: Vec<SocketAddr>, prefer_ipv6: bool) -> Vec<SocketAddr> {
+    let (v6, v4): (Vec<SocketAddr>, Vec<SocketAddr>) = addresses.into_iter().partition(|a| a.is_ipv6());
+    let (mut first, mut second) = if prefer_ipv6 { (v6.into_iter(), v4.into_iter()) } else { (v4.into_iter(), v6.into_iter()) };
+    let mut sorted = Vec::with_capacity(first.len() + second.len());
+    loop {
+        match (first.next(), second.next()) {
+            (None, None) => return sorted,
+            (a, b) => sorted.extend(a.into_iter().chain(b)),
+        }
+    }
+}
+
+/// Returns whether the host has an IPv6 address that is usable beyond the local link.
+#[cfg(all(unix, feature = "tokio-net"))]
+fn prefer_ipv6() -> bool {
+    use super::{AddressScope, InterfaceCache, IpInterfaceIterExt};
+    match InterfaceCache::global().interfaces() {
+        Ok(interfaces) => interfaces.iter().up().ipv6().any(|i| i.scope() >= AddressScope::Private),
+        Err(_) => true,
+    }
+}
+
+#[cfg(all(not(unix), feature = "tokio-net"))]
+fn prefer_ipv6() -> bool {
+    true
+}
+
+/// TCP keepalive parameters applied by `set_keepalive`. Parameters that are None keep the
+/// system defaults (net.ipv4.tcp_keepalive_* on linux).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct KeepaliveConfig {
+    /// idle time before the first keepalive probe is sent (TCP_KEEPIDLE, TCP_KEEPALIVE on apple)
+    pub idle: Option<Duration>,
+
+    /// time between keepalive probes (TCP_KEEPINTVL)
+    pub interval: Option<Duration>,
+
+    /// number of unanswered probes before the connection is dropped (TCP_KEEPCNT),
+    /// not supported on windows
+    pub count: Option<u32>,
+
+    /// maximum time transmitted data may remain unacknowledged before the connection is
+    /// dropped (TCP_USER_TIMEOUT), linux and android only
+    pub user_timeout: Option<Duration>,
+}
+
+/// Enables TCP keepalive (SO_KEEPALIVE) on the socket and applies the parameters of the config.
+/// Works for all sockets like std::net::TcpStream or tokio::net::TcpStream. Returns an error of
+/// kind Unsupported if a parameter is set that the platform does not support.
+#[cfg(unix)]
+pub fn set_keepalive<S: AsFd>(socket: &S, config: &KeepaliveConfig) -> Result<()> {
+    apply_keepalive(SockRef::from(socket), config)
+}
+
+/// Enables TCP keepalive (SO_KEEPALIVE) on the socket and applies the parameters of the config.
+/// Works for all sockets like std::net::TcpStream or tokio::net::TcpStream. Returns an error of
+/// kind Unsupported if a parameter is set that the platform does not support.
+#[cfg(windows)]
+pub fn set_keepalive<S: AsSocket>(socket: &S, config: &KeepaliveConfig) -> Result<()> {
+    apply_keepalive(SockRef::from(socket), config)
+}
+
+/// Disables TCP keepalive (SO_KEEPALIVE) on the socket.
+#[cfg(unix)]
+pub fn clear_keepalive<S: AsFd>(socket: &S) -> Result<()> {
+    SockRef::from(socket).set_keepalive(false)
+}
+
+/// Disables TCP keepalive (SO_KEEPALIVE) on the socket.
+#[cfg(windows)]
+pub fn clear_keepalive<S: AsSocket>(socket: &S) -> Result<()> {
+    SockRef::from(socket).set_keepalive(false)
+}
+
+fn apply_keepalive(socket: SockRef<'_>, config: &KeepaliveConfig) -> Result<()> {
+    let mut keepalive = TcpKeepalive::new();
+    if let Some(idle) = config.idle {
+        #[cfg(target_os = "openbsd")]
+        return Err(unsupported("keepalive idle time", idle));
+        #[cfg(not(target_os = "openbsd"))]
+        { keepalive = keepalive.with_time(idle); }
+    }
+    if let Some(interval) = config.interval {
+        #[cfg(target_os = "openbsd")]
+        return Err(unsupported("keepalive interval", interval));
+        #[cfg(not(target_os = "openbsd"))]
+        { keepalive = keepalive.with_interval(interval); }
+    }
+    if let Some(count) = config.count {
+        #[cfg(any(windows, target_os = "openbsd"))]
+        return Err(unsupported("keepalive probe count", count));
+        #[cfg(not(any(windows, target_os = "openbsd")))]
+        { keepalive = keepalive.with_retries(count); }
+    }
+    if let Some(timeout) = config.user_timeout {
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        return Err(unsupported("user timeout", timeout));
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        socket.set_tcp_user_timeout(Some(timeout))?;
+    }
+    socket.set_tcp_keepalive(&keepalive)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn unsupported<T: std::fmt::Debug>(parameter: &str, value: T) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Unsupported, format!("TCP {} ({:?}) is not supported on this platform", parameter, value))
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_sort_candidates() {
+        let addresses: Vec<SocketAddr> = ["10.0.0.1:80", "10.0.0.2:80", "[2001:db8::1]:80", "10.0.0.3:80",
+            "[2001:db8::2]:80"].iter().map(|a| a.parse().unwrap()).collect();
+        let sorted: Vec<String> = sort_candidates(addresses.clone(), true).iter().map(|a| a.to_string()).collect();
+        assert_eq!(sorted, vec!["[2001:db8::1]:80", "10.0.0.1:80", "[2001:db8::2]:80", "10.0.0.2:80", "10.0.0.3:80"]);
+        let sorted: Vec<String> = sort_candidates(addresses, false).iter().map(|a| a.to_string()).collect();
+        assert_eq!(sorted, vec!["10.0.0.1:80", "[2001:db8::1]:80", "10.0.0.2:80", "[2001:db8::2]:80", "10.0.0.3:80"]);
+        assert!(sort_candidates(Vec::new(), true).is_empty());
+    }
+}
@@ -0,0 +1,304 @@
+//! TFTP (RFC 1350) client for reading and writing files in octet mode with block size negotiation
+//! (RFC 2347, RFC 2348), e.g. for provisioning and firmware updates of embedded devices.
+
+use std::{
+    io::{Error, ErrorKind, Read, Result, Write},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+/// Well known TFTP port.
+pub const TFTP_PORT: u16 = 69;
+
+/// Block size of transfers without negotiation.
+pub const DEFAULT_BLOCK_SIZE: u16 = 512;
+
+/// Smallest block size that can be negotiated.
+pub const MIN_BLOCK_SIZE: u16 = 8;
+
+/// Largest block size that can be negotiated.
+pub const MAX_BLOCK_SIZE: u16 = 65464;
+
+const OPCODE_RRQ: u16 = 1;
+const OPCODE_WRQ: u16 = 2;
+const OPCODE_DATA: u16 = 3;
+const OPCODE_ACK: u16 = 4;
+const OPCODE_ERROR: u16 = 5;
+const OPCODE_OACK: u16 = 6;
+
+/// Error code of packets from an unknown transfer id.
+const ERROR_UNKNOWN_TID: u16 = 5;
+
+/// Options of a transfer.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TftpOpts {
+    /// block size requested from the server, None for DEFAULT_BLOCK_SIZE without negotiation
+    pub block_size: Option<u16>,
+
+    /// time to wait for a packet before the last packet is retransmitted
+    pub timeout: Duration,
+
+    /// number of retransmissions before the transfer fails with TimedOut
+    pub retries: u32,
+
+    /// interface the socket is bound to (SO_BINDTODEVICE)
+    pub interface: Option<String>,
+}
+
+impl Default for TftpOpts {
+    fn default() -> Self {
+        TftpOpts { block_size: Some(1428), timeout: Duration::from_secs(1), retries: 5, interface: None }
+    }
+}
+
+/// Reads the file from the server and writes its content to `output`. Returns the number of bytes
+/// transferred. Error packets of the server fail with NotFound, PermissionDenied, AlreadyExists
+/// or Other for the remaining codes.
+pub fn get<W: Write>(server: SocketAddr, filename: &str, output: &mut W, opts: &TftpOpts) -> Result<u64> {
+    let mut transfer = Transfer::new(server, opts)?;
+    let mut packet = transfer.request(OPCODE_RRQ, filename)?;
+    let (mut expected, mut total) = (1_u16, 0_u64);
+    loop {
+        let reply = transfer.exchange(&packet, |reply| {
+            matches!(opcode(reply), OPCODE_DATA | OPCODE_OACK | OPCODE_ERROR)
+        })?;
+        match opcode(&reply) {
+            OPCODE_OACK if expected == 1 && total == 0 => {
+                transfer.accept_options(&reply)?;
+                packet = ack(0);
+            },
+            OPCODE_DATA if block(&reply) == expected => {
+                let data = &reply[4..];
+                if data.len() > transfer.block_size as usize {
+                    return Err(transfer.abort(4, "block larger than the block size"));
+                }
+                output.write_all(data)?;
+                total += data.len() as u64;
+                packet = ack(expected);
+                if data.len() < transfer.block_size as usize {
+                    transfer.send(&packet)?;
+                    return Ok(total);
+                }
+                expected = expected.wrapping_add(1);
+            },
+            // a duplicate of the previous block, the ack is retransmitted
+            OPCODE_DATA if block(&reply) == expected.wrapping_sub(1) => (),
+            OPCODE_ERROR => return Err(server_error(&reply)),
+            _ => return Err(transfer.abort(4, "unexpected packet")),
+        }
+    }
+}
+
+/// Writes the content of `input` to the file on the server. Returns the number of bytes
+/// transferred. Error packets of the server fail like for `get`.
+pub fn put<R: Read>(server: SocketAddr, filename: &str, input: &mut R, opts: &TftpOpts) -> Result<u64> {
+    let mut transfer = Transfer::new(server, opts)?;
+    let mut packet = transfer.request(OPCODE_WRQ, filename)?;
+    let (mut sent, mut total, mut last) = (0_u16, 0_u64, false);
+    loop {
+        let reply = transfer.exchange(&packet, |reply| {
+            matches!(opcode(reply), OPCODE_ACK | OPCODE_OACK | OPCODE_ERROR)
+        })?;
+        match opcode(&reply) {
+            OPCODE_OACK if sent == 0 => transfer.accept_options(&reply)?,
+            OPCODE_ACK if block(&reply) == sent => (),
+            // an ack of the previous block, the current block is retransmitted
+            OPCODE_ACK if block(&reply) == sent.wrapping_sub(1) => continue,
+            OPCODE_ERROR => return Err(server_error(&reply)),
+            _ => return Err(transfer.abort(4, "unexpected packet")),
+        }
+        if last {
+            return Ok(total);
+        }
+        sent = sent.wrapping_add(1);
+        packet = vec![0, OPCODE_DATA as u8];
+        packet.extend_from_slice(&sent.to_be_bytes());
+        let len = read_block(input, &mut packet, transfer.block_size as usize)?;
+        total += len as u64;
+        last = len < transfer.block_size as usize;
+    }
+}
+
+/// State of a transfer: the socket, the server's transfer id and the negotiated block size.
+struct Transfer<'a> {
+    socket: UdpSocket,
+    server: SocketAddr,
+    peer: Option<SocketAddr>,
+    block_size: u16,
+    opts: &'a TftpOpts,
+}
+
+impl Transfer<'_> {
+
+    fn new(server: SocketAddr, opts: &TftpOpts) -> Result<Transfer<'_>> {
+        if let Some(block_size) = opts.block_size {
+            if !(MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE).contains(&block_size) {
+                return Err(Error::new(ErrorKind::InvalidInput, format!("invalid block size {}", block_size)));
+            }
+        }
+        let socket = super::sys_socket::socket(socket2::Domain::for_address(server), socket2::Type::DGRAM,
+                                          Some(socket2::Protocol::UDP))?;
+        if let Some(interface) = &opts.interface {
+            super::device::bind_to_device(&socket, interface, &server)?;
+        }
+        let local: SocketAddr = match server {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        super::sys_socket::bind(&socket, &local.into())?;
+        Ok(Transfer { socket: socket.into(), server, peer: None, block_size: DEFAULT_BLOCK_SIZE, opts })
+    }
+
+    /// Returns the read or write request for the file in octet mode with the block size option.
+    fn request(&self, opcode: u16, filename: &str) -> Result<Vec<u8>> {
+        if filename.is_empty() || filename.contains('\0') {
+            return Err(Error::new(ErrorKind::InvalidInput, "empty filename or filename with NUL"));
+        }
+        let mut packet = opcode.to_be_bytes().to_vec();
+        packet.extend_from_slice(filename.as_bytes());
+        packet.extend_from_slice(b"\0octet\0");
+        if let Some(block_size) = self.opts.block_size {
+            packet.extend_from_slice(format!("blksize\0{}\0", block_size).as_bytes());
+        }
+        Ok(packet)
+    }
+
+    /// Sets the block size acknowledged by the server in an option acknowledgement.
+    fn accept_options(&mut self, oack: &[u8]) -> Result<()> {
+        let fields: Vec<&[u8]> = oack[2..].split(|b| *b == 0).collect();
+        for option in fields.chunks_exact(2) {
+            if !option[0].eq_ignore_ascii_case(b"blksize") {
+                return Err(self.abort(8, "unrequested option"));
+            }
+            let block_size = std::str::from_utf8(option[1]).ok().and_then(|value| value.parse::<u16>().ok());
+            match (block_size, self.opts.block_size) {
+                (Some(block_size), Some(requested)) if (MIN_BLOCK_SIZE..=requested).contains(&block_size) => {
+                    self.block_size = block_size;
+                },
+                _ => return Err(self.abort(8, "invalid block size")),
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends the packet to the server and waits for a packet accepted by the predicate,
+    /// retransmitting it on timeout.
+    fn exchange<F>(&mut self, packet: &[u8], accept: F) -> Result<Vec<u8>> where F: Fn(&[u8]) -> bool {
+        let mut buffer = vec![0_u8; 4 + MAX_BLOCK_SIZE as usize];
+        for _ in 0..=self.opts.retries {
+            self.send(packet)?;
+            let deadline = Instant::now() + self.opts.timeout;
+            loop {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    break;
+                }
+                self.socket.set_read_timeout(Some(remaining))?;
+                let (len, from) = match self.socket.recv_from(&mut buffer) {
+                    Ok(received) => received,
+                    Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => break,
+                    Err(e) => return Err(e),
+                };
+                match self.peer {
+                    // the server answers from a new port, its transfer id
+                    None if from.ip() == self.server.ip() => self.peer = Some(from),
+                    Some(peer) if peer == from => (),
+                    _ => {
+                        let _ = self.socket.send_to(&error(ERROR_UNKNOWN_TID, "unknown transfer id"), from);
+                        continue;
+                    },
+                }
+                if len >= 4 && accept(&buffer[..len]) {
+                    return Ok(buffer[..len].to_vec());
+                }
+            }
+        }
+        Err(Error::new(ErrorKind::TimedOut, format!("TFTP server {} did not answer", self.server)))
+    }
+
+    fn send(&self, packet: &[u8]) -> Result<()> {
+        self.socket.send_to(packet, self.peer.unwrap_or(self.server))?;
+        Ok(())
+    }
+
+    /// Sends an error packet to the server and returns the error the transfer fails with.
+    fn abort(&self, code: u16, message: &str) -> Error {
+        let _ = self.send(&error(code, message));
+        Error::new(ErrorKind::InvalidData, format!("TFTP transfer aborted: {}", message))
+    }
+}
+
+fn opcode(packet: &[u8]) -> u16 {
+    u16::from_be_bytes([packet[0], packet[1]])
+}
+
+fn block(packet: &[u8]) -> u16 {
+    u16::from_be_bytes([packet[2], packet[3]])
+}
+
+fn ack(block: u16) -> Vec<u8> {
+    let mut packet = OPCODE_ACK.to_be_bytes().to_vec();
+    packet.extend_from_slice(&block.to_be_bytes());
+    packet
+}
+
+fn error(code: u16, message: &str) -> Vec<u8> {
+    let mut packet = OPCODE_ERROR.to_be_bytes().to_vec();
+    packet.extend_from_slice(&code.to_be_bytes());
+    packet.extend_from_slice(message.as_bytes());
+    packet.push(0);
+    packet
+}
+
+/// Returns the error for an error packet of the server.
+fn server_error(packet: &[u8]) -> Error {
+    let message = packet[4..].split(|b| *b == 0).next().unwrap_or_default();
+    let message = format!("TFTP error {}: {}", block(packet), String::from_utf8_lossy(message));
+    let kind = match block(packet) {
+        1 => ErrorKind::NotFound,
+        2 => ErrorKind::PermissionDenied,
+        6 => ErrorKind::AlreadyExists,
+        _ => ErrorKind::Other,
+    };
+    Error::new(kind, message)
+}
+
+/// Appends up to `len` bytes of the input to the packet, fewer only at the end of the input.
+fn read_block<R: Read>(input: &mut R, packet: &mut Vec<u8>, len: usize) -> Result<usize> {
+    let start = packet.len();
+    packet.resize(start + len, 0);
+    let mut filled = 0;
+    while filled < len {
+        match input.read(&mut packet[start + filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    packet.truncate(start + filled);
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_packets() {
+        let opts = TftpOpts::default();
+        let mut transfer = Transfer::new("127.0.0.1:69".parse().unwrap(), &opts).unwrap();
+        assert_eq!(transfer.request(OPCODE_RRQ, "boot.bin").unwrap(), b"\0\x01boot.bin\0octet\0blksize\x001428\0");
+        assert!(transfer.request(OPCODE_WRQ, "").is_err());
+
+        transfer.accept_options(b"\0\x06BLKSIZE\x001024\0").unwrap();
+        assert_eq!(transfer.block_size, 1024);
+        assert!(transfer.accept_options(b"\0\x06blksize\x002048\0").is_err());
+        assert!(transfer.accept_options(b"\0\x06tsize\x0010\0").is_err());
+
+        let error = server_error(&error(1, "no such file"));
+        assert_eq!(error.kind(), ErrorKind::NotFound);
+        assert_eq!(error.to_string(), "TFTP error 1: no such file");
+    }
+}
@@ -0,0 +1,106 @@
+//! Packet timestamping: hardware timestamping configuration of interfaces (SIOCSHWTSTAMP),
+//! SO_TIMESTAMPING on sockets and reception of datagrams with their timestamps.
+
+use std::{
+    io::Result,
+    net::SocketAddr,
+    os::unix::io::{AsFd, AsRawFd},
+    time::Duration,
+};
+
+use super::{
+    cmsg::{ControlMessage, recv_msg},
+    ethtool::{SOF_TIMESTAMPING_RAW_HARDWARE, SOF_TIMESTAMPING_RX_HARDWARE, SOF_TIMESTAMPING_RX_SOFTWARE,
+              SOF_TIMESTAMPING_SOFTWARE, SOF_TIMESTAMPING_TX_HARDWARE, SOF_TIMESTAMPING_TX_SOFTWARE},
+    ioctl::interface_data_ioctl,
+    sockopt::set_int_option,
+};
+
+const SIOCSHWTSTAMP: libc::c_ulong = 0x89b0;
+const SIOCGHWTSTAMP: libc::c_ulong = 0x89b1;
+
+/// hwtstamp_tx_types: no hardware timestamps for sent packets.
+pub const HWTSTAMP_TX_OFF: i32 = 0;
+/// hwtstamp_tx_types: hardware timestamps for all sent packets that request one.
+pub const HWTSTAMP_TX_ON: i32 = 1;
+
+/// hwtstamp_rx_filters: no hardware timestamps for received packets.
+pub const HWTSTAMP_FILTER_NONE: i32 = 0;
+/// hwtstamp_rx_filters: hardware timestamps for all received packets.
+pub const HWTSTAMP_FILTER_ALL: i32 = 1;
+/// hwtstamp_rx_filters: PTP v2 event messages over UDP (layer 4).
+pub const HWTSTAMP_FILTER_PTP_V2_L4_EVENT: i32 = 6;
+/// hwtstamp_rx_filters: PTP v2 event messages over any transport.
+pub const HWTSTAMP_FILTER_PTP_V2_EVENT: i32 = 12;
+
+/// SO_TIMESTAMPING flags for hardware timestamps of received and sent packets.
+pub const HARDWARE_TIMESTAMPING: u32 = SOF_TIMESTAMPING_RX_HARDWARE | SOF_TIMESTAMPING_TX_HARDWARE
+    | SOF_TIMESTAMPING_RAW_HARDWARE;
+/// SO_TIMESTAMPING flags for software timestamps of received and sent packets.
+pub const SOFTWARE_TIMESTAMPING: u32 = SOF_TIMESTAMPING_RX_SOFTWARE | SOF_TIMESTAMPING_TX_SOFTWARE
+    | SOF_TIMESTAMPING_SOFTWARE;
+
+#[repr(C)]
+struct HwtstampConfig {
+    flags: i32,
+    tx_type: i32,
+    rx_filter: i32,
+}
+
+/// Hardware timestamping configuration of an interface.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct HardwareTimestampingConfig {
+    /// HWTSTAMP_TX_* mode for sent packets
+    pub tx_type: i32,
+
+    /// HWTSTAMP_FILTER_* filter for received packets
+    pub rx_filter: i32,
+}
+
+/// Timestamps of a packet. Software timestamps are CLOCK_REALTIME times, hardware timestamps are
+/// times of the interface's PTP hardware clock, both as duration since the clock's epoch.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Timestamps {
+    /// timestamp generated by the kernel
+    pub software: Option<Duration>,
+
+    /// raw timestamp generated by the network adapter
+    pub hardware: Option<Duration>,
+}
+
+/// Enables hardware timestamping on the interface (SIOCSHWTSTAMP), requires CAP_NET_ADMIN.
+/// Returns the configuration actually applied by the driver, which may timestamp more packets
+/// than requested (e.g. HWTSTAMP_FILTER_ALL instead of a PTP filter).
+pub fn enable_hardware_timestamping(interface_name: &str, config: &HardwareTimestampingConfig)
+                                    -> Result<HardwareTimestampingConfig> {
+    let mut raw = HwtstampConfig { flags: 0, tx_type: config.tx_type, rx_filter: config.rx_filter };
+    interface_data_ioctl(interface_name, SIOCSHWTSTAMP, &mut raw)?;
+    Ok(HardwareTimestampingConfig { tx_type: raw.tx_type, rx_filter: raw.rx_filter })
+}
+
+/// Retrieves the current hardware timestamping configuration of the interface (SIOCGHWTSTAMP).
+pub fn hardware_timestamping_config(interface_name: &str) -> Result<HardwareTimestampingConfig> {
+    let mut raw = HwtstampConfig { flags: 0, tx_type: 0, rx_filter: 0 };
+    interface_data_ioctl(interface_name, SIOCGHWTSTAMP, &mut raw)?;
+    Ok(HardwareTimestampingConfig { tx_type: raw.tx_type, rx_filter: raw.rx_filter })
+}
+
+/// Sets SO_TIMESTAMPING with the SOF_TIMESTAMPING_* flags on the socket, e.g.
+/// HARDWARE_TIMESTAMPING or SOFTWARE_TIMESTAMPING.
+pub fn set_timestamping<S: AsFd>(socket: &S, flags: u32) -> Result<()> {
+    set_int_option(socket.as_fd().as_raw_fd(), libc::SOL_SOCKET, libc::SO_TIMESTAMPING, flags as libc::c_int)
+}
+
+/// Receives a datagram together with its timestamps from a socket with SO_TIMESTAMPING enabled,
+/// returns its length, the sender's address and the timestamps.
+pub fn recv_with_timestamps<S: AsFd>(socket: &S, buf: &mut [u8]) -> Result<(usize, SocketAddr, Timestamps)> {
+    let msg = recv_msg(socket.as_fd().as_raw_fd(), buf, 0)?;
+    let address = msg.address.ok_or_else(|| std::io::Error::other("datagram without IP source address"))?;
+    let timestamps = msg.control.iter()
+        .find_map(|c| match c {
+            ControlMessage::Timestamping(ts) => Some(*ts),
+            _ => None,
+        })
+        .unwrap_or_default();
+    Ok((msg.len, address, timestamps))
+}
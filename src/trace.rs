@@ -0,0 +1,188 @@
+//! Tracing of the socket lifecycle: socket creation, socket options, multicast group membership,
+//! interface resolution and the monitored interface changes are reported as `TraceEvent`s to a
+//! process wide handler, which forwards them to the logging framework of the application, e.g.
+//! `set_trace_handler(|event| log::debug!("{}", event))`. The events are also emitted as
+//! `tracing` events with structured fields (failures at level WARN, the others at DEBUG), within
+//! the spans the socket builders open, so a `tracing` subscriber needs no handler.
+//! Requires the feature 'trace', without it no events are generated.
+
+use std::{
+    fmt,
+    io::Error,
+    net::{IpAddr, SocketAddr},
+    sync::RwLock,
+};
+
+/// Interface a traced operation refers to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TraceInterface {
+    /// interface with the local address, the unspecified address for the default interface
+    Address(IpAddr),
+    /// interface with the index, 0 for the default interface
+    Index(u32),
+}
+
+/// An event of the socket lifecycle.
+#[derive(Clone, Copy, Debug)]
+pub enum TraceEvent<'a> {
+    /// a socket of the kind ("udp", "tcp-listener", ...) was created and bound to the address
+    SocketCreated { kind: &'static str, address: Option<SocketAddr> },
+
+    /// an integer socket option was set (setsockopt), error is set if it failed
+    OptionSet { level: i32, name: i32, value: i32, error: Option<&'a Error> },
+
+    /// the socket joined the multicast group on the interface, error is set if it failed
+    GroupJoined { group: IpAddr, interface: TraceInterface, error: Option<&'a Error> },
+
+    /// the socket left the multicast group on the interface, error is set if it failed
+    GroupLeft { group: IpAddr, interface: TraceInterface, error: Option<&'a Error> },
+
+    /// joining on all interfaces skipped the interface because the membership failed
+    InterfaceSkipped { index: u32, error: &'a Error },
+
+    /// the interface index of the local address was looked up, 0 if no interface has it
+    InterfaceResolved { address: IpAddr, index: u32 },
+
+    /// the kernel reported changes of links or addresses (e.g. an interface went up or down),
+    /// lost is set if notifications were lost
+    InterfacesChanged { lost: bool },
+
+    /// the kernel reported the link with the index up (IFF_UP and IFF_RUNNING) or down, removed
+    /// links are reported down
+    LinkChanged { index: u32, up: bool },
+
+    /// a socket moved its membership of the group from the interface with the local address
+    /// to another one (see `SocketSet::apply`)
+    SocketMoved { group: IpAddr, from: IpAddr, to: IpAddr },
+}
+
+impl TraceEvent<'_> {
+
+    /// Returns whether the event reports a failed operation.
+    pub fn is_error(&self) -> bool {
+        match self {
+            TraceEvent::OptionSet { error, .. } | TraceEvent::GroupJoined { error, .. }
+            | TraceEvent::GroupLeft { error, .. } => error.is_some(),
+            TraceEvent::InterfaceSkipped { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for TraceInterface {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceInterface::Address(address) => write!(f, "interface {}", address),
+            TraceInterface::Index(index) => write!(f, "interface index {}", index),
+        }
+    }
+}
+
+impl fmt::Display for TraceEvent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let result = |f: &mut fmt::Formatter<'_>, error: &Option<&Error>| match error {
+            Some(error) => write!(f, " failed: {}", error),
+            None => Ok(()),
+        };
+        match self {
+            TraceEvent::SocketCreated { kind, address: Some(address) } => write!(f, "created {} socket on {}", kind, address),
+            TraceEvent::SocketCreated { kind, address: None } => write!(f, "created {} socket", kind),
+            TraceEvent::OptionSet { level, name, value, error } => {
+                write!(f, "setsockopt level {} option {} = {}", level, name, value)?;
+                result(f, error)
+            },
+            TraceEvent::GroupJoined { group, interface, error } => {
+                write!(f, "join {} on {}", group, interface)?;
+                result(f, error)
+            },
+            TraceEvent::GroupLeft { group, interface, error } => {
+                write!(f, "leave {} on {}", group, interface)?;
+                result(f, error)
+            },
+            TraceEvent::InterfaceSkipped { index, error } => write!(f, "skipped interface index {}: {}", index, error),
+            TraceEvent::InterfaceResolved { address, index } => write!(f, "resolved {} to interface index {}", address, index),
+            TraceEvent::InterfacesChanged { lost: false } => write!(f, "interfaces changed"),
+            TraceEvent::InterfacesChanged { lost: true } => write!(f, "interfaces changed, notifications lost"),
+            TraceEvent::LinkChanged { index, up: true } => write!(f, "interface index {} up", index),
+            TraceEvent::LinkChanged { index, up: false } => write!(f, "interface index {} down", index),
+            TraceEvent::SocketMoved { group, from, to } => write!(f, "moved {} from interface {} to {}", group, from, to),
+        }
+    }
+}
+
+type TraceHandler = Box<dyn Fn(&TraceEvent) + Send + Sync>;
+
+static HANDLER: RwLock<Option<TraceHandler>> = RwLock::new(None);
+
+/// Installs the process wide handler of trace events, replacing the previous one. The handler
+/// is called synchronously by the thread performing the operation and must not install or
+/// clear handlers itself.
+pub fn set_trace_handler<F>(handler: F) where F: Fn(&TraceEvent) + Send + Sync + 'static {
+    *HANDLER.write().unwrap_or_else(|e| e.into_inner()) = Some(Box::new(handler));
+}
+
+/// Removes the trace handler, events are discarded thereafter.
+pub fn clear_trace_handler() {
+    *HANDLER.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Passes the event to the handler and to `tracing`, the event is only created if a handler is
+/// installed, a `tracing` subscriber is interested or metrics are recorded (feature 'metrics').
+pub(crate) fn emit<'a, F: FnOnce() -> TraceEvent<'a>>(event: F) {
+    let handler = HANDLER.read().unwrap_or_else(|e| e.into_inner());
+    if !(cfg!(feature = "metrics") || handler.is_some() || tracing::enabled!(tracing::Level::WARN)) {
+        return;
+    }
+    let event = event();
+    #[cfg(feature = "metrics")]
+    super::metrics::record(&event);
+    forward(&event);
+    if let Some(handler) = handler.as_ref() {
+        handler(&event);
+    }
+}
+
+/// Emits the event as `tracing` event.
+fn forward(event: &TraceEvent<'_>) {
+    match *event {
+        TraceEvent::SocketCreated { kind, address: Some(address) } => tracing::debug!(kind, %address, "socket created"),
+        TraceEvent::SocketCreated { kind, address: None } => tracing::debug!(kind, "socket created"),
+        TraceEvent::OptionSet { level, name, value, error: None } => tracing::debug!(level, name, value, "socket option set"),
+        TraceEvent::OptionSet { level, name, value, error: Some(error) } => {
+            tracing::warn!(level, name, value, %error, "socket option failed")
+        },
+        TraceEvent::GroupJoined { group, interface, error: None } => tracing::debug!(%group, %interface, "group joined"),
+        TraceEvent::GroupJoined { group, interface, error: Some(error) } => {
+            tracing::warn!(%group, %interface, %error, "group join failed")
+        },
+        TraceEvent::GroupLeft { group, interface, error: None } => tracing::debug!(%group, %interface, "group left"),
+        TraceEvent::GroupLeft { group, interface, error: Some(error) } => {
+            tracing::warn!(%group, %interface, %error, "group leave failed")
+        },
+        TraceEvent::InterfaceSkipped { index, error } => tracing::warn!(index, %error, "interface skipped"),
+        TraceEvent::InterfaceResolved { address, index } => tracing::debug!(%address, index, "interface resolved"),
+        TraceEvent::InterfacesChanged { lost: false } => tracing::debug!("interfaces changed"),
+        TraceEvent::InterfacesChanged { lost: true } => tracing::warn!("interface notifications lost"),
+        TraceEvent::LinkChanged { index, up } => tracing::debug!(index, up, "link changed"),
+        TraceEvent::SocketMoved { group, from, to } => tracing::debug!(%group, %from, %to, "socket moved"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let error = Error::other("no such device");
+        let event = TraceEvent::GroupJoined {
+            group: "239.1.2.3".parse().unwrap(), interface: TraceInterface::Index(9), error: Some(&error),
+        };
+        assert!(event.is_error());
+        assert_eq!(event.to_string(), "join 239.1.2.3 on interface index 9 failed: no such device");
+        let event = TraceEvent::SocketCreated { kind: "udp", address: Some("[::]:5353".parse().unwrap()) };
+        assert!(!event.is_error());
+        assert_eq!(event.to_string(), "created udp socket on [::]:5353");
+    }
+}
@@ -0,0 +1,333 @@
+//! Connected UDP sockets which report the ICMP errors (port unreachable, fragmentation needed,
+//! host unreachable) caused by their datagrams via the socket error queue (IP_RECVERR), which
+//! are otherwise invisible to UDP senders, detection of receive queue overruns (SO_RXQ_OVFL) and
+//! ECN marking and reporting of datagrams.
+
+use std::{
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    os::unix::io::{AsFd, AsRawFd, RawFd},
+    time::Duration,
+};
+
+use socket2::{Domain, Protocol, SockAddr, SockRef, Type};
+
+use super::{
+    cmsg::{ControlMessage, ExtendedError, recv_msg, send_msg},
+    device::bind_to_device,
+    errqueue::{QueuedError, recv_error_queue},
+    pktinfo::{Ecn, PktInfo, set_recv_tos},
+    shutdown::ShutdownHandle,
+    sockopt::{get_option, set_int_option},
+    stats::{SocketStats, StatsCounter},
+};
+
+/// An ICMP or ICMPv6 error received for a datagram sent by the socket.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct IcmpError {
+    /// errno the error is reported as (ECONNREFUSED, EHOSTUNREACH, EMSGSIZE, ...)
+    pub errno: i32,
+
+    /// whether the error is an ICMPv6 error
+    pub ipv6: bool,
+
+    /// ICMP type
+    pub icmp_type: u8,
+
+    /// ICMP code
+    pub icmp_code: u8,
+
+    /// next hop MTU of "fragmentation needed" and "packet too big" errors
+    pub mtu: Option<u32>,
+
+    /// address of the node which sent the ICMP error
+    pub reporter: Option<IpAddr>,
+
+    /// destination of the datagram which caused the error
+    pub destination: Option<SocketAddr>,
+}
+
+impl IcmpError {
+
+    /// Converts an extended error of ICMP origin, None for other origins.
+    pub(crate) fn from_extended(error: &ExtendedError, destination: Option<SocketAddr>) -> Option<IcmpError> {
+        let ipv6 = match error.origin {
+            libc::SO_EE_ORIGIN_ICMP => false,
+            libc::SO_EE_ORIGIN_ICMP6 => true,
+            _ => return None,
+        };
+        let errno = error.errno as i32;
+        Some(IcmpError {
+            errno,
+            ipv6,
+            icmp_type: error.ee_type,
+            icmp_code: error.code,
+            mtu: if errno == libc::EMSGSIZE { Some(error.info) } else { None },
+            reporter: error.offender.map(|offender| offender.ip()),
+            destination,
+        })
+    }
+
+    /// Returns whether no process listens on the destination port.
+    pub fn is_port_unreachable(&self) -> bool {
+        self.errno == libc::ECONNREFUSED
+    }
+
+    /// Returns whether the datagram exceeded the MTU of the path, see `mtu`.
+    pub fn is_fragmentation_needed(&self) -> bool {
+        self.errno == libc::EMSGSIZE
+    }
+
+    /// Returns the error as io::Error.
+    pub fn to_io_error(&self) -> Error {
+        Error::from_raw_os_error(self.errno)
+    }
+}
+
+/// A UDP socket connected to a single destination with IP_RECVERR (IPV6_RECVERR) enabled.
+/// After an ICMP error send and recv fail once with its errno, the details are read with
+/// `icmp_error`. SO_RXQ_OVFL is enabled for the drop counter of `stats`, IP_RECVTOS /
+/// IPV6_RECVTCLASS for the ECN codepoint of `recv_with_ecn`.
+#[derive(Debug)]
+pub struct ConnectedUdpSocket {
+    socket: UdpSocket,
+    stats: StatsCounter,
+    shutdown: ShutdownHandle,
+}
+
+/// Creates a UDP socket connected to the destination, optionally bound to the network device
+/// with the given name, which queues the ICMP errors of its datagrams.
+pub fn create_connected_udp(destination: &SocketAddr, interface: Option<&str>) -> Result<ConnectedUdpSocket> {
+    trace_span!("connected_udp_socket", %destination, interface);
+    let socket = super::sys_socket::socket(Domain::for_address(*destination), Type::DGRAM, Some(Protocol::UDP))?;
+    if let Some(interface) = interface {
+        bind_to_device(&socket, interface, destination)?;
+    }
+    let (local, level, name): (SocketAddr, _, _) = match destination {
+        SocketAddr::V4(_) => ((Ipv4Addr::UNSPECIFIED, 0).into(), libc::IPPROTO_IP, libc::IP_RECVERR),
+        SocketAddr::V6(_) => ((Ipv6Addr::UNSPECIFIED, 0).into(), libc::IPPROTO_IPV6, libc::IPV6_RECVERR),
+    };
+    set_int_option(socket.as_raw_fd(), level, name, 1)?;
+    set_drop_reporting(&socket, true)?;
+    super::sys_socket::bind(&socket, &SockAddr::from(local))?;
+    set_recv_tos(&socket, true)?;
+    socket.connect(&SockAddr::from(*destination))?;
+    trace_event!(crate::trace::TraceEvent::SocketCreated {
+        kind: "udp-connected", address: socket.local_addr().ok().and_then(|address| address.as_socket()),
+    });
+    Ok(ConnectedUdpSocket { socket: socket.into(), stats: StatsCounter::new(), shutdown: ShutdownHandle::new() })
+}
+
+impl ConnectedUdpSocket {
+
+    /// Returns the underlying socket.
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// Returns the underlying socket, IP_RECVERR stays enabled.
+    pub fn into_inner(self) -> UdpSocket {
+        self.socket
+    }
+
+    /// Returns the traffic statistics of the socket.
+    pub fn stats(&self) -> SocketStats {
+        self.stats.snapshot()
+    }
+
+    /// Sends a datagram to the destination.
+    pub fn send(&self, buf: &[u8]) -> Result<usize> {
+        let len = self.socket.send(buf)?;
+        self.stats.sent(len);
+        Ok(len)
+    }
+
+    /// Returns a handle which shuts down blocked and later `recv` calls from other threads, see
+    /// `shutdown::ShutdownHandle`.
+    pub fn shutdown_handle(&self) -> Result<ShutdownHandle> {
+        self.shutdown.share()
+    }
+
+    /// Sets the ECN codepoint of sent datagrams, see `set_ecn`.
+    pub fn set_ecn(&self, ecn: Ecn) -> Result<()> {
+        set_ecn(&self.socket, ecn)
+    }
+
+    /// Receives a datagram from the destination.
+    pub fn recv(&self, buf: &mut [u8]) -> Result<usize> {
+        Ok(self.recv_with_ecn(buf)?.0)
+    }
+
+    /// Receives a datagram from the destination, returns its length and ECN codepoint, e.g. CE
+    /// if a router on the path experienced congestion, which the receiver of a congestion
+    /// controlled protocol echoes to the sender.
+    pub fn recv_with_ecn(&self, buf: &mut [u8]) -> Result<(usize, Option<Ecn>)> {
+        self.shutdown.wait_readable(self.socket.as_fd())?;
+        let message = recv_msg(self.socket.as_raw_fd(), buf, 0)?;
+        self.stats.received(message.len);
+        self.stats.drops(drop_count(&message.control));
+        Ok((message.len, PktInfo::from_control(&message.control).ecn()))
+    }
+
+    /// Receives a datagram from the destination, waiting at most for the timeout. Returns
+    /// Ok(None) if no datagram arrived in time.
+    pub fn recv_timeout(&self, buf: &mut [u8], timeout: Duration) -> Result<Option<usize>> {
+        let previous = self.socket.read_timeout()?;
+        self.socket.set_read_timeout(Some(timeout))?;
+        let result = self.recv(buf);
+        self.socket.set_read_timeout(previous)?;
+        match result {
+            Ok(len) => Ok(Some(len)),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Reads the oldest queued ICMP error without blocking, Ok(None) if there is none. Queued
+    /// errors of other origins (e.g. local errors) are skipped.
+    pub fn icmp_error(&self) -> Result<Option<IcmpError>> {
+        let mut buf = [0_u8; 64];
+        while let Some(message) = recv_error_queue(&self.socket, &mut buf)? {
+            if let QueuedError::Icmp(error) = message.error {
+                return Ok(Some(error));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reads all queued ICMP errors without blocking.
+    pub fn icmp_errors(&self) -> Result<Vec<IcmpError>> {
+        let mut errors = Vec::new();
+        while let Some(error) = self.icmp_error()? {
+            errors.push(error);
+        }
+        Ok(errors)
+    }
+}
+
+/// Enables or disables SO_RXQ_OVFL on the socket: received datagrams carry the number of
+/// datagrams the socket dropped so far because its receive queue was full, see
+/// `recv_from_with_drops`.
+pub fn set_drop_reporting<S: AsFd>(socket: &S, enable: bool) -> Result<()> {
+    set_int_option(socket.as_fd().as_raw_fd(), libc::SOL_SOCKET, libc::SO_RXQ_OVFL, enable as libc::c_int)
+}
+
+/// Receives a datagram from a socket with SO_RXQ_OVFL enabled, returns its length, the sender's
+/// address and the number of datagrams the socket dropped before the datagram was queued. An
+/// increased drop counter indicates that the receiver is too slow or its receive buffer too
+/// small.
+pub fn recv_from_with_drops<S: AsFd>(socket: &S, buf: &mut [u8]) -> Result<(usize, SocketAddr, u32)> {
+    let message = recv_msg(socket.as_fd().as_raw_fd(), buf, 0)?;
+    let address = message.address.ok_or_else(|| Error::other("datagram without IP source address"))?;
+    Ok((message.len, address, drop_count(&message.control)))
+}
+
+/// Returns the drop counter of the control messages, the kernel omits it until the first drop.
+fn drop_count(control: &[ControlMessage]) -> u32 {
+    control.iter().find_map(|control| match control {
+        ControlMessage::DropCount(drops) => Some(*drops),
+        _ => None,
+    }).unwrap_or(0)
+}
+
+/// Sends a datagram to the target with a TTL (IPv4) or hop limit (IPv6) for this datagram only
+/// (IP_TTL / IPV6_HOPLIMIT control message), which overrides the unicast and multicast TTL of the
+/// socket, e.g. to send link-local (TTL 1) and site scoped announcements via one socket.
+pub fn send_to_with_ttl<S: AsFd>(socket: &S, buf: &[u8], target: SocketAddr, ttl: u8) -> Result<usize> {
+    let ttl = (ttl as libc::c_int).to_ne_bytes();
+    send_msg(socket.as_fd().as_raw_fd(), buf, Some(&target), &[ttl_control(&target, &ttl)], 0)
+}
+
+/// Sends a datagram from the tokio socket like `send_to_with_ttl`.
+/// Requires the feature 'tokio-net'.
+#[cfg(feature = "tokio-net")]
+pub async fn send_to_with_ttl_async(socket: &tokio::net::UdpSocket, buf: &[u8], target: SocketAddr, ttl: u8)
+                                    -> Result<usize> {
+    let fd = socket.as_raw_fd();
+    let ttl = (ttl as libc::c_int).to_ne_bytes();
+    socket.async_io(tokio::io::Interest::WRITABLE,
+                    || send_msg(fd, buf, Some(&target), &[ttl_control(&target, &ttl)], libc::MSG_DONTWAIT)).await
+}
+
+/// Sets the ECN codepoint of the datagrams sent by the socket, i.e. the two low bits of its TOS
+/// (IP_TOS) and, for IPv6 sockets, traffic class (IPV6_TCLASS), keeping the DSCP bits. The IPv4
+/// option fails for IPv6 only sockets, which is ignored. The ECN codepoint of received datagrams
+/// is reported with `pktinfo::set_recv_tos`, e.g. for L4S (ECT(1), RFC 9331) experiments.
+pub fn set_ecn<S: AsFd>(socket: &S, ecn: Ecn) -> Result<()> {
+    let fd = socket.as_fd().as_raw_fd();
+    let with_ecn = |tos: libc::c_int| (tos & !0x03) | ecn as libc::c_int;
+    let is_ipv6 = SockRef::from(socket).local_addr()?.is_ipv6();
+    if is_ipv6 {
+        let class = get_option::<libc::c_int>(fd, libc::IPPROTO_IPV6, libc::IPV6_TCLASS)?.max(0);
+        set_int_option(fd, libc::IPPROTO_IPV6, libc::IPV6_TCLASS, with_ecn(class))?;
+    }
+    let result = get_option::<libc::c_int>(fd, libc::IPPROTO_IP, libc::IP_TOS)
+        .and_then(|tos| set_int_option(fd, libc::IPPROTO_IP, libc::IP_TOS, with_ecn(tos)));
+    match result {
+        Err(e) if !is_ipv6 => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Sends a datagram to the target with the ECN codepoint for this datagram only (IP_TOS /
+/// IPV6_TCLASS control message), whose DSCP bits are zero. Datagrams to IPv4 mapped addresses
+/// use IP_TOS.
+pub fn send_to_with_ecn<S: AsFd>(socket: &S, buf: &[u8], target: SocketAddr, ecn: Ecn) -> Result<usize> {
+    let tos = (ecn as libc::c_int).to_ne_bytes();
+    send_msg(socket.as_fd().as_raw_fd(), buf, Some(&target), &[tos_control(&target, &tos)], 0)
+}
+
+/// Sends a datagram from the tokio socket like `send_to_with_ecn`.
+/// Requires the feature 'tokio-net'.
+#[cfg(feature = "tokio-net")]
+pub async fn send_to_with_ecn_async(socket: &tokio::net::UdpSocket, buf: &[u8], target: SocketAddr, ecn: Ecn)
+                                    -> Result<usize> {
+    let fd = socket.as_raw_fd();
+    let tos = (ecn as libc::c_int).to_ne_bytes();
+    socket.async_io(tokio::io::Interest::WRITABLE,
+                    || send_msg(fd, buf, Some(&target), &[tos_control(&target, &tos)], libc::MSG_DONTWAIT)).await
+}
+
+/// Returns the TOS or traffic class control message of the target, IP_TOS for IPv4 and IPv4
+/// mapped addresses.
+pub(crate) fn tos_control<'a>(target: &SocketAddr, tos: &'a [u8]) -> (libc::c_int, libc::c_int, &'a [u8]) {
+    match target {
+        SocketAddr::V6(v6) if v6.ip().to_ipv4_mapped().is_none() => (libc::IPPROTO_IPV6, libc::IPV6_TCLASS, tos),
+        _ => (libc::IPPROTO_IP, libc::IP_TOS, tos),
+    }
+}
+
+/// Returns the TTL control message of the address family of the target.
+fn ttl_control<'a>(target: &SocketAddr, ttl: &'a [u8]) -> (libc::c_int, libc::c_int, &'a [u8]) {
+    match target {
+        SocketAddr::V4(_) => (libc::IPPROTO_IP, libc::IP_TTL, ttl),
+        SocketAddr::V6(_) => (libc::IPPROTO_IPV6, libc::IPV6_HOPLIMIT, ttl),
+    }
+}
+
+impl AsRawFd for ConnectedUdpSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_from_extended() {
+        let mut error = ExtendedError {
+            errno: libc::EMSGSIZE as u32, origin: libc::SO_EE_ORIGIN_ICMP, ee_type: 3, code: 4, info: 1400, data: 0,
+            offender: Some("192.0.2.1:0".parse().unwrap()),
+        };
+        let icmp = IcmpError::from_extended(&error, Some("192.0.2.9:53".parse().unwrap())).unwrap();
+        assert!(icmp.is_fragmentation_needed());
+        assert!(!icmp.ipv6);
+        assert_eq!(icmp.mtu, Some(1400));
+        assert_eq!(icmp.reporter, Some("192.0.2.1".parse().unwrap()));
+        error.origin = libc::SO_EE_ORIGIN_LOCAL;
+        assert_eq!(IcmpError::from_extended(&error, None), None);
+    }
+}
@@ -0,0 +1,68 @@
+//! UDP-Lite (RFC 3828) sockets with partial checksum coverage.
+
+use std::{
+    io::Result,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, UdpSocket},
+    os::unix::io::{AsFd, AsRawFd},
+};
+
+use socket2::{Domain, Protocol, SockAddr, Type};
+
+use super::{
+    multicast::{create_multicast_socket_ipv4, create_multicast_socket_ipv6, find_interface_index},
+    sockopt::{get_option, set_int_option},
+};
+
+/// Protocol number of UDP-Lite.
+pub const IPPROTO_UDPLITE: libc::c_int = 136;
+const SOL_UDPLITE: libc::c_int = IPPROTO_UDPLITE;
+const UDPLITE_SEND_CSCOV: libc::c_int = 10;
+const UDPLITE_RECV_CSCOV: libc::c_int = 11;
+
+/// Length of the UDP-Lite header, the minimum checksum coverage apart from 0 (full coverage).
+pub const UDPLITE_HEADER_LEN: u16 = 8;
+
+/// Creates a UDP-Lite socket bound to the address. The returned std::net::UdpSocket can be used
+/// like a UDP socket, by default the checksum covers the whole datagram.
+pub fn bind(address: SocketAddr) -> Result<UdpSocket> {
+    let protocol = Protocol::from(IPPROTO_UDPLITE);
+    let socket = super::sys_socket::socket(Domain::for_address(address), Type::DGRAM, Some(protocol))?;
+    super::sys_socket::bind(&socket, &SockAddr::from(address))?;
+    Ok(socket.into())
+}
+
+/// Creates a UDP-Lite socket for multicast reception with SO_REUSEADDR set for IPv4, see
+/// `create_std_multicast_socket_ipv4` for the arguments.
+pub fn create_multicast_socket_v4(mc_address: &SocketAddrV4, interface: &Ipv4Addr) -> Result<UdpSocket> {
+    create_multicast_socket_ipv4(mc_address, interface, Protocol::from(IPPROTO_UDPLITE))
+}
+
+/// Creates a UDP-Lite socket for multicast reception with SO_REUSEADDR set for IPv6, see
+/// `create_std_multicast_socket_ipv6` for the arguments.
+pub fn create_multicast_socket_v6(mc_address: &SocketAddrV6, interface: &Ipv6Addr) -> Result<UdpSocket> {
+    create_multicast_socket_ipv6(mc_address, interface, find_interface_index, Protocol::from(IPPROTO_UDPLITE))
+}
+
+/// Sets the number of bytes (including the 8 byte header) covered by the checksum of sent
+/// datagrams (UDPLITE_SEND_CSCOV), 0 covers the whole datagram.
+pub fn set_send_checksum_coverage<S: AsFd>(socket: &S, coverage: u16) -> Result<()> {
+    set_int_option(socket.as_fd().as_raw_fd(), SOL_UDPLITE, UDPLITE_SEND_CSCOV, coverage as libc::c_int)
+}
+
+/// Sets the minimum checksum coverage of received datagrams (UDPLITE_RECV_CSCOV), datagrams
+/// with a smaller coverage are dropped. 0 requires full coverage.
+pub fn set_recv_checksum_coverage<S: AsFd>(socket: &S, coverage: u16) -> Result<()> {
+    set_int_option(socket.as_fd().as_raw_fd(), SOL_UDPLITE, UDPLITE_RECV_CSCOV, coverage as libc::c_int)
+}
+
+/// Returns the checksum coverage of sent datagrams.
+pub fn send_checksum_coverage<S: AsFd>(socket: &S) -> Result<u16> {
+    let coverage: libc::c_int = get_option(socket.as_fd().as_raw_fd(), SOL_UDPLITE, UDPLITE_SEND_CSCOV)?;
+    Ok(coverage as u16)
+}
+
+/// Returns the minimum checksum coverage of received datagrams.
+pub fn recv_checksum_coverage<S: AsFd>(socket: &S) -> Result<u16> {
+    let coverage: libc::c_int = get_option(socket.as_fd().as_raw_fd(), SOL_UDPLITE, UDPLITE_RECV_CSCOV)?;
+    Ok(coverage as u16)
+}
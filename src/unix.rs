@@ -0,0 +1,134 @@
+//! Unix domain socket helpers: abstract namespace names, credential passing, peer credentials.
+
+use std::{
+    io::{Error, Result},
+    os::unix::{
+        io::{AsFd, AsRawFd},
+        net::{UnixDatagram, UnixListener, UnixStream},
+    },
+    path::PathBuf,
+};
+
+use socket2::{Domain, SockAddr, Type};
+
+use super::sockaddr::{sock_addr_from_raw, unix_socket_address, unix_socket_name_from};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use super::sockopt::{get_option, set_int_option};
+
+/// Name (address) of a unix domain socket.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum UnixSocketName {
+    /// socket file in the file system
+    Path(PathBuf),
+
+    /// name in the abstract namespace without the leading NUL byte (linux and android only),
+    /// the name does not appear in the file system and vanishes with the last socket using it
+    Abstract(Vec<u8>),
+
+    /// unbound socket
+    Unnamed,
+}
+
+impl UnixSocketName {
+
+    /// Creates a name in the abstract namespace.
+    pub fn abstract_name(name: &[u8]) -> UnixSocketName {
+        UnixSocketName::Abstract(name.to_vec())
+    }
+}
+
+impl From<PathBuf> for UnixSocketName {
+    fn from(path: PathBuf) -> UnixSocketName {
+        UnixSocketName::Path(path)
+    }
+}
+
+impl From<&std::path::Path> for UnixSocketName {
+    fn from(path: &std::path::Path) -> UnixSocketName {
+        UnixSocketName::Path(path.to_path_buf())
+    }
+}
+
+/// Credentials of the process at the other end of a unix socket connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PeerCredentials {
+    /// process id of the peer, None if the system does not report it
+    pub pid: Option<u32>,
+
+    /// effective user id of the peer
+    pub uid: u32,
+
+    /// effective group id of the peer
+    pub gid: u32,
+}
+
+/// Creates a unix datagram socket bound to the name.
+pub fn bind_datagram(name: &UnixSocketName) -> Result<UnixDatagram> {
+    let socket = super::sys_socket::socket(Domain::UNIX, Type::DGRAM, None)?;
+    super::sys_socket::bind(&socket, &sock_addr(name)?)?;
+    Ok(UnixDatagram::from(std::os::unix::io::OwnedFd::from(socket)))
+}
+
+/// Creates an unbound unix datagram socket connected to the name.
+pub fn connect_datagram(name: &UnixSocketName) -> Result<UnixDatagram> {
+    let socket = super::sys_socket::socket(Domain::UNIX, Type::DGRAM, None)?;
+    socket.connect(&sock_addr(name)?)?;
+    Ok(UnixDatagram::from(std::os::unix::io::OwnedFd::from(socket)))
+}
+
+/// Creates a unix stream listener bound to the name with the backlog tcp::DEFAULT_BACKLOG.
+pub fn bind_listener(name: &UnixSocketName) -> Result<UnixListener> {
+    let socket = super::sys_socket::socket(Domain::UNIX, Type::STREAM, None)?;
+    super::sys_socket::bind(&socket, &sock_addr(name)?)?;
+    super::sys_socket::listen(&socket, super::tcp::DEFAULT_BACKLOG)?;
+    Ok(UnixListener::from(std::os::unix::io::OwnedFd::from(socket)))
+}
+
+/// Connects a unix stream socket to the name.
+pub fn connect_stream(name: &UnixSocketName) -> Result<UnixStream> {
+    let socket = super::sys_socket::socket(Domain::UNIX, Type::STREAM, None)?;
+    socket.connect(&sock_addr(name)?)?;
+    Ok(UnixStream::from(std::os::unix::io::OwnedFd::from(socket)))
+}
+
+/// Returns the name the socket is bound to.
+pub fn local_name<S: AsFd>(socket: &S) -> Result<UnixSocketName> {
+    let mut addr: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of_val(&addr) as libc::socklen_t;
+    if unsafe { libc::getsockname(socket.as_fd().as_raw_fd(), std::ptr::addr_of_mut!(addr) as *mut libc::sockaddr,
+                                  &mut len) } != 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(unix_socket_name_from(&addr, len))
+}
+
+/// Enables or disables SO_PASSCRED: the credentials of the sender are then delivered with every
+/// received message as SCM_CREDENTIALS control message.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn set_pass_credentials<S: AsFd>(socket: &S, pass: bool) -> Result<()> {
+    set_int_option(socket.as_fd().as_raw_fd(), libc::SOL_SOCKET, libc::SO_PASSCRED, pass as libc::c_int)
+}
+
+/// Retrieves the credentials of the peer of a connected unix stream socket (SO_PEERCRED), the
+/// credentials are those at the time of connect(2) or socketpair(2).
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn peer_credentials<S: AsFd>(socket: &S) -> Result<PeerCredentials> {
+    let cred: libc::ucred = get_option(socket.as_fd().as_raw_fd(), libc::SOL_SOCKET, libc::SO_PEERCRED)?;
+    Ok(PeerCredentials { pid: Some(cred.pid as u32), uid: cred.uid, gid: cred.gid })
+}
+
+/// Retrieves the credentials of the peer of a connected unix stream socket (getpeereid), the
+/// credentials are those at the time of connect(2) or socketpair(2).
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub fn peer_credentials<S: AsFd>(socket: &S) -> Result<PeerCredentials> {
+    let (mut uid, mut gid) = (0, 0);
+    if unsafe { libc::getpeereid(socket.as_fd().as_raw_fd(), &mut uid, &mut gid) } != 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(PeerCredentials { pid: None, uid, gid })
+}
+
+fn sock_addr(name: &UnixSocketName) -> Result<SockAddr> {
+    let (addr, len) = unix_socket_address(name)?;
+    Ok(sock_addr_from_raw(&addr, len))
+}
@@ -0,0 +1,446 @@
+//! VRRP (RFC 3768 version 2, RFC 9568 version 3) monitoring: reception and parsing of
+//! advertisements on raw sockets and tracking of the master router of every virtual router per
+//! interface.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    os::unix::io::{AsFd, AsRawFd},
+    time::{Duration, Instant},
+};
+
+use socket2::{InterfaceIndexOrAddress, SockRef};
+
+use super::{checksum::{internet_checksum, pseudo_header_checksum, verify}, cmsg::ControlMessage, netlink::{join_links, LinkInfo},
+            raw::{RawSocket, RawSocketBuilder}, shutdown::ShutdownHandle};
+
+/// IPv4 multicast group of VRRP advertisements.
+pub const VRRP_MULTICAST_V4: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 18);
+
+/// IPv6 (link-local) multicast group of VRRP advertisements.
+pub const VRRP_MULTICAST_V6: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0x12);
+
+/// IP protocol number of VRRP.
+pub const IPPROTO_VRRP: libc::c_int = 112;
+
+/// Priority of a master that stops being master.
+pub const PRIORITY_RESIGN: u8 = 0;
+
+/// Priority of the router owning the virtual addresses.
+pub const PRIORITY_OWNER: u8 = 255;
+
+/// A VRRP advertisement.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VrrpAdvertisement {
+    /// protocol version (2 or 3)
+    pub version: u8,
+
+    /// virtual router id
+    pub vrid: u8,
+
+    /// priority of the sender, PRIORITY_RESIGN when the master resigns
+    pub priority: u8,
+
+    /// interval between advertisements (seconds for version 2, centiseconds for version 3)
+    pub interval: Duration,
+
+    /// virtual addresses of the router
+    pub addresses: Vec<IpAddr>,
+}
+
+impl VrrpAdvertisement {
+
+    /// Parses a VRRP message sent from source to destination, the addresses are needed for the
+    /// checksum of version 3. None if the message is invalid or its checksum is wrong.
+    pub fn parse(data: &[u8], source: &IpAddr, destination: &IpAddr) -> Option<VrrpAdvertisement> {
+        // only the advertisement (type 1) is defined
+        if data.len() < 8 || data[0] & 0x0f != 1 {
+            return None;
+        }
+        let version = data[0] >> 4;
+        let count = data[3] as usize;
+        let (address_len, addresses_end) = match (version, source) {
+            (2, IpAddr::V4(_)) => (4, 8 + 4 * count),
+            (3, IpAddr::V4(_)) => (4, 8 + 4 * count),
+            (3, IpAddr::V6(_)) => (16, 8 + 16 * count),
+            _ => return None,
+        };
+        let valid = match version {
+            // version 2 has 8 bytes authentication data after the addresses
+            2 => data.len() >= addresses_end + 8 && verify(data),
+            _ => data.len() >= addresses_end && pseudo_header_checksum(source, destination, IPPROTO_VRRP as u8, data) == 0,
+        };
+        if !valid {
+            return None;
+        }
+        let interval = match version {
+            2 => Duration::from_secs(data[5] as u64),
+            _ => Duration::from_millis(10 * (u16::from_be_bytes([data[4], data[5]]) & 0x0fff) as u64),
+        };
+        let addresses = data[8..addresses_end].chunks_exact(address_len)
+            .map(|address| match address_len {
+                4 => IpAddr::V4(Ipv4Addr::new(address[0], address[1], address[2], address[3])),
+                _ => {
+                    let mut octets = [0_u8; 16];
+                    octets.copy_from_slice(address);
+                    IpAddr::V6(Ipv6Addr::from(octets))
+                },
+            })
+            .collect();
+        Some(VrrpAdvertisement { version, vrid: data[1], priority: data[2], interval, addresses })
+    }
+
+    /// Returns the advertisement in wire format for the source and destination address, version 2
+    /// without authentication.
+    pub fn encode(&self, source: &IpAddr, destination: &IpAddr) -> Result<Vec<u8>> {
+        if self.addresses.len() > 255 || (self.version != 2 && self.version != 3)
+            || (self.version == 2 && source.is_ipv6())
+            || self.addresses.iter().any(|address| address.is_ipv4() != source.is_ipv4()) {
+            return Err(Error::new(ErrorKind::InvalidInput, "invalid version, address family or address count"));
+        }
+        let mut data = vec![self.version << 4 | 1, self.vrid, self.priority, self.addresses.len() as u8];
+        match self.version {
+            2 => data.extend_from_slice(&[0, self.interval.as_secs().min(255) as u8]),
+            _ => data.extend_from_slice(&((self.interval.as_millis() / 10).min(0x0fff) as u16).to_be_bytes()),
+        }
+        data.extend_from_slice(&[0, 0]);
+        for address in &self.addresses {
+            match address {
+                IpAddr::V4(address) => data.extend_from_slice(&address.octets()),
+                IpAddr::V6(address) => data.extend_from_slice(&address.octets()),
+            }
+        }
+        let checksum = match self.version {
+            2 => {
+                data.extend_from_slice(&[0; 8]);
+                internet_checksum(&data)
+            },
+            _ => pseudo_header_checksum(source, destination, IPPROTO_VRRP as u8, &data),
+        };
+        data[6..8].copy_from_slice(&checksum.to_be_bytes());
+        Ok(data)
+    }
+
+    /// Returns the time after which backups take over if the master stops advertising.
+    pub fn master_down_interval(&self) -> Duration {
+        let skew = self.interval * (256 - self.priority as u32) / 256;
+        self.interval * 3 + skew
+    }
+}
+
+/// A received advertisement.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VrrpPacket {
+    /// index of the interface the advertisement was received on
+    pub interface_index: u32,
+
+    /// address of the sending router
+    pub source: IpAddr,
+
+    /// the advertisement
+    pub advertisement: VrrpAdvertisement,
+}
+
+/// A change of the master of a virtual router.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MasterChange {
+    /// index of the interface of the virtual router
+    pub interface_index: u32,
+
+    /// virtual router id
+    pub vrid: u8,
+
+    /// whether the virtual router is an IPv6 router
+    pub ipv6: bool,
+
+    /// previous master, None if the virtual router was unknown or had no master
+    pub previous: Option<IpAddr>,
+
+    /// new master, None if the master resigned or stopped advertising
+    pub master: Option<IpAddr>,
+}
+
+/// Tracks the master of the virtual routers of received advertisements.
+#[derive(Clone, Debug, Default)]
+pub struct MasterTracker {
+    /// master and when it is considered down per interface, vrid and address family
+    masters: HashMap<(u32, u8, bool), (IpAddr, Instant)>,
+}
+
+impl MasterTracker {
+
+    /// Creates a tracker without known virtual routers.
+    pub fn new() -> MasterTracker {
+        MasterTracker::default()
+    }
+
+    /// Updates the virtual router of the advertisement, returns the change if the sender became
+    /// master or the master resigned.
+    pub fn update(&mut self, packet: &VrrpPacket, now: Instant) -> Option<MasterChange> {
+        let advertisement = &packet.advertisement;
+        let key = (packet.interface_index, advertisement.vrid, packet.source.is_ipv6());
+        let previous = self.masters.get(&key).map(|(master, _)| *master);
+        let master = if advertisement.priority == PRIORITY_RESIGN {
+            if previous != Some(packet.source) {
+                return None;
+            }
+            self.masters.remove(&key);
+            None
+        } else {
+            self.masters.insert(key, (packet.source, now + advertisement.master_down_interval()));
+            Some(packet.source)
+        };
+        if previous == master {
+            return None;
+        }
+        Some(MasterChange { interface_index: key.0, vrid: key.1, ipv6: key.2, previous, master })
+    }
+
+    /// Removes the masters that did not advertise within the master down interval and returns
+    /// the changes.
+    pub fn expire(&mut self, now: Instant) -> Vec<MasterChange> {
+        let mut changes = Vec::new();
+        self.masters.retain(|key, (master, down)| {
+            if *down > now {
+                return true;
+            }
+            changes.push(MasterChange { interface_index: key.0, vrid: key.1, ipv6: key.2, previous: Some(*master),
+                                        master: None });
+            false
+        });
+        changes
+    }
+
+    /// Returns the current master of the virtual router on the interface.
+    pub fn master(&self, interface_index: u32, vrid: u8, ipv6: bool) -> Option<IpAddr> {
+        self.masters.get(&(interface_index, vrid, ipv6)).map(|(master, _)| *master)
+    }
+
+    fn next_expiry(&self) -> Option<Instant> {
+        self.masters.values().map(|(_, down)| *down).min()
+    }
+}
+
+/// Receives the VRRP advertisements of one address family on a raw socket. Requires CAP_NET_RAW.
+#[derive(Debug)]
+pub struct VrrpListener {
+    socket: RawSocket,
+    ipv6: bool,
+    tracker: MasterTracker,
+    /// expired masters not yet returned by `next_change`
+    expired: VecDeque<MasterChange>,
+    shutdown: ShutdownHandle,
+}
+
+impl VrrpListener {
+
+    /// Creates a listener joined to VRRP_MULTICAST_V4 on the interface, on all multicast capable
+    /// interfaces if None.
+    pub fn ipv4(interface_index: Option<u32>) -> Result<VrrpListener> {
+        let socket = RawSocketBuilder::ipv4(IPPROTO_VRRP as u8).build()?;
+        super::sockopt::set_int_option(socket.as_raw_fd(), libc::IPPROTO_IP, libc::IP_PKTINFO, 1)?;
+        join_links(interface_index, multicast_capable, |index| {
+            SockRef::from(&socket).join_multicast_v4_n(&VRRP_MULTICAST_V4, &InterfaceIndexOrAddress::Index(index))
+        })?;
+        Ok(VrrpListener {
+            socket, ipv6: false, tracker: MasterTracker::new(), expired: VecDeque::new(), shutdown: ShutdownHandle::new(),
+        })
+    }
+
+    /// Creates a listener joined to VRRP_MULTICAST_V6 on the interface, on all multicast capable
+    /// interfaces if None.
+    pub fn ipv6(interface_index: Option<u32>) -> Result<VrrpListener> {
+        let socket = RawSocketBuilder::ipv6(IPPROTO_VRRP as u8).build()?;
+        super::sockopt::set_int_option(socket.as_raw_fd(), libc::IPPROTO_IPV6, libc::IPV6_RECVPKTINFO, 1)?;
+        super::sockopt::set_int_option(socket.as_raw_fd(), libc::IPPROTO_IPV6, libc::IPV6_RECVHOPLIMIT, 1)?;
+        join_links(interface_index, multicast_capable, |index| SockRef::from(&socket).join_multicast_v6(&VRRP_MULTICAST_V6, index))?;
+        Ok(VrrpListener {
+            socket, ipv6: true, tracker: MasterTracker::new(), expired: VecDeque::new(), shutdown: ShutdownHandle::new(),
+        })
+    }
+
+    /// Sets the timeout of `recv`, None blocks indefinitely.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        self.socket.set_read_timeout(timeout)
+    }
+
+    /// Returns a handle which shuts down blocked and later `recv` and `next_change` calls from
+    /// other threads, see `shutdown::ShutdownHandle`.
+    pub fn shutdown_handle(&self) -> Result<ShutdownHandle> {
+        self.shutdown.share()
+    }
+
+    /// Receives the next valid advertisement, advertisements not sent with TTL (hop limit) 255
+    /// are skipped.
+    pub fn recv(&self) -> Result<VrrpPacket> {
+        let mut buffer = [0_u8; 1500];
+        loop {
+            self.shutdown.wait_readable(self.socket.as_fd())?;
+            let message = super::cmsg::recv_msg(self.socket.as_raw_fd(), &mut buffer, 0)?;
+            let packet = match self.ipv6 {
+                false => parse_ipv4(&buffer[..message.len], &message.control),
+                true => message.address.and_then(|address| parse_ipv6(&buffer[..message.len], &address.ip(),
+                                                                         &message.control)),
+            };
+            if let Some(packet) = packet {
+                return Ok(packet);
+            }
+        }
+    }
+
+    /// Returns the tracker of the masters seen by `next_change`.
+    pub fn tracker(&self) -> &MasterTracker {
+        &self.tracker
+    }
+
+    /// Receives advertisements until the master of a virtual router changes, including masters
+    /// that stop advertising, and returns the change. Replaces the read timeout of the socket.
+    pub fn next_change(&mut self) -> Result<MasterChange> {
+        loop {
+            let now = Instant::now();
+            self.expired.extend(self.tracker.expire(now));
+            if let Some(change) = self.expired.pop_front() {
+                return Ok(change);
+            }
+            let timeout = self.tracker.next_expiry().map(|expiry| (expiry - now).max(Duration::from_millis(1)));
+            self.socket.set_read_timeout(timeout)?;
+            match self.recv() {
+                Ok(packet) => {
+                    if let Some(change) = self.tracker.update(&packet, Instant::now()) {
+                        return Ok(change);
+                    }
+                },
+                Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => (),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Accepts the multicast capable links.
+fn multicast_capable(link: &LinkInfo) -> bool {
+    link.flags & libc::IFF_MULTICAST as u32 != 0
+}
+
+/// Parses an IPv4 packet (with header) of the raw socket.
+fn parse_ipv4(packet: &[u8], control: &[ControlMessage]) -> Option<VrrpPacket> {
+    let header_len = 4 * (*packet.first()? & 0x0f) as usize;
+    if packet.len() < header_len.max(20) || packet[8] != 255 {
+        return None;
+    }
+    let source = IpAddr::V4(Ipv4Addr::new(packet[12], packet[13], packet[14], packet[15]));
+    let destination = IpAddr::V4(Ipv4Addr::new(packet[16], packet[17], packet[18], packet[19]));
+    let interface_index = control.iter().find_map(|control| match control {
+        ControlMessage::Other { level: libc::IPPROTO_IP, msg_type: libc::IP_PKTINFO, data } if data.len() >= 4 => {
+            Some(i32::from_ne_bytes([data[0], data[1], data[2], data[3]]) as u32)
+        },
+        _ => None,
+    })?;
+    let advertisement = VrrpAdvertisement::parse(&packet[header_len..], &source, &destination)?;
+    Some(VrrpPacket { interface_index, source, advertisement })
+}
+
+/// Parses the VRRP message of the IPv6 raw socket with destination and hop limit of the control
+/// messages.
+fn parse_ipv6(data: &[u8], source: &IpAddr, control: &[ControlMessage]) -> Option<VrrpPacket> {
+    let mut pktinfo = None;
+    let mut hop_limit = None;
+    for control in control {
+        match control {
+            ControlMessage::Other { level: libc::IPPROTO_IPV6, msg_type: libc::IPV6_PKTINFO, data } if data.len() >= 20 => {
+                let mut destination = [0_u8; 16];
+                destination.copy_from_slice(&data[..16]);
+                let index = u32::from_ne_bytes([data[16], data[17], data[18], data[19]]);
+                pktinfo = Some((IpAddr::V6(Ipv6Addr::from(destination)), index));
+            },
+            ControlMessage::Other { level: libc::IPPROTO_IPV6, msg_type: libc::IPV6_HOPLIMIT, data } if data.len() >= 4 => {
+                hop_limit = Some(i32::from_ne_bytes([data[0], data[1], data[2], data[3]]));
+            },
+            _ => (),
+        }
+    }
+    let (destination, interface_index) = pktinfo?;
+    if hop_limit != Some(255) {
+        return None;
+    }
+    let advertisement = VrrpAdvertisement::parse(data, source, &destination)?;
+    Some(VrrpPacket { interface_index, source: *source, advertisement })
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_advertisement_v2() {
+        let source: IpAddr = "192.0.2.10".parse().unwrap();
+        let destination = IpAddr::V4(VRRP_MULTICAST_V4);
+        let advertisement = VrrpAdvertisement {
+            version: 2,
+            vrid: 51,
+            priority: 100,
+            interval: Duration::from_secs(1),
+            addresses: vec!["192.0.2.1".parse().unwrap()],
+        };
+        let data = advertisement.encode(&source, &destination).unwrap();
+        assert_eq!(data.len(), 20);
+        assert_eq!(&data[..6], &[0x21, 51, 100, 1, 0, 1]);
+        assert_eq!(VrrpAdvertisement::parse(&data, &source, &destination), Some(advertisement.clone()));
+        assert_eq!(advertisement.master_down_interval(), Duration::from_millis(3609) + Duration::from_micros(375));
+        let mut corrupted = data.clone();
+        corrupted[2] = 101;
+        assert_eq!(VrrpAdvertisement::parse(&corrupted, &source, &destination), None);
+    }
+
+    #[test]
+    fn test_advertisement_v3() {
+        let source: IpAddr = "fe80::1".parse().unwrap();
+        let destination = IpAddr::V6(VRRP_MULTICAST_V6);
+        let advertisement = VrrpAdvertisement {
+            version: 3,
+            vrid: 7,
+            priority: 255,
+            interval: Duration::from_millis(250),
+            addresses: vec!["fe80::7".parse().unwrap(), "2001:db8::7".parse().unwrap()],
+        };
+        let data = advertisement.encode(&source, &destination).unwrap();
+        assert_eq!(&data[..6], &[0x31, 7, 255, 2, 0, 25]);
+        assert_eq!(VrrpAdvertisement::parse(&data, &source, &destination), Some(advertisement.clone()));
+        // the checksum covers the pseudo header
+        assert_eq!(VrrpAdvertisement::parse(&data, &"fe80::2".parse().unwrap(), &destination), None);
+        assert!(VrrpAdvertisement { version: 2, ..advertisement }.encode(&source, &destination).is_err());
+    }
+
+    #[test]
+    fn test_master_tracker() {
+        let now = Instant::now();
+        let mut tracker = MasterTracker::new();
+        let packet = |source: &str, priority: u8| VrrpPacket {
+            interface_index: 2,
+            source: source.parse().unwrap(),
+            advertisement: VrrpAdvertisement {
+                version: 3,
+                vrid: 1,
+                priority,
+                interval: Duration::from_secs(1),
+                addresses: Vec::new(),
+            },
+        };
+        let change = tracker.update(&packet("192.0.2.10", 100), now).unwrap();
+        assert_eq!((change.previous, change.master), (None, Some("192.0.2.10".parse().unwrap())));
+        assert_eq!(tracker.update(&packet("192.0.2.10", 100), now), None);
+        let change = tracker.update(&packet("192.0.2.11", 200), now).unwrap();
+        assert_eq!(change.previous, Some("192.0.2.10".parse().unwrap()));
+        // only the master can resign
+        assert_eq!(tracker.update(&packet("192.0.2.10", PRIORITY_RESIGN), now), None);
+        assert_eq!(tracker.master(2, 1, false), Some("192.0.2.11".parse().unwrap()));
+
+        assert!(tracker.expire(now + Duration::from_secs(3)).is_empty());
+        let changes = tracker.expire(now + Duration::from_secs(4));
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].master, None);
+        assert_eq!(tracker.master(2, 1, false), None);
+    }
+}
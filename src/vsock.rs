@@ -0,0 +1,212 @@
+//! Virtual machine sockets (AF_VSOCK) for host-guest communication addressed by CID and port.
+
+use std::{
+    fmt,
+    io::{Error, Read, Result, Write},
+    net::Shutdown,
+    os::unix::io::{AsFd, AsRawFd, BorrowedFd, RawFd},
+    time::Duration,
+};
+
+use socket2::{Domain, SockAddr, Socket, Type};
+
+use super::sockaddr::{sock_addr_from_raw, vsock_addr_from, vsock_socket_address};
+
+/// Wildcard CID for binding to any local CID.
+pub const VMADDR_CID_ANY: u32 = u32::MAX;
+/// CID of the hypervisor.
+pub const VMADDR_CID_HYPERVISOR: u32 = 0;
+/// CID for local communication (loopback, requires the vsock_loopback module).
+pub const VMADDR_CID_LOCAL: u32 = 1;
+/// CID of the host, used by guests to connect to the host.
+pub const VMADDR_CID_HOST: u32 = 2;
+/// Wildcard port for binding to any free port.
+pub const VMADDR_PORT_ANY: u32 = u32::MAX;
+
+const IOCTL_VM_SOCKETS_GET_LOCAL_CID: libc::c_ulong = 0x7b9;
+
+/// Address of a vsock socket.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VsockAddr {
+    /// context id of the virtual machine (or host)
+    pub cid: u32,
+
+    /// port number
+    pub port: u32,
+}
+
+impl VsockAddr {
+
+    /// Creates an address from context id and port.
+    pub fn new(cid: u32, port: u32) -> VsockAddr {
+        VsockAddr { cid, port }
+    }
+}
+
+impl fmt::Display for VsockAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "vsock:{}:{}", self.cid, self.port)
+    }
+}
+
+/// A connected vsock stream socket.
+#[derive(Debug)]
+pub struct VsockStream {
+    socket: Socket,
+}
+
+impl VsockStream {
+
+    /// Connects to the address, with a timeout the connect is performed non-blocking.
+    pub fn connect(address: &VsockAddr, timeout: Option<Duration>) -> Result<VsockStream> {
+        let socket = super::sys_socket::socket(Domain::VSOCK, Type::STREAM, None)?;
+        match timeout {
+            Some(timeout) => socket.connect_timeout(&sock_addr(address), timeout)?,
+            None => socket.connect(&sock_addr(address))?,
+        }
+        Ok(VsockStream { socket })
+    }
+
+    /// Returns the local address of the stream.
+    pub fn local_addr(&self) -> Result<VsockAddr> {
+        to_vsock_addr(&self.socket.local_addr()?)
+    }
+
+    /// Returns the address of the peer.
+    pub fn peer_addr(&self) -> Result<VsockAddr> {
+        to_vsock_addr(&self.socket.peer_addr()?)
+    }
+
+    /// Shuts down the read, write or both halves of the connection.
+    pub fn shutdown(&self, how: Shutdown) -> Result<()> {
+        self.socket.shutdown(how)
+    }
+
+    /// Switches the stream into or out of non-blocking mode.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> Result<()> {
+        self.socket.set_nonblocking(nonblocking)
+    }
+}
+
+impl Read for VsockStream {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.socket.read(buf)
+    }
+}
+
+impl Write for VsockStream {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.socket.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A vsock stream socket listening for connections.
+#[derive(Debug)]
+pub struct VsockListener {
+    socket: Socket,
+}
+
+impl VsockListener {
+
+    /// Creates a listener bound to the address (e.g. VMADDR_CID_ANY and a port) with the backlog
+    /// tcp::DEFAULT_BACKLOG.
+    pub fn bind(address: &VsockAddr) -> Result<VsockListener> {
+        let socket = super::sys_socket::socket(Domain::VSOCK, Type::STREAM, None)?;
+        super::sys_socket::bind(&socket, &sock_addr(address))?;
+        super::sys_socket::listen(&socket, super::tcp::DEFAULT_BACKLOG)?;
+        Ok(VsockListener { socket })
+    }
+
+    /// Accepts a new connection, returns the stream and the address of the peer.
+    pub fn accept(&self) -> Result<(VsockStream, VsockAddr)> {
+        let (socket, address) = self.socket.accept()?;
+        Ok((VsockStream { socket }, to_vsock_addr(&address)?))
+    }
+
+    /// Returns the local address of the listener.
+    pub fn local_addr(&self) -> Result<VsockAddr> {
+        to_vsock_addr(&self.socket.local_addr()?)
+    }
+
+    /// Switches the listener into or out of non-blocking mode.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> Result<()> {
+        self.socket.set_nonblocking(nonblocking)
+    }
+}
+
+/// A vsock datagram socket (only supported by some transports, e.g. VMCI).
+#[derive(Debug)]
+pub struct VsockDatagram {
+    socket: Socket,
+}
+
+impl VsockDatagram {
+
+    /// Creates a datagram socket bound to the address.
+    pub fn bind(address: &VsockAddr) -> Result<VsockDatagram> {
+        let socket = super::sys_socket::socket(Domain::VSOCK, Type::DGRAM, None)?;
+        super::sys_socket::bind(&socket, &sock_addr(address))?;
+        Ok(VsockDatagram { socket })
+    }
+
+    /// Sends a datagram to the address.
+    pub fn send_to(&self, buf: &[u8], address: &VsockAddr) -> Result<usize> {
+        self.socket.send_to(buf, &sock_addr(address))
+    }
+
+    /// Receives a datagram, returns its length and the sender's address.
+    pub fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, VsockAddr)> {
+        // recv_from only writes initialized bytes into the buffer
+        let uninit = unsafe { &mut *(buf as *mut [u8] as *mut [std::mem::MaybeUninit<u8>]) };
+        let (len, address) = self.socket.recv_from(uninit)?;
+        Ok((len, to_vsock_addr(&address)?))
+    }
+
+    /// Returns the local address of the socket.
+    pub fn local_addr(&self) -> Result<VsockAddr> {
+        to_vsock_addr(&self.socket.local_addr()?)
+    }
+}
+
+macro_rules! impl_fd {
+    ($t:ty) => {
+        impl AsFd for $t {
+            fn as_fd(&self) -> BorrowedFd<'_> {
+                self.socket.as_fd()
+            }
+        }
+
+        impl AsRawFd for $t {
+            fn as_raw_fd(&self) -> RawFd {
+                self.socket.as_raw_fd()
+            }
+        }
+    };
+}
+
+impl_fd!(VsockStream);
+impl_fd!(VsockListener);
+impl_fd!(VsockDatagram);
+
+/// Retrieves the CID of the local machine via /dev/vsock.
+pub fn local_cid() -> Result<u32> {
+    let device = std::fs::File::open("/dev/vsock")?;
+    let mut cid: u32 = 0;
+    if unsafe { libc::ioctl(device.as_raw_fd(), IOCTL_VM_SOCKETS_GET_LOCAL_CID as _, &mut cid) } != 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(cid)
+}
+
+fn sock_addr(address: &VsockAddr) -> SockAddr {
+    let raw = vsock_socket_address(address);
+    sock_addr_from_raw(&raw, std::mem::size_of_val(&raw) as libc::socklen_t)
+}
+
+fn to_vsock_addr(address: &SockAddr) -> Result<VsockAddr> {
+    vsock_addr_from(address).ok_or_else(|| Error::other("not a vsock address"))
+}
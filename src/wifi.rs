@@ -0,0 +1,244 @@
+//! Wireless interface details (mode, SSID, BSSID, frequency, signal strength) via nl80211.
+//! Multicast over Wi-Fi is sent at the lowest basic rate and unacknowledged, applications can use
+//! this module to detect wireless interfaces and the quality of their link.
+
+use std::{
+    convert::TryInto,
+    io::{ErrorKind, Result},
+};
+
+use super::{
+    IpInterface,
+    netlink::{AttributeIter, GENL_HDRLEN, MessageBuilder, NetlinkMessage, NetlinkSocket, generic_header,
+              string_from, u32_from},
+};
+
+const GENL_NAME: &str = "nl80211";
+const NL80211_CMD_GET_INTERFACE: u8 = 5;
+const NL80211_CMD_GET_STATION: u8 = 17;
+
+const NL80211_ATTR_IFINDEX: u16 = 3;
+const NL80211_ATTR_IFNAME: u16 = 4;
+const NL80211_ATTR_IFTYPE: u16 = 5;
+const NL80211_ATTR_MAC: u16 = 6;
+const NL80211_ATTR_STA_INFO: u16 = 21;
+const NL80211_ATTR_WIPHY_FREQ: u16 = 38;
+const NL80211_ATTR_SSID: u16 = 52;
+
+const NL80211_STA_INFO_SIGNAL: u16 = 7;
+const NL80211_STA_INFO_SIGNAL_AVG: u16 = 13;
+
+/// Operating mode of a wireless interface (NL80211_IFTYPE_*).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WifiMode {
+    /// ad-hoc (IBSS) network member
+    Adhoc,
+    /// client associated with an access point
+    Station,
+    /// access point
+    AccessPoint,
+    /// passive monitor
+    Monitor,
+    /// mesh point (802.11s)
+    MeshPoint,
+    /// P2P (Wi-Fi Direct) client
+    P2pClient,
+    /// P2P (Wi-Fi Direct) group owner
+    P2pGroupOwner,
+    /// other modes
+    Other(u32),
+}
+
+impl WifiMode {
+
+    fn from_iftype(iftype: u32) -> WifiMode {
+        match iftype {
+            1 => WifiMode::Adhoc,
+            2 => WifiMode::Station,
+            3 => WifiMode::AccessPoint,
+            6 => WifiMode::Monitor,
+            7 => WifiMode::MeshPoint,
+            8 => WifiMode::P2pClient,
+            9 => WifiMode::P2pGroupOwner,
+            _ => WifiMode::Other(iftype),
+        }
+    }
+}
+
+/// A wireless interface with its current association.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct WifiInterface {
+    /// interface index
+    pub index: u32,
+
+    /// interface name
+    pub name: String,
+
+    /// operating mode
+    pub mode: Option<WifiMode>,
+
+    /// MAC address of the interface
+    pub mac: Option<[u8; 6]>,
+
+    /// SSID of the network, None if not associated
+    pub ssid: Option<String>,
+
+    /// operating frequency in MHz, None if not associated
+    pub frequency: Option<u32>,
+
+    /// MAC address of the access point (station mode), None if not associated
+    pub bssid: Option<[u8; 6]>,
+
+    /// signal strength of the access point in dBm (station mode)
+    pub signal: Option<i8>,
+}
+
+impl WifiInterface {
+
+    /// Returns the IEEE 802.11 channel number of the operating frequency (2.4, 5 and 6 GHz bands).
+    pub fn channel(&self) -> Option<u32> {
+        match self.frequency? {
+            2484 => Some(14),
+            frequency @ 2412..=2472 => Some((frequency - 2407) / 5),
+            frequency @ 5955..=7115 => Some((frequency - 5950) / 5),
+            frequency @ 5000..=5900 => Some((frequency - 5000) / 5),
+            _ => None,
+        }
+    }
+}
+
+/// Retrieves all wireless interfaces of the host. Hosts without nl80211 support have none.
+pub fn wifi_interfaces() -> Result<Vec<WifiInterface>> {
+    let mut socket = NetlinkSocket::new(libc::NETLINK_GENERIC, 0)?;
+    let family = match socket.generic_family(GENL_NAME) {
+        Ok(family) => family,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let request = generic_header(NL80211_CMD_GET_INTERFACE, 0);
+    let messages = socket.request(family, libc::NLM_F_DUMP as u16, &request)?;
+    let mut interfaces: Vec<WifiInterface> = messages.iter()
+        .filter(|msg| msg.msg_type == family)
+        .filter_map(parse_interface)
+        .collect();
+    for interface in interfaces.iter_mut().filter(|interface| interface.mode == Some(WifiMode::Station)) {
+        let request = MessageBuilder::new(&generic_header(NL80211_CMD_GET_STATION, 0))
+            .u32_attribute(NL80211_ATTR_IFINDEX, interface.index)
+            .into_payload();
+        let stations = socket.request(family, libc::NLM_F_DUMP as u16, &request)?;
+        if let Some((bssid, signal)) = stations.iter().filter(|msg| msg.msg_type == family).find_map(parse_station) {
+            interface.bssid = Some(bssid);
+            interface.signal = signal;
+        }
+    }
+    Ok(interfaces)
+}
+
+/// Retrieves the wireless interface with the given name, Ok(None) if it is no wireless interface.
+pub fn wifi_interface(name: &str) -> Result<Option<WifiInterface>> {
+    Ok(wifi_interfaces()?.into_iter().find(|interface| interface.name == name))
+}
+
+impl IpInterface {
+
+    /// Returns the wireless details of this interface, Ok(None) if it is no wireless interface.
+    pub fn wifi(&self) -> Result<Option<WifiInterface>> {
+        Ok(wifi_interfaces()?.into_iter().find(|interface| interface.index == self.index))
+    }
+}
+
+/// Parses a NL80211_CMD_NEW_INTERFACE response, None without interface index.
+fn parse_interface(msg: &NetlinkMessage) -> Option<WifiInterface> {
+    let mut interface = WifiInterface {
+        index: 0, name: String::new(), mode: None, mac: None, ssid: None, frequency: None, bssid: None,
+        signal: None,
+    };
+    for (attr_type, data) in AttributeIter::new(msg.payload.get(GENL_HDRLEN..)?) {
+        match attr_type {
+            NL80211_ATTR_IFINDEX => interface.index = u32_from(data)?,
+            NL80211_ATTR_IFNAME => interface.name = string_from(data).unwrap_or_default(),
+            NL80211_ATTR_IFTYPE => interface.mode = u32_from(data).map(WifiMode::from_iftype),
+            NL80211_ATTR_MAC => interface.mac = data.get(0..6).map(|d| d.try_into().unwrap()),
+            NL80211_ATTR_SSID => interface.ssid = Some(String::from_utf8_lossy(data).into_owned()),
+            NL80211_ATTR_WIPHY_FREQ => interface.frequency = u32_from(data),
+            _ => {},
+        }
+    }
+    if interface.index == 0 { None } else { Some(interface) }
+}
+
+/// Parses a NL80211_CMD_NEW_STATION response into the MAC address and the signal strength
+/// (average if available) of the station.
+fn parse_station(msg: &NetlinkMessage) -> Option<([u8; 6], Option<i8>)> {
+    let mut mac = None;
+    let (mut signal, mut signal_avg) = (None, None);
+    for (attr_type, data) in AttributeIter::new(msg.payload.get(GENL_HDRLEN..)?) {
+        match attr_type {
+            NL80211_ATTR_MAC => mac = data.get(0..6).map(|d| d.try_into().unwrap()),
+            NL80211_ATTR_STA_INFO => for (info_type, info) in AttributeIter::new(data) {
+                match info_type {
+                    NL80211_STA_INFO_SIGNAL => signal = info.first().map(|s| *s as i8),
+                    NL80211_STA_INFO_SIGNAL_AVG => signal_avg = info.first().map(|s| *s as i8),
+                    _ => {},
+                }
+            },
+            _ => {},
+        }
+    }
+    Some((mac?, signal_avg.or(signal)))
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn message(payload: Vec<u8>) -> NetlinkMessage {
+        NetlinkMessage { msg_type: 0x1c, flags: 0, seq: 1, payload }
+    }
+
+    #[test]
+    fn test_parse_interface() {
+        let payload = MessageBuilder::new(&generic_header(7, 1))
+            .u32_attribute(NL80211_ATTR_IFINDEX, 3)
+            .str_attribute(NL80211_ATTR_IFNAME, "wlan0")
+            .u32_attribute(NL80211_ATTR_IFTYPE, 2)
+            .attribute(NL80211_ATTR_MAC, &[2, 0, 0, 0, 0, 1])
+            .attribute(NL80211_ATTR_SSID, b"home")
+            .u32_attribute(NL80211_ATTR_WIPHY_FREQ, 5180)
+            .into_payload();
+        let interface = parse_interface(&message(payload)).unwrap();
+        assert_eq!(interface.index, 3);
+        assert_eq!(interface.name, "wlan0");
+        assert_eq!(interface.mode, Some(WifiMode::Station));
+        assert_eq!(interface.mac, Some([2, 0, 0, 0, 0, 1]));
+        assert_eq!(interface.ssid.as_deref(), Some("home"));
+        assert_eq!(interface.channel(), Some(36));
+        assert!(parse_interface(&message(generic_header(7, 1).to_vec())).is_none());
+    }
+
+    #[test]
+    fn test_parse_station() {
+        let payload = MessageBuilder::new(&generic_header(19, 1))
+            .u32_attribute(NL80211_ATTR_IFINDEX, 3)
+            .attribute(NL80211_ATTR_MAC, &[2, 0, 0, 0, 0, 9])
+            .nested(NL80211_ATTR_STA_INFO, |b| b.u8_attribute(NL80211_STA_INFO_SIGNAL, -61_i8 as u8))
+            .into_payload();
+        assert_eq!(parse_station(&message(payload)), Some(([2, 0, 0, 0, 0, 9], Some(-61))));
+    }
+
+    #[test]
+    fn test_channel() {
+        let mut interface = WifiInterface {
+            index: 1, name: String::new(), mode: None, mac: None, ssid: None, frequency: Some(2412), bssid: None,
+            signal: None,
+        };
+        assert_eq!(interface.channel(), Some(1));
+        interface.frequency = Some(2484);
+        assert_eq!(interface.channel(), Some(14));
+        interface.frequency = Some(5955);
+        assert_eq!(interface.channel(), Some(1));
+        interface.frequency = None;
+        assert_eq!(interface.channel(), None);
+    }
+}
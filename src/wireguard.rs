@@ -0,0 +1,550 @@
+//! WireGuard interface configuration via generic netlink: creation of wg devices, private key,
+//! listen port, peers with their allowed IPs, and the handshake and traffic statistics of peers.
+//! Configuration and retrieval require CAP_NET_ADMIN.
+
+use std::{
+    convert::TryInto,
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use super::{
+    IpInterface,
+    netlink::{AttributeIter, GENL_HDRLEN, MessageBuilder, NetlinkSocket, generic_header, ip_address_from,
+              string_from, u32_from},
+};
+
+/// Link kind of WireGuard devices.
+pub const LINK_KIND: &str = "wireguard";
+/// Length of WireGuard keys.
+pub const KEY_LEN: usize = 32;
+
+const GENL_NAME: &str = "wireguard";
+const GENL_VERSION: u8 = 1;
+const WG_CMD_GET_DEVICE: u8 = 0;
+const WG_CMD_SET_DEVICE: u8 = 1;
+
+const WGDEVICE_A_IFINDEX: u16 = 1;
+const WGDEVICE_A_IFNAME: u16 = 2;
+const WGDEVICE_A_PRIVATE_KEY: u16 = 3;
+const WGDEVICE_A_PUBLIC_KEY: u16 = 4;
+const WGDEVICE_A_FLAGS: u16 = 5;
+const WGDEVICE_A_LISTEN_PORT: u16 = 6;
+const WGDEVICE_A_FWMARK: u16 = 7;
+const WGDEVICE_A_PEERS: u16 = 8;
+const WGDEVICE_F_REPLACE_PEERS: u32 = 1;
+
+const WGPEER_A_PUBLIC_KEY: u16 = 1;
+const WGPEER_A_PRESHARED_KEY: u16 = 2;
+const WGPEER_A_FLAGS: u16 = 3;
+const WGPEER_A_ENDPOINT: u16 = 4;
+const WGPEER_A_PERSISTENT_KEEPALIVE_INTERVAL: u16 = 5;
+const WGPEER_A_LAST_HANDSHAKE_TIME: u16 = 6;
+const WGPEER_A_RX_BYTES: u16 = 7;
+const WGPEER_A_TX_BYTES: u16 = 8;
+const WGPEER_A_ALLOWEDIPS: u16 = 9;
+const WGPEER_A_PROTOCOL_VERSION: u16 = 10;
+const WGPEER_F_REMOVE_ME: u32 = 1;
+const WGPEER_F_REPLACE_ALLOWEDIPS: u32 = 2;
+
+const WGALLOWEDIP_A_FAMILY: u16 = 1;
+const WGALLOWEDIP_A_IPADDR: u16 = 2;
+const WGALLOWEDIP_A_CIDR_MASK: u16 = 3;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// A Curve25519 key (private, public or preshared).
+pub type Key = [u8; KEY_LEN];
+
+/// Parses a key in the base64 format of `wg`.
+pub fn parse_key(text: &str) -> Result<Key> {
+    let text = text.trim().as_bytes();
+    if text.len() != 44 || text[43] != b'=' {
+        return Err(Error::new(ErrorKind::InvalidInput, "key is not 32 bytes base64"));
+    }
+    let mut bits = 0_u32;
+    let mut bit_count = 0;
+    let mut key = Vec::with_capacity(KEY_LEN + 1);
+    for c in &text[..43] {
+        let value = BASE64.iter().position(|b| b == c)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "invalid base64 character in key"))?;
+        bits = (bits << 6 | value as u32) & 0xffff;
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            key.push((bits >> bit_count) as u8);
+        }
+    }
+    Ok(key[..KEY_LEN].try_into().unwrap())
+}
+
+/// Formats a key in the base64 format of `wg`.
+pub fn format_key(key: &Key) -> String {
+    let mut text = String::with_capacity(44);
+    for chunk in key.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0_u32, |bits, (i, b)| bits | (*b as u32) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            text.push(BASE64[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+    }
+    text.push('=');
+    text
+}
+
+/// Generates a new clamped private key from the random generator of the operating system. The
+/// public key is derived by the kernel and reported in `WireguardDevice::public_key` once the
+/// private key is configured.
+pub fn generate_private_key() -> Result<Key> {
+    let mut key = [0_u8; KEY_LEN];
+    super::random::secure_random_bytes(&mut key)?;
+    key[0] &= 248;
+    key[31] = (key[31] & 127) | 64;
+    Ok(key)
+}
+
+/// An IP network whose traffic is routed to (and accepted from) a peer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AllowedIp {
+    /// network address
+    pub address: IpAddr,
+
+    /// prefix length
+    pub prefix_len: u8,
+}
+
+/// A peer of a WireGuard device as reported by the kernel.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WireguardPeer {
+    /// public key of the peer
+    pub public_key: Key,
+
+    /// preshared key, None if not set
+    pub preshared_key: Option<Key>,
+
+    /// current endpoint
+    pub endpoint: Option<SocketAddr>,
+
+    /// persistent keepalive interval, None if disabled
+    pub persistent_keepalive: Option<Duration>,
+
+    /// time of the last completed handshake, None if there was none
+    pub last_handshake: Option<SystemTime>,
+
+    /// received bytes
+    pub rx_bytes: u64,
+
+    /// transmitted bytes
+    pub tx_bytes: u64,
+
+    /// allowed IPs
+    pub allowed_ips: Vec<AllowedIp>,
+
+    /// protocol version
+    pub protocol_version: u32,
+}
+
+/// A WireGuard device with its peers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WireguardDevice {
+    /// interface index
+    pub index: u32,
+
+    /// interface name
+    pub name: String,
+
+    /// private key, None if not set
+    pub private_key: Option<Key>,
+
+    /// public key derived from the private key
+    pub public_key: Option<Key>,
+
+    /// UDP listen port, 0 if not yet assigned
+    pub listen_port: u16,
+
+    /// firewall mark of outgoing packets, 0 for none
+    pub fwmark: u32,
+
+    /// peers of the device
+    pub peers: Vec<WireguardPeer>,
+}
+
+/// Configuration of a peer for `configure`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PeerConfig {
+    /// public key identifying the peer
+    pub public_key: Key,
+
+    /// removes the peer instead of adding or updating it
+    pub remove: bool,
+
+    /// preshared key, all zero to remove it, None to keep it unchanged
+    pub preshared_key: Option<Key>,
+
+    /// endpoint, None to keep it unchanged
+    pub endpoint: Option<SocketAddr>,
+
+    /// persistent keepalive interval (whole seconds), zero to disable, None to keep it unchanged
+    pub persistent_keepalive: Option<Duration>,
+
+    /// replaces the allowed IPs of the peer instead of adding to them
+    pub replace_allowed_ips: bool,
+
+    /// allowed IPs to add
+    pub allowed_ips: Vec<AllowedIp>,
+}
+
+/// Configuration of a device for `configure`, unset fields are kept unchanged.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DeviceConfig {
+    /// private key, all zero to remove it
+    pub private_key: Option<Key>,
+
+    /// UDP listen port, 0 for a random port
+    pub listen_port: Option<u16>,
+
+    /// firewall mark, 0 to remove it
+    pub fwmark: Option<u32>,
+
+    /// removes all peers not contained in `peers`
+    pub replace_peers: bool,
+
+    /// peers to add, update or remove
+    pub peers: Vec<PeerConfig>,
+}
+
+/// Creates a WireGuard device with the given name and returns its interface index.
+/// Fails if the kernel has no WireGuard support.
+pub fn create_device(name: &str) -> Result<u32> {
+    Ok(super::link_config::create_link(name, LINK_KIND)?.index)
+}
+
+/// Applies the configuration to the WireGuard device.
+pub fn configure(name: &str, config: &DeviceConfig) -> Result<()> {
+    let mut socket = NetlinkSocket::new(libc::NETLINK_GENERIC, 0)?;
+    let family = socket.generic_family(GENL_NAME)?;
+    socket.request(family, libc::NLM_F_ACK as u16, &encode_config(name, config))?;
+    Ok(())
+}
+
+/// Retrieves the configuration and peer statistics of the WireGuard device.
+pub fn device(name: &str) -> Result<WireguardDevice> {
+    let mut socket = NetlinkSocket::new(libc::NETLINK_GENERIC, 0)?;
+    let family = socket.generic_family(GENL_NAME)?;
+    let request = MessageBuilder::new(&generic_header(WG_CMD_GET_DEVICE, GENL_VERSION))
+        .str_attribute(WGDEVICE_A_IFNAME, name)
+        .into_payload();
+    let messages = socket.request(family, libc::NLM_F_DUMP as u16, &request)?;
+    let mut parts = messages.iter()
+        .filter(|msg| msg.msg_type == family && msg.payload.len() >= GENL_HDRLEN)
+        .map(|msg| &msg.payload[GENL_HDRLEN..]);
+    let mut device = match parts.next() {
+        Some(first) => parse_device(first),
+        None => return Err(Error::new(ErrorKind::NotFound, format!("no WireGuard device {}", name))),
+    };
+    // large devices are split over multiple messages, a peer may continue in the next message
+    for part in parts {
+        for peer in parse_device(part).peers {
+            match device.peers.last_mut() {
+                Some(last) if last.public_key == peer.public_key => last.allowed_ips.extend(peer.allowed_ips),
+                _ => device.peers.push(peer),
+            }
+        }
+    }
+    Ok(device)
+}
+
+/// Retrieves all WireGuard devices of the host.
+pub fn devices() -> Result<Vec<WireguardDevice>> {
+    super::netlink::links()?.iter()
+        .filter(|link| link.kind.as_deref() == Some(LINK_KIND))
+        .filter_map(|link| link.name.as_deref())
+        .map(device)
+        .collect()
+}
+
+impl IpInterface {
+
+    /// Returns the link kind of virtual interfaces (e.g. "bridge", "vlan", "wireguard"), None
+    /// for physical interfaces.
+    pub fn link_kind(&self) -> Result<Option<String>> {
+        Ok(super::link_config::link(&self.name)?.and_then(|link| link.kind))
+    }
+
+    /// Returns the WireGuard configuration and peers of this interface, Ok(None) if it is no
+    /// WireGuard device.
+    pub fn wireguard(&self) -> Result<Option<WireguardDevice>> {
+        if self.link_kind()?.as_deref() != Some(LINK_KIND) {
+            return Ok(None);
+        }
+        device(&self.name).map(Some)
+    }
+}
+
+/// Encodes a WG_CMD_SET_DEVICE request.
+fn encode_config(name: &str, config: &DeviceConfig) -> Vec<u8> {
+    let mut builder = MessageBuilder::new(&generic_header(WG_CMD_SET_DEVICE, GENL_VERSION))
+        .str_attribute(WGDEVICE_A_IFNAME, name);
+    if let Some(key) = &config.private_key {
+        builder = builder.attribute(WGDEVICE_A_PRIVATE_KEY, key);
+    }
+    if let Some(port) = config.listen_port {
+        builder = builder.u16_attribute(WGDEVICE_A_LISTEN_PORT, port);
+    }
+    if let Some(fwmark) = config.fwmark {
+        builder = builder.u32_attribute(WGDEVICE_A_FWMARK, fwmark);
+    }
+    if config.replace_peers {
+        builder = builder.u32_attribute(WGDEVICE_A_FLAGS, WGDEVICE_F_REPLACE_PEERS);
+    }
+    if !config.peers.is_empty() {
+        builder = builder.nested(WGDEVICE_A_PEERS, |peers| {
+            config.peers.iter().fold(peers, |peers, peer| peers.nested(0, |b| encode_peer(b, peer)))
+        });
+    }
+    builder.into_payload()
+}
+
+fn encode_peer(mut builder: MessageBuilder, peer: &PeerConfig) -> MessageBuilder {
+    builder = builder.attribute(WGPEER_A_PUBLIC_KEY, &peer.public_key);
+    let flags = if peer.remove { WGPEER_F_REMOVE_ME } else { 0 }
+        | if peer.replace_allowed_ips { WGPEER_F_REPLACE_ALLOWEDIPS } else { 0 };
+    if flags != 0 {
+        builder = builder.u32_attribute(WGPEER_A_FLAGS, flags);
+    }
+    if let Some(key) = &peer.preshared_key {
+        builder = builder.attribute(WGPEER_A_PRESHARED_KEY, key);
+    }
+    if let Some(endpoint) = peer.endpoint {
+        builder = builder.attribute(WGPEER_A_ENDPOINT, &encode_sockaddr(&endpoint));
+    }
+    if let Some(interval) = peer.persistent_keepalive {
+        builder = builder.u16_attribute(WGPEER_A_PERSISTENT_KEEPALIVE_INTERVAL,
+                                        interval.as_secs().min(u16::MAX as u64) as u16);
+    }
+    if !peer.allowed_ips.is_empty() {
+        builder = builder.nested(WGPEER_A_ALLOWEDIPS, |ips| {
+            peer.allowed_ips.iter().fold(ips, |ips, ip| ips.nested(0, |b| {
+                let (family, octets) = match ip.address {
+                    IpAddr::V4(address) => (libc::AF_INET, address.octets().to_vec()),
+                    IpAddr::V6(address) => (libc::AF_INET6, address.octets().to_vec()),
+                };
+                b.u16_attribute(WGALLOWEDIP_A_FAMILY, family as u16)
+                    .attribute(WGALLOWEDIP_A_IPADDR, &octets)
+                    .u8_attribute(WGALLOWEDIP_A_CIDR_MASK, ip.prefix_len)
+            }))
+        });
+    }
+    builder
+}
+
+/// Parses the attributes of a WG_CMD_GET_DEVICE response.
+fn parse_device(data: &[u8]) -> WireguardDevice {
+    let mut device = WireguardDevice {
+        index: 0, name: String::new(), private_key: None, public_key: None, listen_port: 0, fwmark: 0,
+        peers: Vec::new(),
+    };
+    for (attr_type, data) in AttributeIter::new(data) {
+        match attr_type {
+            WGDEVICE_A_IFINDEX => device.index = u32_from(data).unwrap_or(0),
+            WGDEVICE_A_IFNAME => device.name = string_from(data).unwrap_or_default(),
+            WGDEVICE_A_PRIVATE_KEY => device.private_key = key_from(data),
+            WGDEVICE_A_PUBLIC_KEY => device.public_key = key_from(data),
+            WGDEVICE_A_LISTEN_PORT => device.listen_port = u16_from(data).unwrap_or(0),
+            WGDEVICE_A_FWMARK => device.fwmark = u32_from(data).unwrap_or(0),
+            WGDEVICE_A_PEERS => device.peers = AttributeIter::new(data).filter_map(|(_, d)| parse_peer(d)).collect(),
+            _ => {},
+        }
+    }
+    device
+}
+
+fn parse_peer(data: &[u8]) -> Option<WireguardPeer> {
+    let mut peer = WireguardPeer {
+        public_key: [0; KEY_LEN], preshared_key: None, endpoint: None, persistent_keepalive: None,
+        last_handshake: None, rx_bytes: 0, tx_bytes: 0, allowed_ips: Vec::new(), protocol_version: 0,
+    };
+    let mut has_key = false;
+    for (attr_type, data) in AttributeIter::new(data) {
+        match attr_type {
+            WGPEER_A_PUBLIC_KEY => if let Some(key) = key_from(data) {
+                peer.public_key = key;
+                has_key = true;
+            },
+            WGPEER_A_PRESHARED_KEY => peer.preshared_key = key_from(data).filter(|key| key.iter().any(|b| *b != 0)),
+            WGPEER_A_ENDPOINT => peer.endpoint = sockaddr_from(data),
+            WGPEER_A_PERSISTENT_KEEPALIVE_INTERVAL => peer.persistent_keepalive = u16_from(data)
+                .filter(|seconds| *seconds != 0)
+                .map(|seconds| Duration::from_secs(seconds as u64)),
+            WGPEER_A_LAST_HANDSHAKE_TIME if data.len() >= 16 => {
+                let seconds = i64::from_ne_bytes(data[0..8].try_into().unwrap());
+                let nanos = i64::from_ne_bytes(data[8..16].try_into().unwrap());
+                if seconds > 0 || nanos > 0 {
+                    peer.last_handshake = Some(UNIX_EPOCH + Duration::new(seconds as u64, nanos as u32));
+                }
+            },
+            WGPEER_A_RX_BYTES => peer.rx_bytes = u64_from(data).unwrap_or(0),
+            WGPEER_A_TX_BYTES => peer.tx_bytes = u64_from(data).unwrap_or(0),
+            WGPEER_A_ALLOWEDIPS => peer.allowed_ips = AttributeIter::new(data)
+                .filter_map(|(_, d)| parse_allowed_ip(d))
+                .collect(),
+            WGPEER_A_PROTOCOL_VERSION => peer.protocol_version = u32_from(data).unwrap_or(0),
+            _ => {},
+        }
+    }
+    if has_key { Some(peer) } else { None }
+}
+
+fn parse_allowed_ip(data: &[u8]) -> Option<AllowedIp> {
+    let (mut family, mut address, mut prefix_len) = (None, None, None);
+    for (attr_type, data) in AttributeIter::new(data) {
+        match attr_type {
+            WGALLOWEDIP_A_FAMILY => family = u16_from(data),
+            WGALLOWEDIP_A_IPADDR => address = Some(data),
+            WGALLOWEDIP_A_CIDR_MASK => prefix_len = data.first().copied(),
+            _ => {},
+        }
+    }
+    Some(AllowedIp { address: ip_address_from(family? as i32, address?)?, prefix_len: prefix_len? })
+}
+
+/// Encodes the endpoint as struct sockaddr_in or sockaddr_in6.
+fn encode_sockaddr(address: &SocketAddr) -> Vec<u8> {
+    let mut data = Vec::with_capacity(28);
+    match address {
+        SocketAddr::V4(address) => {
+            data.extend_from_slice(&(libc::AF_INET as u16).to_ne_bytes());
+            data.extend_from_slice(&address.port().to_be_bytes());
+            data.extend_from_slice(&address.ip().octets());
+            data.extend_from_slice(&[0; 8]);
+        },
+        SocketAddr::V6(address) => {
+            data.extend_from_slice(&(libc::AF_INET6 as u16).to_ne_bytes());
+            data.extend_from_slice(&address.port().to_be_bytes());
+            data.extend_from_slice(&address.flowinfo().to_be_bytes());
+            data.extend_from_slice(&address.ip().octets());
+            data.extend_from_slice(&address.scope_id().to_ne_bytes());
+        },
+    }
+    data
+}
+
+fn sockaddr_from(data: &[u8]) -> Option<SocketAddr> {
+    let family = u16_from(data)? as i32;
+    let port = u16::from_be_bytes(data.get(2..4)?.try_into().unwrap());
+    match family {
+        libc::AF_INET if data.len() >= 8 => {
+            let octets: [u8; 4] = data[4..8].try_into().unwrap();
+            Some(SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::from(octets), port)))
+        },
+        libc::AF_INET6 if data.len() >= 28 => {
+            let flowinfo = u32::from_be_bytes(data[4..8].try_into().unwrap());
+            let octets: [u8; 16] = data[8..24].try_into().unwrap();
+            let scope_id = u32::from_ne_bytes(data[24..28].try_into().unwrap());
+            Some(SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::from(octets), port, flowinfo, scope_id)))
+        },
+        _ => None,
+    }
+}
+
+fn key_from(data: &[u8]) -> Option<Key> {
+    data.get(0..KEY_LEN).map(|d| d.try_into().unwrap())
+}
+
+fn u16_from(data: &[u8]) -> Option<u16> {
+    data.get(0..2).map(|d| u16::from_ne_bytes(d.try_into().unwrap()))
+}
+
+fn u64_from(data: &[u8]) -> Option<u64> {
+    data.get(0..8).map(|d| u64::from_ne_bytes(d.try_into().unwrap()))
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_key_base64() {
+        let key = parse_key("yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=").unwrap();
+        assert_eq!(key[0], 0xc8);
+        assert_eq!(key[31], 0x69);
+        assert_eq!(format_key(&key), "yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=");
+        assert_eq!(format_key(&[0; KEY_LEN]), "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=");
+        assert!(parse_key("yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk").is_err());
+        assert!(parse_key("yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBm!=").is_err());
+    }
+
+    #[test]
+    fn test_generate_private_key() {
+        let key = generate_private_key().unwrap();
+        assert_eq!(key[0] & 7, 0);
+        assert_eq!(key[31] & 0xc0, 0x40);
+    }
+
+    #[test]
+    fn test_sockaddr() {
+        for address in ["192.0.2.1:51820", "[fe80::1%3]:51820"].iter() {
+            let address: SocketAddr = address.parse().unwrap();
+            let data = encode_sockaddr(&address);
+            assert_eq!(data.len(), if address.is_ipv4() { 16 } else { 28 });
+            assert_eq!(sockaddr_from(&data), Some(address));
+        }
+    }
+
+    #[test]
+    fn test_config_roundtrip() {
+        // the configuration request uses the same attributes as the device response
+        let peer_key = parse_key("xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=").unwrap();
+        let config = DeviceConfig {
+            private_key: Some([1; KEY_LEN]),
+            listen_port: Some(51820),
+            fwmark: None,
+            replace_peers: true,
+            peers: vec![PeerConfig {
+                public_key: peer_key,
+                endpoint: Some("192.0.2.1:51820".parse().unwrap()),
+                persistent_keepalive: Some(Duration::from_secs(25)),
+                allowed_ips: vec![
+                    AllowedIp { address: "10.0.0.0".parse().unwrap(), prefix_len: 24 },
+                    AllowedIp { address: "fd00::".parse().unwrap(), prefix_len: 64 },
+                ],
+                ..PeerConfig::default()
+            }],
+        };
+        let payload = encode_config("wg0", &config);
+        assert_eq!(&payload[..GENL_HDRLEN], &[WG_CMD_SET_DEVICE, GENL_VERSION, 0, 0]);
+        let device = parse_device(&payload[GENL_HDRLEN..]);
+        assert_eq!(device.name, "wg0");
+        assert_eq!(device.private_key, Some([1; KEY_LEN]));
+        assert_eq!(device.listen_port, 51820);
+        assert_eq!(device.peers.len(), 1);
+        let peer = &device.peers[0];
+        assert_eq!(peer.public_key, peer_key);
+        assert_eq!(peer.endpoint, Some("192.0.2.1:51820".parse().unwrap()));
+        assert_eq!(peer.persistent_keepalive, Some(Duration::from_secs(25)));
+        assert_eq!(peer.allowed_ips, config.peers[0].allowed_ips);
+        assert_eq!(peer.last_handshake, None);
+    }
+
+    #[test]
+    fn test_parse_peer_statistics() {
+        let mut handshake = 1_700_000_000_i64.to_ne_bytes().to_vec();
+        handshake.extend_from_slice(&5_i64.to_ne_bytes());
+        let data = MessageBuilder::default()
+            .attribute(WGPEER_A_PUBLIC_KEY, &[2; KEY_LEN])
+            .attribute(WGPEER_A_PRESHARED_KEY, &[0; KEY_LEN])
+            .attribute(WGPEER_A_LAST_HANDSHAKE_TIME, &handshake)
+            .attribute(WGPEER_A_RX_BYTES, &1234_u64.to_ne_bytes())
+            .attribute(WGPEER_A_TX_BYTES, &5678_u64.to_ne_bytes())
+            .u16_attribute(WGPEER_A_PERSISTENT_KEEPALIVE_INTERVAL, 0)
+            .u32_attribute(WGPEER_A_PROTOCOL_VERSION, 1)
+            .into_payload();
+        let peer = parse_peer(&data).unwrap();
+        assert_eq!(peer.preshared_key, None);
+        assert_eq!(peer.persistent_keepalive, None);
+        assert_eq!(peer.last_handshake, Some(UNIX_EPOCH + Duration::new(1_700_000_000, 5)));
+        assert_eq!((peer.rx_bytes, peer.tx_bytes, peer.protocol_version), (1234, 5678, 1));
+        assert!(parse_peer(&MessageBuilder::default().u32_attribute(WGPEER_A_RX_BYTES, 1).into_payload()).is_none());
+    }
+}
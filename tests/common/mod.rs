@@ -0,0 +1,11 @@
+use std::io::{ErrorKind, Result};
+
+/// Returns the value of a multicast send or query, None on hosts without multicast route, on
+/// which the test is skipped.
+pub fn multicast_routed<T>(result: Result<T>) -> Option<T> {
+    match result {
+        Ok(value) => Some(value),
+        Err(e) if e.kind() == ErrorKind::NetworkUnreachable => None,
+        Err(e) => panic!("{}", e),
+    }
+}
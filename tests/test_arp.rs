@@ -0,0 +1,126 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::{arp, IpInterface};
+use std::{net::Ipv4Addr, time::Duration};
+
+const OTHER_MAC: [u8; 6] = [0x02, 0, 0, 0, 0, 0x99];
+
+/// Opens a packet socket for ARP on the loopback interface, None without CAP_NET_RAW.
+fn arp_socket() -> Option<(libc::c_int, libc::sockaddr_ll)> {
+    let socket = unsafe { libc::socket(libc::AF_PACKET, libc::SOCK_DGRAM, (arp::ETHERTYPE_ARP.to_be()) as i32) };
+    if socket < 0 {
+        return None;
+    }
+    let mut address: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+    address.sll_family = libc::AF_PACKET as u16;
+    address.sll_protocol = arp::ETHERTYPE_ARP.to_be();
+    address.sll_ifindex = unsafe { libc::if_nametoindex(b"lo\0".as_ptr() as *const libc::c_char) } as i32;
+    address.sll_halen = 6;
+    address.sll_addr[..6].copy_from_slice(&arp::BROADCAST_MAC);
+    assert_eq!(unsafe { libc::bind(socket, &address as *const _ as *const libc::sockaddr,
+                                   std::mem::size_of::<libc::sockaddr_ll>() as u32) }, 0);
+    Some((socket, address))
+}
+
+fn recv_arp(socket: libc::c_int) -> Vec<u8> {
+    let mut buffer = [0_u8; 128];
+    let len = unsafe { libc::recv(socket, buffer.as_mut_ptr() as *mut libc::c_void, buffer.len(), 0) };
+    assert!(len >= 28);
+    buffer[..len as usize].to_vec()
+}
+
+#[test]
+fn test_arp_announce() {
+    let (socket, _) = match arp_socket() {
+        Some(socket) => socket,
+        None => return,
+    };
+    let address = Ipv4Addr::new(192, 0, 2, 201);
+    arp::announce("lo", &address).unwrap();
+    let packet = loop {
+        let packet = recv_arp(socket);
+        if packet[24..28] == address.octets() {
+            break packet;
+        }
+    };
+    unsafe { libc::close(socket) };
+    assert_eq!(&packet[6..8], &[0, 1]);
+    assert_eq!(&packet[14..18], &address.octets());
+}
+
+#[test]
+fn test_arp_probe() {
+    let (socket, reply_to) = match arp_socket() {
+        Some(socket) => socket,
+        None => return,
+    };
+    let used = Ipv4Addr::new(192, 0, 2, 202);
+    std::thread::spawn(move || {
+        // answers probes for the used address as the host owning it
+        loop {
+            let request = recv_arp(socket);
+            if request[6..8] != [0, 1] || request[24..28] != used.octets() || request[8..14] == OTHER_MAC {
+                continue;
+            }
+            let mut reply = request.clone();
+            reply[7] = 2;
+            reply[8..14].copy_from_slice(&OTHER_MAC);
+            reply[14..18].copy_from_slice(&used.octets());
+            reply[18..24].copy_from_slice(&request[8..14]);
+            reply[24..28].copy_from_slice(&request[14..18]);
+            unsafe {
+                libc::sendto(socket, reply.as_ptr() as *const libc::c_void, 28, 0,
+                             &reply_to as *const _ as *const libc::sockaddr,
+                             std::mem::size_of::<libc::sockaddr_ll>() as u32);
+            }
+        }
+    });
+
+    assert_eq!(arp::probe("lo", &used, Duration::from_secs(2)).unwrap(), Some(OTHER_MAC));
+    assert_eq!(arp::probe("lo", &Ipv4Addr::new(192, 0, 2, 203), Duration::from_millis(200)).unwrap(), None);
+}
+
+#[test]
+fn test_mac_address() {
+    for interface in IpInterface::retrieve_ip_interfaces().unwrap() {
+        let mac = interface.mac_address().unwrap();
+        if interface.is_loopback() {
+            assert_eq!(mac, None);
+        }
+    }
+}
+
+#[test]
+fn test_arp_scan() {
+    let (socket, reply_to) = match arp_socket() {
+        Some(socket) => socket,
+        None => return,
+    };
+    let hosts = [Ipv4Addr::new(192, 0, 2, 211), Ipv4Addr::new(192, 0, 2, 214)];
+    std::thread::spawn(move || {
+        loop {
+            let request = recv_arp(socket);
+            let target = Ipv4Addr::new(request[24], request[25], request[26], request[27]);
+            if request[6..8] != [0, 1] || !hosts.contains(&target) {
+                continue;
+            }
+            let mut reply = request.clone();
+            reply[7] = 2;
+            reply[8..14].copy_from_slice(&OTHER_MAC);
+            reply[13] = request[27];
+            reply[14..18].copy_from_slice(&target.octets());
+            reply[18..24].copy_from_slice(&request[8..14]);
+            reply[24..28].copy_from_slice(&request[14..18]);
+            unsafe {
+                libc::sendto(socket, reply.as_ptr() as *const libc::c_void, 28, 0,
+                             &reply_to as *const _ as *const libc::sockaddr,
+                             std::mem::size_of::<libc::sockaddr_ll>() as u32);
+            }
+        }
+    });
+
+    let opts = arp::ScanOpts { concurrency: 2, timeout: Duration::from_millis(200), retries: 0, ..arp::ScanOpts::default() };
+    let found = arp::scan("lo", &Ipv4Addr::new(192, 0, 2, 208), 29, &opts).unwrap();
+    assert_eq!(found, vec![(hosts[0], [0x02, 0, 0, 0, 0, 211]), (hosts[1], [0x02, 0, 0, 0, 0, 214])]);
+    assert!(arp::scan("lo", &Ipv4Addr::new(10, 0, 0, 0), 8, &opts).is_err());
+}
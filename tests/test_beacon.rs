@@ -0,0 +1,59 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::beacon::{Beacon, BeaconBuilder, BeaconEvent};
+use std::{net::SocketAddr, time::Duration};
+
+fn beacon(payload: &[u8]) -> Beacon {
+    let group: SocketAddr = "239.255.77.9:1915".parse().unwrap();
+    BeaconBuilder::new(group, "127.0.0.1".parse().unwrap())
+        .interval(Duration::from_millis(200))
+        .payload(payload.to_vec())
+        .build()
+        .unwrap()
+}
+
+/// Receives with the beacon until an event matches, None after twenty calls without.
+fn wait_for<F: Fn(&BeaconEvent) -> bool>(beacon: &mut Beacon, matches: F) -> Option<BeaconEvent> {
+    (0..20).flat_map(|_| beacon.recv().unwrap()).find(|event| matches(event))
+}
+
+#[test]
+fn test_beacons() {
+    let mut a = beacon(b"a");
+    let mut b = beacon(b"b");
+    let id = b.node_id();
+    b.announce().unwrap();
+    match wait_for(&mut a, |event| matches!(event, BeaconEvent::Appeared(peer) if peer.node_id == id)) {
+        Some(BeaconEvent::Appeared(peer)) => assert_eq!(peer.payload, b"b"),
+        event => panic!("{:?}", event),
+    }
+    assert_eq!(a.peers().len(), 1);
+    assert_eq!(a.peer(id).unwrap().interface, "127.0.0.1".parse::<std::net::IpAddr>().unwrap());
+
+    b.set_payload(b"b2".to_vec()).unwrap();
+    wait_for(&mut a, |event| matches!(event, BeaconEvent::Changed(peer) if peer.payload == b"b2")).unwrap();
+    b.goodbye().unwrap();
+    wait_for(&mut a, |event| matches!(event, BeaconEvent::Left(peer) if peer.node_id == id)).unwrap();
+    assert!(a.peer(id).is_none());
+
+    // a silent peer expires after three intervals
+    b.announce().unwrap();
+    drop(b);
+    wait_for(&mut a, |event| matches!(event, BeaconEvent::Appeared(peer) if peer.node_id == id)).unwrap();
+    wait_for(&mut a, |event| matches!(event, BeaconEvent::Expired(peer) if peer.node_id == id)).unwrap();
+    assert!(a.peers().is_empty());
+}
+
+#[test]
+fn test_signed_beacons() {
+    let group: SocketAddr = "239.255.77.10:1916".parse().unwrap();
+    let builder = BeaconBuilder::new(group, "127.0.0.1".parse().unwrap()).interval(Duration::from_millis(100));
+    let mut signed = builder.clone().key(b"secret".to_vec()).build().unwrap();
+    let mut unsigned = builder.clone().build().unwrap();
+    let mut other = builder.key(b"secret".to_vec()).build().unwrap();
+    unsigned.announce().unwrap();
+    other.announce().unwrap();
+    let id = other.node_id();
+    wait_for(&mut signed, |event| matches!(event, BeaconEvent::Appeared(peer) if peer.node_id == id)).unwrap();
+    assert!(signed.peer(unsigned.node_id()).is_none());
+}
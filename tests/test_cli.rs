@@ -0,0 +1,36 @@
+#![cfg(all(feature = "cli", target_os = "linux"))]
+
+use std::{process::{Command, Stdio}, thread, time::Duration};
+
+const NETU: &str = env!("CARGO_BIN_EXE_netu");
+
+#[test]
+fn test_if_list() {
+    let output = Command::new(NETU).args(["if", "list"]).output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.lines().any(|line| line.contains(" lo ") && line.contains("127.0.0.1/8")), "{}", stdout);
+}
+
+#[test]
+fn test_mc_send_recv() {
+    let receiver = Command::new(NETU).args(["mc", "recv", "239.255.77.6:1912", "--iface", "lo", "--count", "1",
+                                            "--timeout", "5"])
+        .stdout(Stdio::piped()).stderr(Stdio::null()).spawn().unwrap();
+    thread::sleep(Duration::from_millis(300));
+    let sender = Command::new(NETU).args(["mc", "send", "239.255.77.6:1912", "hello", "--iface", "127.0.0.1",
+                                          "--count", "3", "--interval", "100"])
+        .output().unwrap();
+    assert!(sender.status.success());
+    let output = receiver.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("5 bytes from ") && stdout.trim_end().ends_with(": hello"), "{}", stdout);
+}
+
+#[test]
+fn test_usage() {
+    let output = Command::new(NETU).args(["mc", "recv"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("usage:"));
+}
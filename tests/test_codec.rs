@@ -0,0 +1,58 @@
+#![cfg(all(target_os = "linux", feature = "codec"))]
+
+use net_utils::codec::{RawCodec, TypedSender};
+use std::net::UdpSocket;
+
+#[test]
+fn test_typed_sender() {
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut sender = TypedSender::new(UdpSocket::bind("127.0.0.1:0").unwrap(), receiver.local_addr().unwrap(), RawCodec)
+        .message_type(5);
+    sender.send(&vec![1, 2, 3]).unwrap();
+    assert!(sender.send(&vec![0; 70000]).is_err());
+    let mut buf = [0_u8; 64];
+    let len = receiver.recv(&mut buf).unwrap();
+    assert_eq!(&buf[..len], b"NT\x00\x05\x00\x00\x00\x03\x01\x02\x03");
+    assert_eq!(sender.stats().datagrams_sent, 1);
+}
+
+#[cfg(feature = "tokio-net")]
+#[tokio::test]
+async fn test_typed_multicast_receiver() {
+    use net_utils::{MulticastSocketBuilder, codec::{TypedMulticastReceiver, Utf8Codec}};
+    use std::net::Ipv4Addr;
+    let group = "239.255.77.13:1919".parse().unwrap();
+    let socket = MulticastSocketBuilder::new(group, "127.0.0.1".parse().unwrap()).build_tokio().unwrap();
+    let mut receiver = TypedMulticastReceiver::new(socket, Utf8Codec).message_type(1);
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket2::SockRef::from(&socket).set_multicast_if_v4(&Ipv4Addr::LOCALHOST).unwrap();
+    let source = socket.local_addr().unwrap();
+    let mut other = TypedSender::new(socket.try_clone().unwrap(), group, Utf8Codec).message_type(2);
+    let mut sender = TypedSender::new(socket, group, Utf8Codec).message_type(1);
+    other.send(&"other type".to_string()).unwrap();
+    sender.get_ref().send_to(b"NT\x00\x01\x00\x00\x00\x01\xff", group).unwrap();
+    sender.send(&"temperature 21.5".to_string()).unwrap();
+
+    let (message, from) = tokio::time::timeout(std::time::Duration::from_secs(2), receiver.recv()).await
+        .unwrap()
+        .unwrap();
+    assert_eq!((message.as_str(), from), ("temperature 21.5", source));
+    // the other message type and the invalid UTF-8 are dropped
+    assert_eq!(receiver.rejected(), 2);
+}
+
+#[cfg(all(feature = "json", feature = "tokio-net"))]
+#[tokio::test]
+async fn test_json_messages() {
+    use net_utils::codec::TypedMulticastReceiver;
+    let socket = tokio::net::UdpSocket::bind("[::1]:0").await.unwrap();
+    let mut receiver = TypedMulticastReceiver::<(String, f64)>::json(socket);
+    let destination = receiver.get_ref().local_addr().unwrap();
+    let mut sender = TypedSender::<(String, f64)>::json(UdpSocket::bind("[::1]:0").unwrap(), destination);
+    sender.send(&("temperature".to_string(), 21.5)).unwrap();
+    let (message, _) = tokio::time::timeout(std::time::Duration::from_secs(2), receiver.recv()).await
+        .unwrap()
+        .unwrap();
+    assert_eq!(message, ("temperature".to_string(), 21.5));
+}
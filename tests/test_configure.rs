@@ -0,0 +1,43 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::{configure, link_config};
+use std::{io::ErrorKind, time::Duration};
+
+#[test]
+fn test_add_address_and_wait_dad() {
+    let address = "2001:db8:77::1".parse().unwrap();
+    let result = match configure::add_address_and_wait_dad("eth0", address, 64, Duration::from_secs(5)) {
+        Ok(result) => result,
+        Err(e) if e.kind() == ErrorKind::PermissionDenied || e.kind() == ErrorKind::NotFound => return,
+        Err(e) => panic!("{}", e),
+    };
+    configure::delete_address("eth0", address, 64).unwrap();
+    match result {
+        configure::DadResult::Usable(info) => {
+            assert_eq!(info.address, address);
+            assert!(info.is_usable());
+        },
+        configure::DadResult::Failed(info) => panic!("duplicate address {:?}", info),
+    }
+}
+
+#[test]
+fn test_dad_timeout() {
+    // DAD does not start on a bridge without ports (no carrier), IPv4 addresses need none
+    match link_config::create_bridge("nutest-br2", false) {
+        Ok(_) => {},
+        Err(e) if e.kind() == ErrorKind::PermissionDenied || e.raw_os_error() == Some(libc::EOPNOTSUPP) => return,
+        Err(e) => panic!("{}", e),
+    }
+    let result = link_config::set_link_up("nutest-br2", true).map(|_| {
+        let v6 = configure::add_address_and_wait_dad("nutest-br2", "2001:db8:78::1".parse().unwrap(), 64,
+                                                       Duration::from_millis(200));
+        let v4 = configure::add_address_and_wait_dad("nutest-br2", "192.0.2.78".parse().unwrap(), 24,
+                                                       Duration::from_millis(200));
+        (v6, v4)
+    });
+    link_config::delete_link("nutest-br2").unwrap();
+    let (v6, v4) = result.unwrap();
+    assert_eq!(v6.unwrap_err().kind(), ErrorKind::TimedOut);
+    assert!(matches!(v4.unwrap(), configure::DadResult::Usable(_)));
+}
@@ -0,0 +1,34 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::connectivity::{Connectivity, ConnectivityMonitor, ConnectivityOpts, check_interface};
+use std::{io::ErrorKind, time::Duration};
+
+fn opts() -> ConnectivityOpts {
+    ConnectivityOpts { timeout: Duration::from_millis(200), ..ConnectivityOpts::default() }
+}
+
+#[test]
+fn test_check_interface() {
+    // no default route via loopback
+    let result = check_interface("lo", &opts()).unwrap();
+    assert_eq!((result.index, result.gateway, result.connectivity), (1, None, Connectivity::None));
+    assert!(!result.dns_resolved);
+
+    assert_eq!(check_interface("nonexistent0", &opts()).unwrap_err().kind(), ErrorKind::NotFound);
+    let invalid = ConnectivityOpts { probe_url: Some("https://example.com/".to_string()), ..opts() };
+    assert_eq!(check_interface("lo", &invalid).unwrap_err().kind(), ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_monitor() {
+    assert_eq!(ConnectivityMonitor::new(opts(), Duration::ZERO).unwrap_err().kind(), ErrorKind::InvalidInput);
+    let mut monitor = ConnectivityMonitor::new(opts(), Duration::from_millis(100)).unwrap();
+    // all interfaces are reported by the first check
+    let changes = monitor.recv().unwrap();
+    assert_eq!(changes.len(), monitor.interfaces().len());
+    assert!(changes.iter().all(|change| change.previous.is_none()));
+    assert!(monitor.get(1).is_none());
+    for change in monitor.recv().unwrap() {
+        assert!(change.previous.is_some());
+    }
+}
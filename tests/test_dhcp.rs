@@ -0,0 +1,69 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::dhcp::{self, DhcpOpts};
+use std::{
+    io::ErrorKind,
+    net::{Ipv4Addr, UdpSocket},
+    time::Duration,
+};
+
+/// Returns the option with the code from a DHCP message.
+fn option(message: &[u8], code: u8) -> Option<&[u8]> {
+    let mut options = &message[240..];
+    while options.len() >= 2 && options[0] != 255 {
+        if options[0] == code {
+            return Some(&options[2..2 + options[1] as usize]);
+        }
+        options = &options[2 + options[1] as usize..];
+    }
+    None
+}
+
+fn reply(request: &[u8], message_type: u8) -> Vec<u8> {
+    let mut reply = request[..240].to_vec();
+    reply[0] = 2;
+    reply[16..20].copy_from_slice(&[10, 1, 2, 3]);
+    reply.extend_from_slice(&[53, 1, message_type, 54, 4, 127, 0, 0, 1, 1, 4, 255, 0, 0, 0, 51, 4, 0, 0, 0x0e, 0x10, 255]);
+    reply
+}
+
+/// Serves a DHCP exchange on the loopback interface.
+#[test]
+fn test_dhcp_obtain_lease() {
+    let server = match UdpSocket::bind((Ipv4Addr::UNSPECIFIED, dhcp::DHCP_SERVER_PORT)) {
+        Ok(server) => server,
+        // the port is privileged or a DHCP server runs on the host
+        Err(e) if matches!(e.kind(), ErrorKind::PermissionDenied | ErrorKind::AddrInUse) => return,
+        Err(e) => panic!("{}", e),
+    };
+    server.set_broadcast(true).unwrap();
+    server.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    // broadcasts are routed to the default interface otherwise
+    socket2::SockRef::from(&server).bind_device(Some(b"lo")).unwrap();
+    let server = std::thread::spawn(move || {
+        let mut request = [0_u8; 1500];
+        let (len, _) = server.recv_from(&mut request).unwrap();
+        assert_eq!(option(&request[..len], 53), Some(&[1][..]));
+        server.send_to(&reply(&request[..len], 2), (Ipv4Addr::BROADCAST, dhcp::DHCP_CLIENT_PORT)).unwrap();
+        let (len, _) = server.recv_from(&mut request).unwrap();
+        assert_eq!(option(&request[..len], 53), Some(&[3][..]));
+        assert_eq!(option(&request[..len], 50), Some(&[10, 1, 2, 3][..]));
+        assert_eq!(option(&request[..len], 12), Some(&b"client"[..]));
+        server.send_to(&reply(&request[..len], 5), (Ipv4Addr::BROADCAST, dhcp::DHCP_CLIENT_PORT)).unwrap();
+    });
+
+    let opts = DhcpOpts { timeout: Duration::from_secs(5), hostname: Some("client".to_string()) };
+    let lease = dhcp::obtain_lease("lo", &opts).unwrap();
+    assert_eq!(lease.address, Ipv4Addr::new(10, 1, 2, 3));
+    assert_eq!(lease.server, Ipv4Addr::LOCALHOST);
+    assert_eq!(lease.subnet_mask, Some(Ipv4Addr::new(255, 0, 0, 0)));
+    assert_eq!(lease.lease_time, Some(Duration::from_secs(3600)));
+    server.join().unwrap();
+}
+
+#[test]
+fn test_dhcp_lease_of_static_address() {
+    let interfaces = net_utils::IpInterface::retrieve_ip_interfaces().unwrap();
+    let lo = interfaces.iter().find(|i| i.is_loopback() && i.address.is_ipv4()).unwrap();
+    assert_eq!(lo.dhcp_lease().unwrap(), None);
+}
@@ -0,0 +1,84 @@
+#![cfg(unix)]
+
+use net_utils::dns;
+
+#[test]
+fn test_system_resolvers() {
+    let resolvers = dns::system_resolvers().unwrap();
+    for resolver in &resolvers {
+        assert!(!resolver.address.is_unspecified());
+    }
+}
+
+/// Starts a DNS server answering every query with an A record of the client address, after
+/// ignoring the first `drop` queries.
+fn fake_server(drop: usize) -> std::net::SocketAddr {
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let address = socket.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let mut buffer = [0_u8; 512];
+        let mut received = 0;
+        loop {
+            let (len, from) = socket.recv_from(&mut buffer).unwrap();
+            received += 1;
+            if received <= drop {
+                continue;
+            }
+            let mut message = dns::DnsMessage::parse(&buffer[..len]).unwrap();
+            message.flags |= dns::FLAG_RESPONSE;
+            let client = match from.ip() {
+                std::net::IpAddr::V4(address) => address,
+                _ => unreachable!(),
+            };
+            message.answers.push(dns::DnsRecord {
+                name: message.questions[0].name.clone(), record_type: dns::TYPE_A, class: dns::CLASS_IN, ttl: 60,
+                data: dns::RecordData::A(client),
+            });
+            socket.send_to(&message.encode().unwrap(), from).ok();
+        }
+    });
+    address
+}
+
+#[cfg(feature = "tokio-net")]
+#[tokio::test]
+async fn test_query() {
+    let opts = dns::DnsOpts { server: Some(fake_server(1)), ..dns::DnsOpts::default() };
+    let response = dns::query("host.example", dns::TYPE_A, &opts).await.unwrap();
+    assert_eq!(response.rcode(), dns::RCODE_NOERROR);
+    assert_eq!(response.answers.len(), 1);
+    assert_eq!(response.answers[0].name, "host.example");
+    assert_eq!(response.answers[0].data, dns::RecordData::A(std::net::Ipv4Addr::LOCALHOST));
+}
+
+#[test]
+fn test_query_blocking() {
+    let opts = dns::DnsOpts { server: Some(fake_server(1)), ..dns::DnsOpts::default() };
+    let response = dns::query_blocking("host.example", dns::TYPE_A, &opts).unwrap();
+    assert_eq!(response.answers.len(), 1);
+    assert_eq!(response.answers[0].data, dns::RecordData::A(std::net::Ipv4Addr::LOCALHOST));
+}
+
+#[cfg(all(feature = "tokio-net", target_os = "linux"))]
+#[tokio::test]
+async fn test_query_pinned_to_interface() {
+    let opts = dns::DnsOpts { server: Some(fake_server(0)), interface: Some("lo".to_string()), ..dns::DnsOpts::default() };
+    match dns::query("host.example", dns::TYPE_A, &opts).await {
+        Ok(response) => assert_eq!(response.answers.len(), 1),
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {},
+        Err(e) => panic!("{}", e),
+    }
+}
+
+#[cfg(feature = "tokio-net")]
+#[tokio::test]
+async fn test_query_timeout() {
+    let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let opts = dns::DnsOpts {
+        server: Some(silent.local_addr().unwrap()),
+        timeout: std::time::Duration::from_millis(300),
+        ..dns::DnsOpts::default()
+    };
+    let error = dns::query("host.example", dns::TYPE_A, &opts).await.unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::TimedOut);
+}
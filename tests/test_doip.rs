@@ -0,0 +1,102 @@
+use net_utils::doip::{self, DoipConnection, DoipMessage, DoipOpts};
+use std::{
+    io::{ErrorKind, Read, Write},
+    net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream, UdpSocket},
+    time::Duration,
+};
+
+fn read_message(stream: &mut TcpStream) -> DoipMessage {
+    let mut header = [0_u8; doip::HEADER_LEN];
+    stream.read_exact(&mut header).unwrap();
+    let len = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    let mut message = header.to_vec();
+    message.resize(doip::HEADER_LEN + len, 0);
+    stream.read_exact(&mut message[doip::HEADER_LEN..]).unwrap();
+    DoipMessage::parse(&message).unwrap().0
+}
+
+fn write_message(stream: &mut TcpStream, payload_type: u16, payload: Vec<u8>) {
+    let message = DoipMessage { protocol_version: doip::PROTOCOL_VERSION_2012, payload_type, payload };
+    stream.write_all(&message.encode()).unwrap();
+}
+
+/// Accepts one connection like a DoIP entity with address 0x1001 that answers routing activation
+/// with `code`, answers one diagnostic message after an alive check.
+fn fake_entity(code: u8) -> (SocketAddr, std::thread::JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let thread = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let request = read_message(&mut stream);
+        assert_eq!(request.payload_type, doip::TYPE_ROUTING_ACTIVATION_REQUEST);
+        assert_eq!(request.payload, [0x0e, 0x80, 0, 0, 0, 0, 0]);
+        let response = vec![0x0e, 0x80, 0x10, 0x01, code, 0, 0, 0, 0];
+        write_message(&mut stream, doip::TYPE_ROUTING_ACTIVATION_RESPONSE, response);
+        if code != doip::ROUTING_SUCCESS {
+            return;
+        }
+        let request = read_message(&mut stream);
+        assert_eq!(request.payload_type, doip::TYPE_DIAGNOSTIC_MESSAGE);
+        write_message(&mut stream, doip::TYPE_ALIVE_CHECK_REQUEST, Vec::new());
+        let response = read_message(&mut stream);
+        assert_eq!((response.payload_type, response.payload), (doip::TYPE_ALIVE_CHECK_RESPONSE, vec![0x0e, 0x80]));
+        write_message(&mut stream, doip::TYPE_DIAGNOSTIC_ACK, vec![0x10, 0x01, 0x0e, 0x80, 0]);
+        let mut answer = vec![0x10, 0x01, 0x0e, 0x80];
+        answer.extend_from_slice(&[0x50, 0x03]);
+        write_message(&mut stream, doip::TYPE_DIAGNOSTIC_MESSAGE, answer);
+    });
+    (address, thread)
+}
+
+#[test]
+fn test_routing_activation_and_diagnostics() {
+    let (entity, thread) = fake_entity(doip::ROUTING_SUCCESS);
+    let mut connection = DoipConnection::connect(entity, 0x0e80, &DoipOpts::default()).unwrap();
+    assert_eq!(connection.entity_address(), 0x1001);
+    connection.send_diagnostic(0x1001, &[0x10, 0x03]).unwrap();
+    connection.stream().set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+    let answer = connection.recv_diagnostic().unwrap();
+    assert_eq!((answer.source, answer.target, answer.data), (0x1001, 0x0e80, vec![0x50, 0x03]));
+    thread.join().unwrap();
+}
+
+#[test]
+fn test_routing_activation_denied() {
+    let (entity, thread) = fake_entity(0x06);
+    let error = DoipConnection::connect(entity, 0x0e80, &DoipOpts::default()).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+    thread.join().unwrap();
+}
+
+#[test]
+fn test_announcement() {
+    let discovery = match doip::DiscoverySocket::bind(&IpAddr::V4(Ipv4Addr::UNSPECIFIED)) {
+        Ok(discovery) => discovery,
+        Err(e) if e.kind() == ErrorKind::AddrInUse => return,
+        Err(e) => panic!("{}", e),
+    };
+    discovery.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+    let entity = UdpSocket::bind("127.0.0.1:0").unwrap();
+    discovery.request_identification(Some(entity.local_addr().unwrap())).unwrap();
+    let mut buffer = [0_u8; 64];
+    let (len, tester) = entity.recv_from(&mut buffer).unwrap();
+    assert_eq!(&buffer[..len], &[0xff, 0x00, 0x00, 0x01, 0, 0, 0, 0]);
+
+    let announcement = doip::VehicleAnnouncement {
+        vin: "WVWZZZ1JZXW000001".to_string(),
+        logical_address: 0x1001,
+        eid: [2, 0, 0, 0, 0, 1],
+        gid: [2, 0, 0, 0, 0, 1],
+        further_action: 0,
+        sync_status: Some(0),
+    };
+    let message = DoipMessage {
+        protocol_version: doip::PROTOCOL_VERSION_2012,
+        payload_type: doip::TYPE_VEHICLE_ANNOUNCEMENT,
+        payload: announcement.encode().unwrap(),
+    };
+    entity.send_to(&message.encode(), (Ipv4Addr::LOCALHOST, tester.port())).unwrap();
+    let (received, from) = discovery.recv().unwrap();
+    assert_eq!(received, announcement);
+    assert_eq!(from, entity.local_addr().unwrap());
+}
@@ -0,0 +1,59 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::{errqueue::*, timestamping::set_timestamping};
+use std::{net::UdpSocket, os::unix::io::AsRawFd, thread, time::Duration};
+
+fn set_option(socket: &UdpSocket, level: libc::c_int, name: libc::c_int, value: libc::c_int) {
+    let rc = unsafe {
+        libc::setsockopt(socket.as_raw_fd(), level, name, &value as *const _ as *const libc::c_void,
+                         std::mem::size_of_val(&value) as libc::socklen_t)
+    };
+    assert_eq!(rc, 0, "{}", std::io::Error::last_os_error());
+}
+
+#[test]
+fn test_empty_error_queue() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    assert_eq!(recv_error_queue(&socket, &mut [0_u8; 64]).unwrap(), None);
+    assert!(drain_error_queue(&socket).unwrap().is_empty());
+}
+
+#[test]
+fn test_tx_timestamps() {
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.connect(receiver.local_addr().unwrap()).unwrap();
+    set_timestamping(&socket, libc::SOF_TIMESTAMPING_TX_SOFTWARE | libc::SOF_TIMESTAMPING_SOFTWARE
+        | libc::SOF_TIMESTAMPING_OPT_ID).unwrap();
+    socket.send(b"first").unwrap();
+    socket.send(b"second").unwrap();
+    thread::sleep(Duration::from_millis(50));
+    let keys: Vec<u32> = drain_error_queue(&socket).unwrap().into_iter().map(|message| match message.error {
+        QueuedError::Timestamp { key, tstamp_type, timestamps } => {
+            assert_eq!(tstamp_type, SCM_TSTAMP_SND);
+            assert!(timestamps.software.is_some());
+            key
+        },
+        error => panic!("unexpected {:?}", error),
+    }).collect();
+    assert_eq!(keys, vec![0, 1]);
+}
+
+#[test]
+fn test_zerocopy_completion() {
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.connect(receiver.local_addr().unwrap()).unwrap();
+    set_option(&socket, libc::SOL_SOCKET, libc::SO_ZEROCOPY, 1);
+    let data = [7_u8; 1000];
+    let sent = unsafe {
+        libc::send(socket.as_raw_fd(), data.as_ptr() as *const libc::c_void, data.len(), libc::MSG_ZEROCOPY)
+    };
+    assert_eq!(sent, data.len() as isize);
+    receiver.recv(&mut [0_u8; 2000]).unwrap();
+    thread::sleep(Duration::from_millis(50));
+    match recv_error_queue(&socket, &mut [0_u8; 64]).unwrap().expect("no completion").error {
+        QueuedError::Zerocopy { first, last, .. } => assert_eq!((first, last), (0, 0)),
+        error => panic!("unexpected {:?}", error),
+    }
+}
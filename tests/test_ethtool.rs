@@ -0,0 +1,18 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::ethtool;
+
+#[test]
+fn test_loopback_capabilities() {
+    assert!(ethtool::offload_features("lo").is_ok());
+    let ts_info = ethtool::timestamping_info("lo");
+    assert!(ts_info.is_ok());
+    assert!(ts_info.unwrap().supports_software_timestamping());
+}
+
+#[test]
+fn test_unknown_interface() {
+    assert!(ethtool::offload_features("nonexistent0").is_err());
+    assert!(ethtool::link_settings("nonexistent0").is_err());
+    assert!(ethtool::timestamping_info("an-interface-name-too-long").is_err());
+}
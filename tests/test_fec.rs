@@ -0,0 +1,55 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::{MulticastSocketBuilder, fec::{FecEncoder, FecReceiver, FecSender}};
+use std::{net::{Ipv4Addr, SocketAddr, UdpSocket}, time::Duration};
+
+#[test]
+fn test_multicast() {
+    let group: SocketAddr = "239.255.77.11:1917".parse().unwrap();
+    let socket = MulticastSocketBuilder::new(group, "127.0.0.1".parse().unwrap())
+        .read_timeout(Duration::from_secs(2))
+        .build_std()
+        .unwrap();
+    let mut receiver = FecReceiver::new(socket);
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket2::SockRef::from(&socket).set_multicast_if_v4(&Ipv4Addr::LOCALHOST).unwrap();
+    let mut sender = FecSender::new(socket, group, 2, 3).unwrap();
+    sender.send(b"one").unwrap();
+    sender.send(b"two").unwrap();
+    sender.send(b"six").unwrap();
+    sender.flush().unwrap();
+    assert_eq!(sender.stats().datagrams_sent, 5);
+
+    let mut buf = [0_u8; 64];
+    for expected in [b"one", b"two", b"six"].iter() {
+        let (len, source) = receiver.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..len], &expected[..]);
+        assert_eq!(source, sender.get_ref().local_addr().unwrap());
+    }
+    assert_eq!(receiver.recovered(), 0);
+}
+
+#[test]
+fn test_recovery() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+    let destination = socket.local_addr().unwrap();
+    let mut receiver = FecReceiver::new(socket);
+    let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut encoder = FecEncoder::new(3, 5).unwrap();
+    let packets: Vec<Vec<u8>> = [&b"one"[..], b"two", b"three"].iter().flat_map(|d| encoder.encode(d).unwrap()).collect();
+    // the first two datagrams are lost on the way
+    for packet in packets.iter().skip(2) {
+        sender.send_to(packet, destination).unwrap();
+    }
+
+    let mut buf = [0_u8; 64];
+    let mut received: Vec<Vec<u8>> = (0..3).map(|_| {
+        let (len, _) = receiver.recv_from(&mut buf).unwrap();
+        buf[..len].to_vec()
+    }).collect();
+    received.sort();
+    assert_eq!(received, vec![b"one".to_vec(), b"three".to_vec(), b"two".to_vec()]);
+    assert_eq!(receiver.recovered(), 2);
+    assert_eq!(receiver.stats().datagrams_received, 3);
+}
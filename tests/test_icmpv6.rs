@@ -0,0 +1,30 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::icmpv6::*;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{mem::MaybeUninit, net::{Ipv6Addr, SocketAddrV6}, time::Duration};
+
+#[test]
+fn test_icmp6_filter() {
+    let receiver = match Socket::new(Domain::IPV6, Type::RAW, Some(Protocol::ICMPV6)) {
+        Ok(socket) => socket,
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => return,
+        Err(e) => panic!("{}", e),
+    };
+    assert_eq!(icmp6_filter(&receiver).unwrap(), Icmp6Filter::pass_all());
+    // types of the private experimentation range (RFC 4443), the kernel does not answer them
+    let filter = Icmp6Filter::pass_only(&[201]);
+    set_icmp6_filter(&receiver, &filter).unwrap();
+    assert_eq!(icmp6_filter(&receiver).unwrap(), filter);
+    receiver.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+
+    let sender = Socket::new(Domain::IPV6, Type::RAW, Some(Protocol::ICMPV6)).unwrap();
+    let target = SocketAddrV6::new(Ipv6Addr::LOCALHOST, 0, 0, 0).into();
+    sender.send_to(&[200, 0, 0, 0, 1, 2, 3, 4], &target).unwrap();
+    sender.send_to(&[201, 0, 0, 0, 5, 6, 7, 8], &target).unwrap();
+    let mut buf = [MaybeUninit::new(0_u8); 64];
+    let (len, _) = receiver.recv_from(&mut buf).unwrap();
+    let packet: Vec<u8> = buf[..len].iter().map(|b| unsafe { b.assume_init() }).collect();
+    assert_eq!(packet[0], 201);
+    assert_eq!(&packet[4..], &[5, 6, 7, 8]);
+}
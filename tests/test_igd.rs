@@ -0,0 +1,81 @@
+use net_utils::igd::{Gateway, PortMappingProtocol};
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+
+const DESCRIPTION: &str = "<?xml version=\"1.0\"?><root><device><deviceList><device><serviceList>\
+    <service><serviceType>urn:schemas-upnp-org:service:WANCommonInterfaceConfig:1</serviceType>\
+    <controlURL>/ctl/CmnIfCfg</controlURL></service>\
+    <service><serviceType>urn:schemas-upnp-org:service:WANIPConnection:1</serviceType>\
+    <controlURL>/ctl/IPConn</controlURL></service>\
+    </serviceList></device></deviceList></device></root>";
+
+fn read_request(stream: &mut TcpStream) -> String {
+    let mut request = Vec::new();
+    let mut buffer = [0_u8; 4096];
+    loop {
+        let len = stream.read(&mut buffer).unwrap();
+        request.extend_from_slice(&buffer[..len]);
+        let text = String::from_utf8_lossy(&request).into_owned();
+        if let Some(end) = text.find("\r\n\r\n") {
+            let content_length = text.lines()
+                .find_map(|l| l.strip_prefix("Content-Length: "))
+                .map_or(0, |l| l.trim().parse::<usize>().unwrap());
+            if request.len() >= end + 4 + content_length {
+                return text;
+            }
+        }
+    }
+}
+
+/// Serves the device description and answers control requests with the given status and body,
+/// the received control requests are passed to the returned channel.
+fn fake_gateway(responses: Vec<(u16, &'static str)>) -> (String, mpsc::Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let location = format!("http://{}/rootDesc.xml", listener.local_addr().unwrap());
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut responses = responses.into_iter();
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let request = read_request(&mut stream);
+            let (status, body) = if request.starts_with("GET /rootDesc.xml ") {
+                (200, DESCRIPTION)
+            } else {
+                sender.send(request).unwrap();
+                responses.next().unwrap()
+            };
+            write!(stream, "HTTP/1.1 {} X\r\nContent-Length: {}\r\n\r\n{}", status, body.len(), body).unwrap();
+        }
+    });
+    (location, receiver)
+}
+
+#[test]
+fn test_igd_port_mapping() {
+    let (location, requests) = fake_gateway(vec![
+        (200, "<s:Envelope><s:Body><u:AddPortMappingResponse/></s:Body></s:Envelope>"),
+        (500, "<s:Envelope><s:Body><s:Fault><detail><UPnPError><errorCode>714</errorCode>\
+            <errorDescription>NoSuchEntryInArray</errorDescription></UPnPError></detail></s:Fault></s:Body></s:Envelope>"),
+        (200, "<NewExternalIPAddress>203.0.113.7</NewExternalIPAddress>"),
+    ]);
+    let gateway = Gateway::from_location(&location).unwrap();
+    assert_eq!(gateway.service_type(), "urn:schemas-upnp-org:service:WANIPConnection:1");
+    assert_eq!(gateway.local_addr(), "127.0.0.1".parse::<std::net::IpAddr>().unwrap());
+
+    gateway.add_port_mapping(PortMappingProtocol::Udp, 5000, &"192.168.1.10:6000".parse().unwrap(),
+                             3600, "test").unwrap();
+    let request = requests.recv().unwrap();
+    assert!(request.starts_with("POST /ctl/IPConn HTTP/1.1\r\n"));
+    assert!(request.contains("SOAPAction: \"urn:schemas-upnp-org:service:WANIPConnection:1#AddPortMapping\""));
+    assert!(request.contains("<NewExternalPort>5000</NewExternalPort><NewProtocol>UDP</NewProtocol>"));
+    assert!(request.contains("<NewInternalClient>192.168.1.10</NewInternalClient>"));
+
+    let err = gateway.delete_port_mapping(PortMappingProtocol::Tcp, 5000).unwrap_err();
+    assert_eq!(err.to_string(), "UPnP error 714 NoSuchEntryInArray");
+    assert!(requests.recv().unwrap().contains("#DeletePortMapping\""));
+
+    assert_eq!(gateway.external_ip_address().unwrap(), "203.0.113.7".parse::<std::net::Ipv4Addr>().unwrap());
+}
@@ -1,7 +1,70 @@
+#![cfg(unix)]
+
 use net_utils::IpInterface;
 
 #[test]
 fn test_interface_retrieval() {
     let ipifs = IpInterface::retrieve_ip_interfaces();
     assert!(ipifs.is_ok());
+    for ipif in ipifs.unwrap() {
+        assert!(ipif.network().contains(&ipif.address.ip()), "{}", ipif);
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_link_state_retrieval() {
+    let ipifs = IpInterface::retrieve_ip_interfaces().unwrap();
+    for ipif in ipifs.iter() {
+        assert!(ipif.link_state().is_ok());
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_address_info_retrieval() {
+    let addresses = net_utils::IpAddressInfo::retrieve_ip_addresses().unwrap();
+    assert!(addresses.iter().any(|a| a.address.is_loopback() && a.is_usable()));
+
+    let ipifs = IpInterface::retrieve_ip_interfaces().unwrap();
+    let lo = ipifs.iter().find(|i| i.address.ip().is_loopback()).unwrap();
+    assert!(lo.address_info().unwrap().is_some());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_interface_kind() {
+    let ipifs = IpInterface::retrieve_ip_interfaces().unwrap();
+    let lo = ipifs.iter().find(|i| i.address.ip().is_loopback()).unwrap();
+    assert_eq!(lo.kind().unwrap(), net_utils::InterfaceKind::Loopback);
+    let wifi = net_utils::wifi::wifi_interfaces().unwrap();
+    for ipif in ipifs.iter() {
+        assert_eq!(ipif.is_wireless().unwrap(), wifi.iter().any(|w| w.index == ipif.index));
+        // PPP links and wireless devices with a link kind are no virtual kinds
+        let kind = ipif.kind().unwrap();
+        if ipif.link_kind().unwrap().is_some_and(|k| k != "ppp") && kind != net_utils::InterfaceKind::Wireless {
+            assert!(kind.is_virtual(), "{} is {:?}", ipif.name, kind);
+        }
+    }
+}
+
+#[test]
+fn test_interface_iteration() {
+    let ipifs = IpInterface::retrieve_ip_interfaces().unwrap();
+
+    let mut iter = IpInterface::iter_ip_interfaces().unwrap();
+    let mut names = Vec::new();
+    while let Some(entry) = iter.next_entry() {
+        names.push(entry.name().to_string());
+    }
+    assert_eq!(names, ipifs.iter().map(|i| i.name.clone()).collect::<Vec<_>>());
+
+    let mut iter = IpInterface::iter_ip_interfaces().unwrap();
+    let mut target = ipifs[0].clone();
+    let mut count = 0;
+    while iter.next_into(&mut target) {
+        assert_eq!(target, ipifs[count]);
+        count += 1;
+    }
+    assert_eq!(count, ipifs.len());
 }
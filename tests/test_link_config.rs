@@ -0,0 +1,128 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::{link_config, netlink, IpInterface};
+use std::{io::ErrorKind, time::Duration};
+
+#[test]
+fn test_link() {
+    let lo = link_config::link("lo").unwrap().unwrap();
+    assert_eq!(lo.link_type, libc::ARPHRD_LOOPBACK);
+    assert!(link_config::link("no-such-link").unwrap().is_none());
+}
+
+#[test]
+fn test_create_delete_link() {
+    let link = match link_config::create_link("nutest-dummy0", "dummy") {
+        Ok(link) => link,
+        // no permission or no dummy support in the kernel
+        Err(e) if e.kind() == ErrorKind::PermissionDenied || e.raw_os_error() == Some(libc::EOPNOTSUPP) => return,
+        Err(e) => panic!("{}", e),
+    };
+    assert_eq!(link.kind.as_deref(), Some("dummy"));
+    let err = link_config::create_link("nutest-dummy0", "dummy").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+    link_config::set_link_up("nutest-dummy0", true).unwrap();
+    assert_ne!(link_config::link("nutest-dummy0").unwrap().unwrap().flags & libc::IFF_UP as u32, 0);
+    link_config::delete_link("nutest-dummy0").unwrap();
+    assert!(link_config::link("nutest-dummy0").unwrap().is_none());
+}
+
+#[test]
+fn test_create_multicast_vxlan() {
+    let interface = IpInterface::retrieve_ip_interfaces().unwrap().into_iter()
+        .find(|i| i.address.is_ipv4() && !i.is_loopback() && i.supports_multicast());
+    let interface = match interface {
+        Some(interface) => interface,
+        None => return,
+    };
+    let config = link_config::VxlanConfig::multicast(4711, "239.1.1.1".parse().unwrap(), &interface);
+    let link = match link_config::create_vxlan("nutest-vx0", &config) {
+        Ok(link) => link,
+        Err(e) if e.kind() == ErrorKind::PermissionDenied || e.raw_os_error() == Some(libc::EOPNOTSUPP) => return,
+        Err(e) => panic!("{}", e),
+    };
+    link_config::delete_link("nutest-vx0").unwrap();
+    assert_eq!(link.kind.as_deref(), Some("vxlan"));
+    assert_eq!(link.link_type, libc::ARPHRD_ETHER);
+}
+
+#[test]
+fn test_bridge_ports() {
+    match link_config::create_bridge("nutest-br0", false) {
+        Ok(bridge) => assert_eq!(bridge.kind.as_deref(), Some("bridge")),
+        Err(e) if e.kind() == ErrorKind::PermissionDenied || e.raw_os_error() == Some(libc::EOPNOTSUPP) => return,
+        Err(e) => panic!("{}", e),
+    }
+    // a unicast VXLAN device serves as ethernet port
+    let port = link_config::VxlanConfig::unicast(4712, "192.0.2.99".parse().unwrap());
+    let result = link_config::create_vxlan("nutest-vx1", &port).and_then(|_| {
+        link_config::set_master("nutest-vx1", Some("nutest-br0"))?;
+        let ports = link_config::bridge_ports("nutest-br0")?;
+        link_config::set_master("nutest-vx1", None)?;
+        Ok((ports, link_config::bridge_ports("nutest-br0")?))
+    });
+    let _ = link_config::delete_link("nutest-vx1");
+    link_config::delete_link("nutest-br0").unwrap();
+    let (ports, released) = match result {
+        Ok(result) => result,
+        Err(e) if e.raw_os_error() == Some(libc::EOPNOTSUPP) => return,
+        Err(e) => panic!("{}", e),
+    };
+    assert_eq!(ports.len(), 1);
+    assert_eq!(ports[0].link.name.as_deref(), Some("nutest-vx1"));
+    assert_eq!(ports[0].state, link_config::StpState::Disabled);
+    assert!(released.is_empty());
+}
+
+#[test]
+fn test_bond() {
+    match link_config::create_bond("nutest-bond0", link_config::BondMode::ActiveBackup,
+                                   Some(Duration::from_millis(100))) {
+        Ok(bond) => assert_eq!(bond.kind.as_deref(), Some("bond")),
+        Err(e) if e.kind() == ErrorKind::PermissionDenied || e.raw_os_error() == Some(libc::EOPNOTSUPP) => return,
+        Err(e) => panic!("{}", e),
+    }
+    let port = link_config::VxlanConfig::unicast(4713, "192.0.2.99".parse().unwrap());
+    let result = link_config::create_vxlan("nutest-vx2", &port)
+        .and_then(|_| link_config::add_bond_slave("nutest-bond0", "nutest-vx2"))
+        .and_then(|_| link_config::bond_info("nutest-bond0"));
+    let _ = link_config::delete_link("nutest-vx2");
+    link_config::delete_link("nutest-bond0").unwrap();
+    let info = result.unwrap().unwrap();
+    assert_eq!(info.mode, link_config::BondMode::ActiveBackup);
+    assert_eq!(info.slaves, vec!["nutest-vx2".to_string()]);
+    assert!(link_config::bond_info("lo").unwrap().is_none());
+}
+
+#[test]
+fn test_team() {
+    match link_config::create_team("nutest-team0") {
+        Ok(team) => assert_eq!(team.kind.as_deref(), Some("team")),
+        Err(e) if e.kind() == ErrorKind::PermissionDenied || e.raw_os_error() == Some(libc::EOPNOTSUPP) => return,
+        Err(e) => panic!("{}", e),
+    }
+    link_config::delete_link("nutest-team0").unwrap();
+}
+
+#[test]
+fn test_macvlan() {
+    // an ethernet link which can serve as parent
+    let parent = netlink::links().unwrap().into_iter()
+        .find(|l| l.link_type == libc::ARPHRD_ETHER && l.kind.is_none() && l.name.is_some());
+    let parent = match parent {
+        Some(parent) => parent,
+        None => return,
+    };
+    let parent_name = parent.name.as_deref().unwrap();
+    let link = match link_config::create_macvlan("nutest-mv0", parent_name, link_config::MacvlanMode::Bridge) {
+        Ok(link) => link,
+        Err(e) if e.kind() == ErrorKind::PermissionDenied || e.raw_os_error() == Some(libc::EOPNOTSUPP) => return,
+        Err(e) => panic!("{}", e),
+    };
+    let address = [0x02, 0, 0x5e, 0x10, 0, 1];
+    let result = link_config::set_address("nutest-mv0", &address).and_then(|_| link_config::link("nutest-mv0"));
+    link_config::delete_link("nutest-mv0").unwrap();
+    assert_eq!(link.kind.as_deref(), Some("macvlan"));
+    assert_eq!(link.parent, Some(parent.index));
+    assert_eq!(result.unwrap().unwrap().address, Some(address.to_vec()));
+}
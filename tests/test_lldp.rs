@@ -0,0 +1,66 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::lldp::{self, LldpId, LldpListener};
+use std::time::Duration;
+
+fn tlv(tlv_type: u8, value: &[u8]) -> Vec<u8> {
+    let mut tlv = (((tlv_type as u16) << 9) | value.len() as u16).to_be_bytes().to_vec();
+    tlv.extend_from_slice(value);
+    tlv
+}
+
+/// Sends the frame on the interface with a packet socket.
+fn send_frame(frame: &[u8], interface_index: u32) {
+    let socket = unsafe { libc::socket(libc::AF_PACKET, libc::SOCK_RAW, 0) };
+    assert!(socket >= 0);
+    let mut address: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+    address.sll_family = libc::AF_PACKET as u16;
+    address.sll_ifindex = interface_index as i32;
+    address.sll_halen = 6;
+    address.sll_addr[..6].copy_from_slice(&lldp::LLDP_MULTICAST);
+    let sent = unsafe {
+        libc::sendto(socket, frame.as_ptr() as *const libc::c_void, frame.len(), 0,
+                     &address as *const libc::sockaddr_ll as *const libc::sockaddr,
+                     std::mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t)
+    };
+    unsafe { libc::close(socket) };
+    assert_eq!(sent, frame.len() as isize);
+}
+
+#[test]
+fn test_lldp_listener() {
+    let index = unsafe { libc::if_nametoindex(b"lo\0".as_ptr() as *const libc::c_char) };
+    let listener = match LldpListener::new(Some(index)) {
+        Ok(listener) => listener,
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => return,
+        Err(e) => panic!("{}", e),
+    };
+    listener.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+
+    let mut frame = lldp::LLDP_MULTICAST.to_vec();
+    frame.extend_from_slice(&[0x02, 0, 0, 0, 0, 0x42]);
+    frame.extend_from_slice(&lldp::LLDP_ETHERTYPE.to_be_bytes());
+    frame.extend(tlv(1, &[7, b'n', b'e', b't', b'u']));
+    frame.extend(tlv(2, &[3, 0x02, 0, 0, 0, 0, 0x42]));
+    frame.extend(tlv(3, &[0, 30]));
+    frame.extend(tlv(5, b"neighbor"));
+    frame.extend(tlv(0, &[]));
+    send_frame(&frame, index);
+
+    let neighbor = listener.recv().unwrap();
+    assert_eq!(neighbor.interface_index, index);
+    assert_eq!(neighbor.chassis_id, LldpId::Local("netu".to_string()));
+    assert_eq!(neighbor.port_id, LldpId::MacAddress([0x02, 0, 0, 0, 0, 0x42]));
+    assert_eq!(neighbor.ttl, Duration::from_secs(30));
+    assert_eq!(neighbor.system_name.as_deref(), Some("neighbor"));
+}
+
+#[test]
+fn test_lldp_listener_all_interfaces() {
+    match LldpListener::new(None) {
+        Ok(listener) => {
+            listener.set_read_timeout(Some(Duration::from_millis(10))).unwrap();
+        },
+        Err(e) => assert_eq!(e.kind(), std::io::ErrorKind::PermissionDenied),
+    }
+}
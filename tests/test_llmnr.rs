@@ -0,0 +1,54 @@
+mod common;
+
+use common::multicast_routed;
+use net_utils::{dns, llmnr};
+use std::{
+    io::ErrorKind,
+    net::{IpAddr, Ipv4Addr},
+    time::Duration,
+};
+
+#[test]
+fn test_llmnr_query_v4() {
+    let mut responder = match llmnr::LlmnrResponder::new("netutils-test", &IpAddr::V4(Ipv4Addr::UNSPECIFIED)) {
+        Ok(responder) => responder,
+        Err(e) if e.kind() == ErrorKind::AddrInUse || e.kind() == ErrorKind::PermissionDenied => return,
+        Err(e) => panic!("{}", e),
+    };
+    responder.add_address("192.0.2.77".parse().unwrap());
+    responder.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let thread = std::thread::spawn(move || {
+        // the responder also receives the looped back query of other tests, answer until ours
+        while let Ok(answered) = responder.respond_once() {
+            if answered.is_some() {
+                break;
+            }
+        }
+    });
+
+    let responses = match multicast_routed(llmnr::query("NetUtils-Test", dns::TYPE_A, &IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                                                        Duration::from_millis(500))) {
+        Some(responses) => responses,
+        None => return,
+    };
+    thread.join().unwrap();
+    assert_eq!(responses.len(), 1);
+    assert!(!responses[0].conflict);
+    assert_eq!(responses[0].answers[0].data, dns::RecordData::A("192.0.2.77".parse().unwrap()));
+}
+
+#[test]
+fn test_llmnr_query_unanswered() {
+    match llmnr::query("netutils-nobody", dns::TYPE_AAAA, &IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                       Duration::from_millis(200)) {
+        Ok(responses) => assert!(responses.is_empty()),
+        Err(e) => assert_eq!(e.kind(), ErrorKind::NetworkUnreachable),
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_local_hostname() {
+    let hostname = llmnr::local_hostname().unwrap();
+    assert!(!hostname.contains('.'));
+}
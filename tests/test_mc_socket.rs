@@ -1,5 +1,5 @@
 use net_utils::*;
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::{net::{Ipv4Addr, Ipv6Addr}, time::{Duration, Instant}};
 
 #[test]
 fn test_mc_socket_ip4() {
@@ -15,4 +15,119 @@ fn test_mc_socket_ip6() {
                                                   &Ipv6Addr::UNSPECIFIED);
     assert!(socket.is_ok());
     drop(socket);
-}
\ No newline at end of file
+}
+
+#[cfg(all(unix, feature = "test-util"))]
+#[test]
+fn test_mc_socket_ip6_with_provider() {
+    let provider = MockInterfaceProvider::new(Vec::new());
+    let socket = create_std_multicast_socket_ipv6_with(&"[ff02::c]:1901".parse().unwrap(),
+                                                       &Ipv6Addr::LOCALHOST, &provider);
+    assert!(socket.is_ok());
+}
+
+#[test]
+fn test_presets() {
+    assert_eq!(MulticastPreset::Mdns.group(false), "224.0.0.251:5353".parse().unwrap());
+    assert_eq!(MulticastPreset::Ssdp.group(true), "[ff02::c]:1900".parse().unwrap());
+    assert_eq!(MulticastPreset::PtpGeneral.group(true), "[ff0e::181]:320".parse().unwrap());
+    let group = "239.69.1.2:5004".parse().unwrap();
+    assert_eq!(MulticastPreset::Rtp(group).group(true), group);
+
+    let socket = MulticastSocketBuilder::preset(MulticastPreset::Ssdp, Ipv4Addr::UNSPECIFIED.into())
+        .port(1902)
+        .build_std()
+        .unwrap();
+    assert_eq!(socket.multicast_ttl_v4().unwrap(), 2);
+    assert!(socket.multicast_loop_v4().unwrap());
+    assert_eq!(socket.local_addr().unwrap().port(), 1902);
+
+    let socket = MulticastSocketBuilder::preset(MulticastPreset::PtpEvent, Ipv6Addr::UNSPECIFIED.into())
+        .port(1903)
+        .build_std()
+        .unwrap();
+    assert!(!socket.multicast_loop_v6().unwrap());
+
+    let err = MulticastSocketBuilder::preset(MulticastPreset::Rtp(group), Ipv6Addr::UNSPECIFIED.into())
+        .build_std()
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    let err = MulticastSocketBuilder::new("192.0.2.1:5004".parse().unwrap(), Ipv4Addr::UNSPECIFIED.into())
+        .build_std()
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_timeouts() {
+    let socket = MulticastSocketBuilder::new("239.255.77.1:1904".parse().unwrap(), Ipv4Addr::UNSPECIFIED.into())
+        .read_timeout(Duration::from_secs(5))
+        .write_timeout(Duration::from_secs(1))
+        .build_std()
+        .unwrap();
+    assert_eq!(socket.read_timeout().unwrap(), Some(Duration::from_secs(5)));
+    assert_eq!(socket.write_timeout().unwrap(), Some(Duration::from_secs(1)));
+
+    let started = Instant::now();
+    assert_eq!(recv_from_timeout(&socket, &mut [0_u8; 16], Duration::from_millis(50)).unwrap(), None);
+    assert!(started.elapsed() >= Duration::from_millis(50));
+    assert_eq!(socket.read_timeout().unwrap(), Some(Duration::from_secs(5)));
+
+    let err = MulticastSocketBuilder::new("239.255.77.1:1904".parse().unwrap(), Ipv4Addr::UNSPECIFIED.into())
+        .read_timeout(Duration::ZERO)
+        .build_std()
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_only_v6() {
+    let socket = create_std_multicast_socket_ipv6(&"[ff02::c]:1905".parse().unwrap(), &Ipv6Addr::UNSPECIFIED).unwrap();
+    assert!(socket2::SockRef::from(&socket).only_v6().unwrap());
+    let builder = MulticastSocketBuilder::new("[ff02::c]:1906".parse().unwrap(), Ipv6Addr::UNSPECIFIED.into());
+    let socket = builder.clone().build_std().unwrap();
+    assert!(socket2::SockRef::from(&socket).only_v6().unwrap());
+    let socket = builder.port(1907).only_v6(false).build_std().unwrap();
+    // linux implies IPv6 only when binding to the group address
+    let only_v6 = socket2::SockRef::from(&socket).only_v6().unwrap();
+    if cfg!(target_os = "linux") {
+        assert!(only_v6);
+    } else if cfg!(windows) {
+        assert!(!only_v6);
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_report_drops() {
+    use std::os::unix::io::AsRawFd;
+
+    let socket = MulticastSocketBuilder::new("239.255.77.2:1908".parse().unwrap(), Ipv4Addr::UNSPECIFIED.into())
+        .report_drops(true)
+        .build_std()
+        .unwrap();
+    let mut value: libc::c_int = 0;
+    let mut len = std::mem::size_of_val(&value) as libc::socklen_t;
+    let rc = unsafe {
+        libc::getsockopt(socket.as_raw_fd(), libc::SOL_SOCKET, libc::SO_RXQ_OVFL,
+                         &mut value as *mut _ as *mut libc::c_void, &mut len)
+    };
+    assert_eq!((rc, value), (0, 1));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_receive_ttl() {
+    let group = "239.255.77.10:1917".parse().unwrap();
+    let socket = MulticastSocketBuilder::new(group, Ipv4Addr::LOCALHOST.into())
+        .ttl(255)
+        .loopback(true)
+        .receive_ttl(true)
+        .build_std()
+        .unwrap();
+    socket.send_to(b"ttl", group).unwrap();
+    let mut buf = [0_u8; 16];
+    let (len, _, info) = pktinfo::recv_with_pktinfo(&socket, &mut buf).unwrap();
+    assert_eq!(len, 3);
+    assert_eq!(info.ttl, Some(255));
+}
@@ -0,0 +1,79 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::{
+    IpInterface,
+    icmpv6::{Icmp6Filter, MLD2_LISTENER_REPORT, set_icmp6_filter},
+    igmp_proxy::{CHANGE_TO_EXCLUDE_MODE, IgmpMessage},
+    membership_report::{IgmpReporter, MldReporter},
+    mld::{MLDV2_ROUTERS, MldMessage, MldRecord},
+    packet::{IPPROTO_ICMPV6, IPPROTO_IGMP, Ipv4Packet},
+    raw::RawSocketBuilder,
+};
+use std::{io::ErrorKind, net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket}, time::Duration};
+
+#[test]
+fn test_igmp_reporter() {
+    let mut reporter = match IgmpReporter::new("lo") {
+        Ok(reporter) => reporter,
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => return,
+        Err(e) => panic!("{}", e),
+    };
+    assert_eq!(reporter.source(), Ipv4Addr::LOCALHOST);
+    reporter.set_source("127.0.0.77".parse().unwrap());
+    let receiver = RawSocketBuilder::ipv4(IPPROTO_IGMP).bind_device("lo").build().unwrap();
+    receiver.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+    // the sent report is looped back if the host is member of the group
+    let group = "239.1.2.3".parse().unwrap();
+    let member = UdpSocket::bind("0.0.0.0:0").unwrap();
+    member.join_multicast_v4(&group, &Ipv4Addr::LOCALHOST).unwrap();
+
+    let report = IgmpMessage::ReportV2 { group };
+    reporter.send(&report).unwrap();
+    let mut buf = [0_u8; 256];
+    loop {
+        let (len, source) = receiver.recv_from(&mut buf).unwrap();
+        let packet = Ipv4Packet::new(&buf[..len]).unwrap();
+        if source != IpAddr::from([127, 0, 0, 77]) {
+            continue;
+        }
+        assert_eq!((packet.ttl(), packet.tos(), packet.options()), (1, 0xc0, &[0x94, 0x04, 0, 0][..]));
+        assert_eq!(packet.destination(), Ipv4Addr::new(239, 1, 2, 3));
+        assert!(packet.verify_checksum());
+        assert_eq!(IgmpMessage::parse(packet.payload()), Some(report));
+        break;
+    }
+}
+
+#[test]
+fn test_mld_reporter() {
+    let interfaces = IpInterface::retrieve_ip_interfaces().unwrap();
+    let link_local = interfaces.iter().find_map(|i| match i.address {
+        SocketAddr::V6(a) if a.ip().is_unicast_link_local() && i.supports_multicast() => Some((i, *a.ip())),
+        _ => None,
+    });
+    let Some((interface, address)) = link_local else { return };
+    let reporter = match MldReporter::new(&interface.name) {
+        Ok(reporter) => reporter,
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => return,
+        Err(e) => panic!("{}", e),
+    };
+    assert_eq!(reporter.source(), address);
+    let receiver = RawSocketBuilder::ipv6(IPPROTO_ICMPV6).bind_device(&interface.name).build().unwrap();
+    set_icmp6_filter(&receiver, &Icmp6Filter::pass_only(&[MLD2_LISTENER_REPORT])).unwrap();
+    // the sent report is looped back as the receiver is member of the group
+    socket2::SockRef::from(&receiver).join_multicast_v6(&MLDV2_ROUTERS, interface.index).unwrap();
+    receiver.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+
+    let report = MldMessage::ReportV2 { records: vec![
+        MldRecord { record_type: CHANGE_TO_EXCLUDE_MODE, group: "ff15::1234".parse().unwrap(), sources: Vec::new() },
+    ] };
+    reporter.send(&report).unwrap();
+    let mut buf = [0_u8; 256];
+    loop {
+        let (len, source) = receiver.recv_from(&mut buf).unwrap();
+        let message = MldMessage::parse(&buf[..len]);
+        if source == IpAddr::V6(address) && message.as_ref() == Some(&report) {
+            break;
+        }
+    }
+}
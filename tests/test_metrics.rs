@@ -0,0 +1,68 @@
+#![cfg(feature = "metrics")]
+
+use metrics::{Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit};
+use net_utils::create_std_multicast_socket_ipv4;
+use std::{collections::HashMap, net::{Ipv4Addr, UdpSocket}, sync::{Arc, Mutex, atomic::{AtomicU64, Ordering}}};
+
+/// Keeps the counters and gauges by name and labels, gauges as f64 bits.
+#[derive(Default)]
+struct TestRecorder(Mutex<HashMap<String, Arc<AtomicU64>>>);
+
+impl TestRecorder {
+    fn handle(&self, key: &Key) -> Arc<AtomicU64> {
+        let labels: Vec<String> = key.labels().map(|label| format!("{}={}", label.key(), label.value())).collect();
+        let name = format!("{}{{{}}}", key.name(), labels.join(","));
+        self.0.lock().unwrap().entry(name).or_default().clone()
+    }
+
+    fn counter(&self, name: &str) -> u64 {
+        self.0.lock().unwrap().get(name).map(|value| value.load(Ordering::Relaxed)).unwrap_or(0)
+    }
+
+    fn gauge(&self, name: &str) -> f64 {
+        self.0.lock().unwrap().get(name).map(|value| f64::from_bits(value.load(Ordering::Relaxed))).unwrap_or(0.0)
+    }
+}
+
+impl Recorder for TestRecorder {
+    fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+        Counter::from_arc(self.handle(key))
+    }
+
+    fn register_gauge(&self, key: &Key, _: &Metadata<'_>) -> Gauge {
+        Gauge::from_arc(self.handle(key))
+    }
+
+    fn register_histogram(&self, _: &Key, _: &Metadata<'_>) -> Histogram {
+        Histogram::noop()
+    }
+}
+
+#[test]
+fn test_metrics() {
+    let recorder = TestRecorder::default();
+    let socket = metrics::with_local_recorder(&recorder, || {
+        create_std_multicast_socket_ipv4(&"239.255.77.4:1910".parse().unwrap(), &Ipv4Addr::UNSPECIFIED)
+    });
+    assert!(socket.is_ok());
+    assert_eq!(recorder.counter("net_utils_group_joins_total{}"), 1);
+    assert_eq!(recorder.counter("net_utils_sockets_created_total{kind=udp-multicast}"), 1);
+}
+
+#[test]
+fn test_sockets_open() {
+    use net_utils::rate_limit::{RateLimit, RateLimitedSender};
+    let recorder = TestRecorder::default();
+    metrics::with_local_recorder(&recorder, || {
+        let sender = RateLimitedSender::new(UdpSocket::bind("127.0.0.1:0").unwrap(), RateLimit::default());
+        assert_eq!(recorder.gauge("net_utils_sockets_open{}"), 1.0);
+        drop(sender);
+    });
+    assert_eq!(recorder.gauge("net_utils_sockets_open{}"), 0.0);
+}
@@ -0,0 +1,24 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::multicast_diagnosis::{MulticastProblem, diagnose_multicast, diagnose_multicast_with};
+use std::{io::ErrorKind, time::Duration};
+
+#[test]
+fn test_diagnose_loopback() {
+    let diagnosis = diagnose_multicast_with("239.1.2.5".parse().unwrap(), "lo", Duration::from_millis(200)).unwrap();
+    assert_eq!(diagnosis.interface_index, Some(1));
+    assert!(diagnosis.join_error.is_none());
+    assert!(diagnosis.listed);
+    assert!(diagnosis.looped_back, "{}", diagnosis);
+    let problems = diagnosis.problems();
+    assert!(!problems.contains(&MulticastProblem::InterfaceDown));
+    assert!(!problems.contains(&MulticastProblem::NoLoopback));
+    assert!(!problems.contains(&MulticastProblem::MulticastDisabled));
+}
+
+#[test]
+fn test_diagnose_invalid() {
+    let diagnosis = diagnose_multicast("239.1.2.5".parse().unwrap(), "nonexistent0").unwrap();
+    assert_eq!(diagnosis.problems(), vec![MulticastProblem::InterfaceNotFound]);
+    assert_eq!(diagnose_multicast("192.0.2.1".parse().unwrap(), "lo").unwrap_err().kind(), ErrorKind::InvalidInput);
+}
@@ -0,0 +1,25 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::{create_std_multicast_socket_ipv4, multicast_groups, IpInterface};
+use std::net::{IpAddr, SocketAddr};
+
+#[test]
+fn test_join_is_visible() {
+    let interface = IpInterface::retrieve_ip_interfaces().unwrap().into_iter()
+        .find(|i| i.address.is_ipv4() && i.supports_multicast() && i.is_up());
+    let interface = match interface {
+        Some(interface) => interface,
+        None => return,
+    };
+    let address = match interface.address {
+        SocketAddr::V4(address) => *address.ip(),
+        SocketAddr::V6(_) => unreachable!(),
+    };
+    let group: IpAddr = "239.255.77.1".parse().unwrap();
+    assert!(!multicast_groups::is_joined(&group, interface.index).unwrap());
+    let socket = create_std_multicast_socket_ipv4(&"239.255.77.1:40001".parse().unwrap(), &address).unwrap();
+    assert!(multicast_groups::is_joined(&group, interface.index).unwrap());
+    assert!(interface.joined_groups().unwrap().contains(&group));
+    drop(socket);
+    assert!(!multicast_groups::is_joined(&group, interface.index).unwrap());
+}
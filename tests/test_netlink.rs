@@ -0,0 +1,58 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::netlink;
+
+#[test]
+fn test_links() {
+    let links = netlink::links().unwrap();
+    let lo = links.iter().find(|l| l.name.as_deref() == Some("lo")).unwrap();
+    assert_eq!(lo.link_type, libc::ARPHRD_LOOPBACK);
+    assert!(lo.mtu.is_some());
+}
+
+#[test]
+fn test_addresses_routes_neighbors() {
+    assert!(netlink::addresses().unwrap().iter().any(|a| a.address.is_loopback()));
+    let routes = netlink::routes().unwrap();
+    assert!(routes.iter().any(|r| r.table == libc::RT_TABLE_LOCAL as u32));
+    assert!(netlink::neighbors().is_ok());
+}
+
+#[test]
+fn test_generic_family() {
+    let mut socket = netlink::NetlinkSocket::new(libc::NETLINK_GENERIC, 0).unwrap();
+    assert_eq!(socket.generic_family("nlctrl").unwrap(), 0x10);
+    let err = socket.generic_family("no-such-family").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+}
+
+#[cfg(feature = "tokio-net")]
+#[tokio::test]
+async fn test_async_request() {
+    let mut socket = netlink::AsyncNetlinkSocket::new(libc::NETLINK_ROUTE, 0).unwrap();
+    let messages = socket.request(libc::RTM_GETLINK, libc::NLM_F_DUMP as u16, &[0_u8; 16]).await.unwrap();
+    assert!(messages.iter().filter_map(netlink::LinkInfo::from_message).any(|l| l.name.as_deref() == Some("lo")));
+}
+
+#[cfg(feature = "tokio-net")]
+#[tokio::test]
+async fn test_async_shutdown() {
+    let socket = netlink::AsyncNetlinkSocket::new(libc::NETLINK_ROUTE, libc::RTMGRP_LINK as u32).unwrap();
+    let handle = socket.shutdown_handle();
+    tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        handle.shutdown();
+    });
+    assert!(socket.next().await.is_none());
+
+    // a drain delivers the queued response before the stream ends
+    let socket = netlink::AsyncNetlinkSocket::new(libc::NETLINK_ROUTE, 0).unwrap();
+    socket.send(libc::RTM_GETLINK, (libc::NLM_F_REQUEST | libc::NLM_F_DUMP) as u16, 1, &[0_u8; 16]).await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    socket.shutdown_handle().drain();
+    let mut messages = Vec::new();
+    while let Some(received) = socket.next().await {
+        messages.extend(received.unwrap());
+    }
+    assert!(messages.iter().filter_map(netlink::LinkInfo::from_message).any(|l| l.name.as_deref() == Some("lo")));
+}
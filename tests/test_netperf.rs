@@ -0,0 +1,43 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::netperf::{self, TcpReflector, TcpTestOpts, UdpReflector, UdpTestOpts};
+use std::{net::UdpSocket, time::Duration};
+
+#[test]
+fn test_udp_test() {
+    let reflector = UdpReflector::bind("127.0.0.1:0").unwrap();
+    let target = reflector.local_addr().unwrap();
+    std::thread::spawn(move || reflector.run());
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let opts = UdpTestOpts { packet_size: 200, rate: 200, duration: Duration::from_millis(250) };
+    let reports = netperf::udp_test(&socket, target, &opts).unwrap();
+    assert_eq!(reports.len(), 1);
+    let report = &reports[0];
+    assert_eq!(report.reflector, target);
+    assert!(report.sent >= 40);
+    assert_eq!(report.received, report.sent);
+    assert_eq!(report.loss(), 0.0);
+    assert!(report.rtt_min <= report.rtt_avg && report.rtt_avg <= report.rtt_max);
+    assert!(report.rtt_max < Duration::from_millis(100));
+    assert!(report.forward_delay.is_some());
+    assert!(report.throughput > 0);
+
+    let invalid = UdpTestOpts { packet_size: 8, ..opts };
+    assert!(netperf::udp_test(&socket, target, &invalid).is_err());
+}
+
+#[test]
+fn test_tcp_test() {
+    let reflector = TcpReflector::bind("127.0.0.1:0").unwrap();
+    let target = reflector.local_addr().unwrap();
+    let server = std::thread::spawn(move || reflector.serve_once().unwrap());
+
+    let opts = TcpTestOpts { duration: Duration::from_millis(200), ..TcpTestOpts::default() };
+    let report = netperf::tcp_test(target, &opts).unwrap();
+    let (_, received) = server.join().unwrap();
+    assert_eq!(report.bytes_received, received);
+    assert_eq!(report.bytes_sent, received);
+    assert!(report.throughput > 0);
+    assert!(report.elapsed >= opts.duration);
+}
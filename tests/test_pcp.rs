@@ -0,0 +1,60 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::{igd::PortMappingProtocol, pcp};
+use std::{net::UdpSocket, time::Duration};
+
+/// Binds a fake gateway on the PCP port of a loopback address, None if the port is not available.
+fn fake_gateway(address: &str) -> Option<UdpSocket> {
+    UdpSocket::bind((address, pcp::PCP_PORT)).ok()
+}
+
+#[test]
+fn test_nat_pmp_map() {
+    let Some(server) = fake_gateway("127.0.0.2") else { return };
+    std::thread::spawn(move || {
+        let mut request = [0_u8; 64];
+        // drop the first request for a retransmission
+        server.recv_from(&mut request).unwrap();
+        let (len, client) = server.recv_from(&mut request).unwrap();
+        assert_eq!(len, 12);
+        let mut response = [0_u8; 16];
+        response[1] = 0x80 | request[1];
+        response[8..10].copy_from_slice(&request[4..6]);
+        response[10..12].copy_from_slice(&40000_u16.to_be_bytes());
+        response[12..16].copy_from_slice(&request[8..12]);
+        server.send_to(&response, client).unwrap();
+    });
+    let mapping = pcp::nat_pmp_map("127.0.0.2".parse().unwrap(), PortMappingProtocol::Udp, 5000, 0,
+                                   Duration::from_secs(3600)).unwrap();
+    assert_eq!(mapping.internal_port, 5000);
+    assert_eq!(mapping.external_port, 40000);
+    assert_eq!(mapping.lifetime, Duration::from_secs(3600));
+}
+
+#[test]
+fn test_pcp_map() {
+    let Some(server) = fake_gateway("127.0.0.3") else { return };
+    std::thread::spawn(move || {
+        let mut request = [0_u8; 1100];
+        let (len, client) = server.recv_from(&mut request).unwrap();
+        assert_eq!(len, 60);
+        let mut response = request[..len].to_vec();
+        response[1] |= 0x80;
+        response[8..24].fill(0);
+        response[42..44].copy_from_slice(&41000_u16.to_be_bytes());
+        response[44..60].copy_from_slice(&"203.0.113.9".parse::<std::net::Ipv4Addr>().unwrap().to_ipv6_mapped().octets());
+        server.send_to(&response, client).unwrap();
+    });
+    let mapping = pcp::pcp_map("127.0.0.3".parse().unwrap(), PortMappingProtocol::Tcp, 8080, None,
+                               Duration::from_secs(600)).unwrap();
+    assert_eq!(mapping.external_port, 41000);
+    assert_eq!(mapping.external_address, Some("203.0.113.9".parse().unwrap()));
+    assert_eq!(mapping.lifetime, Duration::from_secs(600));
+}
+
+#[test]
+fn test_default_gateway() {
+    if let Ok(gateway) = pcp::default_gateway() {
+        assert!(!gateway.is_unspecified());
+    }
+}
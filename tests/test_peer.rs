@@ -0,0 +1,21 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::{peer::{classify_peer, PeerLocation}, IpInterface};
+
+#[test]
+fn test_classify_peer() {
+    match classify_peer(&"127.0.0.1".parse().unwrap()).unwrap() {
+        PeerLocation::Local(interface) => assert!(interface.is_loopback()),
+        location => panic!("{:?}", location),
+    }
+    let interface = IpInterface::retrieve_ip_interfaces().unwrap().into_iter()
+        .find(|i| i.address.is_ipv4() && !i.is_loopback() && i.p2p_address.is_none() && i.prefix_len() < 31);
+    if let Some(interface) = interface {
+        assert_eq!(classify_peer(&interface.address.ip()).unwrap(), PeerLocation::Local(interface.clone()));
+        let broadcast = interface.network().broadcast().unwrap();
+        match classify_peer(&broadcast.into()).unwrap() {
+            PeerLocation::OnLink(on_link) => assert_eq!(on_link.index, interface.index),
+            location => panic!("{:?}", location),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::pktinfo::*;
+use std::{net::UdpSocket, time::{Duration, SystemTime}};
+
+#[test]
+fn test_recv_with_pktinfo() {
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    set_pktinfo(&receiver, true).unwrap();
+    let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket2::SockRef::from(&sender).set_tos(0x10).unwrap();
+    sender.send_to(b"hello", receiver.local_addr().unwrap()).unwrap();
+    let mut buf = [0_u8; 16];
+    let (len, source, info) = recv_with_pktinfo(&receiver, &mut buf).unwrap();
+    assert_eq!((len, source), (5, sender.local_addr().unwrap()));
+    assert_eq!(info.dst_addr, Some("127.0.0.1".parse().unwrap()));
+    assert_eq!(info.if_index, Some(1));
+    assert_eq!(info.tos, Some(0x10));
+    let age = SystemTime::now().duration_since(info.timestamp.unwrap()).unwrap();
+    assert!(age < Duration::from_secs(5));
+
+    set_pktinfo(&receiver, false).unwrap();
+    sender.send_to(b"hello", receiver.local_addr().unwrap()).unwrap();
+    let (_, _, info) = recv_with_pktinfo(&receiver, &mut buf).unwrap();
+    assert_eq!(info, PktInfo::default());
+}
+
+#[test]
+fn test_recv_batch_with_pktinfo() {
+    let receiver = UdpSocket::bind("[::1]:0").unwrap();
+    set_pktinfo(&receiver, true).unwrap();
+    let sender = UdpSocket::bind("[::1]:0").unwrap();
+    for message in [&b"one"[..], b"two", b"three"] {
+        sender.send_to(message, receiver.local_addr().unwrap()).unwrap();
+    }
+    let mut bufs = [[0_u8; 16]; 4];
+    let mut slices: Vec<&mut [u8]> = bufs.iter_mut().map(|buf| &mut buf[..]).collect();
+    let datagrams = recv_batch_with_pktinfo(&receiver, &mut slices).unwrap();
+    assert_eq!(datagrams.iter().map(|d| d.0).collect::<Vec<_>>(), vec![3, 3, 5]);
+    assert!(datagrams.iter().all(|(_, _, info)| info.dst_addr == Some("::1".parse().unwrap()) && info.if_index == Some(1)));
+    assert_eq!(&bufs[2][..5], b"three");
+}
+
+#[cfg(feature = "tokio-net")]
+#[tokio::test]
+async fn test_recv_with_pktinfo_async() {
+    let receiver = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    set_pktinfo(&receiver, true).unwrap();
+    let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+    sender.send_to(b"async", receiver.local_addr().unwrap()).unwrap();
+    let mut buf = [0_u8; 16];
+    let (len, _, info) = recv_with_pktinfo_async(&receiver, &mut buf).await.unwrap();
+    assert_eq!(len, 5);
+    assert_eq!(info.dst_addr, Some("127.0.0.1".parse().unwrap()));
+
+    sender.send_to(b"batch", receiver.local_addr().unwrap()).unwrap();
+    let mut bufs = [[0_u8; 16]; 2];
+    let mut slices: Vec<&mut [u8]> = bufs.iter_mut().map(|buf| &mut buf[..]).collect();
+    let datagrams = recv_batch_with_pktinfo_async(&receiver, &mut slices).await.unwrap();
+    assert_eq!(datagrams.len(), 1);
+    assert_eq!(datagrams[0].2.if_index, Some(1));
+}
+
+#[test]
+fn test_recv_ttl() {
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    set_recv_ttl(&receiver, true).unwrap();
+    let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+    sender.set_ttl(255).unwrap();
+    sender.send_to(b"gtsm", receiver.local_addr().unwrap()).unwrap();
+    let mut buf = [0_u8; 16];
+    let (_, _, info) = recv_with_pktinfo(&receiver, &mut buf).unwrap();
+    assert_eq!(info.ttl, Some(255));
+    assert!(info.ttl_at_least(255));
+    assert_eq!(info.dst_addr, None);
+
+    let receiver = UdpSocket::bind("[::1]:0").unwrap();
+    set_recv_ttl(&receiver, true).unwrap();
+    let sender = socket2::Socket::new(socket2::Domain::IPV6, socket2::Type::DGRAM, None).unwrap();
+    sender.set_unicast_hops_v6(7).unwrap();
+    sender.send_to(b"far", &receiver.local_addr().unwrap().into()).unwrap();
+    let (_, _, info) = recv_with_pktinfo(&receiver, &mut buf).unwrap();
+    assert_eq!(info.ttl, Some(7));
+    assert!(!info.ttl_at_least(255));
+    assert!(!PktInfo::default().ttl_at_least(1));
+}
@@ -0,0 +1,30 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::{IpInterface, proc_interfaces::{InterfaceBackend, interface_backend, proc_ip_interfaces, set_interface_backend}};
+use std::net::IpAddr;
+
+fn addresses(interfaces: &[IpInterface]) -> Vec<(String, u32, IpAddr)> {
+    let mut addresses: Vec<_> = interfaces.iter()
+        .map(|interface| (interface.name.clone(), interface.index, interface.address.ip()))
+        .collect();
+    addresses.sort();
+    addresses
+}
+
+#[test]
+fn test_backends_agree() {
+    let from_getifaddrs = IpInterface::retrieve_ip_interfaces().unwrap();
+    let from_proc = proc_ip_interfaces().unwrap();
+    // point-to-point and routeless IPv4 addresses are not reported by /proc
+    for address in addresses(&from_proc) {
+        assert!(addresses(&from_getifaddrs).contains(&address), "{:?} not reported by getifaddrs", address);
+    }
+    let loopback = from_proc.iter().find(|interface| interface.address.ip().is_loopback() && interface.address.is_ipv4()).unwrap();
+    let expected = from_getifaddrs.iter().find(|interface| interface.address == loopback.address).unwrap();
+    assert_eq!((loopback.net_mask, loopback.flags & 0xffff), (expected.net_mask, expected.flags & 0xffff));
+
+    set_interface_backend(InterfaceBackend::Proc);
+    assert_eq!(interface_backend(), InterfaceBackend::Proc);
+    assert_eq!(addresses(&IpInterface::retrieve_ip_interfaces().unwrap()), addresses(&from_proc));
+    set_interface_backend(InterfaceBackend::Getifaddrs);
+}
@@ -0,0 +1,32 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::{IpInterfaceIterExt, IpInterfaces, ptp::{self, PtpSockets, PtpTransport, TimestampSource}};
+
+#[test]
+fn test_ptp_software_timestamps() {
+    let interfaces = IpInterfaces::retrieve().unwrap();
+    let interface = match interfaces.iter().up().multicast_capable().non_loopback().ipv4().next() {
+        Some(interface) => interface.clone(),
+        None => return,
+    };
+    let sockets = PtpSockets::open(&interface.name, PtpTransport::Ipv4, TimestampSource::Software).unwrap();
+
+    let sender = std::net::UdpSocket::bind((interface.address.ip(), 0)).unwrap();
+    if let std::net::IpAddr::V4(address) = interface.address.ip() {
+        socket2::SockRef::from(&sender).set_multicast_if_v4(&address).unwrap();
+    }
+    // the kernel enables receive timestamps deferred when the first socket requests them, so
+    // the first packets may arrive without one
+    let mut buffer = [0_u8; 64];
+    for _ in 0..10 {
+        sender.send_to(b"sync", (ptp::PTP_PRIMARY_MULTICAST_V4, ptp::PTP_EVENT_PORT)).unwrap();
+        let (len, from, timestamps) = sockets.recv_event(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], b"sync");
+        assert_eq!(from, sender.local_addr().unwrap());
+        if timestamps.software.is_some() {
+            return;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    panic!("no software timestamp received");
+}
@@ -0,0 +1,71 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::{pktinfo::Ecn, quic::{QuicSocketOpts, Transmit, create_quic_socket}};
+use std::time::Duration;
+
+#[test]
+fn test_quic_socket_ecn_and_source() {
+    let receiver = create_quic_socket("127.0.0.1:0".parse().unwrap(), None, &QuicSocketOpts::default()).unwrap();
+    receiver.socket().set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+    let sender = create_quic_socket("0.0.0.0:0".parse().unwrap(), None, &QuicSocketOpts::default()).unwrap();
+    assert!(receiver.capabilities().recv_buffer_size > 0);
+
+    let mut transmit = Transmit::new(receiver.socket().local_addr().unwrap(), b"initial");
+    transmit.ecn = Some(Ecn::Ect0);
+    transmit.src_ip = Some("127.0.0.1".parse().unwrap());
+    assert_eq!(sender.send(&transmit).unwrap(), 7);
+
+    let mut buf = vec![0_u8; 65536];
+    let meta = receiver.recv(&mut buf).unwrap();
+    assert_eq!(&buf[..meta.len], b"initial");
+    assert_eq!((meta.stride, meta.segments()), (7, 1));
+    assert_eq!(meta.ecn(), Some(Ecn::Ect0));
+    assert_eq!(meta.source.port(), sender.socket().local_addr().unwrap().port());
+    assert_eq!(meta.info.dst_addr, Some("127.0.0.1".parse().unwrap()));
+    assert_eq!(sender.stats().datagrams_sent, 1);
+}
+
+#[test]
+fn test_quic_socket_segmentation() {
+    let opts = QuicSocketOpts { gro: false, ..QuicSocketOpts::default() };
+    let receiver = create_quic_socket("127.0.0.1:0".parse().unwrap(), None, &opts).unwrap();
+    receiver.socket().set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+    let sender = create_quic_socket("127.0.0.1:0".parse().unwrap(), None, &opts).unwrap();
+    assert_eq!(receiver.capabilities().gro_segments, 1);
+
+    let contents: Vec<u8> = (0..250_u8).collect();
+    let mut transmit = Transmit::new(receiver.socket().local_addr().unwrap(), &contents);
+    transmit.segment_size = Some(100);
+    if sender.capabilities().max_gso_segments == 1 {
+        // no UDP_SEGMENT support by the kernel
+        assert!(sender.send(&transmit).is_err());
+        return;
+    }
+    assert_eq!(sender.send(&transmit).unwrap(), 250);
+    let mut buf = vec![0_u8; 2048];
+    for expected in contents.chunks(100) {
+        let meta = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..meta.len], expected);
+    }
+    assert_eq!(sender.stats().datagrams_sent, 3);
+    assert_eq!(receiver.stats().datagrams_received, 3);
+
+    transmit.segment_size = Some(1);
+    assert!(sender.send(&transmit).is_err());
+}
+
+#[test]
+fn test_quic_socket_dual_stack() {
+    let receiver = create_quic_socket("[::]:0".parse().unwrap(), None, &QuicSocketOpts::default()).unwrap();
+    receiver.socket().set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+    let port = receiver.socket().local_addr().unwrap().port();
+    let sender = create_quic_socket("[::]:0".parse().unwrap(), None, &QuicSocketOpts::default()).unwrap();
+
+    let mut transmit = Transmit::new(format!("[::ffff:127.0.0.1]:{}", port).parse().unwrap(), b"mapped");
+    transmit.ecn = Some(Ecn::Ect1);
+    sender.send(&transmit).unwrap();
+    let mut buf = vec![0_u8; 65536];
+    let meta = receiver.recv(&mut buf).unwrap();
+    assert_eq!(&buf[..meta.len], b"mapped");
+    assert_eq!(meta.ecn(), Some(Ecn::Ect1));
+}
@@ -0,0 +1,38 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::{IpInterface, ra::{self, RaListener}};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{net::SocketAddrV6, time::Duration};
+
+#[test]
+fn test_ra_listener() {
+    let interfaces = IpInterface::retrieve_ip_interfaces().unwrap();
+    let link_local = interfaces.iter().find_map(|i| match i.address {
+        std::net::SocketAddr::V6(a) if a.ip().is_unicast_link_local() && i.supports_multicast() => Some((i.index, *a.ip())),
+        _ => None,
+    });
+    let Some((index, address)) = link_local else { return };
+    let listener = match RaListener::new(Some(index)) {
+        Ok(listener) => listener,
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => return,
+        Err(e) => panic!("{}", e),
+    };
+    listener.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+
+    let sender = Socket::new(Domain::IPV6, Type::RAW, Some(Protocol::ICMPV6)).unwrap();
+    sender.bind(&SocketAddrV6::new(address, 0, 0, index).into()).unwrap();
+    sender.set_multicast_hops_v6(255).unwrap();
+    sender.set_multicast_if_v6(index).unwrap();
+    sender.set_multicast_loop_v6(true).unwrap();
+    let mut advertisement = vec![134, 0, 0, 0, 64, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    advertisement.extend_from_slice(&[3, 4, 64, 0xc0, 0, 0, 0x0e, 0x10, 0, 0, 0x07, 0x08, 0, 0, 0, 0]);
+    advertisement.extend_from_slice(&"2001:db8:42::".parse::<std::net::Ipv6Addr>().unwrap().octets());
+    sender.send_to(&advertisement, &SocketAddrV6::new(ra::ALL_NODES_MULTICAST, 0, 0, index).into()).unwrap();
+
+    let ra = listener.recv().unwrap();
+    assert_eq!(ra.interface_index, index);
+    assert_eq!(ra.router, address);
+    assert!(ra.managed);
+    assert_eq!(ra.router_lifetime, Duration::ZERO);
+    assert_eq!(ra.prefixes[0].prefix, "2001:db8:42::".parse::<std::net::Ipv6Addr>().unwrap());
+}
@@ -0,0 +1,37 @@
+use net_utils::rate_limit::{RateLimit, RateLimitedSender};
+use std::{net::UdpSocket, time::{Duration, Instant}};
+
+#[test]
+fn test_rate_limited_sender() {
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let limit = RateLimit { packets_per_second: Some(100), burst_packets: Some(2), ..RateLimit::default() };
+    let mut sender = RateLimitedSender::new(UdpSocket::bind("127.0.0.1:0").unwrap(), limit);
+    let started = Instant::now();
+    for _ in 0..6 {
+        sender.send_to(b"data", receiver.local_addr().unwrap()).unwrap();
+    }
+    // the burst of 2 datagrams is sent at once, the other 4 at 10 ms intervals
+    assert!(started.elapsed() >= Duration::from_millis(40));
+    assert!(sender.delay(4) > Duration::ZERO);
+    let stats = sender.stats();
+    assert_eq!((stats.datagrams_sent, stats.bytes_sent), (6, 24));
+
+    let mut unlimited = RateLimitedSender::new(sender.into_inner(), RateLimit::default());
+    unlimited.send_to(b"data", receiver.local_addr().unwrap()).unwrap();
+    assert_eq!(unlimited.delay(100_000), Duration::ZERO);
+}
+
+#[cfg(feature = "tokio-net")]
+#[tokio::test]
+async fn test_rate_limited_sender_tokio() {
+    let receiver = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket.connect(receiver.local_addr().unwrap()).await.unwrap();
+    let limit = RateLimit { bytes_per_second: Some(10_000), burst_bytes: Some(1000), ..RateLimit::default() };
+    let mut sender = RateLimitedSender::new(socket, limit);
+    let started = Instant::now();
+    for _ in 0..3 {
+        sender.send(&[0_u8; 500]).await.unwrap();
+    }
+    assert!(started.elapsed() >= Duration::from_millis(50));
+}
@@ -0,0 +1,66 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::raw::{RawSocket, RawSocketBuilder};
+use std::{io::ErrorKind, net::IpAddr, time::Duration};
+
+/// experimental protocol number (RFC 3692)
+const PROTOCOL: u8 = 253;
+
+fn build(builder: RawSocketBuilder) -> Option<RawSocket> {
+    match builder.build() {
+        Ok(socket) => Some(socket),
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => None,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+#[test]
+fn test_raw_ipv4() {
+    let localhost: IpAddr = "127.0.0.1".parse().unwrap();
+    let Some(receiver) = build(RawSocketBuilder::ipv4(PROTOCOL).bind_device("lo").local_address(localhost)) else { return };
+    receiver.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+    assert!(!receiver.header_included().unwrap());
+    let sender = RawSocketBuilder::ipv4(PROTOCOL).hops(7).build().unwrap();
+    sender.send_to(b"payload", localhost).unwrap();
+    let mut buf = [0_u8; 128];
+    let (len, source) = receiver.recv_from(&mut buf).unwrap();
+    assert_eq!(source, localhost);
+    // received IPv4 packets include the header
+    assert_eq!((buf[0], buf[8], buf[9]), (0x45, 7, PROTOCOL));
+    assert_eq!(&buf[20..len], b"payload");
+
+    // the kernel fills length, checksum, identification and the source address
+    let sender = RawSocketBuilder::ipv4(PROTOCOL).header_included(true).build().unwrap();
+    assert!(sender.header_included().unwrap());
+    let mut packet = vec![0x45, 0, 0, 0, 0, 0, 0, 0, 3, PROTOCOL, 0, 0, 0, 0, 0, 0, 127, 0, 0, 1];
+    packet.extend_from_slice(b"own header");
+    sender.send_to(&packet, localhost).unwrap();
+    let (len, _) = receiver.recv_from(&mut buf).unwrap();
+    assert_eq!(len, packet.len());
+    assert_eq!(buf[8], 3);
+    assert_eq!(&buf[12..16], &[127, 0, 0, 1]);
+    assert_eq!(&buf[20..len], b"own header");
+}
+
+#[test]
+fn test_raw_ipv6_checksum() {
+    let localhost: IpAddr = "::1".parse().unwrap();
+    let Some(receiver) = build(RawSocketBuilder::ipv6(PROTOCOL).checksum_offset(0).local_address(localhost)) else { return };
+    receiver.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+    assert!(receiver.is_ipv6());
+    // packets with invalid checksums are dropped by the receiver
+    let unchecked = RawSocketBuilder::ipv6(PROTOCOL).build().unwrap();
+    unchecked.send_to(&[0, 0, 1, 2], localhost).unwrap();
+    let sender = RawSocketBuilder::ipv6(PROTOCOL).checksum_offset(0).build().unwrap();
+    sender.send_to(&[0, 0, 3, 4], localhost).unwrap();
+    let mut buf = [0_u8; 64];
+    let (len, source) = receiver.recv_from(&mut buf).unwrap();
+    assert_eq!((len, source), (4, localhost));
+    assert_eq!(&buf[2..4], &[3, 4]);
+    assert_ne!(&buf[..2], &[0, 0]);
+
+    let error = RawSocketBuilder::ipv4(PROTOCOL).checksum_offset(0).build().unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    let error = RawSocketBuilder::ipv4(PROTOCOL).local_address(localhost).build().unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidInput);
+}
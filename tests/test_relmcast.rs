@@ -0,0 +1,141 @@
+#![cfg(all(target_os = "linux", feature = "relmcast"))]
+
+use net_utils::{MulticastSocketBuilder, relmcast::{Delivery, ReliableReceiver, ReliableReceiverOpts, ReliableSender,
+                                                   ReliableSenderOpts}};
+use std::{net::{Ipv4Addr, SocketAddr, UdpSocket}, time::Duration};
+
+fn receiver_opts() -> ReliableReceiverOpts {
+    ReliableReceiverOpts { nack_interval: Duration::from_millis(50), nack_retries: 3, ..ReliableReceiverOpts::default() }
+}
+
+fn sender_opts() -> ReliableSenderOpts {
+    ReliableSenderOpts { heartbeat_interval: Duration::from_millis(100), retransmit_holdoff: Duration::ZERO,
+                         ..ReliableSenderOpts::default() }
+}
+
+fn payload(delivery: Delivery) -> Vec<u8> {
+    match delivery {
+        Delivery::Data { payload, .. } => payload,
+        delivery => panic!("{:?}", delivery),
+    }
+}
+
+#[test]
+fn test_multicast() {
+    let group: SocketAddr = "239.255.77.10:1916".parse().unwrap();
+    let socket = MulticastSocketBuilder::new(group, "127.0.0.1".parse().unwrap()).build_std().unwrap();
+    let mut receiver = ReliableReceiver::new(socket, receiver_opts());
+    receiver.set_read_timeout(Some(Duration::from_secs(2)));
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket2::SockRef::from(&socket).set_multicast_if_v4(&Ipv4Addr::LOCALHOST).unwrap();
+    let mut sender = ReliableSender::new(socket, group, sender_opts()).unwrap();
+
+    assert_eq!(sender.send(b"one").unwrap(), 1);
+    assert_eq!(sender.send(b"two").unwrap(), 2);
+    match receiver.recv().unwrap() {
+        Delivery::Data { sender: address, session, sequence, payload } => {
+            assert_eq!(address, sender.get_ref().local_addr().unwrap());
+            assert_eq!((session, sequence, payload.as_slice()), (sender.session(), 1, &b"one"[..]));
+        },
+        delivery => panic!("{:?}", delivery),
+    }
+    assert_eq!(payload(receiver.recv().unwrap()), b"two");
+    assert_eq!(sender.retransmissions(), 0);
+    assert_eq!(receiver.stats().datagrams_sent, 0);
+}
+
+/// Forwards datagrams from the sender to the receiver except the ones at the given positions,
+/// and NACKs from the receiver to the sender.
+fn relay(socket: UdpSocket, sender: SocketAddr, receiver: SocketAddr, drop: &'static [usize]) {
+    std::thread::spawn(move || {
+        let mut buffer = [0_u8; 2048];
+        let mut count = 0;
+        while let Ok((len, source)) = socket.recv_from(&mut buffer) {
+            if source == sender {
+                count += 1;
+                if !drop.contains(&count) {
+                    socket.send_to(&buffer[..len], receiver).unwrap();
+                }
+            } else {
+                socket.send_to(&buffer[..len], sender).unwrap();
+            }
+        }
+    });
+}
+
+#[test]
+fn test_loss_recovery() {
+    let relay_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let receiver_address = socket.local_addr().unwrap();
+    let mut receiver = ReliableReceiver::new(socket, receiver_opts());
+    receiver.set_read_timeout(Some(Duration::from_secs(2)));
+    let mut sender = ReliableSender::new(UdpSocket::bind("127.0.0.1:0").unwrap(), relay_socket.local_addr().unwrap(),
+                                         sender_opts()).unwrap();
+    // the second datagram is lost, as is the first heartbeat
+    relay(relay_socket, sender.get_ref().local_addr().unwrap(), receiver_address, &[2, 4]);
+
+    let receiving = std::thread::spawn(move || (0..3).map(|_| payload(receiver.recv().unwrap())).collect::<Vec<_>>());
+    for data in [b"one", b"two", b"six"].iter() {
+        sender.send(*data).unwrap();
+    }
+    while !receiving.is_finished() {
+        sender.poll(Duration::from_millis(20)).unwrap();
+    }
+    assert_eq!(receiving.join().unwrap(), vec![b"one".to_vec(), b"two".to_vec(), b"six".to_vec()]);
+    assert!(sender.retransmissions() >= 1);
+}
+
+#[test]
+fn test_tail_loss() {
+    let relay_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let receiver_address = socket.local_addr().unwrap();
+    let mut receiver = ReliableReceiver::new(socket, receiver_opts());
+    receiver.set_read_timeout(Some(Duration::from_secs(2)));
+    let relay_address = relay_socket.local_addr().unwrap();
+    let opts = ReliableSenderOpts { window: 1, ..sender_opts() };
+    let mut sender = ReliableSender::new(UdpSocket::bind("127.0.0.1:0").unwrap(), relay_address, opts).unwrap();
+    // the last two datagrams are lost, only the last one can be retransmitted
+    relay(relay_socket, sender.get_ref().local_addr().unwrap(), receiver_address, &[2, 3]);
+
+    let receiving = std::thread::spawn(move || (0..3).map(|_| receiver.recv().unwrap()).collect::<Vec<_>>());
+    for data in [b"one", b"two", b"six"].iter() {
+        sender.send(*data).unwrap();
+    }
+    sender.heartbeat().unwrap();
+    while !receiving.is_finished() {
+        sender.poll(Duration::from_millis(20)).unwrap();
+    }
+    let deliveries = receiving.join().unwrap();
+    assert!(matches!(deliveries[0], Delivery::Data { sequence: 1, .. }));
+    // the receiver sees the relay as the sender
+    assert_eq!(deliveries[1], Delivery::Lost { sender: relay_address, session: sender.session(), sequence: 2, count: 1 });
+    assert!(matches!(deliveries[2], Delivery::Data { sequence: 3, .. }));
+}
+
+#[test]
+fn test_fragmented_message() {
+    let relay_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let receiver_address = socket.local_addr().unwrap();
+    let mut receiver = ReliableReceiver::new(socket, receiver_opts());
+    receiver.set_read_timeout(Some(Duration::from_secs(2)));
+    let opts = ReliableSenderOpts { max_datagram: 500, ..sender_opts() };
+    let mut sender = ReliableSender::new(UdpSocket::bind("127.0.0.1:0").unwrap(), relay_socket.local_addr().unwrap(),
+                                         opts).unwrap();
+    // the third of the eleven fragments is lost
+    relay(relay_socket, sender.get_ref().local_addr().unwrap(), receiver_address, &[3]);
+
+    let receiving = std::thread::spawn(move || (0..2).map(|_| receiver.recv().unwrap()).collect::<Vec<_>>());
+    let message: Vec<u8> = (0..5000).map(|i| i as u8).collect();
+    assert_eq!(sender.send(&message).unwrap(), 1);
+    assert_eq!(sender.send(b"small").unwrap(), 12);
+    while !receiving.is_finished() {
+        sender.poll(Duration::from_millis(20)).unwrap();
+    }
+    let deliveries = receiving.join().unwrap();
+    assert!(matches!(&deliveries[0], Delivery::Data { sequence: 1, payload, .. } if *payload == message));
+    assert!(matches!(&deliveries[1], Delivery::Data { sequence: 12, payload, .. } if payload == b"small"));
+    assert!(sender.retransmissions() >= 1);
+}
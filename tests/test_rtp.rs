@@ -0,0 +1,40 @@
+mod common;
+
+use common::multicast_routed;
+use net_utils::rtp::{self, RtpHeader, RtpSession};
+use std::{
+    io::ErrorKind,
+    net::{IpAddr, Ipv4Addr},
+    time::Duration,
+};
+
+#[test]
+fn test_rtp_session() {
+    let group = "239.255.77.10:50770".parse().unwrap();
+    let session = RtpSession::join(group, &IpAddr::V4(Ipv4Addr::UNSPECIFIED), 1, rtp::DSCP_EF).unwrap();
+    assert_eq!(session.rtcp_destination().port(), 50771);
+    assert_eq!(session.rtcp_socket().local_addr().unwrap().port(), 50771);
+
+    let header = RtpHeader { payload_type: 97, sequence: 1, timestamp: 48000, ssrc: 42, ..RtpHeader::default() };
+    let mut packet = Vec::new();
+    header.encode(&mut packet).unwrap();
+    packet.extend_from_slice(&[1, 2, 3, 4]);
+    if multicast_routed(session.send_rtp(&packet)).is_none() {
+        return;
+    }
+    session.rtp_socket().set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+    let mut buffer = [0_u8; 1500];
+    let (len, _) = session.rtp_socket().recv_from(&mut buffer).unwrap();
+    let (received, payload) = RtpHeader::parse(&buffer[..len]).unwrap();
+    assert_eq!(received, header);
+    assert_eq!(payload, &[1, 2, 3, 4]);
+}
+
+#[test]
+fn test_rtp_session_odd_port() {
+    let group = "239.255.77.10:50771".parse().unwrap();
+    let error = RtpSession::join(group, &IpAddr::V4(Ipv4Addr::UNSPECIFIED), 1, 0).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    let group = "[ff15::77]:50770".parse().unwrap();
+    assert!(RtpSession::join(group, &IpAddr::V4(Ipv4Addr::UNSPECIFIED), 1, 0).is_err());
+}
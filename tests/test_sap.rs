@@ -0,0 +1,37 @@
+mod common;
+
+use common::multicast_routed;
+use net_utils::sap;
+use std::{
+    io::ErrorKind,
+    net::{IpAddr, Ipv4Addr},
+    time::Duration,
+};
+
+const SDP: &str = "v=0\r\no=- 42 1 IN IP4 192.0.2.2\r\ns=net-utils test\r\nc=IN IP4 239.69.11.12/32\r\nt=0 0\r\n\
+    m=audio 5004 RTP/AVP 97\r\na=rtpmap:97 L16/48000/2\r\n";
+
+#[test]
+fn test_announce_and_listen() {
+    let listener = match sap::SapListener::join(&Ipv4Addr::UNSPECIFIED) {
+        Ok(listener) => listener,
+        Err(e) if e.kind() == ErrorKind::AddrInUse || e.kind() == ErrorKind::PermissionDenied => return,
+        Err(e) => panic!("{}", e),
+    };
+    listener.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+    let announcer = sap::SapAnnouncer::new(&Ipv4Addr::UNSPECIFIED, 1).unwrap();
+    let announcement = sap::SapAnnouncement::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2)), SDP);
+    if multicast_routed(announcer.send(&announcement)).is_none() {
+        return;
+    }
+    // other announcements on the network are skipped
+    loop {
+        let (received, _) = listener.recv().unwrap();
+        if received == announcement {
+            break;
+        }
+    }
+    let session = announcement.session().unwrap();
+    assert_eq!(session.name, "net-utils test");
+    assert_eq!(session.media_destination(&session.media[0]), Some("239.69.11.12:5004".parse().unwrap()));
+}
@@ -0,0 +1,86 @@
+#![cfg(any(feature = "tokio-net", target_os = "linux"))]
+
+use net_utils::scan::{self, PortState, ScanOpts};
+use std::{
+    net::{Ipv4Addr, TcpListener},
+    time::Duration,
+};
+
+/// Returns a local port nothing listens on for TCP.
+fn closed_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+#[cfg(feature = "tokio-net")]
+#[tokio::test]
+async fn test_tcp_connect_scan() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let open = listener.local_addr().unwrap().port();
+    let closed = closed_port();
+    let localhost = std::net::IpAddr::V4(Ipv4Addr::LOCALHOST);
+    let opts = ScanOpts { concurrency: 1, rate: 100, ..ScanOpts::default() };
+    let results = scan::tcp_connect_scan(&[localhost], &[closed, open], &opts).await.unwrap();
+    assert_eq!(results.len(), 2);
+    for result in results {
+        let expected = if result.target.port() == open { PortState::Open } else { PortState::Closed };
+        assert_eq!(result.state, expected);
+    }
+}
+
+#[cfg(all(feature = "tokio-net", target_os = "linux"))]
+#[tokio::test]
+async fn test_tcp_connect_scan_device() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let opts = ScanOpts { device: Some("lo".to_string()), source: Some(Ipv4Addr::LOCALHOST.into()), ..ScanOpts::default() };
+    let target = listener.local_addr().unwrap();
+    let results = match scan::tcp_connect_scan(&[target.ip()], &[target.port()], &opts).await {
+        Ok(results) => results,
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => return,
+        Err(e) => panic!("{}", e),
+    };
+    assert_eq!(results[0].state, PortState::Open);
+}
+
+#[cfg(feature = "tokio-net")]
+#[tokio::test]
+async fn test_udp_scan() {
+    let echo = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let open = echo.local_addr().unwrap().port();
+    let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let unanswered = silent.local_addr().unwrap().port();
+    let closed = std::net::UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    std::thread::spawn(move || {
+        let mut buffer = [0_u8; 64];
+        while let Ok((len, from)) = echo.recv_from(&mut buffer) {
+            echo.send_to(&buffer[..len], from).unwrap();
+        }
+    });
+    let opts = ScanOpts { timeout: Duration::from_millis(300), ..ScanOpts::default() };
+    let results = scan::udp_scan(&[Ipv4Addr::LOCALHOST.into()], &[open, unanswered, closed], b"ping", &opts)
+        .await.unwrap();
+    let state = |port: u16| results.iter().find(|r| r.target == std::net::SocketAddr::from((Ipv4Addr::LOCALHOST, port)))
+        .unwrap().state;
+    assert_eq!(state(open), PortState::Open);
+    assert_eq!(state(unanswered), PortState::OpenOrFiltered);
+    assert_eq!(state(closed), PortState::Closed);
+    drop(silent);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_syn_scan() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let open = listener.local_addr().unwrap().port();
+    let closed = closed_port();
+    let opts = ScanOpts { timeout: Duration::from_millis(500), ..ScanOpts::default() };
+    let results = match scan::syn_scan(&[Ipv4Addr::LOCALHOST], &[open, closed], &opts) {
+        Ok(results) => results,
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => return,
+        Err(e) => panic!("{}", e),
+    };
+    assert_eq!(results.len(), 2);
+    for result in results {
+        let expected = if result.target.port() == open { PortState::Open } else { PortState::Closed };
+        assert_eq!(result.state, expected);
+    }
+}
@@ -0,0 +1,21 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::sctp::{SctpSocket, SctpStyle};
+
+#[test]
+fn test_sctp_one_to_many() {
+    let server = match SctpSocket::bind(SctpStyle::OneToMany, &["127.0.0.1:0".parse().unwrap()]) {
+        Ok(socket) => socket,
+        // kernel without SCTP support
+        Err(e) if e.raw_os_error() == Some(libc::EPROTONOSUPPORT)
+            || e.raw_os_error() == Some(libc::ESOCKTNOSUPPORT) => return,
+        Err(e) => panic!("{}", e),
+    };
+    server.listen(16).unwrap();
+    let address = server.local_addr().unwrap();
+    let client = SctpSocket::bind(SctpStyle::OneToMany, &["127.0.0.1:0".parse().unwrap()]).unwrap();
+    client.send_to(b"hello", &address).unwrap();
+    let mut buffer = [0_u8; 16];
+    let (len, _) = server.recv_from(&mut buffer).unwrap();
+    assert_eq!(&buffer[..len], b"hello");
+}
@@ -0,0 +1,57 @@
+#![cfg(all(target_os = "linux", feature = "secure"))]
+
+use net_utils::{MulticastSocketBuilder, fec::{FecDecoder, FecEncoder}, secure::{Opener, Sealer, SecureReceiver,
+                                                                                 SecureSender}};
+use std::{net::{Ipv4Addr, SocketAddr, UdpSocket}, time::Duration};
+
+const PSK: &[u8] = b"net-utils test pre-shared key";
+
+#[test]
+fn test_multicast() {
+    let group: SocketAddr = "239.255.77.12:1918".parse().unwrap();
+    let socket = MulticastSocketBuilder::new(group, "127.0.0.1".parse().unwrap())
+        .read_timeout(Duration::from_secs(2))
+        .build_std()
+        .unwrap();
+    let mut receiver = SecureReceiver::new(socket, PSK).unwrap();
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket2::SockRef::from(&socket).set_multicast_if_v4(&Ipv4Addr::LOCALHOST).unwrap();
+    let mut sender = SecureSender::new(socket, group, PSK).unwrap();
+
+    // a packet with another key, a replayed packet and a plain datagram are dropped
+    let intruder = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket2::SockRef::from(&intruder).set_multicast_if_v4(&Ipv4Addr::LOCALHOST).unwrap();
+    let forged = Sealer::new(b"guessed pre-shared key").unwrap().seal(b"forged");
+    intruder.send_to(&forged, group).unwrap();
+    let mut sealer = Sealer::new(PSK).unwrap();
+    let packet = sealer.seal(b"once");
+    intruder.send_to(&packet, group).unwrap();
+    intruder.send_to(&packet, group).unwrap();
+    intruder.send_to(b"plain", group).unwrap();
+    sender.send(b"secret").unwrap();
+
+    let mut buf = [0_u8; 64];
+    let (len, _, sender_id) = receiver.recv_from(&mut buf).unwrap();
+    assert_eq!((&buf[..len], sender_id), (&b"once"[..], sealer.sender_id()));
+    let (len, source, sender_id) = receiver.recv_from(&mut buf).unwrap();
+    assert_eq!(&buf[..len], b"secret");
+    assert_eq!((source, sender_id), (sender.get_ref().local_addr().unwrap(), sender.sender_id()));
+    assert_eq!(receiver.rejected(), 3);
+}
+
+#[test]
+fn test_with_fec() {
+    let mut encoder = FecEncoder::new(2, 3).unwrap();
+    let mut sealer = Sealer::new(PSK).unwrap();
+    let packets: Vec<Vec<u8>> = [&b"one"[..], b"two"].iter()
+        .flat_map(|datagram| encoder.encode(datagram).unwrap())
+        .map(|packet| sealer.seal(&packet))
+        .collect();
+    let mut opener = Opener::new(PSK).unwrap();
+    let mut decoder = FecDecoder::new();
+    // the first packet is lost
+    let received: Vec<Vec<u8>> = packets[1..].iter()
+        .flat_map(|packet| decoder.push(&opener.open(packet).unwrap()))
+        .collect();
+    assert_eq!(received, vec![b"two".to_vec(), b"one".to_vec()]);
+}
@@ -0,0 +1,47 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::{
+    igmp_proxy::IgmpMessage,
+    membership_report::IgmpReporter,
+    snooping::{SnoopingEvent, SnoopingObserver},
+};
+use std::{io::ErrorKind, net::{IpAddr, Ipv4Addr}, time::Duration};
+
+/// Receives events until one matches, None after ten messages without.
+fn wait_for(observer: &mut SnoopingObserver, expected: &SnoopingEvent) -> Option<()> {
+    for _ in 0..10 {
+        if observer.recv().unwrap().contains(expected) {
+            return Some(());
+        }
+    }
+    None
+}
+
+#[test]
+fn test_snooping_observer() {
+    let mut observer = match SnoopingObserver::new(Some(1)) {
+        Ok(observer) => observer,
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => return,
+        Err(e) => panic!("{}", e),
+    };
+    observer.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+    let mut reporter = IgmpReporter::new("lo").unwrap();
+    reporter.set_source("127.0.0.21".parse().unwrap());
+    let querier = IpAddr::V4(reporter.source());
+
+    let query = IgmpMessage::Query { max_response_time: Duration::from_secs(10), group: Ipv4Addr::UNSPECIFIED, sources: vec![] };
+    reporter.send(&query).unwrap();
+    wait_for(&mut observer, &SnoopingEvent::QuerierChanged { interface_index: 1, ipv6: false, previous: None,
+                                                              querier: Some(querier) }).unwrap();
+    let info = observer.table().querier(1, false).unwrap();
+    assert_eq!((info.address, info.version), (querier, 2));
+
+    let group = "239.1.2.4".parse().unwrap();
+    reporter.set_source("127.0.0.22".parse().unwrap());
+    reporter.send(&IgmpMessage::ReportV2 { group }).unwrap();
+    wait_for(&mut observer, &SnoopingEvent::GroupJoined { interface_index: 1, group: IpAddr::V4(group) }).unwrap();
+    assert_eq!(observer.table().reporters(1, &IpAddr::V4(group)), vec![IpAddr::V4(reporter.source())]);
+    reporter.send(&IgmpMessage::Leave { group }).unwrap();
+    wait_for(&mut observer, &SnoopingEvent::GroupLeft { interface_index: 1, group: IpAddr::V4(group) }).unwrap();
+    assert!(observer.table().groups(1).is_empty());
+}
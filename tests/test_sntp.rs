@@ -0,0 +1,52 @@
+use net_utils::sntp::{self, SntpOpts};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn ntp_timestamp(time: SystemTime) -> [u8; 8] {
+    let since_unix = time.duration_since(UNIX_EPOCH).unwrap();
+    let seconds = since_unix.as_secs() + 2_208_988_800;
+    let fraction = ((since_unix.subsec_nanos() as u64) << 32) / 1_000_000_000;
+    ((seconds << 32) | fraction).to_be_bytes()
+}
+
+/// Answers a single SNTP request with a clock that is `ahead` seconds ahead of the local one.
+fn fake_server(ahead: u64) -> std::net::SocketAddr {
+    let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let mut request = [0_u8; 48];
+        let (_, client) = server.recv_from(&mut request).unwrap();
+        let mut response = [0_u8; 48];
+        response[0] = 0x24;
+        response[1] = 1;
+        response[12..16].copy_from_slice(b"GPS\0");
+        response[24..32].copy_from_slice(&request[40..48]);
+        let now = SystemTime::now() + Duration::from_secs(ahead);
+        response[32..40].copy_from_slice(&ntp_timestamp(now));
+        response[40..48].copy_from_slice(&ntp_timestamp(now));
+        server.send_to(&response, client).unwrap();
+    });
+    address
+}
+
+#[test]
+fn test_sntp_query() {
+    let result = sntp::query(fake_server(100), &SntpOpts::default()).unwrap();
+    assert!((result.offset - 100.0).abs() < 1.0);
+    assert_eq!(result.stratum, 1);
+    assert_eq!(&result.reference_id, b"GPS\0");
+}
+
+#[test]
+fn test_sntp_timeout() {
+    let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let opts = SntpOpts { timeout: Duration::from_millis(100), ..SntpOpts::default() };
+    let err = sntp::query(silent.local_addr().unwrap(), &opts).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+}
+
+#[cfg(feature = "tokio-net")]
+#[tokio::test]
+async fn test_sntp_query_async() {
+    let result = sntp::query_async(fake_server(0), &SntpOpts::default()).await.unwrap();
+    assert!(result.offset.abs() < 1.0);
+}
@@ -0,0 +1,60 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::{
+    MulticastSocketBuilder,
+    socket_factory::{SocketFactory, SocketSpec, UdpSpec},
+    tcp::TcpListenerBuilder,
+};
+use std::io::ErrorKind;
+
+#[test]
+fn test_pool_and_seal() {
+    let mut factory = SocketFactory::new();
+    factory.register("client", UdpSpec::new("127.0.0.1:0".parse().unwrap()), 2).unwrap();
+    factory.register("control", TcpListenerBuilder::new("127.0.0.1:0".parse().unwrap()), 1).unwrap();
+    assert_eq!((factory.available("client"), factory.available("control")), (2, 1));
+
+    // before sealing an exhausted pool creates sockets on demand
+    let listener = factory.take_tcp_listener("control").unwrap();
+    assert!(listener.local_addr().unwrap().port() != 0);
+    factory.take_tcp_listener("control").unwrap();
+    // a socket of another kind stays in the pool
+    assert_eq!(factory.take_udp("control").unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(factory.available("control"), 1);
+    assert_eq!(factory.take_udp("unknown").unwrap_err().kind(), ErrorKind::NotFound);
+
+    factory.seal();
+    assert!(factory.is_sealed());
+    let error = factory.register("client", UdpSpec::new("127.0.0.1:0".parse().unwrap()), 1).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+    let first = factory.take_udp("client").unwrap();
+    let second = factory.take_udp("client").unwrap();
+    assert_ne!(first.local_addr().unwrap(), second.local_addr().unwrap());
+    assert_eq!(factory.take_udp("client").unwrap_err().kind(), ErrorKind::PermissionDenied);
+    factory.take_tcp_listener("control").unwrap();
+    assert_eq!(factory.take_tcp_listener("control").unwrap_err().kind(), ErrorKind::PermissionDenied);
+}
+
+#[test]
+fn test_register() {
+    let mut factory = SocketFactory::new();
+    let group = "239.255.77.16:1922".parse().unwrap();
+    let builder = MulticastSocketBuilder::new(group, "127.0.0.1".parse().unwrap());
+    factory.register("events", builder.clone(), 1).unwrap();
+    assert_eq!(factory.spec("events"), Some(&SocketSpec::Multicast(builder.clone())));
+    let error = factory.register("events", builder.ttl(4), 1).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    assert_eq!(factory.available("events"), 1);
+    assert_eq!(factory.take_udp("events").unwrap().local_addr().unwrap(), group);
+
+    // a socket which cannot be created leaves no pool
+    let spec = UdpSpec::new("127.0.0.1:0".parse().unwrap()).bind_device("no-such-device0");
+    assert!(factory.register("bound", spec, 1).is_err());
+    assert_eq!(factory.spec("bound"), None);
+
+    let spec = UdpSpec::new("127.0.0.1:0".parse().unwrap()).recv_buffer_size(65536).send_buffer_size(65536);
+    assert!(!spec.forces_buffers());
+    factory.register("buffered", spec, 1).unwrap();
+    let socket = factory.take_udp("buffered").unwrap();
+    assert!(socket2::SockRef::from(&socket).recv_buffer_size().unwrap() >= 65536);
+}
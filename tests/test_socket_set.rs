@@ -0,0 +1,96 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::{MulticastSocketBuilder, multicast_groups::is_joined, socket_set::*};
+use std::{io::ErrorKind, net::IpAddr, os::unix::io::AsRawFd};
+
+fn entry(name: &str, group: &str, interface: &str) -> (String, MulticastSocketBuilder) {
+    (name.to_string(), MulticastSocketBuilder::new(group.parse().unwrap(), interface.parse().unwrap()))
+}
+
+fn fd(sockets: &SocketSet, name: &str) -> i32 {
+    sockets.get(name).unwrap().as_raw_fd()
+}
+
+#[test]
+fn test_apply() {
+    let mut sockets = SocketSet::new();
+    let config = vec![entry("a", "239.255.77.7:1913", "127.0.0.1"), entry("b", "239.255.77.8:1914", "127.0.0.1")];
+    assert_eq!(sockets.apply(&config).unwrap(),
+               vec![SocketSetEvent::Opened("a".to_string()), SocketSetEvent::Opened("b".to_string())]);
+    let (a, b) = (fd(&sockets, "a"), fd(&sockets, "b"));
+    assert_eq!(sockets.apply(&config).unwrap(), vec![]);
+    assert_eq!((fd(&sockets, "a"), fd(&sockets, "b")), (a, b));
+
+    // moving a to another interface keeps its socket, changed options of b replace the socket
+    let group: IpAddr = "239.255.77.7".parse().unwrap();
+    let eth0 = net_utils::IpInterface::retrieve_ip_interfaces().unwrap().into_iter()
+        .find(|i| i.address.ip() == "192.0.2.2".parse::<IpAddr>().unwrap());
+    let moved = match eth0 {
+        Some(eth0) => {
+            let config = vec![entry("a", "239.255.77.7:1913", "192.0.2.2"),
+                              (config[1].0.clone(), config[1].1.clone().ttl(4))];
+            assert_eq!(sockets.apply(&config).unwrap(), vec![
+                SocketSetEvent::Rejoined { name: "a".to_string(), from: "127.0.0.1".parse().unwrap(),
+                                           to: "192.0.2.2".parse().unwrap() },
+                SocketSetEvent::Reopened("b".to_string()),
+            ]);
+            assert_eq!(fd(&sockets, "a"), a);
+            assert!(is_joined(&group, eth0.index).unwrap());
+            assert_eq!(sockets.builder("a").unwrap().interface(), "192.0.2.2".parse::<IpAddr>().unwrap());
+            true
+        },
+        None => false,
+    };
+
+    let config = vec![entry("c", "239.255.77.9:1915", "127.0.0.1")];
+    let mut events = sockets.apply(&config).unwrap();
+    events.sort_by_key(|event| format!("{:?}", event));
+    assert_eq!(events, vec![SocketSetEvent::Closed("a".to_string()), SocketSetEvent::Closed("b".to_string()),
+                            SocketSetEvent::Opened("c".to_string())]);
+    assert_eq!(sockets.len(), 1);
+    if moved {
+        assert!(sockets.get("a").is_none());
+    }
+
+    // a failing entry leaves the set unchanged
+    let c = fd(&sockets, "c");
+    let invalid = vec![config[0].clone(), entry("d", "192.0.2.1:1916", "127.0.0.1")];
+    assert_eq!(sockets.apply(&invalid).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(sockets.iter().map(|(name, _)| name).collect::<Vec<_>>(), vec!["c"]);
+    assert_eq!(fd(&sockets, "c"), c);
+
+    let duplicate = vec![config[0].clone(), config[0].clone()];
+    assert_eq!(sockets.apply(&duplicate).unwrap_err().kind(), ErrorKind::InvalidInput);
+    assert_eq!(sockets.clear(), vec![SocketSetEvent::Closed("c".to_string())]);
+    assert!(sockets.is_empty());
+}
+
+#[test]
+fn test_failed_rejoin() {
+    let eth0 = net_utils::IpInterface::retrieve_ip_interfaces().unwrap().into_iter()
+        .find(|i| i.address.ip() == "192.0.2.2".parse::<IpAddr>().unwrap());
+    let eth0 = match eth0 {
+        Some(eth0) => eth0,
+        None => return,
+    };
+    let mut sockets = SocketSet::new();
+    let config = vec![entry("a", "239.255.77.17:1923", "127.0.0.1"), entry("b", "239.255.77.18:1924", "127.0.0.1")];
+    sockets.apply(&config).unwrap();
+    let a = fd(&sockets, "a");
+
+    // a joins on eth0, b fails on an address of no interface
+    let moved = vec![entry("a", "239.255.77.17:1923", "192.0.2.2"), entry("b", "239.255.77.18:1924", "192.0.2.77")];
+    assert!(sockets.apply(&moved).is_err());
+    let group: IpAddr = "239.255.77.17".parse().unwrap();
+    assert!(!is_joined(&group, eth0.index).unwrap());
+    assert_eq!(fd(&sockets, "a"), a);
+    assert_eq!(sockets.builder("a").unwrap().interface(), "127.0.0.1".parse::<IpAddr>().unwrap());
+    let socket = socket2::SockRef::from(sockets.get("a").unwrap());
+    assert_eq!(socket.multicast_if_v4().unwrap(), "127.0.0.1".parse::<std::net::Ipv4Addr>().unwrap());
+
+    // the membership on the previous interface was kept, the one on eth0 left
+    assert_eq!(sockets.apply(&moved[..1]).unwrap()[0], SocketSetEvent::Rejoined {
+        name: "a".to_string(), from: "127.0.0.1".parse().unwrap(), to: "192.0.2.2".parse().unwrap(),
+    });
+    assert!(is_joined(&group, eth0.index).unwrap());
+}
@@ -0,0 +1,58 @@
+use net_utils::stun;
+use std::time::Duration;
+
+/// Answers a single binding request with the client's address in an XOR-MAPPED-ADDRESS
+/// attribute, after ignoring the first `drop` requests.
+fn fake_server(drop: usize) -> std::net::SocketAddr {
+    let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let mut request = [0_u8; 64];
+        for _ in 0..drop {
+            server.recv_from(&mut request).unwrap();
+        }
+        let (_, client) = server.recv_from(&mut request).unwrap();
+        let client = match client {
+            std::net::SocketAddr::V4(client) => client,
+            _ => unreachable!(),
+        };
+        let mut response = request[..20].to_vec();
+        response[0..4].copy_from_slice(&[0x01, 0x01, 0x00, 0x0c]);
+        response.extend_from_slice(&[0x00, 0x20, 0x00, 0x08, 0x00, 0x01]);
+        response.extend_from_slice(&(client.port() ^ 0x2112).to_be_bytes());
+        response.extend_from_slice(&(u32::from(*client.ip()) ^ 0x2112_a442).to_be_bytes());
+        server.send_to(&response, client).unwrap();
+    });
+    address
+}
+
+#[test]
+fn test_stun_binding_request() {
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mapped = stun::binding_request(&socket, fake_server(0), Duration::from_secs(5)).unwrap();
+    assert_eq!(mapped, socket.local_addr().unwrap());
+    assert_eq!(socket.read_timeout().unwrap(), None);
+}
+
+#[test]
+fn test_stun_retransmission() {
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mapped = stun::binding_request(&socket, fake_server(1), Duration::from_secs(5)).unwrap();
+    assert_eq!(mapped, socket.local_addr().unwrap());
+}
+
+#[test]
+fn test_stun_timeout() {
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let err = stun::binding_request(&socket, silent.local_addr().unwrap(), Duration::from_millis(100)).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+}
+
+#[cfg(feature = "tokio-net")]
+#[tokio::test]
+async fn test_stun_binding_request_async() {
+    let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let mapped = stun::binding_request_async(&socket, fake_server(0), Duration::from_secs(5)).await.unwrap();
+    assert_eq!(mapped, socket.local_addr().unwrap());
+}
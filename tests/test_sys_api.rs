@@ -0,0 +1,80 @@
+#![cfg(all(target_os = "linux", feature = "test-util"))]
+
+use net_utils::{
+    IpInterface, MockInterfaceProvider, MockSysApi, MulticastSocketBuilder, SysCall, netperf::UdpReflector,
+    socket_factory::{SocketFactory, UdpSpec}, udp::create_connected_udp, with_sys_api,
+};
+use std::{
+    io::ErrorKind,
+    os::unix::io::AsRawFd,
+    sync::Arc,
+};
+
+#[test]
+fn test_factory_without_network() {
+    let mock = Arc::new(MockSysApi::new());
+    with_sys_api(mock.clone(), || {
+        let mut factory = SocketFactory::new();
+        factory.register("dns", UdpSpec::new("192.0.2.53:5353".parse().unwrap()).recv_buffer_size(1 << 20), 2).unwrap();
+        let socket = factory.take_udp("dns").unwrap();
+        assert_eq!(mock.int_option(socket.as_raw_fd(), libc::SOL_SOCKET, libc::SO_RCVBUF), Some(1 << 20));
+        assert_eq!(mock.calls().iter().filter(|call| call.name() == "bind").count(), 2);
+
+        mock.fail("bind", libc::EADDRINUSE);
+        let error = factory.register("ntp", UdpSpec::new("192.0.2.53:5123".parse().unwrap()), 1).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::AddrInUse);
+        assert!(matches!(mock.calls().last(), Some(SysCall::Bind { .. })));
+    });
+}
+
+#[test]
+fn test_mock_interfaces() {
+    let mock = Arc::new(MockSysApi::new());
+    mock.set_interfaces(vec![MockInterfaceProvider::interface(2, "eth0", libc::IFF_UP, "192.0.2.1:0".parse().unwrap())]);
+    let interfaces = with_sys_api(mock, IpInterface::retrieve_ip_interfaces).unwrap();
+    assert_eq!(interfaces.len(), 1);
+    assert_eq!(interfaces[0].index, 2);
+}
+
+#[test]
+fn test_multicast_join_failure() {
+    let mock = Arc::new(MockSysApi::new());
+    with_sys_api(mock.clone(), || {
+        let builder = MulticastSocketBuilder::new("239.255.77.5:1911".parse().unwrap(), "127.0.0.1".parse().unwrap());
+        let socket = builder.build_std().unwrap();
+        assert!(mock.option(socket.as_raw_fd(), libc::IPPROTO_IP, libc::IP_ADD_MEMBERSHIP).is_some());
+
+        mock.fail("setsockopt", libc::ENODEV);
+        assert_eq!(builder.build_std().unwrap_err().raw_os_error(), Some(libc::ENODEV));
+        mock.succeed("setsockopt");
+        mock.fail("bind", libc::EADDRINUSE);
+        assert_eq!(builder.build_std().unwrap_err().kind(), ErrorKind::AddrInUse);
+    });
+}
+
+#[test]
+fn test_connected_udp_failures() {
+    let mock = Arc::new(MockSysApi::new());
+    with_sys_api(mock.clone(), || {
+        let destination = "192.0.2.7:9".parse().unwrap();
+        mock.fail("setsockopt", libc::ENOPROTOOPT);
+        assert_eq!(create_connected_udp(&destination, None).unwrap_err().raw_os_error(), Some(libc::ENOPROTOOPT));
+        assert!(!mock.calls().iter().any(|call| call.name() == "bind"));
+        mock.succeed("setsockopt");
+        mock.fail("bind", libc::EADDRNOTAVAIL);
+        assert_eq!(create_connected_udp(&destination, None).unwrap_err().kind(), ErrorKind::AddrNotAvailable);
+    });
+}
+
+#[test]
+fn test_protocol_socket_bind() {
+    let mock = Arc::new(MockSysApi::new());
+    with_sys_api(mock.clone(), || {
+        let address = "192.0.2.9:5001".parse().unwrap();
+        UdpReflector::bind(address).unwrap();
+        assert!(mock.calls().iter().any(|call| matches!(call, SysCall::Bind { address: bound, .. }
+            if bound.as_socket() == Some(address))));
+        mock.fail("bind", libc::EADDRINUSE);
+        assert_eq!(UdpReflector::bind(address).unwrap_err().kind(), ErrorKind::AddrInUse);
+    });
+}
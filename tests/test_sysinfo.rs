@@ -0,0 +1,49 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::sysinfo::{self, Family, RpFilter};
+use std::io::ErrorKind;
+
+#[test]
+fn test_read_settings() {
+    assert!(sysinfo::rp_filter("lo").is_ok());
+    assert!(sysinfo::effective_rp_filter("lo").is_ok());
+    assert!(sysinfo::forwarding(Family::Ipv4, "all").is_ok());
+    assert!(!sysinfo::mc_forwarding(Family::Ipv4, "lo").unwrap());
+    if std::path::Path::new("/proc/sys/net/ipv6/conf/lo").exists() {
+        assert!(sysinfo::accept_ra("lo").is_ok());
+        assert!(sysinfo::forwarding(Family::Ipv6, "lo").is_ok());
+    }
+    let global = sysinfo::ip_forwarding(Family::Ipv4, None).unwrap();
+    assert_eq!(global, std::fs::read_to_string("/proc/sys/net/ipv4/ip_forward").unwrap().trim() == "1");
+    assert!(sysinfo::ip_forwarding(Family::Ipv4, Some("lo")).is_ok());
+    let err = sysinfo::rp_filter("no-such-interface").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+}
+
+#[test]
+fn test_set_rp_filter() {
+    let previous = sysinfo::rp_filter("lo").unwrap();
+    let filter = if previous == RpFilter::Loose { RpFilter::Strict } else { RpFilter::Loose };
+    match sysinfo::set_rp_filter("lo", filter) {
+        Ok(()) => {},
+        // no permission or read-only /proc/sys in containers
+        Err(e) if e.kind() == ErrorKind::PermissionDenied || e.raw_os_error() == Some(libc::EROFS) => return,
+        Err(e) => panic!("{}", e),
+    }
+    let current = sysinfo::rp_filter("lo").unwrap();
+    sysinfo::set_rp_filter("lo", previous).unwrap();
+    assert_eq!(current, filter);
+}
+
+#[test]
+fn test_set_ip_forwarding() {
+    let previous = sysinfo::ip_forwarding(Family::Ipv4, Some("lo")).unwrap();
+    match sysinfo::set_ip_forwarding(Family::Ipv4, Some("lo"), !previous) {
+        Ok(()) => {},
+        Err(e) if e.kind() == ErrorKind::PermissionDenied || e.raw_os_error() == Some(libc::EROFS) => return,
+        Err(e) => panic!("{}", e),
+    }
+    let current = sysinfo::ip_forwarding(Family::Ipv4, Some("lo")).unwrap();
+    sysinfo::set_ip_forwarding(Family::Ipv4, Some("lo"), previous).unwrap();
+    assert_eq!(current, !previous);
+}
@@ -0,0 +1,40 @@
+use net_utils::syslog::{Facility, Severity, SyslogSender};
+use std::{
+    net::{IpAddr, Ipv4Addr, UdpSocket},
+    time::Duration,
+};
+
+#[test]
+fn test_send_from_source() {
+    let collector = UdpSocket::bind("127.0.0.1:0").unwrap();
+    collector.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+    let source = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    let mut sender = SyslogSender::new(collector.local_addr().unwrap(), Some(source), None).unwrap();
+    sender.set_app_name("net-utils");
+    sender.send(Facility::Daemon, Severity::Error, None, "link down").unwrap();
+
+    let mut buffer = [0_u8; 2048];
+    let (len, from) = collector.recv_from(&mut buffer).unwrap();
+    assert_eq!(from, sender.local_addr().unwrap());
+    let message = std::str::from_utf8(&buffer[..len]).unwrap();
+    assert!(message.starts_with("<27>1 "));
+    assert!(message.ends_with(&format!(" 127.0.0.1 net-utils {} - - link down", std::process::id())));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_send_bound_to_interface() {
+    let collector = UdpSocket::bind("127.0.0.1:0").unwrap();
+    collector.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+    let sender = match SyslogSender::new(collector.local_addr().unwrap(), None, Some("lo")) {
+        Ok(sender) => sender,
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => return,
+        Err(e) => panic!("{}", e),
+    };
+    sender.send(Facility::Local0, Severity::Informational, Some("TEST"), "bound").unwrap();
+    let mut buffer = [0_u8; 2048];
+    let len = collector.recv(&mut buffer).unwrap();
+    assert!(std::str::from_utf8(&buffer[..len]).unwrap().ends_with(" TEST - bound"));
+
+    assert!(SyslogSender::new(collector.local_addr().unwrap(), Some("::1".parse().unwrap()), None).is_err());
+}
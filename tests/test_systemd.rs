@@ -0,0 +1,76 @@
+#![cfg(all(target_os = "linux", feature = "systemd"))]
+
+use net_utils::{MulticastSocketBuilder, systemd::ActivatedSockets, tcp::TcpListenerBuilder};
+use std::{
+    io::ErrorKind,
+    net::{Ipv4Addr, SocketAddr, TcpListener, UdpSocket},
+    os::unix::io::OwnedFd,
+};
+
+#[test]
+fn test_adopt_udp_and_tcp() {
+    let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let udp_address = udp.local_addr().unwrap();
+    let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+    let tcp_address = tcp.local_addr().unwrap();
+    let mut sockets = ActivatedSockets::from_fds(vec![
+        (OwnedFd::from(tcp), Some("control".to_string())),
+        (OwnedFd::from(udp), None),
+    ]);
+    assert_eq!((sockets.len(), sockets.names()), (2, vec!["control"]));
+
+    // the unnamed socket is found by kind and address
+    let adopted = sockets.udp(None, udp_address).unwrap();
+    assert_eq!(adopted.local_addr().unwrap(), udp_address);
+    let listener = sockets.tcp_listener(Some("control"), &TcpListenerBuilder::new(tcp_address)).unwrap();
+    assert_eq!(listener.local_addr().unwrap(), tcp_address);
+    assert!(sockets.is_empty());
+
+    // without passed socket it is created
+    let created = sockets.udp(Some("control"), "127.0.0.1:0".parse().unwrap()).unwrap();
+    assert_ne!(created.local_addr().unwrap(), udp_address);
+}
+
+#[test]
+fn test_mismatch() {
+    let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let udp_address = udp.local_addr().unwrap();
+    let mut sockets = ActivatedSockets::from_fds(vec![(OwnedFd::from(udp), Some("dns".to_string()))]);
+    let error = sockets.tcp_listener(Some("dns"), &TcpListenerBuilder::new(udp_address)).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    assert!(error.to_string().contains("datagram socket on 127.0.0.1"), "{}", error);
+    let other: SocketAddr = "127.0.0.2:53".parse().unwrap();
+    assert_eq!(sockets.udp(Some("dns"), other).unwrap_err().kind(), ErrorKind::InvalidInput);
+    // a listener on another address is created instead of the unnamed socket
+    let listener = sockets.tcp_listener(None, &TcpListenerBuilder::new("127.0.0.1:0".parse().unwrap())).unwrap();
+    assert_ne!(listener.local_addr().unwrap(), udp_address);
+    assert_eq!(sockets.len(), 1);
+}
+
+#[test]
+fn test_adopt_multicast() {
+    let group: SocketAddr = "239.255.77.15:1921".parse().unwrap();
+    let passed = UdpSocket::bind(group).unwrap();
+    let mut sockets = ActivatedSockets::from_fds(vec![(OwnedFd::from(passed), Some("events".to_string()))]);
+    let builder = MulticastSocketBuilder::new(group, Ipv4Addr::LOCALHOST.into()).loopback(true);
+    let socket = sockets.multicast(Some("events"), &builder).unwrap();
+    assert!(sockets.is_empty());
+
+    // the adopted socket joined the group on the interface of the builder
+    let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket2::SockRef::from(&sender).set_multicast_if_v4(&Ipv4Addr::LOCALHOST).unwrap();
+    sender.send_to(b"event", group).unwrap();
+    socket.set_read_timeout(Some(std::time::Duration::from_secs(2))).unwrap();
+    let mut buf = [0_u8; 16];
+    let (len, source) = socket.recv_from(&mut buf).unwrap();
+    assert_eq!((&buf[..len], source), (&b"event"[..], sender.local_addr().unwrap()));
+}
+
+#[test]
+fn test_from_env_other_process() {
+    std::env::set_var("LISTEN_PID", "1");
+    std::env::set_var("LISTEN_FDS", "1");
+    let sockets = ActivatedSockets::from_env().unwrap();
+    assert!(sockets.is_empty());
+    assert!(std::env::var("LISTEN_FDS").is_err());
+}
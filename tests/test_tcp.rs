@@ -0,0 +1,159 @@
+use net_utils::tcp::TcpListenerBuilder;
+
+#[test]
+fn test_tcp_listener_builder() {
+    let listener = TcpListenerBuilder::new("127.0.0.1:0".parse().unwrap())
+        .backlog(16)
+        .build_std()
+        .unwrap();
+    let address = listener.local_addr().unwrap();
+    let client = std::net::TcpStream::connect(address);
+    assert!(client.is_ok());
+    assert!(listener.accept().is_ok());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_tcp_listener_reuse_port() {
+    let first = TcpListenerBuilder::new("127.0.0.1:0".parse().unwrap())
+        .reuse_port(true)
+        .build_std()
+        .unwrap();
+    let address = first.local_addr().unwrap();
+    let second = TcpListenerBuilder::new(address).reuse_port(true).build_std();
+    assert!(second.is_ok());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_tcp_listener_linux_options() {
+    let listener = TcpListenerBuilder::new("[::1]:0".parse().unwrap())
+        .only_v6(true)
+        .defer_accept(5)
+        .fast_open(16)
+        .build_std();
+    assert!(listener.is_ok());
+}
+
+#[cfg(all(target_os = "linux", feature = "test-util"))]
+#[test]
+fn test_tcp_listener_freebind() {
+    use net_utils::{MockSysApi, with_sys_api};
+    use std::{io::ErrorKind, os::unix::io::AsRawFd, sync::Arc};
+
+    let mock = Arc::new(MockSysApi::new());
+    with_sys_api(mock.clone(), || {
+        for (address, level, name) in [
+            ("192.0.2.213:0", libc::IPPROTO_IP, libc::IP_FREEBIND),
+            ("[2001:db8:213::1]:0", libc::IPPROTO_IPV6, libc::IPV6_FREEBIND),
+        ].iter() {
+            mock.fail("bind", libc::EADDRNOTAVAIL);
+            let err = TcpListenerBuilder::new(address.parse().unwrap()).build_std().unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::AddrNotAvailable);
+            mock.succeed("bind");
+            let listener = TcpListenerBuilder::new(address.parse().unwrap()).freebind(true).build_std().unwrap();
+            assert_eq!(mock.int_option(listener.as_raw_fd(), *level, *name), Some(1));
+        }
+        mock.fail("setsockopt", libc::EPERM);
+        let err = TcpListenerBuilder::new("127.0.0.1:0".parse().unwrap()).transparent(true).build_std().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+    });
+}
+
+#[test]
+fn test_set_keepalive() {
+    use net_utils::tcp::{KeepaliveConfig, clear_keepalive, set_keepalive};
+    use std::time::Duration;
+
+    let listener = TcpListenerBuilder::new("127.0.0.1:0".parse().unwrap()).build_std().unwrap();
+    let client = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let config = KeepaliveConfig {
+        idle: Some(Duration::from_secs(30)),
+        interval: Some(Duration::from_secs(5)),
+        ..KeepaliveConfig::default()
+    };
+    set_keepalive(&client, &config).unwrap();
+    let socket = socket2::SockRef::from(&client);
+    assert!(socket.keepalive().unwrap());
+    #[cfg(target_os = "linux")]
+    {
+        assert_eq!(socket.keepalive_time().unwrap(), Duration::from_secs(30));
+        assert_eq!(socket.keepalive_interval().unwrap(), Duration::from_secs(5));
+    }
+    clear_keepalive(&client).unwrap();
+    assert!(!socket.keepalive().unwrap());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_set_keepalive_linux_options() {
+    use net_utils::tcp::{KeepaliveConfig, set_keepalive};
+    use std::time::Duration;
+
+    let listener = TcpListenerBuilder::new("127.0.0.1:0".parse().unwrap()).build_std().unwrap();
+    let client = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let config = KeepaliveConfig {
+        count: Some(4),
+        user_timeout: Some(Duration::from_secs(20)),
+        ..KeepaliveConfig::default()
+    };
+    set_keepalive(&client, &config).unwrap();
+    let socket = socket2::SockRef::from(&client);
+    assert_eq!(socket.keepalive_retries().unwrap(), 4);
+    assert_eq!(socket.tcp_user_timeout().unwrap(), Some(Duration::from_secs(20)));
+}
+
+#[test]
+fn test_connect_via() {
+    use net_utils::tcp::{ConnectOpts, connect_via};
+    use std::time::Duration;
+
+    let listener = TcpListenerBuilder::new("127.0.0.1:0".parse().unwrap()).build_std().unwrap();
+    let opts = ConnectOpts {
+        timeout: Some(Duration::from_secs(5)),
+        source_addr: Some("127.0.0.1:0".parse().unwrap()),
+        ..ConnectOpts::default()
+    };
+    let stream = connect_via(listener.local_addr().unwrap(), &opts).unwrap();
+    let (_, peer) = listener.accept().unwrap();
+    assert_eq!(stream.local_addr().unwrap(), peer);
+
+    let opts = ConnectOpts { source_addr: Some("[::1]:0".parse().unwrap()), ..ConnectOpts::default() };
+    let err = connect_via(listener.local_addr().unwrap(), &opts).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_connect_via_device() {
+    use net_utils::tcp::{ConnectOpts, connect_via};
+
+    let listener = TcpListenerBuilder::new("127.0.0.1:0".parse().unwrap()).build_std().unwrap();
+    let opts = ConnectOpts { device: Some(String::from("lo")), ..ConnectOpts::default() };
+    assert!(connect_via(listener.local_addr().unwrap(), &opts).is_ok());
+}
+
+#[cfg(feature = "tokio-net")]
+#[tokio::test]
+async fn test_connect_happy_eyeballs() {
+    let listener = TcpListenerBuilder::new("127.0.0.1:0".parse().unwrap()).build_std().unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let stream = net_utils::tcp::connect_happy_eyeballs("localhost", port).await.unwrap();
+    assert_eq!(stream.peer_addr().unwrap(), listener.local_addr().unwrap());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_mptcp() {
+    use net_utils::tcp::{ConnectOpts, connect_via, is_mptcp};
+
+    let listener = TcpListenerBuilder::new("127.0.0.1:0".parse().unwrap()).mptcp(true).build_std().unwrap();
+    let opts = ConnectOpts { mptcp: true, ..ConnectOpts::default() };
+    let client = connect_via(listener.local_addr().unwrap(), &opts).unwrap();
+    let (server, _) = listener.accept().unwrap();
+    // both ends use MPTCP if the kernel supports it, otherwise both fell back to TCP
+    assert_eq!(is_mptcp(&client).unwrap(), is_mptcp(&server).unwrap());
+
+    let plain = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    assert!(!is_mptcp(&plain).unwrap());
+}
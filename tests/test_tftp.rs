@@ -0,0 +1,125 @@
+use net_utils::tftp;
+use std::{
+    io::ErrorKind,
+    net::{SocketAddr, UdpSocket},
+    thread::JoinHandle,
+    time::Duration,
+};
+
+/// Serves one request on a new transfer id like a TFTP server: reads return `content`, writes
+/// are returned by the thread. Options are acknowledged with the block size `block_size`.
+fn fake_server(content: Vec<u8>, block_size: Option<u16>) -> (SocketAddr, JoinHandle<Vec<u8>>) {
+    let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let thread = std::thread::spawn(move || {
+        let mut buffer = [0_u8; 65536];
+        listener.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let (len, client) = listener.recv_from(&mut buffer).unwrap();
+        let request = buffer[..len].to_vec();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut recv = || {
+            let len = socket.recv(&mut buffer).unwrap();
+            buffer[..len].to_vec()
+        };
+        let filename = request[2..].split(|b| *b == 0).next().unwrap();
+        if filename == b"missing" {
+            socket.send_to(b"\0\x05\0\x01File not found\0", client).unwrap();
+            return Vec::new();
+        }
+        let size = match block_size {
+            Some(size) => {
+                socket.send_to(format!("\0\x06blksize\0{}\0", size).as_bytes(), client).unwrap();
+                size as usize
+            },
+            None => 512,
+        };
+        if request[1] == 1 {
+            if block_size.is_some() {
+                assert_eq!(recv(), b"\0\x04\0\0");
+            }
+            for (i, chunk) in content.chunks(size).chain(std::iter::once(&[][..])).enumerate() {
+                let block = (i + 1) as u16;
+                let mut data = vec![0, 3];
+                data.extend_from_slice(&block.to_be_bytes());
+                data.extend_from_slice(chunk);
+                socket.send_to(&data, client).unwrap();
+                if i == 0 {
+                    // duplicate, which would be acked again
+                    socket.send_to(&data, client).unwrap();
+                    assert_eq!(recv()[2..], block.to_be_bytes());
+                }
+                assert_eq!(recv()[2..], block.to_be_bytes());
+                if chunk.len() < size {
+                    break;
+                }
+            }
+            Vec::new()
+        } else {
+            if block_size.is_none() {
+                socket.send_to(b"\0\x04\0\0", client).unwrap();
+            }
+            let mut written = Vec::new();
+            loop {
+                let data = recv();
+                assert_eq!(&data[..2], &[0, 3]);
+                written.extend_from_slice(&data[4..]);
+                socket.send_to(&[0, 4, data[2], data[3]], client).unwrap();
+                if data.len() - 4 < size {
+                    return written;
+                }
+            }
+        }
+    });
+    (address, thread)
+}
+
+#[test]
+fn test_get_with_block_size() {
+    let content: Vec<u8> = (0..5000_u32).map(|i| i as u8).collect();
+    let (server, thread) = fake_server(content.clone(), Some(1024));
+    let mut output = Vec::new();
+    let opts = tftp::TftpOpts { block_size: Some(1428), ..tftp::TftpOpts::default() };
+    assert_eq!(tftp::get(server, "firmware.bin", &mut output, &opts).unwrap(), 5000);
+    assert_eq!(output, content);
+    thread.join().unwrap();
+}
+
+#[test]
+fn test_get_without_negotiation() {
+    // a multiple of the block size ends with an empty block
+    let content = vec![0x55_u8; 1024];
+    let (server, thread) = fake_server(content.clone(), None);
+    let mut output = Vec::new();
+    let opts = tftp::TftpOpts { block_size: None, ..tftp::TftpOpts::default() };
+    assert_eq!(tftp::get(server, "config.txt", &mut output, &opts).unwrap(), 1024);
+    assert_eq!(output, content);
+    thread.join().unwrap();
+}
+
+#[test]
+fn test_put() {
+    let content: Vec<u8> = (0..3000_u32).map(|i| (i * 7) as u8).collect();
+    let (server, thread) = fake_server(Vec::new(), Some(512));
+    let sent = tftp::put(server, "upload.bin", &mut &content[..], &tftp::TftpOpts::default()).unwrap();
+    assert_eq!(sent, 3000);
+    assert_eq!(thread.join().unwrap(), content);
+
+    let (server, thread) = fake_server(Vec::new(), None);
+    let opts = tftp::TftpOpts { block_size: None, ..tftp::TftpOpts::default() };
+    assert_eq!(tftp::put(server, "empty.bin", &mut &[][..], &opts).unwrap(), 0);
+    assert!(thread.join().unwrap().is_empty());
+}
+
+#[test]
+fn test_errors() {
+    let (server, thread) = fake_server(Vec::new(), None);
+    let error = tftp::get(server, "missing", &mut Vec::new(), &tftp::TftpOpts::default()).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::NotFound);
+    thread.join().unwrap();
+
+    let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let opts = tftp::TftpOpts { timeout: Duration::from_millis(50), retries: 2, ..tftp::TftpOpts::default() };
+    let error = tftp::get(silent.local_addr().unwrap(), "x", &mut Vec::new(), &opts).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::TimedOut);
+}
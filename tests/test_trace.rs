@@ -0,0 +1,67 @@
+#![cfg(feature = "trace")]
+
+use net_utils::{create_std_multicast_socket_ipv4, trace::*};
+use std::{net::Ipv4Addr, sync::{Arc, Mutex}};
+
+#[test]
+fn test_trace_handler() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorded = events.clone();
+    set_trace_handler(move |event| recorded.lock().unwrap().push(event.to_string()));
+    let socket = create_std_multicast_socket_ipv4(&"239.255.77.3:1909".parse().unwrap(), &Ipv4Addr::UNSPECIFIED);
+    clear_trace_handler();
+    assert!(socket.is_ok());
+    let events = events.lock().unwrap();
+    assert!(events.contains(&"join 239.255.77.3 on interface 0.0.0.0".to_string()), "{:?}", events);
+    assert!(events.iter().any(|event| event.starts_with("created udp-multicast socket on ")), "{:?}", events);
+}
+
+/// Records the names of the spans entered and the messages of the events.
+struct Recorder(Arc<Mutex<Vec<String>>>);
+
+struct Message<'a>(&'a mut String);
+
+impl tracing::field::Visit for Message<'_> {
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            *self.0 = format!("{:?}", value);
+        }
+    }
+}
+
+impl tracing::Subscriber for Recorder {
+    fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+        self.0.lock().unwrap().push(format!("span {}", span.metadata().name()));
+        tracing::span::Id::from_u64(1)
+    }
+
+    fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+
+    fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+    fn event(&self, event: &tracing::Event<'_>) {
+        let mut message = String::new();
+        event.record(&mut Message(&mut message));
+        self.0.lock().unwrap().push(format!("{} {}", event.metadata().level(), message));
+    }
+
+    fn enter(&self, _: &tracing::span::Id) {}
+
+    fn exit(&self, _: &tracing::span::Id) {}
+}
+
+#[test]
+fn test_tracing_events() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let socket = tracing::subscriber::with_default(Recorder(events.clone()), || {
+        create_std_multicast_socket_ipv4(&"239.255.77.4:1910".parse().unwrap(), &Ipv4Addr::UNSPECIFIED)
+    });
+    assert!(socket.is_ok());
+    let events = events.lock().unwrap();
+    assert_eq!(events.first().map(String::as_str), Some("span multicast_socket"), "{:?}", events);
+    assert!(events.contains(&"DEBUG group joined".to_string()), "{:?}", events);
+}
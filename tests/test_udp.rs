@@ -0,0 +1,197 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::udp::*;
+use std::{net::{SocketAddr, UdpSocket}, thread, time::Duration};
+
+#[test]
+fn test_port_unreachable() {
+    let destination: SocketAddr = {
+        let closed = UdpSocket::bind("127.0.0.1:0").unwrap();
+        closed.local_addr().unwrap()
+    };
+    let socket = create_connected_udp(&destination, None).unwrap();
+    assert_eq!(socket.icmp_error().unwrap(), None);
+    socket.send(b"ping").unwrap();
+    thread::sleep(Duration::from_millis(100));
+    let error = socket.icmp_error().unwrap().expect("no ICMP error queued");
+    assert!(error.is_port_unreachable());
+    assert!(!error.ipv6);
+    assert_eq!(error.reporter, Some(destination.ip()));
+    assert_eq!(error.destination, Some(destination));
+    assert!(socket.icmp_errors().unwrap().is_empty());
+}
+
+#[test]
+fn test_recv_timeout() {
+    let peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let socket = create_connected_udp(&peer.local_addr().unwrap(), None).unwrap();
+    peer.connect(socket.socket().local_addr().unwrap()).unwrap();
+    let mut buf = [0_u8; 16];
+    assert_eq!(socket.recv_timeout(&mut buf, Duration::from_millis(20)).unwrap(), None);
+    peer.send(b"pong").unwrap();
+    assert_eq!(socket.recv_timeout(&mut buf, Duration::from_secs(1)).unwrap(), Some(4));
+    assert_eq!(&buf[..4], b"pong");
+    assert_eq!(socket.socket().read_timeout().unwrap(), None);
+    let stats = socket.stats();
+    assert_eq!((stats.datagrams_received, stats.bytes_received, stats.datagrams_sent), (1, 4, 0));
+    assert_eq!(stats.drops, Some(0));
+    assert!(stats.last_received.is_some());
+}
+
+#[test]
+fn test_receive_queue_drops() {
+    let peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let socket = create_connected_udp(&peer.local_addr().unwrap(), None).unwrap();
+    socket2::SockRef::from(socket.socket()).set_recv_buffer_size(4096).unwrap();
+    peer.connect(socket.socket().local_addr().unwrap()).unwrap();
+    for _ in 0..50 {
+        peer.send(&[0_u8; 1000]).unwrap();
+    }
+    socket.send(b"request").unwrap();
+    let mut buf = [0_u8; 2000];
+    while socket.recv_timeout(&mut buf, Duration::from_millis(20)).unwrap().is_some() {}
+    // the drop counter is reported with datagrams queued after the drops
+    peer.send(b"last").unwrap();
+    socket.recv(&mut buf).unwrap();
+    let stats = socket.stats();
+    assert!(stats.drops.unwrap() > 0);
+    assert_eq!((stats.datagrams_sent, stats.bytes_sent), (1, 7));
+}
+
+#[test]
+fn test_recv_from_with_drops() {
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    set_drop_reporting(&receiver, true).unwrap();
+    socket2::SockRef::from(&receiver).set_recv_buffer_size(4096).unwrap();
+    let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+    sender.connect(receiver.local_addr().unwrap()).unwrap();
+    sender.send(b"first").unwrap();
+    let mut buf = [0_u8; 2000];
+    assert_eq!(recv_from_with_drops(&receiver, &mut buf).unwrap(), (5, sender.local_addr().unwrap(), 0));
+
+    for _ in 0..50 {
+        sender.send(&[0_u8; 1000]).unwrap();
+    }
+    receiver.set_nonblocking(true).unwrap();
+    while receiver.recv(&mut buf).is_ok() {}
+    receiver.set_nonblocking(false).unwrap();
+    sender.send(b"last").unwrap();
+    let (len, _, drops) = recv_from_with_drops(&receiver, &mut buf).unwrap();
+    assert_eq!(len, 4);
+    assert!(drops > 0);
+}
+
+#[test]
+fn test_shutdown() {
+    let peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let socket = create_connected_udp(&peer.local_addr().unwrap(), None).unwrap();
+    peer.connect(socket.socket().local_addr().unwrap()).unwrap();
+    let handle = socket.shutdown_handle().unwrap();
+    peer.send(b"queued").unwrap();
+    let receiver = thread::spawn(move || {
+        let mut buf = [0_u8; 16];
+        let mut received = Vec::new();
+        loop {
+            match socket.recv(&mut buf) {
+                Ok(len) => received.push(buf[..len].to_vec()),
+                Err(e) => return (received, net_utils::shutdown::is_shutdown_error(&e)),
+            }
+        }
+    });
+    thread::sleep(Duration::from_millis(50));
+    peer.send(b"drained").unwrap();
+    handle.drain();
+    let (received, shut_down) = receiver.join().unwrap();
+    assert_eq!(received, vec![b"queued".to_vec(), b"drained".to_vec()]);
+    assert!(shut_down);
+}
+
+#[test]
+fn test_send_to_with_ttl() {
+    use net_utils::{MulticastSocketBuilder, pktinfo::{recv_with_pktinfo, set_recv_ttl}};
+
+    let group: SocketAddr = "239.255.77.11:1918".parse().unwrap();
+    let socket = MulticastSocketBuilder::new(group, "127.0.0.1".parse().unwrap())
+        .ttl(32)
+        .loopback(true)
+        .receive_ttl(true)
+        .build_std()
+        .unwrap();
+    let mut buf = [0_u8; 16];
+    assert_eq!(send_to_with_ttl(&socket, b"link", group, 1).unwrap(), 4);
+    socket.send_to(b"site", group).unwrap();
+    let ttls: Vec<_> = (0..2).map(|_| recv_with_pktinfo(&socket, &mut buf).unwrap().2.ttl).collect();
+    assert_eq!(ttls, vec![Some(1), Some(32)]);
+
+    let receiver = UdpSocket::bind("[::1]:0").unwrap();
+    set_recv_ttl(&receiver, true).unwrap();
+    let sender = UdpSocket::bind("[::1]:0").unwrap();
+    send_to_with_ttl(&sender, b"hops", receiver.local_addr().unwrap(), 3).unwrap();
+    assert_eq!(recv_with_pktinfo(&receiver, &mut buf).unwrap().2.ttl, Some(3));
+}
+
+#[test]
+fn test_ecn() {
+    use net_utils::{MulticastSocketBuilder, pktinfo::{Ecn, recv_with_pktinfo, set_recv_tos}};
+
+    let peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+    set_recv_tos(&peer, true).unwrap();
+    let socket = create_connected_udp(&peer.local_addr().unwrap(), None).unwrap();
+    peer.connect(socket.socket().local_addr().unwrap()).unwrap();
+    socket.set_ecn(Ecn::Ect1).unwrap();
+    socket.send(b"l4s").unwrap();
+    let mut buf = [0_u8; 16];
+    assert_eq!(recv_with_pktinfo(&peer, &mut buf).unwrap().2.ecn(), Some(Ecn::Ect1));
+    send_to_with_ecn(&peer, b"ce", socket.socket().local_addr().unwrap(), Ecn::Ce).unwrap();
+    assert_eq!(socket.recv_with_ecn(&mut buf).unwrap(), (2, Some(Ecn::Ce)));
+    peer.send(b"plain").unwrap();
+    assert_eq!(socket.recv_with_ecn(&mut buf).unwrap(), (5, Some(Ecn::NotEct)));
+
+    // the DSCP bits of the socket are kept
+    socket2::SockRef::from(socket.socket()).set_tos(0xb8).unwrap();
+    socket.set_ecn(Ecn::Ect0).unwrap();
+    socket.send(b"ef").unwrap();
+    assert_eq!(recv_with_pktinfo(&peer, &mut buf).unwrap().2.tos, Some(0xba));
+
+    let group: SocketAddr = "239.255.77.14:1920".parse().unwrap();
+    let socket = MulticastSocketBuilder::new(group, "127.0.0.1".parse().unwrap())
+        .loopback(true)
+        .receive_tos(true)
+        .ecn(Ecn::Ect0)
+        .build_std()
+        .unwrap();
+    socket.send_to(b"marked", group).unwrap();
+    assert_eq!(recv_with_pktinfo(&socket, &mut buf).unwrap().2.ecn(), Some(Ecn::Ect0));
+
+    let receiver = UdpSocket::bind("[::1]:0").unwrap();
+    set_recv_tos(&receiver, true).unwrap();
+    let sender = UdpSocket::bind("[::1]:0").unwrap();
+    set_ecn(&sender, Ecn::Ect1).unwrap();
+    sender.send_to(b"class", receiver.local_addr().unwrap()).unwrap();
+    assert_eq!(recv_with_pktinfo(&receiver, &mut buf).unwrap().2.ecn(), Some(Ecn::Ect1));
+    send_to_with_ecn(&sender, b"ce", receiver.local_addr().unwrap(), Ecn::Ce).unwrap();
+    assert_eq!(recv_with_pktinfo(&receiver, &mut buf).unwrap().2.ecn(), Some(Ecn::Ce));
+}
+
+#[cfg(feature = "tokio-net")]
+#[tokio::test]
+async fn test_send_to_with_ttl_async() {
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    net_utils::pktinfo::set_recv_ttl(&receiver, true).unwrap();
+    let sender = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    send_to_with_ttl_async(&sender, b"async", receiver.local_addr().unwrap(), 9).await.unwrap();
+    let mut buf = [0_u8; 16];
+    assert_eq!(net_utils::pktinfo::recv_with_pktinfo(&receiver, &mut buf).unwrap().2.ttl, Some(9));
+}
+
+#[cfg(feature = "tokio-net")]
+#[tokio::test]
+async fn test_send_to_with_ecn_async() {
+    use net_utils::pktinfo::{Ecn, recv_with_pktinfo, set_recv_tos};
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    set_recv_tos(&receiver, true).unwrap();
+    let sender = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    send_to_with_ecn_async(&sender, b"async", receiver.local_addr().unwrap(), Ecn::Ect0).await.unwrap();
+    let mut buf = [0_u8; 16];
+    assert_eq!(recv_with_pktinfo(&receiver, &mut buf).unwrap().2.ecn(), Some(Ecn::Ect0));
+}
@@ -0,0 +1,25 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::udplite;
+
+#[test]
+fn test_udplite_checksum_coverage() {
+    let receiver = udplite::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    udplite::set_recv_checksum_coverage(&receiver, 20).unwrap();
+    assert_eq!(udplite::recv_checksum_coverage(&receiver).unwrap(), 20);
+
+    let sender = udplite::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    udplite::set_send_checksum_coverage(&sender, udplite::UDPLITE_HEADER_LEN + 12).unwrap();
+    assert_eq!(udplite::send_checksum_coverage(&sender).unwrap(), 20);
+    sender.send_to(b"partially covered payload", receiver.local_addr().unwrap()).unwrap();
+    let mut buffer = [0_u8; 64];
+    let (len, from) = receiver.recv_from(&mut buffer).unwrap();
+    assert_eq!(&buffer[..len], b"partially covered payload");
+    assert_eq!(from, sender.local_addr().unwrap());
+}
+
+#[test]
+fn test_udplite_multicast() {
+    let socket = udplite::create_multicast_socket_v4(&"239.255.42.1:5400".parse().unwrap(), &"0.0.0.0".parse().unwrap());
+    assert!(socket.is_ok());
+}
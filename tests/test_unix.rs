@@ -0,0 +1,52 @@
+#![cfg(unix)]
+
+use net_utils::unix::{self, UnixSocketName};
+
+fn temp_socket_path(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("net-utils-{}-{}.sock", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn test_datagram_path() {
+    let path = temp_socket_path("dgram");
+    let name = UnixSocketName::from(path.clone());
+    let receiver = unix::bind_datagram(&name).unwrap();
+    assert_eq!(unix::local_name(&receiver).unwrap(), name);
+    let sender = unix::connect_datagram(&name).unwrap();
+    sender.send(b"hello").unwrap();
+    let mut buffer = [0_u8; 16];
+    assert_eq!(receiver.recv(&mut buffer).unwrap(), 5);
+    assert_eq!(unix::local_name(&sender).unwrap(), UnixSocketName::Unnamed);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_stream_peer_credentials() {
+    let path = temp_socket_path("stream");
+    let name = UnixSocketName::from(path.clone());
+    let listener = unix::bind_listener(&name).unwrap();
+    let client = unix::connect_stream(&name).unwrap();
+    let (server, _) = listener.accept().unwrap();
+    let credentials = unix::peer_credentials(&server).unwrap();
+    assert_eq!(credentials.uid, unsafe { libc::geteuid() });
+    assert_eq!(credentials.gid, unsafe { libc::getegid() });
+    #[cfg(target_os = "linux")]
+    assert_eq!(credentials.pid, Some(std::process::id()));
+    assert!(unix::peer_credentials(&client).is_ok());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_abstract_namespace() {
+    let name = UnixSocketName::abstract_name(format!("net-utils-{}", std::process::id()).as_bytes());
+    let receiver = unix::bind_datagram(&name).unwrap();
+    unix::set_pass_credentials(&receiver, true).unwrap();
+    assert_eq!(unix::local_name(&receiver).unwrap(), name);
+    let sender = unix::connect_datagram(&name).unwrap();
+    sender.send(b"abstract").unwrap();
+    let mut buffer = [0_u8; 16];
+    assert_eq!(receiver.recv(&mut buffer).unwrap(), 8);
+}
@@ -0,0 +1,51 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::{netlink, vrrp};
+use std::{
+    io::ErrorKind,
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+#[test]
+fn test_master_change() {
+    // a global IPv4 address of a multicast capable interface, multicast advertisements loop back
+    let links = netlink::links().unwrap();
+    let interface = netlink::addresses().unwrap().into_iter().find(|a| {
+        a.address.is_ipv4() && !a.address.is_loopback()
+            && links.iter().any(|l| l.index == a.index && l.flags & libc::IFF_MULTICAST as u32 != 0)
+    });
+    let (index, source) = match interface {
+        Some(interface) => (interface.index, interface.address),
+        None => return,
+    };
+    let mut listener = match vrrp::VrrpListener::ipv4(Some(index)) {
+        Ok(listener) => listener,
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => return,
+        Err(e) => panic!("{}", e),
+    };
+
+    let sender = socket2::Socket::new(socket2::Domain::IPV4, socket2::Type::RAW,
+                                      Some(socket2::Protocol::from(vrrp::IPPROTO_VRRP))).unwrap();
+    sender.bind(&SocketAddr::new(source, 0).into()).unwrap();
+    sender.set_multicast_ttl_v4(255).unwrap();
+    sender.set_multicast_loop_v4(true).unwrap();
+    let destination = IpAddr::V4(vrrp::VRRP_MULTICAST_V4);
+    let advertisement = vrrp::VrrpAdvertisement {
+        version: 3,
+        vrid: 201,
+        priority: 120,
+        interval: Duration::from_millis(100),
+        addresses: vec!["192.0.2.254".parse().unwrap()],
+    };
+    let data = advertisement.encode(&source, &destination).unwrap();
+    sender.send_to(&data, &SocketAddr::new(destination, 0).into()).unwrap();
+
+    listener.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+    let change = listener.next_change().unwrap();
+    assert_eq!((change.interface_index, change.vrid, change.ipv6), (index, 201, false));
+    assert_eq!((change.previous, change.master), (None, Some(source)));
+    // without further advertisements the master is down after about 350ms
+    let change = listener.next_change().unwrap();
+    assert_eq!((change.previous, change.master), (Some(source), None));
+}
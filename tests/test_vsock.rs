@@ -0,0 +1,15 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::vsock::{VMADDR_CID_ANY, VMADDR_PORT_ANY, VsockAddr, VsockListener};
+
+#[test]
+fn test_vsock_listener() {
+    let listener = match VsockListener::bind(&VsockAddr::new(VMADDR_CID_ANY, VMADDR_PORT_ANY)) {
+        Ok(listener) => listener,
+        // no vsock transport available (e.g. container without vsock modules)
+        Err(e) if e.raw_os_error() == Some(libc::EAFNOSUPPORT) || e.raw_os_error() == Some(libc::EADDRNOTAVAIL) => return,
+        Err(e) => panic!("{}", e),
+    };
+    let address = listener.local_addr().unwrap();
+    assert_ne!(address.port, VMADDR_PORT_ANY);
+}
@@ -0,0 +1,37 @@
+#![cfg(target_os = "linux")]
+
+use net_utils::{link_config, wireguard};
+use std::{io::ErrorKind, time::Duration};
+
+#[test]
+fn test_configure_device() {
+    match wireguard::create_device("nutest-wg0") {
+        Ok(_) => {},
+        // no permission or no WireGuard support in the kernel
+        Err(e) if e.kind() == ErrorKind::PermissionDenied || e.raw_os_error() == Some(libc::EOPNOTSUPP) => return,
+        Err(e) => panic!("{}", e),
+    }
+    let private_key = wireguard::generate_private_key().unwrap();
+    let peer_key = wireguard::parse_key("xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=").unwrap();
+    let config = wireguard::DeviceConfig {
+        private_key: Some(private_key),
+        listen_port: Some(0),
+        peers: vec![wireguard::PeerConfig {
+            public_key: peer_key,
+            endpoint: Some("192.0.2.1:51820".parse().unwrap()),
+            persistent_keepalive: Some(Duration::from_secs(25)),
+            allowed_ips: vec![wireguard::AllowedIp { address: "10.99.0.0".parse().unwrap(), prefix_len: 24 }],
+            ..wireguard::PeerConfig::default()
+        }],
+        ..wireguard::DeviceConfig::default()
+    };
+    let result = wireguard::configure("nutest-wg0", &config).and_then(|_| wireguard::device("nutest-wg0"));
+    link_config::delete_link("nutest-wg0").unwrap();
+    let device = result.unwrap();
+    assert_eq!(device.private_key, Some(private_key));
+    assert!(device.public_key.is_some());
+    assert_eq!(device.peers.len(), 1);
+    assert_eq!(device.peers[0].public_key, peer_key);
+    assert_eq!(device.peers[0].allowed_ips, config.peers[0].allowed_ips);
+    assert_eq!(device.peers[0].last_handshake, None);
+}